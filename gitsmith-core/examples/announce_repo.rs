@@ -0,0 +1,43 @@
+//! Announce a local git repository on Nostr
+//!
+//! ```sh
+//! NOSTR_PRIVATE_KEY=nsec1... cargo run -p gitsmith-core --example announce_repo -- \
+//!     path/to/repo wss://relay.damus.io
+//! ```
+
+use gitsmith_core::relay::Network;
+use gitsmith_core::{GitsmithError, PublishConfig, Result, announce_repository, detect_from_git};
+use nostr::Keys;
+use std::path::PathBuf;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let repo_path = PathBuf::from(args.next().unwrap_or_else(|| ".".to_string()));
+    let relays: Vec<String> = args.collect();
+
+    let private_key = std::env::var("NOSTR_PRIVATE_KEY")
+        .map_err(|_| GitsmithError::msg("Set NOSTR_PRIVATE_KEY to an nsec or hex key"))?;
+    let keys = Keys::parse(&private_key)?;
+
+    // Name, root commit and clone URLs come from the repository itself
+    let mut announcement = detect_from_git(&repo_path)?;
+    if !relays.is_empty() {
+        announcement.relays = relays;
+    }
+
+    let config = PublishConfig {
+        timeout_secs: 30,
+        wait_for_send: true,
+    };
+    let result = announce_repository(&Network::default(), announcement, &keys, config).await?;
+
+    println!("Announced as {url}", url = result.nostr_url);
+    for relay in &result.successes {
+        println!("  ✓ {relay}");
+    }
+    for (relay, error) in &result.failures {
+        println!("  ✗ {relay}: {error}");
+    }
+    Ok(())
+}
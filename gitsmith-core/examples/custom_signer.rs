@@ -0,0 +1,71 @@
+//! Plug a custom signer into gitsmith
+//!
+//! Anything implementing [`Signer`] can sign announcements, state and PR
+//! events: a hardware token, a signing service, or, as here, in-memory keys
+//! that log every event they sign.
+//!
+//! ```sh
+//! cargo run -p gitsmith-core --example custom_signer
+//! ```
+
+use futures::future::BoxFuture;
+use gitsmith_core::{RepoAnnouncement, Result, Signer, build_announcement_event};
+use nostr::{Event, JsonUtil, Keys, NostrSigner, PublicKey, UnsignedEvent};
+use std::sync::Arc;
+
+/// Signs with local keys, printing each event before signing it
+#[derive(Debug)]
+struct LoggingSigner {
+    keys: Keys,
+}
+
+impl Signer for LoggingSigner {
+    fn public_key(&self) -> PublicKey {
+        self.keys.public_key()
+    }
+
+    fn sign_event(&self, unsigned: UnsignedEvent) -> BoxFuture<'_, Result<Event>> {
+        Box::pin(async move {
+            eprintln!(
+                "Signing kind {kind} event with {count} tag(s)",
+                kind = unsigned.kind,
+                count = unsigned.tags.len()
+            );
+            Ok(unsigned.sign_with_keys(&self.keys)?)
+        })
+    }
+
+    fn nostr_signer(&self) -> Arc<dyn NostrSigner> {
+        Arc::new(self.keys.clone())
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let signer = LoggingSigner {
+        keys: Keys::generate(),
+    };
+
+    let announcement = RepoAnnouncement {
+        identifier: "example".to_string(),
+        name: "Example".to_string(),
+        description: "Announced with a custom signer".to_string(),
+        clone_urls: vec!["https://example.com/example.git".to_string()],
+        relays: vec!["wss://relay.damus.io".to_string()],
+        web: vec![],
+        root_commit: "0000000000000000000000000000000000000000".to_string(),
+        maintainers: vec![],
+        ci_keys: vec![],
+        grasp_servers: vec![],
+        blossom_servers: vec![],
+        min_pow: 0,
+        license: None,
+        topics: vec![],
+        readme: None,
+    };
+
+    // Pass the signer wherever gitsmith expects one, e.g. `announce_repository`
+    let event = build_announcement_event(&announcement, &signer).await?;
+    println!("{json}", json = event.as_json());
+    Ok(())
+}
@@ -0,0 +1,73 @@
+//! Send the commits on top of a base commit as a pull request
+//!
+//! ```sh
+//! NOSTR_PRIVATE_KEY=nsec1... cargo run -p gitsmith-core --example send_pull_request -- \
+//!     path/to/repo origin/main "Add frobnicator"
+//! ```
+//!
+//! The repository must have been announced (see the `announce_repo` example),
+//! its relays are read from the git config.
+
+use gitsmith_core::patches::{self, PullRequestOptions};
+use gitsmith_core::{GitsmithError, Result, detect_from_git, relay};
+use nostr::Keys;
+use std::path::PathBuf;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [repo_path, base, title] = args.as_slice() else {
+        return Err(GitsmithError::msg(
+            "Usage: send_pull_request <repo path> <base commit> <title>",
+        ));
+    };
+    let repo_path = PathBuf::from(repo_path);
+
+    let private_key = std::env::var("NOSTR_PRIVATE_KEY")
+        .map_err(|_| GitsmithError::msg("Set NOSTR_PRIVATE_KEY to an nsec or hex key"))?;
+    let keys = Keys::parse(&private_key)?;
+
+    let announcement = detect_from_git(&repo_path)?;
+    let repo_coordinate = format!(
+        "30617:{pubkey}:{identifier}",
+        pubkey = keys.public_key(),
+        identifier = announcement.identifier
+    );
+
+    // One patch per commit, in git format-patch format
+    let patches = patches::generate_patches(&repo_path, Some(base), None)?;
+    let options = PullRequestOptions {
+        commits: patches::commit_metadata(&repo_path, &patches)?,
+        ..Default::default()
+    };
+    let events = patches::create_pull_request_event(
+        &keys,
+        &repo_coordinate,
+        title,
+        "",
+        patches,
+        &announcement.root_commit,
+        &options,
+    )
+    .await?;
+
+    let client = relay::connect(
+        &relay::Network::default(),
+        &announcement.relays,
+        Some(&keys),
+    )
+    .await?;
+    let result = relay::publish_events(&client, &events).await?;
+    client.disconnect().await;
+
+    // The PR event comes last, after the patches it references
+    if let Some(pr) = result.event_ids.last() {
+        println!("Sent pull request {pr}");
+    }
+    println!(
+        "Accepted by {count} relay(s), rejected by {failed}",
+        count = result.successes.len(),
+        failed = result.failures.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! Print pull requests for repositories as they are published, until Ctrl-C
+//!
+//! ```sh
+//! cargo run -p gitsmith-core --example watch_pull_requests -- \
+//!     30617:<owner hex pubkey>:<identifier>,30617:<owner hex pubkey>:<other> \
+//!     wss://relay.damus.io
+//! ```
+
+use gitsmith_core::patches::{KIND_PULL_REQUEST, KIND_PULL_REQUEST_UPDATE};
+use gitsmith_core::pull_request::{build_pull_requests, format_pull_request};
+use gitsmith_core::subscriptions::RepoSubscriptions;
+use gitsmith_core::time::TimeFormat;
+use gitsmith_core::{GitsmithError, Result, relay};
+use nostr::{Alphabet, TagKind};
+use nostr_sdk::RelayPoolNotification;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let repo_coordinates = args.next().ok_or_else(|| {
+        GitsmithError::msg("Usage: watch_pull_requests <repo coordinate>[,...] <relay>...")
+    })?;
+    let relays: Vec<String> = args.collect();
+
+    let client = relay::connect(&relay::Network::default(), &relays, None).await?;
+
+    // Only new PRs, `pull_request::list_pull_requests` returns the existing ones.
+    // All repositories share as few subscriptions as the relays allow.
+    let mut subscriptions = RepoSubscriptions::new(
+        client.clone(),
+        [KIND_PULL_REQUEST, KIND_PULL_REQUEST_UPDATE],
+    );
+    for repo_coordinate in repo_coordinates.split(',') {
+        subscriptions.add_repo(repo_coordinate).await?;
+        println!("Watching {repo_coordinate}");
+    }
+    println!("Press Ctrl-C to stop");
+
+    let time = TimeFormat::default();
+    let mut notifications = client.notifications();
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            notification = notifications.recv() => {
+                let Ok(RelayPoolNotification::Event { subscription_id, event, .. }) = notification else {
+                    continue;
+                };
+                if !subscriptions.contains(&subscription_id) {
+                    continue;
+                }
+                // The repository owner decides which revisions count
+                let repo_owner = event
+                    .tags
+                    .find(TagKind::single_letter(Alphabet::A, false))
+                    .and_then(|tag| tag.content())
+                    .and_then(|coordinate| coordinate.split(':').nth(1))
+                    .map(str::to_string);
+                for pr in build_pull_requests(vec![*event], repo_owner.as_deref())? {
+                    println!("{pr}", pr = format_pull_request(&pr, &time));
+                }
+            }
+        }
+    }
+
+    subscriptions.clear().await;
+    client.disconnect().await;
+    Ok(())
+}
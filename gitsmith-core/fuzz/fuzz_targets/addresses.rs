@@ -0,0 +1,8 @@
+//! Repository URLs, addresses and coordinates given on the command line or found in events
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|address: &str| {
+    gitsmith_core_fuzz::parse_address(address);
+});
@@ -0,0 +1,13 @@
+//! Events as JSON, one per line, the way relays deliver them
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use nostr::{Event, JsonUtil};
+
+fuzz_target!(|data: &str| {
+    let events = data
+        .lines()
+        .filter_map(|line| Event::from_json(line).ok())
+        .collect();
+    gitsmith_core_fuzz::parse_events(events, true);
+});
@@ -0,0 +1,17 @@
+//! Events with arbitrary kinds, tags and content, as a hostile relay could send
+#![no_main]
+
+use gitsmith_core_fuzz::FuzzEvent;
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    events: Vec<FuzzEvent>,
+    owned: bool,
+}
+
+fuzz_target!(|input: Input| {
+    let events = input.events.iter().map(FuzzEvent::to_event).collect();
+    gitsmith_core_fuzz::parse_events(events, input.owned);
+});
@@ -0,0 +1,8 @@
+//! Patch contents of patch events
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|patch: &str| {
+    gitsmith_core_fuzz::parse_patch(patch);
+});
@@ -0,0 +1,106 @@
+//! Shared harness of the fuzz targets: everything gitsmith derives from
+//! untrusted input must return, never panic or hang
+
+use gitsmith_core::pull_request::{self, PullRequest};
+use gitsmith_core::{
+    apply, ci, comments, events, issues, orgs, patches, remote_helper, repo, secrets,
+};
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use nostr::secp256k1::schnorr::Signature;
+use nostr::{Event, EventId, Keys, Kind, PublicKey, Tag, Timestamp};
+use std::sync::LazyLock;
+
+/// Authors events are attributed to, so ownership checks get exercised
+static AUTHORS: LazyLock<[PublicKey; 2]> =
+    LazyLock::new(|| [Keys::generate().public_key(), Keys::generate().public_key()]);
+
+/// An event as a relay could deliver it, unsigned since parsing never checks signatures
+#[derive(Arbitrary, Debug)]
+pub struct FuzzEvent {
+    pub id: [u8; 32],
+    pub by_owner: bool,
+    pub created_at: u64,
+    pub kind: u16,
+    pub tags: Vec<Vec<String>>,
+    pub content: String,
+}
+
+impl FuzzEvent {
+    pub fn to_event(&self) -> Event {
+        let author = AUTHORS[usize::from(!self.by_owner)];
+        let tags = self
+            .tags
+            .iter()
+            .filter_map(|tag| Tag::parse(tag.iter().map(String::as_str)).ok());
+        Event::new(
+            EventId::from_byte_array(self.id),
+            author,
+            Timestamp::from(self.created_at),
+            Kind::from(self.kind),
+            tags,
+            &self.content,
+            Signature::from_slice(&[1; 64]).expect("64 bytes is a valid signature length"),
+        )
+    }
+}
+
+/// Run every parser and aggregation over the events
+pub fn parse_events(events: Vec<Event>, owned: bool) {
+    let owner = AUTHORS[0].to_hex();
+    let repo_owner = owned.then_some(owner.as_str());
+
+    for event in &events {
+        let _ = events::parse_announcement_event(event);
+        let _ = issues::parse_issue_event(event);
+        let _ = comments::parse_comment_event(event);
+        let _ = ci::parse_check_event(event);
+        let _ = orgs::parse_organization_event(event);
+        let _ = pull_request::is_root_patch(event);
+        let _ = pull_request::revision_of(event);
+        let _ = pull_request::patch_event_ids(event);
+        let _ = pull_request::cover_letter_id(event);
+        let _ = pull_request::manifest_id(event);
+        let _ = pull_request::manifest_entries(event);
+        let _ = pull_request::summarize_patch(event);
+    }
+
+    if let Some((root, replies)) = events.split_first() {
+        let _ = pull_request::order_thread(root, replies.to_vec());
+    }
+
+    let events = pull_request::filter_low_pow(events, 0);
+    let mut prs: Vec<PullRequest> =
+        pull_request::build_pull_requests(events.clone(), repo_owner).unwrap_or_default();
+    pull_request::apply_status_events(&mut prs, &events, repo_owner);
+    pull_request::apply_engagement(&mut prs, &events);
+    for pr in &prs {
+        let _ = pull_request::format_pull_request(pr);
+        let _ = pull_request::format_engagement(pr);
+        let _ = pr.latest_event_id();
+    }
+}
+
+/// Parse the content of a patch event
+pub fn parse_patch(patch: &str) {
+    if let Ok(parsed) = apply::parse_patch(patch) {
+        let _ = apply::diffstat(&parsed.diff);
+    }
+    let _ = apply::diffstat(patch);
+    let _ = secrets::scan_patch(patch);
+    let _ = patches::ManifestEntry::new(EventId::all_zeros(), patch, None);
+    let _ = patches::cover_letter_body(patch);
+}
+
+/// Parse a repository address, URL or coordinate
+pub fn parse_address(address: &str) {
+    let _ = repo::parse_nostr_url(address);
+    let _ = repo::parse_repository_address(address);
+    let _ = patches::parse_repo_coordinate(address);
+    let _ = orgs::parse_organization_address(address);
+    let _ = orgs::parse_repository_reference(address);
+    let _ = gitsmith_core::github::parse_github_url(address);
+    let _ = remote_helper::parse_push_spec(address);
+    let _ = remote_helper::parse_fetch_command(address);
+    let _ = remote_helper::parse_porcelain_push(address);
+    let _ = remote_helper::parse_ls_remote(address);
+}
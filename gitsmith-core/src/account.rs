@@ -1,18 +1,49 @@
-use anyhow::{Context, Result, ensure};
+use argon2::Argon2;
 use chacha20poly1305::{
     ChaCha20Poly1305, Nonce,
-    aead::{Aead, AeadCore, KeyInit, OsRng},
+    aead::{Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore},
 };
-use nostr::{FromBech32, Keys, ToBech32};
+use nostr::nips::nip49::{EncryptedSecretKey, KeySecurity};
+use nostr::{Filter, FromBech32, Keys, Kind, PublicKey, Timestamp, ToBech32};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, warn};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::error::{Context, GitsmithError, Result, bail, ensure};
+use crate::events::KIND_GIT_REPO_ANNOUNCEMENT;
+use crate::relay::{self, Network};
+use crate::signer::{BunkerSigner, Signer};
+
+/// Storage format of accounts written by this version
+///
+/// 1. Key derived with an unsalted SHA-256 of the password
+/// 2. Key derived with Argon2id and a per-account salt
+pub const ACCOUNT_VERSION: u32 = 2;
+
+/// scrypt cost of exported ncryptsec keys, the value NIP-49 recommends
+const NCRYPTSEC_LOG_N: u8 = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoredAccount {
+    /// Storage format, older accounts are migrated when unlocked
+    #[serde(default = "legacy_version")]
+    pub version: u32,
     pub npub: String,
+    /// The account's private key, or the app key authorized by the remote
+    /// signer for bunker accounts
     pub encrypted_nsec: Vec<u8>,
     pub nonce: Vec<u8>,
+    /// Argon2id salt of the encryption key
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub salt: Vec<u8>,
+    /// Remote signer (NIP-46) holding the account's private key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bunker_uri: Option<String>,
+    /// Unix time the account was last unlocked to sign
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -65,141 +96,794 @@ pub fn get_account_storage_path() -> Result<PathBuf> {
     Ok(home.join(".config").join("gitsmith").join("accounts.json"))
 }
 
-/// Derive encryption key from password
-fn derive_key(password: &str) -> [u8; 32] {
-    use sha2::{Digest, Sha256};
-    let mut hasher = Sha256::new();
-    hasher.update(password.as_bytes());
-    hasher.update(b"gitsmith-account-encryption");
-    let result = hasher.finalize();
+/// Where accounts and their encrypted keys are persisted
+pub trait StorageBackend {
+    fn load(&self) -> Result<AccountStorage>;
+    fn save(&self, storage: &AccountStorage) -> Result<()>;
+}
+
+/// Accounts kept in a JSON file, `accounts.json` by default
+pub struct FileBackend {
+    path: PathBuf,
+}
+
+impl FileBackend {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl StorageBackend for FileBackend {
+    fn load(&self) -> Result<AccountStorage> {
+        AccountStorage::load(&self.path)
+    }
+
+    fn save(&self, storage: &AccountStorage) -> Result<()> {
+        storage.save(&self.path)
+    }
+}
+
+/// Accounts kept in the OS keychain: Secret Service on Linux, Keychain on
+/// macOS and Credential Manager on Windows
+///
+/// Keys are still encrypted with the account password before being stored.
+#[cfg(feature = "keyring")]
+pub struct KeyringBackend {
+    entry: keyring::Entry,
+}
+
+#[cfg(feature = "keyring")]
+impl KeyringBackend {
+    const SERVICE: &str = "gitsmith";
+    const USER: &str = "accounts";
+
+    pub fn new() -> Result<Self> {
+        let entry = keyring::Entry::new(Self::SERVICE, Self::USER)
+            .map_err(|e| GitsmithError::msg(format!("Failed to open the OS keychain: {e}")))?;
+        Ok(Self { entry })
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl StorageBackend for KeyringBackend {
+    fn load(&self) -> Result<AccountStorage> {
+        match self.entry.get_password() {
+            Ok(data) => serde_json::from_str(&data).context("Failed to parse account storage"),
+            Err(keyring::Error::NoEntry) => Ok(AccountStorage::new()),
+            Err(e) => bail!("Failed to read accounts from the OS keychain: {e}"),
+        }
+    }
+
+    fn save(&self, storage: &AccountStorage) -> Result<()> {
+        let data = serde_json::to_string(storage)?;
+        self.entry.set_password(&data).map_err(|e| {
+            GitsmithError::msg(format!("Failed to write accounts to the OS keychain: {e}"))
+        })
+    }
+}
+
+/// Environment variable selecting the account to sign with
+pub const ACCOUNT_ENV: &str = "GITSMITH_ACCOUNT";
+
+/// Where accounts are stored and which one signs, chosen by the caller
+#[derive(Debug, Clone, Default)]
+pub struct AccountOptions {
+    /// Keep accounts in the OS keychain instead of `accounts.json`
+    pub keyring: bool,
+    /// Sign with this stored account (npub or hex) instead of the active one
+    pub account: Option<String>,
+}
+
+impl AccountOptions {
+    /// The storage backend, the OS keychain with [`AccountOptions::keyring`]
+    pub fn storage_backend(&self) -> Result<Box<dyn StorageBackend>> {
+        if self.keyring {
+            #[cfg(feature = "keyring")]
+            return Ok(Box::new(KeyringBackend::new()?));
+            #[cfg(not(feature = "keyring"))]
+            bail!(
+                "gitsmith was built without OS keychain support, rebuild with `--features keyring`"
+            );
+        }
+
+        Ok(Box::new(FileBackend::new(get_account_storage_path()?)))
+    }
+
+    /// The selected account, or else the active one
+    fn active_npub(&self, storage: &AccountStorage) -> Result<String> {
+        match &self.account {
+            Some(account) => Ok(find_account(storage, account)?.npub.clone()),
+            None => storage
+                .active_npub
+                .clone()
+                .ok_or(GitsmithError::NoActiveAccount),
+        }
+    }
+}
+
+/// Accounts saved before the storage format was versioned
+fn legacy_version() -> u32 {
+    1
+}
+
+/// Derive the encryption key of an account from the password
+fn derive_key(password: &str, account: &StoredAccount) -> Result<[u8; 32]> {
     let mut key = [0u8; 32];
-    key.copy_from_slice(&result);
-    key
+    if account.version < 2 {
+        use sha2::{Digest, Sha256};
+        let mut hasher = Sha256::new();
+        hasher.update(password.as_bytes());
+        hasher.update(b"gitsmith-account-encryption");
+        key.copy_from_slice(&hasher.finalize());
+        return Ok(key);
+    }
+
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), &account.salt, &mut key)
+        .map_err(|e| GitsmithError::msg(format!("Key derivation failed: {e}")))?;
+    Ok(key)
 }
 
 /// Login with a private key and password
-pub fn login(nsec_or_hex: &str, password: &str) -> Result<()> {
+pub fn login(options: &AccountOptions, nsec_or_hex: &str, password: &str) -> Result<()> {
     // Parse the key (works with both nsec bech32 and hex format)
     let keys = Keys::parse(nsec_or_hex)?;
 
     let npub = keys.public_key().to_bech32()?;
+    store_account(options, npub.clone(), &keys, None, password)?;
 
-    // Encrypt the private key
-    let key = derive_key(password);
-    let cipher = ChaCha20Poly1305::new_from_slice(&key)?;
-    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    eprintln!("Logged in as {npub}");
+    Ok(())
+}
 
-    let secret_key_bytes = keys.secret_key().to_secret_bytes();
-    let encrypted = cipher
-        .encrypt(&nonce, secret_key_bytes.as_ref())
-        .map_err(|e| anyhow::anyhow!("Encryption failed: {e}"))?;
+/// Login with a NIP-49 encrypted key
+///
+/// The same password decrypts the ncryptsec and encrypts the stored account.
+pub fn login_ncryptsec(options: &AccountOptions, ncryptsec: &str, password: &str) -> Result<()> {
+    let keys = decrypt_ncryptsec(ncryptsec, password)?;
+
+    let npub = keys.public_key().to_bech32()?;
+    store_account(options, npub.clone(), &keys, None, password)?;
+
+    eprintln!("Logged in as {npub}");
+    Ok(())
+}
 
+/// Login through a NIP-46 remote signer, given its `bunker://` URI
+///
+/// The private key stays with the remote signer, only the app key it
+/// authorizes is stored, encrypted with the password.
+pub async fn connect_bunker(
+    options: &AccountOptions,
+    bunker_uri: &str,
+    password: &str,
+) -> Result<()> {
+    let app_keys = Keys::generate();
+    let signer = BunkerSigner::connect(bunker_uri, app_keys.clone())
+        .await
+        .context("Failed to connect to the remote signer")?;
+
+    let npub = signer.public_key().to_bech32()?;
+    let bunker_uri = signer.bunker_uri().await?;
+    store_account(options, npub.clone(), &app_keys, Some(bunker_uri), password)?;
+
+    eprintln!("Logged in as {npub} (remote signer)");
+    Ok(())
+}
+
+/// Encrypt the keys and save the account as the active one
+fn store_account(
+    options: &AccountOptions,
+    npub: String,
+    keys: &Keys,
+    bunker_uri: Option<String>,
+    password: &str,
+) -> Result<()> {
     // Load existing storage
-    let storage_path = get_account_storage_path()?;
-    let mut storage = AccountStorage::load(&storage_path)?;
+    let backend = options.storage_backend()?;
+    let mut storage = backend.load()?;
 
     // Check if account already exists
-    if let Some(existing) = storage.accounts.iter_mut().find(|a| a.npub == npub) {
-        // Update existing account
-        existing.encrypted_nsec = encrypted;
-        existing.nonce = nonce.to_vec();
-    } else {
-        // Add new account
-        storage.accounts.push(StoredAccount {
-            npub: npub.clone(),
-            encrypted_nsec: encrypted,
-            nonce: nonce.to_vec(),
-        });
-    }
+    let index = match storage.accounts.iter().position(|a| a.npub == npub) {
+        Some(index) => index,
+        None => {
+            storage.accounts.push(StoredAccount {
+                version: ACCOUNT_VERSION,
+                npub: npub.clone(),
+                encrypted_nsec: Vec::new(),
+                nonce: Vec::new(),
+                salt: Vec::new(),
+                bunker_uri: None,
+                last_used: None,
+            });
+            storage.accounts.len() - 1
+        }
+    };
+    let account = &mut storage.accounts[index];
+    account.bunker_uri = bunker_uri;
+    encrypt_keys(account, keys, password)?;
 
     // Set as active account
-    storage.active_npub = Some(npub.clone());
+    storage.active_npub = Some(npub);
 
     // Save storage
-    storage.save(&storage_path)?;
-
-    eprintln!("Logged in as {npub}");
-    Ok(())
+    backend.save(&storage)
 }
 
 /// Logout (remove active account)
-pub fn logout() -> Result<()> {
-    let storage_path = get_account_storage_path()?;
-    let mut storage = AccountStorage::load(&storage_path)?;
+pub fn logout(options: &AccountOptions) -> Result<()> {
+    let backend = options.storage_backend()?;
+    let mut storage = backend.load()?;
 
     ensure!(storage.active_npub.is_some(), "No active account to logout");
 
     let npub = storage.active_npub.take().unwrap();
-    storage.save(&storage_path)?;
+    backend.save(&storage)?;
 
     eprintln!("Logged out from {npub}");
     Ok(())
 }
 
+/// Make a stored account the active one, given its npub or hex public key
+pub fn switch_account(options: &AccountOptions, public_key: &str) -> Result<()> {
+    let backend = options.storage_backend()?;
+    let mut storage = backend.load()?;
+
+    let npub = find_account(&storage, public_key)?.npub.clone();
+    storage.active_npub = Some(npub.clone());
+    backend.save(&storage)?;
+
+    eprintln!("Switched to {npub}");
+    Ok(())
+}
+
+/// Delete a stored account, given its npub or hex public key
+///
+/// Unless the key was exported or lives in a remote signer, this destroys it.
+pub fn remove_account(options: &AccountOptions, public_key: &str) -> Result<StoredAccount> {
+    let backend = options.storage_backend()?;
+    let mut storage = backend.load()?;
+
+    let npub = find_account(&storage, public_key)?.npub.clone();
+    let index = storage
+        .accounts
+        .iter()
+        .position(|a| a.npub == npub)
+        .context("Account not found")?;
+    let removed = storage.accounts.remove(index);
+    if storage.active_npub.as_ref() == Some(&npub) {
+        storage.active_npub = None;
+    }
+    backend.save(&storage)?;
+
+    Ok(removed)
+}
+
+/// Look up a stored account (no password required)
+pub fn get_account(options: &AccountOptions, public_key: &str) -> Result<StoredAccount> {
+    let storage = options.storage_backend()?.load()?;
+    find_account(&storage, public_key).cloned()
+}
+
+/// The active account (no password required)
+pub fn get_active_account(options: &AccountOptions) -> Result<StoredAccount> {
+    let storage = options.storage_backend()?.load()?;
+    let active_npub = options.active_npub(&storage)?;
+    find_account(&storage, &active_npub).cloned()
+}
+
+/// Find an account by npub or hex public key
+fn find_account<'a>(storage: &'a AccountStorage, public_key: &str) -> Result<&'a StoredAccount> {
+    let npub = PublicKey::parse(public_key.trim())
+        .with_context(|| format!("Invalid public key {public_key}"))?
+        .to_bech32()?;
+    storage
+        .accounts
+        .iter()
+        .find(|a| a.npub == npub)
+        .with_context(|| format!("No stored account for {npub}, see 'gitsmith account list'"))
+}
+
 /// Get the active account keys
-pub fn get_active_keys(password: &str) -> Result<Keys> {
-    let storage_path = get_account_storage_path()?;
-    let storage = AccountStorage::load(&storage_path)?;
+///
+/// Fails for accounts whose key is held by a remote signer, see
+/// [`get_active_signer`].
+pub fn get_active_keys(options: &AccountOptions, password: &str) -> Result<Keys> {
+    get_keys(options, None, password)
+}
+
+/// Get the signer of the active account, local keys or a remote signer
+pub fn get_active_signer(options: &AccountOptions, password: &str) -> Result<Box<dyn Signer>> {
+    get_signer(options, None, password)
+}
+
+/// Get the keys of a stored account (npub or hex), or of the active one
+pub fn get_keys(options: &AccountOptions, account: Option<&str>, password: &str) -> Result<Keys> {
+    let (account, keys) = unlock_account(options, account, password)?;
+    if account.bunker_uri.is_some() {
+        bail!(
+            "{npub} signs with a remote signer, its private key is not available",
+            npub = account.npub
+        );
+    }
+
+    Ok(keys)
+}
+
+/// Get the signer of a stored account (npub or hex), or of the active one
+pub fn get_signer(
+    options: &AccountOptions,
+    account: Option<&str>,
+    password: &str,
+) -> Result<Box<dyn Signer>> {
+    let (account, keys) = unlock_account(options, account, password)?;
+
+    match &account.bunker_uri {
+        Some(bunker_uri) => {
+            let public_key = nostr::PublicKey::from_bech32(&account.npub)?;
+            Ok(Box::new(BunkerSigner::reconnect(
+                bunker_uri, keys, public_key,
+            )?))
+        }
+        None => Ok(Box::new(keys)),
+    }
+}
 
-    let active_npub = storage
-        .active_npub
-        .context("No active account. Please login first")?;
+/// Decrypt an account's keys, migrating it to the current storage format
+fn unlock_account(
+    options: &AccountOptions,
+    account: Option<&str>,
+    password: &str,
+) -> Result<(StoredAccount, Keys)> {
+    let backend = options.storage_backend()?;
+    let mut storage = backend.load()?;
+
+    let account = account.or(options.account.as_deref());
+    let (account, keys, migrated) = unlock(&mut storage, account, password)?;
+    // Only the usage time and the migration are saved, the old entry still
+    // decrypts, so failing to save is not fatal
+    match backend.save(&storage) {
+        Ok(()) if migrated => {
+            debug!(npub = %account.npub, "Migrated account to version {ACCOUNT_VERSION}")
+        }
+        Ok(()) => {}
+        Err(e) => warn!("Failed to save account: {e}"),
+    }
+
+    Ok((account, keys))
+}
 
+/// Decrypt an account's keys (the active one when `account` is `None`) and
+/// record the use, re-encrypting them in place if the account uses an older
+/// storage format
+fn unlock(
+    storage: &mut AccountStorage,
+    account: Option<&str>,
+    password: &str,
+) -> Result<(StoredAccount, Keys, bool)> {
+    let npub = match account {
+        Some(account) => find_account(storage, account)?.npub.clone(),
+        None => storage
+            .active_npub
+            .clone()
+            .ok_or(GitsmithError::NoActiveAccount)?,
+    };
     let account = storage
         .accounts
-        .iter()
-        .find(|a| a.npub == active_npub)
-        .context("Active account not found in storage")?;
+        .iter_mut()
+        .find(|a| a.npub == npub)
+        .with_context(|| format!("Account {npub} not found in storage"))?;
+
+    let keys = decrypt_keys(account, password)?;
+    account.last_used = Some(Timestamp::now().as_u64());
+    let migrated = account.version < ACCOUNT_VERSION;
+    if migrated {
+        encrypt_keys(account, &keys, password)?;
+    }
+
+    Ok((account.clone(), keys, migrated))
+}
+
+/// Encrypt the keys into the account with a fresh salt and nonce
+fn encrypt_keys(account: &mut StoredAccount, keys: &Keys, password: &str) -> Result<()> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    account.version = ACCOUNT_VERSION;
+    account.salt = salt.to_vec();
+
+    let key = derive_key(password, account)?;
+    let cipher = ChaCha20Poly1305::new(&key.into());
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
 
+    let secret_key_bytes = keys.secret_key().to_secret_bytes();
+    account.encrypted_nsec = cipher
+        .encrypt(&nonce, secret_key_bytes.as_ref())
+        .map_err(|e| GitsmithError::msg(format!("Encryption failed: {e}")))?;
+    account.nonce = nonce.to_vec();
+
+    Ok(())
+}
+
+fn decrypt_keys(account: &StoredAccount, password: &str) -> Result<Keys> {
     // Decrypt the private key
-    let key = derive_key(password);
-    let cipher = ChaCha20Poly1305::new_from_slice(&key)?;
+    let key = derive_key(password, account)?;
+    let cipher = ChaCha20Poly1305::new(&key.into());
     let nonce = Nonce::from_slice(&account.nonce);
 
     let decrypted = cipher
         .decrypt(nonce, account.encrypted_nsec.as_ref())
-        .map_err(|_| anyhow::anyhow!("Failed to decrypt key. Wrong password?"))?;
+        .map_err(|_| GitsmithError::WrongPassword)?;
 
     // Parse the decrypted key
     let hex_key = hex::encode(decrypted);
-    Keys::parse(&hex_key).map_err(|e| anyhow::anyhow!("Failed to parse key: {e}"))
+    Keys::parse(&hex_key).context("Failed to parse key")
 }
 
 /// Export the active account
-pub fn export_keys(password: &str) -> Result<String> {
-    let keys = get_active_keys(password)?;
+pub fn export_keys(options: &AccountOptions, password: &str) -> Result<String> {
+    let keys = get_active_keys(options, password)?;
     Ok(keys.secret_key().to_bech32()?)
 }
 
+/// Export the active account as a NIP-49 ncryptsec, encrypted with its password
+pub fn export_ncryptsec(options: &AccountOptions, password: &str) -> Result<String> {
+    let keys = get_active_keys(options, password)?;
+    encrypt_ncryptsec(&keys, password, NCRYPTSEC_LOG_N)
+}
+
+fn encrypt_ncryptsec(keys: &Keys, password: &str, log_n: u8) -> Result<String> {
+    let encrypted =
+        EncryptedSecretKey::new(keys.secret_key(), password, log_n, KeySecurity::Unknown)?;
+    Ok(encrypted.to_bech32()?)
+}
+
+fn decrypt_ncryptsec(ncryptsec: &str, password: &str) -> Result<Keys> {
+    let encrypted = EncryptedSecretKey::from_bech32(ncryptsec.trim())
+        .context("Invalid ncryptsec, expected a NIP-49 encrypted key")?;
+    let secret_key = encrypted
+        .decrypt(password)
+        .map_err(|_| GitsmithError::WrongPassword)?;
+    Ok(Keys::new(secret_key))
+}
+
 /// Get the active account's public key (no password required)
-pub fn get_active_public_key() -> Result<String> {
-    let storage_path = get_account_storage_path()?;
-    let storage = AccountStorage::load(&storage_path)?;
+pub fn get_active_public_key(options: &AccountOptions) -> Result<String> {
+    let storage = options.storage_backend()?.load()?;
 
-    let active_npub = storage
-        .active_npub
-        .context("No active account. Please login first")?;
+    let active_npub = options.active_npub(&storage)?;
 
     // Convert npub to hex public key
     let public_key = nostr::PublicKey::from_bech32(&active_npub)?;
     Ok(public_key.to_hex())
 }
 
+/// Public details of a stored account, without its encrypted key
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountSummary {
+    pub npub: String,
+    pub public_key: String,
+    pub remote_signer: bool,
+    pub active: bool,
+    pub version: u32,
+    pub last_used: Option<u64>,
+}
+
+impl StoredAccount {
+    pub fn summary(&self, active: bool) -> Result<AccountSummary> {
+        Ok(AccountSummary {
+            npub: self.npub.clone(),
+            public_key: nostr::PublicKey::from_bech32(&self.npub)?.to_hex(),
+            remote_signer: self.bunker_uri.is_some(),
+            active,
+            version: self.version,
+            last_used: self.last_used,
+        })
+    }
+}
+
+/// Public details of all stored accounts
+pub fn account_summaries(options: &AccountOptions) -> Result<Vec<AccountSummary>> {
+    let storage = options.storage_backend()?.load()?;
+    let active = options.active_npub(&storage).ok();
+
+    storage
+        .accounts
+        .iter()
+        .map(|a| a.summary(active.as_ref() == Some(&a.npub)))
+        .collect()
+}
+
 /// List all accounts
-pub fn list_accounts() -> Result<Vec<String>> {
-    let storage_path = get_account_storage_path()?;
-    let storage = AccountStorage::load(&storage_path)?;
+pub fn list_accounts(options: &AccountOptions) -> Result<Vec<String>> {
+    let storage = options.storage_backend()?.load()?;
+    let active = options.active_npub(&storage).ok();
 
     Ok(storage
         .accounts
         .iter()
         .map(|a| {
-            let active = storage.active_npub.as_ref() == Some(&a.npub);
-            if active {
-                format!("{npub} (active)", npub = a.npub)
-            } else {
-                a.npub.clone()
+            let mut line = a.npub.clone();
+            if a.bunker_uri.is_some() {
+                line.push_str(" (remote signer)");
+            }
+            if active.as_ref() == Some(&a.npub) {
+                line.push_str(" (active)");
             }
+            line
         })
         .collect())
 }
+
+/// Accounts unused for longer than this are flagged by the audit
+const STALE_AFTER_SECS: u64 = 180 * 24 * 60 * 60;
+
+/// Key hygiene report of a stored account
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountAudit {
+    pub npub: String,
+    pub active: bool,
+    pub remote_signer: bool,
+    /// Unix time of the last signature, unknown for accounts never unlocked
+    /// since usage was recorded
+    pub last_used: Option<u64>,
+    /// Encrypted with the unsalted SHA-256 key derivation
+    pub legacy_encryption: bool,
+    /// Identifiers of the repositories announced with this key
+    pub announced_repos: Vec<String>,
+    /// Whether the key has a profile or notes, i.e. is someone's identity
+    pub personal_identity: bool,
+    pub warnings: Vec<String>,
+}
+
+/// Audit every stored account, looking up on the relays what each key published
+///
+/// No password is needed: only public keys and storage metadata are read.
+pub async fn audit_accounts(
+    options: &AccountOptions,
+    network: &Network,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<Vec<AccountAudit>> {
+    let storage = options.storage_backend()?.load()?;
+    let active = options.active_npub(&storage).ok();
+    let client = if relays.is_empty() {
+        None
+    } else {
+        Some(relay::connect(network, relays, None).await?)
+    };
+
+    let mut audits = Vec::new();
+    for account in &storage.accounts {
+        let mut audit = AccountAudit {
+            npub: account.npub.clone(),
+            active: active.as_ref() == Some(&account.npub),
+            remote_signer: account.bunker_uri.is_some(),
+            last_used: account.last_used,
+            legacy_encryption: account.version < ACCOUNT_VERSION,
+            announced_repos: Vec::new(),
+            personal_identity: false,
+            warnings: Vec::new(),
+        };
+
+        if let Some(client) = &client {
+            let public_key = PublicKey::from_bech32(&account.npub)?;
+            let filters = vec![
+                Filter::new()
+                    .kind(Kind::from(KIND_GIT_REPO_ANNOUNCEMENT))
+                    .author(public_key),
+                Filter::new()
+                    .kinds([Kind::Metadata, Kind::TextNote])
+                    .author(public_key)
+                    .limit(1),
+            ];
+            for event in relay::fetch_events(network, client, filters, timeout).await? {
+                if event.kind == Kind::from(KIND_GIT_REPO_ANNOUNCEMENT) {
+                    if let Some(identifier) = event.tags.identifier()
+                        && !audit.announced_repos.iter().any(|r| r == identifier)
+                    {
+                        audit.announced_repos.push(identifier.to_string());
+                    }
+                } else {
+                    audit.personal_identity = true;
+                }
+            }
+            audit.announced_repos.sort();
+        }
+
+        audit.warnings = hygiene_warnings(&audit, Timestamp::now().as_u64());
+        audits.push(audit);
+    }
+
+    if let Some(client) = client {
+        client.disconnect().await;
+    }
+    Ok(audits)
+}
+
+/// Advice for an audited account
+fn hygiene_warnings(audit: &AccountAudit, now: u64) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    if audit.legacy_encryption {
+        warnings.push(
+            "Encrypted with the legacy SHA-256 scheme, use the account once to upgrade it to Argon2id"
+                .to_string(),
+        );
+    }
+    match audit.last_used {
+        Some(last_used) if now.saturating_sub(last_used) > STALE_AFTER_SECS => {
+            warnings.push(format!(
+                "Not used for {days} days, remove it with logout if it is no longer needed",
+                days = now.saturating_sub(last_used) / (24 * 60 * 60)
+            ))
+        }
+        _ => {}
+    }
+    if audit.personal_identity && !audit.announced_repos.is_empty() {
+        warnings.push(format!(
+            "Also a personal identity and owner of {count} repo(s), a dedicated key per project limits what a leak exposes",
+            count = audit.announced_repos.len()
+        ));
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage_with(account: StoredAccount) -> AccountStorage {
+        AccountStorage {
+            active_npub: Some(account.npub.clone()),
+            accounts: vec![account],
+        }
+    }
+
+    #[test]
+    fn test_encrypt_round_trip() {
+        let keys = Keys::generate();
+        let mut account = StoredAccount {
+            version: ACCOUNT_VERSION,
+            npub: keys.public_key().to_bech32().unwrap(),
+            encrypted_nsec: Vec::new(),
+            nonce: Vec::new(),
+            salt: Vec::new(),
+            bunker_uri: None,
+            last_used: None,
+        };
+        encrypt_keys(&mut account, &keys, "secret").unwrap();
+        assert_eq!(account.salt.len(), 16);
+
+        let decrypted = decrypt_keys(&account, "secret").unwrap();
+        assert_eq!(decrypted.public_key(), keys.public_key());
+        assert!(matches!(
+            decrypt_keys(&account, "wrong"),
+            Err(GitsmithError::WrongPassword)
+        ));
+    }
+
+    #[test]
+    fn test_legacy_account_migrated_on_unlock() {
+        let keys = Keys::generate();
+        // Written before the storage format had a version
+        let mut account: StoredAccount = serde_json::from_value(serde_json::json!({
+            "npub": keys.public_key().to_bech32().unwrap(),
+            "encrypted_nsec": [],
+            "nonce": [],
+        }))
+        .unwrap();
+        assert_eq!(account.version, 1);
+        let key = derive_key("secret", &account).unwrap();
+        let cipher = ChaCha20Poly1305::new(&key.into());
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        account.encrypted_nsec = cipher
+            .encrypt(&nonce, keys.secret_key().to_secret_bytes().as_ref())
+            .unwrap();
+        account.nonce = nonce.to_vec();
+
+        let mut storage = storage_with(account);
+        assert!(matches!(
+            unlock(&mut storage, None, "wrong"),
+            Err(GitsmithError::WrongPassword)
+        ));
+        assert_eq!(storage.accounts[0].version, 1);
+
+        let (account, unlocked, migrated) = unlock(&mut storage, None, "secret").unwrap();
+        assert!(migrated);
+        assert_eq!(unlocked.public_key(), keys.public_key());
+        assert_eq!(account.version, ACCOUNT_VERSION);
+        assert!(account.last_used.is_some());
+        assert_eq!(storage.accounts[0].version, ACCOUNT_VERSION);
+
+        let (_, unlocked, migrated) = unlock(&mut storage, None, "secret").unwrap();
+        assert!(!migrated);
+        assert_eq!(unlocked.public_key(), keys.public_key());
+    }
+
+    #[test]
+    fn test_ncryptsec_round_trip() {
+        let keys = Keys::generate();
+        let ncryptsec = encrypt_ncryptsec(&keys, "secret", 4).unwrap();
+        assert!(ncryptsec.starts_with("ncryptsec1"));
+
+        let decrypted = decrypt_ncryptsec(&ncryptsec, "secret").unwrap();
+        assert_eq!(decrypted.public_key(), keys.public_key());
+        assert!(matches!(
+            decrypt_ncryptsec(&ncryptsec, "wrong"),
+            Err(GitsmithError::WrongPassword)
+        ));
+        assert!(decrypt_ncryptsec("nsec1invalid", "secret").is_err());
+    }
+
+    #[test]
+    fn test_file_backend_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = FileBackend::new(dir.path().join("gitsmith").join("accounts.json"));
+        assert!(backend.load().unwrap().accounts.is_empty());
+
+        let keys = Keys::generate();
+        let mut account = StoredAccount {
+            version: ACCOUNT_VERSION,
+            npub: keys.public_key().to_bech32().unwrap(),
+            encrypted_nsec: Vec::new(),
+            nonce: Vec::new(),
+            salt: Vec::new(),
+            bunker_uri: None,
+            last_used: None,
+        };
+        encrypt_keys(&mut account, &keys, "secret").unwrap();
+        backend.save(&storage_with(account)).unwrap();
+
+        let mut storage = backend.load().unwrap();
+        let (_, unlocked, _) = unlock(&mut storage, None, "secret").unwrap();
+        assert_eq!(unlocked.public_key(), keys.public_key());
+    }
+
+    #[test]
+    fn test_hygiene_warnings() {
+        let now = 1_717_243_200;
+        let mut audit = AccountAudit {
+            npub: "npub1test".to_string(),
+            active: true,
+            remote_signer: false,
+            last_used: Some(now - 60),
+            legacy_encryption: false,
+            announced_repos: vec!["gitsmith".to_string()],
+            personal_identity: false,
+            warnings: Vec::new(),
+        };
+        assert!(hygiene_warnings(&audit, now).is_empty());
+
+        audit.legacy_encryption = true;
+        audit.last_used = Some(now - 200 * 24 * 60 * 60);
+        audit.personal_identity = true;
+        let warnings = hygiene_warnings(&audit, now);
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].contains("Argon2id"));
+        assert!(warnings[1].contains("200 days"));
+        assert!(warnings[2].contains("owner of 1 repo(s)"));
+    }
+
+    #[test]
+    fn test_find_account() {
+        let keys = Keys::generate();
+        let npub = keys.public_key().to_bech32().unwrap();
+        let storage = storage_with(StoredAccount {
+            version: ACCOUNT_VERSION,
+            npub: npub.clone(),
+            encrypted_nsec: Vec::new(),
+            nonce: Vec::new(),
+            salt: Vec::new(),
+            bunker_uri: None,
+            last_used: None,
+        });
+
+        assert_eq!(find_account(&storage, &npub).unwrap().npub, npub);
+        let hex = keys.public_key().to_hex();
+        assert_eq!(find_account(&storage, &hex).unwrap().npub, npub);
+        let other = Keys::generate().public_key().to_bech32().unwrap();
+        assert!(find_account(&storage, &other).is_err());
+        assert!(find_account(&storage, "not a key").is_err());
+    }
+}
@@ -0,0 +1,1392 @@
+use git2::{Diff, Oid, Repository, Signature, StatusOptions, Time, build::CheckoutBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tracing::{debug, info};
+
+use crate::error::{Context, GitsmithError, Result, bail, ensure};
+use crate::format_patch;
+use crate::repo::open_repository;
+
+/// A patch in `git format-patch` layout split into commit metadata and diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedPatch {
+    pub commit_id: Option<String>,
+    pub author_name: String,
+    pub author_email: String,
+    /// Author time as (seconds since epoch, offset in minutes)
+    pub author_time: Option<(i64, i32)>,
+    pub subject: String,
+    pub body: String,
+    pub diff: String,
+}
+
+impl ParsedPatch {
+    /// Full commit message (subject and body)
+    pub fn message(&self) -> String {
+        if self.body.is_empty() {
+            format!("{subject}\n", subject = self.subject)
+        } else {
+            format!(
+                "{subject}\n\n{body}\n",
+                subject = self.subject,
+                body = self.body
+            )
+        }
+    }
+}
+
+/// Lines added and removed in one file of a patch
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStat {
+    pub path: String,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+/// Per-file and total line changes of a patch, like `git diff --stat`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffStat {
+    pub files: Vec<FileStat>,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+/// Options controlling how patches are applied
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ApplyOptions {
+    /// Only verify that the patches apply cleanly, without changing the repository
+    pub check: bool,
+    /// Fall back to a 3-way merge using `git am --3way`
+    pub three_way: bool,
+}
+
+/// A single applied (or checked) patch
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedPatch {
+    pub subject: String,
+    /// Commit created for the patch (None when only checking)
+    pub commit: Option<String>,
+}
+
+/// `git am --3way` stopped because a patch left conflicts in the working tree
+///
+/// The repository stays mid-`git am`; resolve with [`continue_apply`] or undo
+/// everything with [`abort_apply`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApplyConflict {
+    /// Subject of the patch that stopped
+    pub subject: String,
+    /// Files with unresolved conflicts, relative to the repository root
+    pub files: Vec<String>,
+}
+
+impl fmt::Display for ApplyConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Patch '{subject}' has conflicts in: {files}",
+            subject = self.subject,
+            files = self.files.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for ApplyConflict {}
+
+/// Result of turning a patch series into a local branch
+#[derive(Debug, Clone, Serialize)]
+pub struct BranchFromPatches {
+    pub branch: String,
+    pub base: String,
+    pub head: String,
+    pub patches: Vec<AppliedPatch>,
+}
+
+/// How [`merge_patches`] brings a patch series into the target branch
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Commit the patches on top of the branch, keeping history linear
+    #[default]
+    Apply,
+    /// Commit the patches onto their original parent and merge them with a merge commit
+    Merge,
+}
+
+/// Result of merging a patch series into a branch
+#[derive(Debug, Clone, Serialize)]
+pub struct MergedPatches {
+    pub branch: String,
+    /// Branch tip before the merge
+    pub previous_head: String,
+    pub head: String,
+    /// Merge commit, with [`MergeStrategy::Merge`]
+    pub merge_commit: Option<String>,
+    pub patches: Vec<AppliedPatch>,
+}
+
+/// Commits a patch series landed as outside gitsmith, found by [`find_applied_commits`]
+#[derive(Debug, Clone, Serialize)]
+pub struct LandedPatches {
+    /// Merge commit among the given commits, if any
+    pub merge_commit: Option<String>,
+    pub patches: Vec<AppliedPatch>,
+}
+
+/// Parse a patch produced by `git format-patch` (or gitsmith)
+pub fn parse_patch(patch: &str) -> Result<ParsedPatch> {
+    let mut commit_id = None;
+    let mut author_name = String::new();
+    let mut author_email = String::new();
+    let mut author_time = None;
+    let mut subject = String::new();
+
+    let mut lines = patch.lines().peekable();
+
+    // Mail headers, terminated by the first empty line
+    while let Some(line) = lines.next() {
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some(rest) = line.strip_prefix("From ") {
+            commit_id = rest.split_whitespace().next().map(|s| s.to_string());
+        } else if let Some(from) = line.strip_prefix("From: ") {
+            let mut from = from.to_string();
+            // Folded header continuation lines
+            while let Some(next) = lines.peek() {
+                if !next.starts_with([' ', '\t']) {
+                    break;
+                }
+                from.push_str(next);
+                lines.next();
+            }
+            (author_name, author_email) = parse_author(&format_patch::decode_header(&from));
+        } else if let Some(date) = line.strip_prefix("Date: ") {
+            author_time = chrono::DateTime::parse_from_rfc2822(date.trim())
+                .ok()
+                .map(|dt| (dt.timestamp(), dt.offset().local_minus_utc() / 60));
+        } else if let Some(value) = line.strip_prefix("Subject: ") {
+            subject = value.trim().to_string();
+            // Folded header continuation lines
+            while let Some(next) = lines.peek() {
+                if !next.starts_with([' ', '\t']) {
+                    break;
+                }
+                subject.push(' ');
+                subject.push_str(next.trim());
+                lines.next();
+            }
+        }
+    }
+
+    ensure!(!subject.is_empty(), "Patch is missing a Subject header");
+
+    // Commit message body, up to the start of the diff
+    let mut body_lines = Vec::new();
+    let mut diff = String::new();
+    let mut in_diff = false;
+    for line in lines {
+        if !in_diff && (line.starts_with("diff --git ") || line == "---") {
+            in_diff = true;
+            if line == "---" {
+                continue;
+            }
+        }
+
+        if in_diff {
+            diff.push_str(line);
+            diff.push('\n');
+        } else {
+            body_lines.push(line);
+        }
+    }
+
+    // Drop the signature footer ("-- \n<git version>")
+    if let Some(pos) = diff.rfind("\n-- \n") {
+        diff.truncate(pos + 1);
+    } else if diff.starts_with("-- \n") {
+        diff.clear();
+    }
+
+    // Skip the diffstat that `git format-patch` places between "---" and the diff
+    if let Some(pos) = diff.find("diff --git ") {
+        diff.drain(..pos);
+    }
+
+    Ok(ParsedPatch {
+        commit_id,
+        author_name,
+        author_email,
+        author_time,
+        subject: strip_patch_prefix(&format_patch::decode_header(&subject)).to_string(),
+        body: body_lines.join("\n").trim().to_string(),
+        diff,
+    })
+}
+
+/// Count changed lines per file in a unified diff
+pub fn diffstat(diff: &str) -> DiffStat {
+    let mut stat = DiffStat::default();
+    let mut in_hunk = false;
+
+    for line in diff.lines() {
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            // "a/old b/new": report the new path, which is the one that exists afterwards
+            let path = paths
+                .rsplit_once(" b/")
+                .map(|(_, new)| new)
+                .unwrap_or(paths);
+            stat.files.push(FileStat {
+                path: path.to_string(),
+                ..FileStat::default()
+            });
+            in_hunk = false;
+            continue;
+        }
+        if line.starts_with("@@") {
+            in_hunk = true;
+            continue;
+        }
+
+        let Some(file) = stat.files.last_mut().filter(|_| in_hunk) else {
+            continue;
+        };
+        if line.starts_with('+') {
+            file.insertions += 1;
+            stat.insertions += 1;
+        } else if line.starts_with('-') {
+            file.deletions += 1;
+            stat.deletions += 1;
+        }
+    }
+
+    stat
+}
+
+/// Split a "Name <email>" header value
+fn parse_author(value: &str) -> (String, String) {
+    match (value.find('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => (
+            unquote_name(value[..start].trim()),
+            value[start + 1..end].trim().to_string(),
+        ),
+        _ => (value.trim().to_string(), String::new()),
+    }
+}
+
+/// Undo the RFC 822 quoting of a display name (`"Doe, J." <...>`)
+fn unquote_name(name: &str) -> String {
+    let Some(quoted) = name
+        .strip_prefix('"')
+        .and_then(|name| name.strip_suffix('"'))
+    else {
+        return name.to_string();
+    };
+    let mut unquoted = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+/// Remove a leading "[PATCH ...]" marker from a subject, like `git mailinfo`
+fn strip_patch_prefix(subject: &str) -> &str {
+    let trimmed = subject.trim_start();
+    if trimmed.starts_with("[PATCH")
+        && let Some(end) = trimmed.find(']')
+    {
+        return trimmed[end + 1..].trim_start();
+    }
+    trimmed
+}
+
+/// Apply patches on top of `base`, creating one commit per patch without touching
+/// refs or the working tree. Returns the ID of the last commit.
+pub fn commit_patches_onto(repo: &Repository, base: Oid, patches: &[ParsedPatch]) -> Result<Oid> {
+    let committer = repo
+        .signature()
+        .context("Failed to determine committer identity (set user.name and user.email)")?;
+    let mut parent = repo.find_commit(base)?;
+
+    for patch in patches {
+        let tree = apply_to_tree(repo, &parent.tree()?, patch)?;
+
+        let time = patch
+            .author_time
+            .map(|(secs, offset)| Time::new(secs, offset))
+            .unwrap_or_else(|| committer.when());
+        let author =
+            Signature::new(&patch.author_name, &patch.author_email, &time).with_context(|| {
+                format!(
+                    "Invalid author in patch '{subject}'",
+                    subject = patch.subject
+                )
+            })?;
+
+        let oid = repo.commit(
+            None,
+            &author,
+            &committer,
+            &patch.message(),
+            &tree,
+            &[&parent],
+        )?;
+        debug!(%oid, subject = %patch.subject, "Created commit for patch");
+        parent = repo.find_commit(oid)?;
+    }
+
+    Ok(parent.id())
+}
+
+/// Apply a single patch to a tree in memory
+pub(crate) fn apply_to_tree<'r>(
+    repo: &'r Repository,
+    tree: &git2::Tree<'_>,
+    patch: &ParsedPatch,
+) -> Result<git2::Tree<'r>> {
+    let diff = Diff::from_buffer(patch.diff.as_bytes()).with_context(|| {
+        format!(
+            "Failed to parse diff of '{subject}'",
+            subject = patch.subject
+        )
+    })?;
+    let mut index = repo
+        .apply_to_tree(tree, &diff, None)
+        .with_context(|| format!("Patch '{subject}' does not apply", subject = patch.subject))?;
+    let tree_id = index.write_tree_to(repo)?;
+    Ok(repo.find_tree(tree_id)?)
+}
+
+/// Apply a patch series to the current branch with `git am`-like semantics
+pub fn apply_patches(
+    repo_path: &Path,
+    patches: &[String],
+    options: ApplyOptions,
+) -> Result<Vec<AppliedPatch>> {
+    let repo = open_repository(repo_path)?;
+    let parsed = patches
+        .iter()
+        .map(|p| parse_patch(p))
+        .collect::<Result<Vec<_>>>()?;
+
+    if options.check {
+        let mut tree = repo.head()?.peel_to_tree()?;
+        for patch in &parsed {
+            tree = apply_to_tree(&repo, &tree, patch)?;
+        }
+        return Ok(parsed
+            .into_iter()
+            .map(|p| AppliedPatch {
+                subject: p.subject,
+                commit: None,
+            })
+            .collect());
+    }
+
+    ensure_clean_worktree(&repo)?;
+
+    if options.three_way {
+        return apply_with_git_am(repo_path, patches, &parsed);
+    }
+
+    let head = repo.head()?;
+    let base = head.peel_to_commit()?.id();
+    let tip = commit_patches_onto(&repo, base, &parsed)?;
+
+    // Update the working tree first, then move the branch
+    let tip_commit = repo.find_commit(tip)?;
+    repo.checkout_tree(tip_commit.as_object(), Some(CheckoutBuilder::new().safe()))?;
+    if head.is_branch() {
+        let mut reference = head.resolve()?;
+        reference.set_target(tip, "gitsmith: apply patches")?;
+    } else {
+        repo.set_head_detached(tip)?;
+    }
+    info!(%tip, count = parsed.len(), "Applied patches");
+
+    applied_patches(&repo, tip, &parsed)
+}
+
+/// Pair each patch with the commit created for it, walking back from the series tip
+fn applied_patches(
+    repo: &Repository,
+    tip: Oid,
+    parsed: &[ParsedPatch],
+) -> Result<Vec<AppliedPatch>> {
+    let mut commits = Vec::new();
+    let mut current = Some(repo.find_commit(tip)?);
+    for _ in 0..parsed.len() {
+        let Some(commit) = current else { break };
+        commits.push(commit.id().to_string());
+        current = commit.parent(0).ok();
+    }
+    commits.reverse();
+
+    Ok(parsed
+        .iter()
+        .zip(commits)
+        .map(|(p, commit)| AppliedPatch {
+            subject: p.subject.clone(),
+            commit: Some(commit),
+        })
+        .collect())
+}
+
+/// Create (or reset with `force`) a local branch containing a patch series
+///
+/// The series is applied onto `base`, which defaults to the parent of the first
+/// patch's original commit when it exists locally and to HEAD otherwise.
+pub fn create_branch_from_patches(
+    repo_path: &Path,
+    branch_name: &str,
+    patches: &[String],
+    base: Option<&str>,
+    force: bool,
+) -> Result<BranchFromPatches> {
+    let repo = open_repository(repo_path)?;
+    let parsed = patches
+        .iter()
+        .map(|p| parse_patch(p))
+        .collect::<Result<Vec<_>>>()?;
+
+    let base_oid = match base {
+        Some(rev) => repo
+            .revparse_single(rev)
+            .and_then(|obj| obj.peel_to_commit())
+            .with_context(|| format!("Base commit {rev} not found locally. Try 'git fetch' first"))?
+            .id(),
+        None => default_base(&repo, &parsed)?,
+    };
+
+    if !force
+        && repo
+            .find_branch(branch_name, git2::BranchType::Local)
+            .is_ok()
+    {
+        bail!("Branch {branch_name} already exists. Use --force to overwrite it");
+    }
+
+    let tip = commit_patches_onto(&repo, base_oid, &parsed)?;
+    repo.branch(branch_name, &repo.find_commit(tip)?, force)?;
+    info!(%tip, branch = branch_name, "Created branch from patches");
+
+    Ok(BranchFromPatches {
+        branch: branch_name.to_string(),
+        base: base_oid.to_string(),
+        head: tip.to_string(),
+        patches: applied_patches(&repo, tip, &parsed)?,
+    })
+}
+
+/// Merge a patch series into the local branch `branch`
+///
+/// With [`MergeStrategy::Merge`] the patches are committed onto `base`, which
+/// defaults like [`create_branch_from_patches`], then merged with `message`.
+/// The working tree is only updated when `branch` is checked out, and must be
+/// clean then.
+pub fn merge_patches(
+    repo_path: &Path,
+    branch: &str,
+    patches: &[String],
+    strategy: MergeStrategy,
+    base: Option<&str>,
+    message: &str,
+) -> Result<MergedPatches> {
+    let repo = open_repository(repo_path)?;
+    let parsed = patches
+        .iter()
+        .map(|p| parse_patch(p))
+        .collect::<Result<Vec<_>>>()?;
+    let mut reference = repo
+        .find_branch(branch, git2::BranchType::Local)
+        .with_context(|| format!("Branch {branch} not found"))?
+        .into_reference();
+    let target = reference.peel_to_commit()?;
+    let checked_out = repo
+        .head()
+        .ok()
+        .and_then(|head| head.name().map(String::from))
+        == reference.name().map(String::from);
+    if checked_out {
+        ensure_clean_worktree(&repo)?;
+    }
+
+    let (tip, series_tip, merge_commit) = match strategy {
+        MergeStrategy::Apply => {
+            let tip = commit_patches_onto(&repo, target.id(), &parsed)?;
+            (tip, tip, None)
+        }
+        MergeStrategy::Merge => {
+            let base_oid = match base {
+                Some(rev) => repo
+                    .revparse_single(rev)
+                    .and_then(|obj| obj.peel_to_commit())
+                    .with_context(|| {
+                        format!("Base commit {rev} not found locally. Try 'git fetch' first")
+                    })?
+                    .id(),
+                None => default_base(&repo, &parsed)?,
+            };
+            let series_tip = commit_patches_onto(&repo, base_oid, &parsed)?;
+            let series = repo.find_commit(series_tip)?;
+            let mut index = repo.merge_commits(&target, &series, None)?;
+            if index.has_conflicts() {
+                let files: Vec<String> = index
+                    .conflicts()?
+                    .filter_map(|conflict| conflict.ok())
+                    .filter_map(|conflict| conflict.our.or(conflict.their))
+                    .map(|entry| String::from_utf8_lossy(&entry.path).into_owned())
+                    .collect();
+                bail!(
+                    "Merging into {branch} conflicts in: {files}. Try the apply strategy, or 'gitsmith pr apply --3way'",
+                    files = files.join(", ")
+                );
+            }
+            let tree = repo.find_tree(index.write_tree_to(&repo)?)?;
+            let committer = repo
+                .signature()
+                .context("Failed to determine committer identity (set user.name and user.email)")?;
+            let merge = repo.commit(
+                None,
+                &committer,
+                &committer,
+                message,
+                &tree,
+                &[&target, &series],
+            )?;
+            (merge, series_tip, Some(merge.to_string()))
+        }
+    };
+
+    // Update the working tree first, then move the branch
+    if checked_out {
+        let tip_commit = repo.find_commit(tip)?;
+        repo.checkout_tree(tip_commit.as_object(), Some(CheckoutBuilder::new().safe()))?;
+    }
+    reference.set_target(tip, "gitsmith: merge patches")?;
+    info!(%tip, branch, count = parsed.len(), "Merged patches");
+
+    Ok(MergedPatches {
+        branch: branch.to_string(),
+        previous_head: target.id().to_string(),
+        head: tip.to_string(),
+        merge_commit,
+        patches: applied_patches(&repo, series_tip, &parsed)?,
+    })
+}
+
+/// Stable patch ID of a unified diff, like `git patch-id --stable`
+///
+/// Patch IDs ignore line numbers and whitespace, so a patch and the commit it
+/// was applied as share one even after a rebase.
+pub fn patch_id(diff: &str) -> Result<Oid> {
+    let diff = Diff::from_buffer(diff.as_bytes()).context("Failed to parse diff")?;
+    Ok(diff.patchid(None)?)
+}
+
+/// Patch ID of the changes a commit makes to its first parent
+fn commit_patch_id(repo: &Repository, commit: &git2::Commit<'_>) -> Result<Oid> {
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) => None,
+    };
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+    Ok(diff.patchid(None)?)
+}
+
+/// Patch IDs of the commits on HEAD and the remote-tracking branches
+///
+/// Merge commits and commits made before `since` (seconds since epoch) are
+/// skipped. Commit times can be out of order, e.g. when a branch keeps the
+/// times of the author's clone, so older commits don't end the walk.
+pub fn history_patch_ids(repo_path: &Path, since: i64) -> Result<HashSet<Oid>> {
+    let repo = open_repository(repo_path)?;
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(git2::Sort::TIME)?;
+    if repo.head().is_ok() {
+        walk.push_head()?;
+    }
+    walk.push_glob("refs/remotes/*")?;
+
+    let mut ids = HashSet::new();
+    for oid in walk {
+        let commit = repo.find_commit(oid?)?;
+        if commit.time().seconds() < since {
+            continue;
+        }
+        if commit.parent_count() < 2 {
+            ids.insert(commit_patch_id(&repo, &commit)?);
+        }
+    }
+    debug!(count = ids.len(), "Computed patch IDs of the local history");
+    Ok(ids)
+}
+
+/// Match each patch of a series to the local commit it was applied as
+///
+/// `commits` are revisions that exist locally. A merge commit stands for the
+/// commits it brought in and is reported as the merge commit; at most one may
+/// be given. Fails when a patch has no commit with the same patch ID.
+pub fn find_applied_commits(
+    repo_path: &Path,
+    patches: &[String],
+    commits: &[String],
+) -> Result<LandedPatches> {
+    let repo = open_repository(repo_path)?;
+    let parsed = patches
+        .iter()
+        .map(|p| parse_patch(p))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut merge_commit = None;
+    let mut candidates = Vec::new();
+    for rev in commits {
+        let commit = repo
+            .revparse_single(rev)
+            .and_then(|obj| obj.peel_to_commit())
+            .with_context(|| format!("Commit {rev} not found locally. Try 'git fetch' first"))?;
+        if commit.parent_count() < 2 {
+            candidates.push(commit);
+            continue;
+        }
+        if merge_commit.replace(commit.id()).is_some() {
+            bail!("Only one merge commit can be given");
+        }
+        // Commits the merge brought in
+        let mut walk = repo.revwalk()?;
+        walk.push(commit.id())?;
+        walk.hide(commit.parent_id(0)?)?;
+        for oid in walk {
+            let merged = repo.find_commit(oid?)?;
+            if merged.parent_count() < 2 {
+                candidates.push(merged);
+            }
+        }
+    }
+
+    let mut by_patch_id = HashMap::new();
+    for commit in &candidates {
+        by_patch_id
+            .entry(commit_patch_id(&repo, commit)?)
+            .or_insert_with(|| commit.id().to_string());
+    }
+
+    let patches = parsed
+        .iter()
+        .map(|patch| {
+            let commit = patch_id(&patch.diff)
+                .ok()
+                .and_then(|id| by_patch_id.get(&id).cloned())
+                .with_context(|| {
+                    format!(
+                        "No given commit matches patch '{subject}'",
+                        subject = patch.subject
+                    )
+                })?;
+            Ok(AppliedPatch {
+                subject: patch.subject.clone(),
+                commit: Some(commit),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(LandedPatches {
+        merge_commit: merge_commit.map(|oid| oid.to_string()),
+        patches,
+    })
+}
+
+/// Parent of the series' first original commit if known locally, HEAD otherwise
+fn default_base(repo: &Repository, parsed: &[ParsedPatch]) -> Result<Oid> {
+    let original_parent = parsed
+        .first()
+        .and_then(|p| p.commit_id.as_deref())
+        .and_then(|id| Oid::from_str(id).ok())
+        .and_then(|oid| repo.find_commit(oid).ok())
+        .and_then(|commit| commit.parent_id(0).ok());
+
+    match original_parent {
+        Some(oid) => Ok(oid),
+        None => Ok(repo.head()?.peel_to_commit()?.id()),
+    }
+}
+
+/// Refuse to touch the working tree while tracked files have uncommitted changes
+pub(crate) fn ensure_clean_worktree(repo: &Repository) -> Result<()> {
+    let mut status_options = StatusOptions::new();
+    status_options
+        .include_untracked(false)
+        .include_ignored(false);
+    let statuses = repo.statuses(Some(&mut status_options))?;
+    ensure!(
+        statuses.is_empty(),
+        "Working tree has uncommitted changes. Commit or stash them first"
+    );
+    Ok(())
+}
+
+/// Apply patches through `git am --3way`, leaving conflicts for the user to resolve
+fn apply_with_git_am(
+    repo_path: &Path,
+    patches: &[String],
+    parsed: &[ParsedPatch],
+) -> Result<Vec<AppliedPatch>> {
+    let mut child = Command::new("git")
+        .args(["am", "--3way"])
+        .current_dir(repo_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run git am")?;
+
+    {
+        let mut stdin = child.stdin.take().context("Failed to open git am stdin")?;
+        // git am reads the series as a single mbox
+        for patch in patches {
+            stdin.write_all(patch.as_bytes())?;
+            if !patch.ends_with('\n') {
+                stdin.write_all(b"\n")?;
+            }
+        }
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(am_failure(repo_path, &output.stderr));
+    }
+
+    let repo = open_repository(repo_path)?;
+    let tip = repo.head()?.peel_to_commit()?.id();
+    applied_patches(&repo, tip, parsed)
+}
+
+/// Error for a failed `git am` run, an [`ApplyConflict`] if it stopped on conflicts
+fn am_failure(repo_path: &Path, stderr: &[u8]) -> GitsmithError {
+    let stderr = String::from_utf8_lossy(stderr);
+    match (apply_in_progress(repo_path), conflicted_files(repo_path)) {
+        (Ok(true), Ok(files)) if !files.is_empty() => {
+            // git am reports "Patch failed at 0001 <subject>"
+            let subject = stderr
+                .lines()
+                .find_map(|line| line.strip_prefix("Patch failed at "))
+                .and_then(|rest| rest.split_once(' '))
+                .map(|(_, subject)| subject.to_string())
+                .unwrap_or_default();
+            ApplyConflict { subject, files }.into()
+        }
+        _ => GitsmithError::msg(format!(
+            "git am --3way failed:\n{stderr}",
+            stderr = stderr.trim()
+        )),
+    }
+}
+
+/// Whether a `git am` run stopped midway in the repository
+pub fn apply_in_progress(repo_path: &Path) -> Result<bool> {
+    let repo = open_repository(repo_path)?;
+    Ok(repo.path().join("rebase-apply").join("applying").exists())
+}
+
+/// Files with unresolved conflicts in the index
+pub fn conflicted_files(repo_path: &Path) -> Result<Vec<String>> {
+    let repo = open_repository(repo_path)?;
+    let index = repo.index()?;
+    let mut files = Vec::new();
+    for conflict in index.conflicts()? {
+        let conflict = conflict?;
+        let entry = conflict.our.or(conflict.their).or(conflict.ancestor);
+        if let Some(entry) = entry {
+            files.push(String::from_utf8_lossy(&entry.path).into_owned());
+        }
+    }
+    Ok(files)
+}
+
+/// Whether a file in the working tree still contains conflict markers
+pub fn has_conflict_markers(repo_path: &Path, file: &str) -> Result<bool> {
+    let content =
+        std::fs::read(repo_path.join(file)).with_context(|| format!("Failed to read {file}"))?;
+    let content = String::from_utf8_lossy(&content);
+    Ok(content
+        .lines()
+        .any(|line| line.starts_with("<<<<<<< ") || line.starts_with(">>>>>>> ")))
+}
+
+/// Stage the resolved files and resume the stopped `git am` run
+///
+/// Fails with an [`ApplyConflict`] when a later patch conflicts as well.
+pub fn continue_apply(repo_path: &Path) -> Result<()> {
+    ensure!(
+        apply_in_progress(repo_path)?,
+        "No patch application in progress"
+    );
+
+    let files = conflicted_files(repo_path)?;
+    let unresolved: Vec<&String> = files
+        .iter()
+        .filter(|file| has_conflict_markers(repo_path, file).unwrap_or(false))
+        .collect();
+    ensure!(
+        unresolved.is_empty(),
+        "Conflict markers left in: {files}",
+        files = unresolved
+            .iter()
+            .map(|file| file.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    if !files.is_empty() {
+        let mut args = vec!["add", "--"];
+        args.extend(files.iter().map(|file| file.as_str()));
+        run_git(repo_path, &args)?;
+    }
+
+    let output = Command::new("git")
+        .args(["am", "--continue"])
+        .current_dir(repo_path)
+        .output()
+        .context("Failed to run git am --continue")?;
+    if !output.status.success() {
+        return Err(am_failure(repo_path, &output.stderr));
+    }
+    info!("Resumed patch application");
+    Ok(())
+}
+
+/// Undo a stopped `git am` run, restoring the branch to its state before applying
+pub fn abort_apply(repo_path: &Path) -> Result<()> {
+    ensure!(
+        apply_in_progress(repo_path)?,
+        "No patch application in progress"
+    );
+    run_git(repo_path, &["am", "--abort"])
+}
+
+/// Force-push a local branch to the branch of the same name on `remote`
+pub fn push_branch(repo_path: &Path, remote: &str, branch: &str) -> Result<()> {
+    let refspec = format!("+refs/heads/{branch}:refs/heads/{branch}");
+    run_git(repo_path, &["push", "--quiet", remote, &refspec])?;
+    info!(%remote, %branch, "Pushed branch");
+    Ok(())
+}
+
+fn run_git(repo_path: &Path, args: &[&str]) -> Result<()> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(repo_path)
+        .output()
+        .with_context(|| format!("Failed to run git {args}", args = args.join(" ")))?;
+    ensure!(
+        output.status.success(),
+        "git {args} failed: {stderr}",
+        args = args.join(" "),
+        stderr = String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patches::generate_patches;
+    use std::fs;
+
+    fn commit_all(repo: &Repository, message: &str) -> Oid {
+        let sig = Signature::now("Alice Example", "alice@example.com").unwrap();
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parents = match repo.head() {
+            Ok(head) => vec![head.peel_to_commit().unwrap()],
+            Err(_) => vec![],
+        };
+        let parents: Vec<&git2::Commit> = parents.iter().collect();
+        repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents)
+            .unwrap()
+    }
+
+    const PATCH: &str = "From 1234567890abcdef1234567890abcdef12345678 Mon Sep 17 00:00:00 2001
+From: Alice Example <alice@example.com>
+Date: Tue, 02 Jan 2024 03:04:05 +0000
+Subject: [PATCH 1/2] Change b to c
+
+Longer explanation
+of the change.
+
+diff --git a/f.txt b/f.txt
+index 422c2b7..0f7bc76 100644
+--- a/f.txt
++++ b/f.txt
+@@ -1,2 +1,2 @@
+ a
+-b
++c
+--\x20
+2.34.1
+";
+
+    #[test]
+    fn test_parse_patch() {
+        let patch = parse_patch(PATCH).unwrap();
+
+        assert_eq!(
+            patch.commit_id.as_deref(),
+            Some("1234567890abcdef1234567890abcdef12345678")
+        );
+        assert_eq!(patch.author_name, "Alice Example");
+        assert_eq!(patch.author_email, "alice@example.com");
+        assert_eq!(patch.author_time, Some((1704164645, 0)));
+        assert_eq!(patch.subject, "Change b to c");
+        assert_eq!(patch.body, "Longer explanation\nof the change.");
+        assert!(patch.diff.starts_with("diff --git a/f.txt b/f.txt\n"));
+        assert!(patch.diff.ends_with("+c\n"));
+        assert_eq!(
+            patch.message(),
+            "Change b to c\n\nLonger explanation\nof the change.\n"
+        );
+    }
+
+    #[test]
+    fn test_diffstat() {
+        let patch = parse_patch(PATCH).unwrap();
+        let stat = diffstat(&patch.diff);
+        assert_eq!(
+            stat.files,
+            vec![FileStat {
+                path: "f.txt".to_string(),
+                insertions: 1,
+                deletions: 1,
+            }]
+        );
+
+        let diff = "diff --git a/old.txt b/new.txt\n\
+                    similarity index 90%\n\
+                    rename from old.txt\n\
+                    rename to new.txt\n\
+                    diff --git a/g.txt b/g.txt\n\
+                    --- a/g.txt\n\
+                    +++ b/g.txt\n\
+                    @@ -1 +1,3 @@\n\
+                    \x20x\n\
+                    +y\n\
+                    +z\n";
+        let stat = diffstat(diff);
+        assert_eq!(stat.files.len(), 2);
+        assert_eq!(stat.files[0].path, "new.txt");
+        assert_eq!((stat.insertions, stat.deletions), (2, 0));
+    }
+
+    #[test]
+    fn test_parse_patch_requires_subject() {
+        assert!(parse_patch("From: a <b@c>\n\ndiff --git a/x b/x\n").is_err());
+    }
+
+    #[test]
+    fn test_generated_patches_apply_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Maintainer").unwrap();
+        config
+            .set_str("user.email", "maintainer@example.com")
+            .unwrap();
+
+        fs::write(dir.path().join("f.txt"), "a\nb\n").unwrap();
+        let base = commit_all(&repo, "Initial commit");
+        fs::write(dir.path().join("f.txt"), "a\nc\n").unwrap();
+        commit_all(&repo, "Change b to c\n\nWith a body.");
+        fs::write(dir.path().join("g.txt"), "new\n").unwrap();
+        commit_all(&repo, "Add g");
+
+        let patches = generate_patches(dir.path(), Some("HEAD~2"), None).unwrap();
+        assert_eq!(patches.len(), 2);
+
+        // Rewind and re-apply the series
+        let base_commit = repo.find_commit(base).unwrap();
+        repo.reset(base_commit.as_object(), git2::ResetType::Hard, None)
+            .unwrap();
+
+        let checked = apply_patches(
+            dir.path(),
+            &patches,
+            ApplyOptions {
+                check: true,
+                three_way: false,
+            },
+        )
+        .unwrap();
+        assert!(checked.iter().all(|p| p.commit.is_none()));
+        assert_eq!(repo.head().unwrap().peel_to_commit().unwrap().id(), base);
+
+        let applied = apply_patches(dir.path(), &patches, ApplyOptions::default()).unwrap();
+        assert_eq!(applied.len(), 2);
+        assert_eq!(applied[0].subject, "Change b to c");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("f.txt")).unwrap(),
+            "a\nc\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("g.txt")).unwrap(),
+            "new\n"
+        );
+
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.summary(), Some("Add g"));
+        assert_eq!(head.author().name(), Some("Alice Example"));
+        assert_eq!(head.committer().name(), Some("Maintainer"));
+        let first = head.parent(0).unwrap();
+        assert_eq!(first.message(), Some("Change b to c\n\nWith a body.\n"));
+        assert_eq!(first.parent_id(0).unwrap(), base);
+    }
+
+    #[test]
+    fn test_binary_patches_apply_intact() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Maintainer").unwrap();
+        config
+            .set_str("user.email", "maintainer@example.com")
+            .unwrap();
+
+        fs::write(dir.path().join("f.txt"), "a\n").unwrap();
+        let base = commit_all(&repo, "Initial commit");
+        let image: Vec<u8> = (0..4096u32).map(|i| (i * 7 % 256) as u8).collect();
+        fs::write(dir.path().join("image.png"), &image).unwrap();
+        // Latin-1 text is text to git, but can't go into an event as is
+        fs::write(dir.path().join("latin1.txt"), b"caf\xe9\n").unwrap();
+        commit_all(&repo, "Add binary files");
+        let mut changed = image.clone();
+        changed[100] = 0;
+        changed.extend_from_slice(b"\0tail");
+        fs::write(dir.path().join("image.png"), &changed).unwrap();
+        commit_all(&repo, "Change the image");
+
+        let patches = generate_patches(dir.path(), Some("HEAD~2"), None).unwrap();
+        assert!(patches[0].contains("GIT binary patch"));
+        let full_index = format!("index {zeros}..", zeros = "0".repeat(40));
+        assert!(patches[0].contains(&full_index));
+
+        // Through libgit2 and through git am
+        for three_way in [false, true] {
+            let base_commit = repo.find_commit(base).unwrap();
+            repo.reset(base_commit.as_object(), git2::ResetType::Hard, None)
+                .unwrap();
+            apply_patches(
+                dir.path(),
+                &patches,
+                ApplyOptions {
+                    check: false,
+                    three_way,
+                },
+            )
+            .unwrap();
+            assert_eq!(fs::read(dir.path().join("image.png")).unwrap(), changed);
+            assert_eq!(
+                fs::read(dir.path().join("latin1.txt")).unwrap(),
+                b"caf\xe9\n"
+            );
+        }
+    }
+
+    #[test]
+    fn test_apply_rejects_conflicting_patch() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("f.txt"), "a\nb\n").unwrap();
+        commit_all(&repo, "Initial commit");
+
+        let result = apply_patches(
+            dir.path(),
+            &[PATCH.replace(" a\n-b", " x\n-y")],
+            ApplyOptions {
+                check: true,
+                three_way: false,
+            },
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_three_way_conflict_continue_and_abort() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Maintainer").unwrap();
+        config
+            .set_str("user.email", "maintainer@example.com")
+            .unwrap();
+        fs::write(dir.path().join("f.txt"), "a\nb\n").unwrap();
+        let base = commit_all(&repo, "Initial commit");
+        fs::write(dir.path().join("f.txt"), "a\nc\n").unwrap();
+        commit_all(&repo, "Change b to c");
+        let patches = generate_patches(dir.path(), Some("HEAD~1"), None).unwrap();
+
+        // Diverge from the patch on the same line
+        let base_commit = repo.find_commit(base).unwrap();
+        repo.reset(base_commit.as_object(), git2::ResetType::Hard, None)
+            .unwrap();
+        fs::write(dir.path().join("f.txt"), "a\nd\n").unwrap();
+        let diverged = commit_all(&repo, "Change b to d");
+        let options = ApplyOptions {
+            check: false,
+            three_way: true,
+        };
+
+        let err = apply_patches(dir.path(), &patches, options).unwrap_err();
+        let GitsmithError::Conflict(conflict) = err.root() else {
+            panic!("expected a conflict, got {err}");
+        };
+        assert_eq!(conflict.files, vec!["f.txt".to_string()]);
+        assert!(apply_in_progress(dir.path()).unwrap());
+
+        // Markers must be resolved before continuing
+        assert!(continue_apply(dir.path()).is_err());
+
+        abort_apply(dir.path()).unwrap();
+        assert!(!apply_in_progress(dir.path()).unwrap());
+        assert_eq!(
+            repo.head().unwrap().peel_to_commit().unwrap().id(),
+            diverged
+        );
+
+        apply_patches(dir.path(), &patches, options).unwrap_err();
+        fs::write(dir.path().join("f.txt"), "a\nc and d\n").unwrap();
+        continue_apply(dir.path()).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.summary(), Some("Change b to c"));
+        assert_eq!(head.parent_id(0).unwrap(), diverged);
+    }
+
+    #[test]
+    fn test_create_branch_from_patches() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Reviewer").unwrap();
+        config
+            .set_str("user.email", "reviewer@example.com")
+            .unwrap();
+        fs::write(dir.path().join("f.txt"), "a\nb\n").unwrap();
+        let base = commit_all(&repo, "Initial commit");
+        fs::write(dir.path().join("f.txt"), "a\nc\n").unwrap();
+        commit_all(&repo, "Change b to c");
+        let patches = generate_patches(dir.path(), Some("HEAD~1"), None).unwrap();
+        fs::write(dir.path().join("h.txt"), "unrelated\n").unwrap();
+        let head = commit_all(&repo, "Unrelated work");
+
+        // Defaults to the parent of the original commit when it is known locally
+        let result =
+            create_branch_from_patches(dir.path(), "pr/abcd1234", &patches, None, false).unwrap();
+        assert_eq!(result.branch, "pr/abcd1234");
+        assert_eq!(result.base, base.to_string());
+        assert_eq!(result.patches.len(), 1);
+
+        let branch = repo
+            .find_branch("pr/abcd1234", git2::BranchType::Local)
+            .unwrap();
+        let tip = branch.get().peel_to_commit().unwrap();
+        assert_eq!(tip.id().to_string(), result.head);
+        assert_eq!(tip.parent_id(0).unwrap(), base);
+        // The working tree and HEAD are left untouched
+        assert_eq!(repo.head().unwrap().peel_to_commit().unwrap().id(), head);
+
+        assert!(
+            create_branch_from_patches(dir.path(), "pr/abcd1234", &patches, None, false).is_err()
+        );
+        let forced =
+            create_branch_from_patches(dir.path(), "pr/abcd1234", &patches, Some("HEAD~2"), true)
+                .unwrap();
+        assert_eq!(forced.base, base.to_string());
+    }
+
+    #[test]
+    fn test_merge_patches() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Maintainer").unwrap();
+        config
+            .set_str("user.email", "maintainer@example.com")
+            .unwrap();
+        fs::write(dir.path().join("f.txt"), "a\nb\n").unwrap();
+        let base = commit_all(&repo, "Initial commit");
+        fs::write(dir.path().join("f.txt"), "a\nc\n").unwrap();
+        commit_all(&repo, "Change b to c");
+        let patches = generate_patches(dir.path(), Some("HEAD~1"), None).unwrap();
+        let branch = repo.head().unwrap().shorthand().unwrap().to_string();
+
+        // Back to the base, with unrelated work on the branch since
+        let mut reference = repo.head().unwrap();
+        reference.set_target(base, "reset").unwrap();
+        repo.checkout_head(Some(CheckoutBuilder::new().force()))
+            .unwrap();
+        fs::write(dir.path().join("h.txt"), "unrelated\n").unwrap();
+        let head = commit_all(&repo, "Unrelated work");
+
+        let merged = merge_patches(
+            dir.path(),
+            &branch,
+            &patches,
+            MergeStrategy::Merge,
+            None,
+            "Merge PR",
+        )
+        .unwrap();
+        assert_eq!(merged.previous_head, head.to_string());
+        let merge = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(merged.merge_commit, Some(merge.id().to_string()));
+        assert_eq!(merge.parent_ids().collect::<Vec<_>>()[0], head);
+        let series = merge.parent(1).unwrap();
+        assert_eq!(series.parent_id(0).unwrap(), base);
+        assert_eq!(merged.patches[0].commit, Some(series.id().to_string()));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("f.txt")).unwrap(),
+            "a\nc\n"
+        );
+        assert!(dir.path().join("h.txt").exists());
+
+        // Applying puts the patches on top of the branch instead
+        let mut reference = repo.head().unwrap();
+        reference.set_target(head, "reset").unwrap();
+        repo.checkout_head(Some(CheckoutBuilder::new().force()))
+            .unwrap();
+        let applied = merge_patches(
+            dir.path(),
+            &branch,
+            &patches,
+            MergeStrategy::Apply,
+            None,
+            "",
+        )
+        .unwrap();
+        assert!(applied.merge_commit.is_none());
+        let tip = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(applied.head, tip.id().to_string());
+        assert_eq!(tip.parent_id(0).unwrap(), head);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("f.txt")).unwrap(),
+            "a\nc\n"
+        );
+
+        assert!(
+            merge_patches(
+                dir.path(),
+                "missing",
+                &patches,
+                MergeStrategy::Apply,
+                None,
+                ""
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_find_applied_commits() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("f.txt"), "a\nb\n").unwrap();
+        let base = commit_all(&repo, "Initial commit");
+        fs::write(dir.path().join("f.txt"), "a\nc\n").unwrap();
+        commit_all(&repo, "Change b to c");
+        let patches = generate_patches(dir.path(), Some("HEAD~1"), None).unwrap();
+
+        // Applied by hand on top of unrelated work, with a reworded message
+        let mut reference = repo.head().unwrap();
+        reference.set_target(base, "reset").unwrap();
+        repo.checkout_head(Some(CheckoutBuilder::new().force()))
+            .unwrap();
+        fs::write(dir.path().join("h.txt"), "unrelated\n").unwrap();
+        let unrelated = commit_all(&repo, "Unrelated work");
+        fs::write(dir.path().join("f.txt"), "a\nc\n").unwrap();
+        let applied = commit_all(&repo, "Use c instead of b");
+
+        let landed = find_applied_commits(
+            dir.path(),
+            &patches,
+            &[unrelated.to_string(), "HEAD".to_string()],
+        )
+        .unwrap();
+        assert!(landed.merge_commit.is_none());
+        assert_eq!(landed.patches[0].commit, Some(applied.to_string()));
+
+        let err = find_applied_commits(dir.path(), &patches, &[unrelated.to_string()])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Change b to c"), "{err}");
+        assert!(find_applied_commits(dir.path(), &patches, &["0".repeat(40)]).is_err());
+
+        let id = patch_id(&parse_patch(&patches[0]).unwrap().diff).unwrap();
+        assert!(history_patch_ids(dir.path(), 0).unwrap().contains(&id));
+        let later = repo
+            .head()
+            .unwrap()
+            .peel_to_commit()
+            .unwrap()
+            .time()
+            .seconds()
+            + 1;
+        assert!(history_patch_ids(dir.path(), later).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_history_patch_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let commit_at = |file: &str, seconds: i64, parents: &[Oid], update_ref: Option<&str>| {
+            fs::write(dir.path().join(file), format!("{seconds}\n")).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new(file)).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let sig = Signature::new(
+                "Alice Example",
+                "alice@example.com",
+                &git2::Time::new(seconds, 0),
+            )
+            .unwrap();
+            let parents: Vec<git2::Commit> = parents
+                .iter()
+                .map(|oid| repo.find_commit(*oid).unwrap())
+                .collect();
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            repo.commit(update_ref, &sig, &sig, file, &tree, &parents)
+                .unwrap()
+        };
+        let patch_id_of =
+            |oid: Oid| commit_patch_id(&repo, &repo.find_commit(oid).unwrap()).unwrap();
+
+        let old = commit_at("old.txt", 1_000, &[], Some("HEAD"));
+        // Rebased on top of newer work with the times of the author's clone
+        let newer = commit_at("newer.txt", 5_000, &[old], Some("HEAD"));
+        let skewed = commit_at("skewed.txt", 2_000, &[newer], Some("HEAD"));
+        // Only on a remote-tracking branch
+        let remote = commit_at("remote.txt", 4_000, &[old], None);
+        repo.reference("refs/remotes/origin/main", remote, false, "test")
+            .unwrap();
+        let merge = commit_at("merge.txt", 6_000, &[skewed, remote], Some("HEAD"));
+
+        let ids = history_patch_ids(dir.path(), 3_000).unwrap();
+        assert!(ids.contains(&patch_id_of(newer)));
+        assert!(ids.contains(&patch_id_of(remote)));
+        assert!(!ids.contains(&patch_id_of(old)));
+        assert!(!ids.contains(&patch_id_of(skewed)));
+        assert!(!ids.contains(&patch_id_of(merge)));
+        assert_eq!(ids.len(), 2);
+
+        assert_eq!(history_patch_ids(dir.path(), 0).unwrap().len(), 4);
+    }
+}
@@ -0,0 +1,223 @@
+use base64::Engine;
+use nostr::{Event, EventBuilder, JsonUtil, Kind, Tag, Timestamp};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::error::{Context, Result, bail, ensure};
+use crate::signer::{self, Signer};
+
+/// Kind for Blossom authorization events (BUD-01)
+pub const KIND_BLOSSOM_AUTH: Kind = Kind::Custom(24242);
+
+/// How long an upload authorization stays valid
+const AUTH_EXPIRATION_SECS: u64 = 300;
+
+/// Blob descriptor returned by Blossom servers (BUD-02)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobDescriptor {
+    pub url: String,
+    pub sha256: String,
+    pub size: u64,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// Hex encoded SHA-256 of a blob, which is also its Blossom address
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Build the signed authorization event for a Blossom action (e.g. "upload")
+pub async fn build_auth_event(
+    signer: &dyn Signer,
+    action: &str,
+    sha256: &str,
+    description: &str,
+) -> Result<Event> {
+    let expiration = Timestamp::now().as_u64() + AUTH_EXPIRATION_SECS;
+    let builder = EventBuilder::new(KIND_BLOSSOM_AUTH, description).tags([
+        Tag::parse(["t", action])?,
+        Tag::parse(["x", sha256])?,
+        Tag::parse(["expiration", &expiration.to_string()])?,
+    ]);
+    signer::sign(signer, builder).await
+}
+
+/// Authorization to upload `data`, valid on any server
+///
+/// Signed once per blob, so a remote signer is asked once however many
+/// servers get it.
+async fn upload_auth(signer: &dyn Signer, data: &[u8]) -> Result<Event> {
+    build_auth_event(signer, "upload", &sha256_hex(data), "Upload blob").await
+}
+
+fn http_client() -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .user_agent(concat!("gitsmith/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(60))
+        .build()?)
+}
+
+/// Upload a blob to a Blossom server
+pub async fn upload_blob(
+    server: &str,
+    signer: &dyn Signer,
+    data: Vec<u8>,
+    content_type: &str,
+) -> Result<BlobDescriptor> {
+    let auth = upload_auth(signer, &data).await?;
+    put_blob(server, &auth, data, content_type).await
+}
+
+/// Upload a blob to a Blossom server with an authorization from [`upload_auth`]
+async fn put_blob(
+    server: &str,
+    auth: &Event,
+    data: Vec<u8>,
+    content_type: &str,
+) -> Result<BlobDescriptor> {
+    let sha256 = sha256_hex(&data);
+    let auth_header = format!(
+        "Nostr {token}",
+        token = base64::engine::general_purpose::STANDARD.encode(auth.as_json())
+    );
+
+    let upload_url = format!("{server}/upload", server = server.trim_end_matches('/'));
+    debug!(%upload_url, %sha256, size = data.len(), "Uploading blob");
+    let response = http_client()?
+        .put(&upload_url)
+        .header("Authorization", auth_header)
+        .header("Content-Type", content_type)
+        .body(data)
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach {upload_url}"))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        // Servers explain rejections in the X-Reason header
+        let reason = response
+            .headers()
+            .get("X-Reason")
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        bail!("Blossom server {server} rejected upload ({status}): {reason}");
+    }
+
+    let descriptor: BlobDescriptor = response
+        .json()
+        .await
+        .with_context(|| format!("Invalid blob descriptor from {server}"))?;
+    ensure!(
+        descriptor.sha256 == sha256,
+        "Blossom server {server} stored a different blob ({stored} instead of {sha256})",
+        stored = descriptor.sha256
+    );
+
+    Ok(descriptor)
+}
+
+/// Upload a blob to the first server that accepts it
+pub async fn upload_to_any(
+    servers: &[String],
+    signer: &dyn Signer,
+    data: Vec<u8>,
+    content_type: &str,
+) -> Result<BlobDescriptor> {
+    ensure!(!servers.is_empty(), "No Blossom servers configured");
+
+    let auth = upload_auth(signer, &data).await?;
+    for server in servers {
+        match put_blob(server, &auth, data.clone(), content_type).await {
+            Ok(descriptor) => return Ok(descriptor),
+            Err(e) => warn!("Upload to {server} failed: {e:#}"),
+        }
+    }
+
+    bail!(
+        "Upload failed on all {count} Blossom server(s)",
+        count = servers.len()
+    )
+}
+
+/// Upload a blob to every server, keeping the descriptors of those that accepted it
+pub async fn upload_to_all(
+    servers: &[String],
+    signer: &dyn Signer,
+    data: Vec<u8>,
+    content_type: &str,
+) -> Result<Vec<BlobDescriptor>> {
+    ensure!(!servers.is_empty(), "No Blossom servers configured");
+
+    let auth = upload_auth(signer, &data).await?;
+    let mut descriptors = Vec::new();
+    for server in servers {
+        match put_blob(server, &auth, data.clone(), content_type).await {
+            Ok(descriptor) => descriptors.push(descriptor),
+            Err(e) => warn!("Upload to {server} failed: {e:#}"),
+        }
+    }
+    ensure!(
+        !descriptors.is_empty(),
+        "Upload failed on all {count} Blossom server(s)",
+        count = servers.len()
+    );
+
+    Ok(descriptors)
+}
+
+/// Download a blob and verify it matches its SHA-256 address
+pub async fn download_blob(url: &str, sha256: &str) -> Result<Vec<u8>> {
+    debug!(%url, "Downloading blob");
+    let response = http_client()?
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach {url}"))?;
+    let status = response.status();
+    ensure!(status.is_success(), "Download of {url} failed ({status})");
+
+    let data = response.bytes().await?.to_vec();
+    let actual = sha256_hex(&data);
+    ensure!(
+        actual == sha256,
+        "Blob at {url} does not match its hash (expected {sha256}, got {actual})"
+    );
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::Keys;
+
+    #[tokio::test]
+    async fn test_auth_event() {
+        let keys = Keys::generate();
+        let sha256 = sha256_hex(b"hello");
+        assert_eq!(
+            sha256,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+
+        let event = build_auth_event(&keys, "upload", &sha256, "Upload blob")
+            .await
+            .unwrap();
+        assert_eq!(event.kind, KIND_BLOSSOM_AUTH);
+        let tag = |name: &str| {
+            event
+                .tags
+                .iter()
+                .find(|t| t.as_slice()[0] == name)
+                .map(|t| t.as_slice()[1].clone())
+        };
+        assert_eq!(tag("t").as_deref(), Some("upload"));
+        assert_eq!(tag("x"), Some(sha256));
+        let expiration: u64 = tag("expiration").unwrap().parse().unwrap();
+        assert!(expiration > Timestamp::now().as_u64());
+    }
+}
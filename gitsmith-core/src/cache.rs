@@ -0,0 +1,657 @@
+//! Local cache of events fetched from relays
+//!
+//! Every backend implements [`CacheStore`]: SQLite is the default, LMDB is
+//! available with the `lmdb` feature and the in-memory store is meant for
+//! tests and short-lived processes.
+
+use nostr::filter::MatchEventOptions;
+use nostr::{Event, EventId, Filter, JsonUtil, Timestamp};
+use rusqlite::OptionalExtension;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use strum::{Display, EnumString};
+
+use crate::error::{Context, GitsmithError, Result};
+use crate::events::newest_first;
+
+/// Storage of cached events
+pub trait CacheStore: Send + Sync {
+    /// Store events, returns how many weren't cached yet
+    fn save_events(&self, events: &[Event]) -> Result<usize>;
+
+    /// Cached events matching any of the filters, newest first
+    fn query(&self, filters: &[Filter]) -> Result<Vec<Event>>;
+
+    /// Number of cached events
+    fn count(&self) -> Result<usize>;
+
+    /// When `key` was last synced completely, see [`crate::relay::fetch_events_synced`]
+    fn sync_mark(&self, key: &str) -> Result<Option<Timestamp>>;
+
+    /// Record that `key` was synced completely up to `at`
+    fn set_sync_mark(&self, key: &str, at: Timestamp) -> Result<()>;
+
+    /// Remove all cached events, and the sync marks that depend on them
+    fn clear(&self) -> Result<()>;
+}
+
+/// Available cache backends
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum CacheBackend {
+    #[default]
+    Sqlite,
+    /// Requires the `lmdb` feature
+    Lmdb,
+    Memory,
+}
+
+/// Events matching any of the filters, newest first with ties ordered by ID,
+/// each filter's `limit` applied to its own matches
+fn select<'a>(events: impl Iterator<Item = &'a Event> + Clone, filters: &[Filter]) -> Vec<Event> {
+    let mut seen = HashSet::new();
+    let mut selected = Vec::new();
+
+    for filter in filters {
+        let mut matches: Vec<&Event> = events
+            .clone()
+            .filter(|event| filter.match_event(event, MatchEventOptions::new()))
+            .collect();
+        matches.sort_by(|a, b| newest_first(a, b));
+        if let Some(limit) = filter.limit {
+            matches.truncate(limit);
+        }
+        for event in matches {
+            if seen.insert(event.id) {
+                selected.push(event.clone());
+            }
+        }
+    }
+
+    selected.sort_by(newest_first);
+    selected
+}
+
+/// Events kept in memory until the store is dropped
+#[derive(Default)]
+pub struct MemoryStore {
+    events: Mutex<BTreeMap<EventId, Event>>,
+    marks: Mutex<BTreeMap<String, Timestamp>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn events(&self) -> Result<std::sync::MutexGuard<'_, BTreeMap<EventId, Event>>> {
+        self.events
+            .lock()
+            .map_err(|_| GitsmithError::msg("Event cache lock poisoned"))
+    }
+
+    fn marks(&self) -> Result<std::sync::MutexGuard<'_, BTreeMap<String, Timestamp>>> {
+        self.marks
+            .lock()
+            .map_err(|_| GitsmithError::msg("Event cache lock poisoned"))
+    }
+}
+
+impl CacheStore for MemoryStore {
+    fn save_events(&self, events: &[Event]) -> Result<usize> {
+        let mut cached = self.events()?;
+        Ok(events
+            .iter()
+            .filter(|event| cached.insert(event.id, (*event).clone()).is_none())
+            .count())
+    }
+
+    fn query(&self, filters: &[Filter]) -> Result<Vec<Event>> {
+        Ok(select(self.events()?.values(), filters))
+    }
+
+    fn count(&self) -> Result<usize> {
+        Ok(self.events()?.len())
+    }
+
+    fn sync_mark(&self, key: &str) -> Result<Option<Timestamp>> {
+        Ok(self.marks()?.get(key).copied())
+    }
+
+    fn set_sync_mark(&self, key: &str, at: Timestamp) -> Result<()> {
+        self.marks()?.insert(key.to_string(), at);
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.events()?.clear();
+        self.marks()?.clear();
+        Ok(())
+    }
+}
+
+/// Events kept in a SQLite database
+pub struct SqliteStore {
+    connection: Mutex<rusqlite::Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create cache directory {parent:?}"))?;
+        }
+        let connection = rusqlite::Connection::open(path)
+            .with_context(|| format!("Failed to open event cache {path:?}"))?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS events (
+                id TEXT PRIMARY KEY,
+                pubkey TEXT NOT NULL,
+                kind INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                json TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS events_kind ON events (kind, created_at);
+            CREATE TABLE IF NOT EXISTS sync_marks (
+                key TEXT PRIMARY KEY,
+                synced_at INTEGER NOT NULL
+            );",
+        )?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn connection(&self) -> Result<std::sync::MutexGuard<'_, rusqlite::Connection>> {
+        self.connection
+            .lock()
+            .map_err(|_| GitsmithError::msg("Event cache lock poisoned"))
+    }
+
+    /// Cached events of the filter's kinds and authors, the rest of the
+    /// filter is checked by [`select`]
+    fn candidates(connection: &rusqlite::Connection, filter: &Filter) -> Result<Vec<Event>> {
+        let mut conditions = Vec::new();
+        let mut params: Vec<rusqlite::types::Value> = Vec::new();
+        if let Some(kinds) = &filter.kinds {
+            conditions.push(format!(
+                "kind IN ({placeholders})",
+                placeholders = vec!["?"; kinds.len()].join(", ")
+            ));
+            params.extend(kinds.iter().map(|kind| i64::from(kind.as_u16()).into()));
+        }
+        if let Some(authors) = &filter.authors {
+            conditions.push(format!(
+                "pubkey IN ({placeholders})",
+                placeholders = vec!["?"; authors.len()].join(", ")
+            ));
+            params.extend(authors.iter().map(|author| author.to_hex().into()));
+        }
+        let mut sql = "SELECT json FROM events".to_string();
+        if !conditions.is_empty() {
+            sql.push_str(&format!(
+                " WHERE {conditions}",
+                conditions = conditions.join(" AND ")
+            ));
+        }
+
+        let mut statement = connection.prepare(&sql)?;
+        let rows = statement.query_map(rusqlite::params_from_iter(params), |row| {
+            row.get::<_, String>(0)
+        })?;
+        let mut events = Vec::new();
+        for json in rows {
+            events.push(Event::from_json(json?)?);
+        }
+        Ok(events)
+    }
+}
+
+impl CacheStore for SqliteStore {
+    fn save_events(&self, events: &[Event]) -> Result<usize> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+        let mut saved = 0;
+        for event in events {
+            saved += transaction.execute(
+                "INSERT OR IGNORE INTO events (id, pubkey, kind, created_at, json)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![
+                    event.id.to_hex(),
+                    event.pubkey.to_hex(),
+                    i64::from(event.kind.as_u16()),
+                    event.created_at.as_u64() as i64,
+                    event.as_json(),
+                ],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(saved)
+    }
+
+    fn query(&self, filters: &[Filter]) -> Result<Vec<Event>> {
+        let connection = self.connection()?;
+        let mut events = Vec::new();
+        for filter in filters {
+            let candidates = Self::candidates(&connection, filter)?;
+            events.extend(select(candidates.iter(), std::slice::from_ref(filter)));
+        }
+        Ok(select(events.iter(), &[Filter::new()]))
+    }
+
+    fn count(&self) -> Result<usize> {
+        let count: i64 =
+            self.connection()?
+                .query_row("SELECT COUNT(*) FROM events", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    fn sync_mark(&self, key: &str) -> Result<Option<Timestamp>> {
+        let synced_at: Option<i64> = self
+            .connection()?
+            .query_row(
+                "SELECT synced_at FROM sync_marks WHERE key = ?1",
+                [key],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(synced_at.map(|at| Timestamp::from(at.max(0) as u64)))
+    }
+
+    fn set_sync_mark(&self, key: &str, at: Timestamp) -> Result<()> {
+        self.connection()?.execute(
+            "INSERT OR REPLACE INTO sync_marks (key, synced_at) VALUES (?1, ?2)",
+            rusqlite::params![key, at.as_u64() as i64],
+        )?;
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.connection()?
+            .execute_batch("DELETE FROM events; DELETE FROM sync_marks;")?;
+        Ok(())
+    }
+}
+
+/// Events kept in an LMDB environment, keyed by event ID
+///
+/// Index databases list the events of each kind and author, so queries for
+/// some kinds or authors only read their events.
+#[cfg(feature = "lmdb")]
+pub struct LmdbStore {
+    env: heed::Env,
+    events: heed::Database<heed::types::Str, heed::types::Str>,
+    /// `<kind>:<event ID>` of every event
+    kinds: heed::Database<heed::types::Str, heed::types::Unit>,
+    /// `<pubkey>:<event ID>` of every event
+    authors: heed::Database<heed::types::Str, heed::types::Unit>,
+    marks: heed::Database<heed::types::Str, heed::types::Str>,
+}
+
+#[cfg(feature = "lmdb")]
+impl LmdbStore {
+    /// Upper bound of the database size, LMDB reserves it as address space
+    const MAP_SIZE: usize = 1 << 30;
+
+    pub fn open(path: &Path) -> Result<Self> {
+        fs::create_dir_all(path)
+            .with_context(|| format!("Failed to create cache directory {path:?}"))?;
+        // SAFETY: the environment is opened once per process, through
+        // `open_cache`, and nothing else modifies the files while it is open
+        let env = unsafe {
+            heed::EnvOpenOptions::new()
+                .map_size(Self::MAP_SIZE)
+                .max_dbs(4)
+                .open(path)
+        }
+        .with_context(|| format!("Failed to open event cache {path:?}"))?;
+        let mut transaction = env.write_txn()?;
+        let events = env.create_database(&mut transaction, Some("events"))?;
+        let kinds = env.create_database(&mut transaction, Some("kinds"))?;
+        let authors = env.create_database(&mut transaction, Some("authors"))?;
+        let marks = env.create_database(&mut transaction, Some("sync_marks"))?;
+        transaction.commit()?;
+        let store = Self {
+            env,
+            events,
+            kinds,
+            authors,
+            marks,
+        };
+        store.reindex()?;
+        Ok(store)
+    }
+
+    /// Add `event` to the kind and author indexes
+    fn index(&self, transaction: &mut heed::RwTxn, event: &Event) -> Result<()> {
+        let id = event.id.to_hex();
+        self.kinds.put(
+            transaction,
+            &format!("{kind}:{id}", kind = event.kind.as_u16()),
+            &(),
+        )?;
+        self.authors.put(
+            transaction,
+            &format!("{author}:{id}", author = event.pubkey.to_hex()),
+            &(),
+        )?;
+        Ok(())
+    }
+
+    /// Index the events cached before the store had indexes
+    fn reindex(&self) -> Result<()> {
+        let mut transaction = self.env.write_txn()?;
+        if self.kinds.len(&transaction)? == self.events.len(&transaction)? {
+            return Ok(());
+        }
+        let mut events = Vec::new();
+        for entry in self.events.iter(&transaction)? {
+            let (_, json) = entry?;
+            events.push(Event::from_json(json)?);
+        }
+        self.kinds.clear(&mut transaction)?;
+        self.authors.clear(&mut transaction)?;
+        for event in &events {
+            self.index(&mut transaction, event)?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// IDs of the events listed under any of `prefixes` in `index`
+    fn indexed_ids(
+        index: &heed::Database<heed::types::Str, heed::types::Unit>,
+        transaction: &heed::RoTxn,
+        prefixes: impl Iterator<Item = String>,
+    ) -> Result<HashSet<String>> {
+        let mut ids = HashSet::new();
+        for prefix in prefixes {
+            for entry in index.prefix_iter(transaction, &prefix)? {
+                let (key, ()) = entry?;
+                ids.insert(key[prefix.len()..].to_string());
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Cached events of the filter's IDs, kinds and authors, the rest of the
+    /// filter is checked by [`select`]
+    fn candidates(&self, transaction: &heed::RoTxn, filter: &Filter) -> Result<Vec<Event>> {
+        let mut ids: Option<HashSet<String>> = filter
+            .ids
+            .as_ref()
+            .map(|ids| ids.iter().map(EventId::to_hex).collect());
+        let mut narrow = |found: HashSet<String>| {
+            ids = Some(match ids.take() {
+                Some(ids) => ids.intersection(&found).cloned().collect(),
+                None => found,
+            });
+        };
+        if let Some(kinds) = &filter.kinds {
+            narrow(Self::indexed_ids(
+                &self.kinds,
+                transaction,
+                kinds
+                    .iter()
+                    .map(|kind| format!("{kind}:", kind = kind.as_u16())),
+            )?);
+        }
+        if let Some(authors) = &filter.authors {
+            narrow(Self::indexed_ids(
+                &self.authors,
+                transaction,
+                authors
+                    .iter()
+                    .map(|author| format!("{author}:", author = author.to_hex())),
+            )?);
+        }
+
+        let mut events = Vec::new();
+        match ids {
+            Some(ids) => {
+                for id in ids {
+                    if let Some(json) = self.events.get(transaction, &id)? {
+                        events.push(Event::from_json(json)?);
+                    }
+                }
+            }
+            None => {
+                for entry in self.events.iter(transaction)? {
+                    let (_, json) = entry?;
+                    events.push(Event::from_json(json)?);
+                }
+            }
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(feature = "lmdb")]
+impl CacheStore for LmdbStore {
+    fn save_events(&self, events: &[Event]) -> Result<usize> {
+        let mut transaction = self.env.write_txn()?;
+        let mut saved = 0;
+        for event in events {
+            let id = event.id.to_hex();
+            if self.events.get(&transaction, &id)?.is_none() {
+                self.events.put(&mut transaction, &id, &event.as_json())?;
+                self.index(&mut transaction, event)?;
+                saved += 1;
+            }
+        }
+        transaction.commit()?;
+        Ok(saved)
+    }
+
+    fn query(&self, filters: &[Filter]) -> Result<Vec<Event>> {
+        let transaction = self.env.read_txn()?;
+        let mut events = Vec::new();
+        for filter in filters {
+            let candidates = self.candidates(&transaction, filter)?;
+            events.extend(select(candidates.iter(), std::slice::from_ref(filter)));
+        }
+        Ok(select(events.iter(), &[Filter::new()]))
+    }
+
+    fn count(&self) -> Result<usize> {
+        let transaction = self.env.read_txn()?;
+        Ok(self.events.len(&transaction)? as usize)
+    }
+
+    fn sync_mark(&self, key: &str) -> Result<Option<Timestamp>> {
+        let transaction = self.env.read_txn()?;
+        Ok(self
+            .marks
+            .get(&transaction, key)?
+            .and_then(|at| at.parse::<u64>().ok())
+            .map(Timestamp::from))
+    }
+
+    fn set_sync_mark(&self, key: &str, at: Timestamp) -> Result<()> {
+        let mut transaction = self.env.write_txn()?;
+        self.marks
+            .put(&mut transaction, key, &at.as_u64().to_string())?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        let mut transaction = self.env.write_txn()?;
+        self.events.clear(&mut transaction)?;
+        self.kinds.clear(&mut transaction)?;
+        self.authors.clear(&mut transaction)?;
+        self.marks.clear(&mut transaction)?;
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+/// Get the default path of a cache backend
+pub fn get_cache_path(backend: CacheBackend) -> Result<PathBuf> {
+    let home = dirs::home_dir().context("Failed to get home directory")?;
+    let directory = home.join(".cache").join("gitsmith");
+    Ok(match backend {
+        CacheBackend::Sqlite => directory.join("cache.sqlite"),
+        CacheBackend::Lmdb => directory.join("cache.lmdb"),
+        CacheBackend::Memory => PathBuf::new(),
+    })
+}
+
+/// Open a cache backend at its default location
+pub fn open_cache(backend: CacheBackend) -> Result<Arc<dyn CacheStore>> {
+    match backend {
+        CacheBackend::Sqlite => Ok(Arc::new(SqliteStore::open(&get_cache_path(backend)?)?)),
+        #[cfg(feature = "lmdb")]
+        CacheBackend::Lmdb => Ok(Arc::new(LmdbStore::open(&get_cache_path(backend)?)?)),
+        #[cfg(not(feature = "lmdb"))]
+        CacheBackend::Lmdb => Err(GitsmithError::msg(
+            "gitsmith was built without LMDB support, rebuild with `--features lmdb`",
+        )),
+        CacheBackend::Memory => Ok(Arc::new(MemoryStore::new())),
+    }
+}
+
+/// Events of the cache matching any of the filters, for offline use
+pub fn cached_events(cache: Option<&dyn CacheStore>, filters: &[Filter]) -> Result<Vec<Event>> {
+    let cache = cache
+        .ok_or_else(|| GitsmithError::msg("Offline mode needs the event cache, drop --no-cache"))?;
+    cache.query(filters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::{EventBuilder, Keys, Kind, Timestamp};
+
+    fn events(keys: &Keys) -> Vec<Event> {
+        (0..3)
+            .map(|i| {
+                EventBuilder::new(Kind::TextNote, format!("note {i}"))
+                    .custom_created_at(Timestamp::from(1_700_000_000 + i))
+                    .sign_with_keys(keys)
+                    .unwrap()
+            })
+            .chain([EventBuilder::new(Kind::Metadata, "{}")
+                .sign_with_keys(keys)
+                .unwrap()])
+            .collect()
+    }
+
+    fn check_store(store: &dyn CacheStore) {
+        let keys = Keys::generate();
+        let events = events(&keys);
+        assert_eq!(store.save_events(&events).unwrap(), 4);
+        assert_eq!(store.save_events(&events[..2]).unwrap(), 0);
+        assert_eq!(store.count().unwrap(), 4);
+
+        let notes = store
+            .query(&[Filter::new().author(keys.public_key()).kind(Kind::TextNote)])
+            .unwrap();
+        let contents: Vec<&str> = notes.iter().map(|event| event.content.as_str()).collect();
+        assert_eq!(contents, ["note 2", "note 1", "note 0"]);
+
+        let newest = store
+            .query(&[Filter::new().kind(Kind::TextNote).limit(1)])
+            .unwrap();
+        assert_eq!(newest.len(), 1);
+        assert_eq!(newest[0].content, "note 2");
+
+        let all = store
+            .query(&[
+                Filter::new().kind(Kind::TextNote),
+                Filter::new().kind(Kind::Metadata),
+            ])
+            .unwrap();
+        assert_eq!(all.len(), 4);
+        assert!(
+            store
+                .query(&[Filter::new().author(Keys::generate().public_key())])
+                .unwrap()
+                .is_empty()
+        );
+        let by_id = store
+            .query(&[Filter::new().id(events[0].id).kind(events[0].kind)])
+            .unwrap();
+        assert_eq!(by_id, events[..1]);
+
+        // Events created in the same second come out in ID order
+        let tied: Vec<Event> = (0..3)
+            .map(|i| {
+                EventBuilder::new(Kind::TextNote, format!("tie {i}"))
+                    .custom_created_at(Timestamp::from(1_800_000_000))
+                    .sign_with_keys(&keys)
+                    .unwrap()
+            })
+            .collect();
+        store.save_events(&tied).unwrap();
+        let newest = store
+            .query(&[Filter::new().kind(Kind::TextNote).limit(3)])
+            .unwrap();
+        let mut expected: Vec<EventId> = tied.iter().map(|event| event.id).collect();
+        expected.sort();
+        assert_eq!(
+            newest.iter().map(|event| event.id).collect::<Vec<_>>(),
+            expected
+        );
+
+        assert_eq!(store.sync_mark("wss://relay.example|notes").unwrap(), None);
+        store
+            .set_sync_mark("wss://relay.example|notes", Timestamp::from(1_700_000_000))
+            .unwrap();
+        store
+            .set_sync_mark("wss://relay.example|notes", Timestamp::from(1_800_000_000))
+            .unwrap();
+        assert_eq!(
+            store.sync_mark("wss://relay.example|notes").unwrap(),
+            Some(Timestamp::from(1_800_000_000))
+        );
+
+        store.clear().unwrap();
+        assert_eq!(store.count().unwrap(), 0);
+        assert_eq!(store.sync_mark("wss://relay.example|notes").unwrap(), None);
+    }
+
+    #[test]
+    fn test_memory_store() {
+        check_store(&MemoryStore::new());
+    }
+
+    #[test]
+    fn test_sqlite_store() {
+        let dir = tempfile::tempdir().unwrap();
+        check_store(&SqliteStore::open(&dir.path().join("cache.sqlite")).unwrap());
+    }
+
+    #[cfg(feature = "lmdb")]
+    #[test]
+    fn test_lmdb_store() {
+        let dir = tempfile::tempdir().unwrap();
+        check_store(&LmdbStore::open(&dir.path().join("cache.lmdb")).unwrap());
+    }
+
+    #[cfg(feature = "lmdb")]
+    #[test]
+    fn test_lmdb_store_indexes_older_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.lmdb");
+        let keys = Keys::generate();
+        let events = events(&keys);
+        {
+            let store = LmdbStore::open(&path).unwrap();
+            store.save_events(&events).unwrap();
+            // Caches written before the indexes existed only have events
+            let mut transaction = store.env.write_txn().unwrap();
+            store.kinds.clear(&mut transaction).unwrap();
+            store.authors.clear(&mut transaction).unwrap();
+            transaction.commit().unwrap();
+        }
+
+        let store = LmdbStore::open(&path).unwrap();
+        let notes = store
+            .query(&[Filter::new().author(keys.public_key()).kind(Kind::TextNote)])
+            .unwrap();
+        assert_eq!(notes.len(), 3);
+    }
+}
@@ -0,0 +1,527 @@
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use nostr::{Event, EventBuilder, EventId, Filter, Kind, PublicKey, Tag, ToBech32};
+use nostr_sdk::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::time::Duration;
+use strum::{Display, EnumString};
+use tracing::info;
+
+use crate::blossom::{self, BlobDescriptor};
+use crate::comments;
+use crate::error::{Context, GitsmithError, Result, ensure};
+use crate::relay::{self, Network};
+use crate::signer::{self, Signer};
+use crate::types::BroadcastResult;
+use crate::watch;
+
+/// Outcome reported by a CI check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display, EnumString)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum CheckState {
+    Pending,
+    Success,
+    Failure,
+    Error,
+}
+
+/// Reference to a gzip compressed build log stored on a Blossom server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckLog {
+    pub url: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+/// A CI check reported on a PR
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckResult {
+    pub id: String,
+    pub pr_id: String,
+    pub name: String,
+    pub state: CheckState,
+    pub author: String,
+    pub created_at: u64,
+    pub description: String,
+    pub log: Option<CheckLog>,
+    /// Page with the details of the run, e.g. its log on the CI system
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+/// Latest state of a check as reported by one reporter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportedCheck {
+    pub name: String,
+    /// Public key (hex) of the reporter
+    pub reporter: String,
+    pub state: CheckState,
+}
+
+/// Latest state of each check reported on a PR by a trusted reporter
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckSummary {
+    /// Checks ordered by name and reporter
+    pub checks: Vec<ReportedCheck>,
+}
+
+impl CheckSummary {
+    pub fn is_empty(&self) -> bool {
+        self.checks.is_empty()
+    }
+
+    /// State of the check `name` as reported by `reporter` (hex)
+    pub fn state_of(&self, name: &str, reporter: &str) -> Option<CheckState> {
+        self.checks
+            .iter()
+            .find(|check| check.name == name && check.reporter == reporter)
+            .map(|check| check.state)
+    }
+
+    /// Overall state: failures outweigh errors, which outweigh pending checks
+    pub fn state(&self) -> Option<CheckState> {
+        [
+            CheckState::Failure,
+            CheckState::Error,
+            CheckState::Pending,
+            CheckState::Success,
+        ]
+        .into_iter()
+        .find(|state| self.checks.iter().any(|check| check.state == *state))
+    }
+}
+
+/// What to report when attaching a build log
+#[derive(Debug, Clone)]
+pub struct AttachLogOptions {
+    /// Check name, e.g. "build" or "test"
+    pub name: String,
+    pub state: CheckState,
+    /// Uncompressed log contents
+    pub log: Vec<u8>,
+    /// Blossom servers to try, in order
+    pub servers: Vec<String>,
+    pub description: String,
+}
+
+/// What to report with [`report_check`]
+#[derive(Debug, Clone)]
+pub struct ReportOptions {
+    /// Check name, e.g. "build" or "test"
+    pub name: String,
+    pub state: CheckState,
+    /// Page with the details of the run
+    pub url: Option<String>,
+    pub description: String,
+}
+
+/// Build a check event for a PR
+///
+/// Checks are NIP-22 comments on the PR carrying a `check` tag (name and state)
+/// and, when a log was uploaded, a `log` tag (url, sha256 and size of the blob).
+pub async fn build_check_event(
+    signer: &dyn Signer,
+    pr: &Event,
+    repo_coordinate: &str,
+    name: &str,
+    state: CheckState,
+    log: Option<&BlobDescriptor>,
+    description: &str,
+) -> Result<Event> {
+    let mut builder = check_builder(pr, repo_coordinate, name, state, description)?;
+    if let Some(log) = log {
+        builder = builder.tag(Tag::parse([
+            "log",
+            &log.url,
+            &log.sha256,
+            &log.size.to_string(),
+        ])?);
+    }
+
+    signer::sign(signer, builder).await
+}
+
+/// Start a check event for a PR, returned unsigned so callers can attach a
+/// log or details URL
+pub fn check_builder(
+    pr: &Event,
+    repo_coordinate: &str,
+    name: &str,
+    state: CheckState,
+    description: &str,
+) -> Result<EventBuilder> {
+    let content = if description.is_empty() {
+        format!("CI check {name}: {state}")
+    } else {
+        format!("CI check {name}: {state}\n\n{description}")
+    };
+
+    let tags = vec![
+        Tag::parse(["check", name, &state.to_string()])?,
+        Tag::parse(["alt", &format!("CI check {name}: {state}")])?,
+    ];
+
+    Ok(comments::comment_builder(pr, None, &content, Some(repo_coordinate))?.tags(tags))
+}
+
+/// Convert a check event to a CheckResult, None if the event isn't a check
+pub fn parse_check_event(event: &Event) -> Option<CheckResult> {
+    let tags: Vec<&[String]> = event.tags.iter().map(|tag| tag.as_slice()).collect();
+
+    let check = tags.iter().find(|tag| tag.len() > 2 && tag[0] == "check")?;
+    let state = check[2].parse().ok()?;
+    let pr_id = tags
+        .iter()
+        .find(|tag| tag.len() > 1 && tag[0] == "E")
+        .map(|tag| tag[1].clone())?;
+    let log = tags
+        .iter()
+        .find(|tag| tag.len() > 3 && tag[0] == "log")
+        .and_then(|tag| {
+            Some(CheckLog {
+                url: tag[1].clone(),
+                sha256: tag[2].clone(),
+                size: tag[3].parse().ok()?,
+            })
+        });
+    let url = tags
+        .iter()
+        .find(|tag| tag.len() > 1 && tag[0] == "r")
+        .map(|tag| tag[1].clone());
+    let description = event
+        .content
+        .split_once("\n\n")
+        .map(|(_, description)| description.to_string())
+        .unwrap_or_default();
+
+    Some(CheckResult {
+        id: event.id.to_hex(),
+        pr_id,
+        name: check[1].clone(),
+        state,
+        author: event.pubkey.to_hex(),
+        created_at: event.created_at.as_u64(),
+        description,
+        log,
+        url,
+    })
+}
+
+/// Summarize the checks on the PR `pr_id` among `events`, keeping the latest
+/// report of each check by each reporter
+///
+/// Only reports by `reporters` count, anyone else (e.g. the PR author) could
+/// otherwise cover a failure with a later success.
+pub fn summarize_checks(events: &[Event], pr_id: &str, reporters: &[PublicKey]) -> CheckSummary {
+    let mut latest: BTreeMap<(String, String), (u64, EventId, CheckState)> = BTreeMap::new();
+    for event in events {
+        if !reporters.contains(&event.pubkey) {
+            continue;
+        }
+        let Some(check) = parse_check_event(event) else {
+            continue;
+        };
+        if check.pr_id != pr_id {
+            continue;
+        }
+        let report = (event.created_at.as_u64(), event.id, check.state);
+        let entry = latest.entry((check.name, check.author)).or_insert(report);
+        // Ties are broken by event ID for deterministic results
+        if (report.0, report.1) > (entry.0, entry.1) {
+            *entry = report;
+        }
+    }
+
+    CheckSummary {
+        checks: latest
+            .into_iter()
+            .map(|((name, reporter), (_, _, state))| ReportedCheck {
+                name,
+                reporter,
+                state,
+            })
+            .collect(),
+    }
+}
+
+/// Format a check summary for display (e.g. "✅ build (npub1abcd…wxyz), ❌ test (…)")
+pub fn format_checks(summary: &CheckSummary) -> String {
+    summary
+        .checks
+        .iter()
+        .map(|check| {
+            let icon = match check.state {
+                CheckState::Success => "✅",
+                CheckState::Failure => "❌",
+                CheckState::Error => "⚠️",
+                CheckState::Pending => "⏳",
+            };
+            let reporter = PublicKey::from_hex(&check.reporter)
+                .ok()
+                .and_then(|key| key.to_bech32().ok())
+                .unwrap_or_else(|| check.reporter.clone());
+            format!(
+                "{icon} {name} ({reporter})",
+                name = check.name,
+                reporter = watch::short_npub(&reporter)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Gzip compress a build log
+pub fn compress_log(log: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(log)?;
+    Ok(encoder.finish()?)
+}
+
+/// Decompress a gzip compressed build log
+pub fn decompress_log(data: &[u8]) -> Result<String> {
+    let mut log = String::new();
+    GzDecoder::new(data)
+        .read_to_string(&mut log)
+        .context("Build log is not valid gzip compressed text")?;
+    Ok(log)
+}
+
+/// Upload a compressed build log and publish a check referencing it on the PR
+pub async fn attach_log(
+    network: &Network,
+    signer: &dyn Signer,
+    pr_event_id: &str,
+    repo_coordinate: &str,
+    relays: &[String],
+    options: &AttachLogOptions,
+) -> Result<BroadcastResult> {
+    let pr_id = EventId::from_hex(pr_event_id)
+        .with_context(|| format!("Invalid event ID: {pr_event_id}"))?;
+    ensure!(
+        !options.name.trim().is_empty(),
+        "Check name cannot be empty"
+    );
+
+    let client = relay::connect(network, relays, Some(signer)).await?;
+    let pr = fetch_pr(network, &client, pr_id).await?;
+
+    let compressed = compress_log(&options.log)?;
+    let blob =
+        blossom::upload_to_any(&options.servers, signer, compressed, "application/gzip").await?;
+    info!(url = %blob.url, size = blob.size, "Uploaded build log");
+
+    let event = build_check_event(
+        signer,
+        &pr,
+        repo_coordinate,
+        &options.name,
+        options.state,
+        Some(&blob),
+        &options.description,
+    )
+    .await?;
+
+    relay::publish_events(&client, &[event]).await
+}
+
+/// Publish a check on a PR, linking to the run's details instead of uploading a log
+pub async fn report_check(
+    network: &Network,
+    signer: &dyn Signer,
+    pr_event_id: &str,
+    repo_coordinate: &str,
+    relays: &[String],
+    options: &ReportOptions,
+) -> Result<BroadcastResult> {
+    let pr_id = EventId::from_hex(pr_event_id)
+        .with_context(|| format!("Invalid event ID: {pr_event_id}"))?;
+    ensure!(
+        !options.name.trim().is_empty(),
+        "Check name cannot be empty"
+    );
+
+    let client = relay::connect(network, relays, Some(signer)).await?;
+    let pr = fetch_pr(network, &client, pr_id).await?;
+
+    let mut builder = check_builder(
+        &pr,
+        repo_coordinate,
+        &options.name,
+        options.state,
+        &options.description,
+    )?;
+    if let Some(url) = &options.url {
+        builder = builder.tag(Tag::parse(["r", url])?);
+    }
+    let event = signer::sign(signer, builder).await?;
+
+    relay::publish_events(&client, &[event]).await
+}
+
+/// Fetch the PR a check is reported on
+async fn fetch_pr(network: &Network, client: &Client, pr_id: EventId) -> Result<Event> {
+    let found = relay::fetch_events(
+        network,
+        client,
+        vec![Filter::new().id(pr_id)],
+        Duration::from_secs(5),
+    )
+    .await?;
+    found
+        .into_iter()
+        .next()
+        .ok_or_else(|| GitsmithError::EventNotFound(format!("PR {pr_id}")))
+}
+
+/// Fetch the CI checks reported on a PR, newest first
+pub async fn fetch_checks(
+    network: &Network,
+    pr_event_id: &str,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<Vec<CheckResult>> {
+    let pr_id = EventId::from_hex(pr_event_id)
+        .with_context(|| format!("Invalid event ID: {pr_event_id}"))?;
+    let client = relay::connect(network, relays, None).await?;
+
+    let filter = Filter::new().kind(Kind::Comment).event(pr_id);
+    let events = relay::fetch_events(network, &client, vec![filter], timeout).await?;
+
+    let mut checks: Vec<CheckResult> = events.iter().filter_map(parse_check_event).collect();
+    checks.sort_by_key(|check| std::cmp::Reverse(check.created_at));
+    Ok(checks)
+}
+
+/// Download, verify and decompress a check's build log
+pub async fn download_log(log: &CheckLog) -> Result<String> {
+    let data = blossom::download_blob(&log.url, &log.sha256).await?;
+    decompress_log(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::{EventBuilder, Keys};
+
+    #[tokio::test]
+    async fn test_check_event_round_trip() {
+        let keys = Keys::generate();
+        let pr = EventBuilder::new(Kind::Custom(1618), "pr")
+            .sign_with_keys(&keys)
+            .unwrap();
+        let blob = BlobDescriptor {
+            url: "https://blossom.example.com/abc".to_string(),
+            sha256: "abc".to_string(),
+            size: 42,
+            mime_type: Some("application/gzip".to_string()),
+        };
+
+        let event = build_check_event(
+            &keys,
+            &pr,
+            "30617:abc:repo",
+            "build",
+            CheckState::Failure,
+            Some(&blob),
+            "2 tests failed",
+        )
+        .await
+        .unwrap();
+        assert_eq!(event.kind, Kind::Comment);
+
+        let check = parse_check_event(&event).unwrap();
+        assert_eq!(check.pr_id, pr.id.to_hex());
+        assert_eq!(check.name, "build");
+        assert_eq!(check.state, CheckState::Failure);
+        assert_eq!(check.description, "2 tests failed");
+        assert_eq!(
+            check.log,
+            Some(CheckLog {
+                url: blob.url,
+                sha256: blob.sha256,
+                size: 42,
+            })
+        );
+
+        // Plain comments are not checks
+        let comment = comments::build_comment_event(&keys, &pr, None, "LGTM", None)
+            .await
+            .unwrap();
+        assert!(parse_check_event(&comment).is_none());
+    }
+
+    #[test]
+    fn test_summarize_checks() {
+        let keys = Keys::generate();
+        let pr = EventBuilder::new(Kind::Custom(1618), "pr")
+            .sign_with_keys(&keys)
+            .unwrap();
+        let report = |name: &str, state: CheckState, created_at: u64| {
+            check_builder(&pr, "30617:abc:repo", name, state, "")
+                .unwrap()
+                .tag(Tag::parse(["r", "https://ci.example.com/run/1"]).unwrap())
+                .custom_created_at(nostr::Timestamp::from(created_at))
+                .sign_with_keys(&keys)
+                .unwrap()
+        };
+        let mut events = vec![
+            report("build", CheckState::Failure, 100),
+            report("build", CheckState::Success, 200),
+            report("test", CheckState::Pending, 150),
+        ];
+
+        let check = parse_check_event(&events[0]).unwrap();
+        assert_eq!(check.url.as_deref(), Some("https://ci.example.com/run/1"));
+        assert!(check.log.is_none());
+
+        let ci = keys.public_key();
+        let summary = summarize_checks(&events, &pr.id.to_hex(), &[ci]);
+        assert_eq!(
+            summary.state_of("build", &ci.to_hex()),
+            Some(CheckState::Success)
+        );
+        assert_eq!(summary.state(), Some(CheckState::Pending));
+        let short = watch::short_npub(&ci.to_bech32().unwrap());
+        assert_eq!(
+            format_checks(&summary),
+            format!("✅ build ({short}), ⏳ test ({short})")
+        );
+        assert!(summarize_checks(&events, &"0".repeat(64), &[ci]).is_empty());
+
+        // A later success by someone else neither hides the failure nor counts
+        let author = Keys::generate();
+        events.push(report("test", CheckState::Failure, 300));
+        events.push(
+            check_builder(&pr, "30617:abc:repo", "test", CheckState::Success, "")
+                .unwrap()
+                .custom_created_at(nostr::Timestamp::from(400))
+                .sign_with_keys(&author)
+                .unwrap(),
+        );
+        let summary = summarize_checks(&events, &pr.id.to_hex(), &[ci]);
+        assert_eq!(summary.state(), Some(CheckState::Failure));
+        assert_eq!(summary.checks.len(), 2);
+
+        // Reports of the same check by different trusted reporters are kept apart
+        let summary = summarize_checks(&events, &pr.id.to_hex(), &[ci, author.public_key()]);
+        assert_eq!(summary.checks.len(), 3);
+        assert_eq!(
+            summary.state_of("test", &author.public_key().to_hex()),
+            Some(CheckState::Success)
+        );
+        assert_eq!(summary.state(), Some(CheckState::Failure));
+    }
+
+    #[test]
+    fn test_log_compression() {
+        let log = "error[E0308]: mismatched types\n".repeat(100);
+        let compressed = compress_log(log.as_bytes()).unwrap();
+        assert!(compressed.len() < log.len());
+        assert_eq!(decompress_log(&compressed).unwrap(), log);
+        assert!(decompress_log(b"not gzip").is_err());
+    }
+}
@@ -0,0 +1,600 @@
+use nostr::{Alphabet, Event, EventBuilder, EventId, Filter, Kind, SingleLetterTag, Tag};
+use nostr_sdk::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use crate::error::{Context, GitsmithError, Result, ensure};
+use crate::relay::{self, Network};
+use crate::signer::{self, Signer};
+use crate::time::{TimeFormat, format_timestamp};
+use crate::types::BroadcastResult;
+
+/// A comment on a PR, patch or issue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comment {
+    pub id: String,
+    pub author: String,
+    pub created_at: u64,
+    pub content: String,
+    /// Event being replied to (the PR/issue itself for top-level comments)
+    pub parent_id: Option<String>,
+    /// Place in the PR's diff an inline comment is about
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<CodeLocation>,
+}
+
+/// Place in a PR's diff an inline comment is anchored to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodeLocation {
+    /// Path of the file after the change
+    pub path: String,
+    /// Line in the file after the change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    /// Header of the hunk holding the line (e.g. `@@ -10,6 +10,8 @@`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hunk: Option<String>,
+}
+
+impl CodeLocation {
+    /// `file`, `line` and `hunk` tags of an inline comment
+    pub fn tags(&self) -> Result<Vec<Tag>> {
+        let mut tags = vec![Tag::parse(["file", &self.path])?];
+        if let Some(line) = self.line {
+            tags.push(Tag::parse(["line", &line.to_string()])?);
+        }
+        if let Some(hunk) = &self.hunk {
+            tags.push(Tag::parse(["hunk", hunk])?);
+        }
+        Ok(tags)
+    }
+
+    /// Location from the tags of an inline comment, None for other comments
+    pub fn from_event(event: &Event) -> Option<Self> {
+        let value = |name: &str| {
+            event.tags.iter().find_map(|tag| {
+                let tag = tag.as_slice();
+                (tag.len() > 1 && tag[0] == name).then(|| tag[1].clone())
+            })
+        };
+        Some(Self {
+            path: value("file")?,
+            line: value("line").and_then(|line| line.parse().ok()),
+            hunk: value("hunk"),
+        })
+    }
+}
+
+impl std::fmt::Display for CodeLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{path}:{line}", path = self.path),
+            None => write!(f, "{path}", path = self.path),
+        }
+    }
+}
+
+/// Start a NIP-22 comment (kind 1111) anchored to `root`
+///
+/// `parent` is the comment being replied to; top-level comments use the root as
+/// parent. The builder is returned so callers can attach extra tags.
+pub fn comment_builder(
+    root: &Event,
+    parent: Option<&Event>,
+    content: &str,
+    repo_coordinate: Option<&str>,
+) -> Result<EventBuilder> {
+    let parent = parent.unwrap_or(root);
+    let root_id = root.id.to_hex();
+    let root_kind = root.kind.as_u16().to_string();
+    let root_author = root.pubkey.to_hex();
+    let parent_id = parent.id.to_hex();
+    let parent_kind = parent.kind.as_u16().to_string();
+    let parent_author = parent.pubkey.to_hex();
+
+    let mut tags = vec![
+        Tag::parse(["E", &root_id, "", &root_author])?,
+        Tag::parse(["K", &root_kind])?,
+        Tag::parse(["P", &root_author])?,
+        Tag::parse(["e", &parent_id, "", &parent_author])?,
+        Tag::parse(["k", &parent_kind])?,
+        Tag::parse(["p", &parent_author])?,
+    ];
+    if let Some(coordinate) = repo_coordinate {
+        tags.push(Tag::parse(["a", coordinate])?);
+    }
+
+    Ok(EventBuilder::new(Kind::Comment, content).tags(tags))
+}
+
+/// Build and sign a comment on `root`, optionally replying to another comment
+pub async fn build_comment_event(
+    signer: &dyn Signer,
+    root: &Event,
+    parent: Option<&Event>,
+    content: &str,
+    repo_coordinate: Option<&str>,
+) -> Result<Event> {
+    ensure!(!content.trim().is_empty(), "Comment cannot be empty");
+
+    let builder = comment_builder(root, parent, content, repo_coordinate)?;
+    signer::sign(signer, builder).await
+}
+
+/// Convert a comment event (NIP-22, or a NIP-10 text note reply) to a Comment
+pub fn parse_comment_event(event: &Event) -> Comment {
+    // NIP-22 puts the parent in the lowercase `e` tag; NIP-10 marks it as "reply"
+    // and falls back to the last `e` tag
+    let e_tags: Vec<&[String]> = event
+        .tags
+        .iter()
+        .map(|tag| tag.as_slice())
+        .filter(|tag| tag.len() > 1 && tag[0] == "e")
+        .collect();
+    let parent_id = e_tags
+        .iter()
+        .find(|tag| tag.get(3).is_some_and(|marker| marker == "reply"))
+        .or_else(|| e_tags.last())
+        .map(|tag| tag[1].clone());
+
+    Comment {
+        id: event.id.to_hex(),
+        author: event.pubkey.to_hex(),
+        created_at: event.created_at.as_u64(),
+        content: event.content.clone(),
+        parent_id,
+        location: CodeLocation::from_event(event),
+    }
+}
+
+/// Order comments as a thread: replies follow their parent, siblings oldest first
+///
+/// Returns each comment with its nesting depth (0 for replies to the root).
+pub fn thread(comments: &[Comment]) -> Vec<(usize, &Comment)> {
+    let known: HashMap<&str, &Comment> = comments.iter().map(|c| (c.id.as_str(), c)).collect();
+    let mut children: HashMap<Option<&str>, Vec<&Comment>> = HashMap::new();
+    for comment in comments {
+        // Comments whose parent isn't in the set are shown at the top level
+        let parent = comment
+            .parent_id
+            .as_deref()
+            .filter(|id| known.contains_key(id));
+        children.entry(parent).or_default().push(comment);
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+    }
+
+    let mut ordered = Vec::new();
+    let mut stack: Vec<(usize, &Comment)> = children
+        .get(&None)
+        .map(|top| top.iter().rev().map(|c| (0, *c)).collect())
+        .unwrap_or_default();
+    while let Some((depth, comment)) = stack.pop() {
+        ordered.push((depth, comment));
+        if let Some(replies) = children.get(&Some(comment.id.as_str())) {
+            stack.extend(replies.iter().rev().map(|c| (depth + 1, *c)));
+        }
+    }
+
+    ordered
+}
+
+/// Format a comment thread for display
+pub fn format_thread(comments: &[Comment], time: &TimeFormat) -> String {
+    let mut output = String::new();
+
+    for (depth, comment) in thread(comments) {
+        let indent = "  ".repeat(depth);
+        output.push_str(&format_comment(&indent, comment, time));
+    }
+
+    output
+}
+
+/// A comment's header line and indented content
+fn format_comment(indent: &str, comment: &Comment, time: &TimeFormat) -> String {
+    let mut output = format!(
+        "{indent}💬 {author}... ({created_at})",
+        author = &comment.author[..16.min(comment.author.len())],
+        created_at = format_timestamp(time, comment.created_at)
+    );
+    if let Some(location) = &comment.location {
+        output.push_str(&format!(" on {location}"));
+    }
+    output.push('\n');
+    for line in comment.content.lines() {
+        output.push_str(&format!("{indent}   {line}\n"));
+    }
+    output
+}
+
+/// Header of the hunk of `diff` changing `line` of `path`, counted in the file
+/// after the change
+pub fn find_hunk(diff: &str, path: &str, line: u32) -> Option<String> {
+    let mut current_path = None;
+    for diff_line in diff.lines() {
+        if let Some(paths) = diff_line.strip_prefix("diff --git ") {
+            current_path = paths.rsplit_once(" b/").map(|(_, new)| new);
+        } else if diff_line.starts_with("@@") && current_path == Some(path) {
+            let in_hunk = hunk_range(diff_line, '+')
+                .is_some_and(|(start, count)| (start..start + count).contains(&line));
+            if in_hunk {
+                return Some(hunk_header(diff_line).to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Start and length of the old (`-`) or new (`+`) side of a hunk header
+/// (`@@ -a,b +c,d @@`)
+fn hunk_range(header: &str, side: char) -> Option<(u32, u32)> {
+    let range = header
+        .split_whitespace()
+        .skip(1)
+        .find_map(|range| range.strip_prefix(side))?;
+    match range.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+/// A hunk header without the function context git appends after it
+fn hunk_header(line: &str) -> &str {
+    line.get(2..)
+        .and_then(|rest| rest.find("@@"))
+        .map_or(line, |end| &line[..end + 4])
+}
+
+/// Print a patch with its inline comments, and their replies, under the hunks
+/// they are anchored to
+///
+/// Comments whose ID is in `shown` are skipped, and the ones printed are added,
+/// so a comment shows once across the patches of a series.
+pub fn annotate_patch(
+    patch: &str,
+    comments: &[Comment],
+    shown: &mut HashSet<String>,
+    time: &TimeFormat,
+) -> String {
+    let threaded = thread(comments);
+    let mut output = String::new();
+    let mut path: Option<&str> = None;
+    // Header, new range and the old and new lines still to come of the current hunk
+    let mut hunk: Option<(&str, (u32, u32), u32, u32)> = None;
+
+    let mut flush = |output: &mut String, path: &str, anchor: Option<(&str, (u32, u32))>| {
+        for (i, (depth, comment)) in threaded.iter().enumerate() {
+            let Some(location) = comment.location.as_ref().filter(|l| l.path == path) else {
+                continue;
+            };
+            let matches = match (anchor, location.line) {
+                (None, None) => location.hunk.is_none(),
+                (Some((_, (start, count))), Some(line)) => (start..start + count).contains(&line),
+                (Some((header, _)), None) => location.hunk.as_deref() == Some(header),
+                (None, Some(_)) => false,
+            };
+            if !matches || !shown.insert(comment.id.clone()) {
+                continue;
+            }
+            output.push_str(&format_comment("    ", comment, time));
+            for (reply_depth, reply) in threaded[i + 1..]
+                .iter()
+                .take_while(|(reply_depth, _)| reply_depth > depth)
+            {
+                shown.insert(reply.id.clone());
+                output.push_str(&format_comment(&"  ".repeat(reply_depth + 2), reply, time));
+            }
+        }
+    };
+
+    for line in patch.lines() {
+        output.push_str(line);
+        output.push('\n');
+
+        if let Some((header, range, old_left, new_left)) = hunk.as_mut() {
+            match line.chars().next() {
+                Some('+') => *new_left = new_left.saturating_sub(1),
+                Some('-') => *old_left = old_left.saturating_sub(1),
+                Some('\\') => {}
+                _ => {
+                    *old_left = old_left.saturating_sub(1);
+                    *new_left = new_left.saturating_sub(1);
+                }
+            }
+            if *old_left == 0 && *new_left == 0 {
+                let anchor = (*header, *range);
+                hunk = None;
+                if let Some(path) = path {
+                    flush(&mut output, path, Some(anchor));
+                }
+            }
+            continue;
+        }
+
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            path = paths.rsplit_once(" b/").map(|(_, new)| new);
+        } else if line.starts_with("+++ ") {
+            if let Some(path) = path {
+                flush(&mut output, path, None);
+            }
+        } else if line.starts_with("@@") {
+            hunk = hunk_range(line, '-')
+                .zip(hunk_range(line, '+'))
+                .map(|((_, old_count), range)| (hunk_header(line), range, old_count, range.1));
+        }
+    }
+
+    output
+}
+
+/// Fetch all comments in the thread rooted at `root_id`
+pub async fn fetch_comments(
+    network: &Network,
+    root_id: &EventId,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<Vec<Comment>> {
+    let client = relay::connect(network, relays, None).await?;
+    let events = fetch_comment_events(network, &client, root_id, timeout).await?;
+    Ok(events.iter().map(parse_comment_event).collect())
+}
+
+/// Fetch the comment events in the thread rooted at `root_id` through a connected client
+pub async fn fetch_comment_events(
+    network: &Network,
+    client: &Client,
+    root_id: &EventId,
+    timeout: Duration,
+) -> Result<Vec<Event>> {
+    // Nested NIP-22 comments only reference the root through the uppercase `E` tag
+    let rooted = Filter::new()
+        .kind(Kind::Comment)
+        .custom_tag(SingleLetterTag::uppercase(Alphabet::E), root_id.to_hex());
+    let direct = Filter::new()
+        .kinds([Kind::Comment, Kind::TextNote])
+        .event(*root_id);
+
+    relay::fetch_events(network, client, vec![rooted, direct], timeout).await
+}
+
+/// Publish a comment on a PR, patch or issue, or a reply to an existing comment
+///
+/// A `location` anchors the comment to a file and line of the PR's diff.
+pub async fn post_comment(
+    network: &Network,
+    signer: &dyn Signer,
+    target_event_id: &str,
+    content: &str,
+    location: Option<&CodeLocation>,
+    repo_coordinate: Option<&str>,
+    relays: &[String],
+) -> Result<BroadcastResult> {
+    ensure!(!content.trim().is_empty(), "Comment cannot be empty");
+    let target_id = EventId::from_hex(target_event_id)
+        .with_context(|| format!("Invalid event ID: {target_event_id}"))?;
+    let timeout = Duration::from_secs(5);
+    let client = relay::connect(network, relays, Some(signer)).await?;
+
+    let found =
+        relay::fetch_events(network, &client, vec![Filter::new().id(target_id)], timeout).await?;
+    let Some(target) = found.into_iter().next() else {
+        return Err(GitsmithError::EventNotFound(format!(
+            "Event {target_event_id}"
+        )));
+    };
+
+    let mut builder = if target.kind == Kind::Comment {
+        // Replying to a comment: keep the thread anchored to its root
+        let root_id = target
+            .tags
+            .iter()
+            .map(|tag| tag.as_slice())
+            .find(|tag| tag.len() > 1 && tag[0] == "E")
+            .and_then(|tag| EventId::from_hex(&tag[1]).ok())
+            .context("Comment does not reference a root event")?;
+        let found =
+            relay::fetch_events(network, &client, vec![Filter::new().id(root_id)], timeout).await?;
+        let Some(root) = found.first() else {
+            return Err(GitsmithError::EventNotFound(format!(
+                "Root event {root_id}"
+            )));
+        };
+        comment_builder(root, Some(&target), content, repo_coordinate)?
+    } else {
+        comment_builder(&target, None, content, repo_coordinate)?
+    };
+    if let Some(location) = location {
+        builder = builder.tags(location.tags()?);
+    }
+    let event = signer::sign(signer, builder).await?;
+
+    relay::publish_events(&client, &[event]).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::{Keys, Timestamp};
+
+    fn tag<'a>(event: &'a Event, name: &str) -> Option<&'a str> {
+        event
+            .tags
+            .iter()
+            .find(|t| t.as_slice()[0] == name)
+            .map(|t| t.as_slice()[1].as_str())
+    }
+
+    #[tokio::test]
+    async fn test_comment_tags() {
+        let keys = Keys::generate();
+        let root = EventBuilder::new(Kind::Custom(1621), "issue")
+            .sign_with_keys(&keys)
+            .unwrap();
+
+        let top = build_comment_event(&keys, &root, None, "First", Some("30617:abc:repo"))
+            .await
+            .unwrap();
+        assert_eq!(top.kind, Kind::Comment);
+        assert_eq!(tag(&top, "E"), Some(root.id.to_hex().as_str()));
+        assert_eq!(tag(&top, "K"), Some("1621"));
+        assert_eq!(tag(&top, "e"), Some(root.id.to_hex().as_str()));
+        assert_eq!(tag(&top, "a"), Some("30617:abc:repo"));
+
+        let reply = build_comment_event(&keys, &root, Some(&top), "Reply", None)
+            .await
+            .unwrap();
+        assert_eq!(tag(&reply, "E"), Some(root.id.to_hex().as_str()));
+        assert_eq!(tag(&reply, "e"), Some(top.id.to_hex().as_str()));
+        assert_eq!(tag(&reply, "k"), Some("1111"));
+        assert_eq!(parse_comment_event(&reply).parent_id, Some(top.id.to_hex()));
+
+        assert!(
+            build_comment_event(&keys, &root, None, " ", None)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_thread_order() {
+        let keys = Keys::generate();
+        let root = EventBuilder::new(Kind::Custom(1618), "pr")
+            .sign_with_keys(&keys)
+            .unwrap();
+        let at = |event: EventBuilder, ts: u64| {
+            event
+                .custom_created_at(Timestamp::from(ts))
+                .sign_with_keys(&keys)
+                .unwrap()
+        };
+
+        let first = at(comment_builder(&root, None, "first", None).unwrap(), 100);
+        let second = at(comment_builder(&root, None, "second", None).unwrap(), 200);
+        let reply = at(
+            comment_builder(&root, Some(&first), "reply", None).unwrap(),
+            300,
+        );
+
+        // Deliberately out of order
+        let comments: Vec<Comment> = [&reply, &second, &first]
+            .into_iter()
+            .map(parse_comment_event)
+            .collect();
+        let ordered: Vec<(usize, &str)> = thread(&comments)
+            .into_iter()
+            .map(|(depth, c)| (depth, c.content.as_str()))
+            .collect();
+
+        assert_eq!(ordered, vec![(0, "first"), (1, "reply"), (0, "second")]);
+    }
+
+    const PATCH: &str = "From abc Mon Sep 17 00:00:00 2001
+Subject: [PATCH] Change lib
+
+---
+ src/lib.rs | 3 ++-
+ 1 file changed, 2 insertions(+), 1 deletion(-)
+
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,4 @@ mod a;
+ fn one() {}
+-fn two() {}
++fn two() -> u8 { 2 }
++fn three() {}
+ fn four() {}
+@@ -10,2 +11,2 @@
+-fn ten() {}
++fn eleven() {}
+ fn twelve() {}
+-- 
+2.45.0
+";
+
+    #[test]
+    fn test_find_hunk() {
+        assert_eq!(
+            find_hunk(PATCH, "src/lib.rs", 3).as_deref(),
+            Some("@@ -1,3 +1,4 @@")
+        );
+        assert_eq!(
+            find_hunk(PATCH, "src/lib.rs", 12).as_deref(),
+            Some("@@ -10,2 +11,2 @@")
+        );
+        assert_eq!(find_hunk(PATCH, "src/lib.rs", 8), None);
+        assert_eq!(find_hunk(PATCH, "src/main.rs", 3), None);
+    }
+
+    #[tokio::test]
+    async fn test_inline_comments() {
+        let keys = Keys::generate();
+        let root = EventBuilder::new(Kind::Custom(1618), "pr")
+            .sign_with_keys(&keys)
+            .unwrap();
+        let inline = |content: &str, line: Option<u32>, parent: Option<&Event>| {
+            let location = CodeLocation {
+                path: "src/lib.rs".to_string(),
+                line,
+                hunk: line.and_then(|line| find_hunk(PATCH, "src/lib.rs", line)),
+            };
+            comment_builder(&root, parent, content, None)
+                .unwrap()
+                .tags(location.tags().unwrap())
+                .sign_with_keys(&keys)
+                .unwrap()
+        };
+
+        let on_three = inline("Why a new function?", Some(3), None);
+        let on_eleven = inline("Rename it", Some(11), None);
+        let on_file = inline("Split this file", None, None);
+        let reply = comment_builder(&root, Some(&on_three), "For the tests", None)
+            .unwrap()
+            .sign_with_keys(&keys)
+            .unwrap();
+        let plain = build_comment_event(&keys, &root, None, "LGTM", None)
+            .await
+            .unwrap();
+
+        let comments: Vec<Comment> = [&on_three, &on_eleven, &on_file, &reply, &plain]
+            .into_iter()
+            .map(parse_comment_event)
+            .collect();
+        let location = comments[0].location.as_ref().unwrap();
+        assert_eq!(location.line, Some(3));
+        assert_eq!(location.hunk.as_deref(), Some("@@ -1,3 +1,4 @@"));
+        assert_eq!(location.to_string(), "src/lib.rs:3");
+        assert!(comments[4].location.is_none());
+
+        let mut shown = HashSet::new();
+        let annotated = annotate_patch(PATCH, &comments, &mut shown, &TimeFormat::default());
+        let lines: Vec<&str> = annotated.lines().collect();
+        let position = |text: &str| lines.iter().position(|line| line.contains(text)).unwrap();
+        // File comments follow the file header, line comments the end of their hunk
+        assert_eq!(
+            position("Split this file"),
+            position("+++ b/src/lib.rs") + 2
+        );
+        assert_eq!(
+            position("Why a new function?"),
+            position(" fn four() {}") + 2
+        );
+        assert_eq!(
+            position("For the tests"),
+            position("Why a new function?") + 2
+        );
+        assert_eq!(position("Rename it"), position(" fn twelve() {}") + 2);
+        assert!(!annotated.contains("LGTM"));
+        assert!(annotated.ends_with("-- \n2.45.0\n"));
+
+        // A comment shows once across the patches of a series
+        assert_eq!(
+            annotate_patch(PATCH, &comments, &mut shown, &TimeFormat::default()),
+            PATCH
+        );
+    }
+}
@@ -0,0 +1,676 @@
+//! Verification of the commit signatures patches carry over Nostr
+//!
+//! A patch event keeps the signature of the commit it was generated from in
+//! its NIP-34 `commit-pgp-sig` tag. The signed commit is rebuilt from the
+//! patch (the tree by applying the diff onto the original parent, author and
+//! message from the patch, committer from the `committer` tag) and has to hash
+//! to the `commit` tag. Its signature is then checked with `gpg` (PGP) or
+//! `ssh-keygen` (SSH) against the keys the maintainers declare in the
+//! repository: [`PGP_KEYS_PATH`] and [`ALLOWED_SIGNERS_PATH`] at HEAD.
+
+use git2::{ObjectType, Oid, Repository};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+use crate::apply;
+use crate::error::{Context, GitsmithError, Result, bail};
+use crate::patches::CommitMetadata;
+use crate::pull_request::PatchSummary;
+use crate::repo::open_repository;
+
+/// Armored PGP public keys of the maintainers, committed in the repository
+pub const PGP_KEYS_PATH: &str = ".gitsmith/signers.asc";
+
+/// SSH keys of the maintainers in `ssh-keygen` allowed signers format, like
+/// git's `gpg.ssh.allowedSignersFile`, committed in the repository
+pub const ALLOWED_SIGNERS_PATH: &str = ".gitsmith/allowed_signers";
+
+/// Namespace git signs commits in with SSH keys
+const SSH_NAMESPACE: &str = "git";
+
+/// Kind of commit signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureFormat {
+    Pgp,
+    Ssh,
+}
+
+impl SignatureFormat {
+    /// Format of an ASCII-armored signature, None for unknown ones
+    pub fn detect(signature: &str) -> Option<Self> {
+        let signature = signature.trim_start();
+        if signature.starts_with("-----BEGIN PGP SIGNATURE-----") {
+            Some(Self::Pgp)
+        } else if signature.starts_with("-----BEGIN SSH SIGNATURE-----") {
+            Some(Self::Ssh)
+        } else {
+            None
+        }
+    }
+}
+
+/// Outcome of checking the signature of a commit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SignatureStatus {
+    /// The commit carries no signature
+    Unsigned,
+    /// Good signature by a key a maintainer declared
+    Verified { signer: String, key: String },
+    /// Signed with a key no maintainer declared; for PGP the signature itself
+    /// can't be checked without the key
+    UnknownKey { key: String },
+    /// The signature doesn't match the commit or its key can't be trusted
+    Bad { reason: String },
+    /// The signature couldn't be checked
+    Unverified { reason: String },
+}
+
+impl fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsigned => write!(f, "unsigned"),
+            Self::Verified { signer, key } => write!(f, "verified, signed by {signer} ({key})"),
+            Self::UnknownKey { key } => write!(f, "signed by undeclared key {key}"),
+            Self::Bad { reason } => write!(f, "bad signature: {reason}"),
+            Self::Unverified { reason } => write!(f, "not verified: {reason}"),
+        }
+    }
+}
+
+/// Signature status of the commit a patch was generated from
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitSignature {
+    pub commit: Option<String>,
+    pub subject: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<SignatureFormat>,
+    #[serde(flatten)]
+    pub status: SignatureStatus,
+}
+
+/// Signing keys the maintainers declared in the repository
+#[derive(Debug, Clone, Default)]
+struct DeclaredKeys {
+    pgp: Vec<u8>,
+    ssh: Vec<u8>,
+}
+
+impl DeclaredKeys {
+    /// Keys committed at HEAD, so a patch can't vouch for itself
+    fn at_head(repo: &Repository) -> Self {
+        let file = |path: &str| -> Option<Vec<u8>> {
+            let tree = repo.head().ok()?.peel_to_tree().ok()?;
+            let entry = tree.get_path(Path::new(path)).ok()?;
+            let blob = entry.to_object(repo).ok()?.peel_to_blob().ok()?;
+            Some(blob.content().to_vec())
+        };
+        Self {
+            pgp: file(PGP_KEYS_PATH).unwrap_or_default(),
+            ssh: file(ALLOWED_SIGNERS_PATH).unwrap_or_default(),
+        }
+    }
+}
+
+/// Check the signatures of the commits a patch series was generated from
+///
+/// Patches are expected in series order: each commit is rebuilt onto the one
+/// before it, or onto its parent in the local repository.
+pub fn verify_patch_signatures(
+    repo_path: &Path,
+    patches: &[PatchSummary],
+) -> Result<Vec<CommitSignature>> {
+    let repo = open_repository(repo_path)?;
+    let keys = DeclaredKeys::at_head(&repo);
+    let mut trees = HashMap::new();
+
+    Ok(patches
+        .iter()
+        .map(|patch| {
+            let Some(commit) = &patch.commit else {
+                return CommitSignature {
+                    commit: None,
+                    subject: patch.subject.clone(),
+                    format: None,
+                    status: SignatureStatus::Unsigned,
+                };
+            };
+            // Unsigned commits are rebuilt too, later ones in the series need their tree
+            let payload = rebuild_commit(&repo, &mut trees, commit, &patch.content);
+            let signature = commit.pgp_sig.as_deref();
+            let status = match (signature, payload) {
+                (None, _) => SignatureStatus::Unsigned,
+                (Some(_), Err(e)) => SignatureStatus::Unverified {
+                    reason: e.to_string(),
+                },
+                (Some(signature), Ok(payload)) => {
+                    check_signature(&keys, commit, signature, &payload)
+                }
+            };
+            CommitSignature {
+                commit: Some(commit.commit.clone()),
+                subject: patch.subject.clone(),
+                format: signature.and_then(SignatureFormat::detect),
+                status,
+            }
+        })
+        .collect())
+}
+
+/// One line per commit, like `5f1e2c0b  verified, signed by ...  Add frobnicator`
+pub fn format_signature(signature: &CommitSignature) -> String {
+    format!(
+        "{commit:.8}  {status}  {subject}",
+        commit = signature.commit.as_deref().unwrap_or("--------"),
+        status = signature.status,
+        subject = signature.subject
+    )
+}
+
+/// Signed payload of the commit a patch was generated from: the commit object
+/// without its signature header
+fn rebuild_commit(
+    repo: &Repository,
+    trees: &mut HashMap<String, Oid>,
+    commit: &CommitMetadata,
+    content: &str,
+) -> Result<String> {
+    let patch = apply::parse_patch(content)?;
+    let base = match &commit.parent_commit {
+        Some(parent) => match trees.get(parent) {
+            Some(tree) => *tree,
+            None => Oid::from_str(parent)
+                .ok()
+                .and_then(|oid| repo.find_commit(oid).ok())
+                .map(|parent| parent.tree_id())
+                .with_context(|| format!("parent commit {parent:.8} not found locally"))?,
+        },
+        None => repo.treebuilder(None)?.write()?,
+    };
+    let tree = apply::apply_to_tree(repo, &repo.find_tree(base)?, &patch)?.id();
+    trees.insert(commit.commit.clone(), tree);
+
+    let (author_time, author_offset) = patch.author_time.context("the patch has no date")?;
+    let mut payload = format!("tree {tree}\n");
+    if let Some(parent) = &commit.parent_commit {
+        payload.push_str(&format!("parent {parent}\n"));
+    }
+    payload.push_str(&format!(
+        "author {identity}\n",
+        identity = identity(
+            &patch.author_name,
+            &patch.author_email,
+            author_time,
+            author_offset
+        )
+    ));
+    payload.push_str(&format!(
+        "committer {identity}\n\n",
+        identity = identity(
+            &commit.committer_name,
+            &commit.committer_email,
+            commit.committer_time,
+            commit.committer_offset
+        )
+    ));
+    payload.push_str(&patch.message());
+    Ok(payload)
+}
+
+/// Author or committer line of a commit object
+fn identity(name: &str, email: &str, time: i64, offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let offset = offset.abs();
+    format!(
+        "{name} <{email}> {time} {sign}{hours:02}{minutes:02}",
+        hours = offset / 60,
+        minutes = offset % 60
+    )
+}
+
+/// Commit objects with the signature header, as git and as libgit2 write it
+///
+/// git continues each line of the signature, libgit2 continues after every
+/// newline, which leaves a blank continuation line after a trailing one.
+fn signed_commits(payload: &str, signature: &str) -> [String; 2] {
+    let (headers, message) = payload.split_once("\n\n").unwrap_or((payload, ""));
+    let object = |signature: &str| {
+        format!(
+            "{headers}\ngpgsig {signature}\n\n{message}",
+            signature = signature.replace('\n', "\n ")
+        )
+    };
+    [object(signature.trim_end_matches('\n')), object(signature)]
+}
+
+/// Check a signature once its rebuilt commit is known to be the signed one
+fn check_signature(
+    keys: &DeclaredKeys,
+    commit: &CommitMetadata,
+    signature: &str,
+    payload: &str,
+) -> SignatureStatus {
+    let rebuilt = signed_commits(payload, signature).iter().any(|object| {
+        Oid::hash_object(ObjectType::Commit, object.as_bytes())
+            .is_ok_and(|oid| oid.to_string() == commit.commit)
+    });
+    if !rebuilt {
+        return SignatureStatus::Unverified {
+            reason: format!(
+                "the patch doesn't rebuild commit {commit:.8}",
+                commit = commit.commit
+            ),
+        };
+    }
+
+    let result = match SignatureFormat::detect(signature) {
+        Some(SignatureFormat::Pgp) => verify_pgp(&keys.pgp, signature, payload),
+        Some(SignatureFormat::Ssh) => verify_ssh(&keys.ssh, signature, payload),
+        None => Err(GitsmithError::msg("unsupported signature format")),
+    };
+    result.unwrap_or_else(|e| SignatureStatus::Unverified {
+        reason: e.to_string(),
+    })
+}
+
+/// Check a PGP signature with gpg, in a keyring holding only the declared keys
+fn verify_pgp(keys: &[u8], signature: &str, payload: &str) -> Result<SignatureStatus> {
+    let dir = tempfile::tempdir()?;
+    let home = dir.path().join("gnupg");
+    fs::create_dir(&home)?;
+    let gpg = || {
+        let mut command = Command::new("gpg");
+        command
+            .args(["--batch", "--no-tty", "--homedir"])
+            .arg(&home);
+        command
+    };
+
+    if !keys.is_empty() {
+        let keys_path = dir.path().join("signers.asc");
+        fs::write(&keys_path, keys)?;
+        let output = run_tool(gpg().arg("--import").arg(&keys_path), None)?;
+        if !output.status.success() {
+            bail!("failed to import the keys in {PGP_KEYS_PATH}");
+        }
+    }
+
+    let signature_path = dir.path().join("commit.sig");
+    fs::write(&signature_path, signature)?;
+    let output = run_tool(
+        gpg()
+            .args(["--status-fd", "1", "--verify"])
+            .arg(&signature_path)
+            .arg("-"),
+        Some(payload.as_bytes()),
+    )?;
+    Ok(parse_gpg_status(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Status of a signature from the `--status-fd` output of `gpg --verify`
+fn parse_gpg_status(status: &str) -> SignatureStatus {
+    let mut signer = None;
+    let mut fingerprint = None;
+    let mut error = false;
+    for line in status.lines() {
+        let Some(line) = line.strip_prefix("[GNUPG:] ") else {
+            continue;
+        };
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let key = rest.split(' ').next().unwrap_or_default().to_string();
+        let reason = match keyword {
+            "NO_PUBKEY" => return SignatureStatus::UnknownKey { key },
+            "BADSIG" => format!("signature by key {key} doesn't match the commit"),
+            "EXPKEYSIG" => format!("key {key} has expired"),
+            "REVKEYSIG" => format!("key {key} was revoked"),
+            "EXPSIG" => "the signature has expired".to_string(),
+            "GOODSIG" => {
+                signer = rest.split_once(' ').map(|(_, uid)| uid.to_string());
+                continue;
+            }
+            "VALIDSIG" => {
+                fingerprint = Some(key);
+                continue;
+            }
+            "ERRSIG" => {
+                error = true;
+                continue;
+            }
+            _ => continue,
+        };
+        return SignatureStatus::Bad { reason };
+    }
+
+    match fingerprint {
+        Some(key) if !error => SignatureStatus::Verified {
+            signer: signer.unwrap_or_else(|| key.clone()),
+            key,
+        },
+        _ => SignatureStatus::Unverified {
+            reason: "gpg could not check the signature".to_string(),
+        },
+    }
+}
+
+/// Check an SSH signature with ssh-keygen against the declared allowed signers
+fn verify_ssh(allowed_signers: &[u8], signature: &str, payload: &str) -> Result<SignatureStatus> {
+    let dir = tempfile::tempdir()?;
+    let allowed_path = dir.path().join("allowed_signers");
+    fs::write(&allowed_path, allowed_signers)?;
+    let signature_path = dir.path().join("commit.sig");
+    fs::write(&signature_path, signature)?;
+
+    let principals = run_tool(
+        Command::new("ssh-keygen")
+            .args(["-Y", "find-principals", "-f"])
+            .arg(&allowed_path)
+            .arg("-s")
+            .arg(&signature_path),
+        None,
+    )?;
+    let principal = String::from_utf8_lossy(&principals.stdout)
+        .lines()
+        .next()
+        .map(str::to_string)
+        .filter(|_| principals.status.success());
+
+    let Some(principal) = principal else {
+        // Not a declared key, but still tell a valid signature from a forged one
+        let output = run_tool(
+            Command::new("ssh-keygen")
+                .args(["-Y", "check-novalidate", "-n", SSH_NAMESPACE, "-s"])
+                .arg(&signature_path),
+            Some(payload.as_bytes()),
+        )?;
+        if !output.status.success() {
+            return Ok(SignatureStatus::Bad {
+                reason: tool_message(&output),
+            });
+        }
+        return Ok(SignatureStatus::UnknownKey {
+            key: ssh_fingerprint(&output).unwrap_or_default(),
+        });
+    };
+
+    let output = run_tool(
+        Command::new("ssh-keygen")
+            .args(["-Y", "verify", "-f"])
+            .arg(&allowed_path)
+            .args(["-I", &principal, "-n", SSH_NAMESPACE, "-s"])
+            .arg(&signature_path),
+        Some(payload.as_bytes()),
+    )?;
+    if !output.status.success() {
+        return Ok(SignatureStatus::Bad {
+            reason: tool_message(&output),
+        });
+    }
+    Ok(SignatureStatus::Verified {
+        signer: principal,
+        key: ssh_fingerprint(&output).unwrap_or_default(),
+    })
+}
+
+/// Key fingerprint (`SHA256:...`) ssh-keygen reports for a good signature
+fn ssh_fingerprint(output: &Output) -> Option<String> {
+    [&output.stdout, &output.stderr]
+        .into_iter()
+        .flat_map(|stream| {
+            String::from_utf8_lossy(stream)
+                .split_whitespace()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .find(|word| word.starts_with("SHA256:"))
+}
+
+/// Last line a tool printed, to explain a failure
+fn tool_message(output: &Output) -> String {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stderr
+        .lines()
+        .chain(stdout.lines())
+        .rfind(|line| !line.trim().is_empty())
+        .unwrap_or("signature verification failed")
+        .trim()
+        .to_string()
+}
+
+/// Run a verification tool, feeding it `input` on stdin
+fn run_tool(command: &mut Command, input: Option<&[u8]>) -> Result<Output> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run {program}, is it installed?"))?;
+    if let Some(input) = input {
+        child
+            .stdin
+            .take()
+            .context("Failed to open stdin")?
+            .write_all(input)?;
+    }
+    Ok(child.wait_with_output()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patches;
+    use git2::{Signature, Time};
+
+    /// Repository with a root commit and a commit carrying `signature` next to
+    /// it, HEAD staying at the root; returns the signed commit's patch and ID
+    fn signed_series(dir: &Path, signature: &str) -> (Vec<PatchSummary>, String) {
+        let repo = Repository::init(dir).unwrap();
+        let author =
+            Signature::new("Alice", "alice@example.com", &Time::new(1700000000, 60)).unwrap();
+        let write = |content: &str| {
+            fs::write(dir.join("f.txt"), content).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new("f.txt")).unwrap();
+            index.write_tree().unwrap()
+        };
+
+        let tree = repo.find_tree(write("one\n")).unwrap();
+        let root = repo
+            .commit(Some("HEAD"), &author, &author, "Initial", &tree, &[])
+            .unwrap();
+        let root_commit = repo.find_commit(root).unwrap();
+        let tree = repo.find_tree(write("one\ntwo\n")).unwrap();
+        let buffer = repo
+            .commit_create_buffer(
+                &author,
+                &author,
+                "Add two\n\nSecond line.\n",
+                &tree,
+                &[&root_commit],
+            )
+            .unwrap();
+        let payload = buffer.as_str().unwrap().to_string();
+        let signed = repo.commit_signed(&payload, signature, None).unwrap();
+        repo.reference("refs/heads/feature", signed, true, "signed")
+            .unwrap();
+        (vec![summary_of(&repo, signed)], signed.to_string())
+    }
+
+    /// Patch summary of a local commit, as a patch event carries it
+    fn summary_of(repo: &Repository, commit: Oid) -> PatchSummary {
+        let commit = repo.find_commit(commit).unwrap();
+        let patch = crate::format_patch::format_commit(repo, &commit, None).unwrap();
+        let dir = repo.workdir().unwrap();
+        let commits = patches::commit_metadata(dir, std::slice::from_ref(&patch)).unwrap();
+        PatchSummary {
+            id: String::new(),
+            subject: commit.summary().unwrap().to_string(),
+            git_author: String::new(),
+            created_at: 0,
+            diffstat: Default::default(),
+            content: patch,
+            commit: commits.into_iter().next(),
+        }
+    }
+
+    #[test]
+    fn test_signed_commit_rebuilds_its_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let signature = "-----BEGIN SSH SIGNATURE-----\nU1NIU0lH\n-----END SSH SIGNATURE-----";
+        let (patches, commit) = signed_series(dir.path(), signature);
+        let repo = Repository::open(dir.path()).unwrap();
+        let metadata = patches[0].commit.as_ref().unwrap();
+        assert_eq!(metadata.commit, commit);
+        assert_eq!(
+            SignatureFormat::detect(metadata.pgp_sig.as_deref().unwrap()),
+            Some(SignatureFormat::Ssh)
+        );
+
+        let payload =
+            rebuild_commit(&repo, &mut HashMap::new(), metadata, &patches[0].content).unwrap();
+        let objects = signed_commits(&payload, metadata.pgp_sig.as_deref().unwrap());
+        assert!(objects.iter().any(|object| {
+            Oid::hash_object(ObjectType::Commit, object.as_bytes())
+                .unwrap()
+                .to_string()
+                == commit
+        }));
+
+        // A tampered diff no longer rebuilds the signed commit
+        let mut tampered = patches[0].clone();
+        tampered.content = tampered.content.replace("+two", "+evil");
+        let status = verify_patch_signatures(dir.path(), &[tampered]).unwrap();
+        assert!(
+            matches!(&status[0].status, SignatureStatus::Unverified { .. }),
+            "{status:?}"
+        );
+    }
+
+    #[test]
+    fn test_ssh_signature_against_declared_keys() {
+        let keys = tempfile::tempdir().unwrap();
+        let key_path = keys.path().join("id_ed25519");
+        let generated = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+            .arg(&key_path)
+            .status();
+        if !generated.is_ok_and(|status| status.success()) {
+            eprintln!("ssh-keygen not available, skipping");
+            return;
+        }
+        // Signed by git itself, the way contributors sign their commits
+        let dir = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .current_dir(dir.path())
+                .args([
+                    "-c",
+                    "user.name=Alice",
+                    "-c",
+                    "user.email=alice@example.com",
+                ])
+                .args(["-c", "gpg.format=ssh", "-c"])
+                .arg(format!("user.signingkey={key}", key = key_path.display()))
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success(), "git {args:?}");
+        };
+        git(&["init", "-q"]);
+        fs::write(dir.path().join("f.txt"), "one\n").unwrap();
+        git(&["add", "f.txt"]);
+        git(&["commit", "-q", "-m", "Initial"]);
+        fs::write(dir.path().join("f.txt"), "one\ntwo\n").unwrap();
+        git(&[
+            "commit",
+            "-q",
+            "-a",
+            "-S",
+            "-m",
+            "Add two",
+            "-m",
+            "Second line.",
+        ]);
+        let repo = Repository::open(dir.path()).unwrap();
+        let head = repo.head().unwrap().target().unwrap();
+        let patches = vec![summary_of(&repo, head)];
+
+        let status = verify_patch_signatures(dir.path(), &patches).unwrap();
+        assert!(
+            matches!(&status[0].status, SignatureStatus::UnknownKey { key } if key.starts_with("SHA256:")),
+            "{status:?}"
+        );
+
+        // Declare the key in the repository and check again
+        let public_key = fs::read_to_string(key_path.with_extension("pub")).unwrap();
+        fs::create_dir_all(dir.path().join(".gitsmith")).unwrap();
+        fs::write(
+            dir.path().join(ALLOWED_SIGNERS_PATH),
+            format!("alice@example.com {public_key}"),
+        )
+        .unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(ALLOWED_SIGNERS_PATH)).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let author = head.author();
+        repo.commit(
+            Some("HEAD"),
+            &author,
+            &author,
+            "Declare keys",
+            &tree,
+            &[&head],
+        )
+        .unwrap();
+
+        let status = verify_patch_signatures(dir.path(), &patches).unwrap();
+        assert!(
+            matches!(&status[0].status, SignatureStatus::Verified { signer, .. } if signer == "alice@example.com"),
+            "{status:?}"
+        );
+        assert_eq!(status[0].format, Some(SignatureFormat::Ssh));
+    }
+
+    #[test]
+    fn test_parse_gpg_status() {
+        let good = "[GNUPG:] NEWSIG\n\
+            [GNUPG:] GOODSIG 1234ABCD Alice <alice@example.com>\n\
+            [GNUPG:] VALIDSIG ABCDEF0123456789 2023-11-14 1700000000 0 4 0 22 8 00 ABCDEF0123456789\n\
+            [GNUPG:] TRUST_UNDEFINED 0 pgp\n";
+        assert_eq!(
+            parse_gpg_status(good),
+            SignatureStatus::Verified {
+                signer: "Alice <alice@example.com>".to_string(),
+                key: "ABCDEF0123456789".to_string()
+            }
+        );
+        assert_eq!(
+            parse_gpg_status(
+                "[GNUPG:] ERRSIG 1234ABCD 22 8 00 1700000000 9 -\n[GNUPG:] NO_PUBKEY 1234ABCD\n"
+            ),
+            SignatureStatus::UnknownKey {
+                key: "1234ABCD".to_string()
+            }
+        );
+        assert!(matches!(
+            parse_gpg_status("[GNUPG:] BADSIG 1234ABCD Alice <alice@example.com>\n"),
+            SignatureStatus::Bad { .. }
+        ));
+        assert!(matches!(
+            parse_gpg_status(""),
+            SignatureStatus::Unverified { .. }
+        ));
+    }
+}
@@ -0,0 +1,410 @@
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item};
+use tracing::warn;
+
+use crate::error::{Context, Result, bail};
+
+/// Environment variable pointing at another configuration file
+pub const CONFIG_ENV: &str = "GITSMITH_CONFIG";
+
+/// Type of a configuration value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    String,
+    Integer,
+    List,
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::String => "a string",
+            Self::Integer => "a number",
+            Self::List => "a list of strings",
+        })
+    }
+}
+
+/// A key of the configuration file
+#[derive(Debug)]
+pub struct ConfigKey {
+    pub name: &'static str,
+    pub kind: ValueKind,
+    pub description: &'static str,
+}
+
+/// Every key gitsmith reads from the configuration file
+pub const KEYS: &[ConfigKey] = &[
+    ConfigKey {
+        name: "relays",
+        kind: ValueKind::List,
+        description: "Relays used when neither --relay nor the repository configures any",
+    },
+    ConfigKey {
+        name: "account",
+        kind: ValueKind::String,
+        description: "Stored account (npub) to sign with instead of the active one",
+    },
+    ConfigKey {
+        name: "proxy",
+        kind: ValueKind::String,
+        description: "SOCKS5 proxy used when neither --proxy nor git config nostr.proxy sets one",
+    },
+    ConfigKey {
+        name: "timeout",
+        kind: ValueKind::Integer,
+        description: "Default of --timeout, in seconds",
+    },
+    ConfigKey {
+        name: "format",
+        kind: ValueKind::String,
+        description: "Default of --format: human, json or minimal",
+    },
+    ConfigKey {
+        name: "cache",
+        kind: ValueKind::String,
+        description: "Default of --cache: sqlite, lmdb or memory",
+    },
+    ConfigKey {
+        name: "time",
+        kind: ValueKind::String,
+        description: "Default of --time: relative or absolute",
+    },
+    ConfigKey {
+        name: "timezone",
+        kind: ValueKind::String,
+        description: "Default of --timezone",
+    },
+    ConfigKey {
+        name: "date-format",
+        kind: ValueKind::String,
+        description: "Default of --date-format",
+    },
+];
+
+/// Look up a configuration key by name
+pub fn key(name: &str) -> Result<&'static ConfigKey> {
+    KEYS.iter().find(|key| key.name == name).with_context(|| {
+        let names: Vec<&str> = KEYS.iter().map(|key| key.name).collect();
+        format!(
+            "Unknown configuration key {name}, expected one of: {names}",
+            names = names.join(", ")
+        )
+    })
+}
+
+/// A configuration value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    String(String),
+    Integer(u64),
+    List(Vec<String>),
+}
+
+impl Value {
+    /// Parse a value given on the command line, lists are comma separated
+    pub fn parse(kind: ValueKind, text: &str) -> Result<Self> {
+        Ok(match kind {
+            ValueKind::String => Self::String(text.to_string()),
+            ValueKind::Integer => Self::Integer(
+                text.trim()
+                    .parse()
+                    .with_context(|| format!("Invalid number {text}"))?,
+            ),
+            ValueKind::List => Self::List(
+                text.split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(String::from)
+                    .collect(),
+            ),
+        })
+    }
+
+    pub fn kind(&self) -> ValueKind {
+        match self {
+            Self::String(_) => ValueKind::String,
+            Self::Integer(_) => ValueKind::Integer,
+            Self::List(_) => ValueKind::List,
+        }
+    }
+
+    fn to_toml(&self) -> Result<toml_edit::Value> {
+        Ok(match self {
+            Self::String(value) => value.as_str().into(),
+            Self::Integer(value) => i64::try_from(*value)
+                .with_context(|| format!("{value} is too large"))?
+                .into(),
+            Self::List(items) => items.iter().map(String::as_str).collect(),
+        })
+    }
+
+    fn from_toml(value: &toml_edit::Value) -> Result<Self> {
+        Ok(match value {
+            toml_edit::Value::String(value) => Self::String(value.value().clone()),
+            toml_edit::Value::Integer(value) => Self::Integer(
+                u64::try_from(*value.value())
+                    .with_context(|| format!("Expected a positive number, got {value}"))?,
+            ),
+            toml_edit::Value::Array(items) => Self::List(
+                items
+                    .iter()
+                    .map(|item| {
+                        item.as_str()
+                            .map(String::from)
+                            .with_context(|| format!("Expected a string, got {item}"))
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            value => {
+                bail!("Unsupported value {value}, expected a string, a number or a list of strings")
+            }
+        })
+    }
+}
+
+/// Values as `config set` takes them, lists comma separated
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(value) => f.write_str(value),
+            Self::Integer(value) => write!(f, "{value}"),
+            Self::List(items) => f.write_str(&items.join(",")),
+        }
+    }
+}
+
+/// The configuration file, `~/.config/gitsmith/config.toml` by default
+///
+/// Only top-level keys holding strings, integers and lists of strings are
+/// read. Formatting, comments and unknown keys are kept when the file is
+/// written back.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    document: DocumentMut,
+    /// Known keys set in the document, in file order
+    values: Vec<(String, Value)>,
+}
+
+impl PartialEq for Config {
+    fn eq(&self, other: &Self) -> bool {
+        self.values == other.values
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read configuration from {path:?}"))?;
+
+        Self::parse(&data).with_context(|| format!("Failed to parse configuration {path:?}"))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {parent:?}"))?;
+        }
+
+        fs::write(path, self.to_string())
+            .with_context(|| format!("Failed to write configuration to {path:?}"))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let document: DocumentMut = text.parse().context("Invalid TOML")?;
+
+        let mut values = Vec::new();
+        for (name, item) in document.iter() {
+            let Some(value) = item.as_value() else {
+                bail!("{name}: tables are not supported, set keys at the top level");
+            };
+            let Ok(key) = key(name) else {
+                warn!("Ignoring unknown configuration key {name}");
+                continue;
+            };
+            let value = Value::from_toml(value).with_context(|| name.to_string())?;
+            if value.kind() != key.kind {
+                bail!("{name} expects {kind}", kind = key.kind);
+            }
+            values.push((name.to_string(), value));
+        }
+
+        Ok(Self { document, values })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.entries()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn string(&self, name: &str) -> Option<&str> {
+        match self.get(name) {
+            Some(Value::String(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn integer(&self, name: &str) -> Option<u64> {
+        match self.get(name) {
+            Some(Value::Integer(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn list(&self, name: &str) -> Vec<String> {
+        match self.get(name) {
+            Some(Value::List(items)) => items.clone(),
+            _ => vec![],
+        }
+    }
+
+    /// Set a key, replacing its value in place if it is already set
+    pub fn set(&mut self, name: &str, value: Value) -> Result<()> {
+        let key = key(name)?;
+        if value.kind() != key.kind {
+            bail!("{name} expects {kind}", kind = key.kind);
+        }
+
+        let mut toml = value.to_toml()?;
+        match self.document.get_mut(name).and_then(Item::as_value_mut) {
+            // Keep the spacing and trailing comment around the old value
+            Some(current) => {
+                *toml.decor_mut() = current.decor().clone();
+                *current = toml;
+            }
+            None => {
+                self.document.insert(name, Item::Value(toml));
+            }
+        }
+        match self.values.iter_mut().find(|(key, _)| key == name) {
+            Some((_, current)) => *current = value,
+            None => self.values.push((name.to_string(), value)),
+        }
+        Ok(())
+    }
+
+    /// Remove a key. Returns false if it wasn't set.
+    pub fn unset(&mut self, name: &str) -> bool {
+        self.values.retain(|(key, _)| key != name);
+        self.document.remove(name).is_some()
+    }
+
+    /// Keys set in the file, in file order
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.values.iter().map(|(key, value)| (key.as_str(), value))
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{document}", document = self.document)
+    }
+}
+
+/// Get the path of the configuration file, `GITSMITH_CONFIG` overrides it
+pub fn get_config_path() -> Result<PathBuf> {
+    if let Some(path) = std::env::var_os(CONFIG_ENV) {
+        return Ok(PathBuf::from(path));
+    }
+    let home = dirs::home_dir().context("Failed to get home directory")?;
+    Ok(home.join(".config").join("gitsmith").join("config.toml"))
+}
+
+/// Load the configuration file, empty if there is none
+pub fn load() -> Result<Config> {
+    Config::load(&get_config_path()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse(
+            r#"# gitsmith defaults
+relays = [
+    "wss://relay.damus.io", # main relay
+    'wss://nos.lol',
+]
+timeout = 30
+account = "npub1#example"
+date-format = "%Y-%m-%d \"%H\""
+unknown = true
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.list("relays"),
+            vec!["wss://relay.damus.io", "wss://nos.lol"]
+        );
+        assert_eq!(config.integer("timeout"), Some(30));
+        assert_eq!(config.string("account"), Some("npub1#example"));
+        assert_eq!(config.string("date-format"), Some("%Y-%m-%d \"%H\""));
+        assert_eq!(config.get("unknown"), None);
+        assert_eq!(config.get("proxy"), None);
+
+        // Written back it reads the same, comments and unknown keys included
+        let written = config.to_string();
+        assert!(written.contains("# gitsmith defaults"));
+        assert!(written.contains("unknown = true"));
+        assert_eq!(Config::parse(&written).unwrap(), config);
+    }
+
+    #[test]
+    fn test_parse_config_errors() {
+        assert!(Config::parse("[section]").is_err());
+        assert!(Config::parse("timeout").is_err());
+        assert!(Config::parse("timeout = \"30\"").is_err());
+        assert!(Config::parse("relays = [\"wss://a\"").is_err());
+        assert!(Config::parse("proxy = \"a\"\nproxy = \"b\"").is_err());
+        assert!(Config::parse("proxy = \"open").is_err());
+    }
+
+    #[test]
+    fn test_set_and_unset() {
+        let mut config = Config::parse("# comment\ntimeout = 5 # seconds\n").unwrap();
+
+        config
+            .set("timeout", Value::parse(ValueKind::Integer, "20").unwrap())
+            .unwrap();
+        config
+            .set(
+                "relays",
+                Value::parse(ValueKind::List, "wss://a, wss://b").unwrap(),
+            )
+            .unwrap();
+        assert_eq!(
+            config.to_string(),
+            "# comment\ntimeout = 20 # seconds\nrelays = [\"wss://a\", \"wss://b\"]\n"
+        );
+
+        assert!(config.set("timeout", Value::String("x".into())).is_err());
+        assert!(config.set("nope", Value::Integer(1)).is_err());
+        assert!(config.unset("timeout"));
+        assert!(!config.unset("timeout"));
+        assert_eq!(config.get("timeout"), None);
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        assert_eq!(Config::load(&path).unwrap(), Config::default());
+
+        let mut config = Config::default();
+        config.set("format", Value::String("json".into())).unwrap();
+        config.save(&path).unwrap();
+        assert_eq!(Config::load(&path).unwrap(), config);
+    }
+}
@@ -0,0 +1,285 @@
+use nostr::{Alphabet, Event, EventId, Filter, Kind, PublicKey, SingleLetterTag};
+use nostr_sdk::{Client, RelayStatus};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::error::Result;
+use crate::events::{KIND_GIT_REPO_ANNOUNCEMENT, KIND_GIT_STATE};
+use crate::patches::{self, KIND_PATCH, KIND_PULL_REQUEST, KIND_PULL_REQUEST_UPDATE};
+use crate::pull_request;
+use crate::relay::{self, Network};
+
+/// Maximum number of event IDs asked for in one request, relays reject larger filters
+const IDS_PER_REQUEST: usize = 250;
+
+/// Number of repository events of each type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct EventCounts {
+    pub announcement: usize,
+    pub state: usize,
+    pub pull_requests: usize,
+    pub patches: usize,
+}
+
+impl EventCounts {
+    /// Count the repository events among `events`
+    pub fn from_events<'a>(events: impl IntoIterator<Item = &'a Event>) -> Self {
+        let mut counts = Self::default();
+        for event in events {
+            let kind = event.kind;
+            if kind == Kind::from(KIND_GIT_REPO_ANNOUNCEMENT) {
+                counts.announcement += 1;
+            } else if kind == Kind::from(KIND_GIT_STATE) {
+                counts.state += 1;
+            } else if kind == KIND_PULL_REQUEST || kind == KIND_PULL_REQUEST_UPDATE {
+                counts.pull_requests += 1;
+            } else if kind == KIND_PATCH {
+                counts.patches += 1;
+            }
+        }
+        counts
+    }
+
+    pub fn total(&self) -> usize {
+        self.announcement + self.state + self.pull_requests + self.patches
+    }
+}
+
+/// Which of the known repository events a relay holds
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayCoverage {
+    pub url: String,
+    pub connected: bool,
+    pub held: EventCounts,
+    /// Known events the relay doesn't hold (or holds an outdated version of)
+    pub missing: EventCounts,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RelayCoverage {
+    pub fn has_gaps(&self) -> bool {
+        self.missing.total() > 0
+    }
+}
+
+/// Distribution of a repository's events across its relays
+#[derive(Debug, Clone, Serialize)]
+pub struct CoverageReport {
+    /// Events found on at least one relay
+    pub known: EventCounts,
+    pub relays: Vec<RelayCoverage>,
+}
+
+impl CoverageReport {
+    /// Relays that miss at least one known event
+    pub fn relays_with_gaps(&self) -> impl Iterator<Item = &RelayCoverage> {
+        self.relays.iter().filter(|relay| relay.has_gaps())
+    }
+}
+
+/// Find all events of a repository on its relays, then ask each relay which of them it holds
+///
+/// Relays don't reliably support NIP-45 COUNT, so every relay is probed by
+/// fetching the known events by ID.
+pub async fn repository_coverage(
+    network: &Network,
+    repo_coordinate: &str,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<CoverageReport> {
+    let (_, owner, identifier) = patches::parse_repo_coordinate(repo_coordinate)?;
+    let owner = PublicKey::from_hex(&owner)?;
+
+    let client = relay::new_client(network, None);
+    for relay_url in relays {
+        client.add_relay(relay_url).await?;
+    }
+    client.connect().await;
+    // Unreachable relays are reported as such, so not reaching all of them is fine
+    let _ =
+        relay::wait_for_connections(&client, relays.len(), relay::DEFAULT_CONNECT_TIMEOUT).await;
+
+    let known = fetch_known_events(
+        network,
+        &client,
+        repo_coordinate,
+        owner,
+        &identifier,
+        timeout,
+    )
+    .await?;
+    let known_counts = EventCounts::from_events(&known);
+    let known_ids: Vec<EventId> = known.iter().map(|event| event.id).collect();
+    debug!("Found {count} known event(s)", count = known_ids.len());
+
+    let statuses: HashMap<String, RelayStatus> = client
+        .pool()
+        .relays()
+        .await
+        .into_iter()
+        .map(|(url, relay)| {
+            (
+                url.to_string().trim_end_matches('/').to_string(),
+                relay.status(),
+            )
+        })
+        .collect();
+
+    let mut coverage = Vec::new();
+    for url in relays {
+        let connected = statuses.get(url.trim_end_matches('/')) == Some(&RelayStatus::Connected);
+        let (held, error) = if connected {
+            match fetch_held_ids(&client, url, &known_ids, timeout).await {
+                Ok(held) => (held, None),
+                Err(e) => {
+                    warn!("Failed to probe {url}: {e}");
+                    (HashSet::new(), Some(e.to_string()))
+                }
+            }
+        } else {
+            (HashSet::new(), Some("not connected".to_string()))
+        };
+
+        let (held, missing): (Vec<&Event>, Vec<&Event>) =
+            known.iter().partition(|event| held.contains(&event.id));
+        coverage.push(RelayCoverage {
+            url: url.clone(),
+            connected,
+            held: EventCounts::from_events(held),
+            missing: EventCounts::from_events(missing),
+            error,
+        });
+    }
+    client.disconnect().await;
+
+    Ok(CoverageReport {
+        known: known_counts,
+        relays: coverage,
+    })
+}
+
+/// Latest announcement and state, all PRs and their patches, from any relay
+async fn fetch_known_events(
+    network: &Network,
+    client: &Client,
+    repo_coordinate: &str,
+    owner: PublicKey,
+    identifier: &str,
+    timeout: Duration,
+) -> Result<Vec<Event>> {
+    let replaceable = Filter::new()
+        .kinds([
+            Kind::from(KIND_GIT_REPO_ANNOUNCEMENT),
+            Kind::from(KIND_GIT_STATE),
+        ])
+        .author(owner)
+        .identifier(identifier);
+    let referencing = Filter::new()
+        .kinds([KIND_PULL_REQUEST, KIND_PULL_REQUEST_UPDATE, KIND_PATCH])
+        .custom_tag(SingleLetterTag::lowercase(Alphabet::A), repo_coordinate);
+    let events =
+        relay::fetch_events(network, client, vec![replaceable, referencing], timeout).await?;
+    let mut known = latest_replaceable(events);
+
+    // Patches from older clients only reference their PR, not the repository
+    let have: HashSet<EventId> = known.iter().map(|event| event.id).collect();
+    let patch_ids: Vec<EventId> = known
+        .iter()
+        .filter(|event| event.kind == KIND_PULL_REQUEST || event.kind == KIND_PULL_REQUEST_UPDATE)
+        .flat_map(|pr| {
+            pull_request::patch_event_ids(pr)
+                .into_iter()
+                .chain(pull_request::cover_letter_id(pr))
+        })
+        .filter(|id| !have.contains(id))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    for ids in patch_ids.chunks(IDS_PER_REQUEST) {
+        let filter = Filter::new().kind(KIND_PATCH).ids(ids.iter().copied());
+        known.extend(relay::fetch_events(network, client, vec![filter], timeout).await?);
+    }
+
+    Ok(known)
+}
+
+/// Drop all but the newest version of replaceable events
+///
+/// Relays that still serve an older announcement or state then show up as gaps.
+fn latest_replaceable(events: Vec<Event>) -> Vec<Event> {
+    let mut latest: HashMap<Kind, Event> = HashMap::new();
+    let mut regular = Vec::new();
+
+    for event in events {
+        if !event.kind.is_replaceable() && !event.kind.is_addressable() {
+            regular.push(event);
+            continue;
+        }
+        match latest.get(&event.kind) {
+            Some(current) if (current.created_at, current.id) >= (event.created_at, event.id) => {}
+            _ => {
+                latest.insert(event.kind, event);
+            }
+        }
+    }
+
+    latest.into_values().chain(regular).collect()
+}
+
+/// IDs of the given events that a single relay holds
+async fn fetch_held_ids(
+    client: &Client,
+    url: &str,
+    ids: &[EventId],
+    timeout: Duration,
+) -> Result<HashSet<EventId>> {
+    let mut held = HashSet::new();
+    for chunk in ids.chunks(IDS_PER_REQUEST) {
+        let filter = Filter::new().ids(chunk.iter().copied());
+        let events = client.fetch_events_from([url], filter, timeout).await?;
+        held.extend(events.into_iter().map(|event| event.id));
+    }
+    Ok(held)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::{EventBuilder, Keys, Tag, Timestamp};
+
+    #[test]
+    fn test_latest_replaceable_counts() {
+        let keys = Keys::generate();
+        let announcement = |created_at| {
+            EventBuilder::new(Kind::from(KIND_GIT_REPO_ANNOUNCEMENT), "")
+                .tag(Tag::identifier("repo"))
+                .custom_created_at(Timestamp::from(created_at))
+                .sign_with_keys(&keys)
+                .unwrap()
+        };
+        let old = announcement(1);
+        let new = announcement(2);
+        let pr = EventBuilder::new(KIND_PULL_REQUEST, "")
+            .sign_with_keys(&keys)
+            .unwrap();
+        let patch = EventBuilder::new(KIND_PATCH, "")
+            .sign_with_keys(&keys)
+            .unwrap();
+
+        let known = latest_replaceable(vec![new.clone(), pr, old, patch]);
+        assert!(known.iter().any(|event| event.id == new.id));
+        assert_eq!(
+            EventCounts::from_events(&known),
+            EventCounts {
+                announcement: 1,
+                state: 0,
+                pull_requests: 1,
+                patches: 1,
+            }
+        );
+        assert_eq!(EventCounts::from_events(&known).total(), 3);
+    }
+}
@@ -0,0 +1,567 @@
+use futures::TryStreamExt;
+use git2::Config;
+use lettre::message::{Mailbox, Message, header::ContentType};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use std::fmt::Debug;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore, crypto, pki_types::ServerName};
+use tracing::{debug, info};
+
+use crate::apply::parse_patch;
+use crate::error::{Context, Result, bail, ensure};
+use crate::repo::open_repository;
+
+/// How the SMTP connection is secured (mirrors git's `sendemail.smtpEncryption`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpEncryption {
+    /// Implicit TLS, usually on port 465 (`ssl`)
+    Ssl,
+    /// STARTTLS upgrade, usually on port 587 (`tls`)
+    StartTls,
+    /// Plain text connection
+    None,
+}
+
+/// SMTP settings read from git's `sendemail` configuration
+///
+/// Values are looked up in `sendemail.<profile>.*` first and fall back to
+/// `sendemail.*`, the same way `git send-email --identity` resolves them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmtpProfile {
+    pub server: String,
+    pub port: Option<u16>,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub encryption: SmtpEncryption,
+    /// Sender address, defaults to `user.name <user.email>`
+    pub from: String,
+}
+
+impl SmtpProfile {
+    /// Load a profile from the repository's git configuration
+    pub fn from_git_config(repo_path: &Path, profile: Option<&str>) -> Result<Self> {
+        let repo = open_repository(repo_path)?;
+        let config = repo.config()?.snapshot()?;
+        let lookup = |key: &str| -> Option<String> {
+            profile
+                .and_then(|p| config.get_string(&format!("sendemail.{p}.{key}")).ok())
+                .or_else(|| config.get_string(&format!("sendemail.{key}")).ok())
+                .filter(|value| !value.is_empty())
+        };
+
+        let Some(server) = lookup("smtpServer") else {
+            match profile {
+                Some(p) => bail!("No SMTP server configured. Set sendemail.{p}.smtpServer"),
+                None => bail!("No SMTP server configured. Set sendemail.smtpServer"),
+            }
+        };
+
+        let port = lookup("smtpServerPort")
+            .map(|port| {
+                port.parse::<u16>()
+                    .with_context(|| format!("Invalid SMTP port: {port}"))
+            })
+            .transpose()?;
+
+        let encryption = match lookup("smtpEncryption").as_deref() {
+            Some("ssl") => SmtpEncryption::Ssl,
+            Some("tls") | None => SmtpEncryption::StartTls,
+            Some("none") => SmtpEncryption::None,
+            Some(other) => {
+                bail!("Unsupported SMTP encryption: {other} (expected ssl, tls or none)")
+            }
+        };
+
+        let from = match lookup("from") {
+            Some(from) => from,
+            None => default_sender(&config)?,
+        };
+
+        Ok(Self {
+            server,
+            port,
+            user: lookup("smtpUser"),
+            password: lookup("smtpPass"),
+            encryption,
+            from,
+        })
+    }
+}
+
+fn default_sender(config: &Config) -> Result<String> {
+    let name = config.get_string("user.name").ok();
+    let email = config
+        .get_string("user.email")
+        .context("No sender configured. Set sendemail.from or user.email")?;
+    Ok(match name {
+        Some(name) => format!("{name} <{email}>"),
+        None => email,
+    })
+}
+
+/// Cover letter and recipients for an emailed patch series
+#[derive(Debug, Clone)]
+pub struct PatchEmailOptions {
+    pub from: String,
+    pub to: Vec<String>,
+    pub title: String,
+    pub description: String,
+}
+
+/// Turn a patch series into threaded emails in `git send-email` layout
+///
+/// Series with more than one patch get a `[PATCH 0/N]` cover letter. Every patch
+/// replies to the first message so mail clients show the series as one thread.
+pub fn build_patch_emails(patches: &[String], options: &PatchEmailOptions) -> Result<Vec<Message>> {
+    ensure!(!patches.is_empty(), "No patches to email");
+    ensure!(!options.to.is_empty(), "No email recipients given");
+
+    let from: Mailbox = options
+        .from
+        .parse()
+        .with_context(|| format!("Invalid sender address: {from}", from = options.from))?;
+    let to = options
+        .to
+        .iter()
+        .map(|addr| {
+            addr.parse::<Mailbox>()
+                .with_context(|| format!("Invalid recipient address: {addr}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let total = patches.len();
+    let id_prefix = message_id_prefix();
+    let domain = from.email.domain().to_string();
+    let message_id = |n: usize| format!("<{id_prefix}-{n}@{domain}>");
+
+    let mut messages = Vec::new();
+    let mut thread_root = None;
+
+    if total > 1 {
+        let body = if options.description.is_empty() {
+            format!("{title}\n", title = options.title)
+        } else {
+            format!("{description}\n", description = options.description)
+        };
+        let subject = format!("[PATCH 0/{total}] {title}", title = options.title);
+        messages.push(build_message(
+            &from,
+            &to,
+            &subject,
+            message_id(0),
+            None,
+            body,
+        )?);
+        thread_root = Some(message_id(0));
+    }
+
+    for (i, patch) in patches.iter().enumerate() {
+        let parsed = parse_patch(patch)?;
+        let subject = if total > 1 {
+            format!(
+                "[PATCH {n}/{total}] {subject}",
+                n = i + 1,
+                subject = parsed.subject
+            )
+        } else {
+            format!("[PATCH] {subject}", subject = parsed.subject)
+        };
+
+        let mut body = String::new();
+        // Keep authorship when someone else's commit is being sent
+        if !parsed.author_email.is_empty() && parsed.author_email != from.email.to_string() {
+            body.push_str(&format!(
+                "From: {name} <{email}>\n\n",
+                name = parsed.author_name,
+                email = parsed.author_email
+            ));
+        }
+        if !parsed.body.is_empty() {
+            body.push_str(&parsed.body);
+            body.push('\n');
+        }
+        body.push_str("---\n");
+        body.push_str(&parsed.diff);
+
+        let id = message_id(i + 1);
+        messages.push(build_message(
+            &from,
+            &to,
+            &subject,
+            id.clone(),
+            thread_root.clone(),
+            body,
+        )?);
+        thread_root.get_or_insert(id);
+    }
+
+    Ok(messages)
+}
+
+fn build_message(
+    from: &Mailbox,
+    to: &[Mailbox],
+    subject: &str,
+    message_id: String,
+    in_reply_to: Option<String>,
+    body: String,
+) -> Result<Message> {
+    let mut builder = Message::builder()
+        .from(from.clone())
+        .subject(subject)
+        .message_id(Some(message_id))
+        .header(ContentType::TEXT_PLAIN);
+    for recipient in to {
+        builder = builder.to(recipient.clone());
+    }
+    if let Some(parent) = in_reply_to {
+        builder = builder.in_reply_to(parent.clone()).references(parent);
+    }
+    builder
+        .body(body)
+        .with_context(|| format!("Failed to build email '{subject}'"))
+}
+
+fn message_id_prefix() -> String {
+    let now = chrono::Utc::now();
+    format!(
+        "{timestamp}.{nanos}.gitsmith",
+        timestamp = now.timestamp(),
+        nanos = now.timestamp_subsec_nanos()
+    )
+}
+
+/// Send messages through the profile's SMTP server, in order
+pub async fn send_emails(profile: &SmtpProfile, messages: Vec<Message>) -> Result<()> {
+    let builder = match profile.encryption {
+        SmtpEncryption::Ssl => AsyncSmtpTransport::<Tokio1Executor>::relay(&profile.server)?,
+        SmtpEncryption::StartTls => {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&profile.server)?
+        }
+        SmtpEncryption::None => {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&profile.server)
+        }
+    };
+    let builder = match profile.port {
+        Some(port) => builder.port(port),
+        None => builder,
+    };
+    let builder = match (&profile.user, &profile.password) {
+        (Some(user), Some(password)) => {
+            builder.credentials(Credentials::new(user.clone(), password.clone()))
+        }
+        (Some(user), None) => bail!("No SMTP password configured for user {user}"),
+        _ => builder,
+    };
+    let transport = builder.build();
+
+    info!(server = %profile.server, count = messages.len(), "Sending patch emails");
+    for message in messages {
+        debug!(headers = ?message.headers().get_raw("Subject"), "Sending email");
+        transport.send(message).await.with_context(|| {
+            format!("Failed to send email via {server}", server = profile.server)
+        })?;
+    }
+
+    Ok(())
+}
+
+/// IMAP settings read from git's `imap` configuration (as used by `git imap-send`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImapProfile {
+    pub host: String,
+    pub port: Option<u16>,
+    pub user: String,
+    pub password: Option<String>,
+    /// Connect over TLS, unless `imap.host` uses the `imap://` scheme
+    pub tls: bool,
+}
+
+impl ImapProfile {
+    /// Load the profile from the repository's git configuration
+    pub fn from_git_config(repo_path: &Path) -> Result<Self> {
+        let repo = open_repository(repo_path)?;
+        let config = repo.config()?.snapshot()?;
+        let lookup = |key: &str| {
+            config
+                .get_string(&format!("imap.{key}"))
+                .ok()
+                .filter(|value| !value.is_empty())
+        };
+
+        let host = lookup("host").context("No IMAP server configured. Set imap.host")?;
+        let (host, tls) = match host.strip_prefix("imap://") {
+            Some(host) => (host.to_string(), false),
+            None => (
+                host.strip_prefix("imaps://").unwrap_or(&host).to_string(),
+                true,
+            ),
+        };
+        let port = lookup("port")
+            .map(|port| {
+                port.parse::<u16>()
+                    .with_context(|| format!("Invalid IMAP port: {port}"))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            host: host.trim_end_matches('/').to_string(),
+            port,
+            user: lookup("user").context("No IMAP user configured. Set imap.user")?,
+            password: lookup("pass"),
+            tls,
+        })
+    }
+}
+
+/// An email waiting in an IMAP folder
+#[derive(Debug, Clone)]
+pub struct InboxMessage {
+    pub uid: u32,
+    /// The message in RFC 5322 format
+    pub raw: Vec<u8>,
+}
+
+/// Connection an IMAP session runs over, with or without TLS
+trait ImapStream: AsyncRead + AsyncWrite + Unpin + Send + Debug {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + Debug> ImapStream for T {}
+
+type ImapSession = async_imap::Session<Box<dyn ImapStream>>;
+
+async fn imap_login(profile: &ImapProfile) -> Result<ImapSession> {
+    let port = profile.port.unwrap_or(if profile.tls { 993 } else { 143 });
+    let tcp = TcpStream::connect((profile.host.as_str(), port))
+        .await
+        .with_context(|| format!("Failed to reach {host}:{port}", host = profile.host))?;
+    let stream: Box<dyn ImapStream> = if profile.tls {
+        let roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.into(),
+        };
+        let config =
+            ClientConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()?
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+        let Ok(name) = ServerName::try_from(profile.host.clone()) else {
+            bail!("Invalid IMAP server name: {host}", host = profile.host);
+        };
+        Box::new(
+            TlsConnector::from(Arc::new(config))
+                .connect(name, tcp)
+                .await?,
+        )
+    } else {
+        Box::new(tcp)
+    };
+
+    let mut client = async_imap::Client::new(stream);
+    client
+        .read_response()
+        .await?
+        .context("IMAP server closed the connection")?;
+    let Some(password) = &profile.password else {
+        bail!(
+            "No IMAP password configured for user {user}",
+            user = profile.user
+        );
+    };
+    client
+        .login(&profile.user, password)
+        .await
+        .map_err(|(e, _)| e)
+        .with_context(|| format!("Failed to log in to {host}", host = profile.host))
+}
+
+/// Fetch the unseen messages of an IMAP folder without marking them seen
+pub async fn fetch_unseen(profile: &ImapProfile, folder: &str) -> Result<Vec<InboxMessage>> {
+    let mut session = imap_login(profile).await?;
+    session.select(folder).await?;
+    let uids = session.uid_search("UNSEEN").await?;
+    let mut messages = Vec::new();
+    if !uids.is_empty() {
+        let set = uid_set(uids);
+        let fetches: Vec<_> = session
+            .uid_fetch(&set, "BODY.PEEK[]")
+            .await?
+            .try_collect()
+            .await?;
+        messages.extend(fetches.iter().filter_map(|fetch| {
+            Some(InboxMessage {
+                uid: fetch.uid?,
+                raw: fetch.body()?.to_vec(),
+            })
+        }));
+    }
+    session.logout().await?;
+    debug!(%folder, count = messages.len(), "Fetched unseen emails");
+    Ok(messages)
+}
+
+/// Flag messages of an IMAP folder as seen
+pub async fn mark_seen(profile: &ImapProfile, folder: &str, uids: &[u32]) -> Result<()> {
+    if uids.is_empty() {
+        return Ok(());
+    }
+    let mut session = imap_login(profile).await?;
+    session.select(folder).await?;
+    session
+        .uid_store(uid_set(uids.iter().copied()), "+FLAGS.SILENT (\\Seen)")
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+    session.logout().await?;
+    Ok(())
+}
+
+/// IMAP sequence set listing `uids`
+fn uid_set(uids: impl IntoIterator<Item = u32>) -> String {
+    let mut uids: Vec<u32> = uids.into_iter().collect();
+    uids.sort_unstable();
+    uids.iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Repository;
+
+    const PATCH: &str = "From 1234567890abcdef1234567890abcdef12345678 Mon Sep 17 00:00:00 2001
+From: Bob Author <bob@example.org>
+Date: Tue, 02 Jan 2024 03:04:05 +0000
+Subject: Change b to c
+
+Explain the change.
+
+diff --git a/f.txt b/f.txt
+--- a/f.txt
++++ b/f.txt
+@@ -1,2 +1,2 @@
+ a
+-b
++c
+-- \n2.34.1
+";
+
+    fn options() -> PatchEmailOptions {
+        PatchEmailOptions {
+            from: "Alice <alice@example.com>".to_string(),
+            to: vec!["list@project.org".to_string()],
+            title: "Improve f".to_string(),
+            description: "Makes f better.".to_string(),
+        }
+    }
+
+    fn header(message: &Message, name: &str) -> Option<String> {
+        message.headers().get_raw(name).map(str::to_string)
+    }
+
+    #[test]
+    fn test_single_patch_email() {
+        let messages = build_patch_emails(&[PATCH.to_string()], &options()).unwrap();
+        assert_eq!(messages.len(), 1);
+
+        let message = &messages[0];
+        assert_eq!(
+            header(message, "Subject").as_deref(),
+            Some("[PATCH] Change b to c")
+        );
+        assert!(header(message, "In-Reply-To").is_none());
+
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+        assert!(formatted.contains("From: Bob Author <bob@example.org>"));
+        assert!(formatted.contains("Explain the change.\r\n---\r\ndiff --git a/f.txt b/f.txt"));
+    }
+
+    #[test]
+    fn test_series_is_threaded_under_cover_letter() {
+        let patches = vec![PATCH.to_string(), PATCH.to_string()];
+        let messages = build_patch_emails(&patches, &options()).unwrap();
+        assert_eq!(messages.len(), 3);
+
+        assert_eq!(
+            header(&messages[0], "Subject").as_deref(),
+            Some("[PATCH 0/2] Improve f")
+        );
+        assert_eq!(
+            header(&messages[2], "Subject").as_deref(),
+            Some("[PATCH 2/2] Change b to c")
+        );
+
+        let cover_id = header(&messages[0], "Message-ID").unwrap();
+        assert!(cover_id.ends_with("@example.com>"));
+        for patch in &messages[1..] {
+            assert_eq!(header(patch, "In-Reply-To"), Some(cover_id.clone()));
+            assert_eq!(header(patch, "References"), Some(cover_id.clone()));
+            assert_ne!(header(patch, "Message-ID"), Some(cover_id.clone()));
+        }
+    }
+
+    #[test]
+    fn test_profile_from_git_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Alice").unwrap();
+        config.set_str("user.email", "alice@example.com").unwrap();
+        config
+            .set_str("sendemail.smtpServer", "smtp.default.org")
+            .unwrap();
+        config
+            .set_str("sendemail.work.smtpServer", "smtp.work.org")
+            .unwrap();
+        config
+            .set_str("sendemail.work.smtpServerPort", "465")
+            .unwrap();
+        config
+            .set_str("sendemail.work.smtpEncryption", "ssl")
+            .unwrap();
+        config.set_str("sendemail.work.smtpUser", "alice").unwrap();
+
+        let work = SmtpProfile::from_git_config(dir.path(), Some("work")).unwrap();
+        assert_eq!(work.server, "smtp.work.org");
+        assert_eq!(work.port, Some(465));
+        assert_eq!(work.encryption, SmtpEncryption::Ssl);
+        assert_eq!(work.user.as_deref(), Some("alice"));
+        assert_eq!(work.from, "Alice <alice@example.com>");
+
+        let default = SmtpProfile::from_git_config(dir.path(), None).unwrap();
+        assert_eq!(default.server, "smtp.default.org");
+        assert_eq!(default.encryption, SmtpEncryption::StartTls);
+        assert_eq!(default.user, None);
+    }
+
+    #[test]
+    fn test_imap_profile_from_git_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        assert!(ImapProfile::from_git_config(dir.path()).is_err());
+
+        let mut config = repo.config().unwrap();
+        config
+            .set_str("imap.host", "imaps://imap.example.org")
+            .unwrap();
+        config.set_str("imap.user", "bridge").unwrap();
+        let profile = ImapProfile::from_git_config(dir.path()).unwrap();
+        assert_eq!(profile.host, "imap.example.org");
+        assert!(profile.tls);
+        assert_eq!(profile.password, None);
+
+        config.set_str("imap.host", "imap://localhost").unwrap();
+        config.set_str("imap.port", "1143").unwrap();
+        let profile = ImapProfile::from_git_config(dir.path()).unwrap();
+        assert_eq!(profile.host, "localhost");
+        assert!(!profile.tls);
+        assert_eq!(profile.port, Some(1143));
+
+        assert_eq!(uid_set([7, 3, 5]), "3,5,7");
+    }
+}
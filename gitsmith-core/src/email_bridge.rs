@@ -0,0 +1,464 @@
+//! Gateway between a repository's relays and a mailing list
+//!
+//! [`run_email_bridge`] emails the patch series of PRs opened on Nostr to a
+//! list in `git send-email` layout, then reads the replies that reached an
+//! IMAP folder and publishes them as comments on the PR their thread belongs
+//! to. Emails are matched to PRs through their `In-Reply-To` and `References`
+//! headers, so the Message-IDs of everything sent or ingested are kept in an
+//! [`EmailBridgeState`] in the git directory.
+
+use mail_parser::{HeaderValue, MessageParser};
+use nostr::nips::nip19::ToBech32;
+use nostr::{Event, EventId, Filter, Timestamp};
+use nostr_sdk::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::comments;
+use crate::email::{self, ImapProfile, PatchEmailOptions, SmtpProfile};
+use crate::error::{Context, GitsmithError, Result, bail};
+use crate::pull_request::{self, ListOptions, PullRequest, PullRequestStatus};
+use crate::relay::{self, Network};
+use crate::signer::{self, Signer};
+
+/// How long to wait for relays while bridging
+const BRIDGE_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where [`run_email_bridge`] sends patches and reads replies
+#[derive(Debug, Clone)]
+pub struct EmailBridgeOptions {
+    /// Mailing list addresses the series are sent to
+    pub to: Vec<String>,
+    pub smtp: SmtpProfile,
+    pub imap: ImapProfile,
+    /// IMAP folder replies arrive in, every unseen message in it is read
+    pub folder: String,
+}
+
+/// The Nostr side of an email
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MailEvent {
+    /// PR event ID (hex) of the thread
+    pub pr: String,
+    /// Event ID (hex) the email was sent from or published as
+    pub event: String,
+}
+
+/// What the email bridge sent and ingested so far
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct EmailBridgeState {
+    /// Only PRs opened from this time on are emailed, set by the first run
+    pub started_at: u64,
+    /// PR and revision event IDs (hex) already emailed
+    pub emailed: BTreeSet<String>,
+    /// Nostr side of every email sent or ingested, keyed by Message-ID
+    /// (without angle brackets)
+    pub messages: BTreeMap<String, MailEvent>,
+}
+
+impl EmailBridgeState {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read email bridge state from {path:?}"))?;
+
+        serde_json::from_str(&data).context("Failed to parse email bridge state")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {parent:?}"))?;
+        }
+
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(path, data)
+            .with_context(|| format!("Failed to write email bridge state to {path:?}"))
+    }
+
+    /// The thread a reply belongs to, by the closest message it refers to
+    pub fn thread_of(&self, reply: &MailReply) -> Option<&MailEvent> {
+        reply
+            .in_reply_to
+            .iter()
+            .chain(reply.references.iter().rev())
+            .find_map(|id| self.messages.get(id))
+    }
+}
+
+/// Path of the email bridge state of the repository at `repo_path`, inside its git directory
+pub fn email_bridge_state_path(repo_path: &Path) -> Result<PathBuf> {
+    let repo = git2::Repository::discover(repo_path)
+        .with_context(|| format!("Failed to open git repository at {repo_path:?}"))?;
+    Ok(repo.path().join("gitsmith").join("email-bridge.json"))
+}
+
+/// A patch series emailed by a bridge run
+#[derive(Debug, Clone, Serialize)]
+pub struct EmailedSeries {
+    /// PR or revision event ID (hex)
+    pub event_id: String,
+    pub title: String,
+    pub messages: usize,
+}
+
+/// Outcome of a bridge run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EmailBridgeReport {
+    pub emailed: Vec<EmailedSeries>,
+    /// Replies published as comments
+    pub comments: usize,
+    /// Emails that don't reply to a bridged thread
+    pub ignored: usize,
+    /// What could not be bridged and why
+    pub failed: Vec<String>,
+}
+
+/// Headers and text of an email read from the inbox
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MailReply {
+    pub message_id: String,
+    pub in_reply_to: Vec<String>,
+    pub references: Vec<String>,
+    /// Sender as `Name <address>`
+    pub from: String,
+    pub body: String,
+}
+
+/// Parse an RFC 5322 message, None for messages without Message-ID or text
+pub fn parse_reply(raw: &[u8]) -> Option<MailReply> {
+    let message = MessageParser::default().parse(raw)?;
+    let ids = |value: &HeaderValue| -> Vec<String> {
+        match value {
+            HeaderValue::Text(id) => vec![id.to_string()],
+            HeaderValue::TextList(ids) => ids.iter().map(|id| id.to_string()).collect(),
+            _ => Vec::new(),
+        }
+    };
+    let from = message.from().and_then(|from| from.first()).map(|from| {
+        match (from.name(), from.address()) {
+            (Some(name), Some(address)) => format!("{name} <{address}>"),
+            (name, address) => name.or(address).unwrap_or_default().to_string(),
+        }
+    });
+
+    Some(MailReply {
+        message_id: message.message_id()?.to_string(),
+        in_reply_to: ids(message.in_reply_to()),
+        references: ids(message.references()),
+        from: from.unwrap_or_default(),
+        body: message.body_text(0)?.trim_end().to_string(),
+    })
+}
+
+/// Content of the comment publishing an emailed reply
+pub fn reply_content(reply: &MailReply) -> String {
+    format!(
+        "{body}\n\n---\nReplied by email by {from}",
+        body = reply.body,
+        from = reply.from
+    )
+}
+
+/// Cover letter of an emailed series: the PR's description and where it came from
+pub fn series_description(description: &str, pr: &Event) -> Result<String> {
+    let origin = format!(
+        "Sent from Nostr: nostr:{id}\nOpened by nostr:{author}",
+        id = pr.id.to_bech32()?,
+        author = pr.pubkey.to_bech32()?
+    );
+    if description.trim().is_empty() {
+        Ok(origin)
+    } else {
+        Ok(format!(
+            "{description}\n\n---\n{origin}",
+            description = description.trim_end()
+        ))
+    }
+}
+
+/// Message-ID of a built email, without angle brackets
+fn message_id(message: &lettre::Message) -> Option<String> {
+    message.headers().get_raw("Message-ID").map(|id| {
+        id.trim()
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .to_string()
+    })
+}
+
+/// Email new patch series to the list and publish the replies as comments
+///
+/// PRs opened since the first run are emailed once per revision. Every unseen
+/// email in the IMAP folder is then read and flagged as seen; the replies to
+/// a bridged thread become comments. The state at `state_path` is saved after
+/// each series and once the inbox has been read.
+pub async fn run_email_bridge(
+    network: &Network,
+    signer: &dyn Signer,
+    repo_coordinate: &str,
+    relays: &[String],
+    options: &EmailBridgeOptions,
+    state_path: &Path,
+) -> Result<EmailBridgeReport> {
+    let mut state = EmailBridgeState::load(state_path)?;
+    if state.started_at == 0 {
+        state.started_at = Timestamp::now().as_u64();
+        state.save(state_path)?;
+    }
+
+    let list_options = ListOptions {
+        timeout: BRIDGE_FETCH_TIMEOUT,
+        since: Some(Timestamp::from(state.started_at)),
+        ..Default::default()
+    };
+    let prs = pull_request::list_pull_requests_with_options(
+        network,
+        repo_coordinate,
+        relays.to_vec(),
+        &list_options,
+    )
+    .await?;
+    let client = relay::connect(network, relays, Some(signer)).await?;
+
+    let mut report = EmailBridgeReport::default();
+    let pending: Vec<&PullRequest> = prs
+        .iter()
+        .filter(|pr| {
+            matches!(
+                pr.status,
+                PullRequestStatus::Open | PullRequestStatus::Updated
+            ) && !state.emailed.contains(pr.latest_event_id())
+        })
+        .collect();
+    for pr in pending {
+        match email_series(network, &client, pr, options).await {
+            Ok((series, ids)) => {
+                for id in ids {
+                    let event = MailEvent {
+                        pr: pr.id.clone(),
+                        event: pr.id.clone(),
+                    };
+                    state.messages.insert(id, event);
+                }
+                state.emailed.insert(series.event_id.clone());
+                report.emailed.push(series);
+                state.save(state_path)?;
+            }
+            Err(e) => {
+                warn!(pr = %pr.id, "Failed to email: {e}");
+                report
+                    .failed
+                    .push(format!("PR {id}: {e}", id = &pr.id[..8]));
+            }
+        }
+    }
+
+    let inbox = email::fetch_unseen(&options.imap, &options.folder).await?;
+    let mut read = Vec::new();
+    for message in &inbox {
+        let Some(reply) = parse_reply(&message.raw) else {
+            report.ignored += 1;
+            read.push(message.uid);
+            continue;
+        };
+        // Our own emails come back through the list
+        if state.messages.contains_key(&reply.message_id) {
+            read.push(message.uid);
+            continue;
+        }
+        let Some(thread) = state.thread_of(&reply).cloned() else {
+            report.ignored += 1;
+            read.push(message.uid);
+            continue;
+        };
+
+        match publish_reply(network, &client, signer, repo_coordinate, &thread, &reply).await {
+            Ok(event_id) => {
+                let event = MailEvent {
+                    pr: thread.pr,
+                    event: event_id,
+                };
+                state.messages.insert(reply.message_id, event);
+                report.comments += 1;
+                read.push(message.uid);
+            }
+            Err(e) => {
+                warn!(message_id = %reply.message_id, "Failed to publish reply: {e}");
+                report
+                    .failed
+                    .push(format!("Email {id}: {e}", id = reply.message_id));
+            }
+        }
+    }
+    state.save(state_path)?;
+    email::mark_seen(&options.imap, &options.folder, &read).await?;
+    client.disconnect().await;
+
+    Ok(report)
+}
+
+/// Email the latest patch series of a PR, returning the Message-IDs sent
+async fn email_series(
+    network: &Network,
+    client: &Client,
+    pr: &PullRequest,
+    options: &EmailBridgeOptions,
+) -> Result<(EmailedSeries, Vec<String>)> {
+    let revision = pr.latest_event_id();
+    let series =
+        pull_request::fetch_patch_series_with(network, client, revision, BRIDGE_FETCH_TIMEOUT)
+            .await?;
+    let messages = email::build_patch_emails(
+        &series.patch_contents(),
+        &PatchEmailOptions {
+            from: options.smtp.from.clone(),
+            to: options.to.clone(),
+            title: pr.title.clone(),
+            description: series_description(&pr.description, &series.pr)?,
+        },
+    )?;
+    let ids: Vec<String> = messages.iter().filter_map(message_id).collect();
+    let count = messages.len();
+    email::send_emails(&options.smtp, messages).await?;
+    info!(pr = %pr.id, count, "Emailed patch series");
+
+    let series = EmailedSeries {
+        event_id: revision.to_string(),
+        title: pr.title.clone(),
+        messages: count,
+    };
+    Ok((series, ids))
+}
+
+/// Publish a reply as a comment in its thread, returning the comment's event ID
+async fn publish_reply(
+    network: &Network,
+    client: &Client,
+    signer: &dyn Signer,
+    repo_coordinate: &str,
+    thread: &MailEvent,
+    reply: &MailReply,
+) -> Result<String> {
+    let ids = [&thread.pr, &thread.event]
+        .into_iter()
+        .map(|id| EventId::from_hex(id).with_context(|| format!("Invalid event ID: {id}")))
+        .collect::<Result<Vec<_>>>()?;
+    let events = relay::fetch_events(
+        network,
+        client,
+        vec![Filter::new().ids(ids)],
+        BRIDGE_FETCH_TIMEOUT,
+    )
+    .await?;
+    let find = |id: &str| events.iter().find(|event| event.id.to_hex() == id);
+    let Some(root) = find(&thread.pr) else {
+        return Err(GitsmithError::EventNotFound(format!(
+            "Pull request {pr}",
+            pr = thread.pr
+        )));
+    };
+    let parent = find(&thread.event).filter(|parent| parent.id != root.id);
+
+    let builder =
+        comments::comment_builder(root, parent, &reply_content(reply), Some(repo_coordinate))?;
+    let event = signer::sign(signer, builder).await?;
+    let result = relay::publish_events(client, std::slice::from_ref(&event)).await?;
+    if result.successes.is_empty() {
+        bail!("No relay accepted the comment");
+    }
+    Ok(event.id.to_hex())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPLY: &str = "Message-ID: <reply-1@example.org>\r
+In-Reply-To: <1700000000.1.gitsmith-1@example.com>\r
+References: <1700000000.1.gitsmith-0@example.com>\r
+ <1700000000.1.gitsmith-1@example.com>\r
+From: Carol Reviewer <carol@example.org>\r
+Subject: Re: [PATCH 1/2] Change b to c\r
+\r
+> -b\r
+> +c\r
+\r
+Why not d?\r
+";
+
+    #[test]
+    fn test_parse_reply() {
+        let reply = parse_reply(REPLY.as_bytes()).unwrap();
+        assert_eq!(reply.message_id, "reply-1@example.org");
+        assert_eq!(reply.in_reply_to, ["1700000000.1.gitsmith-1@example.com"]);
+        assert_eq!(reply.references.len(), 2);
+        assert_eq!(reply.from, "Carol Reviewer <carol@example.org>");
+        assert!(reply.body.ends_with("Why not d?"));
+        assert!(
+            reply_content(&reply)
+                .ends_with("Replied by email by Carol Reviewer <carol@example.org>")
+        );
+
+        assert!(parse_reply(b"Subject: no id\r\n\r\nbody\r\n").is_none());
+    }
+
+    #[test]
+    fn test_thread_of() {
+        let mut state = EmailBridgeState::default();
+        let reply = parse_reply(REPLY.as_bytes()).unwrap();
+        assert!(state.thread_of(&reply).is_none());
+
+        let cover = MailEvent {
+            pr: "aa".to_string(),
+            event: "aa".to_string(),
+        };
+        state.messages.insert(
+            "1700000000.1.gitsmith-0@example.com".to_string(),
+            cover.clone(),
+        );
+        assert_eq!(state.thread_of(&reply), Some(&cover));
+
+        // The message replied to wins over older references
+        let comment = MailEvent {
+            pr: "aa".to_string(),
+            event: "bb".to_string(),
+        };
+        state.messages.insert(
+            "1700000000.1.gitsmith-1@example.com".to_string(),
+            comment.clone(),
+        );
+        assert_eq!(state.thread_of(&reply), Some(&comment));
+    }
+
+    #[test]
+    fn test_emailed_message_ids() {
+        let patch = "From 1234567890abcdef1234567890abcdef12345678 Mon Sep 17 00:00:00 2001
+From: Bob <bob@example.org>
+Subject: Change b to c
+
+---
+ f.txt | 2 +-
+";
+        let messages = email::build_patch_emails(
+            &[patch.to_string()],
+            &PatchEmailOptions {
+                from: "Bridge <bridge@example.com>".to_string(),
+                to: vec!["list@example.org".to_string()],
+                title: "Change b".to_string(),
+                description: String::new(),
+            },
+        )
+        .unwrap();
+        let id = message_id(&messages[0]).unwrap();
+        assert!(id.ends_with("@example.com"));
+        assert!(!id.starts_with('<'));
+    }
+}
@@ -0,0 +1,214 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::apply::ApplyConflict;
+use crate::relay::RelayConnectionError;
+
+/// Result type of the gitsmith-core API
+pub type Result<T, E = GitsmithError> = std::result::Result<T, E>;
+
+/// Error from a library gitsmith-core builds on
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Errors returned by gitsmith-core
+///
+/// Errors wrapped with [`Context`] keep their cause, use [`GitsmithError::root`]
+/// to match on it.
+#[derive(Debug, thiserror::Error)]
+pub enum GitsmithError {
+    /// The password doesn't decrypt the account keys
+    #[error("Failed to decrypt key. Wrong password?")]
+    WrongPassword,
+    /// No account is logged in
+    #[error("No active account. Please login first")]
+    NoActiveAccount,
+    /// The path is not a git repository
+    #[error("Not a git repository: {}", .0.display())]
+    NotARepository(PathBuf),
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+    /// `git am` stopped on conflicts
+    #[error(transparent)]
+    Conflict(#[from] ApplyConflict),
+    /// None of the relays has the event
+    #[error("{0} not found on any relay")]
+    EventNotFound(String),
+    /// Relays couldn't be reached or didn't accept or return events
+    #[error(transparent)]
+    Relay(BoxError),
+    /// An event, key or address doesn't follow the Nostr protocol
+    #[error(transparent)]
+    Protocol(BoxError),
+    /// The signer couldn't be reached or refused to sign
+    #[error(transparent)]
+    Signer(BoxError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    /// An error with a message describing what was being done
+    #[error("{message}")]
+    Context {
+        message: String,
+        #[source]
+        source: Box<GitsmithError>,
+    },
+    #[error(transparent)]
+    Other(BoxError),
+}
+
+impl GitsmithError {
+    pub fn relay(error: impl Into<BoxError>) -> Self {
+        Self::Relay(error.into())
+    }
+
+    pub fn protocol(error: impl Into<BoxError>) -> Self {
+        Self::Protocol(error.into())
+    }
+
+    pub fn msg(message: impl fmt::Display) -> Self {
+        Self::Other(message.to_string().into())
+    }
+
+    /// The error under all context messages
+    pub fn root(&self) -> &GitsmithError {
+        match self {
+            Self::Context { source, .. } => source.root(),
+            error => error,
+        }
+    }
+}
+
+impl From<RelayConnectionError> for GitsmithError {
+    fn from(error: RelayConnectionError) -> Self {
+        Self::Relay(error.into())
+    }
+}
+
+macro_rules! impl_from {
+    ($variant:ident: $($error:ty),+ $(,)?) => {
+        $(
+            impl From<$error> for GitsmithError {
+                fn from(error: $error) -> Self {
+                    Self::$variant(error.into())
+                }
+            }
+        )+
+    };
+}
+
+impl_from!(
+    Relay: nostr_sdk::client::Error,
+    nostr_sdk::pool::pool::Error,
+    nostr_sdk::pool::relay::Error,
+);
+impl_from!(
+    Protocol: nostr::event::Error,
+    nostr::event::builder::Error,
+    nostr::event::tag::Error,
+    nostr::key::Error,
+    nostr::nips::nip01::Error,
+    nostr::nips::nip19::Error,
+    nostr::nips::nip49::Error,
+    nostr::nips::nip46::Error,
+    nostr::types::url::Error,
+);
+impl_from!(
+    Signer: nostr::signer::SignerError,
+    nostr_connect::error::Error,
+);
+impl_from!(
+    Other: reqwest::Error,
+    lettre::error::Error,
+    lettre::address::AddressError,
+    lettre::transport::smtp::Error,
+    async_imap::error::Error,
+    tokio_rustls::rustls::Error,
+    rusqlite::Error,
+    std::num::ParseIntError,
+    std::num::TryFromIntError,
+    toml_edit::TomlError,
+);
+
+#[cfg(feature = "lmdb")]
+impl From<heed::Error> for GitsmithError {
+    fn from(error: heed::Error) -> Self {
+        Self::Other(error.into())
+    }
+}
+
+impl From<std::convert::Infallible> for GitsmithError {
+    fn from(error: std::convert::Infallible) -> Self {
+        match error {}
+    }
+}
+
+/// Add a message to an error, like `anyhow::Context`
+pub trait Context<T> {
+    fn context<C: fmt::Display>(self, context: C) -> Result<T>;
+
+    fn with_context<C: fmt::Display, F: FnOnce() -> C>(self, f: F) -> Result<T>;
+}
+
+impl<T, E: Into<GitsmithError>> Context<T> for std::result::Result<T, E> {
+    fn context<C: fmt::Display>(self, context: C) -> Result<T> {
+        self.map_err(|error| GitsmithError::Context {
+            message: context.to_string(),
+            source: Box::new(error.into()),
+        })
+    }
+
+    fn with_context<C: fmt::Display, F: FnOnce() -> C>(self, f: F) -> Result<T> {
+        self.map_err(|error| GitsmithError::Context {
+            message: f().to_string(),
+            source: Box::new(error.into()),
+        })
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context<C: fmt::Display>(self, context: C) -> Result<T> {
+        self.ok_or_else(|| GitsmithError::msg(context))
+    }
+
+    fn with_context<C: fmt::Display, F: FnOnce() -> C>(self, f: F) -> Result<T> {
+        self.ok_or_else(|| GitsmithError::msg(f()))
+    }
+}
+
+/// Return early with a [`GitsmithError::Other`] message
+macro_rules! bail {
+    ($($arg:tt)*) => {
+        return Err($crate::error::GitsmithError::msg(format!($($arg)*)))
+    };
+}
+
+/// Return early with a [`GitsmithError::Other`] message unless the condition holds
+macro_rules! ensure {
+    ($condition:expr, $($arg:tt)*) => {
+        if !$condition {
+            $crate::error::bail!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use {bail, ensure};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_keeps_root_error() {
+        let error = Err::<(), _>(GitsmithError::WrongPassword)
+            .context("Failed to load keys")
+            .context("Failed to sign event")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Failed to sign event");
+        assert!(matches!(error.root(), GitsmithError::WrongPassword));
+
+        let error = None::<()>.context("Missing").unwrap_err();
+        assert!(matches!(error.root(), GitsmithError::Other(_)));
+        assert_eq!(error.to_string(), "Missing");
+    }
+}
@@ -1,15 +1,40 @@
-use anyhow::Result;
-use nostr::{Event, EventBuilder, FromBech32, Keys, Kind, PublicKey, Tag, TagKind};
+use nostr::nips::nip01::Coordinate;
+use nostr::nips::nip09::EventDeletionRequest;
+use nostr::{
+    Event, EventBuilder, EventId, FromBech32, Keys, Kind, PublicKey, Tag, TagKind, ToBech32,
+};
 use std::borrow::Cow;
+use std::cmp::Ordering;
 
+use crate::error::{Context, GitsmithError, Result, ensure};
+use crate::signer::{self, Signer};
 use crate::types::*;
+use crate::{grasp, license, relay};
 
 pub const KIND_GIT_REPO_ANNOUNCEMENT: u16 = 30617;
 pub const KIND_GIT_STATE: u16 = 30618;
 pub const KIND_GIT_PATCH: u16 = 1617;
+pub const KIND_GIT_STATUS_OPEN: u16 = 1630;
+pub const KIND_GIT_STATUS_APPLIED: u16 = 1631;
+pub const KIND_GIT_STATUS_CLOSED: u16 = 1632;
+pub const KIND_GIT_STATUS_DRAFT: u16 = 1633;
 
 /// Build repository announcement event (NIP-34 compatible)
-pub fn build_announcement_event(announcement: &RepoAnnouncement, keys: &Keys) -> Result<Event> {
+pub async fn build_announcement_event(
+    announcement: &RepoAnnouncement,
+    signer: &dyn Signer,
+) -> Result<Event> {
+    let builder = announcement_builder(announcement, &signer.public_key())?;
+    let event = signer::sign(signer, builder).await?;
+
+    Ok(event)
+}
+
+/// Unsigned repository announcement of `owner`, to preview it before signing
+pub fn announcement_builder(
+    announcement: &RepoAnnouncement,
+    owner: &PublicKey,
+) -> Result<EventBuilder> {
     let mut tags = vec![
         Tag::identifier(&announcement.identifier),
         Tag::custom(
@@ -26,24 +51,40 @@ pub fn build_announcement_event(announcement: &RepoAnnouncement, keys: &Keys) ->
         ));
     }
 
-    // Add root commit
+    // Add root commit, marked as the earliest unique commit (NIP-34)
     tags.push(Tag::custom(
         TagKind::Custom(Cow::Borrowed("r")),
-        vec![announcement.root_commit.clone()],
+        vec![announcement.root_commit.clone(), "euc".to_string()],
     ));
 
+    // GRASP servers are listed both as a clone URL and as a relay
+    let mut clone_urls = announcement.clone_urls.clone();
+    let mut relays = announcement.relays.clone();
+    for server in &announcement.grasp_servers {
+        let server = grasp::normalize_server(server)?;
+        let clone_url = grasp::clone_url(&server, owner, &announcement.identifier)?;
+        if !clone_urls.contains(&clone_url) {
+            clone_urls.push(clone_url);
+        }
+        let relay_url = grasp::relay_url(&server);
+        if !relays
+            .iter()
+            .any(|known| relay::same_relay(known, &relay_url))
+        {
+            relays.push(relay_url);
+        }
+    }
+
     // Add clone URLs
-    if !announcement.clone_urls.is_empty() {
-        let mut clone_tag = vec!["clone".to_string()];
-        clone_tag.extend(announcement.clone_urls.clone());
+    if !clone_urls.is_empty() {
         tags.push(Tag::custom(
             TagKind::Custom(Cow::Borrowed("clone")),
-            clone_tag[1..].to_vec(),
+            clone_urls,
         ));
     }
 
     // Add relays
-    for relay in &announcement.relays {
+    for relay in &relays {
         tags.push(Tag::custom(
             TagKind::Custom(Cow::Borrowed("relays")),
             vec![relay.clone()],
@@ -67,15 +108,187 @@ pub fn build_announcement_event(announcement: &RepoAnnouncement, keys: &Keys) ->
         }
     }
 
-    let event = EventBuilder::new(Kind::from(KIND_GIT_REPO_ANNOUNCEMENT), "")
-        .tags(tags)
-        .sign_with_keys(keys)?;
+    // CI systems whose check reports on PRs are trusted
+    let ci_keys: Vec<String> = announcement
+        .ci_keys
+        .iter()
+        .filter_map(|key| PublicKey::parse(key).ok())
+        .map(|key| key.to_hex())
+        .collect();
+    if !ci_keys.is_empty() {
+        tags.push(Tag::custom(TagKind::Custom(Cow::Borrowed("ci")), ci_keys));
+    }
 
-    Ok(event)
+    // Advertise the proof-of-work requirement for inbound PRs and comments
+    if announcement.min_pow > 0 {
+        tags.push(Tag::custom(
+            TagKind::Custom(Cow::Borrowed("min-pow")),
+            vec![announcement.min_pow.to_string()],
+        ));
+    }
+
+    if let Some(license) = &announcement.license {
+        license::validate_license(license)?;
+        tags.push(Tag::custom(
+            TagKind::Custom(Cow::Borrowed("license")),
+            vec![license.clone()],
+        ));
+    }
+
+    for topic in &announcement.topics {
+        tags.push(Tag::hashtag(topic));
+    }
+
+    // Clients can show the project description without cloning
+    if let Some(readme) = &announcement.readme {
+        tags.push(Tag::custom(
+            TagKind::Custom(Cow::Borrowed("readme")),
+            vec![readme.clone()],
+        ));
+    }
+
+    Ok(EventBuilder::new(Kind::from(KIND_GIT_REPO_ANNOUNCEMENT), "").tags(tags))
+}
+
+/// Parse a repository announcement event (Kind 30617)
+pub fn parse_announcement_event(event: &Event) -> Result<RepoAnnouncement> {
+    if event.kind != Kind::from(KIND_GIT_REPO_ANNOUNCEMENT) {
+        return Err(GitsmithError::protocol(format!(
+            "Event {id} is not a repository announcement",
+            id = event.id
+        )));
+    }
+
+    let first = |tag_name: &str| {
+        tag_values(event, tag_name)
+            .first()
+            .map(|value| value.to_string())
+    };
+    let list = |tag_name: &str| -> Vec<String> {
+        list_tag_values(event, tag_name)
+            .into_iter()
+            .map(String::from)
+            .collect()
+    };
+
+    let identifier = first("d").context("Announcement is missing the d tag")?;
+    let name = first("name").unwrap_or_else(|| identifier.clone());
+    let description = first("description").unwrap_or_default();
+    let root_commit = earliest_unique_commit(event).unwrap_or_default();
+
+    // Maintainers can be listed as p tags (gitsmith) or a maintainers tag (NIP-34)
+    let mut maintainers = Vec::new();
+    for value in tag_values(event, "p")
+        .into_iter()
+        .chain(list_tag_values(event, "maintainers"))
+    {
+        if let Ok(pubkey) = PublicKey::parse(value)
+            && let Ok(npub) = pubkey.to_bech32()
+            && !maintainers.contains(&npub)
+        {
+            maintainers.push(npub);
+        }
+    }
+
+    let ci_keys = list_tag_values(event, "ci")
+        .into_iter()
+        .filter_map(|value| PublicKey::parse(value).ok()?.to_bech32().ok())
+        .collect();
+
+    let min_pow = first("min-pow").and_then(|v| v.parse().ok()).unwrap_or(0);
+
+    let clone_urls = list("clone");
+    let relays = list("relays");
+    let grasp_servers = grasp::servers_in(&clone_urls, &relays, &event.pubkey, &identifier);
+
+    Ok(RepoAnnouncement {
+        identifier,
+        name,
+        description,
+        clone_urls,
+        relays,
+        web: list("web"),
+        root_commit,
+        maintainers,
+        ci_keys,
+        grasp_servers,
+        blossom_servers: vec![],
+        min_pow,
+        license: first("license"),
+        topics: tag_values(event, "t")
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        readme: first("readme"),
+    })
+}
+
+/// Earliest unique commit an announcement names: the `r` tag marked `euc`, or
+/// the first `r` tag of announcements published without the marker
+pub fn earliest_unique_commit(event: &Event) -> Option<String> {
+    let commits: Vec<&[String]> = event
+        .tags
+        .iter()
+        .map(|tag| tag.as_slice())
+        .filter(|tag| tag.len() >= 2 && tag[0] == "r")
+        .collect();
+    commits
+        .iter()
+        .find(|tag| tag.get(2).is_some_and(|marker| marker == "euc"))
+        .or_else(|| commits.first())
+        .map(|tag| tag[1].clone())
+}
+
+/// Parse a repository state event (Kind 30618) back into its refs
+pub fn parse_state_event(event: &Event) -> Result<GitState> {
+    if event.kind != Kind::from(KIND_GIT_STATE) {
+        return Err(GitsmithError::protocol(format!(
+            "Event {id} is not a repository state",
+            id = event.id
+        )));
+    }
+
+    let identifier = tag_values(event, "d")
+        .first()
+        .map(|identifier| identifier.to_string())
+        .context("State is missing the d tag")?;
+    let refs = event
+        .tags
+        .iter()
+        .filter_map(|tag| match tag.as_slice() {
+            [name, value, ..] if name == "HEAD" || name.starts_with("refs/") => {
+                Some((name.clone(), value.clone()))
+            }
+            _ => None,
+        })
+        .collect();
+
+    Ok(GitState { identifier, refs })
+}
+
+/// First value of every tag with the given name
+pub(crate) fn tag_values<'a>(event: &'a Event, tag_name: &str) -> Vec<&'a str> {
+    event
+        .tags
+        .iter()
+        .filter(|tag| tag.as_slice().len() > 1 && tag.as_slice()[0] == tag_name)
+        .map(|tag| tag.as_slice()[1].as_str())
+        .collect()
+}
+
+/// Every value of the tags with the given name, for NIP-34 tags listing
+/// several values in one tag (`clone`, `relays`, `web`, `maintainers`)
+fn list_tag_values<'a>(event: &'a Event, tag_name: &str) -> Vec<&'a str> {
+    event
+        .tags
+        .iter()
+        .filter(|tag| tag.as_slice().first().is_some_and(|name| name == tag_name))
+        .flat_map(|tag| tag.as_slice()[1..].iter().map(String::as_str))
+        .collect()
 }
 
 /// Build git state event
-pub fn build_state_event(state: &GitState, keys: &Keys) -> Result<Event> {
+pub async fn build_state_event(state: &GitState, signer: &dyn Signer) -> Result<Event> {
     let mut tags = vec![Tag::identifier(&state.identifier)];
 
     // Add all refs
@@ -94,9 +307,259 @@ pub fn build_state_event(state: &GitState, keys: &Keys) -> Result<Event> {
         ));
     }
 
-    let event = EventBuilder::new(Kind::from(KIND_GIT_STATE), "")
-        .tags(tags)
-        .sign_with_keys(keys)?;
+    let builder = EventBuilder::new(Kind::from(KIND_GIT_STATE), "").tags(tags);
+    let event = signer::sign(signer, builder).await?;
+
+    Ok(event)
+}
+
+/// Build a NIP-09 deletion request retracting a repository announcement
+///
+/// The `a` tags cover every version of the announcement, and of the state with
+/// `include_state`, up to now; `event_ids` name the versions relays hold, for
+/// relays that only delete by ID.
+pub async fn build_retraction_event(
+    identifier: &str,
+    event_ids: &[EventId],
+    include_state: bool,
+    reason: Option<&str>,
+    signer: &dyn Signer,
+) -> Result<Event> {
+    let owner = signer.public_key();
+    let mut kinds = vec![KIND_GIT_REPO_ANNOUNCEMENT];
+    if include_state {
+        kinds.push(KIND_GIT_STATE);
+    }
+
+    let mut request = EventDeletionRequest::new()
+        .coordinates(
+            kinds
+                .into_iter()
+                .map(|kind| Coordinate::new(Kind::from(kind), owner).identifier(identifier)),
+        )
+        .ids(event_ids.iter().copied());
+    if let Some(reason) = reason {
+        request = request.reason(reason);
+    }
+
+    signer::sign(signer, EventBuilder::delete(request)).await
+}
+
+/// Newest events first, events created in the same second ordered by ID
+///
+/// Sorting with this puts the replaceable event that wins first, since NIP-01
+/// keeps the lowest ID on a `created_at` tie.
+pub fn newest_first(a: &Event, b: &Event) -> Ordering {
+    b.created_at
+        .cmp(&a.created_at)
+        .then_with(|| a.id.cmp(&b.id))
+}
 
+/// Build a NIP-34 status event (kinds 1630-1633) for a PR, patch or issue
+pub fn build_status_event(
+    status_kind: u16,
+    target_id: &EventId,
+    target_author: &PublicKey,
+    repo_coordinate: &str,
+    message: &str,
+    keys: &Keys,
+) -> Result<Event> {
+    let event = status_builder(
+        status_kind,
+        target_id,
+        target_author,
+        repo_coordinate,
+        message,
+    )?
+    .sign_with_keys(keys)?;
     Ok(event)
 }
+
+/// Start a NIP-34 status event, returned unsigned so callers can attach extra
+/// tags (e.g. the commits a PR was applied as)
+pub fn status_builder(
+    status_kind: u16,
+    target_id: &EventId,
+    target_author: &PublicKey,
+    repo_coordinate: &str,
+    message: &str,
+) -> Result<EventBuilder> {
+    ensure!(
+        (KIND_GIT_STATUS_OPEN..=KIND_GIT_STATUS_DRAFT).contains(&status_kind),
+        "Invalid status kind {status_kind}"
+    );
+
+    let mut tags = vec![
+        Tag::custom(
+            TagKind::Custom(Cow::Borrowed("e")),
+            vec![target_id.to_hex(), String::new(), "root".to_string()],
+        ),
+        Tag::custom(
+            TagKind::Custom(Cow::Borrowed("a")),
+            vec![repo_coordinate.to_string()],
+        ),
+        Tag::public_key(*target_author),
+    ];
+
+    // Notify the repository owner as well
+    if let Some(owner) = repo_coordinate
+        .split(':')
+        .nth(1)
+        .and_then(|pk| PublicKey::from_hex(pk).ok())
+        .filter(|owner| owner != target_author)
+    {
+        tags.push(Tag::public_key(owner));
+    }
+
+    Ok(EventBuilder::new(Kind::from(status_kind), message).tags(tags))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_build_retraction_event() {
+        let keys = Keys::generate();
+        let announcement_id = EventId::all_zeros();
+        let event = build_retraction_event(
+            "repo",
+            &[announcement_id],
+            true,
+            Some("Published by mistake"),
+            &keys,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(event.kind, Kind::EventDeletion);
+        assert_eq!(event.content, "Published by mistake");
+        let values = |name: &str| {
+            event
+                .tags
+                .iter()
+                .filter(|tag| tag.kind().as_str() == name)
+                .filter_map(|tag| tag.content().map(String::from))
+                .collect::<Vec<_>>()
+        };
+        let owner = keys.public_key().to_hex();
+        assert_eq!(
+            values("a"),
+            [format!("30617:{owner}:repo"), format!("30618:{owner}:repo")]
+        );
+        assert_eq!(values("e"), [announcement_id.to_hex()]);
+    }
+
+    #[tokio::test]
+    async fn test_announcement_round_trip() {
+        let keys = Keys::generate();
+        let maintainer = Keys::generate().public_key().to_bech32().unwrap();
+        let ci_key = Keys::generate().public_key().to_bech32().unwrap();
+        let announcement = RepoAnnouncement {
+            identifier: "gitsmith".to_string(),
+            name: "gitsmith".to_string(),
+            description: "Publish git repositories to Nostr".to_string(),
+            clone_urls: vec!["https://example.com/gitsmith.git".to_string()],
+            relays: vec!["wss://relay.one".to_string(), "wss://relay.two".to_string()],
+            web: vec!["https://example.com".to_string()],
+            root_commit: "a".repeat(40),
+            maintainers: vec![maintainer.clone()],
+            ci_keys: vec![ci_key.clone()],
+            grasp_servers: vec!["wss://grasp.example.com".to_string()],
+            blossom_servers: vec![],
+            min_pow: 16,
+            license: Some("MIT OR Apache-2.0".to_string()),
+            topics: vec!["git".to_string(), "nostr".to_string()],
+            readme: Some(format!(
+                "30023:{owner}:gitsmith-readme",
+                owner = keys.public_key().to_hex()
+            )),
+        };
+
+        let event = build_announcement_event(&announcement, &keys)
+            .await
+            .unwrap();
+        let parsed = parse_announcement_event(&event).unwrap();
+
+        assert_eq!(parsed.identifier, announcement.identifier);
+        assert_eq!(parsed.description, announcement.description);
+        let grasp_clone_url = grasp::clone_url(
+            "https://grasp.example.com",
+            &keys.public_key(),
+            &announcement.identifier,
+        )
+        .unwrap();
+        assert_eq!(parsed.clone_urls[..1], announcement.clone_urls);
+        assert_eq!(parsed.clone_urls[1..], [grasp_clone_url]);
+        assert_eq!(parsed.relays[..2], announcement.relays);
+        assert_eq!(parsed.relays[2..], ["wss://grasp.example.com"]);
+        assert_eq!(parsed.grasp_servers, ["https://grasp.example.com"]);
+        assert_eq!(parsed.web, announcement.web);
+        assert_eq!(parsed.root_commit, announcement.root_commit);
+        assert_eq!(parsed.maintainers, vec![maintainer]);
+        assert_eq!(parsed.ci_keys, vec![ci_key]);
+        assert_eq!(parsed.license, announcement.license);
+        assert_eq!(parsed.topics, announcement.topics);
+        assert_eq!(parsed.readme, announcement.readme);
+        assert_eq!(parsed.min_pow, 16);
+        assert!(event.tags.iter().any(|tag| tag.as_slice()
+            == [
+                "r".to_string(),
+                announcement.root_commit.clone(),
+                "euc".to_string()
+            ]));
+    }
+
+    #[test]
+    fn test_earliest_unique_commit() {
+        let keys = Keys::generate();
+        let event = |tags: Vec<Vec<&str>>| {
+            EventBuilder::new(Kind::from(KIND_GIT_REPO_ANNOUNCEMENT), "")
+                .tags(tags.into_iter().map(|tag| Tag::parse(tag).unwrap()))
+                .sign_with_keys(&keys)
+                .unwrap()
+        };
+
+        let marked = event(vec![
+            vec!["d", "repo"],
+            vec!["r", "https://example.com"],
+            vec!["r", "abc123", "euc"],
+        ]);
+        assert_eq!(earliest_unique_commit(&marked).as_deref(), Some("abc123"));
+        let unmarked = event(vec![vec!["d", "repo"], vec!["r", "abc123"]]);
+        assert_eq!(earliest_unique_commit(&unmarked).as_deref(), Some("abc123"));
+        assert_eq!(
+            earliest_unique_commit(&event(vec![vec!["d", "repo"]])),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_state_round_trip() {
+        let keys = Keys::generate();
+        let state = GitState {
+            identifier: "gitsmith".to_string(),
+            refs: [
+                ("refs/heads/main".to_string(), "a".repeat(40)),
+                ("refs/tags/v1.0".to_string(), "b".repeat(40)),
+                ("HEAD".to_string(), "ref: refs/heads/main".to_string()),
+            ]
+            .into(),
+        };
+
+        let event = build_state_event(&state, &keys).await.unwrap();
+        let parsed = parse_state_event(&event).unwrap();
+
+        assert_eq!(parsed.identifier, state.identifier);
+        assert_eq!(parsed.refs, state.refs);
+    }
+
+    #[test]
+    fn test_parse_announcement_rejects_other_kinds() {
+        let keys = Keys::generate();
+        let event = EventBuilder::text_note("hello")
+            .sign_with_keys(&keys)
+            .unwrap();
+        assert!(parse_announcement_event(&event).is_err());
+    }
+}
@@ -0,0 +1,1371 @@
+//! Patches in `git format-patch` layout
+//!
+//! [`format_commit`] renders a commit byte for byte the way `git format-patch`
+//! does: RFC 2047 encoded headers, the author date in the author's timezone,
+//! the `---` diffstat and summary, and rename detection following the
+//! repository's `diff.renames`. Only the version in the signature footer and
+//! the compressed data of binary files differ from what git writes.
+//!
+//! [`write_patch_files`] and [`mbox`] save patches the way `git format-patch`
+//! does with `-o` and `--stdout`, [`split_mbox`] and [`import_series`] read
+//! them back from an mbox or a mail thread.
+
+use base64::Engine;
+use git2::{Delta, DiffOptions, Repository};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::apply;
+use crate::error::{Context, GitsmithError, Result, ensure};
+
+/// Version line of the signature footer
+const FOOTER_VERSION: &str = "2.34.1";
+
+/// Header lines are wrapped at this column
+const MAX_HEADER_LENGTH: usize = 78;
+
+/// Lines holding RFC 2047 encoded words are wrapped at this column
+const MAX_ENCODED_LENGTH: usize = 76;
+
+/// Longest patch file name `git format-patch` writes
+const PATCH_NAME_MAX: usize = 64;
+
+const PATCH_SUFFIX: &str = ".patch";
+
+/// Full score of git's similarity estimate
+const MAX_SCORE: usize = 60000;
+
+/// Modulus of the chunk hashes in git's similarity estimate
+const SPAN_HASH_BASE: u32 = 107927;
+
+/// Width of the diffstat, the mail wrap width `git format-patch` uses
+const STAT_WIDTH: usize = 72;
+
+/// Render a commit as `git format-patch` does
+///
+/// `position` is the patch number and the size of its series, single patches
+/// get a plain `[PATCH]` subject prefix.
+pub fn format_commit(
+    repo: &Repository,
+    commit: &git2::Commit,
+    position: Option<(usize, usize)>,
+) -> Result<String> {
+    let parent = if commit.parent_count() > 0 {
+        Some(commit.parent(0)?)
+    } else {
+        None
+    };
+    let tree = commit.tree()?;
+    let parent_tree = parent.as_ref().map(|p| p.tree()).transpose()?;
+
+    let author = commit.author();
+    let message = String::from_utf8_lossy(commit.message_bytes()).into_owned();
+    let (title, body) = split_message(&message);
+
+    let mut patch = format!(
+        "From {commit_id} Mon Sep 17 00:00:00 2001\n",
+        commit_id = commit.id()
+    );
+    patch.push_str(&from_header(
+        &String::from_utf8_lossy(author.name_bytes()),
+        &String::from_utf8_lossy(author.email_bytes()),
+    ));
+    patch.push_str(&format!(
+        "Date: {date}\n",
+        date = format_date(author.when())
+    ));
+    let prefix = match position {
+        Some((number, total)) if total > 1 => {
+            let width = total.to_string().len();
+            format!("[PATCH {number:0width$}/{total}]")
+        }
+        _ => "[PATCH]".to_string(),
+    };
+    patch.push_str(&subject_header(&prefix, &title));
+    if !message.is_ascii() {
+        patch.push_str(
+            "MIME-Version: 1.0\n\
+             Content-Type: text/plain; charset=UTF-8\n\
+             Content-Transfer-Encoding: 8bit\n",
+        );
+    }
+    patch.push('\n');
+    patch.push_str(&body);
+
+    let diff = commit_diff(repo, parent_tree.as_ref(), &tree)?;
+    patch.push_str("---\n");
+    if !diff.files.is_empty() {
+        patch.push_str(&format_diffstat(&diff.files));
+        patch.push_str(&diff.summary);
+        patch.push('\n');
+        patch.push_str(&diff.text);
+    }
+
+    patch.push_str(&format!("-- \n{FOOTER_VERSION}\n\n"));
+    Ok(patch)
+}
+
+/// File name `git format-patch` gives a patch, like `0001-Fix-the-frobnicator.patch`
+///
+/// Runs of characters other than ASCII letters, digits, `.` and `_` become a
+/// single `-`, and the name is cut to 64 characters.
+pub fn patch_file_name(number: usize, subject: &str) -> String {
+    let mut name = format!("{number:04}-");
+    let start = name.len();
+    // 2 before the first title character, 1 after a separator
+    let mut space = 2;
+    let mut bytes = subject.bytes().peekable();
+    while let Some(byte) = bytes.next() {
+        if byte.is_ascii_alphanumeric() || byte == b'.' || byte == b'_' {
+            if space == 1 {
+                name.push('-');
+            }
+            space = 0;
+            name.push(char::from(byte));
+            if byte == b'.' {
+                while bytes.next_if_eq(&b'.').is_some() {}
+            }
+        } else {
+            space |= 1;
+        }
+    }
+    let trimmed = start + name[start..].trim_end_matches(['.', '-']).len();
+    name.truncate(trimmed.min(PATCH_NAME_MAX - PATCH_SUFFIX.len() - 1));
+    name.push_str(PATCH_SUFFIX);
+    name
+}
+
+/// Write patches to numbered files in `dir`, returning their paths
+pub fn write_patch_files(patches: &[String], dir: &Path) -> Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create {dir}", dir = dir.display()))?;
+    patches
+        .iter()
+        .enumerate()
+        .map(|(i, patch)| {
+            let subject = apply::parse_patch(patch)
+                .map(|parsed| parsed.subject)
+                .unwrap_or_default();
+            let path = dir.join(patch_file_name(i + 1, &subject));
+            fs::write(&path, with_from_line(patch))
+                .with_context(|| format!("Failed to write {path}", path = path.display()))?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Patches as a single mbox, the way `git format-patch --stdout` writes them
+pub fn mbox(patches: &[String]) -> String {
+    patches
+        .iter()
+        .map(|patch| with_from_line(patch))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A patch starting with the `From <commit>` line mbox readers split on
+///
+/// Patches of other clients may lack it, they get git's placeholder commit.
+fn with_from_line(patch: &str) -> String {
+    let mut patch = if patch.starts_with("From ") {
+        patch.to_string()
+    } else {
+        format!(
+            "From {commit} Mon Sep 17 00:00:00 2001\n{patch}",
+            commit = "0".repeat(40)
+        )
+    };
+    if !patch.ends_with('\n') {
+        patch.push('\n');
+    }
+    patch
+}
+
+/// Headers kept when importing mail, the ones `git format-patch` writes
+const PATCH_HEADERS: [&str; 6] = [
+    "From:",
+    "Date:",
+    "Subject:",
+    "MIME-Version:",
+    "Content-Type:",
+    "Content-Transfer-Encoding:",
+];
+
+/// Patches and cover letter read from an mbox or patch files
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportedSeries {
+    /// The `[PATCH 0/N]` message, if the series has one
+    pub cover_letter: Option<String>,
+    /// Patches in series order
+    pub patches: Vec<String>,
+}
+
+/// Split an mbox into its messages
+///
+/// Messages start at `From ` lines ending in a date, as `git mailsplit`
+/// recognizes them, so `From ` in a message body doesn't split it.
+pub fn split_mbox(mbox: &str) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut current: Option<String> = None;
+    for line in mbox.split_inclusive('\n') {
+        if is_from_line(line) {
+            messages.extend(current.take());
+            current = Some(String::new());
+        }
+        if let Some(message) = current.as_mut() {
+            message.push_str(line);
+        }
+    }
+    messages.extend(current);
+    messages
+}
+
+/// Whether a line is an mbox `From <sender> <date>` separator
+fn is_from_line(line: &str) -> bool {
+    let line = line.trim_end_matches(['\r', '\n']);
+    let Some(rest) = line.strip_prefix("From ") else {
+        return false;
+    };
+    if line.len() < 19 {
+        return false;
+    }
+    // The time's last colon, with digits around it and a year after
+    let Some(colon) = rest.rfind(':') else {
+        return false;
+    };
+    let bytes = rest.as_bytes();
+    let digit = |i: Option<usize>| i.and_then(|i| bytes.get(i)).is_some_and(u8::is_ascii_digit);
+    let year = rest[colon + 1..]
+        .get(2..)
+        .and_then(|year| year.split_whitespace().next())
+        .and_then(|year| year.parse::<u32>().ok());
+    [
+        colon.checked_sub(4),
+        colon.checked_sub(2),
+        colon.checked_sub(1),
+    ]
+    .into_iter()
+    .chain([Some(colon + 1), Some(colon + 2)])
+    .all(digit)
+        && year.is_some_and(|year| year > 90)
+}
+
+/// Read the `.patch` files of a directory in name order
+pub fn read_patch_dir(dir: &Path) -> Result<Vec<String>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {dir}", dir = dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<_>>()?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "patch"));
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| {
+            fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {path}", path = path.display()))
+        })
+        .collect()
+}
+
+/// Sort mail into a patch series
+///
+/// Transport headers (`Received`, `To`, `Message-Id`, ...) are dropped so
+/// patches read like `git format-patch` output. Replies and other messages
+/// without a diff are skipped, and numbered patches are put in order.
+pub fn import_series(messages: &[String]) -> Result<ImportedSeries> {
+    let mut series = ImportedSeries::default();
+    let mut numbered = Vec::new();
+    for message in messages {
+        let message = strip_transport_headers(&message.replace("\r\n", "\n"));
+        let Ok(parsed) = apply::parse_patch(&message) else {
+            continue;
+        };
+        let number = patch_number(&message);
+        if number == Some(0) {
+            if series.cover_letter.is_none() {
+                series.cover_letter = Some(message);
+            }
+            continue;
+        }
+        if parsed.diff.is_empty() || is_reply(&message) {
+            continue;
+        }
+        numbered.push((number.unwrap_or(usize::MAX), message));
+    }
+    // Stable, so unnumbered patches keep their order
+    numbered.sort_by_key(|(number, _)| *number);
+    series.patches = numbered.into_iter().map(|(_, patch)| patch).collect();
+    ensure!(!series.patches.is_empty(), "No patches found");
+    Ok(series)
+}
+
+/// Keep the `From <commit>` line, the headers `git format-patch` writes and the body
+fn strip_transport_headers(message: &str) -> String {
+    let Some((headers, body)) = message.split_once("\n\n") else {
+        return message.to_string();
+    };
+    let mut kept = String::with_capacity(message.len());
+    let mut keep = false;
+    for (i, line) in headers.lines().enumerate() {
+        if line.starts_with([' ', '\t']) {
+            // Continuation of a folded header
+        } else if i == 0 && line.starts_with("From ") {
+            keep = true;
+        } else {
+            keep = PATCH_HEADERS.iter().any(|header| {
+                line.get(..header.len())
+                    .is_some_and(|name| name.eq_ignore_ascii_case(header))
+            });
+        }
+        if keep {
+            kept.push_str(line);
+            kept.push('\n');
+        }
+    }
+    kept.push('\n');
+    kept.push_str(body);
+    kept
+}
+
+/// Decoded `Subject` header value of a message
+fn subject(message: &str) -> Option<String> {
+    let mut lines = message.lines().take_while(|line| !line.is_empty());
+    let mut subject = lines
+        .find_map(|line| line.strip_prefix("Subject: "))?
+        .to_string();
+    for line in lines.take_while(|line| line.starts_with([' ', '\t'])) {
+        subject.push_str(line);
+    }
+    Some(decode_header(&subject))
+}
+
+/// Number of a patch from its `[PATCH n/m]` subject prefix
+fn patch_number(message: &str) -> Option<usize> {
+    let subject = subject(message)?;
+    let (marker, _) = subject.trim_start().strip_prefix('[')?.split_once(']')?;
+    let (position, _total) = marker.rsplit_once('/')?;
+    position.split_whitespace().last()?.parse().ok()
+}
+
+fn is_reply(message: &str) -> bool {
+    subject(message).is_some_and(|subject| {
+        subject
+            .trim_start()
+            .get(..3)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("re:"))
+    })
+}
+
+/// Subject and body of a commit message as `git log --pretty=email` prints them
+///
+/// The subject joins the lines of the first paragraph. Body lines lose their
+/// trailing whitespace and leading blank lines are dropped.
+fn split_message(message: &str) -> (String, String) {
+    let mut lines = message.lines().map(str::trim_end).peekable();
+    while lines.next_if(|line| line.is_empty()).is_some() {}
+
+    let mut title = Vec::new();
+    for line in lines.by_ref() {
+        if line.is_empty() {
+            break;
+        }
+        title.push(line);
+    }
+    while lines.next_if(|line| line.is_empty()).is_some() {}
+
+    let body = lines.fold(String::new(), |mut body, line| {
+        body.push_str(line);
+        body.push('\n');
+        body
+    });
+    (title.join(" "), body)
+}
+
+/// `Date` header value, in the timezone the time was recorded in
+fn format_date(time: git2::Time) -> String {
+    chrono::FixedOffset::east_opt(time.offset_minutes() * 60)
+        .and_then(|offset| {
+            chrono::DateTime::from_timestamp(time.seconds(), 0)
+                .map(|date| date.with_timezone(&offset))
+        })
+        .map(|date| date.format("%a, %-d %b %Y %H:%M:%S %z").to_string())
+        .unwrap_or_else(|| "Thu, 1 Jan 1970 00:00:00 +0000".to_string())
+}
+
+/// `From` header for an author, encoded or quoted as needed
+fn from_header(name: &str, email: &str) -> String {
+    let mut header = "From: ".to_string();
+    let mut max_length = MAX_HEADER_LENGTH;
+    if needs_rfc2047(name) {
+        add_rfc2047(&mut header, name, true);
+        max_length = MAX_ENCODED_LENGTH;
+    } else if name.contains(is_rfc822_special) {
+        let mut quoted = "\"".to_string();
+        for c in name.chars() {
+            if c == '"' || c == '\\' {
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+        quoted.push('"');
+        add_wrapped(&mut header, &quoted, 1, max_length);
+    } else {
+        add_wrapped(&mut header, name, 1, max_length);
+    }
+    if max_length < email.len() + 3 {
+        header.push('\n');
+    }
+    header.push_str(&format!(" <{email}>\n"));
+    header
+}
+
+/// `Subject` header for a commit title, encoded or folded as needed
+fn subject_header(prefix: &str, title: &str) -> String {
+    let mut header = format!("Subject: {prefix} ");
+    if needs_rfc2047(title) {
+        add_rfc2047(&mut header, title, false);
+    } else {
+        add_wrapped(&mut header, title, 1, MAX_HEADER_LENGTH);
+    }
+    header.push('\n');
+    header
+}
+
+fn is_rfc822_special(c: char) -> bool {
+    matches!(
+        c,
+        '(' | ')' | '<' | '>' | '[' | ']' | ':' | ';' | '@' | ',' | '.' | '"' | '\\'
+    )
+}
+
+fn needs_rfc2047(text: &str) -> bool {
+    !text.is_ascii() || text.contains('\n') || text.contains("=?")
+}
+
+/// Whether a byte is written `=XX` in an encoded word
+///
+/// Addresses allow fewer characters unencoded than other headers (RFC 2047,
+/// section 5).
+fn is_rfc2047_special(byte: u8, address: bool) -> bool {
+    if !byte.is_ascii_graphic() || matches!(byte, b'=' | b'?' | b'_') {
+        return true;
+    }
+    address && !(byte.is_ascii_alphanumeric() || matches!(byte, b'!' | b'*' | b'+' | b'-' | b'/'))
+}
+
+/// Length of the text after the last line break
+fn last_line_len(text: &str) -> usize {
+    text.rsplit('\n').next().map_or(0, str::len)
+}
+
+/// Append `text` as Q-encoded words, never splitting a character across words
+fn add_rfc2047(out: &mut String, text: &str, address: bool) {
+    let mut line_len = last_line_len(out) + "=?UTF-8?q?".len();
+    out.push_str("=?UTF-8?q?");
+    for c in text.chars() {
+        let mut buf = [0; 4];
+        let bytes = c.encode_utf8(&mut buf).as_bytes();
+        let special = bytes.len() > 1 || is_rfc2047_special(bytes[0], address);
+        let encoded_len = if special { 3 * bytes.len() } else { 1 };
+        if line_len + encoded_len + 2 > MAX_ENCODED_LENGTH {
+            out.push_str("?=\n =?UTF-8?q?");
+            line_len = " =?UTF-8?q?".len();
+        }
+        if special {
+            for byte in bytes {
+                let _ = write!(out, "={byte:02X}");
+            }
+        } else {
+            out.push(c);
+        }
+        line_len += encoded_len;
+    }
+    out.push_str("?=");
+}
+
+/// Append `text` folded at whitespace to lines of at most `width` columns
+///
+/// Continues the last line of `out` and indents following lines by `indent`,
+/// like git's `strbuf_add_wrapped_text`. Words longer than a line are kept
+/// whole.
+fn add_wrapped(out: &mut String, text: &str, indent: usize, width: usize) {
+    let bytes = text.as_bytes();
+    let mut pos = 0;
+    let mut bol = 0;
+    let mut line_indent = 0;
+    let mut w = last_line_len(out);
+    // Where the text since the last output starts, at its separating space
+    let mut space = Some(0);
+    loop {
+        let c = bytes.get(pos).copied();
+        if c.is_some_and(|c| !c.is_ascii_whitespace()) {
+            w += 1;
+            pos += text[pos..].chars().next().map_or(1, char::len_utf8);
+            continue;
+        }
+        if w <= width || space.is_none() {
+            if c.is_none() && pos == bol {
+                return;
+            }
+            let start = match space {
+                Some(start) => start,
+                None => {
+                    out.push_str(&" ".repeat(line_indent));
+                    bol
+                }
+            };
+            out.push_str(&text[start..pos]);
+            if c.is_none() {
+                return;
+            }
+            space = Some(pos);
+            if c == Some(b'\t') {
+                w |= 0x07;
+            }
+            w += 1;
+            pos += 1;
+        } else {
+            out.push('\n');
+            let start = space.unwrap_or(bol);
+            bol = start + usize::from(bytes[start].is_ascii_whitespace());
+            pos = bol;
+            space = None;
+            w = indent;
+            line_indent = indent;
+        }
+    }
+}
+
+/// Decode the RFC 2047 encoded words of a mail header value
+///
+/// Whitespace between adjacent encoded words is dropped. UTF-8 and Latin-1
+/// are decoded, other charsets are read as UTF-8.
+pub fn decode_header(value: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = value;
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let (before, candidate) = rest.split_at(start);
+        match decode_word(candidate) {
+            Some((text, len)) => {
+                if !(after_word && before.trim().is_empty()) {
+                    decoded.push_str(before);
+                }
+                decoded.push_str(&text);
+                rest = &candidate[len..];
+                after_word = true;
+            }
+            None => {
+                decoded.push_str(before);
+                decoded.push_str("=?");
+                rest = &candidate[2..];
+                after_word = false;
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Decode one `=?charset?encoding?text?=` word, returning it and its length
+fn decode_word(word: &str) -> Option<(String, usize)> {
+    let (charset, rest) = word.strip_prefix("=?")?.split_once('?')?;
+    let (encoding, rest) = rest.split_once('?')?;
+    let text = &rest[..rest.find("?=")?];
+    if text.contains(char::is_whitespace) {
+        return None;
+    }
+    let bytes = match encoding {
+        "q" | "Q" => {
+            let mut bytes = Vec::with_capacity(text.len());
+            let mut chars = text.bytes();
+            while let Some(byte) = chars.next() {
+                match byte {
+                    b'_' => bytes.push(b' '),
+                    b'=' => {
+                        let hex = [chars.next()?, chars.next()?];
+                        let hex = std::str::from_utf8(&hex).ok()?;
+                        bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                    }
+                    byte => bytes.push(byte),
+                }
+            }
+            bytes
+        }
+        "b" | "B" => base64::engine::general_purpose::STANDARD
+            .decode(text)
+            .ok()?,
+        _ => return None,
+    };
+    let decoded = if charset.eq_ignore_ascii_case("iso-8859-1") {
+        bytes.iter().copied().map(char::from).collect()
+    } else {
+        String::from_utf8_lossy(&bytes).into_owned()
+    };
+    let len = charset.len() + encoding.len() + text.len() + "=????=".len();
+    Some((decoded, len))
+}
+
+/// A file in the diffstat
+struct FileStat {
+    /// Path as shown, `old => new` for renames and copies
+    name: String,
+    /// Added lines, or the new size of binary files
+    added: usize,
+    /// Deleted lines, or the old size of binary files
+    deleted: usize,
+    binary: bool,
+}
+
+/// Diff of a commit with its diffstat and summary
+struct CommitDiff {
+    files: Vec<FileStat>,
+    /// `create mode`, `rename` and `mode change` lines
+    summary: String,
+    text: String,
+}
+
+/// Diff of a commit's tree against its parent's
+///
+/// Binary files, and text that isn't UTF-8 and so can't go into an event,
+/// become `GIT binary patch` data so `git am` recreates them byte for byte.
+fn commit_diff(
+    repo: &Repository,
+    parent_tree: Option<&git2::Tree<'_>>,
+    tree: &git2::Tree<'_>,
+) -> Result<CommitDiff> {
+    let mut options = DiffOptions::new();
+    options.show_binary(true);
+    let mut diff = repo.diff_tree_to_tree(parent_tree, Some(tree), Some(&mut options))?;
+    diff.find_similar(None)?;
+
+    let mut commit_diff = CommitDiff {
+        files: Vec::new(),
+        summary: String::new(),
+        text: String::new(),
+    };
+    for index in 0..diff.deltas().len() {
+        let Some(mut file) = git2::Patch::from_diff(&diff, index)? else {
+            continue;
+        };
+        let buf = file.to_buf()?;
+        let delta = file.delta();
+        let binary = delta.flags().is_binary();
+        let mut file_diff = match std::str::from_utf8(&buf) {
+            Ok(file_diff) if !binary => file_diff.to_string(),
+            _ => binary_file_diff(repo, parent_tree, tree, &delta)?,
+        };
+        let renamed = matches!(delta.status(), Delta::Renamed | Delta::Copied);
+        let similarity = if renamed {
+            let similarity = similarity(repo, &delta)?;
+            file_diff = replace_similarity(&file_diff, similarity);
+            similarity
+        } else {
+            0
+        };
+
+        let (_context, added, deleted) = file.line_stats()?;
+        let (added, deleted) = if binary {
+            (
+                blob_size(repo, &delta.new_file())?,
+                blob_size(repo, &delta.old_file())?,
+            )
+        } else {
+            (added, deleted)
+        };
+        let old_path = file_path(&delta.old_file());
+        let new_path = file_path(&delta.new_file());
+        commit_diff.files.push(FileStat {
+            name: if renamed {
+                rename_name(&old_path, &new_path)
+            } else {
+                quote_path(&new_path)
+            },
+            added,
+            deleted,
+            binary,
+        });
+        commit_diff
+            .summary
+            .push_str(&summary_line(&delta, &old_path, &new_path, similarity));
+        commit_diff.text.push_str(&file_diff);
+    }
+    Ok(commit_diff)
+}
+
+fn file_path(file: &git2::DiffFile<'_>) -> String {
+    file.path_bytes()
+        .map(|path| String::from_utf8_lossy(path).into_owned())
+        .unwrap_or_default()
+}
+
+/// Size of a file's blob, 0 for a side that doesn't exist
+fn blob_size(repo: &Repository, file: &git2::DiffFile<'_>) -> Result<usize> {
+    if file.id().is_zero() {
+        return Ok(0);
+    }
+    Ok(repo.find_blob(file.id())?.size())
+}
+
+/// Summary line of a created, deleted, renamed or mode-changed file
+fn summary_line(
+    delta: &git2::DiffDelta<'_>,
+    old_path: &str,
+    new_path: &str,
+    similarity: usize,
+) -> String {
+    let old_mode = u32::from(delta.old_file().mode());
+    let new_mode = u32::from(delta.new_file().mode());
+    let mode_change = old_mode != 0 && new_mode != 0 && old_mode != new_mode;
+    match delta.status() {
+        Delta::Added => format!(
+            " create mode {new_mode:06o} {path}\n",
+            path = quote_path(new_path)
+        ),
+        Delta::Deleted => format!(
+            " delete mode {old_mode:06o} {path}\n",
+            path = quote_path(old_path)
+        ),
+        Delta::Renamed | Delta::Copied => {
+            let action = if delta.status() == Delta::Renamed {
+                "rename"
+            } else {
+                "copy"
+            };
+            let mut line = format!(
+                " {action} {names} ({similarity}%)\n",
+                names = rename_name(old_path, new_path)
+            );
+            if mode_change {
+                line.push_str(&format!(" mode change {old_mode:06o} => {new_mode:06o}\n"));
+            }
+            line
+        }
+        _ if mode_change => format!(
+            " mode change {old_mode:06o} => {new_mode:06o} {path}\n",
+            path = quote_path(new_path)
+        ),
+        _ => String::new(),
+    }
+}
+
+/// Similarity of a renamed or copied file as git scores it
+///
+/// libgit2 pairs files up but scores them its own way. git counts how much
+/// of the old file's content is found in the new one, in chunks of a line or
+/// at most 64 bytes, relative to the larger of the two.
+fn similarity(repo: &Repository, delta: &git2::DiffDelta<'_>) -> Result<usize> {
+    let old = repo.find_blob(delta.old_file().id())?;
+    let new = repo.find_blob(delta.new_file().id())?;
+    let max_size = old.size().max(new.size());
+    if max_size == 0 {
+        return Ok(100);
+    }
+    let text = !delta.flags().is_binary();
+    let old_spans = span_counts(old.content(), text);
+    let new_spans = span_counts(new.content(), text);
+    let copied: usize = old_spans
+        .iter()
+        .map(|(hash, count)| new_spans.get(hash).map_or(0, |new| (*count).min(*new)))
+        .sum();
+    let score = copied * MAX_SCORE / max_size;
+    Ok(score * 100 / MAX_SCORE)
+}
+
+/// Bytes per chunk hash, chunks ending at a newline or after 64 bytes
+///
+/// The rolling hash of git's `diffcore-delta.c`, so scores come out the same.
+fn span_counts(data: &[u8], text: bool) -> HashMap<u32, usize> {
+    let mut spans = HashMap::new();
+    let (mut accum1, mut accum2, mut n) = (0u32, 0u32, 0);
+    for (i, &c) in data.iter().enumerate() {
+        // CR of a CRLF doesn't count in text
+        if text && c == b'\r' && data.get(i + 1) == Some(&b'\n') {
+            continue;
+        }
+        let old = accum1;
+        accum1 = (accum1 << 7) ^ (accum2 >> 25);
+        accum2 = (accum2 << 7) ^ (old >> 25);
+        accum1 = accum1.wrapping_add(u32::from(c));
+        n += 1;
+        if n < 64 && c != b'\n' {
+            continue;
+        }
+        let hash = accum1.wrapping_add(accum2.wrapping_mul(0x61)) % SPAN_HASH_BASE;
+        *spans.entry(hash).or_default() += n;
+        (accum1, accum2, n) = (0, 0, 0);
+    }
+    if n > 0 {
+        let hash = accum1.wrapping_add(accum2.wrapping_mul(0x61)) % SPAN_HASH_BASE;
+        *spans.entry(hash).or_default() += n;
+    }
+    spans
+}
+
+/// Put git's score in the `similarity index` line of a file diff
+fn replace_similarity(file_diff: &str, similarity: usize) -> String {
+    let mut replaced = String::with_capacity(file_diff.len());
+    let mut in_header = true;
+    for line in file_diff.split_inclusive('\n') {
+        in_header &= !line.starts_with("--- ") && !line.starts_with("@@");
+        if in_header && line.starts_with("similarity index ") {
+            let _ = writeln!(replaced, "similarity index {similarity}%");
+        } else {
+            replaced.push_str(line);
+        }
+    }
+    replaced
+}
+
+/// A path in double quotes with C escapes if it has control or non-ASCII characters
+fn quote_path(path: &str) -> String {
+    let needs_quoting = path
+        .bytes()
+        .any(|byte| !(0x20..0x7f).contains(&byte) || byte == b'"' || byte == b'\\');
+    if !needs_quoting {
+        return path.to_string();
+    }
+    let mut quoted = "\"".to_string();
+    for byte in path.bytes() {
+        match byte {
+            0x07 => quoted.push_str("\\a"),
+            0x08 => quoted.push_str("\\b"),
+            b'\t' => quoted.push_str("\\t"),
+            b'\n' => quoted.push_str("\\n"),
+            0x0b => quoted.push_str("\\v"),
+            0x0c => quoted.push_str("\\f"),
+            b'\r' => quoted.push_str("\\r"),
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            byte if !(0x20..0x7f).contains(&byte) => {
+                let _ = write!(quoted, "\\{byte:03o}");
+            }
+            byte => quoted.push(char::from(byte)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// `old => new` with the common leading and trailing directories factored out
+///
+/// `src/{a => b}/lib.rs`, as git shows renames in the diffstat.
+fn rename_name(old: &str, new: &str) -> String {
+    if quote_path(old) != old || quote_path(new) != new {
+        return format!(
+            "{old} => {new}",
+            old = quote_path(old),
+            new = quote_path(new)
+        );
+    }
+    let (a, b) = (old.as_bytes(), new.as_bytes());
+
+    // Common prefix up to and including a slash
+    let mut prefix = 0;
+    for (i, (x, y)) in a.iter().zip(b).enumerate() {
+        if x != y {
+            break;
+        }
+        if *x == b'/' {
+            prefix = i + 1;
+        }
+    }
+
+    // Common suffix from a slash on, not overlapping the prefix but its slash
+    let mut suffix = 0;
+    let floor = prefix.saturating_sub(1);
+    let (mut i, mut j) = (a.len(), b.len());
+    while i >= floor && j >= floor {
+        let (x, y) = (a.get(i), b.get(j));
+        if x != y {
+            break;
+        }
+        if x == Some(&b'/') {
+            suffix = a.len() - i;
+        }
+        if i == 0 || j == 0 {
+            break;
+        }
+        i -= 1;
+        j -= 1;
+    }
+
+    let a_mid = a.len().saturating_sub(prefix + suffix);
+    let b_mid = b.len().saturating_sub(prefix + suffix);
+    let mut name = String::new();
+    if prefix + suffix > 0 {
+        name.push_str(&old[..prefix]);
+        name.push('{');
+    }
+    name.push_str(&old[prefix..prefix + a_mid]);
+    name.push_str(" => ");
+    name.push_str(&new[prefix..prefix + b_mid]);
+    if prefix + suffix > 0 {
+        name.push('}');
+        name.push_str(&old[old.len() - suffix..]);
+    }
+    name
+}
+
+fn decimal_width(n: usize) -> usize {
+    n.to_string().len()
+}
+
+/// Scale a change count to the graph width, keeping non-zero counts visible
+fn scale_linear(count: usize, width: usize, max_change: usize) -> usize {
+    if count == 0 {
+        0
+    } else {
+        1 + count * (width - 1) / max_change
+    }
+}
+
+/// The diffstat lines and their totals, laid out like git's `--stat`
+fn format_diffstat(files: &[FileStat]) -> String {
+    let mut max_len = 0;
+    let mut max_change = 0;
+    let mut number_width = 0;
+    let mut bin_width = 0;
+    for file in files {
+        max_len = max_len.max(file.name.chars().count());
+        if file.binary {
+            // "Bin XXX -> YYY bytes"
+            bin_width = bin_width.max(14 + decimal_width(file.added) + decimal_width(file.deleted));
+            number_width = 3;
+            continue;
+        }
+        max_change = max_change.max(file.added + file.deleted);
+    }
+
+    number_width = number_width.max(decimal_width(max_change));
+    let width = STAT_WIDTH.max(16 + 6 + number_width);
+    let mut graph_width = if max_change + 4 > bin_width {
+        max_change
+    } else {
+        bin_width - 4
+    };
+    let mut name_width = max_len;
+    if name_width + number_width + 6 + graph_width > width {
+        let graph_limit = (width * 3 / 8).saturating_sub(number_width + 6);
+        if graph_width > graph_limit {
+            graph_width = graph_limit.max(6);
+        }
+        let name_limit = width - number_width - 6 - graph_width;
+        if name_width > name_limit {
+            name_width = name_limit;
+        } else {
+            graph_width = width - number_width - 6 - name_width;
+        }
+    }
+
+    let mut stat = String::new();
+    let (mut insertions, mut deletions) = (0, 0);
+    for file in files {
+        let mut name = file.name.as_str();
+        let mut prefix = "";
+        let mut len = name_width;
+        if name_width < name.chars().count() {
+            prefix = "...";
+            len = len.saturating_sub(3);
+            let skip = name.chars().count().saturating_sub(len);
+            name = name
+                .char_indices()
+                .nth(skip)
+                .map_or("", |(start, _)| &name[start..]);
+            if let Some(slash) = name.find('/') {
+                name = &name[slash..];
+            }
+        }
+        let padding = " ".repeat(len.saturating_sub(name.chars().count()));
+
+        if file.binary {
+            let _ = write!(
+                stat,
+                " {prefix}{name}{padding} | {bin:>number_width$}",
+                bin = "Bin"
+            );
+            if file.added > 0 || file.deleted > 0 {
+                let _ = write!(
+                    stat,
+                    " {deleted} -> {added} bytes",
+                    deleted = file.deleted,
+                    added = file.added
+                );
+            }
+            stat.push('\n');
+            continue;
+        }
+
+        insertions += file.added;
+        deletions += file.deleted;
+        let (mut add, mut del) = (file.added, file.deleted);
+        if graph_width <= max_change {
+            let mut total = scale_linear(add + del, graph_width, max_change);
+            if total < 2 && add > 0 && del > 0 {
+                total = 2;
+            }
+            if add < del {
+                add = scale_linear(add, graph_width, max_change);
+                del = total - add;
+            } else {
+                del = scale_linear(del, graph_width, max_change);
+                add = total - del;
+            }
+        }
+        let changes = file.added + file.deleted;
+        let _ = writeln!(
+            stat,
+            " {prefix}{name}{padding} | {changes:>number_width$}{space}{plus}{minus}",
+            space = if changes > 0 { " " } else { "" },
+            plus = "+".repeat(add),
+            minus = "-".repeat(del)
+        );
+    }
+
+    let count = files.len();
+    let _ = write!(
+        stat,
+        " {count} file{s} changed",
+        s = if count == 1 { "" } else { "s" }
+    );
+    if insertions > 0 || deletions == 0 {
+        let _ = write!(
+            stat,
+            ", {insertions} insertion{s}(+)",
+            s = if insertions == 1 { "" } else { "s" }
+        );
+    }
+    if deletions > 0 || insertions == 0 {
+        let _ = write!(
+            stat,
+            ", {deletions} deletion{s}(-)",
+            s = if deletions == 1 { "" } else { "s" }
+        );
+    }
+    stat.push('\n');
+    stat
+}
+
+/// Binary patch of one file, with the full index line `git apply` insists on
+fn binary_file_diff(
+    repo: &Repository,
+    parent_tree: Option<&git2::Tree<'_>>,
+    tree: &git2::Tree<'_>,
+    delta: &git2::DiffDelta<'_>,
+) -> Result<String> {
+    let mut options = DiffOptions::new();
+    options
+        .show_binary(true)
+        .force_binary(true)
+        .id_abbrev(40)
+        .disable_pathspec_match(true);
+    // Both paths, so a renamed file is still paired up
+    for path in [delta.old_file().path(), delta.new_file().path()]
+        .into_iter()
+        .flatten()
+    {
+        options.pathspec(path);
+    }
+    let mut diff = repo.diff_tree_to_tree(parent_tree, Some(tree), Some(&mut options))?;
+    diff.find_similar(None)?;
+
+    let mut text = String::new();
+    for index in 0..diff.deltas().len() {
+        if let Some(mut file) = git2::Patch::from_diff(&diff, index)? {
+            let buf = file.to_buf()?;
+            let file_diff = std::str::from_utf8(&buf).map_err(GitsmithError::msg)?;
+            text.push_str(file_diff);
+        }
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patches::generate_patches;
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+
+    /// Run git isolated from the user's configuration
+    fn git(dir: &Path, args: &[&str], author: (&str, &str)) -> String {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_AUTHOR_NAME", author.0)
+            .env("GIT_AUTHOR_EMAIL", "author@example.com")
+            .env("GIT_AUTHOR_DATE", author.1)
+            .env("GIT_COMMITTER_NAME", "Committer")
+            .env("GIT_COMMITTER_EMAIL", "committer@example.com")
+            .env("GIT_COMMITTER_DATE", "1700000000 +0000")
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "git {args:?}: {stderr}",
+            stderr = String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn commit(dir: &Path, message: &str, author: (&str, &str)) {
+        git(dir, &["add", "-A"], author);
+        git(dir, &["commit", "-q", "-m", message], author);
+    }
+
+    fn numbered_lines(range: std::ops::Range<usize>) -> String {
+        range.map(|i| format!("line {i}\n")).collect()
+    }
+
+    #[test]
+    fn test_matches_git_format_patch() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let alice = ("Alice Example", "1700000000 +0000");
+        git(dir, &["init", "-q"], alice);
+        git(dir, &["config", "diff.renames", "copies"], alice);
+
+        fs::create_dir_all(dir.join("src/old")).unwrap();
+        fs::write(
+            dir.join("main.c"),
+            "int main(void)\n{\n\tint a = 1;\n\tint b = 2;\n\tint c = 3;\n\treturn a + b + c;\n}\n",
+        )
+        .unwrap();
+        fs::write(dir.join("src/old/lib.rs"), numbered_lines(0..20)).unwrap();
+        fs::write(dir.join("notes.txt"), numbered_lines(0..30)).unwrap();
+        fs::write(dir.join("gone.txt"), "bye\n").unwrap();
+        commit(dir, "Initial commit", alice);
+        let base = git(dir, &["rev-parse", "HEAD"], alice);
+
+        // Function context in the hunk header, a body with trailing whitespace
+        fs::write(
+            dir.join("main.c"),
+            "int main(void)\n{\n\tint a = 1;\n\tint b = 2;\n\tint c = 4;\n\treturn a + b + c;\n}\n",
+        )
+        .unwrap();
+        commit(
+            dir,
+            "Change c\n\nThe body   \n\nhas two paragraphs.\n",
+            ("Alice Example", "1700003600 +0530"),
+        );
+
+        // Creation, deletion and a mode change
+        fs::write(dir.join("new.txt"), "hello\n").unwrap();
+        fs::remove_file(dir.join("gone.txt")).unwrap();
+        commit(
+            dir,
+            "Add, remove and chmod",
+            ("Doe, J.", "1700007200 -0800"),
+        );
+        git(dir, &["update-index", "--chmod=+x", "main.c"], alice);
+        git(
+            dir,
+            &["commit", "-q", "-m", "Make main.c executable"],
+            alice,
+        );
+
+        // A pure rename and one with changes
+        git(dir, &["mv", "src/old", "src/new"], alice);
+        commit(dir, "Move the library", alice);
+        let mut notes = numbered_lines(0..30);
+        notes.push_str("one more line\n");
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        fs::remove_file(dir.join("notes.txt")).unwrap();
+        fs::write(dir.join("renamed-notes.txt"), notes).unwrap();
+        commit(dir, "Rename the notes and add a line", alice);
+
+        // A copy of a modified file
+        let lib = numbered_lines(0..20);
+        fs::write(dir.join("src/new/lib.rs"), format!("{lib}line 20\n")).unwrap();
+        fs::write(dir.join("src/new/copy.rs"), &lib).unwrap();
+        commit(dir, "Copy the library", alice);
+
+        // Encoded headers and folded subjects
+        fs::write(dir.join("new.txt"), "hallo\n").unwrap();
+        commit(
+            dir,
+            "Übersetze die Begrüßung in einen ziemlich langen Betreff, der umbrochen werden muss\n\nMit Umlauten im Text: äöü.\n",
+            ("Jörg Müller", "1700010800 +0100"),
+        );
+        fs::write(dir.join("new.txt"), "hello again\n").unwrap();
+        commit(
+            dir,
+            "A very long ascii subject line that goes on and on well beyond the seventy eight columns",
+            alice,
+        );
+
+        // A long path shortened in the diffstat and a scaled graph
+        let long = "a/deeply/nested/directory/structure/with/a/very/long/file/name.txt";
+        fs::create_dir_all(dir.join(long).parent().unwrap()).unwrap();
+        fs::write(dir.join(long), numbered_lines(0..120)).unwrap();
+        fs::write(dir.join("short.txt"), "short\n").unwrap();
+        commit(dir, "Add a long file", alice);
+
+        // A quoted non-ASCII path
+        fs::write(dir.join("naïve.txt"), "quoted\n").unwrap();
+        commit(dir, "Add a file with a non-ASCII name", alice);
+
+        let ours = generate_patches(dir, Some(base.trim()), None).unwrap();
+        let out = dir.join("out");
+        let range = format!("{base}..HEAD", base = base.trim());
+        let files = git(
+            dir,
+            &["format-patch", "-o", out.to_str().unwrap(), &range],
+            alice,
+        );
+        let files: Vec<&str> = files.lines().collect();
+        assert_eq!(ours.len(), files.len());
+        let written = write_patch_files(&ours, &dir.join("ours")).unwrap();
+        for ((ours, file), written) in ours.iter().zip(&files).zip(&written) {
+            // Only the git version in the signature footer differs
+            let theirs = fs::read_to_string(file).unwrap();
+            let (theirs, _version) = theirs.rsplit_once("\n-- \n").unwrap();
+            assert_eq!(*ours, format!("{theirs}\n-- \n{FOOTER_VERSION}\n\n"));
+            assert_eq!(written.file_name(), Path::new(file).file_name());
+            assert_eq!(fs::read_to_string(written).unwrap(), *ours);
+        }
+
+        let stdout = git(dir, &["format-patch", "--stdout", &range], alice);
+        let version = format!("\n-- \n{FOOTER_VERSION}\n");
+        let stdout = stdout.replace("\n-- \n2.", "\n-- \nVERSION");
+        let stdout: String = stdout
+            .split("\n-- \nVERSION")
+            .enumerate()
+            .map(|(i, part)| match i {
+                0 => part.to_string(),
+                _ => format!("{version}{rest}", rest = part.split_once('\n').unwrap().1),
+            })
+            .collect();
+        assert_eq!(mbox(&ours), stdout);
+
+        // Encoded headers read back as they were committed
+        let parsed = crate::apply::parse_patch(&ours[6]).unwrap();
+        assert_eq!(parsed.author_name, "Jörg Müller");
+        assert!(
+            parsed
+                .subject
+                .starts_with("Übersetze die Begrüßung in einen")
+        );
+        assert!(parsed.subject.ends_with("der umbrochen werden muss"));
+        let parsed = crate::apply::parse_patch(&ours[1]).unwrap();
+        assert_eq!(parsed.author_name, "Doe, J");
+    }
+
+    #[test]
+    fn test_decode_header() {
+        assert_eq!(
+            decode_header("=?UTF-8?q?J=C3=B6rg=20M=C3=BCller?= <j@example.com>"),
+            "Jörg Müller <j@example.com>"
+        );
+        // Whitespace between encoded words of a folded header is dropped
+        assert_eq!(
+            decode_header("[PATCH] =?UTF-8?q?=C3=9Cber?= =?UTF-8?q?setzung?="),
+            "[PATCH] Übersetzung"
+        );
+        assert_eq!(
+            decode_header("=?ISO-8859-1?Q?caf=E9_au_lait?="),
+            "café au lait"
+        );
+        assert_eq!(decode_header("=?UTF-8?B?w7w=?="), "ü");
+        assert_eq!(decode_header("plain =? text"), "plain =? text");
+    }
+
+    #[test]
+    fn test_patch_file_name() {
+        assert_eq!(
+            patch_file_name(1, "Fix the frobnicator"),
+            "0001-Fix-the-frobnicator.patch"
+        );
+        assert_eq!(
+            patch_file_name(12, "[wip] Über... \"quoted\" -- v1.2..3."),
+            "0012-wip-ber.-quoted-v1.2.3.patch"
+        );
+        assert_eq!(
+            patch_file_name(2, &"long ".repeat(20)),
+            "0002-long-long-long-long-long-long-long-long-long-long-lo.patch"
+        );
+    }
+
+    #[test]
+    fn test_mbox_adds_missing_from_lines() {
+        let patches = vec![
+            "From: Alice <a@example.com>\nSubject: [PATCH] A\n\n---\n".to_string(),
+            "From abc Mon Sep 17 00:00:00 2001\nSubject: [PATCH] B\n\n---".to_string(),
+        ];
+        let mbox = mbox(&patches);
+        assert!(mbox.starts_with(&format!("From {zeros} Mon Sep", zeros = "0".repeat(40))));
+        assert!(mbox.contains("---\n\nFrom abc Mon Sep 17 00:00:00 2001\n"));
+        assert!(mbox.ends_with("---\n"));
+    }
+
+    #[test]
+    fn test_split_mbox() {
+        let patches = vec![
+            "From 1111 Mon Sep 17 00:00:00 2001\nSubject: [PATCH 1/2] A\n\nFrom here on\n---\n"
+                .to_string(),
+            "From 2222 Mon Sep 17 00:00:00 2001\nSubject: [PATCH 2/2] B\n\n---\n".to_string(),
+        ];
+        let messages = split_mbox(&mbox(&patches));
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].contains("From here on\n"));
+        assert!(messages[1].starts_with("From 2222 "));
+        assert!(split_mbox("no separator\n").is_empty());
+    }
+
+    #[test]
+    fn test_import_series_from_thread() {
+        let diff = "---\n f | 2 +-\n\ndiff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -1 +1 @@\n-a\n+b\n";
+        let mail = |subject: &str, body: &str| {
+            format!(
+                "From alice@example.com Thu Jan  4 10:00:00 2024\r\n\
+                 Received: from mx.example.com\r\n\
+                 From: Alice <alice@example.com>\r\n\
+                 To: list@example.com\r\n\
+                 Subject: {subject}\r\n\
+                 Message-Id: <1@example.com>\r\n\
+                 \r\n\
+                 {body}",
+                body = body.replace('\n', "\r\n")
+            )
+        };
+        let thread = [
+            mail("[PATCH v2 0/2] Frobnicate", "The cover letter.\n"),
+            mail("[PATCH v2 2/2]\r\n Second", diff),
+            mail("Re: [PATCH v2 1/2] First", "Looks good.\n"),
+            mail("[PATCH v2 1/2] First", diff),
+        ]
+        .concat();
+
+        let series = import_series(&split_mbox(&thread)).unwrap();
+        let cover_letter = series.cover_letter.unwrap();
+        assert!(cover_letter.contains("The cover letter."));
+        assert_eq!(series.patches.len(), 2);
+        let first = &series.patches[0];
+        assert!(first.contains("Subject: [PATCH v2 1/2] First\n"));
+        assert!(!first.contains('\r'));
+        assert!(!first.contains("Received:") && !first.contains("Message-Id:"));
+        assert_eq!(
+            apply::parse_patch(&series.patches[1]).unwrap().subject,
+            "Second"
+        );
+
+        assert!(import_series(&split_mbox(&mail("Re: hello", "hi\n"))).is_err());
+    }
+
+    #[test]
+    fn test_rename_name() {
+        assert_eq!(rename_name("a.txt", "b.txt"), "a.txt => b.txt");
+        assert_eq!(
+            rename_name("src/old/lib.rs", "src/new/lib.rs"),
+            "src/{old => new}/lib.rs"
+        );
+        assert_eq!(rename_name("a/x.rs", "b/x.rs"), "{a => b}/x.rs");
+        assert_eq!(rename_name("lib.rs", "src/lib.rs"), "lib.rs => src/lib.rs");
+        assert_eq!(rename_name("src/a/b.rs", "src/b.rs"), "src/{a => }/b.rs");
+    }
+}
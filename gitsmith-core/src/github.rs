@@ -0,0 +1,853 @@
+use nostr::{Event, EventId, Filter, Tag};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use strum::{Display, EnumString};
+use tracing::{debug, info};
+
+use crate::comments;
+use crate::error::{Context, GitsmithError, Result, bail, ensure};
+use crate::format_patch;
+use crate::relay::{self, Network};
+use crate::repo;
+use crate::signer::{self, Signer};
+use crate::types::{BroadcastResult, RepoAnnouncement};
+
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// Kind of item on GitHub's tracker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display, EnumString)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum GithubItemKind {
+    Issue,
+    Pull,
+}
+
+/// A GitHub issue or pull request referenced by URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GithubItemRef {
+    pub owner: String,
+    pub repo: String,
+    pub kind: GithubItemKind,
+    pub number: u64,
+}
+
+/// Snapshot of a GitHub issue or pull request at the time it was fetched
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GithubItem {
+    pub kind: GithubItemKind,
+    /// Repository in `owner/repo` form
+    pub repository: String,
+    pub number: u64,
+    pub title: String,
+    /// open, closed or merged
+    pub state: String,
+    pub url: String,
+    pub fetched_at: u64,
+}
+
+/// Repository metadata from GitHub that carries over to an announcement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GithubRepository {
+    /// Repository in `owner/repo` form
+    pub full_name: String,
+    pub name: String,
+    pub description: String,
+    pub homepage: Option<String>,
+    pub topics: Vec<String>,
+    pub default_branch: String,
+    pub clone_url: String,
+    pub html_url: String,
+    /// SPDX identifier of the detected license
+    pub license: Option<String>,
+}
+
+/// Someone who committed to a GitHub repository
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GithubContributor {
+    pub login: String,
+    pub contributions: u64,
+}
+
+/// An open issue or pull request on GitHub with what it takes to republish it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GithubOpenItem {
+    pub kind: GithubItemKind,
+    pub number: u64,
+    pub title: String,
+    pub body: String,
+    /// Login of the author
+    pub author: String,
+    /// Creation time as GitHub reports it (RFC 3339)
+    pub created_at: String,
+    pub url: String,
+    pub labels: Vec<String>,
+}
+
+/// A comment on a GitHub issue or pull request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GithubComment {
+    pub id: u64,
+    pub body: String,
+    /// Login of the author
+    pub author: String,
+    /// Creation time as GitHub reports it (RFC 3339)
+    pub created_at: String,
+    pub url: String,
+}
+
+/// A pull request opened on GitHub
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GithubPullRequest {
+    pub number: u64,
+    pub url: String,
+}
+
+/// Items GitHub returns per page, the most it allows
+const GITHUB_PAGE_SIZE: usize = 100;
+
+/// Parse a repository given as `owner/repo`, `https://github.com/owner/repo`
+/// or an SSH remote URL (`git@github.com:owner/repo.git`)
+pub fn parse_github_repository(spec: &str) -> Result<(String, String)> {
+    let spec = spec.trim();
+    let path = spec
+        .strip_prefix("https://github.com/")
+        .or_else(|| spec.strip_prefix("http://github.com/"))
+        .or_else(|| spec.strip_prefix("github.com/"))
+        .or_else(|| spec.strip_prefix("git@github.com:"))
+        .or_else(|| spec.strip_prefix("ssh://git@github.com/"))
+        .unwrap_or(spec);
+    let parts: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+    match parts.as_slice() {
+        [owner, repo] if !owner.is_empty() && !repo.is_empty() && !owner.contains(':') => {
+            let repo = repo.strip_suffix(".git").unwrap_or(repo);
+            Ok((owner.to_string(), repo.to_string()))
+        }
+        _ => bail!("Expected a GitHub repository as owner/name, got: {spec}"),
+    }
+}
+
+/// GET a GitHub REST API endpoint and return the JSON body
+async fn github_get(path: &str, token: Option<&str>) -> Result<serde_json::Value> {
+    Ok(github_request(path, token, "application/vnd.github+json")
+        .await?
+        .json()
+        .await?)
+}
+
+/// POST `body` to a GitHub REST API endpoint and return the JSON response
+async fn github_post(
+    path: &str,
+    token: &str,
+    body: &serde_json::Value,
+) -> Result<serde_json::Value> {
+    let api_url = format!("{GITHUB_API_URL}/{path}");
+    debug!(%api_url, "Posting to GitHub");
+    let request = github_client()?
+        .post(&api_url)
+        .header("Accept", "application/vnd.github+json")
+        .bearer_auth(token)
+        .json(body);
+    Ok(send_github_request(request, &api_url).await?.json().await?)
+}
+
+/// GET a GitHub REST API endpoint in the media type `accept`
+async fn github_request(
+    path: &str,
+    token: Option<&str>,
+    accept: &str,
+) -> Result<reqwest::Response> {
+    let api_url = format!("{GITHUB_API_URL}/{path}");
+    debug!(%api_url, "Querying GitHub");
+    let mut request = github_client()?.get(&api_url).header("Accept", accept);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    send_github_request(request, &api_url).await
+}
+
+fn github_client() -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .user_agent(concat!("gitsmith/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(15))
+        .build()?)
+}
+
+/// Send a GitHub API request, failing on unsuccessful responses
+async fn send_github_request(
+    request: reqwest::RequestBuilder,
+    api_url: &str,
+) -> Result<reqwest::Response> {
+    let response = request
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach {api_url}"))?;
+    let status = response.status();
+    ensure!(
+        status.is_success(),
+        "GitHub API returned {status} for {api_url}"
+    );
+    Ok(response)
+}
+
+/// Fetch the metadata of a GitHub repository
+pub async fn fetch_github_repository(
+    owner: &str,
+    repo: &str,
+    token: Option<&str>,
+) -> Result<GithubRepository> {
+    let body = github_get(&format!("repos/{owner}/{repo}"), token).await?;
+    repository_from_api_response(&body)
+}
+
+/// Fetch up to `limit` contributors of a GitHub repository, most active first
+pub async fn fetch_github_contributors(
+    owner: &str,
+    repo: &str,
+    limit: usize,
+    token: Option<&str>,
+) -> Result<Vec<GithubContributor>> {
+    let per_page = limit.clamp(1, 100);
+    let body = github_get(
+        &format!("repos/{owner}/{repo}/contributors?per_page={per_page}"),
+        token,
+    )
+    .await?;
+    let mut contributors = contributors_from_api_response(&body)?;
+    contributors.truncate(limit);
+    Ok(contributors)
+}
+
+/// Fetch the open issues and pull requests of a GitHub repository, oldest first
+pub async fn fetch_open_items(
+    owner: &str,
+    repo: &str,
+    token: Option<&str>,
+) -> Result<Vec<GithubOpenItem>> {
+    let mut items = Vec::new();
+    for page in 1.. {
+        let body = github_get(
+            &format!(
+                "repos/{owner}/{repo}/issues?state=open&direction=asc&per_page={GITHUB_PAGE_SIZE}&page={page}"
+            ),
+            token,
+        )
+        .await?;
+        let count = body.as_array().map_or(0, Vec::len);
+        items.extend(open_items_from_api_response(&body)?);
+        if count < GITHUB_PAGE_SIZE {
+            break;
+        }
+    }
+    debug!(count = items.len(), "Fetched open GitHub items");
+    Ok(items)
+}
+
+/// Fetch the commits of a GitHub pull request as `git format-patch` patches
+pub async fn fetch_pull_request_patches(
+    owner: &str,
+    repo: &str,
+    number: u64,
+    token: Option<&str>,
+) -> Result<Vec<String>> {
+    let mbox = github_request(
+        &format!("repos/{owner}/{repo}/pulls/{number}"),
+        token,
+        "application/vnd.github.patch",
+    )
+    .await?
+    .text()
+    .await?;
+    let patches = format_patch::split_mbox(&mbox);
+    ensure!(
+        !patches.is_empty(),
+        "GitHub pull request {owner}/{repo}#{number} has no patches"
+    );
+    Ok(patches)
+}
+
+/// Fetch the comments on a GitHub issue or pull request, oldest first
+pub async fn fetch_issue_comments(
+    owner: &str,
+    repo: &str,
+    number: u64,
+    token: Option<&str>,
+) -> Result<Vec<GithubComment>> {
+    let mut comments = Vec::new();
+    for page in 1.. {
+        let body = github_get(
+            &format!(
+                "repos/{owner}/{repo}/issues/{number}/comments?per_page={GITHUB_PAGE_SIZE}&page={page}"
+            ),
+            token,
+        )
+        .await?;
+        let count = body.as_array().map_or(0, Vec::len);
+        comments.extend(comments_from_api_response(&body)?);
+        if count < GITHUB_PAGE_SIZE {
+            break;
+        }
+    }
+    Ok(comments)
+}
+
+/// Comment on a GitHub issue or pull request
+pub async fn post_issue_comment(
+    owner: &str,
+    repo: &str,
+    number: u64,
+    token: &str,
+    body: &str,
+) -> Result<GithubComment> {
+    let response = github_post(
+        &format!("repos/{owner}/{repo}/issues/{number}/comments"),
+        token,
+        &serde_json::json!({ "body": body }),
+    )
+    .await?;
+    comments_from_api_response(&serde_json::Value::Array(vec![response]))?
+        .pop()
+        .context("GitHub response is not a comment")
+}
+
+/// Open a pull request merging branch `head` into `base`
+pub async fn create_pull_request(
+    owner: &str,
+    repo: &str,
+    token: &str,
+    title: &str,
+    body: &str,
+    head: &str,
+    base: &str,
+) -> Result<GithubPullRequest> {
+    let response = github_post(
+        &format!("repos/{owner}/{repo}/pulls"),
+        token,
+        &serde_json::json!({ "title": title, "body": body, "head": head, "base": base }),
+    )
+    .await?;
+    Ok(GithubPullRequest {
+        number: response["number"]
+            .as_u64()
+            .context("GitHub response is missing the number")?,
+        url: response["html_url"]
+            .as_str()
+            .context("GitHub response is missing the URL")?
+            .to_string(),
+    })
+}
+
+/// Build comments from a GitHub issue comments API response
+pub fn comments_from_api_response(body: &serde_json::Value) -> Result<Vec<GithubComment>> {
+    let entries = body
+        .as_array()
+        .context("GitHub response is not a comment list")?;
+    Ok(entries
+        .iter()
+        .filter_map(|entry| {
+            Some(GithubComment {
+                id: entry["id"].as_u64()?,
+                body: entry["body"].as_str().unwrap_or_default().to_string(),
+                author: entry["user"]["login"]
+                    .as_str()
+                    .unwrap_or("ghost")
+                    .to_string(),
+                created_at: entry["created_at"].as_str().unwrap_or_default().to_string(),
+                url: entry["html_url"].as_str()?.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Build the open items from a GitHub issues API response
+///
+/// The issues endpoint lists pull requests too, they carry a `pull_request` field.
+pub fn open_items_from_api_response(body: &serde_json::Value) -> Result<Vec<GithubOpenItem>> {
+    let entries = body
+        .as_array()
+        .context("GitHub response is not an issue list")?;
+    Ok(entries
+        .iter()
+        .filter_map(|entry| {
+            let is_pull = entry.get("pull_request").is_some_and(|pr| !pr.is_null());
+            Some(GithubOpenItem {
+                kind: if is_pull {
+                    GithubItemKind::Pull
+                } else {
+                    GithubItemKind::Issue
+                },
+                number: entry["number"].as_u64()?,
+                title: entry["title"].as_str()?.to_string(),
+                body: entry["body"].as_str().unwrap_or_default().to_string(),
+                author: entry["user"]["login"]
+                    .as_str()
+                    .unwrap_or("ghost")
+                    .to_string(),
+                created_at: entry["created_at"].as_str().unwrap_or_default().to_string(),
+                url: entry["html_url"].as_str()?.to_string(),
+                labels: entry["labels"]
+                    .as_array()
+                    .map(|labels| {
+                        labels
+                            .iter()
+                            .filter_map(|label| label["name"].as_str().map(str::to_string))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            })
+        })
+        .collect())
+}
+
+/// Build repository metadata from a GitHub repos API response
+pub fn repository_from_api_response(body: &serde_json::Value) -> Result<GithubRepository> {
+    let field = |name: &str| {
+        body[name]
+            .as_str()
+            .with_context(|| format!("GitHub response is missing {name}"))
+            .map(str::to_string)
+    };
+    // Optional fields are null or empty when unset
+    let optional = |value: &serde_json::Value| {
+        value
+            .as_str()
+            .filter(|value| !value.trim().is_empty())
+            .map(str::to_string)
+    };
+
+    Ok(GithubRepository {
+        full_name: field("full_name")?,
+        name: field("name")?,
+        description: optional(&body["description"]).unwrap_or_default(),
+        homepage: optional(&body["homepage"]),
+        topics: body["topics"]
+            .as_array()
+            .map(|topics| {
+                topics
+                    .iter()
+                    .filter_map(|topic| topic.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        default_branch: field("default_branch")?,
+        clone_url: field("clone_url")?,
+        html_url: field("html_url")?,
+        // GitHub reports NOASSERTION for licenses it can't identify
+        license: optional(&body["license"]["spdx_id"]).filter(|id| id != "NOASSERTION"),
+    })
+}
+
+/// Build the contributor list from a GitHub contributors API response
+///
+/// Anonymous contributors have no login and are skipped.
+pub fn contributors_from_api_response(body: &serde_json::Value) -> Result<Vec<GithubContributor>> {
+    let entries = body
+        .as_array()
+        .context("GitHub response is not a contributor list")?;
+    Ok(entries
+        .iter()
+        .filter_map(|entry| {
+            Some(GithubContributor {
+                login: entry["login"].as_str()?.to_string(),
+                contributions: entry["contributions"].as_u64().unwrap_or(0),
+            })
+        })
+        .collect())
+}
+
+/// Announcement for a GitHub repository migrated to Nostr
+///
+/// GitHub stays a clone URL and the repository page and homepage become web
+/// URLs, so the project is found from either side.
+pub fn announcement_from_github(
+    github: &GithubRepository,
+    root_commit: String,
+    relays: Vec<String>,
+    maintainers: Vec<String>,
+) -> RepoAnnouncement {
+    let mut web = vec![github.html_url.clone()];
+    web.extend(github.homepage.clone());
+    RepoAnnouncement {
+        identifier: repo::sanitize_identifier(&github.name),
+        name: github.name.clone(),
+        description: github.description.clone(),
+        clone_urls: vec![github.clone_url.clone()],
+        relays,
+        web,
+        root_commit,
+        maintainers,
+        ci_keys: vec![],
+        grasp_servers: vec![],
+        blossom_servers: vec![],
+        min_pow: 0,
+        license: github.license.clone(),
+        topics: github.topics.clone(),
+        readme: None,
+    }
+}
+
+/// Parse an issue or pull request URL such as `https://github.com/owner/repo/pull/12`
+pub fn parse_github_url(url: &str) -> Result<GithubItemRef> {
+    let path = url
+        .trim()
+        .strip_prefix("https://github.com/")
+        .or_else(|| url.trim().strip_prefix("http://github.com/"))
+        .or_else(|| url.trim().strip_prefix("github.com/"))
+        .with_context(|| format!("Not a GitHub URL: {url}"))?;
+    // Ignore fragments and query strings (e.g. links to a specific comment)
+    let path = path.split(['#', '?']).next().unwrap_or_default();
+
+    let parts: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+    ensure!(
+        parts.len() >= 4,
+        "Expected a GitHub issue or pull request URL, got: {url}"
+    );
+
+    let kind = match parts[2] {
+        "issues" => GithubItemKind::Issue,
+        "pull" | "pulls" => GithubItemKind::Pull,
+        other => bail!("Unsupported GitHub URL type '{other}', expected issues or pull"),
+    };
+    let number = parts[3]
+        .parse()
+        .with_context(|| format!("Invalid issue or pull request number in {url}"))?;
+
+    Ok(GithubItemRef {
+        owner: parts[0].to_string(),
+        repo: parts[1].to_string(),
+        kind,
+        number,
+    })
+}
+
+/// Fetch the current title and state of a GitHub item through the REST API
+pub async fn fetch_github_item(item: &GithubItemRef, token: Option<&str>) -> Result<GithubItem> {
+    // The issues endpoint serves pull requests as well and reports whether they were merged
+    let body = github_get(
+        &format!(
+            "repos/{owner}/{repo}/issues/{number}",
+            owner = item.owner,
+            repo = item.repo,
+            number = item.number
+        ),
+        token,
+    )
+    .await?;
+
+    item_from_api_response(item, &body)
+}
+
+/// Build a snapshot from a GitHub issues API response
+pub fn item_from_api_response(
+    item: &GithubItemRef,
+    body: &serde_json::Value,
+) -> Result<GithubItem> {
+    let title = body["title"]
+        .as_str()
+        .context("GitHub response is missing the title")?;
+    let url = body["html_url"]
+        .as_str()
+        .context("GitHub response is missing the URL")?;
+
+    let is_pull = body.get("pull_request").is_some_and(|pr| !pr.is_null());
+    let merged = body["pull_request"]["merged_at"].is_string();
+    let state = if merged {
+        "merged".to_string()
+    } else {
+        body["state"].as_str().unwrap_or("unknown").to_string()
+    };
+
+    Ok(GithubItem {
+        kind: if is_pull {
+            GithubItemKind::Pull
+        } else {
+            GithubItemKind::Issue
+        },
+        repository: format!("{owner}/{repo}", owner = item.owner, repo = item.repo),
+        number: item.number,
+        title: title.to_string(),
+        state,
+        url: url.to_string(),
+        fetched_at: chrono::Utc::now().timestamp().max(0) as u64,
+    })
+}
+
+/// Build a NIP-22 comment on a PR or issue that cites a GitHub item
+///
+/// Besides the human readable content the snapshot is kept in tags (`r`, `title`,
+/// `state`, `fetched_at`) so clients can render or verify it.
+pub async fn build_link_event(
+    signer: &dyn Signer,
+    item: &GithubItem,
+    target: &Event,
+    repo_coordinate: &str,
+) -> Result<Event> {
+    let label = match item.kind {
+        GithubItemKind::Issue => "issue",
+        GithubItemKind::Pull => "pull request",
+    };
+    let content = format!(
+        "🔗 GitHub {label} {repository}#{number}: {title} ({state})\n{url}",
+        repository = item.repository,
+        number = item.number,
+        title = item.title,
+        state = item.state,
+        url = item.url
+    );
+
+    let builder = comments::comment_builder(target, None, &content, Some(repo_coordinate))?.tags([
+        Tag::parse(["r", &item.url])?,
+        Tag::parse(["title", &item.title])?,
+        Tag::parse(["state", &item.state])?,
+        Tag::parse(["fetched_at", &item.fetched_at.to_string()])?,
+        Tag::parse(["alt", &format!("Reference to {url}", url = item.url)])?,
+    ]);
+    signer::sign(signer, builder).await
+}
+
+/// Publish a reference to a GitHub item as a comment on a PR or issue
+pub async fn link_github_item(
+    network: &Network,
+    signer: &dyn Signer,
+    item: &GithubItem,
+    target_event_id: &str,
+    repo_coordinate: &str,
+    relays: &[String],
+) -> Result<BroadcastResult> {
+    let target_id = EventId::from_hex(target_event_id)
+        .with_context(|| format!("Invalid event ID: {target_event_id}"))?;
+    let client = relay::connect(network, relays, Some(signer)).await?;
+
+    let found = relay::fetch_events(
+        network,
+        &client,
+        vec![Filter::new().id(target_id)],
+        Duration::from_secs(5),
+    )
+    .await?;
+    let Some(target) = found.first() else {
+        return Err(GitsmithError::EventNotFound(format!(
+            "Event {target_event_id}"
+        )));
+    };
+
+    let event = build_link_event(signer, item, target, repo_coordinate).await?;
+    info!(url = %item.url, target = %target.id, "Publishing GitHub reference");
+
+    relay::publish_events(&client, &[event]).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::issues::{KIND_ISSUE, build_issue_event};
+    use nostr::Keys;
+    use nostr::{Kind, ToBech32};
+
+    #[test]
+    fn test_parse_github_url() {
+        assert_eq!(
+            parse_github_url("https://github.com/rust-lang/rust/pull/12345").unwrap(),
+            GithubItemRef {
+                owner: "rust-lang".to_string(),
+                repo: "rust".to_string(),
+                kind: GithubItemKind::Pull,
+                number: 12345,
+            }
+        );
+
+        let issue =
+            parse_github_url("https://github.com/owner/repo/issues/7#issuecomment-1").unwrap();
+        assert_eq!(issue.kind, GithubItemKind::Issue);
+        assert_eq!(issue.number, 7);
+
+        assert!(parse_github_url("https://gitlab.com/owner/repo/issues/7").is_err());
+        assert!(parse_github_url("https://github.com/owner/repo").is_err());
+        assert!(parse_github_url("https://github.com/owner/repo/wiki/7").is_err());
+    }
+
+    #[test]
+    fn test_parse_github_repository() {
+        let expected = ("rust-lang".to_string(), "rust".to_string());
+        assert_eq!(parse_github_repository("rust-lang/rust").unwrap(), expected);
+        assert_eq!(
+            parse_github_repository("https://github.com/rust-lang/rust.git").unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse_github_repository("git@github.com:rust-lang/rust.git").unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse_github_repository("ssh://git@github.com/rust-lang/rust").unwrap(),
+            expected
+        );
+        assert!(parse_github_repository("rust").is_err());
+        assert!(parse_github_repository("git@gitlab.com:rust-lang/rust.git").is_err());
+        assert!(parse_github_repository("https://github.com/rust-lang/rust/pull/1").is_err());
+    }
+
+    #[test]
+    fn test_repository_from_api_response() {
+        let body = serde_json::json!({
+            "full_name": "owner/My Repo",
+            "name": "My Repo",
+            "description": null,
+            "homepage": "https://example.com",
+            "topics": ["git", "nostr"],
+            "default_branch": "trunk",
+            "clone_url": "https://github.com/owner/My-Repo.git",
+            "html_url": "https://github.com/owner/My-Repo",
+            "license": { "spdx_id": "NOASSERTION" }
+        });
+        let github = repository_from_api_response(&body).unwrap();
+        assert_eq!(github.description, "");
+        assert_eq!(github.default_branch, "trunk");
+        assert_eq!(github.license, None);
+
+        let maintainer = Keys::generate().public_key().to_bech32().unwrap();
+        let announcement = announcement_from_github(
+            &github,
+            "a".repeat(40),
+            vec!["wss://relay.example".to_string()],
+            vec![maintainer.clone()],
+        );
+        assert_eq!(announcement.identifier, "my-repo");
+        assert_eq!(announcement.clone_urls, [github.clone_url.as_str()]);
+        assert_eq!(
+            announcement.web,
+            ["https://github.com/owner/My-Repo", "https://example.com"]
+        );
+        assert_eq!(announcement.topics, ["git", "nostr"]);
+        assert_eq!(announcement.maintainers, [maintainer]);
+
+        let contributors = contributors_from_api_response(&serde_json::json!([
+            { "login": "alice", "contributions": 42 },
+            { "type": "Anonymous", "contributions": 3 }
+        ]))
+        .unwrap();
+        assert_eq!(
+            contributors,
+            [GithubContributor {
+                login: "alice".to_string(),
+                contributions: 42
+            }]
+        );
+    }
+
+    #[test]
+    fn test_item_from_api_response() {
+        let item = parse_github_url("https://github.com/owner/repo/pull/3").unwrap();
+        let body = serde_json::json!({
+            "title": "Fix crash",
+            "state": "closed",
+            "html_url": "https://github.com/owner/repo/pull/3",
+            "pull_request": { "merged_at": "2024-01-01T00:00:00Z" }
+        });
+
+        let snapshot = item_from_api_response(&item, &body).unwrap();
+        assert_eq!(snapshot.kind, GithubItemKind::Pull);
+        assert_eq!(snapshot.repository, "owner/repo");
+        assert_eq!(snapshot.title, "Fix crash");
+        assert_eq!(snapshot.state, "merged");
+
+        let body = serde_json::json!({
+            "title": "Crash",
+            "state": "open",
+            "html_url": "https://github.com/owner/repo/issues/4"
+        });
+        let snapshot = item_from_api_response(&item, &body).unwrap();
+        assert_eq!(snapshot.kind, GithubItemKind::Issue);
+        assert_eq!(snapshot.state, "open");
+    }
+
+    #[test]
+    fn test_open_items_from_api_response() {
+        let body = serde_json::json!([
+            {
+                "number": 5,
+                "title": "Crash on start",
+                "body": null,
+                "user": { "login": "alice" },
+                "created_at": "2024-01-01T00:00:00Z",
+                "html_url": "https://github.com/owner/repo/issues/5",
+                "labels": [{ "name": "bug" }]
+            },
+            {
+                "number": 6,
+                "title": "Fix crash",
+                "body": "Fixes #5",
+                "user": { "login": "bob" },
+                "created_at": "2024-01-02T00:00:00Z",
+                "html_url": "https://github.com/owner/repo/pull/6",
+                "labels": [],
+                "pull_request": { "url": "https://api.github.com/repos/owner/repo/pulls/6" }
+            }
+        ]);
+
+        let items = open_items_from_api_response(&body).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].kind, GithubItemKind::Issue);
+        assert_eq!(items[0].body, "");
+        assert_eq!(items[0].author, "alice");
+        assert_eq!(items[0].labels, ["bug"]);
+        assert_eq!(items[1].kind, GithubItemKind::Pull);
+        assert_eq!(items[1].number, 6);
+        assert!(open_items_from_api_response(&serde_json::json!({})).is_err());
+    }
+
+    #[test]
+    fn test_comments_from_api_response() {
+        let body = serde_json::json!([
+            {
+                "id": 11,
+                "body": "Looks good",
+                "user": {"login": "carol"},
+                "created_at": "2024-01-03T00:00:00Z",
+                "html_url": "https://github.com/owner/repo/pull/6#issuecomment-11"
+            },
+            {"id": 12, "body": "No URL"}
+        ]);
+        let comments = comments_from_api_response(&body).unwrap();
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].id, 11);
+        assert_eq!(comments[0].author, "carol");
+        assert!(comments_from_api_response(&serde_json::json!({})).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_build_link_event() {
+        let keys = Keys::generate();
+        let coordinate = format!("30617:{owner}:repo", owner = keys.public_key().to_hex());
+        let issue = build_issue_event(&keys, &coordinate, "Bug", "", &[])
+            .await
+            .unwrap();
+        let item = GithubItem {
+            kind: GithubItemKind::Issue,
+            repository: "owner/repo".to_string(),
+            number: 4,
+            title: "Crash".to_string(),
+            state: "open".to_string(),
+            url: "https://github.com/owner/repo/issues/4".to_string(),
+            fetched_at: 1_700_000_000,
+        };
+
+        let event = build_link_event(&keys, &item, &issue, &coordinate)
+            .await
+            .unwrap();
+        assert_eq!(event.kind, Kind::Comment);
+        assert!(event.content.contains("owner/repo#4: Crash (open)"));
+
+        let tag = |name: &str| {
+            event
+                .tags
+                .iter()
+                .find(|t| t.as_slice()[0] == name)
+                .map(|t| t.as_slice()[1].clone())
+        };
+        assert_eq!(tag("E"), Some(issue.id.to_hex()));
+        assert_eq!(tag("K"), Some(KIND_ISSUE.as_u16().to_string()));
+        assert_eq!(tag("r").as_deref(), Some(item.url.as_str()));
+        assert_eq!(tag("state").as_deref(), Some("open"));
+    }
+}
@@ -0,0 +1,1056 @@
+//! Bridge between a GitHub repository and its Nostr counterpart
+//!
+//! [`import_from_github`] republishes the open pull requests and issues of a
+//! GitHub repository as NIP-34 patch series and issues. The importing account
+//! signs the events, so the GitHub author and creation time are kept in the
+//! content and the GitHub page in an `r` tag, which also keeps reruns from
+//! importing an item twice.
+//!
+//! [`export_to_github`] goes the other way: open PRs on Nostr become GitHub
+//! pull requests from a branch built out of their patches, and comments are
+//! mirrored between the two in both directions. What was mirrored is kept in
+//! an [`ExportState`] in the git directory, so repeated runs only carry over
+//! what is new.
+//!
+//! Anyone can open a PR on Nostr, while a branch on GitHub runs workflows. So
+//! branches go to a fork rather than the repository itself, only series from
+//! maintainers, allowed authors or approved by a maintainer are pushed, and
+//! series changing `.github/` are refused.
+
+use nostr::nips::nip19::ToBech32;
+use nostr::{Alphabet, Event, EventId, Filter, PublicKey, SingleLetterTag, Tag};
+use nostr_sdk::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::apply;
+use crate::comments;
+use crate::error::{Context, GitsmithError, Result, bail, ensure};
+use crate::github::{self, GithubComment, GithubItemKind, GithubOpenItem};
+use crate::issues::{self, KIND_ISSUE};
+use crate::patches::{self, KIND_PULL_REQUEST, PullRequestOptions};
+use crate::pull_request::{self, ListOptions, PullRequest, PullRequestStatus};
+use crate::relay::{self, Network};
+use crate::repo;
+use crate::reviews::{self, ReviewVerdict};
+use crate::signer::{self, Signer};
+
+/// Footer marking GitHub comments that came from Nostr
+const NOSTR_ORIGIN: &str = "Commented on Nostr by";
+
+/// How long to wait for relays during an export
+const EXPORT_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What [`import_from_github`] imports
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// GitHub token, for private repositories or to avoid rate limits
+    pub token: Option<String>,
+    pub pull_requests: bool,
+    pub issues: bool,
+    /// Proxy, authentication and cache of the relay connections
+    pub network: Network,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            token: None,
+            pull_requests: true,
+            issues: true,
+            network: Network::default(),
+        }
+    }
+}
+
+/// A GitHub item considered by an import
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportedItem {
+    pub kind: GithubItemKind,
+    pub number: u64,
+    pub title: String,
+    pub url: String,
+    /// PR or issue event published for the item
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<String>,
+    /// Why the item could not be imported
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ImportedItem {
+    fn new(item: &GithubOpenItem) -> Self {
+        Self {
+            kind: item.kind,
+            number: item.number,
+            title: item.title.clone(),
+            url: item.url.clone(),
+            event_id: None,
+            error: None,
+        }
+    }
+}
+
+/// Outcome of an import
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    pub imported: Vec<ImportedItem>,
+    /// Items imported by an earlier run
+    pub skipped: Vec<ImportedItem>,
+    pub failed: Vec<ImportedItem>,
+}
+
+/// Content of an imported item: its body and where it came from
+pub fn provenance(item: &GithubOpenItem) -> String {
+    let origin = format!(
+        "Imported from GitHub: {url}\nOpened by @{author} on {created_at}",
+        url = item.url,
+        author = item.author,
+        created_at = item.created_at
+    );
+    if item.body.trim().is_empty() {
+        origin
+    } else {
+        format!("{body}\n\n---\n{origin}", body = item.body.trim_end())
+    }
+}
+
+/// GitHub labels that can be used as labels on Nostr
+fn usable_labels(item: &GithubOpenItem) -> Vec<String> {
+    item.labels
+        .iter()
+        .filter(|label| !label.trim().is_empty() && !patches::is_marker_hashtag(label))
+        .cloned()
+        .collect()
+}
+
+/// Build the events republishing a GitHub item, the PR or issue event last
+///
+/// Pull requests need their `patches`, issues have none.
+pub async fn build_import_events(
+    signer: &dyn Signer,
+    repo_coordinate: &str,
+    root_commit: &str,
+    item: &GithubOpenItem,
+    patches: Vec<String>,
+) -> Result<Vec<Event>> {
+    let content = provenance(item);
+    match item.kind {
+        GithubItemKind::Pull => {
+            let options = PullRequestOptions {
+                max_event_size: Some(patches::DEFAULT_MAX_EVENT_SIZE),
+                split_large_patches: true,
+                labels: usable_labels(item),
+                external_url: Some(item.url.clone()),
+                ..Default::default()
+            };
+            patches::create_pull_request_event(
+                signer,
+                repo_coordinate,
+                &item.title,
+                &content,
+                patches,
+                root_commit,
+                &options,
+            )
+            .await
+        }
+        GithubItemKind::Issue => {
+            let builder = issues::issue_builder(
+                repo_coordinate,
+                &item.title,
+                &content,
+                &usable_labels(item),
+            )?
+            .tag(Tag::reference(&item.url));
+            Ok(vec![signer::sign(signer, builder).await?])
+        }
+    }
+}
+
+/// GitHub pages already republished on the repository's relays
+async fn imported_urls(
+    network: &Network,
+    client: &Client,
+    repo_coordinate: &str,
+    items: &[GithubOpenItem],
+) -> Result<HashSet<String>> {
+    if items.is_empty() {
+        return Ok(HashSet::new());
+    }
+    let filter = Filter::new()
+        .kinds([KIND_PULL_REQUEST, KIND_ISSUE])
+        .custom_tag(SingleLetterTag::lowercase(Alphabet::A), repo_coordinate)
+        .custom_tags(
+            SingleLetterTag::lowercase(Alphabet::R),
+            items.iter().map(|item| item.url.clone()),
+        );
+    let events =
+        relay::fetch_events(network, client, vec![filter], Duration::from_secs(10)).await?;
+    Ok(events
+        .iter()
+        .flat_map(|event| event.tags.iter())
+        .map(|tag| tag.as_slice())
+        .filter(|tag| tag.len() > 1 && tag[0] == "r")
+        .map(|tag| tag[1].clone())
+        .collect())
+}
+
+/// Republish the open pull requests and issues of `owner/repo` on the relays
+///
+/// Items are imported one by one, oldest first; a failing item is reported
+/// and the import goes on with the next one.
+pub async fn import_from_github(
+    signer: &dyn Signer,
+    owner: &str,
+    repo: &str,
+    repo_coordinate: &str,
+    root_commit: &str,
+    relays: &[String],
+    options: &ImportOptions,
+) -> Result<ImportReport> {
+    let network = &options.network;
+    let token = options.token.as_deref();
+    let items: Vec<GithubOpenItem> = github::fetch_open_items(owner, repo, token)
+        .await?
+        .into_iter()
+        .filter(|item| match item.kind {
+            GithubItemKind::Pull => options.pull_requests,
+            GithubItemKind::Issue => options.issues,
+        })
+        .collect();
+    info!(count = items.len(), "Importing open GitHub items");
+
+    let client = relay::connect(network, relays, Some(signer)).await?;
+    let imported = imported_urls(network, &client, repo_coordinate, &items).await?;
+
+    let mut report = ImportReport::default();
+    for item in &items {
+        let mut entry = ImportedItem::new(item);
+        if imported.contains(&item.url) {
+            report.skipped.push(entry);
+            continue;
+        }
+
+        let published = async {
+            let patches = match item.kind {
+                GithubItemKind::Pull => {
+                    github::fetch_pull_request_patches(owner, repo, item.number, token).await?
+                }
+                GithubItemKind::Issue => Vec::new(),
+            };
+            let events =
+                build_import_events(signer, repo_coordinate, root_commit, item, patches).await?;
+            let result = relay::publish_events(&client, &events).await?;
+            if result.successes.is_empty() {
+                bail!("No relay accepted the events");
+            }
+            Ok::<_, GitsmithError>(events.last().map(|event| event.id.to_hex()))
+        }
+        .await;
+
+        match published {
+            Ok(event_id) => {
+                entry.event_id = event_id;
+                report.imported.push(entry);
+            }
+            Err(e) => {
+                warn!(url = %item.url, "Failed to import: {e}");
+                entry.error = Some(e.to_string());
+                report.failed.push(entry);
+            }
+        }
+    }
+    client.disconnect().await;
+
+    Ok(report)
+}
+
+/// Where [`export_to_github`] mirrors PRs to
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    /// GitHub token allowed to open pull requests and comment
+    pub token: String,
+    /// Local clone the branches are built in
+    pub repo_path: PathBuf,
+    /// Git remote of the GitHub fork the branches are pushed to
+    pub fork_remote: String,
+    /// GitHub account owning the fork, see [`fork_owner`]
+    pub fork_owner: String,
+    /// Authors whose PRs are mirrored without a maintainer's approval, besides
+    /// the maintainers
+    pub allowed_authors: Vec<PublicKey>,
+    /// Branch the pull requests are opened against
+    pub base: String,
+    /// Proxy, authentication and cache of the relay connections
+    pub network: Network,
+}
+
+/// GitHub account owning the repository the git remote `remote` points at
+pub fn fork_owner(repo_path: &Path, remote: &str) -> Result<String> {
+    let repo = git2::Repository::discover(repo_path)
+        .with_context(|| format!("Failed to open git repository at {repo_path:?}"))?;
+    let found = repo
+        .find_remote(remote)
+        .with_context(|| format!("Git remote {remote} not found"))?;
+    let Some(url) = found.url() else {
+        bail!("Git remote {remote} has no URL");
+    };
+    let (owner, _name) = github::parse_github_repository(url)
+        .with_context(|| format!("Git remote {remote} is not a GitHub repository"))?;
+    Ok(owner)
+}
+
+/// A Nostr PR and the GitHub pull request mirroring it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MirroredPull {
+    pub number: u64,
+    pub url: String,
+    /// Branch pushed for the PR, None for PRs imported from GitHub
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// Latest revision of the patch series pushed to the branch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+}
+
+/// What the export bridge mirrored so far
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ExportState {
+    /// Mirrored PRs keyed by Nostr PR event ID (hex)
+    pub pulls: BTreeMap<String, MirroredPull>,
+    /// GitHub comment ID keyed by the Nostr comment event ID (hex) it was
+    /// mirrored to or from
+    pub comments: BTreeMap<String, u64>,
+    /// Revision (event ID, hex) keyed by the ID of the maintainer review that
+    /// applies to it, the PR's latest revision when the review was first seen
+    #[serde(default)]
+    pub reviewed_revisions: BTreeMap<String, String>,
+}
+
+impl ExportState {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read export state from {path:?}"))?;
+
+        serde_json::from_str(&data).context("Failed to parse export state")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {parent:?}"))?;
+        }
+
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(path, data).with_context(|| format!("Failed to write export state to {path:?}"))
+    }
+}
+
+/// Path of the export state of the repository at `repo_path`, inside its git directory
+pub fn export_state_path(repo_path: &Path) -> Result<PathBuf> {
+    let repo = git2::Repository::discover(repo_path)
+        .with_context(|| format!("Failed to open git repository at {repo_path:?}"))?;
+    Ok(repo.path().join("gitsmith").join("github-export.json"))
+}
+
+/// A PR considered by an export
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedPull {
+    /// Nostr PR event ID (hex)
+    pub event_id: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Why the PR could not be mirrored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ExportedPull {
+    fn new(pr: &PullRequest, mirrored: Option<&MirroredPull>) -> Self {
+        Self {
+            event_id: pr.id.clone(),
+            title: pr.title.clone(),
+            number: mirrored.map(|mirrored| mirrored.number),
+            url: mirrored.map(|mirrored| mirrored.url.clone()),
+            error: None,
+        }
+    }
+}
+
+/// Outcome of an export run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExportReport {
+    /// PRs opened on GitHub
+    pub opened: Vec<ExportedPull>,
+    /// PRs whose branch was pushed again for a new revision
+    pub updated: Vec<ExportedPull>,
+    /// PRs whose latest revision was not pushed, as neither a maintainer nor
+    /// an allowed author published it and no maintainer approved it
+    pub skipped: Vec<ExportedPull>,
+    pub failed: Vec<ExportedPull>,
+    /// Comments mirrored from Nostr to GitHub
+    pub comments_to_github: usize,
+    /// Comments mirrored from GitHub to Nostr
+    pub comments_to_nostr: usize,
+}
+
+/// Branch a Nostr PR is pushed to on GitHub
+pub fn export_branch(pr_id: &str) -> String {
+    format!("nostr/pr-{short}", short = &pr_id[..pr_id.len().min(8)])
+}
+
+/// Body of the GitHub pull request mirroring the Nostr PR `pr`
+pub fn pull_request_body(description: &str, pr: &Event) -> Result<String> {
+    let origin = format!(
+        "Mirrored from Nostr: nostr:{id}\nOpened by nostr:{author}",
+        id = pr.id.to_bech32()?,
+        author = pr.pubkey.to_bech32()?
+    );
+    if description.trim().is_empty() {
+        Ok(origin)
+    } else {
+        Ok(format!(
+            "{description}\n\n---\n{origin}",
+            description = description.trim_end()
+        ))
+    }
+}
+
+/// Body of the GitHub comment mirroring the Nostr comment `event`
+pub fn comment_to_github(event: &Event) -> Result<String> {
+    Ok(format!(
+        "{content}\n\n---\n{NOSTR_ORIGIN} nostr:{author}",
+        content = event.content.trim_end(),
+        author = event.pubkey.to_bech32()?
+    ))
+}
+
+/// Content of the Nostr comment mirroring the GitHub comment `comment`
+pub fn comment_to_nostr(comment: &GithubComment) -> String {
+    format!(
+        "{body}\n\n---\nCommented on GitHub by @{author}: {url}",
+        body = comment.body.trim_end(),
+        author = comment.author,
+        url = comment.url
+    )
+}
+
+/// URLs in the `r` tags of an event
+fn references(event: &Event) -> impl Iterator<Item = &str> {
+    event
+        .tags
+        .iter()
+        .map(|tag| tag.as_slice())
+        .filter(|tag| tag.len() > 1 && tag[0] == "r")
+        .map(|tag| tag[1].as_str())
+}
+
+/// Whether an event came from `owner/repo` on GitHub, by its `r` tags
+pub fn is_from_github(event: &Event, owner: &str, repo: &str) -> bool {
+    let prefix = format!("https://github.com/{owner}/{repo}/");
+    references(event).any(|url| url.starts_with(&prefix))
+}
+
+/// Whether the latest revision of `pr` may be pushed to GitHub
+///
+/// It must be published by a maintainer or one of the `allowed` authors, or
+/// approved by a maintainer since. A maintainer review among `events` applies
+/// to the revision recorded for it in `reviewed_revisions`, the latest one
+/// when it is first seen, so a backdated revision can't claim an approval.
+pub fn may_push(
+    pr: &PullRequest,
+    events: &[Event],
+    maintainers: &repo::Maintainers,
+    allowed: &[PublicKey],
+    reviewed_revisions: &mut BTreeMap<String, String>,
+) -> bool {
+    let (author, published_at) = pr
+        .revisions
+        .last()
+        .map_or((pr.author.as_str(), pr.created_at), |revision| {
+            (revision.author.as_str(), revision.created_at)
+        });
+    if maintainers.is_maintainer(author) || allowed.iter().any(|key| key.to_hex() == author) {
+        return true;
+    }
+
+    let latest = pr.latest_event_id();
+    let reviews: Vec<Event> = events
+        .iter()
+        .filter(|event| {
+            reviews::review_verdict(event).is_some()
+                && maintainers.is_maintainer(&event.pubkey.to_hex())
+        })
+        .filter(|event| {
+            let revision = reviewed_revisions
+                .entry(event.id.to_hex())
+                .or_insert_with(|| latest.to_string());
+            revision.as_str() == latest && event.created_at.as_u64() >= published_at
+        })
+        .cloned()
+        .collect();
+    reviews::summarize_reviews(&reviews, &pr.id).state() == Some(ReviewVerdict::Approved)
+}
+
+/// Files under `.github/` the commits between `base` and `head` change
+///
+/// Workflows and actions there run with the repository's permissions.
+fn github_config_changes(repo_path: &Path, base: &str, head: &str) -> Result<Vec<String>> {
+    let repo = git2::Repository::discover(repo_path)
+        .with_context(|| format!("Failed to open git repository at {repo_path:?}"))?;
+    let tree = |rev: &str| -> Result<git2::Tree<'_>> {
+        repo.revparse_single(rev)
+            .and_then(|object| object.peel_to_tree())
+            .with_context(|| format!("Commit {rev} not found"))
+    };
+    let diff = repo.diff_tree_to_tree(Some(&tree(base)?), Some(&tree(head)?), None)?;
+    let mut changed: Vec<String> = diff
+        .deltas()
+        .flat_map(|delta| [delta.old_file().path(), delta.new_file().path()])
+        .flatten()
+        .filter(|path| path.starts_with(".github"))
+        .map(|path| path.display().to_string())
+        .collect();
+    changed.sort();
+    changed.dedup();
+    Ok(changed)
+}
+
+/// The GitHub pull request a PR was imported from, if it was imported from `owner/repo`
+fn imported_pull(pr: &Event, owner: &str, repo: &str) -> Option<MirroredPull> {
+    references(pr).find_map(|url| {
+        let item = github::parse_github_url(url).ok()?;
+        (item.owner == owner && item.repo == repo && item.kind == GithubItemKind::Pull).then(|| {
+            MirroredPull {
+                number: item.number,
+                url: url.to_string(),
+                branch: None,
+                revision: None,
+            }
+        })
+    })
+}
+
+/// Mirror the open PRs of the repository to `owner/repo` on GitHub
+///
+/// PRs not mirrored yet are pushed to a branch of the fork at
+/// `options.fork_remote` and opened as pull requests, new revisions are
+/// force-pushed to their branch and the comments not mirrored yet are carried
+/// over in both directions. Revisions [`may_push`] refuses are skipped, PRs
+/// imported from GitHub only get their comments mirrored. The state at
+/// `state_path` is saved after each PR; a failing PR is reported and the run
+/// goes on.
+pub async fn export_to_github(
+    signer: &dyn Signer,
+    owner: &str,
+    repo: &str,
+    repo_coordinate: &str,
+    relays: &[String],
+    options: &ExportOptions,
+    state_path: &Path,
+) -> Result<ExportReport> {
+    ensure!(
+        !options.fork_owner.eq_ignore_ascii_case(owner),
+        "Git remote {remote} points at {owner}/{repo}, PR branches must go to a fork",
+        remote = options.fork_remote
+    );
+    let network = &options.network;
+    let list_options = ListOptions {
+        timeout: EXPORT_FETCH_TIMEOUT,
+        ..Default::default()
+    };
+    let prs = pull_request::list_pull_requests_with_options(
+        network,
+        repo_coordinate,
+        relays.to_vec(),
+        &list_options,
+    )
+    .await?;
+    let mut state = ExportState::load(state_path)?;
+    let client = relay::connect(network, relays, Some(signer)).await?;
+    let maintainers = pull_request::fetch_maintainers(
+        network,
+        Some(&client),
+        repo_coordinate,
+        EXPORT_FETCH_TIMEOUT,
+    )
+    .await?;
+    let exporter = Exporter {
+        client: &client,
+        signer,
+        owner,
+        repo,
+        repo_coordinate,
+        maintainers: &maintainers,
+        options,
+    };
+
+    let mut report = ExportReport::default();
+    for pr in prs.iter().filter(|pr| {
+        matches!(
+            pr.status,
+            PullRequestStatus::Open | PullRequestStatus::Updated
+        )
+    }) {
+        if let Err(e) = exporter.sync_pull(pr, &mut state, &mut report).await {
+            warn!(pr = %pr.id, "Failed to mirror: {e}");
+            let mut entry = ExportedPull::new(pr, state.pulls.get(&pr.id));
+            entry.error = Some(e.to_string());
+            report.failed.push(entry);
+        }
+        state.save(state_path)?;
+    }
+    client.disconnect().await;
+
+    Ok(report)
+}
+
+/// What mirroring a PR needs
+struct Exporter<'a> {
+    client: &'a Client,
+    signer: &'a dyn Signer,
+    owner: &'a str,
+    repo: &'a str,
+    repo_coordinate: &'a str,
+    maintainers: &'a repo::Maintainers,
+    options: &'a ExportOptions,
+}
+
+impl Exporter<'_> {
+    async fn sync_pull(
+        &self,
+        pr: &PullRequest,
+        state: &mut ExportState,
+        report: &mut ExportReport,
+    ) -> Result<()> {
+        let pr_id = EventId::from_hex(&pr.id)
+            .with_context(|| format!("Invalid event ID: {id}", id = pr.id))?;
+        let found = relay::fetch_events(
+            &self.options.network,
+            self.client,
+            vec![Filter::new().id(pr_id)],
+            EXPORT_FETCH_TIMEOUT,
+        )
+        .await?;
+        let Some(root) = found.into_iter().next() else {
+            return Err(GitsmithError::EventNotFound(format!(
+                "Pull request {id}",
+                id = pr.id
+            )));
+        };
+
+        let mut events = comments::fetch_comment_events(
+            &self.options.network,
+            self.client,
+            &root.id,
+            EXPORT_FETCH_TIMEOUT,
+        )
+        .await?;
+        events.sort_by_key(|event| event.created_at);
+        let pushable = may_push(
+            pr,
+            &events,
+            self.maintainers,
+            &self.options.allowed_authors,
+            &mut state.reviewed_revisions,
+        );
+
+        let mirrored = match state.pulls.get(&pr.id) {
+            Some(mirrored) => mirrored.clone(),
+            None => {
+                let mirrored = match imported_pull(&root, self.owner, self.repo) {
+                    Some(imported) => imported,
+                    None if !pushable => {
+                        report.skipped.push(ExportedPull::new(pr, None));
+                        return Ok(());
+                    }
+                    None => {
+                        let mirrored = self.open_pull(pr, &root).await?;
+                        report.opened.push(ExportedPull::new(pr, Some(&mirrored)));
+                        mirrored
+                    }
+                };
+                state.pulls.insert(pr.id.clone(), mirrored.clone());
+                mirrored
+            }
+        };
+
+        if let Some(branch) = &mirrored.branch
+            && mirrored.revision.as_deref() != Some(pr.latest_event_id())
+        {
+            if pushable {
+                self.push_series(pr.latest_event_id(), branch).await?;
+                if let Some(entry) = state.pulls.get_mut(&pr.id) {
+                    entry.revision = Some(pr.latest_event_id().to_string());
+                }
+                report.updated.push(ExportedPull::new(pr, Some(&mirrored)));
+            } else {
+                report.skipped.push(ExportedPull::new(pr, Some(&mirrored)));
+            }
+        }
+
+        self.sync_comments(&root, &events, mirrored.number, state, report)
+            .await
+    }
+
+    /// Push the PR's branch to the fork and open its GitHub pull request
+    async fn open_pull(&self, pr: &PullRequest, root: &Event) -> Result<MirroredPull> {
+        let branch = export_branch(&pr.id);
+        self.push_series(pr.latest_event_id(), &branch).await?;
+        let opened = github::create_pull_request(
+            self.owner,
+            self.repo,
+            &self.options.token,
+            &pr.title,
+            &pull_request_body(&pr.description, root)?,
+            &format!(
+                "{fork_owner}:{branch}",
+                fork_owner = self.options.fork_owner
+            ),
+            &self.options.base,
+        )
+        .await?;
+        info!(pr = %pr.id, url = %opened.url, "Opened GitHub pull request");
+        Ok(MirroredPull {
+            number: opened.number,
+            url: opened.url,
+            branch: Some(branch),
+            revision: Some(pr.latest_event_id().to_string()),
+        })
+    }
+
+    /// Build `branch` from the patch series of `revision` and push it to the fork
+    async fn push_series(&self, revision: &str, branch: &str) -> Result<()> {
+        let series = pull_request::fetch_patch_series_with(
+            &self.options.network,
+            self.client,
+            revision,
+            EXPORT_FETCH_TIMEOUT,
+        )
+        .await?;
+        let created = apply::create_branch_from_patches(
+            &self.options.repo_path,
+            branch,
+            &series.patch_contents(),
+            series.parent_commit().as_deref(),
+            true,
+        )?;
+        let changed = github_config_changes(&self.options.repo_path, &created.base, &created.head)?;
+        ensure!(
+            changed.is_empty(),
+            "The patch series changes {files}, refusing to push it to GitHub",
+            files = changed.join(", ")
+        );
+        apply::push_branch(&self.options.repo_path, &self.options.fork_remote, branch)
+    }
+
+    /// Mirror the comments of one side missing on the other, oldest first
+    async fn sync_comments(
+        &self,
+        root: &Event,
+        events: &[Event],
+        number: u64,
+        state: &mut ExportState,
+        report: &mut ExportReport,
+    ) -> Result<()> {
+        let github_comments =
+            github::fetch_issue_comments(self.owner, self.repo, number, Some(&self.options.token))
+                .await?;
+        let mirrored: HashSet<u64> = state.comments.values().copied().collect();
+
+        for event in events {
+            let id = event.id.to_hex();
+            if state.comments.contains_key(&id) || is_from_github(event, self.owner, self.repo) {
+                continue;
+            }
+            let posted = github::post_issue_comment(
+                self.owner,
+                self.repo,
+                number,
+                &self.options.token,
+                &comment_to_github(event)?,
+            )
+            .await?;
+            state.comments.insert(id, posted.id);
+            report.comments_to_github += 1;
+        }
+
+        for comment in &github_comments {
+            if mirrored.contains(&comment.id) || comment.body.contains(NOSTR_ORIGIN) {
+                continue;
+            }
+            let builder = comments::comment_builder(
+                root,
+                None,
+                &comment_to_nostr(comment),
+                Some(self.repo_coordinate),
+            )?
+            .tag(Tag::reference(&comment.url));
+            let event = signer::sign(self.signer, builder).await?;
+            let result = relay::publish_events(self.client, std::slice::from_ref(&event)).await?;
+            if result.successes.is_empty() {
+                bail!(
+                    "No relay accepted the comment mirroring {url}",
+                    url = comment.url
+                );
+            }
+            state.comments.insert(event.id.to_hex(), comment.id);
+            report.comments_to_nostr += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::tag_values;
+    use crate::pull_request;
+    use nostr::Keys;
+
+    fn item(kind: GithubItemKind) -> GithubOpenItem {
+        GithubOpenItem {
+            kind,
+            number: 6,
+            title: "Fix crash".to_string(),
+            body: "Fixes #5\n".to_string(),
+            author: "bob".to_string(),
+            created_at: "2024-01-02T00:00:00Z".to_string(),
+            url: "https://github.com/owner/repo/pull/6".to_string(),
+            labels: vec!["bug".to_string(), "root".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_provenance() {
+        let content = provenance(&item(GithubItemKind::Pull));
+        assert_eq!(
+            content,
+            "Fixes #5\n\n---\nImported from GitHub: https://github.com/owner/repo/pull/6\n\
+             Opened by @bob on 2024-01-02T00:00:00Z"
+        );
+
+        let empty = GithubOpenItem {
+            body: " ".to_string(),
+            ..item(GithubItemKind::Issue)
+        };
+        assert!(provenance(&empty).starts_with("Imported from GitHub"));
+    }
+
+    #[tokio::test]
+    async fn test_build_import_events() {
+        let keys = Keys::generate();
+        let coordinate = format!("30617:{owner}:repo", owner = keys.public_key().to_hex());
+        let patch = "From 1234567890abcdef1234567890abcdef12345678 Mon Sep 17 00:00:00 2001
+From: Bob <bob@example.com>
+Subject: [PATCH] Fix crash
+
+---
+ f.txt | 2 +-
+"
+        .to_string();
+
+        let events = build_import_events(
+            &keys,
+            &coordinate,
+            "abc",
+            &item(GithubItemKind::Pull),
+            vec![patch],
+        )
+        .await
+        .unwrap();
+        let pr = events.last().unwrap();
+        assert_eq!(pr.kind, KIND_PULL_REQUEST);
+        assert_eq!(pull_request::patch_event_ids(pr).len(), 1);
+        assert_eq!(
+            tag_values(pr, "r"),
+            ["https://github.com/owner/repo/pull/6"]
+        );
+        // NIP-34 markers can't be labels
+        assert_eq!(tag_values(pr, "t"), ["bug"]);
+
+        let events = build_import_events(
+            &keys,
+            &coordinate,
+            "abc",
+            &item(GithubItemKind::Issue),
+            Vec::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, KIND_ISSUE);
+        assert!(events[0].content.contains("Opened by @bob"));
+    }
+
+    #[test]
+    fn test_mirrored_content() {
+        let keys = Keys::generate();
+        let comment = nostr::EventBuilder::text_note("Looks good\n")
+            .sign_with_keys(&keys)
+            .unwrap();
+        let body = comment_to_github(&comment).unwrap();
+        assert!(body.starts_with("Looks good\n\n---\nCommented on Nostr by nostr:npub1"));
+        assert!(
+            pull_request_body("", &comment)
+                .unwrap()
+                .starts_with("Mirrored from Nostr: nostr:note1")
+        );
+
+        let github_comment = GithubComment {
+            id: 11,
+            body: "Thanks".to_string(),
+            author: "carol".to_string(),
+            created_at: "2024-01-03T00:00:00Z".to_string(),
+            url: "https://github.com/owner/repo/pull/6#issuecomment-11".to_string(),
+        };
+        assert_eq!(
+            comment_to_nostr(&github_comment),
+            "Thanks\n\n---\nCommented on GitHub by @carol: \
+             https://github.com/owner/repo/pull/6#issuecomment-11"
+        );
+    }
+
+    #[test]
+    fn test_imported_pull() {
+        let keys = Keys::generate();
+        let pr = nostr::EventBuilder::new(KIND_PULL_REQUEST, "")
+            .tag(Tag::reference("https://github.com/owner/repo/pull/6"))
+            .sign_with_keys(&keys)
+            .unwrap();
+        assert!(is_from_github(&pr, "owner", "repo"));
+        assert!(!is_from_github(&pr, "owner", "other"));
+        assert_eq!(imported_pull(&pr, "owner", "repo").unwrap().number, 6);
+        assert!(imported_pull(&pr, "someone", "repo").is_none());
+        assert_eq!(export_branch(&pr.id.to_hex()).len(), "nostr/pr-".len() + 8);
+    }
+
+    #[test]
+    fn test_export_state_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gitsmith").join("github-export.json");
+        assert!(ExportState::load(&path).unwrap().pulls.is_empty());
+
+        let mut state = ExportState::default();
+        state.pulls.insert(
+            "abc".to_string(),
+            MirroredPull {
+                number: 7,
+                url: "https://github.com/owner/repo/pull/7".to_string(),
+                branch: Some("nostr/pr-abc".to_string()),
+                revision: Some("abc".to_string()),
+            },
+        );
+        state.comments.insert("def".to_string(), 11);
+        state.save(&path).unwrap();
+
+        let loaded = ExportState::load(&path).unwrap();
+        assert_eq!(loaded.pulls, state.pulls);
+        assert_eq!(loaded.comments, state.comments);
+    }
+
+    #[tokio::test]
+    async fn test_may_push() {
+        let owner = Keys::generate();
+        let mallory = Keys::generate();
+        let coordinate = format!("30617:{owner}:repo", owner = owner.public_key().to_hex());
+        let maintainers = repo::resolve_maintainers(&coordinate, &[]).unwrap();
+        let root = nostr::EventBuilder::new(KIND_PULL_REQUEST, "")
+            .sign_with_keys(&mallory)
+            .unwrap();
+        let mut pr = PullRequest {
+            id: root.id.to_hex(),
+            title: "Fix crash".to_string(),
+            description: String::new(),
+            author: mallory.public_key().to_hex(),
+            author_name: None,
+            created_at: root.created_at.as_u64(),
+            updated_at: None,
+            patches_count: 1,
+            root_commit: None,
+            status: PullRequestStatus::Open,
+            comments_count: 0,
+            reactions: BTreeMap::new(),
+            revisions: Vec::new(),
+            by_maintainer: false,
+            reviews: Default::default(),
+            checks: Default::default(),
+            labels: Vec::new(),
+            merged_detected: false,
+        };
+        let approve = async |keys: &Keys, message: &str| {
+            reviews::build_review_event(keys, &root, ReviewVerdict::Approved, message, None)
+                .await
+                .unwrap()
+        };
+        let mut reviewed = BTreeMap::new();
+
+        assert!(!may_push(&pr, &[], &maintainers, &[], &mut reviewed));
+        let allowed = [mallory.public_key()];
+        assert!(may_push(&pr, &[], &maintainers, &allowed, &mut reviewed));
+        // Only maintainers' approvals count
+        let events = vec![approve(&mallory, "").await];
+        assert!(!may_push(&pr, &events, &maintainers, &[], &mut reviewed));
+        let mut events = vec![approve(&owner, "").await];
+        assert!(may_push(&pr, &events, &maintainers, &[], &mut reviewed));
+
+        // The approval stays with the revision it was given to, even if a
+        // later one claims an earlier time
+        pr.revisions.push(pull_request::PullRequestRevision {
+            id: "b".repeat(64),
+            version: 2,
+            author: pr.author.clone(),
+            created_at: pr.created_at,
+            patches_count: 1,
+        });
+        assert!(!may_push(&pr, &events, &maintainers, &[], &mut reviewed));
+        events.push(approve(&owner, "Version 2 looks good").await);
+        assert!(may_push(&pr, &events, &maintainers, &[], &mut reviewed));
+    }
+
+    #[test]
+    fn test_github_config_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        let commit = |path: &str| {
+            let file = dir.path().join(path);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(&file, path).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new(path)).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let signature = git2::Signature::now("Alice", "alice@example.com").unwrap();
+            let parents = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            repo.commit(Some("HEAD"), &signature, &signature, path, &tree, &parents)
+                .unwrap()
+                .to_string()
+        };
+        let base = commit("README.md");
+        let source = commit("src/main.rs");
+        let workflow = commit(".github/workflows/ci.yml");
+
+        assert!(
+            github_config_changes(dir.path(), &base, &source)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            github_config_changes(dir.path(), &base, &workflow).unwrap(),
+            [".github/workflows/ci.yml"]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! GRASP servers: relays that also host the git data of the repositories they
+//! receive announcements for
+//!
+//! An announcement lists a GRASP server twice, as a `relays` entry
+//! (`wss://server`) and as a `clone` URL (`https://server/<npub>/<identifier>.git`).
+//! Servers are kept as their `https://` (or `http://`) base URL.
+
+use nostr::{PublicKey, ToBech32};
+
+use crate::error::{GitsmithError, Result, ensure};
+use crate::relay;
+
+/// Base URL of a GRASP server given as a host, an http(s) or a ws(s) URL
+pub fn normalize_server(server: &str) -> Result<String> {
+    let server = server.trim();
+    let (scheme, host) = [
+        ("wss://", "https"),
+        ("https://", "https"),
+        ("ws://", "http"),
+        ("http://", "http"),
+    ]
+    .into_iter()
+    .find_map(|(prefix, scheme)| server.strip_prefix(prefix).map(|host| (scheme, host)))
+    .unwrap_or(("https", server));
+    let host = host.trim_end_matches('/');
+    ensure!(!host.is_empty(), "Invalid GRASP server {server}");
+
+    let base = format!("{scheme}://{host}");
+    let url = reqwest::Url::parse(&base).map_err(GitsmithError::msg)?;
+    ensure!(
+        url.host_str().is_some() && url.query().is_none(),
+        "Invalid GRASP server {server}"
+    );
+    Ok(base)
+}
+
+/// Relay URL of a GRASP server
+pub fn relay_url(server: &str) -> String {
+    match server.strip_prefix("http://") {
+        Some(rest) => format!("ws://{rest}"),
+        None => format!(
+            "wss://{rest}",
+            rest = server.strip_prefix("https://").unwrap_or(server)
+        ),
+    }
+}
+
+/// Clone URL a GRASP server serves the repository of `owner` at
+pub fn clone_url(server: &str, owner: &PublicKey, identifier: &str) -> Result<String> {
+    let npub = owner.to_bech32()?;
+    Ok(format!("{server}/{npub}/{identifier}.git"))
+}
+
+/// GRASP servers of an announcement: servers listed both as a relay and as a
+/// clone URL of the owner's repository
+pub fn servers_in(
+    clone_urls: &[String],
+    relays: &[String],
+    owner: &PublicKey,
+    identifier: &str,
+) -> Vec<String> {
+    let Ok(npub) = owner.to_bech32();
+    let suffix = format!("/{npub}/{identifier}.git");
+    clone_urls
+        .iter()
+        .filter_map(|url| url.strip_suffix(&suffix))
+        .filter(|server| server.starts_with("https://") || server.starts_with("http://"))
+        .filter(|server| {
+            let relay_url = relay_url(server);
+            relays
+                .iter()
+                .any(|known| relay::same_relay(known, &relay_url))
+        })
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::Keys;
+
+    #[test]
+    fn test_normalize_server() {
+        for server in [
+            "grasp.example.com",
+            "https://grasp.example.com/",
+            "wss://grasp.example.com",
+        ] {
+            assert_eq!(
+                normalize_server(server).unwrap(),
+                "https://grasp.example.com"
+            );
+        }
+        assert_eq!(
+            normalize_server("ws://127.0.0.1:8081").unwrap(),
+            "http://127.0.0.1:8081"
+        );
+        assert!(normalize_server("https://").is_err());
+    }
+
+    #[test]
+    fn test_servers_in() {
+        let owner = Keys::generate().public_key();
+        let server = "https://grasp.example.com";
+        let clone_urls = vec![
+            "https://github.com/example/repo.git".to_string(),
+            clone_url(server, &owner, "repo").unwrap(),
+            clone_url("https://other.example.com", &owner, "repo").unwrap(),
+        ];
+        let relays = vec!["wss://relay.example.com".to_string(), relay_url(server)];
+
+        assert_eq!(servers_in(&clone_urls, &relays, &owner, "repo"), [server]);
+        assert!(servers_in(&clone_urls, &relays, &owner, "other").is_empty());
+    }
+}
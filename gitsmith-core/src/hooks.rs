@@ -0,0 +1,204 @@
+use futures::future::BoxFuture;
+use git2::Repository;
+use nostr::{Event, EventBuilder, JsonUtil, NostrSigner, PublicKey, TagKind, UnsignedEvent};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::error::{Context, Result, bail, ensure};
+use crate::signer::Signer;
+
+/// Git config key holding the pre-sign hook command
+pub const PRE_SIGN_HOOK_KEY: &str = "nostr.pre-sign-hook";
+
+/// Environment variable overriding the configured pre-sign hook
+pub const PRE_SIGN_HOOK_ENV: &str = "GITSMITH_PRE_SIGN_HOOK";
+
+/// Read the pre-sign hook configured for the repository containing `repo_path`
+///
+/// `GITSMITH_PRE_SIGN_HOOK` takes precedence over the git config.
+pub fn configured_pre_sign_hook(repo_path: &Path) -> Option<String> {
+    if let Ok(command) = std::env::var(PRE_SIGN_HOOK_ENV) {
+        return Some(command).filter(|command| !command.is_empty());
+    }
+
+    let repo = Repository::discover(repo_path).ok()?;
+    let config = repo.config().ok()?;
+    config
+        .get_string(PRE_SIGN_HOOK_KEY)
+        .ok()
+        .filter(|command| !command.is_empty())
+}
+
+/// A signer that has the pre-sign hook run on every event before signing it
+///
+/// [`signer::sign`](crate::signer::sign) runs the hook, so it sees every
+/// event once and its changes are signed as they are.
+#[derive(Debug)]
+pub struct HookedSigner {
+    signer: Box<dyn Signer>,
+    command: String,
+}
+
+impl HookedSigner {
+    pub fn new(signer: Box<dyn Signer>, command: String) -> Self {
+        Self { signer, command }
+    }
+}
+
+impl Signer for HookedSigner {
+    fn public_key(&self) -> PublicKey {
+        self.signer.public_key()
+    }
+
+    fn sign_event(&self, unsigned: UnsignedEvent) -> BoxFuture<'_, Result<Event>> {
+        self.signer.sign_event(unsigned)
+    }
+
+    fn nostr_signer(&self) -> Arc<dyn NostrSigner> {
+        self.signer.nostr_signer()
+    }
+
+    fn pre_sign_hook(&self) -> Option<&str> {
+        Some(&self.command)
+    }
+}
+
+/// Have `signer` run the hook configured for the repository containing `repo_path`
+pub fn with_configured_hook(signer: Box<dyn Signer>, repo_path: &Path) -> Box<dyn Signer> {
+    match configured_pre_sign_hook(repo_path) {
+        Some(command) => Box::new(HookedSigner::new(signer, command)),
+        None => signer,
+    }
+}
+
+/// Run a pre-sign hook on an unsigned event
+///
+/// The hook gets the event JSON on stdin and may print a modified event on
+/// stdout; empty output keeps the event unchanged. A non-zero exit vetoes
+/// publication, with the hook's stderr as the reason.
+pub fn run_pre_sign_hook(command: &str, event: &UnsignedEvent) -> Result<UnsignedEvent> {
+    debug!(%command, kind = %event.kind, "Running pre-sign hook");
+    let mut child = Command::new("sh")
+        .args(["-c", command])
+        .env("GITSMITH_EVENT_KIND", event.kind.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run pre-sign hook '{command}'"))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // Hooks that don't read the event close stdin early
+        match stdin.write_all(event.as_json().as_bytes()) {
+            Err(e) if e.kind() != ErrorKind::BrokenPipe => {
+                return Err(e).context("Failed to write event to pre-sign hook");
+            }
+            _ => {}
+        }
+    }
+    let output = child
+        .wait_with_output()
+        .context("Failed to wait for pre-sign hook")?;
+
+    if !output.status.success() {
+        let reason = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if reason.is_empty() {
+            bail!(
+                "Pre-sign hook rejected kind {kind} event ({status})",
+                kind = event.kind,
+                status = output.status
+            );
+        }
+        bail!(
+            "Pre-sign hook rejected kind {kind} event: {reason}",
+            kind = event.kind
+        );
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.trim().is_empty() {
+        return Ok(event.clone());
+    }
+
+    let mut modified = UnsignedEvent::from_json(stdout.trim())
+        .context("Pre-sign hook printed an invalid event")?;
+    ensure!(
+        modified.pubkey == event.pubkey,
+        "Pre-sign hook must not change the event author"
+    );
+    // The ID is recomputed from the (possibly) modified fields
+    modified.id = None;
+    modified.ensure_id();
+
+    Ok(modified)
+}
+
+/// Mine an event to `difficulty`, replacing any nonce it already has
+pub(crate) fn mine(event: UnsignedEvent, difficulty: u8) -> UnsignedEvent {
+    let tags = event
+        .tags
+        .into_iter()
+        .filter(|tag| tag.kind() != TagKind::Nonce);
+    EventBuilder::new(event.kind, event.content)
+        .tags(tags)
+        .custom_created_at(event.created_at)
+        .allow_self_tagging()
+        .pow(difficulty)
+        .build(event.pubkey)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::{EventBuilder, Keys};
+
+    #[test]
+    fn test_run_pre_sign_hook() {
+        let keys = Keys::generate();
+        let event = EventBuilder::text_note("hello")
+            .build(keys.public_key())
+            .sign_with_keys(&keys)
+            .unwrap();
+        let unsigned = UnsignedEvent::from(event.clone());
+
+        // No output keeps the event, including its ID
+        let kept = run_pre_sign_hook("cat > /dev/null", &unsigned).unwrap();
+        assert_eq!(kept.id, Some(event.id));
+
+        let modified = run_pre_sign_hook("sed 's/hello/hello world/'", &unsigned).unwrap();
+        assert_eq!(modified.content, "hello world");
+        assert_ne!(modified.id, Some(event.id));
+        assert!(modified.sign_with_keys(&keys).is_ok());
+
+        let vetoed = run_pre_sign_hook("echo 'secret found' >&2; exit 1", &unsigned);
+        assert!(
+            vetoed
+                .unwrap_err()
+                .to_string()
+                .contains("rejected kind 1 event: secret found")
+        );
+    }
+
+    #[test]
+    fn test_mine_modified_event() {
+        let keys = Keys::generate();
+        let event = EventBuilder::text_note("hello").build(keys.public_key());
+
+        let modified = run_pre_sign_hook("sed 's/hello/hello world/'", &event).unwrap();
+        let mined = mine(modified, 8).sign_with_keys(&keys).unwrap();
+        assert_eq!(mined.content, "hello world");
+        assert_eq!(mined.created_at, event.created_at);
+        assert!(mined.check_pow(8));
+        assert_eq!(
+            mined
+                .tags
+                .iter()
+                .filter(|tag| tag.kind() == TagKind::Nonce)
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,334 @@
+use nostr::nips::nip19::{FromBech32, Nip19};
+use nostr::{Event, EventId, Filter, Kind};
+use nostr_sdk::{Client, RelayStatus};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::error::{Context, GitsmithError, Result};
+use crate::events::{
+    self, KIND_GIT_REPO_ANNOUNCEMENT, KIND_GIT_STATE, KIND_GIT_STATUS_APPLIED,
+    KIND_GIT_STATUS_CLOSED, KIND_GIT_STATUS_DRAFT, KIND_GIT_STATUS_OPEN,
+};
+use crate::issues::KIND_ISSUE;
+use crate::orgs::KIND_ORGANIZATION;
+use crate::patches::{
+    KIND_PATCH, KIND_PATCH_PART, KIND_PULL_REQUEST, KIND_PULL_REQUEST_UPDATE, KIND_SERIES_MANIFEST,
+};
+use crate::relay::{self, Network};
+
+/// An event to inspect: by ID, or the latest version of an addressable event
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventReference {
+    Id(EventId),
+    Address(nostr::nips::nip01::Coordinate),
+}
+
+impl EventReference {
+    fn filter(&self) -> Filter {
+        match self {
+            Self::Id(id) => Filter::new().id(*id),
+            Self::Address(coordinate) => Filter::new()
+                .kind(coordinate.kind)
+                .author(coordinate.public_key)
+                .identifier(&coordinate.identifier),
+        }
+    }
+}
+
+/// Parse an event reference: a hex ID, `note`, `nevent` or `naddr`, with or
+/// without a `nostr:` prefix
+///
+/// Returns the reference and the relay hints it carries.
+pub fn parse_event_reference(reference: &str) -> Result<(EventReference, Vec<String>)> {
+    let reference = reference.trim().trim_start_matches("nostr:");
+    if let Ok(id) = EventId::from_hex(reference) {
+        return Ok((EventReference::Id(id), vec![]));
+    }
+
+    let hints = |relays: &[nostr::RelayUrl]| relays.iter().map(|r| r.to_string()).collect();
+    match Nip19::from_bech32(reference)
+        .with_context(|| format!("Invalid event ID or address: {reference}"))?
+    {
+        Nip19::EventId(id) => Ok((EventReference::Id(id), vec![])),
+        Nip19::Event(event) => Ok((EventReference::Id(event.event_id), hints(&event.relays))),
+        Nip19::Coordinate(naddr) => Ok((
+            EventReference::Address(naddr.coordinate),
+            hints(&naddr.relays),
+        )),
+        _ => Err(GitsmithError::msg(format!(
+            "{reference} is not an event ID or address"
+        ))),
+    }
+}
+
+/// Name of the git-related and common event kinds
+pub fn kind_name(kind: Kind) -> Option<&'static str> {
+    [
+        (
+            Kind::from(KIND_GIT_REPO_ANNOUNCEMENT),
+            "repository announcement",
+        ),
+        (Kind::from(KIND_GIT_STATE), "repository state"),
+        (KIND_PATCH, "patch"),
+        (KIND_PULL_REQUEST, "pull request"),
+        (KIND_PULL_REQUEST_UPDATE, "pull request update"),
+        (KIND_SERIES_MANIFEST, "series manifest"),
+        (KIND_ISSUE, "issue"),
+        (KIND_PATCH_PART, "patch part"),
+        (Kind::from(KIND_GIT_STATUS_OPEN), "status: open"),
+        (
+            Kind::from(KIND_GIT_STATUS_APPLIED),
+            "status: applied/merged",
+        ),
+        (Kind::from(KIND_GIT_STATUS_CLOSED), "status: closed"),
+        (Kind::from(KIND_GIT_STATUS_DRAFT), "status: draft"),
+        (KIND_ORGANIZATION, "organization"),
+        (Kind::Metadata, "profile metadata"),
+        (Kind::TextNote, "text note"),
+        (Kind::EventDeletion, "deletion request"),
+        (Kind::Comment, "comment"),
+        (Kind::RelayList, "relay list"),
+    ]
+    .into_iter()
+    .find(|(known, _)| *known == kind)
+    .map(|(_, name)| name)
+}
+
+/// Whether a relay holds the inspected event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Holding {
+    Holds,
+    /// Holds an older version of the addressable event
+    Outdated,
+    Missing,
+    Unreachable,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayHolding {
+    pub url: String,
+    pub holding: Holding,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// An event as found on relays, with the relays that hold it
+#[derive(Debug, Clone, Serialize)]
+pub struct EventInspection {
+    /// Newest matching event found on any relay, `None` if no relay has it
+    pub event: Option<Event>,
+    /// Whether the ID is the hash of the event's content and tags
+    pub id_valid: bool,
+    /// Whether the signature is valid for the ID and author
+    ///
+    /// The relay pool already discards events with invalid signatures, a relay
+    /// serving only such a copy is reported as missing the event.
+    pub signature_valid: bool,
+    pub relays: Vec<RelayHolding>,
+}
+
+/// Ask every relay for the referenced event
+///
+/// Each relay is queried on its own, so the report tells which relays hold the
+/// event. For addresses the newest version found anywhere is the event,
+/// relays serving an older one are reported as outdated.
+pub async fn inspect_event(
+    network: &Network,
+    reference: &EventReference,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<EventInspection> {
+    let client = relay::new_client(network, None);
+    for relay_url in relays {
+        client.add_relay(relay_url).await?;
+    }
+    client.connect().await;
+    // Unreachable relays are reported as such, so not reaching all of them is fine
+    let _ =
+        relay::wait_for_connections(&client, relays.len(), relay::DEFAULT_CONNECT_TIMEOUT).await;
+
+    let statuses: HashMap<String, RelayStatus> = client
+        .pool()
+        .relays()
+        .await
+        .into_iter()
+        .map(|(url, relay)| {
+            (
+                url.to_string().trim_end_matches('/').to_string(),
+                relay.status(),
+            )
+        })
+        .collect();
+
+    let mut found: Vec<(String, Result<Option<Event>>)> = Vec::new();
+    for url in relays {
+        let result = if statuses.get(url.trim_end_matches('/')) == Some(&RelayStatus::Connected) {
+            fetch_from(&client, url, reference, timeout).await
+        } else {
+            Err(GitsmithError::msg("not connected"))
+        };
+        if let Err(e) = &result {
+            warn!("Failed to query {url}: {e}");
+        }
+        found.push((url.clone(), result));
+    }
+    client.disconnect().await;
+
+    let event = newest(
+        found
+            .iter()
+            .filter_map(|(_, result)| result.as_ref().ok().cloned().flatten()),
+    );
+    debug!(found = event.is_some(), "Inspected {reference:?}");
+    let relays = relay_holdings(found, event.as_ref());
+
+    Ok(EventInspection {
+        id_valid: event.as_ref().is_some_and(Event::verify_id),
+        signature_valid: event.as_ref().is_some_and(Event::verify_signature),
+        event,
+        relays,
+    })
+}
+
+/// Newest event matching the reference on a single relay
+async fn fetch_from(
+    client: &Client,
+    url: &str,
+    reference: &EventReference,
+    timeout: Duration,
+) -> Result<Option<Event>> {
+    let events = client
+        .fetch_events_from([url], reference.filter(), timeout)
+        .await?;
+    Ok(newest(events))
+}
+
+/// The version of an event that wins, the lowest ID on a `created_at` tie
+fn newest(found: impl IntoIterator<Item = Event>) -> Option<Event> {
+    found.into_iter().min_by(events::newest_first)
+}
+
+/// What each relay holds compared to the current version of the event
+fn relay_holdings(
+    found: Vec<(String, Result<Option<Event>>)>,
+    current: Option<&Event>,
+) -> Vec<RelayHolding> {
+    found
+        .into_iter()
+        .map(|(url, result)| {
+            let (holding, error) = match result {
+                Ok(Some(held)) if Some(held.id) == current.map(|e| e.id) => (Holding::Holds, None),
+                Ok(Some(_)) => (Holding::Outdated, None),
+                Ok(None) => (Holding::Missing, None),
+                Err(e) => (Holding::Unreachable, Some(e.to_string())),
+            };
+            RelayHolding {
+                url,
+                holding,
+                error,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::nips::nip01::Coordinate;
+    use nostr::nips::nip19::{Nip19Coordinate, Nip19Event, ToBech32};
+    use nostr::{Keys, RelayUrl};
+
+    #[test]
+    fn test_parse_event_reference() {
+        let id = EventId::all_zeros();
+        let relay = RelayUrl::parse("wss://relay.example.com").unwrap();
+
+        let (reference, hints) = parse_event_reference(&id.to_hex()).unwrap();
+        assert_eq!(reference, EventReference::Id(id));
+        assert!(hints.is_empty());
+
+        let note = id.to_bech32().unwrap();
+        let (reference, _) = parse_event_reference(&format!("nostr:{note}")).unwrap();
+        assert_eq!(reference, EventReference::Id(id));
+
+        let nevent = Nip19Event::new(id).relays([relay.clone()]);
+        let (reference, hints) = parse_event_reference(&nevent.to_bech32().unwrap()).unwrap();
+        assert_eq!(reference, EventReference::Id(id));
+        assert_eq!(hints, vec![relay.to_string()]);
+
+        let coordinate = Coordinate::new(
+            Kind::from(KIND_GIT_REPO_ANNOUNCEMENT),
+            Keys::generate().public_key(),
+        )
+        .identifier("repo");
+        let naddr = Nip19Coordinate::new(coordinate.clone(), [relay.clone()]);
+        let (reference, hints) = parse_event_reference(&naddr.to_bech32().unwrap()).unwrap();
+        assert_eq!(reference, EventReference::Address(coordinate));
+        assert_eq!(hints, vec![relay.to_string()]);
+
+        let npub = Keys::generate().public_key().to_bech32().unwrap();
+        assert!(parse_event_reference(&npub).is_err());
+        assert!(parse_event_reference("not-an-event").is_err());
+    }
+
+    #[test]
+    fn test_kind_name() {
+        assert_eq!(kind_name(KIND_PULL_REQUEST), Some("pull request"));
+        assert_eq!(
+            kind_name(Kind::from(KIND_GIT_STATE)),
+            Some("repository state")
+        );
+        assert_eq!(
+            kind_name(Kind::from(KIND_GIT_STATUS_OPEN)),
+            Some("status: open")
+        );
+        assert_eq!(kind_name(Kind::Custom(4242)), None);
+    }
+
+    #[test]
+    fn test_newest_keeps_lowest_id_on_tie() {
+        let keys = Keys::generate();
+        let version = |content: &str, created_at: u64| {
+            nostr::EventBuilder::new(Kind::from(KIND_GIT_STATE), content)
+                .custom_created_at(nostr::Timestamp::from(created_at))
+                .sign_with_keys(&keys)
+                .unwrap()
+        };
+        let older = version("older", 1_000);
+        let (mut low, mut high) = (version("a", 2_000), version("b", 2_000));
+        if low.id > high.id {
+            std::mem::swap(&mut low, &mut high);
+        }
+
+        let current = newest([high.clone(), older.clone(), low.clone()]).unwrap();
+        assert_eq!(current.id, low.id);
+
+        let found = vec![
+            ("wss://high".to_string(), Ok(Some(high))),
+            ("wss://low".to_string(), Ok(Some(low))),
+            ("wss://older".to_string(), Ok(Some(older))),
+            ("wss://empty".to_string(), Ok(None)),
+            (
+                "wss://down".to_string(),
+                Err(GitsmithError::msg("not connected")),
+            ),
+        ];
+        let holdings: Vec<Holding> = relay_holdings(found, Some(&current))
+            .into_iter()
+            .map(|relay| relay.holding)
+            .collect();
+        assert_eq!(
+            holdings,
+            [
+                Holding::Outdated,
+                Holding::Holds,
+                Holding::Outdated,
+                Holding::Missing,
+                Holding::Unreachable
+            ]
+        );
+    }
+}
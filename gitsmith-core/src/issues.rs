@@ -0,0 +1,432 @@
+use nostr::{Alphabet, Event, EventBuilder, EventId, Filter, Kind, SingleLetterTag, Tag};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use strum::{Display, EnumString};
+use tracing::{debug, info};
+
+use crate::comments::{self, Comment};
+use crate::error::{Context, GitsmithError, Result, ensure};
+use crate::events::{
+    self, KIND_GIT_STATUS_APPLIED, KIND_GIT_STATUS_CLOSED, KIND_GIT_STATUS_DRAFT,
+    KIND_GIT_STATUS_OPEN, tag_values,
+};
+use crate::relay::{self, Network};
+use crate::signer::{self, Signer};
+use crate::types::BroadcastResult;
+
+/// Kind for git issue events (NIP-34)
+pub const KIND_ISSUE: Kind = Kind::Custom(1621);
+
+/// Issue status as published with NIP-34 status events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display, EnumString)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum IssueStatus {
+    Open,
+    Resolved,
+    Closed,
+    Draft,
+}
+
+impl IssueStatus {
+    /// NIP-34 status event kind that publishes this status
+    pub fn status_kind(self) -> u16 {
+        match self {
+            Self::Open => KIND_GIT_STATUS_OPEN,
+            Self::Resolved => KIND_GIT_STATUS_APPLIED,
+            Self::Closed => KIND_GIT_STATUS_CLOSED,
+            Self::Draft => KIND_GIT_STATUS_DRAFT,
+        }
+    }
+
+    /// Status represented by a NIP-34 status event kind
+    pub fn from_status_kind(kind: u16) -> Option<Self> {
+        match kind {
+            KIND_GIT_STATUS_OPEN => Some(Self::Open),
+            KIND_GIT_STATUS_APPLIED => Some(Self::Resolved),
+            KIND_GIT_STATUS_CLOSED => Some(Self::Closed),
+            KIND_GIT_STATUS_DRAFT => Some(Self::Draft),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Issue {
+    pub id: String,
+    pub subject: String,
+    pub content: String,
+    pub author: String,
+    pub created_at: u64,
+    pub labels: Vec<String>,
+    pub status: IssueStatus,
+    /// Number of comments (NIP-22 comments and text note replies) on the issue
+    #[serde(default)]
+    pub comments_count: usize,
+}
+
+/// An issue together with its comments (see `comments::thread` for display order)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IssueThread {
+    #[serde(flatten)]
+    pub issue: Issue,
+    pub comments: Vec<Comment>,
+}
+
+/// Build an issue event for a repository
+pub async fn build_issue_event(
+    signer: &dyn Signer,
+    repo_coordinate: &str,
+    subject: &str,
+    content: &str,
+    labels: &[String],
+) -> Result<Event> {
+    let builder = issue_builder(repo_coordinate, subject, content, labels)?;
+    signer::sign(signer, builder).await
+}
+
+/// Start an issue event, returned unsigned so callers can attach extra tags
+pub fn issue_builder(
+    repo_coordinate: &str,
+    subject: &str,
+    content: &str,
+    labels: &[String],
+) -> Result<EventBuilder> {
+    ensure!(!subject.trim().is_empty(), "Issue subject cannot be empty");
+
+    let mut tags = vec![
+        Tag::parse(["a", repo_coordinate])?,
+        Tag::parse(["subject", subject])?,
+    ];
+    // Notify the repository owner
+    if let Some(owner) = repo_coordinate.split(':').nth(1) {
+        tags.push(Tag::parse(["p", owner])?);
+    }
+    for label in labels {
+        tags.push(Tag::hashtag(label));
+    }
+
+    Ok(EventBuilder::new(KIND_ISSUE, content).tags(tags))
+}
+
+/// Convert an issue event to an Issue
+pub fn parse_issue_event(event: &Event) -> Result<Issue> {
+    ensure!(
+        event.kind == KIND_ISSUE,
+        "Event {id} is not an issue (kind {kind})",
+        id = event.id,
+        kind = event.kind
+    );
+
+    Ok(Issue {
+        id: event.id.to_hex(),
+        subject: tag_value(event, "subject").unwrap_or_else(|| "Untitled issue".to_string()),
+        content: event.content.clone(),
+        author: event.pubkey.to_hex(),
+        created_at: event.created_at.as_u64(),
+        labels: tag_values(event, "t")
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        status: IssueStatus::Open,
+        comments_count: 0,
+    })
+}
+
+/// Publish a new issue to the repository's relays
+pub async fn create_issue(
+    network: &Network,
+    signer: &dyn Signer,
+    repo_coordinate: &str,
+    relays: &[String],
+    subject: &str,
+    content: &str,
+    labels: &[String],
+) -> Result<BroadcastResult> {
+    let event = build_issue_event(signer, repo_coordinate, subject, content, labels).await?;
+
+    let client = relay::connect(network, relays, Some(signer)).await?;
+    relay::publish_events(&client, &[event]).await
+}
+
+/// List issues for a repository, newest first
+pub async fn list_issues(
+    network: &Network,
+    repo_coordinate: &str,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<Vec<Issue>> {
+    info!("Listing issues for {repo_coordinate}");
+    let client = relay::connect(network, relays, None).await?;
+
+    let filter = Filter::new()
+        .kind(KIND_ISSUE)
+        .custom_tag(SingleLetterTag::lowercase(Alphabet::A), repo_coordinate);
+    let mut issues = relay::fetch_events(network, &client, vec![filter], timeout)
+        .await?
+        .iter()
+        .filter_map(|event| parse_issue_event(event).ok())
+        .collect::<Vec<_>>();
+
+    if !issues.is_empty() {
+        let ids: Vec<EventId> = issues
+            .iter()
+            .filter_map(|issue| EventId::from_hex(&issue.id).ok())
+            .collect();
+        let related = fetch_related_events(network, &client, ids, timeout).await?;
+        apply_issue_events(&mut issues, &related, repo_owner(repo_coordinate));
+    }
+
+    issues.sort_by_key(|issue| std::cmp::Reverse(issue.created_at));
+    Ok(issues)
+}
+
+/// Fetch a single issue with its current status and comments
+pub async fn fetch_issue(
+    network: &Network,
+    issue_id: &str,
+    repo_coordinate: &str,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<IssueThread> {
+    let id =
+        EventId::from_hex(issue_id).with_context(|| format!("Invalid event ID: {issue_id}"))?;
+    let client = relay::connect(network, relays, None).await?;
+
+    let found = relay::fetch_events(network, &client, vec![Filter::new().id(id)], timeout).await?;
+    let Some(event) = found.first() else {
+        return Err(GitsmithError::EventNotFound(format!("Issue {issue_id}")));
+    };
+    let mut issues = vec![parse_issue_event(event)?];
+
+    let related = fetch_related_events(network, &client, vec![id], timeout).await?;
+    apply_issue_events(&mut issues, &related, repo_owner(repo_coordinate));
+
+    let comments = related
+        .iter()
+        .filter(|e| e.kind == Kind::Comment || e.kind == Kind::TextNote)
+        .map(comments::parse_comment_event)
+        .collect();
+
+    Ok(IssueThread {
+        issue: issues.remove(0),
+        comments,
+    })
+}
+
+/// Publish a status event (e.g. closed) for an issue
+pub async fn set_issue_status(
+    network: &Network,
+    signer: &dyn Signer,
+    issue_id: &str,
+    status: IssueStatus,
+    repo_coordinate: &str,
+    relays: &[String],
+    message: &str,
+) -> Result<BroadcastResult> {
+    let id =
+        EventId::from_hex(issue_id).with_context(|| format!("Invalid event ID: {issue_id}"))?;
+    let client = relay::connect(network, relays, Some(signer)).await?;
+
+    let filter = Filter::new().id(id).kind(KIND_ISSUE);
+    let found = relay::fetch_events(network, &client, vec![filter], Duration::from_secs(5)).await?;
+    let Some(issue) = found.first() else {
+        return Err(GitsmithError::EventNotFound(format!("Issue {issue_id}")));
+    };
+
+    let builder = events::status_builder(
+        status.status_kind(),
+        &id,
+        &issue.pubkey,
+        repo_coordinate,
+        message,
+    )?;
+    let event = signer::sign(signer, builder).await?;
+
+    relay::publish_events(&client, &[event]).await
+}
+
+/// Fetch status and comment events that reference any of the given issues
+async fn fetch_related_events(
+    network: &Network,
+    client: &nostr_sdk::Client,
+    ids: Vec<EventId>,
+    timeout: Duration,
+) -> Result<Vec<Event>> {
+    let kinds = vec![
+        Kind::Comment,
+        Kind::TextNote,
+        Kind::from(KIND_GIT_STATUS_OPEN),
+        Kind::from(KIND_GIT_STATUS_APPLIED),
+        Kind::from(KIND_GIT_STATUS_CLOSED),
+        Kind::from(KIND_GIT_STATUS_DRAFT),
+    ];
+    let direct = Filter::new().kinds(kinds).events(ids.clone());
+    // Nested NIP-22 comments only reference the issue through the uppercase `E` root tag
+    let rooted = Filter::new().kind(Kind::Comment).custom_tags(
+        SingleLetterTag::uppercase(Alphabet::E),
+        ids.iter().map(|id| id.to_hex()),
+    );
+
+    relay::fetch_events(network, client, vec![direct, rooted], timeout).await
+}
+
+/// Apply status and comment events to issues
+///
+/// Only status events from the issue author or the repository owner count, and the
+/// most recent one wins.
+pub fn apply_issue_events(issues: &mut [Issue], events: &[Event], repo_owner: Option<&str>) {
+    let index: HashMap<String, usize> = issues
+        .iter()
+        .enumerate()
+        .map(|(i, issue)| (issue.id.clone(), i))
+        .collect();
+    let mut latest: HashMap<usize, (u64, EventId)> = HashMap::new();
+    let mut seen = HashSet::new();
+
+    for event in events {
+        if !seen.insert(event.id) {
+            continue;
+        }
+
+        if let Some(status) = IssueStatus::from_status_kind(event.kind.as_u16()) {
+            let Some(&i) = tag_values(event, "e").iter().find_map(|id| index.get(*id)) else {
+                continue;
+            };
+            let author = event.pubkey.to_hex();
+            if author != issues[i].author && Some(author.as_str()) != repo_owner {
+                debug!(
+                    "Ignoring status event {id} from unauthorized author",
+                    id = event.id
+                );
+                continue;
+            }
+
+            let candidate = (event.created_at.as_u64(), event.id);
+            if latest.get(&i).is_none_or(|current| candidate > *current) {
+                latest.insert(i, candidate);
+                issues[i].status = status;
+            }
+        } else if event.kind == Kind::Comment || event.kind == Kind::TextNote {
+            let target = tag_values(event, "E")
+                .into_iter()
+                .chain(tag_values(event, "e"))
+                .find_map(|id| index.get(id));
+            if let Some(&i) = target {
+                issues[i].comments_count += 1;
+            }
+        }
+    }
+}
+
+/// Format an issue for display
+pub fn format_issue(issue: &Issue) -> String {
+    let mut output = String::new();
+
+    output.push_str(&format!("Subject: {subject}\n", subject = issue.subject));
+    output.push_str(&format!(
+        "Author: {author}...\n",
+        author = &issue.author[..16.min(issue.author.len())]
+    ));
+    output.push_str(&format!("Status: {status}\n", status = issue.status));
+    if !issue.labels.is_empty() {
+        output.push_str(&format!(
+            "Labels: {labels}\n",
+            labels = issue.labels.join(", ")
+        ));
+    }
+    if issue.comments_count > 0 {
+        output.push_str(&format!(
+            "Comments: {comments_count}\n",
+            comments_count = issue.comments_count
+        ));
+    }
+    output.push_str(&format!("ID: {id}\n", id = issue.id));
+
+    if !issue.content.is_empty() {
+        output.push_str(&format!("\n{content}\n", content = issue.content));
+    }
+
+    output
+}
+
+fn repo_owner(repo_coordinate: &str) -> Option<&str> {
+    repo_coordinate.split(':').nth(1)
+}
+
+fn tag_value(event: &Event, tag_name: &str) -> Option<String> {
+    tag_values(event, tag_name).first().map(|s| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::{Keys, Timestamp};
+
+    #[tokio::test]
+    async fn test_issue_event_round_trip() {
+        let keys = Keys::generate();
+        let coordinate = format!("30617:{owner}:my-repo", owner = keys.public_key().to_hex());
+        let event = build_issue_event(
+            &keys,
+            &coordinate,
+            "Crash on startup",
+            "Steps to reproduce...",
+            &["bug".to_string(), "ui".to_string()],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(event.kind, KIND_ISSUE);
+        assert_eq!(tag_value(&event, "a").as_deref(), Some(coordinate.as_str()));
+
+        let issue = parse_issue_event(&event).unwrap();
+        assert_eq!(issue.subject, "Crash on startup");
+        assert_eq!(issue.content, "Steps to reproduce...");
+        assert_eq!(issue.labels, vec!["bug", "ui"]);
+        assert_eq!(issue.status, IssueStatus::Open);
+
+        assert!(
+            build_issue_event(&keys, &coordinate, "  ", "", &[])
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_issue_events() {
+        let owner = Keys::generate();
+        let author = Keys::generate();
+        let stranger = Keys::generate();
+        let coordinate = format!("30617:{owner}:repo", owner = owner.public_key().to_hex());
+        let issue_event = build_issue_event(&author, &coordinate, "Bug", "", &[])
+            .await
+            .unwrap();
+        let mut issues = vec![parse_issue_event(&issue_event).unwrap()];
+
+        let status = |kind: u16, keys: &Keys, at: u64| {
+            EventBuilder::new(Kind::from(kind), "")
+                .tags([Tag::event(issue_event.id)])
+                .custom_created_at(Timestamp::from(at))
+                .sign_with_keys(keys)
+                .unwrap()
+        };
+        let comment = EventBuilder::new(Kind::Comment, "Me too")
+            .tags([Tag::parse(["E", &issue_event.id.to_hex()]).unwrap()])
+            .sign_with_keys(&stranger)
+            .unwrap();
+
+        let events = vec![
+            status(KIND_GIT_STATUS_CLOSED, &owner, 200),
+            status(KIND_GIT_STATUS_OPEN, &author, 100),
+            status(KIND_GIT_STATUS_APPLIED, &stranger, 300),
+            comment.clone(),
+            comment,
+        ];
+        apply_issue_events(&mut issues, &events, Some(&owner.public_key().to_hex()));
+
+        assert_eq!(issues[0].status, IssueStatus::Closed);
+        assert_eq!(issues[0].comments_count, 1);
+    }
+}
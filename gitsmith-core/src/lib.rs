@@ -1,17 +1,58 @@
 pub mod account;
+pub mod apply;
+pub mod blossom;
+pub mod cache;
+pub mod ci;
+pub mod comments;
+pub mod commit_signatures;
+pub mod config;
+pub mod coverage;
+pub mod email;
+pub mod email_bridge;
+pub mod error;
 pub mod events;
+pub mod format_patch;
+pub mod github;
+pub mod github_bridge;
+pub mod grasp;
+pub mod hooks;
+pub mod inspect;
+pub mod issues;
+pub mod license;
+pub mod localnet;
+pub mod orgs;
 pub mod patches;
+pub mod profiles;
 pub mod pull_request;
+pub mod readme;
+pub mod relay;
+pub mod relay_lists;
+pub mod relay_migration;
+pub mod relay_probe;
+pub mod release;
+pub mod remote_helper;
 pub mod repo;
+pub mod reviews;
+pub mod secrets;
+pub mod seen;
+pub mod signer;
+pub mod state;
+pub mod subscriptions;
+pub mod template;
+pub mod time;
 pub mod types;
+pub mod watch;
 
 // Re-export main types and functions for convenience
+pub use error::{GitsmithError, Result};
 pub use events::{
-    KIND_GIT_PATCH, KIND_GIT_REPO_ANNOUNCEMENT, KIND_GIT_STATE, build_announcement_event,
-    build_state_event,
+    KIND_GIT_PATCH, KIND_GIT_REPO_ANNOUNCEMENT, KIND_GIT_STATE, KIND_GIT_STATUS_APPLIED,
+    KIND_GIT_STATUS_CLOSED, KIND_GIT_STATUS_DRAFT, KIND_GIT_STATUS_OPEN, build_announcement_event,
+    build_state_event, build_status_event,
 };
 pub use repo::{
-    announce_repository, detect_from_git, get_git_state, get_repo_owner, update_git_config,
-    update_git_config_full,
+    announce_repository, detect_from_git, get_git_state, get_repo_account, get_repo_owner,
+    update_git_config, update_git_config_full,
 };
-pub use types::{GitState, PublishConfig, PublishResult, RepoAnnouncement};
+pub use signer::Signer;
+pub use types::{BroadcastResult, GitState, PublishConfig, PublishResult, RepoAnnouncement};
@@ -0,0 +1,253 @@
+use crate::error::{Result, bail, ensure};
+
+/// How much a license constrains the licensing of derived work
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LicenseFamily {
+    /// MIT, Apache-2.0, BSD, ...: derived work can use any license
+    Permissive,
+    /// MPL, LGPL, EPL: modified files keep the original license
+    WeakCopyleft,
+    /// GPL, AGPL: derived work must use the same license
+    StrongCopyleft,
+    Unknown,
+}
+
+/// Check that `expression` looks like an SPDX license expression
+///
+/// Accepts license identifiers (`MIT`, `GPL-3.0-or-later`, `LicenseRef-Custom`)
+/// combined with `AND`, `OR`, `WITH` and parentheses. Identifiers are not checked
+/// against the SPDX list, so new licenses keep working.
+pub fn validate_license(expression: &str) -> Result<()> {
+    let spaced = expression.replace('(', " ( ").replace(')', " ) ");
+    let tokens: Vec<&str> = spaced.split_whitespace().collect();
+    ensure!(!tokens.is_empty(), "License must not be empty");
+
+    let mut depth = 0i32;
+    let mut expect_license = true;
+    for token in tokens {
+        match token {
+            "(" if expect_license => depth += 1,
+            ")" if !expect_license => depth -= 1,
+            "AND" | "OR" | "WITH" if !expect_license => expect_license = true,
+            _ if expect_license && is_license_id(token) => expect_license = false,
+            _ => bail!("Invalid SPDX license expression: {expression}"),
+        }
+        ensure!(
+            depth >= 0,
+            "Unbalanced parentheses in license expression: {expression}"
+        );
+    }
+    ensure!(
+        depth == 0 && !expect_license,
+        "Incomplete SPDX license expression: {expression}"
+    );
+
+    Ok(())
+}
+
+fn is_license_id(token: &str) -> bool {
+    let id = token.strip_suffix('+').unwrap_or(token);
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+/// Family of an SPDX license expression
+///
+/// Operands of `OR` let the licensee pick, so the most permissive one counts;
+/// operands of `AND` all apply, so the most restrictive one does. Exceptions
+/// after `WITH` are ignored.
+pub fn license_family(expression: &str) -> LicenseFamily {
+    let spaced = expression.replace('(', " ( ").replace(')', " ) ");
+    let mut tokens = spaced.split_whitespace().peekable();
+    let family = parse_or(&mut tokens);
+    match (family, tokens.next()) {
+        (Some(family), None) => family,
+        _ => LicenseFamily::Unknown,
+    }
+}
+
+type Tokens<'a> = std::iter::Peekable<std::str::SplitWhitespace<'a>>;
+
+fn parse_or(tokens: &mut Tokens) -> Option<LicenseFamily> {
+    let mut family = parse_and(tokens)?;
+    while tokens.next_if_eq(&"OR").is_some() {
+        let operand = parse_and(tokens)?;
+        if restrictiveness(operand) < restrictiveness(family) {
+            family = operand;
+        }
+    }
+    Some(family)
+}
+
+fn parse_and(tokens: &mut Tokens) -> Option<LicenseFamily> {
+    let mut family = parse_operand(tokens)?;
+    while tokens.next_if_eq(&"AND").is_some() {
+        let operand = parse_operand(tokens)?;
+        if restrictiveness(operand) > restrictiveness(family) {
+            family = operand;
+        }
+    }
+    Some(family)
+}
+
+fn parse_operand(tokens: &mut Tokens) -> Option<LicenseFamily> {
+    let family = match tokens.next()? {
+        "(" => {
+            let family = parse_or(tokens)?;
+            tokens.next_if_eq(&")")?;
+            family
+        }
+        "AND" | "OR" | "WITH" | ")" => return None,
+        id => id_family(id),
+    };
+    if tokens.next_if_eq(&"WITH").is_some() {
+        tokens.next()?;
+    }
+    Some(family)
+}
+
+/// Order families by how much they constrain derived work
+///
+/// An unknown license may be anything short of strong copyleft, so it ranks
+/// between the known families: it loses to a permissive alternative and wins
+/// over a permissive requirement.
+fn restrictiveness(family: LicenseFamily) -> u8 {
+    match family {
+        LicenseFamily::Permissive => 0,
+        LicenseFamily::WeakCopyleft => 1,
+        LicenseFamily::Unknown => 2,
+        LicenseFamily::StrongCopyleft => 3,
+    }
+}
+
+/// Family of a single SPDX license identifier
+fn id_family(id: &str) -> LicenseFamily {
+    let id = id.trim_end_matches('+').to_ascii_uppercase();
+    let starts_with_any = |prefixes: &[&str]| prefixes.iter().any(|p| id.starts_with(p));
+
+    if starts_with_any(&["AGPL-", "GPL-"]) {
+        LicenseFamily::StrongCopyleft
+    } else if starts_with_any(&["LGPL-", "MPL-", "EPL-", "EUPL-", "CDDL-"]) {
+        LicenseFamily::WeakCopyleft
+    } else if starts_with_any(&[
+        "MIT",
+        "APACHE-",
+        "BSD-",
+        "0BSD",
+        "ISC",
+        "ZLIB",
+        "UNLICENSE",
+        "CC0-",
+    ]) {
+        LicenseFamily::Permissive
+    } else {
+        LicenseFamily::Unknown
+    }
+}
+
+/// Explain why publishing work derived from `upstream` under `downstream` may conflict
+///
+/// Returns None when the licenses are compatible or nothing is known about them.
+pub fn license_conflict(upstream: Option<&str>, downstream: Option<&str>) -> Option<String> {
+    let upstream = upstream?;
+    let same = |a: &str, b: &str| {
+        let normalize = |id: &str| {
+            id.trim()
+                .trim_end_matches('+')
+                .trim_end_matches("-or-later")
+                .trim_end_matches("-only")
+                .to_ascii_uppercase()
+        };
+        normalize(a) == normalize(b)
+    };
+
+    match (license_family(upstream), downstream) {
+        (LicenseFamily::StrongCopyleft | LicenseFamily::WeakCopyleft, None) => Some(format!(
+            "{upstream} requires derived work to keep its license, but no license is set"
+        )),
+        (LicenseFamily::StrongCopyleft, Some(downstream)) if !same(upstream, downstream) => Some(
+            format!("{upstream} requires derived work to use the same license, not {downstream}"),
+        ),
+        (LicenseFamily::WeakCopyleft, Some(downstream)) if !same(upstream, downstream) => {
+            Some(format!(
+                "Files derived from {upstream} code must stay under {upstream}, even in a {downstream} project"
+            ))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_license() {
+        for valid in [
+            "MIT",
+            "GPL-3.0-or-later",
+            "MIT OR Apache-2.0",
+            "(MIT AND BSD-3-Clause) OR GPL-2.0+",
+            "Apache-2.0 WITH LLVM-exception",
+            "LicenseRef-Custom",
+        ] {
+            assert!(validate_license(valid).is_ok(), "{valid}");
+        }
+        for invalid in ["", "MIT OR", "MIT Apache-2.0", "(MIT", "MIT)", "MIT/X11"] {
+            assert!(validate_license(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_license_family() {
+        assert_eq!(license_family("MIT"), LicenseFamily::Permissive);
+        assert_eq!(license_family("GPL-2.0+"), LicenseFamily::StrongCopyleft);
+        assert_eq!(license_family("GPL-2.0 OR MIT"), LicenseFamily::Permissive);
+        assert_eq!(
+            license_family("(MIT OR Apache-2.0)"),
+            LicenseFamily::Permissive
+        );
+        assert_eq!(
+            license_family("MIT AND MPL-2.0"),
+            LicenseFamily::WeakCopyleft
+        );
+        assert_eq!(
+            license_family("(MIT OR Apache-2.0) AND GPL-3.0-only"),
+            LicenseFamily::StrongCopyleft
+        );
+        assert_eq!(
+            license_family("MIT AND GPL-3.0-only OR Apache-2.0"),
+            LicenseFamily::Permissive
+        );
+        assert_eq!(
+            license_family("GPL-2.0-only WITH Classpath-exception-2.0"),
+            LicenseFamily::StrongCopyleft
+        );
+        assert_eq!(
+            license_family("GPL-3.0-only OR LicenseRef-Custom"),
+            LicenseFamily::Unknown
+        );
+        assert_eq!(
+            license_family("MIT AND LicenseRef-Custom"),
+            LicenseFamily::Unknown
+        );
+        assert_eq!(license_family("(MIT"), LicenseFamily::Unknown);
+    }
+
+    #[test]
+    fn test_license_conflict() {
+        assert_eq!(license_conflict(None, Some("MIT")), None);
+        assert_eq!(license_conflict(Some("MIT"), Some("GPL-3.0-only")), None);
+        assert_eq!(
+            license_conflict(Some("GPL-3.0-only"), Some("GPL-3.0-or-later")),
+            None
+        );
+        assert!(license_conflict(Some("GPL-3.0-only"), Some("MIT")).is_some());
+        assert!(license_conflict(Some("MPL-2.0"), Some("MIT")).is_some());
+        assert!(license_conflict(Some("AGPL-3.0-only"), None).is_some());
+        assert_eq!(license_conflict(Some("GPL-2.0 OR MIT"), Some("MIT")), None);
+        assert!(license_conflict(Some("MIT AND GPL-3.0-only"), Some("MIT")).is_some());
+    }
+}
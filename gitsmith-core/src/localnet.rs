@@ -0,0 +1,284 @@
+//! Embedded relay for offline and air-gapped collaboration
+//!
+//! `gitsmith localnet up` runs this relay on localhost so a team can publish
+//! and review without reaching any public relay. Events are kept in a SQLite
+//! [`CacheStore`] and can be copied to public relays later with [`replicate`].
+
+use futures::{SinkExt, StreamExt};
+use nostr::filter::MatchEventOptions;
+use nostr::{ClientMessage, Event, Filter, JsonUtil, RelayMessage, SubscriptionId, Url};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info, warn};
+
+use crate::cache::{CacheStore, SqliteStore};
+use crate::error::{Context, GitsmithError, Result};
+use crate::relay::{self, Network};
+use crate::types::BroadcastResult;
+
+/// Port the local relay listens on by default
+pub const DEFAULT_PORT: u16 = 7777;
+
+/// How many published events a slow connection may fall behind on
+const LIVE_EVENTS_CAPACITY: usize = 1024;
+
+/// URL of the local relay on `port`
+pub fn relay_url(port: u16) -> String {
+    format!("ws://127.0.0.1:{port}")
+}
+
+/// Whether a relay URL points at this machine
+pub fn is_local_relay(url: &str) -> bool {
+    Url::parse(url)
+        .is_ok_and(|url| matches!(url.host_str(), Some("127.0.0.1" | "localhost" | "[::1]")))
+}
+
+/// Directory holding the local relay's events and pid file
+pub fn get_localnet_dir() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("Failed to get home directory")?;
+    Ok(home.join(".config").join("gitsmith").join("localnet"))
+}
+
+/// Open the event store of the local relay
+pub fn open_store() -> Result<Arc<dyn CacheStore>> {
+    Ok(Arc::new(SqliteStore::open(
+        &get_localnet_dir()?.join("events.sqlite"),
+    )?))
+}
+
+/// Minimal NIP-01 relay serving the events of a [`CacheStore`]
+#[derive(Clone)]
+pub struct LocalRelay {
+    store: Arc<dyn CacheStore>,
+    live: broadcast::Sender<Event>,
+}
+
+impl LocalRelay {
+    pub fn new(store: Arc<dyn CacheStore>) -> Self {
+        let (live, _) = broadcast::channel(LIVE_EVENTS_CAPACITY);
+        Self { store, live }
+    }
+
+    /// Accept connections until the listener fails
+    pub async fn serve(self, listener: TcpListener) -> Result<()> {
+        info!(
+            "Local relay listening on {address}",
+            address = listener.local_addr()?
+        );
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let relay = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = relay.handle_connection(stream).await {
+                    debug!("Connection from {peer} closed: {e}");
+                }
+            });
+        }
+    }
+
+    async fn handle_connection(self, stream: TcpStream) -> Result<()> {
+        let socket = tokio_tungstenite::accept_async(stream)
+            .await
+            .map_err(GitsmithError::relay)?;
+        let (mut sink, mut incoming) = socket.split();
+        let mut live = self.live.subscribe();
+        let mut subscriptions: HashMap<SubscriptionId, Vec<Filter>> = HashMap::new();
+
+        loop {
+            let replies = tokio::select! {
+                message = incoming.next() => match message {
+                    Some(Ok(Message::Text(text))) => self.handle_message(&text, &mut subscriptions),
+                    Some(Ok(Message::Close(_))) | None => return Ok(()),
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(GitsmithError::relay(e)),
+                },
+                event = live.recv() => match event {
+                    Ok(event) => live_replies(&event, &subscriptions),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Connection fell behind, {skipped} live event(s) not delivered");
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                },
+            };
+            for reply in replies {
+                sink.send(Message::text(reply.as_json()))
+                    .await
+                    .map_err(GitsmithError::relay)?;
+            }
+        }
+    }
+
+    /// Replies to a client message
+    fn handle_message(
+        &self,
+        text: &str,
+        subscriptions: &mut HashMap<SubscriptionId, Vec<Filter>>,
+    ) -> Vec<RelayMessage<'static>> {
+        let message = match ClientMessage::from_json(text) {
+            Ok(message) => message,
+            Err(e) => return vec![RelayMessage::notice(format!("invalid: {e}"))],
+        };
+
+        match message {
+            ClientMessage::Event(event) => vec![self.save_event(event.into_owned())],
+            ClientMessage::Req {
+                subscription_id,
+                filter,
+            } => self.subscribe(
+                subscription_id.into_owned(),
+                vec![filter.into_owned()],
+                subscriptions,
+            ),
+            ClientMessage::ReqMultiFilter {
+                subscription_id,
+                filters,
+            } => self.subscribe(subscription_id.into_owned(), filters, subscriptions),
+            ClientMessage::Count {
+                subscription_id,
+                filter,
+            } => match self.store.query(&[filter.into_owned()]) {
+                Ok(events) => vec![RelayMessage::count(
+                    subscription_id.into_owned(),
+                    events.len(),
+                )],
+                Err(e) => vec![RelayMessage::closed(
+                    subscription_id.into_owned(),
+                    format!("error: {e}"),
+                )],
+            },
+            ClientMessage::Close(subscription_id) => {
+                subscriptions.remove(&subscription_id);
+                Vec::new()
+            }
+            _ => vec![RelayMessage::notice("unsupported: message type")],
+        }
+    }
+
+    fn save_event(&self, event: Event) -> RelayMessage<'static> {
+        if let Err(e) = event.verify() {
+            return RelayMessage::ok(event.id, false, format!("invalid: {e}"));
+        }
+        match self.store.save_events(std::slice::from_ref(&event)) {
+            Ok(0) => RelayMessage::ok(event.id, true, "duplicate: already have this event"),
+            Ok(_) => {
+                debug!(id = %event.id, kind = %event.kind, "Stored event");
+                let id = event.id;
+                // Nobody listening is fine
+                let _ = self.live.send(event);
+                RelayMessage::ok(id, true, "")
+            }
+            Err(e) => RelayMessage::ok(event.id, false, format!("error: {e}")),
+        }
+    }
+
+    /// Send the stored matches and keep the subscription open for new events
+    fn subscribe(
+        &self,
+        subscription_id: SubscriptionId,
+        filters: Vec<Filter>,
+        subscriptions: &mut HashMap<SubscriptionId, Vec<Filter>>,
+    ) -> Vec<RelayMessage<'static>> {
+        let stored = match self.store.query(&filters) {
+            Ok(stored) => stored,
+            Err(e) => return vec![RelayMessage::closed(subscription_id, format!("error: {e}"))],
+        };
+        let mut replies: Vec<RelayMessage<'static>> = stored
+            .into_iter()
+            .map(|event| RelayMessage::event(subscription_id.clone(), event))
+            .collect();
+        replies.push(RelayMessage::eose(subscription_id.clone()));
+        subscriptions.insert(subscription_id, filters);
+        replies
+    }
+}
+
+/// Deliver a newly stored event to the matching subscriptions
+fn live_replies(
+    event: &Event,
+    subscriptions: &HashMap<SubscriptionId, Vec<Filter>>,
+) -> Vec<RelayMessage<'static>> {
+    subscriptions
+        .iter()
+        .filter(|(_, filters)| {
+            filters
+                .iter()
+                .any(|filter| filter.match_event(event, MatchEventOptions::new()))
+        })
+        .map(|(subscription_id, _)| RelayMessage::event(subscription_id.clone(), event.clone()))
+        .collect()
+}
+
+/// Run the local relay on `127.0.0.1:port` until the process is stopped
+pub async fn run(port: u16, store: Arc<dyn CacheStore>) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .await
+        .with_context(|| format!("Failed to listen on port {port}"))?;
+    LocalRelay::new(store).serve(listener).await
+}
+
+/// Copy every event of the local relay to other relays
+///
+/// Events are sent oldest first and as they are, the pre-sign hook is not
+/// applied since they were signed when first published.
+pub async fn replicate(
+    network: &Network,
+    store: &dyn CacheStore,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<BroadcastResult> {
+    let mut events = store.query(&[Filter::new()])?;
+    events.reverse();
+
+    let client = relay::connect_with_minimum(network, relays, None, 1, timeout).await?;
+    let result = relay::broadcast_events(&client, &events, &relay::PublishOptions::default()).await;
+    client.disconnect().await;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::MemoryStore;
+    use nostr::{EventBuilder, Keys, Kind};
+
+    #[test]
+    fn test_is_local_relay() {
+        assert!(is_local_relay(&relay_url(DEFAULT_PORT)));
+        assert!(is_local_relay("ws://localhost:7777/"));
+        assert!(!is_local_relay("wss://relay.damus.io"));
+    }
+
+    #[tokio::test]
+    async fn test_local_relay_round_trip() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let url = relay_url(listener.local_addr().unwrap().port());
+        tokio::spawn(LocalRelay::new(Arc::new(MemoryStore::new())).serve(listener));
+
+        let keys = Keys::generate();
+        let client = relay::connect(&Network::default(), std::slice::from_ref(&url), Some(&keys))
+            .await
+            .unwrap();
+        let note = EventBuilder::new(Kind::TextNote, "offline")
+            .sign_with_keys(&keys)
+            .unwrap();
+        let output = client.send_event(&note).await.unwrap();
+        assert_eq!(output.success.len(), 1);
+
+        let found = relay::fetch_events(
+            &Network::default(),
+            &client,
+            vec![Filter::new().author(keys.public_key())],
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, note.id);
+    }
+}
@@ -0,0 +1,339 @@
+use nostr::nips::nip01::Coordinate;
+use nostr::nips::nip19::Nip19Coordinate;
+use nostr::{Event, EventBuilder, Filter, FromBech32, Kind, PublicKey, RelayUrl, Tag, ToBech32};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::error::{Context, GitsmithError, Result, bail, ensure};
+use crate::events::{self, KIND_GIT_REPO_ANNOUNCEMENT};
+use crate::relay::{self, Network};
+use crate::repo;
+use crate::signer::{self, Signer};
+use crate::types::{BroadcastResult, RepoAnnouncement};
+
+/// Kind for organization announcements grouping several repositories
+///
+/// Addressable like repository announcements, so updates replace the previous
+/// version. Member repositories are `a` tags, maintainers `p` tags.
+pub const KIND_ORGANIZATION: Kind = Kind::Custom(30620);
+
+/// An organization (namespace) of related repositories
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Organization {
+    pub identifier: String,
+    pub name: String,
+    pub description: String,
+    /// Owner public key (hex), filled in from the event author
+    #[serde(default)]
+    pub owner: String,
+    /// Member repository coordinates (30617:<pubkey>:<identifier>)
+    pub repositories: Vec<String>,
+    /// Maintainer public keys (hex)
+    pub maintainers: Vec<String>,
+    pub web: Vec<String>,
+}
+
+/// A member repository with its announcement, if it could be found
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberRepository {
+    pub coordinate: String,
+    pub announcement: Option<RepoAnnouncement>,
+}
+
+/// Build the announcement event for an organization
+pub async fn build_organization_event(org: &Organization, signer: &dyn Signer) -> Result<Event> {
+    ensure!(
+        !org.identifier.is_empty() && !org.identifier.contains([' ', '/', ':']),
+        "Organization identifier must not be empty or contain spaces, slashes or colons"
+    );
+
+    let mut tags = vec![
+        Tag::identifier(&org.identifier),
+        Tag::parse(["name", &org.name])?,
+    ];
+    if !org.description.is_empty() {
+        tags.push(Tag::parse(["description", &org.description])?);
+    }
+    for coordinate in &org.repositories {
+        tags.push(Tag::parse(["a", coordinate])?);
+    }
+    for maintainer in &org.maintainers {
+        let public_key = PublicKey::from_hex(maintainer)
+            .with_context(|| format!("Invalid maintainer public key: {maintainer}"))?;
+        tags.push(Tag::public_key(public_key));
+    }
+    if !org.web.is_empty() {
+        let mut web = vec!["web".to_string()];
+        web.extend(org.web.iter().cloned());
+        tags.push(Tag::parse(web)?);
+    }
+    tags.push(Tag::parse([
+        "alt",
+        &format!("Organization: {name}", name = org.name),
+    ])?);
+
+    let builder = EventBuilder::new(KIND_ORGANIZATION, "").tags(tags);
+    signer::sign(signer, builder).await
+}
+
+/// Parse an organization announcement event
+pub fn parse_organization_event(event: &Event) -> Result<Organization> {
+    if event.kind != KIND_ORGANIZATION {
+        return Err(GitsmithError::protocol(format!(
+            "Event {id} is not an organization announcement (kind {kind})",
+            id = event.id,
+            kind = event.kind
+        )));
+    }
+
+    let tags: Vec<&[String]> = event.tags.iter().map(|tag| tag.as_slice()).collect();
+    let first = |name: &str| {
+        tags.iter()
+            .find(|tag| tag.len() > 1 && tag[0] == name)
+            .map(|tag| tag[1].clone())
+    };
+    let all = |name: &str| -> Vec<String> {
+        tags.iter()
+            .filter(|tag| tag.len() > 1 && tag[0] == name)
+            .map(|tag| tag[1].clone())
+            .collect()
+    };
+
+    let identifier = first("d").context("Organization announcement is missing a d tag")?;
+    let repositories = all("a")
+        .into_iter()
+        .filter(|coordinate| coordinate.starts_with(&format!("{KIND_GIT_REPO_ANNOUNCEMENT}:")))
+        .collect();
+    let web = tags
+        .iter()
+        .find(|tag| tag.len() > 1 && tag[0] == "web")
+        .map(|tag| tag[1..].to_vec())
+        .unwrap_or_default();
+
+    Ok(Organization {
+        name: first("name").unwrap_or_else(|| identifier.clone()),
+        identifier,
+        description: first("description").unwrap_or_default(),
+        owner: event.pubkey.to_hex(),
+        repositories,
+        maintainers: all("p"),
+        web,
+    })
+}
+
+/// Shareable `naddr` address of an organization
+pub fn organization_naddr(org: &Organization, relays: &[String]) -> Result<String> {
+    let coordinate = Coordinate::new(KIND_ORGANIZATION, PublicKey::from_hex(&org.owner)?)
+        .identifier(&org.identifier);
+    let relays = relays
+        .iter()
+        .filter_map(|relay| RelayUrl::parse(relay).ok())
+        .take(3);
+
+    Ok(Nip19Coordinate::new(coordinate, relays).to_bech32()?)
+}
+
+/// Parse an organization address (`naddr` or `30620:<pubkey>:<identifier>`)
+///
+/// Returns the coordinate and the relay hints carried by an `naddr`.
+pub fn parse_organization_address(address: &str) -> Result<(Coordinate, Vec<String>)> {
+    let address = address.trim().trim_start_matches("nostr:");
+    let (coordinate, relays) = match Nip19Coordinate::from_bech32(address) {
+        Ok(naddr) => (
+            naddr.coordinate,
+            naddr.relays.iter().map(|r| r.to_string()).collect(),
+        ),
+        Err(_) => (
+            Coordinate::from_kpi_format(address)
+                .with_context(|| format!("Invalid organization address: {address}"))?,
+            vec![],
+        ),
+    };
+    ensure!(
+        coordinate.kind == KIND_ORGANIZATION,
+        "{address} is not an organization (kind {kind})",
+        kind = coordinate.kind
+    );
+
+    Ok((coordinate, relays))
+}
+
+/// Resolve a repository reference to its coordinate
+///
+/// Accepts `nostr://` URLs, repository `naddr`s and plain coordinates.
+pub fn parse_repository_reference(reference: &str) -> Result<String> {
+    Ok(repo::parse_repository_address(reference)?.coordinate())
+}
+
+/// Publish (or replace) an organization announcement
+pub async fn publish_organization(
+    network: &Network,
+    signer: &dyn Signer,
+    org: &Organization,
+    relays: &[String],
+) -> Result<BroadcastResult> {
+    let event = build_organization_event(org, signer).await?;
+    let client = relay::connect(network, relays, Some(signer)).await?;
+    relay::publish_events(&client, &[event]).await
+}
+
+/// Fetch the latest announcement of an organization
+pub async fn fetch_organization(
+    network: &Network,
+    coordinate: &Coordinate,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<Option<Organization>> {
+    let client = relay::connect(network, relays, None).await?;
+    let filter = Filter::new()
+        .kind(KIND_ORGANIZATION)
+        .author(coordinate.public_key)
+        .identifier(&coordinate.identifier);
+    let mut found = relay::fetch_events(network, &client, vec![filter], timeout).await?;
+
+    // Replaceable event: the newest one wins
+    found.sort_by_key(|e| std::cmp::Reverse(e.created_at));
+    found.first().map(parse_organization_event).transpose()
+}
+
+/// Fetch the announcements of all member repositories of an organization
+pub async fn fetch_member_repositories(
+    network: &Network,
+    org: &Organization,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<Vec<MemberRepository>> {
+    if org.repositories.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let coordinates: Vec<Coordinate> = org
+        .repositories
+        .iter()
+        .filter_map(|c| Coordinate::from_kpi_format(c).ok())
+        .collect();
+    let client = relay::connect(network, relays, None).await?;
+    let filter = Filter::new()
+        .kind(Kind::from(KIND_GIT_REPO_ANNOUNCEMENT))
+        .authors(coordinates.iter().map(|c| c.public_key))
+        .identifiers(coordinates.iter().map(|c| c.identifier.clone()));
+    let mut found = relay::fetch_events(network, &client, vec![filter], timeout).await?;
+    found.sort_by_key(|e| std::cmp::Reverse(e.created_at));
+
+    let members = org
+        .repositories
+        .iter()
+        .map(|coordinate| {
+            // Newest matching announcement, authors and identifiers were queried separately
+            let announcement = found
+                .iter()
+                .find(|event| {
+                    let identifier = event.tags.identifier().unwrap_or_default();
+                    format!(
+                        "{KIND_GIT_REPO_ANNOUNCEMENT}:{pubkey}:{identifier}",
+                        pubkey = event.pubkey.to_hex()
+                    ) == *coordinate
+                })
+                .and_then(|event| events::parse_announcement_event(event).ok());
+            MemberRepository {
+                coordinate: coordinate.clone(),
+                announcement,
+            }
+        })
+        .collect();
+
+    Ok(members)
+}
+
+/// Add and remove entries of a list, keeping the original order
+pub fn update_members(list: &mut Vec<String>, add: &[String], remove: &[String]) -> Result<()> {
+    for entry in remove {
+        let before = list.len();
+        list.retain(|existing| existing != entry);
+        if list.len() == before {
+            bail!("{entry} is not a member");
+        }
+    }
+    for entry in add {
+        if !list.contains(entry) {
+            list.push(entry.clone());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::Keys;
+
+    fn sample_org(owner: &Keys) -> Organization {
+        let member = Keys::generate();
+        Organization {
+            identifier: "rust-nostr".to_string(),
+            name: "Rust Nostr".to_string(),
+            description: "Crates for building Nostr apps".to_string(),
+            owner: owner.public_key().to_hex(),
+            repositories: vec![
+                format!("30617:{pk}:nostr", pk = owner.public_key().to_hex()),
+                format!("30617:{pk}:nostr-sdk", pk = member.public_key().to_hex()),
+            ],
+            maintainers: vec![member.public_key().to_hex()],
+            web: vec!["https://rust-nostr.org".to_string()],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_organization_event_round_trip() {
+        let keys = Keys::generate();
+        let org = sample_org(&keys);
+
+        let event = build_organization_event(&org, &keys).await.unwrap();
+        assert_eq!(event.kind, KIND_ORGANIZATION);
+        assert_eq!(parse_organization_event(&event).unwrap(), org);
+
+        let mut invalid = org.clone();
+        invalid.identifier = "has space".to_string();
+        assert!(build_organization_event(&invalid, &keys).await.is_err());
+    }
+
+    #[test]
+    fn test_organization_addresses() {
+        let keys = Keys::generate();
+        let org = sample_org(&keys);
+
+        let naddr = organization_naddr(&org, &["wss://relay.example.com".to_string()]).unwrap();
+        assert!(naddr.starts_with("naddr1"));
+        let (coordinate, relays) = parse_organization_address(&naddr).unwrap();
+        assert_eq!(coordinate.identifier, "rust-nostr");
+        assert_eq!(coordinate.public_key, keys.public_key());
+        assert_eq!(relays.len(), 1);
+
+        let plain = format!("30620:{pk}:rust-nostr", pk = keys.public_key().to_hex());
+        assert!(parse_organization_address(&plain).is_ok());
+        // Repository coordinates are not organizations
+        assert!(parse_organization_address(&org.repositories[0]).is_err());
+
+        let url = format!(
+            "nostr://{npub}/relay.example.com/nostr",
+            npub = keys.public_key().to_bech32().unwrap()
+        );
+        assert_eq!(
+            parse_repository_reference(&url).unwrap(),
+            org.repositories[0]
+        );
+    }
+
+    #[test]
+    fn test_update_members() {
+        let mut list = vec!["a".to_string(), "b".to_string()];
+        update_members(
+            &mut list,
+            &["c".to_string(), "a".to_string()],
+            &["b".to_string()],
+        )
+        .unwrap();
+        assert_eq!(list, vec!["a".to_string(), "c".to_string()]);
+        assert!(update_members(&mut list, &[], &["z".to_string()]).is_err());
+    }
+}
@@ -1,7 +1,14 @@
-use anyhow::{Context, Result, bail, ensure};
-use git2::Repository;
-use nostr::{Event, EventBuilder, Keys, Kind, Tag, TagKind};
+use git2::{Oid, Repository, RevparseMode, Sort};
+use nostr::{Event, EventBuilder, EventId, Kind, PublicKey, Tag, TagKind};
+use serde::{Deserialize, Serialize};
 use std::path::Path;
+use tracing::warn;
+
+use crate::apply;
+use crate::error::{Context, GitsmithError, Result, ensure};
+use crate::format_patch;
+use crate::repo::open_repository;
+use crate::signer::{self, Signer};
 
 /// Kind for patch events (NIP-34)
 pub const KIND_PATCH: Kind = Kind::Custom(1617);
@@ -9,190 +16,688 @@ pub const KIND_PATCH: Kind = Kind::Custom(1617);
 pub const KIND_PULL_REQUEST: Kind = Kind::Custom(1618);
 /// Kind for pull request updates
 pub const KIND_PULL_REQUEST_UPDATE: Kind = Kind::Custom(1619);
+/// Kind for series manifests, an index of a PR's patches with their commits and sizes
+pub const KIND_SERIES_MANIFEST: Kind = Kind::Custom(1620);
+/// Kind for the continuation of a patch too large for one event
+pub const KIND_PATCH_PART: Kind = Kind::Custom(1622);
 
-/// Generate patches from git commits
-pub fn generate_patches(
-    repo_path: &Path,
-    since_commit: Option<&str>,
-    count: Option<usize>,
-) -> Result<Vec<String>> {
-    let repo = Repository::open(repo_path)?;
+/// Marker of the `e` tags a split patch references its parts with, in order
+pub const PART_MARKER: &str = "part";
 
-    // Get the commit range
-    let head = repo.head()?.peel_to_commit()?;
-    let mut commits = Vec::new();
+/// Largest event relays commonly accept, in bytes of JSON
+pub const DEFAULT_MAX_EVENT_SIZE: usize = 64 * 1024;
 
-    if let Some(since) = since_commit {
-        // Parse the since commit
-        let since_oid = if since.contains("~") {
-            // Handle HEAD~N notation
-            let parts: Vec<&str> = since.split('~').collect();
-            ensure!(
-                parts.len() == 2 && parts[0] == "HEAD",
-                "Invalid commit reference: {since}"
-            );
-            let n: usize = parts[1]
-                .parse()
-                .with_context(|| format!("Invalid number in {since}"))?;
-
-            // Walk back N commits from HEAD
-            let mut current = head.clone();
-            for _ in 0..n {
-                if let Ok(parent) = current.parent(0) {
-                    current = parent;
-                } else {
-                    bail!("Not enough commits for {since}");
-                }
-            }
-            current.id()
-        } else {
-            // Try to parse as commit hash or reference
-            repo.revparse_single(since)?.id()
-        };
+/// Most parts a patch is split into
+pub const MAX_PATCH_PARTS: usize = 32;
 
-        // Walk from HEAD to since_commit
-        let mut revwalk = repo.revwalk()?;
-        revwalk.push(head.id())?;
+/// JSON bytes of an event besides its content and tags: id, pubkey, sig, kind, created_at
+const EVENT_FIELDS_BYTES: usize = 512;
 
-        for oid in revwalk {
-            let oid = oid?;
-            if oid == since_oid {
-                break;
+/// JSON bytes of one `["e","<id>","","part"]` tag
+const PART_TAG_BYTES: usize = 90;
+
+/// Marker of the `e` tag a PR event references its series manifest with
+pub const MANIFEST_MARKER: &str = "manifest";
+/// Manifest tag listing one patch: event ID, commit, subject, files, insertions, deletions
+pub const SERIES_PATCH_TAG: &str = "series-patch";
+
+/// Optional settings for pull request event creation
+#[derive(Debug, Clone, Default)]
+pub struct PullRequestOptions {
+    /// Existing PR event ID this series updates
+    pub reply_to: Option<String>,
+    /// Existing PR this series is a new revision of
+    pub revision: Option<Revision>,
+    /// NIP-13 proof-of-work difficulty to mine every event to (0 = none)
+    pub pow_difficulty: u8,
+    /// Cover letter text, published as patch 0/N ahead of the series
+    pub cover_letter: Option<String>,
+    /// Commits the patches were generated from, in series order
+    pub commits: Vec<CommitMetadata>,
+    /// Maintainers tagged on every patch so their clients notify them
+    pub maintainers: Vec<PublicKey>,
+    /// How the events of the series reference each other
+    pub layout: PatchLayout,
+    /// Largest event in bytes of JSON, larger patches are rejected or split (None = no limit)
+    pub max_event_size: Option<usize>,
+    /// Split patches over `max_event_size` into parts instead of failing
+    pub split_large_patches: bool,
+    /// Labels, as `t` tags on the PR event and the event starting the series
+    pub labels: Vec<String>,
+    /// Page of the PR on another forge it was imported from, as an `r` tag on the PR event
+    pub external_url: Option<String>,
+}
+
+/// How the patch events of a series are threaded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PatchLayout {
+    /// NIP-34 threading as published by ngit and shown by gitworkshop.dev: the
+    /// first event is tagged `t root`, later patches point at it with a `root`
+    /// marked `e` tag and at their predecessor with a `reply` marked one
+    #[default]
+    Ngit,
+    /// A single unmarked `e` tag to the previous event, as gitsmith used to publish
+    Legacy,
+}
+
+/// `t` tag value of the first event of a patch series (NIP-34)
+pub const ROOT_HASHTAG: &str = "root";
+
+/// A patch as listed in a series manifest
+///
+/// Enough to show the series' diffstat or tell which patch a relay dropped
+/// without downloading the patches.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub event_id: String,
+    pub commit: Option<String>,
+    pub subject: String,
+    pub files: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl ManifestEntry {
+    /// Describe a patch event of the series
+    pub fn new(event_id: EventId, patch: &str, commit: Option<&CommitMetadata>) -> Self {
+        let parsed = match apply::parse_patch(patch) {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                warn!("Failed to parse patch {event_id}, listing it without metadata: {e}");
+                None
             }
-            commits.push(oid);
+        };
+        let stat = parsed
+            .as_ref()
+            .map(|patch| apply::diffstat(&patch.diff))
+            .unwrap_or_default();
+
+        Self {
+            event_id: event_id.to_hex(),
+            commit: commit
+                .map(|commit| commit.commit.clone())
+                .or_else(|| parsed.as_ref().and_then(|patch| patch.commit_id.clone())),
+            subject: parsed.map(|patch| patch.subject).unwrap_or_default(),
+            files: stat.files.len(),
+            insertions: stat.insertions,
+            deletions: stat.deletions,
         }
-    } else if let Some(n) = count {
-        // Get last N commits
-        let mut revwalk = repo.revwalk()?;
-        revwalk.push(head.id())?;
-
-        for (i, oid) in revwalk.enumerate() {
-            if i >= n {
-                break;
-            }
-            commits.push(oid?);
+    }
+
+    fn to_tag(&self) -> Tag {
+        Tag::custom(
+            TagKind::Custom(SERIES_PATCH_TAG.into()),
+            vec![
+                self.event_id.clone(),
+                self.commit.clone().unwrap_or_default(),
+                self.subject.clone(),
+                self.files.to_string(),
+                self.insertions.to_string(),
+                self.deletions.to_string(),
+            ],
+        )
+    }
+
+    /// Parse a `series-patch` tag
+    pub fn from_tag(tag: &[String]) -> Option<Self> {
+        let [
+            name,
+            event_id,
+            commit,
+            subject,
+            files,
+            insertions,
+            deletions,
+            ..,
+        ] = tag
+        else {
+            return None;
+        };
+        if name != SERIES_PATCH_TAG {
+            return None;
         }
-    } else {
-        // Default to last commit
-        commits.push(head.id());
+
+        Some(Self {
+            event_id: event_id.clone(),
+            commit: (!commit.is_empty()).then(|| commit.clone()),
+            subject: subject.clone(),
+            files: files.parse().ok()?,
+            insertions: insertions.parse().ok()?,
+            deletions: deletions.parse().ok()?,
+        })
     }
+}
 
-    // Reverse to get chronological order
-    commits.reverse();
+/// Commit a patch was generated from, published as NIP-34 patch tags
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitMetadata {
+    pub commit: String,
+    pub parent_commit: Option<String>,
+    pub committer_name: String,
+    pub committer_email: String,
+    /// Commit time in seconds since the epoch
+    pub committer_time: i64,
+    /// Timezone offset of the commit time in minutes
+    pub committer_offset: i32,
+    /// ASCII-armored signature of signed commits
+    pub pgp_sig: Option<String>,
+}
+
+impl CommitMetadata {
+    /// Commit tags of a patch event, None when it lacks the `commit` or `committer` tag
+    pub fn from_event(event: &Event) -> Option<Self> {
+        let tag = |name: &str| {
+            event
+                .tags
+                .iter()
+                .map(|tag| tag.as_slice())
+                .find(|tag| tag.len() > 1 && tag[0] == name)
+        };
+        let [_, name, email, time, offset, ..] = tag("committer")? else {
+            return None;
+        };
+
+        Some(Self {
+            commit: tag("commit")?[1].clone(),
+            parent_commit: tag("parent-commit").map(|tag| tag[1].clone()),
+            committer_name: name.clone(),
+            committer_email: email.clone(),
+            committer_time: time.parse().ok()?,
+            committer_offset: offset.parse().ok()?,
+            pgp_sig: tag("commit-pgp-sig")
+                .map(|tag| tag[1].clone())
+                .filter(|sig| !sig.is_empty()),
+        })
+    }
+}
+
+/// Identifies a patch series as a new version of an existing PR
+#[derive(Debug, Clone)]
+pub struct Revision {
+    /// Event ID of the original PR
+    pub pr_id: String,
+    /// Version number of the new series (the original PR is version 1)
+    pub version: u32,
+}
+
+/// `t` tag value marking a PR event as a revision (NIP-34 `root-revision`)
+pub const REVISION_HASHTAG: &str = "root-revision";
 
-    // Generate patches for each commit
+/// Tag holding the version number of a revision
+pub const REVISION_TAG: &str = "revision";
+
+/// Marker of the `e` tag referencing a cover letter and its `t` tag value
+pub const COVER_LETTER_MARKER: &str = "cover-letter";
+
+/// Whether a `t` tag value is one of the NIP-34 markers rather than a label
+pub fn is_marker_hashtag(value: &str) -> bool {
+    [ROOT_HASHTAG, REVISION_HASHTAG, COVER_LETTER_MARKER].contains(&value)
+}
+
+/// Labels of a PR, issue or patch event: its `t` tags other than NIP-34 markers
+pub fn labels(event: &Event) -> Vec<String> {
+    event
+        .tags
+        .iter()
+        .filter(|tag| tag.kind() == TagKind::t())
+        .filter_map(|tag| tag.content())
+        .filter(|value| !is_marker_hashtag(value))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Format a cover letter the way `git format-patch --cover-letter` does
+pub fn format_cover_letter(title: &str, body: &str, total: usize) -> String {
+    format!(
+        "From 0000000000000000000000000000000000000000 Mon Sep 17 00:00:00 2001\n\
+         Subject: [PATCH 0/{total}] {title}\n\
+         \n\
+         {body}\n",
+        body = body.trim_end()
+    )
+}
+
+/// Text of a cover letter without its header lines
+pub fn cover_letter_body(cover_letter: &str) -> &str {
+    cover_letter
+        .split_once("\n\n")
+        .map_or(cover_letter, |(_headers, body)| body)
+        .trim_end()
+}
+
+/// Generate patches from git commits, like `git format-patch`
+///
+/// `range` is a single revision, meaning the commits after it up to HEAD
+/// (`HEAD~2`, `main`, `v1.0`), `A..B` for the commits in B but not in A, or
+/// `A...B` for the commits in either but not both. `count` keeps the newest
+/// commits of the range, or of HEAD's history without one. Without either
+/// only HEAD is used.
+pub fn generate_patches(
+    repo_path: &Path,
+    range: Option<&str>,
+    count: Option<usize>,
+) -> Result<Vec<String>> {
+    let repo = open_repository(repo_path)?;
+    let commits = select_commits(&repo, range, count)?;
+
+    let total = commits.len();
     let mut patches = Vec::new();
-    for oid in commits {
+    for (i, oid) in commits.into_iter().enumerate() {
         let commit = repo.find_commit(oid)?;
-        let patch = generate_patch_for_commit(&repo, &commit)?;
+        let patch = format_patch::format_commit(&repo, &commit, Some((i + 1, total)))?;
         patches.push(patch);
     }
 
     Ok(patches)
 }
 
-/// Generate a patch string for a single commit
-fn generate_patch_for_commit(repo: &Repository, commit: &git2::Commit) -> Result<String> {
-    let parent = if commit.parent_count() > 0 {
-        Some(commit.parent(0)?)
-    } else {
-        None
+/// Commits of a range as [`generate_patches`] reads it, oldest first
+///
+/// Merge commits are skipped, as `git format-patch` does.
+pub fn select_commits(
+    repo: &Repository,
+    range: Option<&str>,
+    count: Option<usize>,
+) -> Result<Vec<Oid>> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL)?;
+    match range {
+        Some(range) => {
+            let spec = repo
+                .revparse(range)
+                .with_context(|| format!("Invalid commit range: {range}"))?;
+            let commit_id = |object: Option<&git2::Object<'_>>| -> Result<Oid> {
+                let object = object.with_context(|| format!("Invalid commit range: {range}"))?;
+                Ok(object.peel_to_commit()?.id())
+            };
+            let from = commit_id(spec.from())?;
+            if spec.mode().contains(RevparseMode::SINGLE) {
+                revwalk.push_head()?;
+                revwalk.hide(from)?;
+            } else {
+                let to = commit_id(spec.to())?;
+                revwalk.push(to)?;
+                if spec.mode().contains(RevparseMode::MERGE_BASE) {
+                    revwalk.push(from)?;
+                    revwalk.hide(repo.merge_base(from, to)?)?;
+                } else {
+                    revwalk.hide(from)?;
+                }
+            }
+        }
+        None => revwalk.push_head()?,
+    }
+    let limit = count.or(range.is_none().then_some(1));
+
+    let mut commits = Vec::new();
+    for oid in revwalk {
+        let oid = oid?;
+        if limit.is_some_and(|limit| commits.len() >= limit) {
+            break;
+        }
+        if repo.find_commit(oid)?.parent_count() > 1 {
+            continue;
+        }
+        commits.push(oid);
+    }
+    commits.reverse();
+    Ok(commits)
+}
+
+/// Look up the commits patches were generated from
+///
+/// The commit of each patch is taken from its `From <commit>` line, so this
+/// only works for patches of commits present in the repository.
+pub fn commit_metadata(repo_path: &Path, patches: &[String]) -> Result<Vec<CommitMetadata>> {
+    let repo = open_repository(repo_path)?;
+
+    patches
+        .iter()
+        .enumerate()
+        .map(|(i, patch)| {
+            let commit_id = apply::parse_patch(patch)
+                .ok()
+                .and_then(|parsed| parsed.commit_id)
+                .with_context(|| format!("Patch {n} has no commit ID", n = i + 1))?;
+            let oid = git2::Oid::from_str(&commit_id)
+                .with_context(|| format!("Invalid commit ID in patch {n}", n = i + 1))?;
+            let commit = repo
+                .find_commit(oid)
+                .with_context(|| format!("Commit {commit_id} not found"))?;
+            let committer = commit.committer();
+            // Unsigned commits have no signature to extract
+            let pgp_sig = repo
+                .extract_signature(&oid, None)
+                .ok()
+                .and_then(|(signature, _)| signature.as_str().map(|s| s.to_string()));
+
+            Ok(CommitMetadata {
+                commit: commit_id,
+                parent_commit: commit.parent_id(0).ok().map(|id| id.to_string()),
+                committer_name: committer.name().unwrap_or("Unknown").to_string(),
+                committer_email: committer.email().unwrap_or_default().to_string(),
+                committer_time: committer.when().seconds(),
+                committer_offset: committer.when().offset_minutes(),
+                pgp_sig,
+            })
+        })
+        .collect()
+}
+
+/// Tags placing an event in its series, given the series root and the preceding event
+///
+/// Without a root the event starts the series, or a new revision of the PR.
+fn thread_tags(
+    options: &PullRequestOptions,
+    root: Option<EventId>,
+    previous: Option<EventId>,
+) -> Vec<Tag> {
+    let marked = |id: EventId, marker: &str| {
+        Tag::custom(
+            TagKind::Custom("e".into()),
+            vec![id.to_hex(), String::new(), marker.to_string()],
+        )
+    };
+
+    let mut tags = match (options.layout, root) {
+        (PatchLayout::Ngit, None) if options.revision.is_some() => {
+            vec![Tag::hashtag(REVISION_HASHTAG)]
+        }
+        (PatchLayout::Ngit, None) => vec![Tag::hashtag(ROOT_HASHTAG)],
+        (PatchLayout::Ngit, Some(root)) => {
+            let mut tags = vec![marked(root, "root")];
+            if let Some(previous) = previous.filter(|previous| *previous != root) {
+                tags.push(marked(previous, "reply"));
+            }
+            tags
+        }
+        (PatchLayout::Legacy, _) => previous.map(Tag::event).into_iter().collect(),
     };
+    // Clients that only read patches see the labels on the first event
+    if root.is_none() {
+        tags.extend(options.labels.iter().map(Tag::hashtag));
+    }
+    tags
+}
 
-    let tree = commit.tree()?;
-    let parent_tree = parent.as_ref().map(|p| p.tree()).transpose()?;
+/// Earliest unique commit (`r`) tag ngit puts on patches so they can be found by repository
+fn euc_tag(layout: PatchLayout, root_commit: &str) -> Option<Tag> {
+    (layout == PatchLayout::Ngit && !root_commit.is_empty()).then(|| Tag::reference(root_commit))
+}
 
-    let mut diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
-    diff.find_similar(None)?;
+/// NIP-34 tags describing the commit of a patch
+fn commit_tags(commit: &CommitMetadata) -> Vec<Tag> {
+    let tag = |name: &str, values: Vec<String>| Tag::custom(TagKind::Custom(name.into()), values);
 
-    let mut patch = String::new();
+    let mut tags = vec![
+        tag("commit", vec![commit.commit.clone()]),
+        // Lets clients find every patch of a given commit
+        Tag::reference(&commit.commit),
+        tag(
+            "committer",
+            vec![
+                commit.committer_name.clone(),
+                commit.committer_email.clone(),
+                commit.committer_time.to_string(),
+                commit.committer_offset.to_string(),
+            ],
+        ),
+        // NIP-34 uses an empty signature for unsigned commits
+        tag(
+            "commit-pgp-sig",
+            vec![commit.pgp_sig.clone().unwrap_or_default()],
+        ),
+    ];
+    if let Some(parent) = &commit.parent_commit {
+        tags.push(tag("parent-commit", vec![parent.clone()]));
+    }
 
-    // Add commit header
-    patch.push_str(&format!(
-        "From {commit_id} Mon Sep 17 00:00:00 2001\n",
-        commit_id = commit.id()
-    ));
-    patch.push_str(&format!(
-        "From: {name} <{email}>\n",
-        name = commit.author().name().unwrap_or("Unknown"),
-        email = commit.author().email().unwrap_or("unknown@example.com")
-    ));
-    patch.push_str(&format!(
-        "Date: {date}\n",
-        date = chrono::DateTime::from_timestamp(commit.time().seconds(), 0)
-            .map(|dt| dt.format("%a, %d %b %Y %H:%M:%S %z").to_string())
-            .unwrap_or_else(|| "Unknown".to_string())
-    ));
-    patch.push_str(&format!(
-        "Subject: {subject}\n",
-        subject = commit.summary().unwrap_or("No subject")
-    ));
-    patch.push('\n');
-
-    // Add commit message body
-    if let Some(msg) = commit.message() {
-        let lines: Vec<&str> = msg.lines().collect();
-        if lines.len() > 1 {
-            for line in &lines[1..] {
-                patch.push_str(line);
-                patch.push('\n');
+    tags
+}
+
+/// Bytes `text` takes as a JSON string, quotes excluded
+fn json_len(text: &str) -> usize {
+    text.chars()
+        .map(|c| match c {
+            '"' | '\\' | '\n' | '\r' | '\t' | '\u{8}' | '\u{c}' => 2,
+            c if c < ' ' => 6,
+            c => c.len_utf8(),
+        })
+        .sum()
+}
+
+/// Estimated JSON size of an event with these tags and content
+fn estimated_event_size(tags: &[Tag], content: &str) -> usize {
+    let tags: usize = tags
+        .iter()
+        .flat_map(|tag| tag.as_slice())
+        .map(|value| json_len(value) + 3)
+        .sum();
+    EVENT_FIELDS_BYTES + tags + json_len(content)
+}
+
+/// Split text into pieces of at most `budget` JSON bytes, at line ends where possible
+pub fn split_content(content: &str, budget: usize) -> Vec<String> {
+    let budget = budget.max(6);
+    let mut pieces = Vec::new();
+    let mut piece = String::new();
+    let mut size = 0;
+    for c in content.chars() {
+        let c_size = json_len(c.encode_utf8(&mut [0; 4]));
+        if size + c_size > budget {
+            // Carry the unfinished line over to the next piece
+            let cut = piece.rfind('\n').map_or(piece.len(), |newline| newline + 1);
+            let rest = piece.split_off(cut);
+            pieces.push(std::mem::replace(&mut piece, rest));
+            size = json_len(&piece);
+            if size + c_size > budget {
+                pieces.push(std::mem::take(&mut piece));
+                size = 0;
             }
-            patch.push('\n');
         }
+        piece.push(c);
+        size += c_size;
+    }
+    if !piece.is_empty() || pieces.is_empty() {
+        pieces.push(piece);
     }
+    pieces
+}
 
-    // Add diff
-    diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
-        patch.push_str(std::str::from_utf8(line.content()).unwrap_or(""));
-        true
-    })?;
+/// Check a patch fits the event size limit, splitting it when allowed
+///
+/// Returns the content of the patch event followed by the content of each
+/// part event.
+fn fit_patch(
+    patch: &str,
+    tags: &[Tag],
+    number: usize,
+    total: usize,
+    options: &PullRequestOptions,
+) -> Result<Vec<String>> {
+    let Some(max) = options.max_event_size else {
+        return Ok(vec![patch.to_string()]);
+    };
+    let size = estimated_event_size(tags, patch);
+    if size <= max {
+        return Ok(vec![patch.to_string()]);
+    }
 
-    // Add footer
-    patch.push_str("-- \n");
-    patch.push_str("2.34.1\n");
+    let subject = apply::parse_patch(patch)
+        .map(|parsed| parsed.subject)
+        .unwrap_or_default();
+    ensure!(
+        options.split_large_patches,
+        "Patch {number}/{total} ({subject}) is about {size} bytes as an event, relays accept \
+         at most {max}. Split the commit, or pass --split-large-patches to send it in parts"
+    );
 
-    Ok(patch)
+    // The patch event also carries a tag for each part
+    let overhead = estimated_event_size(tags, "") + MAX_PATCH_PARTS * PART_TAG_BYTES;
+    ensure!(
+        overhead < max,
+        "Events of {max} bytes are too small to carry patch {number}/{total} ({subject})"
+    );
+    let pieces = split_content(patch, max - overhead);
+    ensure!(
+        pieces.len() <= MAX_PATCH_PARTS,
+        "Patch {number}/{total} ({subject}) needs {count} parts of {max} bytes, at most \
+         {MAX_PATCH_PARTS} are allowed. Split the commit instead",
+        count = pieces.len()
+    );
+    Ok(pieces)
 }
 
-/// Create a pull request event
-pub fn create_pull_request_event(
-    keys: &Keys,
+/// Create the events of a pull request
+///
+/// Returns the cover letter (if any), the patches, the series manifest and
+/// finally the PR event itself. The parts of a split patch come right before
+/// the patch.
+pub async fn create_pull_request_event(
+    signer: &dyn Signer,
     repo_coordinate: &str,
     title: &str,
     description: &str,
     patches: Vec<String>,
     root_commit: &str,
-    reply_to: Option<String>,
+    options: &PullRequestOptions,
 ) -> Result<Vec<Event>> {
+    for label in &options.labels {
+        ensure!(
+            !label.trim().is_empty() && !is_marker_hashtag(label),
+            "'{label}' can't be used as a label"
+        );
+    }
     let mut events = Vec::new();
 
-    // Create patch events first
+    // The cover letter comes first, so the series replies to it like in git send-email
+    let mut cover_letter_id = None;
+    if let Some(body) = &options.cover_letter {
+        let content = format_cover_letter(title, body, patches.len());
+        let cover_letter = signer::sign_with_pow(
+            signer,
+            EventBuilder::new(KIND_PATCH, content)
+                .tags([
+                    Tag::custom(
+                        TagKind::Custom("alt".into()),
+                        vec![format!("git patch cover letter: {title}")],
+                    ),
+                    Tag::hashtag(COVER_LETTER_MARKER),
+                    Tag::custom(
+                        TagKind::Custom("a".into()),
+                        vec![repo_coordinate.to_string()],
+                    ),
+                ])
+                .tags(options.maintainers.iter().copied().map(Tag::public_key))
+                .tags(thread_tags(options, None, None))
+                .tags(euc_tag(options.layout, root_commit)),
+            options.pow_difficulty,
+        )
+        .await?;
+
+        cover_letter_id = Some(cover_letter.id);
+        events.push(cover_letter);
+    }
+
+    // Create patch events
     let mut patch_event_ids = Vec::new();
     for (i, patch) in patches.iter().enumerate() {
-        let mut tags = vec![Tag::custom(
-            TagKind::Custom("alt".into()),
-            vec![format!(
-                "git patch: {current}/{total}",
-                current = i + 1,
-                total = patches.len()
-            )],
-        )];
-
-        // Add reference to previous patch if not first
-        if i > 0 {
-            tags.push(Tag::event(patch_event_ids[i - 1]));
+        let mut tags = vec![
+            Tag::custom(
+                TagKind::Custom("alt".into()),
+                vec![format!(
+                    "git patch: {current}/{total}",
+                    current = i + 1,
+                    total = patches.len()
+                )],
+            ),
+            // Lets relays and clients associate the patch with the repository
+            Tag::custom(
+                TagKind::Custom("a".into()),
+                vec![repo_coordinate.to_string()],
+            ),
+        ];
+        tags.extend(options.maintainers.iter().copied().map(Tag::public_key));
+
+        // Thread the patch under the cover letter or the first patch
+        let root = cover_letter_id.or(patch_event_ids.first().copied());
+        let previous = patch_event_ids.last().copied().or(cover_letter_id);
+        tags.extend(thread_tags(options, root, previous));
+        if let Some(commit) = options.commits.get(i) {
+            tags.extend(commit_tags(commit));
         }
+        tags.extend(euc_tag(options.layout, root_commit));
 
-        let patch_event = EventBuilder::new(KIND_PATCH, patch.clone())
-            .tags(tags)
-            .sign_with_keys(keys)?;
+        // Parts of a split patch go first, the patch event lists their IDs
+        let mut pieces = fit_patch(patch, &tags, i + 1, patches.len(), options)?.into_iter();
+        let content = pieces.next().unwrap_or_default();
+        let count = pieces.len() + 1;
+        for (part, piece) in pieces.enumerate() {
+            let part_event = signer::sign_with_pow(
+                signer,
+                EventBuilder::new(KIND_PATCH_PART, piece).tags([
+                    Tag::custom(
+                        TagKind::Custom("alt".into()),
+                        vec![format!(
+                            "git patch part {current}/{count}",
+                            current = part + 2
+                        )],
+                    ),
+                    Tag::custom(
+                        TagKind::Custom("a".into()),
+                        vec![repo_coordinate.to_string()],
+                    ),
+                ]),
+                options.pow_difficulty,
+            )
+            .await?;
+            tags.push(Tag::custom(
+                TagKind::Custom("e".into()),
+                vec![
+                    part_event.id.to_hex(),
+                    String::new(),
+                    PART_MARKER.to_string(),
+                ],
+            ));
+            events.push(part_event);
+        }
+
+        let patch_event = signer::sign_with_pow(
+            signer,
+            EventBuilder::new(KIND_PATCH, content).tags(tags),
+            options.pow_difficulty,
+        )
+        .await?;
 
         patch_event_ids.push(patch_event.id);
         events.push(patch_event);
     }
 
+    // Index of the series, so it can be summarized without fetching every patch
+    let manifest = signer::sign_with_pow(
+        signer,
+        EventBuilder::new(KIND_SERIES_MANIFEST, "")
+            .tag(Tag::custom(
+                TagKind::Custom("a".into()),
+                vec![repo_coordinate.to_string()],
+            ))
+            .tags(
+                patch_event_ids
+                    .iter()
+                    .zip(&patches)
+                    .enumerate()
+                    .map(|(i, (id, patch))| {
+                        ManifestEntry::new(*id, patch, options.commits.get(i)).to_tag()
+                    }),
+            ),
+        options.pow_difficulty,
+    )
+    .await?;
+    let manifest_id = manifest.id;
+    events.push(manifest);
+
     // Create the PR event
-    let kind = if reply_to.is_some() {
+    let kind = if options.reply_to.is_some() {
         KIND_PULL_REQUEST_UPDATE
     } else {
         KIND_PULL_REQUEST
@@ -218,14 +723,50 @@ pub fn create_pull_request_event(
         ));
     }
 
+    if let Some(cover_letter_id) = cover_letter_id {
+        pr_tags.push(Tag::custom(
+            TagKind::Custom("e".into()),
+            vec![cover_letter_id.to_string(), COVER_LETTER_MARKER.to_string()],
+        ));
+    }
+
+    pr_tags.push(Tag::custom(
+        TagKind::Custom("e".into()),
+        vec![manifest_id.to_string(), MANIFEST_MARKER.to_string()],
+    ));
+
+    pr_tags.extend(options.labels.iter().map(Tag::hashtag));
+    if let Some(url) = &options.external_url {
+        pr_tags.push(Tag::reference(url));
+    }
+
     // Add reply reference if updating
-    if let Some(reply_id) = reply_to {
-        pr_tags.push(Tag::custom(TagKind::Custom("e".into()), vec![reply_id]));
+    if let Some(reply_id) = &options.reply_to {
+        pr_tags.push(Tag::custom(
+            TagKind::Custom("e".into()),
+            vec![reply_id.clone(), String::new(), "reply".to_string()],
+        ));
+    }
+
+    // A revision is a new PR replying to the original, so older clients still show it
+    if let Some(revision) = &options.revision {
+        pr_tags.push(Tag::custom(
+            TagKind::Custom("e".into()),
+            vec![revision.pr_id.clone(), String::new(), "reply".to_string()],
+        ));
+        pr_tags.push(Tag::hashtag(REVISION_HASHTAG));
+        pr_tags.push(Tag::custom(
+            TagKind::Custom(REVISION_TAG.into()),
+            vec![revision.version.to_string()],
+        ));
     }
 
-    let pr_event = EventBuilder::new(kind, description)
-        .tags(pr_tags)
-        .sign_with_keys(keys)?;
+    let pr_event = signer::sign_with_pow(
+        signer,
+        EventBuilder::new(kind, description).tags(pr_tags),
+        options.pow_difficulty,
+    )
+    .await?;
 
     events.push(pr_event);
 
@@ -235,10 +776,11 @@ pub fn create_pull_request_event(
 /// Parse a repository coordinate (e.g., "30617:pubkey:identifier")
 pub fn parse_repo_coordinate(coordinate: &str) -> Result<(String, String, String)> {
     let parts: Vec<&str> = coordinate.split(':').collect();
-    ensure!(
-        parts.len() == 3,
-        "Invalid repository coordinate format. Expected: kind:pubkey:identifier"
-    );
+    if parts.len() != 3 {
+        return Err(GitsmithError::protocol(
+            "Invalid repository coordinate format. Expected: kind:pubkey:identifier",
+        ));
+    }
 
     Ok((
         parts[0].to_string(),
@@ -246,3 +788,245 @@ pub fn parse_repo_coordinate(coordinate: &str) -> Result<(String, String, String
         parts[2].to_string(),
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Signature;
+    use nostr::Keys;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_patch_event_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        let sig = Signature::new(
+            "Alice",
+            "alice@example.com",
+            &git2::Time::new(1700000000, 60),
+        )
+        .unwrap();
+        let mut parent = None;
+        for content in ["a\n", "b\n"] {
+            fs::write(dir.path().join("f.txt"), content).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new("f.txt")).unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parents: Vec<git2::Commit> = parent
+                .iter()
+                .map(|id| repo.find_commit(*id).unwrap())
+                .collect();
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            parent = Some(
+                repo.commit(Some("HEAD"), &sig, &sig, content, &tree, &parents)
+                    .unwrap(),
+            );
+        }
+
+        let patches = generate_patches(dir.path(), Some("HEAD~1"), None).unwrap();
+        let maintainer = Keys::generate().public_key();
+        let options = PullRequestOptions {
+            commits: commit_metadata(dir.path(), &patches).unwrap(),
+            maintainers: vec![maintainer],
+            labels: vec!["bug".to_string()],
+            ..Default::default()
+        };
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(options.commits[0].commit, head.id().to_string());
+        assert_eq!(
+            options.commits[0].parent_commit,
+            Some(head.parent_id(0).unwrap().to_string())
+        );
+
+        let events = create_pull_request_event(
+            &Keys::generate(),
+            "30617:abc:repo",
+            "T",
+            "",
+            patches,
+            "root",
+            &options,
+        )
+        .await
+        .unwrap();
+        let tags: Vec<Vec<String>> = events[0]
+            .tags
+            .iter()
+            .map(|tag| tag.clone().to_vec())
+            .collect();
+        let find = |name: &str| tags.iter().find(|tag| tag[0] == name).unwrap().clone();
+        assert_eq!(find("commit")[1], head.id().to_string());
+        assert_eq!(find("r")[1], head.id().to_string());
+        assert_eq!(
+            find("committer")[1..],
+            ["Alice", "alice@example.com", "1700000000", "60"]
+        );
+        assert_eq!(find("commit-pgp-sig")[1], "");
+        assert_eq!(labels(&events[0]), ["bug"]);
+        assert_eq!(labels(events.last().unwrap()), ["bug"]);
+        assert_eq!(find("a")[1], "30617:abc:repo");
+        assert_eq!(find("p")[1], maintainer.to_hex());
+        assert_eq!(
+            find("parent-commit")[1],
+            head.parent_id(0).unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn test_select_commits() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        let sig = Signature::new(
+            "Alice",
+            "alice@example.com",
+            &git2::Time::new(1700000000, 0),
+        )
+        .unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let commit = |message: &str, parents: &[Oid]| {
+            let parents: Vec<git2::Commit> = parents
+                .iter()
+                .map(|id| repo.find_commit(*id).unwrap())
+                .collect();
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            repo.commit(None, &sig, &sig, message, &tree, &parents)
+                .unwrap()
+        };
+        let base = commit("base", &[]);
+        let main = commit("main", &[base]);
+        let feature1 = commit("feature 1", &[base]);
+        let feature2 = commit("feature 2", &[feature1]);
+        let merge = commit("merge main", &[feature2, main]);
+        let feature3 = commit("feature 3", &[merge]);
+        repo.branch("main", &repo.find_commit(main).unwrap(), false)
+            .unwrap();
+        repo.branch("feature", &repo.find_commit(feature3).unwrap(), false)
+            .unwrap();
+        repo.tag_lightweight("v1", repo.find_commit(base).unwrap().as_object(), false)
+            .unwrap();
+        repo.set_head("refs/heads/feature").unwrap();
+
+        let select = |range: Option<&str>, count: Option<usize>| {
+            select_commits(&repo, range, count).unwrap()
+        };
+        // Merges are left out, whatever the range
+        assert_eq!(
+            select(Some("main..feature"), None),
+            vec![feature1, feature2, feature3]
+        );
+        assert_eq!(
+            select(Some("main"), None),
+            vec![feature1, feature2, feature3]
+        );
+        assert_eq!(select(Some("v1..main"), None), vec![main]);
+        assert_eq!(select(Some("HEAD~1"), None), vec![feature3]);
+        // Both sides of a symmetric difference
+        let mut symmetric = select(Some("feature~2...main"), None);
+        symmetric.sort();
+        let mut expected = vec![main, feature1, feature2];
+        expected.sort();
+        assert_eq!(symmetric, expected);
+        assert_eq!(
+            select(Some("main..feature"), Some(2)),
+            vec![feature2, feature3]
+        );
+        assert_eq!(select(None, Some(1)), vec![feature3]);
+        assert_eq!(select(None, None), vec![feature3]);
+        assert!(select_commits(&repo, Some("nope..feature"), None).is_err());
+    }
+
+    #[test]
+    fn test_split_content() {
+        let content = "first line\nsecond \"quoted\" line\nthird\n";
+        let pieces = split_content(content, 24);
+        assert_eq!(pieces.concat(), content);
+        assert!(pieces.iter().all(|piece| json_len(piece) <= 24));
+        // Lines stay whole when they fit
+        assert_eq!(pieces[0], "first line\n");
+
+        // Lines longer than a piece are cut wherever needed
+        let long = "x".repeat(50);
+        let pieces = split_content(&long, 20);
+        assert_eq!(pieces.len(), 3);
+        assert_eq!(pieces.concat(), long);
+        assert_eq!(split_content("", 20), vec![""]);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_patches() {
+        use nostr::JsonUtil;
+
+        let patch = format!(
+            "From {commit} Mon Sep 17 00:00:00 2001\nSubject: [PATCH] Big\n\n---\n{lines}",
+            commit = "a".repeat(40),
+            lines = "+some added line\n".repeat(2000)
+        );
+        let mut options = PullRequestOptions {
+            max_event_size: Some(16 * 1024),
+            ..Default::default()
+        };
+        let keys = Keys::generate();
+        let create = |options: PullRequestOptions| {
+            let patches = vec![patch.clone()];
+            let keys = keys.clone();
+            async move {
+                create_pull_request_event(
+                    &keys,
+                    "30617:abc:repo",
+                    "Big",
+                    "",
+                    patches,
+                    "root",
+                    &options,
+                )
+                .await
+            }
+        };
+
+        let error = create(options.clone()).await.unwrap_err().to_string();
+        assert!(error.contains("Patch 1/1 (Big)"), "{error}");
+        assert!(error.contains("--split-large-patches"), "{error}");
+
+        options.split_large_patches = true;
+        let events = create(options.clone()).await.unwrap();
+        let parts: Vec<&Event> = events
+            .iter()
+            .filter(|event| event.kind == KIND_PATCH_PART)
+            .collect();
+        assert_eq!(parts.len(), 2);
+        assert!(
+            events
+                .iter()
+                .all(|event| event.as_json().len() <= 16 * 1024)
+        );
+
+        let patch_event = &events[parts.len()];
+        assert_eq!(patch_event.kind, KIND_PATCH);
+        let referenced: Vec<String> = patch_event
+            .tags
+            .iter()
+            .map(|tag| tag.as_slice())
+            .filter(|tag| tag.len() > 3 && tag[3] == PART_MARKER)
+            .map(|tag| tag[1].clone())
+            .collect();
+        assert_eq!(
+            referenced,
+            parts
+                .iter()
+                .map(|part| part.id.to_hex())
+                .collect::<Vec<_>>()
+        );
+        let joined = std::iter::once(patch_event)
+            .chain(parts)
+            .map(|event| event.content.as_str())
+            .collect::<String>();
+        assert_eq!(joined, patch);
+
+        // Patches within the limit are sent as they are
+        options.max_event_size = Some(DEFAULT_MAX_EVENT_SIZE);
+        let events = create(options).await.unwrap();
+        assert_eq!(events.len(), 3);
+    }
+}
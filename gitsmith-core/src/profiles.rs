@@ -0,0 +1,181 @@
+use nostr::{EventBuilder, Filter, JsonUtil, Kind, Metadata, PublicKey};
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::debug;
+
+use crate::error::Result;
+use crate::events;
+use crate::relay::{self, Network};
+use crate::signer::{self, Signer};
+use crate::types::BroadcastResult;
+
+/// Profile fields to change, `None` keeps the published value and an empty
+/// string removes it
+#[derive(Debug, Clone, Default)]
+pub struct ProfileUpdate {
+    pub name: Option<String>,
+    pub about: Option<String>,
+    pub nip05: Option<String>,
+    pub picture: Option<String>,
+}
+
+impl ProfileUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.about.is_none()
+            && self.nip05.is_none()
+            && self.picture.is_none()
+    }
+
+    /// Apply the changes on top of a published profile, other fields are kept
+    pub fn apply(&self, mut metadata: Metadata) -> Metadata {
+        let fields = [
+            (&mut metadata.name, &self.name),
+            (&mut metadata.about, &self.about),
+            (&mut metadata.nip05, &self.nip05),
+            (&mut metadata.picture, &self.picture),
+        ];
+        for (field, value) in fields {
+            if let Some(value) = value {
+                let value = value.trim();
+                *field = (!value.is_empty()).then(|| value.to_string());
+            }
+        }
+        metadata
+    }
+}
+
+/// Name to show for a profile: `display_name`, then `name`
+pub fn display_name(metadata: &Metadata) -> Option<String> {
+    [&metadata.display_name, &metadata.name]
+        .into_iter()
+        .flatten()
+        .map(|name| name.trim())
+        .find(|name| !name.is_empty())
+        .map(str::to_string)
+}
+
+/// Newest kind 0 metadata of each author, keyed by hex public key
+async fn fetch_profiles(
+    network: &Network,
+    client: &nostr_sdk::Client,
+    authors: Vec<PublicKey>,
+    timeout: Duration,
+) -> Result<HashMap<String, Metadata>> {
+    let filter = Filter::new().kind(Kind::Metadata).authors(authors);
+    let mut events = relay::fetch_events(network, client, vec![filter], timeout).await?;
+    events.sort_by(events::newest_first);
+
+    let mut profiles = HashMap::new();
+    for event in events {
+        if profiles.contains_key(&event.pubkey.to_hex()) {
+            continue;
+        }
+        match Metadata::from_json(&event.content) {
+            Ok(metadata) => {
+                profiles.insert(event.pubkey.to_hex(), metadata);
+            }
+            Err(e) => debug!("Ignoring invalid metadata in {id}: {e}", id = event.id),
+        }
+    }
+    Ok(profiles)
+}
+
+/// Fetch the published profile of a public key
+pub async fn fetch_profile(
+    network: &Network,
+    public_key: PublicKey,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<Option<Metadata>> {
+    let client = relay::connect(network, relays, None).await?;
+    let mut profiles = fetch_profiles(network, &client, vec![public_key], timeout).await?;
+    Ok(profiles.remove(&public_key.to_hex()))
+}
+
+/// Display names of the given hex public keys, keys without a profile or a
+/// name are left out
+pub async fn fetch_display_names(
+    network: &Network,
+    public_keys: &[String],
+    relays: &[String],
+    timeout: Duration,
+) -> Result<HashMap<String, String>> {
+    let authors: Vec<PublicKey> = public_keys
+        .iter()
+        .filter_map(|key| PublicKey::from_hex(key).ok())
+        .collect();
+    if authors.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let client = relay::connect(network, relays, None).await?;
+    let profiles = fetch_profiles(network, &client, authors, timeout).await?;
+    Ok(profiles
+        .into_iter()
+        .filter_map(|(key, metadata)| Some((key, display_name(&metadata)?)))
+        .collect())
+}
+
+/// Update the signer's profile and publish it as a kind 0 metadata event
+///
+/// The current profile is fetched first so fields gitsmith doesn't manage
+/// (banner, lud16, ...) are kept.
+pub async fn set_profile(
+    network: &Network,
+    signer: &dyn Signer,
+    relays: &[String],
+    update: &ProfileUpdate,
+    timeout: Duration,
+) -> Result<(Metadata, BroadcastResult)> {
+    let client = relay::connect(network, relays, Some(signer)).await?;
+    let public_key = signer.public_key();
+    let current = fetch_profiles(network, &client, vec![public_key], timeout)
+        .await?
+        .remove(&public_key.to_hex())
+        .unwrap_or_default();
+
+    let metadata = update.apply(current);
+    let event = signer::sign(signer, EventBuilder::metadata(&metadata)).await?;
+    let result = relay::publish_events(&client, &[event]).await?;
+    Ok((metadata, result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_update() {
+        let current = Metadata::new()
+            .name("alice")
+            .about("old bio")
+            .lud16("alice@example.com");
+        let update = ProfileUpdate {
+            about: Some("Rust and Nostr".to_string()),
+            nip05: Some("alice@example.com".to_string()),
+            name: Some(" ".to_string()),
+            ..ProfileUpdate::default()
+        };
+        let metadata = update.apply(current);
+
+        assert_eq!(metadata.name, None);
+        assert_eq!(metadata.about.as_deref(), Some("Rust and Nostr"));
+        assert_eq!(metadata.nip05.as_deref(), Some("alice@example.com"));
+        assert_eq!(metadata.lud16.as_deref(), Some("alice@example.com"));
+        assert!(ProfileUpdate::default().is_empty());
+    }
+
+    #[test]
+    fn test_display_name() {
+        assert_eq!(
+            display_name(&Metadata::new().name("alice").display_name("Alice")),
+            Some("Alice".to_string())
+        );
+        assert_eq!(
+            display_name(&Metadata::new().name("alice").display_name("")),
+            Some("alice".to_string())
+        );
+        assert_eq!(display_name(&Metadata::new()), None);
+    }
+}
@@ -1,13 +1,36 @@
-use anyhow::Result;
-use nostr::{Event, EventId, Filter};
-use nostr_sdk::{Client, RelayPoolNotification};
+use nostr::filter::MatchEventOptions;
+use nostr::{
+    Alphabet, Event, EventId, Filter, Kind, PublicKey, SingleLetterTag, Tag, Timestamp, ToBech32,
+};
+use nostr_sdk::Client;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use strum::{Display, EnumString};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
-use crate::patches::{KIND_PULL_REQUEST, KIND_PULL_REQUEST_UPDATE};
+use crate::apply::{self, DiffStat};
+use crate::cache;
+use crate::ci::{self, CheckSummary};
+use crate::comments::{self, Comment};
+use crate::error::{Context, GitsmithError, Result, ensure};
+use crate::events::{
+    self, KIND_GIT_STATUS_APPLIED, KIND_GIT_STATUS_CLOSED, KIND_GIT_STATUS_DRAFT,
+    KIND_GIT_STATUS_OPEN, tag_values,
+};
+use crate::patches::{
+    self, CommitMetadata, KIND_PATCH, KIND_PULL_REQUEST, KIND_PULL_REQUEST_UPDATE,
+    KIND_SERIES_MANIFEST, ManifestEntry,
+};
+use crate::relay::{self, Network};
+use crate::relay_lists;
+use crate::repo;
+use crate::reviews::{self, ReviewSummary};
+use crate::signer::{self, Signer};
+use crate::time::{TimeFormat, format_timestamp};
+use crate::types::BroadcastResult;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display, EnumString)]
 #[serde(rename_all = "lowercase")]
@@ -15,6 +38,41 @@ use crate::patches::{KIND_PULL_REQUEST, KIND_PULL_REQUEST_UPDATE};
 pub enum PullRequestStatus {
     Open,
     Updated,
+    Merged,
+    Closed,
+    Draft,
+}
+
+impl PullRequestStatus {
+    /// NIP-34 status event kind that publishes this status
+    pub fn status_kind(self) -> Option<u16> {
+        match self {
+            Self::Open => Some(KIND_GIT_STATUS_OPEN),
+            Self::Merged => Some(KIND_GIT_STATUS_APPLIED),
+            Self::Closed => Some(KIND_GIT_STATUS_CLOSED),
+            Self::Draft => Some(KIND_GIT_STATUS_DRAFT),
+            // Updates are derived from update events, there is no status kind for them
+            Self::Updated => None,
+        }
+    }
+
+    /// Status represented by a NIP-34 status event kind
+    pub fn from_status_kind(kind: u16) -> Option<Self> {
+        match kind {
+            KIND_GIT_STATUS_OPEN => Some(Self::Open),
+            KIND_GIT_STATUS_APPLIED => Some(Self::Merged),
+            KIND_GIT_STATUS_CLOSED => Some(Self::Closed),
+            KIND_GIT_STATUS_DRAFT => Some(Self::Draft),
+            _ => None,
+        }
+    }
+}
+
+/// All NIP-34 status event kinds
+fn status_kinds() -> Vec<Kind> {
+    (KIND_GIT_STATUS_OPEN..=KIND_GIT_STATUS_DRAFT)
+        .map(Kind::from)
+        .collect()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,34 +81,527 @@ pub struct PullRequest {
     pub title: String,
     pub description: String,
     pub author: String,
+    /// Display name from the author's kind 0 profile, see [`set_author_names`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author_name: Option<String>,
     pub created_at: u64,
     pub updated_at: Option<u64>,
     pub patches_count: usize,
     pub root_commit: Option<String>,
     pub status: PullRequestStatus,
+    /// Number of comments (NIP-22 comments and text note replies) on the PR
+    #[serde(default)]
+    pub comments_count: usize,
+    /// Reaction counts keyed by emoji (NIP-25)
+    #[serde(default)]
+    pub reactions: BTreeMap<String, usize>,
+    /// Later versions of the patch series, oldest first (the PR itself is version 1)
+    #[serde(default)]
+    pub revisions: Vec<PullRequestRevision>,
+    /// Opened by the repository owner or a maintainer it trusts
+    #[serde(default)]
+    pub by_maintainer: bool,
+    /// Labels from the PR's `t` tags
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Latest review verdict of each reviewer
+    #[serde(default)]
+    pub reviews: ReviewSummary,
+    /// Latest state of each CI check
+    #[serde(default)]
+    pub checks: CheckSummary,
+    /// Merged without a status event, found by its patches in the local history
+    #[serde(default)]
+    pub merged_detected: bool,
+}
+
+impl PullRequest {
+    /// Version of the latest patch series
+    pub fn version(&self) -> u32 {
+        self.revisions.last().map_or(1, |revision| revision.version)
+    }
+
+    /// Event ID of the latest patch series, to fetch or apply the current version
+    pub fn latest_event_id(&self) -> &str {
+        self.revisions
+            .last()
+            .map_or(self.id.as_str(), |revision| revision.id.as_str())
+    }
+}
+
+/// A new version of a PR's patch series
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequestRevision {
+    pub id: String,
+    pub version: u32,
+    pub author: String,
+    pub created_at: u64,
+    pub patches_count: usize,
+}
+
+/// A pull request event together with its patch events, in series order
+#[derive(Debug, Clone)]
+pub struct PatchSeries {
+    pub pr: Event,
+    pub patches: Vec<Event>,
+}
+
+impl PatchSeries {
+    /// Raw patch contents in series order
+    pub fn patch_contents(&self) -> Vec<String> {
+        self.patches.iter().map(|e| e.content.clone()).collect()
+    }
+
+    /// Commit the series was based on, if the first patch declares it (NIP-34 `parent-commit`)
+    pub fn parent_commit(&self) -> Option<String> {
+        self.patches
+            .first()
+            .and_then(|patch| get_tag_value(patch, "parent-commit"))
+    }
+
+    /// Short form of the PR event ID used for branch names
+    pub fn short_id(&self) -> String {
+        self.pr.id.to_hex()[..8].to_string()
+    }
+
+    /// Title of the PR, the subject of its first patch for series without a PR event
+    pub fn title(&self) -> String {
+        get_tag_value(&self.pr, "subject")
+            .or_else(|| {
+                self.patches
+                    .first()
+                    .and_then(|patch| apply::parse_patch(&patch.content).ok())
+                    .map(|patch| patch.subject)
+            })
+            .unwrap_or_else(|| "Untitled PR".to_string())
+    }
+}
+
+/// A patch of a pull request with its change summary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatchSummary {
+    pub id: String,
+    pub subject: String,
+    /// Git author ("Name <email>") as recorded in the patch
+    pub git_author: String,
+    pub created_at: u64,
+    pub diffstat: DiffStat,
+    /// Raw patch in `git format-patch` layout
+    pub content: String,
+    /// Commit the patch was generated from, from its NIP-34 tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<CommitMetadata>,
+}
+
+/// A status change published for a pull request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusChange {
+    pub id: String,
+    pub status: PullRequestStatus,
+    pub author: String,
+    pub created_at: u64,
+    pub message: String,
+    /// Commits a merged PR landed as
+    #[serde(flatten)]
+    pub commits: AppliedCommits,
+}
+
+/// Commits a merged PR landed as, tagged on its applied status (NIP-34)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppliedCommits {
+    /// Merge commit bringing the PR into the target branch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge_commit: Option<String>,
+    /// Commits the patches were applied as, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub applied_as_commits: Vec<String>,
+}
+
+impl AppliedCommits {
+    pub fn is_empty(&self) -> bool {
+        self.merge_commit.is_none() && self.applied_as_commits.is_empty()
+    }
+
+    /// `merge-commit` and `applied-as-commits` tags, with an `r` tag per commit
+    fn tags(&self) -> Result<Vec<Tag>> {
+        let mut tags = Vec::new();
+        if let Some(commit) = &self.merge_commit {
+            tags.push(Tag::parse(["merge-commit", commit])?);
+        }
+        if !self.applied_as_commits.is_empty() {
+            tags.push(Tag::parse(
+                std::iter::once("applied-as-commits")
+                    .chain(self.applied_as_commits.iter().map(String::as_str)),
+            )?);
+        }
+        for commit in self.merge_commit.iter().chain(&self.applied_as_commits) {
+            tags.push(Tag::parse(["r", commit])?);
+        }
+        Ok(tags)
+    }
+
+    fn from_event(event: &Event) -> Self {
+        Self {
+            merge_commit: tag_values(event, "merge-commit")
+                .first()
+                .map(|commit| commit.to_string()),
+            applied_as_commits: event
+                .tags
+                .iter()
+                .map(|tag| tag.as_slice())
+                .find(|tag| tag.first().is_some_and(|name| name == "applied-as-commits"))
+                .map(|tag| tag[1..].to_vec())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// A pull request with its patches, status history, engagement and comment thread
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequestDetails {
+    #[serde(flatten)]
+    pub pr: PullRequest,
+    pub patches: Vec<PatchSummary>,
+    /// Cover letter (patch 0/N) in `git format-patch` layout, if the series has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover_letter: Option<String>,
+    /// Patches listed by the series manifest, including any not found on relays
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub manifest: Vec<ManifestEntry>,
+    /// Authorized status changes, oldest first
+    pub status_history: Vec<StatusChange>,
+    pub comments: Vec<Comment>,
+}
+
+/// Options for listing pull requests
+#[derive(Debug, Clone)]
+pub struct ListOptions {
+    /// Longest wait for relays to deliver events, fetches end early once
+    /// every relay sent EOSE
+    pub timeout: Duration,
+    /// Ignore PR and comment events below this NIP-13 difficulty (0 = accept all)
+    pub min_pow: u8,
+    /// Also look on the relays the authors list in their NIP-65 relay lists
+    pub outbox: bool,
+    /// Only list what the event cache holds, without contacting relays
+    pub offline: bool,
+    /// Only PRs opened by this author, asked of relays
+    pub author: Option<PublicKey>,
+    /// Only PRs opened at or after this time, asked of relays
+    pub since: Option<Timestamp>,
+    /// Largest number of PRs to list, in `sort` order (None = all of them)
+    pub limit: Option<usize>,
+    /// Order of the listed PRs
+    pub sort: PullRequestSort,
+    /// Only PRs with this status, open includes updated PRs
+    pub status: Option<PullRequestStatus>,
+    /// Only PRs carrying all of these labels
+    pub labels: Vec<String>,
+    /// Continue a listing after the last PR of a previous page
+    pub cursor: Option<Cursor>,
+    /// Local clone to look for the patches of open PRs in, marking the PRs
+    /// merged upstream without a status event (not with `offline`)
+    pub repo_path: Option<PathBuf>,
+}
+
+impl ListOptions {
+    /// Whether relays are only asked for part of the PRs, which must not count
+    /// as a complete sync
+    fn is_partial(&self) -> bool {
+        self.since.is_some() || self.limit.is_some() || self.cursor.is_some()
+    }
+
+    /// Whether relays can be asked for the PRs of a page, as they only know
+    /// when PRs were opened, not their statuses or labels
+    fn pages_on_relays(&self) -> bool {
+        self.sort == PullRequestSort::Newest && self.status.is_none() && self.labels.is_empty()
+    }
+
+    /// Whether a PR has the status and labels asked for
+    fn matches(&self, pr: &PullRequest) -> bool {
+        self.status.is_none_or(|status| {
+            pr.status == status
+                || (status == PullRequestStatus::Open && pr.status == PullRequestStatus::Updated)
+        }) && self.labels.iter().all(|label| pr.labels.contains(label))
+    }
+}
+
+/// Number of PRs `gitsmith list` shows per page
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+
+/// Position in a listing: right after the PR with the given ID, whose sort
+/// key (see [`PullRequestSort::key`]) is `key`
+///
+/// Printed as `<key>-<id>` newest first and `<sort>-<key>-<id>` otherwise, the
+/// continuation token of the next page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub sort: PullRequestSort,
+    pub key: u64,
+    pub id: String,
+}
+
+impl Cursor {
+    /// Position right after `pr` in `sort` order
+    fn after(sort: PullRequestSort, pr: &PullRequest) -> Self {
+        Self {
+            sort,
+            key: sort.key(pr),
+            id: pr.id.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.sort != PullRequestSort::Newest {
+            write!(f, "{sort}-", sort = self.sort)?;
+        }
+        write!(f, "{key}-{id}", key = self.key, id = self.id)
+    }
+}
+
+impl std::str::FromStr for Cursor {
+    type Err = GitsmithError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let invalid = || GitsmithError::msg(format!("Invalid cursor '{value}'"));
+        let parts: Vec<&str> = value.trim().split('-').collect();
+        let (sort, key, id) = match parts[..] {
+            [key, id] => (PullRequestSort::Newest, key, id),
+            [sort, key, id] => (sort.parse().map_err(|_| invalid())?, key, id),
+            _ => return Err(invalid()),
+        };
+        if EventId::from_hex(id).is_err() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            sort,
+            key: key.parse().map_err(|_| invalid())?,
+            id: id.to_string(),
+        })
+    }
+}
+
+/// A page of pull requests, newest first
+#[derive(Debug, Clone, Serialize)]
+pub struct PullRequestPage {
+    pub pull_requests: Vec<PullRequest>,
+    /// Continuation token of the next page, which may turn out empty
+    pub next_cursor: Option<String>,
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        Self {
+            timeout: relay::DEFAULT_FETCH_TIMEOUT,
+            min_pow: 0,
+            outbox: true,
+            offline: false,
+            author: None,
+            since: None,
+            limit: None,
+            sort: PullRequestSort::Newest,
+            status: None,
+            labels: Vec::new(),
+            cursor: None,
+            repo_path: None,
+        }
+    }
+}
+
+/// Order of listed pull requests
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum PullRequestSort {
+    /// Most recently opened first
+    #[default]
+    Newest,
+    /// Least recently opened first
+    Oldest,
+    /// Most recently revised first
+    Updated,
+}
+
+impl PullRequestSort {
+    /// Time a PR is ordered by
+    pub fn key(self, pr: &PullRequest) -> u64 {
+        match self {
+            Self::Newest | Self::Oldest => pr.created_at,
+            Self::Updated => pr.updated_at.unwrap_or(pr.created_at),
+        }
+    }
+
+    /// Order of two PRs given by their keys and IDs, ties ordered by ID
+    fn compare(self, (a_key, a_id): (u64, &str), (b_key, b_id): (u64, &str)) -> Ordering {
+        match self {
+            Self::Oldest => a_key.cmp(&b_key),
+            Self::Newest | Self::Updated => b_key.cmp(&a_key),
+        }
+        .then_with(|| a_id.cmp(b_id))
+    }
+}
+
+/// Sort pull requests, ties keep a stable order by ID
+pub fn sort_pull_requests(prs: &mut [PullRequest], order: PullRequestSort) {
+    prs.sort_by(|a, b| order.compare((order.key(a), &a.id), (order.key(b), &b.id)));
 }
 
 /// List pull requests for a repository
 pub async fn list_pull_requests(
+    network: &Network,
     repo_coordinate: &str,
     relays: Vec<String>,
 ) -> Result<Vec<PullRequest>> {
-    list_pull_requests_with_timeout(repo_coordinate, relays, Duration::from_millis(1500)).await
+    list_pull_requests_with_options(network, repo_coordinate, relays, &ListOptions::default()).await
 }
 
 /// List pull requests for a repository with custom timeout
 pub async fn list_pull_requests_with_timeout(
+    network: &Network,
     repo_coordinate: &str,
     relays: Vec<String>,
     timeout_duration: Duration,
 ) -> Result<Vec<PullRequest>> {
+    let options = ListOptions {
+        timeout: timeout_duration,
+        ..ListOptions::default()
+    };
+    list_pull_requests_with_options(network, repo_coordinate, relays, &options).await
+}
+
+/// List pull requests for a repository with custom options
+pub async fn list_pull_requests_with_options(
+    network: &Network,
+    repo_coordinate: &str,
+    relays: Vec<String>,
+    options: &ListOptions,
+) -> Result<Vec<PullRequest>> {
+    Ok(
+        list_pull_request_page(network, repo_coordinate, relays, options)
+            .await?
+            .pull_requests,
+    )
+}
+
+/// List a page of pull requests, `options.limit` of them after `options.cursor`
+///
+/// Newest first and without status or label filters, relays are asked for
+/// PR events opened before the cursor a batch at a time until they fill the
+/// page, then for the revisions of the PRs on the page, which may be newer. Otherwise every PR is listed, and the
+/// page is cut from the matching ones in the order asked for.
+pub async fn list_pull_request_page(
+    network: &Network,
+    repo_coordinate: &str,
+    relays: Vec<String>,
+    options: &ListOptions,
+) -> Result<PullRequestPage> {
+    if let Some(cursor) = &options.cursor {
+        ensure!(
+            cursor.sort == options.sort,
+            "The cursor continues a listing sorted by {cursor_sort}, not {sort}",
+            cursor_sort = cursor.sort,
+            sort = options.sort
+        );
+    }
+    if options.pages_on_relays() {
+        return fetch_pull_request_page(network, repo_coordinate, relays, options).await;
+    }
+
+    let everything = ListOptions {
+        limit: None,
+        cursor: None,
+        ..options.clone()
+    };
+    let mut prs = fetch_pull_request_page(network, repo_coordinate, relays, &everything)
+        .await?
+        .pull_requests;
+    prs.retain(|pr| options.matches(pr));
+    let (pull_requests, next_cursor) = match options.limit {
+        Some(limit) => take_page(prs, options.sort, options.cursor.as_ref(), limit),
+        None => {
+            sort_pull_requests(&mut prs, options.sort);
+            (prs, None)
+        }
+    };
+    Ok(PullRequestPage {
+        pull_requests,
+        next_cursor: next_cursor.map(|cursor| cursor.to_string()),
+    })
+}
+
+/// List a page of pull requests opened before `options.cursor`, newest first
+async fn fetch_pull_request_page(
+    network: &Network,
+    repo_coordinate: &str,
+    relays: Vec<String>,
+    options: &ListOptions,
+) -> Result<PullRequestPage> {
+    let timeout_duration = options.timeout;
     info!(
         "Listing pull requests for {} with timeout {:?}",
         repo_coordinate, timeout_duration
     );
     debug!("Using relays: {:?}", relays);
+    let repo_owner = repo_coordinate.split(':').nth(1);
+
+    if options.offline {
+        let maintainers =
+            fetch_maintainers(network, None, repo_coordinate, timeout_duration).await?;
+        let (filter, root_patches) = pull_request_filters(&maintainers.coordinates, options);
+        let filters = [filter, root_patches];
+        let mut next_cursor = None;
+        let mut result = match options.limit {
+            Some(limit) => {
+                let fetched = fetch_page_events(
+                    PageSource::Cache(network),
+                    &filters,
+                    options,
+                    limit,
+                    repo_owner,
+                )
+                .await?;
+                let events = filter_low_pow(fetched.events, options.min_pow);
+                let page;
+                (page, next_cursor) = cut_page(
+                    events.clone(),
+                    fetched.since,
+                    options.cursor.as_ref(),
+                    limit,
+                    repo_owner,
+                )?;
+                let revisions =
+                    cache::cached_events(network.cache.as_deref(), &revision_filters(&page))?;
+                rebuild_page(events, revisions, &page, options.min_pow, repo_owner)?
+            }
+            None => build_pull_requests(
+                filter_low_pow(
+                    cache::cached_events(network.cache.as_deref(), &filters)?,
+                    options.min_pow,
+                ),
+                repo_owner,
+            )?,
+        };
+        let related = cache::cached_events(network.cache.as_deref(), &related_filters(&result))?;
+        apply_related(
+            &mut result,
+            related,
+            options.min_pow,
+            repo_owner,
+            &maintainers,
+        );
+        mark_maintainers(&mut result, &maintainers);
+        info!(
+            "Returning {count} cached pull requests",
+            count = result.len()
+        );
+        return Ok(PullRequestPage {
+            pull_requests: result,
+            next_cursor: next_cursor.map(|cursor| cursor.to_string()),
+        });
+    }
 
-    let client = Client::default();
+    let client = relay::new_client(network, None);
 
     // Add relays
     for relay_url in &relays {
@@ -62,116 +613,1641 @@ pub async fn list_pull_requests_with_timeout(
     info!("Connecting to {} relay(s)", relays.len());
     client.connect().await;
 
-    // Create filter for PR events
-    let mut filter = Filter::new();
-    filter = filter.kinds(vec![KIND_PULL_REQUEST, KIND_PULL_REQUEST_UPDATE]);
-    // Add custom tag for repository coordinate
-    filter = filter.custom_tag(
-        nostr::SingleLetterTag::lowercase(nostr::Alphabet::A),
-        repo_coordinate,
-    );
+    // Co-maintainers announce the repository under coordinates of their own
+    let maintainers =
+        match fetch_maintainers(network, Some(&client), repo_coordinate, timeout_duration).await {
+            Ok(maintainers) => maintainers,
+            Err(e) => {
+                warn!("Failed to fetch the maintainers' announcements: {e}");
+                repo::resolve_maintainers(repo_coordinate, &[])?
+            }
+        };
+    let (filter, root_patches) = pull_request_filters(&maintainers.coordinates, options);
     debug!(
-        "Created filter for PR events with repository coordinate: {}",
-        repo_coordinate
+        "Listing PRs of coordinates {coordinates:?}",
+        coordinates = maintainers.coordinates
     );
 
-    // Subscribe to events
-    client.subscribe(filter, None).await?;
+    // PRs cached by earlier runs, their older related events are cached as well
+    let known: HashSet<EventId> = match &network.cache {
+        Some(cache) => cache
+            .query(&[filter.clone(), root_patches.clone()])?
+            .iter()
+            .map(|event| event.id)
+            .collect(),
+        None => HashSet::new(),
+    };
+
+    info!("Fetching PR events, waiting up to {timeout_duration:?}");
+    let mut scope = format!(
+        "pull-requests:{coordinates}",
+        coordinates = maintainers.coordinates.join(",")
+    );
+    if let Some(author) = &options.author {
+        scope.push_str(&format!(";author={author}"));
+    }
+    let mut filters = vec![filter, root_patches];
+    let mut since = None;
+    let mut events = match options.limit {
+        Some(limit) => {
+            let fetched = fetch_page_events(
+                PageSource::Relays {
+                    network,
+                    client: &client,
+                    scope: &scope,
+                },
+                &filters,
+                options,
+                limit,
+                repo_owner,
+            )
+            .await?;
+            // The authors' relays are only asked for the span the page was cut from
+            filters = filters
+                .into_iter()
+                .map(|mut filter| {
+                    if let Some(cursor) = &options.cursor {
+                        filter = filter.until(Timestamp::from(cursor.key));
+                    }
+                    if let Some(since) = fetched.since {
+                        filter = filter.since(since);
+                    }
+                    filter
+                })
+                .collect();
+            since = fetched.since;
+            fetched.events
+        }
+        None => {
+            fetch_pull_request_events(network, &client, &scope, filters.clone(), options).await?
+        }
+    };
     info!(
-        "Subscribed to PR events, waiting up to {:?}",
-        timeout_duration
+        "Collected {count} events from relays and cache",
+        count = events.len()
     );
 
-    // Collect events with early exit when found
-    let mut events = Vec::new();
-    let timeout = tokio::time::sleep(timeout_duration); // Use configurable timeout
-    tokio::pin!(timeout);
+    // Authors may publish revisions and receive comments on relays of their own
+    let outbox = if options.outbox {
+        let mut authors: Vec<PublicKey> = events.iter().map(|event| event.pubkey).collect();
+        authors.extend(repo_owner.and_then(|owner| PublicKey::from_hex(owner).ok()));
+        authors.sort();
+        authors.dedup();
+        relay_lists::connect_outbox(network, &client, authors, &relays, timeout_duration).await
+    } else {
+        None
+    };
+    if let Some(outbox) = &outbox {
+        match fetch_pull_request_events(network, outbox, &scope, filters, options).await {
+            Ok(found) => events = relay::merge_events(events, found),
+            Err(e) => warn!("Failed to fetch PR events from the authors' relays: {e}"),
+        }
+    }
+    let events: Vec<Event> = events
+        .into_iter()
+        .filter(|event| {
+            event.kind == KIND_PULL_REQUEST
+                || event.kind == KIND_PULL_REQUEST_UPDATE
+                || is_root_patch(event)
+        })
+        .collect();
+
+    // Drop spam below the repository's proof-of-work requirement
+    let events = filter_low_pow(events, options.min_pow);
+
+    // Process events into pull requests
+    debug!("Processing {} events into pull requests", events.len());
+    let mut result = build_pull_requests(events.clone(), repo_owner)?;
+    let mut next_cursor = None;
+    if let Some(limit) = options.limit {
+        let page;
+        (page, next_cursor) = cut_page(
+            events.clone(),
+            since,
+            options.cursor.as_ref(),
+            limit,
+            repo_owner,
+        )?;
+        // Revisions of the page's PRs may be newer than what relays were asked for
+        let revisions = if page.is_empty() {
+            Vec::new()
+        } else {
+            fetch_related_events(
+                network,
+                &client,
+                outbox.as_ref(),
+                None,
+                revision_filters(&page),
+                timeout_duration,
+            )
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Failed to fetch PR revisions: {e}");
+                Vec::new()
+            })
+        };
+        result = rebuild_page(events, revisions, &page, options.min_pow, repo_owner)?;
+    }
+
+    // Aggregate status, reactions and comments referencing the PRs
+    if !result.is_empty() {
+        // Known PRs are synced incrementally, new ones may have older related events
+        let (known_prs, new_prs): (Vec<&PullRequest>, Vec<&PullRequest>) = result
+            .iter()
+            .partition(|pr| EventId::from_hex(&pr.id).is_ok_and(|id| known.contains(&id)));
+        let scope = format!("related:{repo_coordinate}");
+        let mut related = Vec::new();
+        for (prs, scope) in [(known_prs, Some(scope.as_str())), (new_prs, None)] {
+            if prs.is_empty() {
+                continue;
+            }
+            let filters = related_filters(prs);
+            match fetch_related_events(
+                network,
+                &client,
+                outbox.as_ref(),
+                scope,
+                filters,
+                timeout_duration,
+            )
+            .await
+            {
+                Ok(found) => related = relay::merge_events(related, found),
+                Err(e) => warn!("Failed to fetch status, reactions and comments: {e}"),
+            }
+        }
+        apply_related(
+            &mut result,
+            related,
+            options.min_pow,
+            repo_owner,
+            &maintainers,
+        );
+    }
+    mark_maintainers(&mut result, &maintainers);
+    if let Some(repo_path) = &options.repo_path {
+        detect_merged(network, &client, &mut result, repo_path, timeout_duration).await;
+    }
+
+    info!(
+        "Returning {} unique pull requests after processing",
+        result.len()
+    );
+    Ok(PullRequestPage {
+        pull_requests: result,
+        next_cursor: next_cursor.map(|cursor| cursor.to_string()),
+    })
+}
+
+/// How long before the oldest open PR [`detect_merged`] looks at local
+/// commits, as merged branches keep the commit times of the author's clone
+const DETECT_MERGED_MARGIN: u64 = 30 * 24 * 60 * 60;
+
+/// Mark open PRs whose patches are all in the local history as merged
+///
+/// Patches are matched by patch ID, so PRs applied with `git am` or rebased
+/// are found too. Failures only leave the PRs' statuses as they are.
+async fn detect_merged(
+    network: &Network,
+    client: &Client,
+    prs: &mut [PullRequest],
+    repo_path: &Path,
+    timeout: Duration,
+) {
+    let mut candidates: Vec<&mut PullRequest> = prs
+        .iter_mut()
+        .filter(|pr| {
+            matches!(
+                pr.status,
+                PullRequestStatus::Open | PullRequestStatus::Updated | PullRequestStatus::Draft
+            )
+        })
+        .collect();
+    let Some(oldest) = candidates.iter().map(|pr| pr.created_at).min() else {
+        return;
+    };
+    let since = oldest.saturating_sub(DETECT_MERGED_MARGIN);
+    let history = match apply::history_patch_ids(repo_path, since as i64) {
+        Ok(history) => history,
+        Err(e) => {
+            warn!("Failed to read the local history to detect merged PRs: {e}");
+            return;
+        }
+    };
+
+    let series = futures::future::join_all(
+        candidates
+            .iter()
+            .map(|pr| fetch_patch_series_with(network, client, pr.latest_event_id(), timeout)),
+    )
+    .await;
+    for (pr, series) in candidates.iter_mut().zip(series) {
+        let series = match series {
+            Ok(series) => series,
+            Err(e) => {
+                debug!(pr = %pr.id, "Failed to fetch patches to detect a merge: {e}");
+                continue;
+            }
+        };
+        let merged = !series.patches.is_empty()
+            && series.patches.iter().all(|patch| {
+                apply::parse_patch(&patch.content)
+                    .and_then(|parsed| apply::patch_id(&parsed.diff))
+                    .is_ok_and(|id| history.contains(&id))
+            });
+        if merged {
+            pr.status = PullRequestStatus::Merged;
+            pr.merged_detected = true;
+        }
+    }
+}
+
+/// The first `limit` PRs after `cursor` in `sort` order, and the cursor after
+/// the last of them when more PRs follow
+fn take_page(
+    mut prs: Vec<PullRequest>,
+    sort: PullRequestSort,
+    cursor: Option<&Cursor>,
+    limit: usize,
+) -> (Vec<PullRequest>, Option<Cursor>) {
+    if let Some(cursor) = cursor {
+        prs.retain(|pr| {
+            sort.compare((sort.key(pr), &pr.id), (cursor.key, &cursor.id))
+                .is_gt()
+        });
+    }
+    sort_pull_requests(&mut prs, sort);
+    let more = prs.len() > limit;
+    prs.truncate(limit);
+    let next = more
+        .then(|| prs.last())
+        .flatten()
+        .map(|last| Cursor::after(sort, last));
+    (prs, next)
+}
 
-    let mut notifications = client.notifications();
-    let mut last_event_time = tokio::time::Instant::now();
+/// Where [`fetch_page_events`] fetches PR events from
+enum PageSource<'a> {
+    Cache(&'a Network),
+    Relays {
+        network: &'a Network,
+        client: &'a Client,
+        scope: &'a str,
+    },
+}
+
+impl PageSource<'_> {
+    /// Events matching any of the filters
+    async fn fetch(&self, filters: Vec<Filter>, options: &ListOptions) -> Result<Vec<Event>> {
+        match self {
+            Self::Cache(network) => cache::cached_events(network.cache.as_deref(), &filters),
+            Self::Relays {
+                network,
+                client,
+                scope,
+            } => fetch_pull_request_events(network, client, scope, filters, options).await,
+        }
+    }
+}
+
+/// PR events fetched for a page, see [`fetch_page_events`]
+struct PageEvents {
+    events: Vec<Event>,
+    /// Oldest timestamp all PR events up to the cursor were fetched from,
+    /// `None` when relays have no older ones
+    since: Option<Timestamp>,
+}
 
+/// Fetch PR events newest first from the cursor on, `limit` per filter at a
+/// time, until their PRs fill a page or relays run out of them
+///
+/// A full batch may stop partway through the PRs opened in its oldest second,
+/// so that second is fetched again without a limit before asking for older
+/// events. PRs sharing a timestamp with the cursor or with each other are
+/// therefore never skipped, and pages short of spam or duplicates keep going.
+async fn fetch_page_events(
+    source: PageSource<'_>,
+    filters: &[Filter],
+    options: &ListOptions,
+    limit: usize,
+    repo_owner: Option<&str>,
+) -> Result<PageEvents> {
+    let mut until = options
+        .cursor
+        .as_ref()
+        .map(|cursor| Timestamp::from(cursor.key));
+    let mut events = Vec::new();
     loop {
-        tokio::select! {
-            _ = &mut timeout => {
-                debug!("Timeout reached after {:?}", timeout_duration);
-                break;
-            },
-            notification = notifications.recv() => {
-                if let Ok(notification) = notification {
-                    if let RelayPoolNotification::Event { event, .. } = notification
-                        && (event.kind == KIND_PULL_REQUEST || event.kind == KIND_PULL_REQUEST_UPDATE) {
-                            debug!("Received PR event: {} (kind={})", event.id, event.kind);
-                            events.push(*event);
-                            last_event_time = tokio::time::Instant::now();
+        let batch = source
+            .fetch(
+                filters
+                    .iter()
+                    .map(|filter| {
+                        let filter = filter.clone().limit(limit);
+                        match until {
+                            Some(until) => filter.until(until),
+                            None => filter,
                         }
-                    // If we've found events and 100ms have passed without new ones, exit early
-                    if !events.is_empty() && last_event_time.elapsed() > Duration::from_millis(100) {
-                        debug!("Early exit: found {} events and 100ms elapsed without new ones", events.len());
-                        break;
-                    }
-                }
-            }
+                    })
+                    .collect(),
+                options,
+            )
+            .await?;
+        // The oldest second of a filter that returned its whole limit may be cut short
+        let cut = filters
+            .iter()
+            .filter_map(|filter| {
+                let matching: Vec<Timestamp> = batch
+                    .iter()
+                    .filter(|event| filter.match_event(event, MatchEventOptions::new()))
+                    .map(|event| event.created_at)
+                    .collect();
+                (matching.len() >= limit)
+                    .then(|| matching.into_iter().min())
+                    .flatten()
+            })
+            .max();
+        events = relay::merge_events(events, batch);
+        let Some(since) = cut else {
+            return Ok(PageEvents {
+                events,
+                since: None,
+            });
+        };
+
+        let second = filters
+            .iter()
+            .map(|filter| filter.clone().since(since).until(since))
+            .collect();
+        events = relay::merge_events(events, source.fetch(second, options).await?);
+        let (page, _) = cut_page(
+            filter_low_pow(events.clone(), options.min_pow),
+            Some(since),
+            options.cursor.as_ref(),
+            limit,
+            repo_owner,
+        )?;
+        if page.len() == limit || since.as_u64() == 0 {
+            return Ok(PageEvents {
+                events,
+                since: Some(since),
+            });
         }
+        until = Some(Timestamp::from(since.as_u64() - 1));
     }
+}
 
-    info!("Collected {} raw events from relays", events.len());
+/// Cut a newest first page from PR events fetched by [`fetch_page_events`]
+///
+/// Only PRs opened from `since` on are complete, and a full page may be
+/// followed by older PRs on relays.
+fn cut_page(
+    events: Vec<Event>,
+    since: Option<Timestamp>,
+    cursor: Option<&Cursor>,
+    limit: usize,
+    repo_owner: Option<&str>,
+) -> Result<(Vec<PullRequest>, Option<Cursor>)> {
+    let sort = PullRequestSort::Newest;
+    let mut prs = build_pull_requests(events, repo_owner)?;
+    if let Some(since) = since {
+        prs.retain(|pr| pr.created_at >= since.as_u64());
+    }
+    let (page, next) = take_page(prs, sort, cursor, limit);
+    let next = match since {
+        Some(_) if page.len() == limit => page.last().map(|last| Cursor::after(sort, last)),
+        _ => next,
+    };
+    Ok((page, next))
+}
+
+/// Filters for the updates and revisions of PRs
+fn revision_filters(prs: &[PullRequest]) -> Vec<Filter> {
+    let pr_ids: Vec<EventId> = prs
+        .iter()
+        .filter_map(|pr| EventId::from_hex(&pr.id).ok())
+        .collect();
+    vec![
+        Filter::new()
+            .kinds(vec![KIND_PULL_REQUEST, KIND_PULL_REQUEST_UPDATE])
+            .events(pr_ids.clone()),
+        Filter::new()
+            .kind(KIND_PATCH)
+            .events(pr_ids)
+            .hashtag(patches::REVISION_HASHTAG),
+    ]
+}
+
+/// Build the PRs of a page again, now with their revisions
+fn rebuild_page(
+    events: Vec<Event>,
+    revisions: Vec<Event>,
+    page: &[PullRequest],
+    min_pow: u8,
+    repo_owner: Option<&str>,
+) -> Result<Vec<PullRequest>> {
+    let events = relay::merge_events(events, filter_low_pow(revisions, min_pow));
+    let mut prs = build_pull_requests(events, repo_owner)?;
+    prs.retain(|pr| page.iter().any(|listed| listed.id == pr.id));
+    Ok(prs)
+}
+
+/// Filters for the PR events and root patches of any of the repository
+/// coordinates, narrowed to the author and time the options ask for
+///
+/// Pages narrow them further as [`fetch_page_events`] walks back in time.
+fn pull_request_filters(coordinates: &[String], options: &ListOptions) -> (Filter, Filter) {
+    let narrow = |mut filter: Filter| {
+        if let Some(author) = options.author {
+            filter = filter.author(author);
+        }
+        if let Some(since) = options.since {
+            filter = filter.since(since);
+        }
+        filter
+    };
+    // Pages only count PRs, their updates and revisions are fetched separately
+    let (kinds, hashtags) = if options.limit.is_some() {
+        (vec![KIND_PULL_REQUEST], vec![patches::ROOT_HASHTAG])
+    } else {
+        (
+            vec![KIND_PULL_REQUEST, KIND_PULL_REQUEST_UPDATE],
+            vec![patches::ROOT_HASHTAG, patches::REVISION_HASHTAG],
+        )
+    };
+    let filter = Filter::new()
+        .kinds(kinds)
+        .custom_tags(SingleLetterTag::lowercase(Alphabet::A), coordinates);
+    // Series published without a PR event (e.g. by ngit) start with a root patch
+    let root_patches = Filter::new()
+        .kind(KIND_PATCH)
+        .custom_tags(SingleLetterTag::lowercase(Alphabet::A), coordinates)
+        .hashtags(hashtags);
+    (narrow(filter), narrow(root_patches))
+}
+
+/// Fetch PR events, syncing `scope` incrementally unless only part of them are
+/// asked for, as its sync mark would then claim the rest was fetched too
+async fn fetch_pull_request_events(
+    network: &Network,
+    client: &Client,
+    scope: &str,
+    filters: Vec<Filter>,
+    options: &ListOptions,
+) -> Result<Vec<Event>> {
+    if options.is_partial() {
+        relay::fetch_events(network, client, filters, options.timeout).await
+    } else {
+        relay::fetch_events_synced(network, client, scope, filters, options.timeout).await
+    }
+}
+
+/// Resolve the repository's maintainers from announcements on relays, or in
+/// the event cache without a client
+///
+/// Every round asks for the announcements of newly trusted maintainers, as
+/// theirs may list further maintainers.
+pub(crate) async fn fetch_maintainers(
+    network: &Network,
+    client: Option<&Client>,
+    repo_coordinate: &str,
+    timeout: Duration,
+) -> Result<repo::Maintainers> {
+    let fetch = async |filters: Vec<Filter>| match client {
+        Some(client) => relay::fetch_events(network, client, filters, timeout).await,
+        None => cache::cached_events(network.cache.as_deref(), &filters),
+    };
+    let (_kind, _owner, identifier) = patches::parse_repo_coordinate(repo_coordinate)?;
+    let announcement = Filter::new().kind(Kind::from(events::KIND_GIT_REPO_ANNOUNCEMENT));
+    let mut announcements = fetch(vec![announcement.clone().identifier(identifier)]).await?;
+
+    let mut asked = HashSet::new();
+    loop {
+        let maintainers = repo::resolve_maintainers(repo_coordinate, &announcements)?;
+        // The owner's announcement came with the identifier
+        let unknown: Vec<PublicKey> = maintainers
+            .keys
+            .iter()
+            .skip(1)
+            .filter(|key| asked.insert(**key))
+            .copied()
+            .collect();
+        if unknown.is_empty() {
+            return Ok(maintainers);
+        }
+        let found = fetch(vec![announcement.clone().authors(unknown)]).await?;
+        announcements.extend(found);
+    }
+}
+
+/// Resolve the repository's maintainers from the announcements held by `store`
+///
+/// Like [`fetch_maintainers`], every round looks up the announcements of newly
+/// trusted maintainers.
+fn cached_maintainers(
+    store: &dyn cache::CacheStore,
+    repo_coordinate: &str,
+) -> Result<repo::Maintainers> {
+    let (_kind, _owner, identifier) = patches::parse_repo_coordinate(repo_coordinate)?;
+    let announcement = Filter::new().kind(Kind::from(events::KIND_GIT_REPO_ANNOUNCEMENT));
+    let mut announcements = store.query(&[announcement.clone().identifier(identifier)])?;
+
+    let mut asked = HashSet::new();
+    loop {
+        let maintainers = repo::resolve_maintainers(repo_coordinate, &announcements)?;
+        let unknown: Vec<PublicKey> = maintainers
+            .keys
+            .iter()
+            .skip(1)
+            .filter(|key| asked.insert(**key))
+            .copied()
+            .collect();
+        if unknown.is_empty() {
+            return Ok(maintainers);
+        }
+        announcements.extend(store.query(&[announcement.clone().authors(unknown)])?);
+    }
+}
+
+/// Flag the PRs opened by the owner or a trusted maintainer
+fn mark_maintainers(prs: &mut [PullRequest], maintainers: &repo::Maintainers) {
+    for pr in prs {
+        pr.by_maintainer = maintainers.is_maintainer(&pr.author);
+    }
+}
+
+/// Turn PR, update and revision events into pull requests
+///
+/// Updates replace the description of the PR they reply to. Revisions are
+/// grouped under the original PR, which then reflects the latest version.
+/// Only revisions by the PR author or the repository owner are accepted.
+/// PRs are returned newest first, events created in the same second are
+/// ordered by ID so every run and relay gives the same result.
+pub fn build_pull_requests(
+    mut events: Vec<Event>,
+    repo_owner: Option<&str>,
+) -> Result<Vec<PullRequest>> {
+    // Originals must be known before the updates and revisions replying to them
+    events.sort_by_key(|event| (event.created_at, event.id));
+    // Root patches of series that have a PR event are already listed through it
+    let indexed: HashSet<EventId> = events
+        .iter()
+        .filter(|event| event.kind == KIND_PULL_REQUEST || event.kind == KIND_PULL_REQUEST_UPDATE)
+        .flat_map(|event| {
+            patch_event_ids(event)
+                .into_iter()
+                .chain(cover_letter_id(event))
+        })
+        .collect();
+    let events = events
+        .into_iter()
+        .filter(|event| event.kind != KIND_PATCH || !indexed.contains(&event.id));
+    let (originals, replies): (Vec<Event>, Vec<Event>) = events.partition(|event| {
+        (event.kind == KIND_PULL_REQUEST || is_root_patch(event)) && revision_of(event).is_none()
+    });
 
-    // Process events into pull requests
-    debug!("Processing {} events into pull requests", events.len());
     let mut prs: HashMap<EventId, PullRequest> = HashMap::new();
+    for event in &originals {
+        debug!("Processing event {} into PR", event.id);
+        let pr = if event.kind == KIND_PATCH {
+            root_patch_to_pull_request(event)
+        } else {
+            event_to_pull_request(event)?
+        };
+        prs.insert(event.id, pr);
+    }
+
+    let mut seen = HashSet::new();
+    for event in &replies {
+        let Some(original_id) = find_reply_to(event) else {
+            continue;
+        };
+        let Some(existing) = prs.get_mut(&original_id) else {
+            debug!(
+                "Ignoring reply {id} to unknown PR {original_id}",
+                id = event.id
+            );
+            continue;
+        };
+        if !seen.insert(event.id) || existing.created_at > event.created_at.as_u64() {
+            continue;
+        }
+
+        if let Some(version) = revision_of(event) {
+            let author = event.pubkey.to_hex();
+            if author != existing.author && Some(author.as_str()) != repo_owner {
+                debug!(
+                    "Ignoring revision {id} from unauthorized author",
+                    id = event.id
+                );
+                continue;
+            }
+            existing.revisions.push(PullRequestRevision {
+                id: event.id.to_hex(),
+                // Untagged revisions are numbered in the order they were published
+                version: version.unwrap_or(existing.revisions.len() as u32 + 2),
+                author,
+                created_at: event.created_at.as_u64(),
+                patches_count: series_patch_count(event),
+            });
+        }
+
+        // Update the existing PR, patch revisions carry a patch rather than a description
+        existing.updated_at = Some(event.created_at.as_u64());
+        if event.kind == KIND_PULL_REQUEST_UPDATE
+            || (event.kind == KIND_PULL_REQUEST && !event.content.is_empty())
+        {
+            existing.description = event.content.clone();
+        }
+        existing.status = PullRequestStatus::Updated;
+    }
+
+    let mut result: Vec<PullRequest> = prs.into_values().collect();
+    for pr in &mut result {
+        pr.revisions.sort_by(|a, b| {
+            (a.version, a.created_at, &a.id).cmp(&(b.version, b.created_at, &b.id))
+        });
+        if let Some(latest) = pr.revisions.last() {
+            pr.patches_count = latest.patches_count;
+        }
+    }
+    result.sort_by(|a, b| {
+        b.created_at
+            .cmp(&a.created_at)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+
+    Ok(result)
+}
+
+/// Version number of a revision event, if the event is a revision
+///
+/// The inner value is None when the revision doesn't declare its version.
+pub fn revision_of(event: &Event) -> Option<Option<u32>> {
+    if (event.kind != KIND_PULL_REQUEST && event.kind != KIND_PATCH)
+        || !tag_values(event, "t").contains(&patches::REVISION_HASHTAG)
+    {
+        return None;
+    }
+    Some(
+        get_tag_value(event, patches::REVISION_TAG)
+            .and_then(|version| version.parse().ok())
+            .filter(|version| *version > 1),
+    )
+}
+
+/// Filters for status, reaction and comment events referencing any of the PRs
+fn related_filters<'a>(prs: impl IntoIterator<Item = &'a PullRequest>) -> Vec<Filter> {
+    let pr_ids: Vec<EventId> = prs
+        .into_iter()
+        .filter_map(|pr| EventId::from_hex(&pr.id).ok())
+        .collect();
+
+    // Status events, reactions and replies point at the PR with a lowercase `e` tag
+    let mut kinds = vec![Kind::Reaction, Kind::Comment, Kind::TextNote];
+    kinds.extend(status_kinds());
+    let direct = Filter::new().kinds(kinds).events(pr_ids.clone());
+    // Nested NIP-22 comments only reference the PR through the uppercase `E` root tag
+    let rooted = Filter::new().kind(Kind::Comment).custom_tags(
+        SingleLetterTag::uppercase(Alphabet::E),
+        pr_ids.iter().map(|id| id.to_hex()),
+    );
+    vec![direct, rooted]
+}
+
+/// Fetch the status, reaction and comment events matching `filters`
+///
+/// With a sync `scope` only events since the last sync are fetched, see
+/// [`relay::fetch_events_synced`]. The `outbox` client, if any, is asked as
+/// well; its failures are only logged.
+async fn fetch_related_events(
+    network: &Network,
+    client: &Client,
+    outbox: Option<&Client>,
+    scope: Option<&str>,
+    filters: Vec<Filter>,
+    timeout: Duration,
+) -> Result<Vec<Event>> {
+    let fetch = |client, filters| async move {
+        match scope {
+            Some(scope) => {
+                relay::fetch_events_synced(network, client, scope, filters, timeout).await
+            }
+            None => relay::fetch_events(network, client, filters, timeout).await,
+        }
+    };
+    let mut related = fetch(client, filters.clone()).await?;
+    if let Some(outbox) = outbox {
+        match fetch(outbox, filters).await {
+            Ok(found) => related = relay::merge_events(related, found),
+            Err(e) => warn!("Failed to fetch related events from the authors' relays: {e}"),
+        }
+    }
+    Ok(related)
+}
+
+/// Apply status changes, reactions and comments to the PRs
+fn apply_related(
+    prs: &mut [PullRequest],
+    related: Vec<Event>,
+    min_pow: u8,
+    repo_owner: Option<&str>,
+    maintainers: &repo::Maintainers,
+) {
+    let related = filter_low_pow(related, min_pow);
+    apply_status_events(prs, &related, repo_owner);
+    apply_engagement(prs, &related);
+    apply_checks(prs, &related, maintainers);
+}
+
+/// Apply the latest valid NIP-34 status event to each PR
+///
+/// Only status events published by the PR author or the repository owner are
+/// considered, and the most recent one wins.
+pub fn apply_status_events(prs: &mut [PullRequest], events: &[Event], repo_owner: Option<&str>) {
+    for pr in prs.iter_mut() {
+        if let Some(latest) = status_timeline(pr, events, repo_owner).last() {
+            pr.status = latest.status;
+        }
+    }
+}
+
+/// Status changes of a PR published by its author or the repository owner, oldest first
+pub fn status_timeline(
+    pr: &PullRequest,
+    events: &[Event],
+    repo_owner: Option<&str>,
+) -> Vec<StatusChange> {
+    let mut seen = HashSet::new();
+    let mut timeline: Vec<(u64, EventId, StatusChange)> = Vec::new();
 
     for event in events {
-        debug!("Processing event {} into PR", event.id);
-        let pr = event_to_pull_request(&event)?;
+        let Some(status) = PullRequestStatus::from_status_kind(event.kind.as_u16()) else {
+            continue;
+        };
+        if !tag_values(event, "e").contains(&pr.id.as_str()) || !seen.insert(event.id) {
+            continue;
+        }
+        let author = event.pubkey.to_hex();
+        if author != pr.author && Some(author.as_str()) != repo_owner {
+            debug!(
+                "Ignoring status event {id} from unauthorized author",
+                id = event.id
+            );
+            continue;
+        }
 
-        // Check if this is an update to existing PR
-        let is_update = event.kind == KIND_PULL_REQUEST_UPDATE;
+        timeline.push((
+            event.created_at.as_u64(),
+            event.id,
+            StatusChange {
+                id: event.id.to_hex(),
+                status,
+                author,
+                created_at: event.created_at.as_u64(),
+                message: event.content.clone(),
+                commits: AppliedCommits::from_event(event),
+            },
+        ));
+    }
 
-        if is_update {
-            // Find the original PR this updates
-            if let Some(original_id) = find_reply_to(&event)
-                && let Some(existing) = prs.get_mut(&original_id)
-            {
-                // Update the existing PR
-                if existing.created_at < event.created_at.as_u64() {
-                    existing.updated_at = Some(event.created_at.as_u64());
-                    existing.description = pr.description;
-                    existing.status = PullRequestStatus::Updated;
-                }
+    // Ties are broken by event ID for deterministic results
+    timeline.sort_by_key(|(created_at, id, _)| (*created_at, *id));
+    timeline.into_iter().map(|(_, _, change)| change).collect()
+}
+
+/// Aggregate reaction, comment and review events into per-PR counts
+pub fn apply_engagement(prs: &mut [PullRequest], events: &[Event]) {
+    let index: HashMap<String, usize> = prs
+        .iter()
+        .enumerate()
+        .map(|(i, pr)| (pr.id.clone(), i))
+        .collect();
+    // Relays commonly return the same event more than once
+    let mut seen = HashSet::new();
+
+    for event in events {
+        if !seen.insert(event.id) {
+            continue;
+        }
+
+        if event.kind == Kind::Reaction {
+            // NIP-25: the last `e` tag is the reacted-to event
+            let target = tag_values(event, "e").last().and_then(|id| index.get(*id));
+            if let Some(&i) = target {
+                *prs[i]
+                    .reactions
+                    .entry(normalize_reaction(&event.content))
+                    .or_insert(0) += 1;
+            }
+        } else if event.kind == Kind::Comment || event.kind == Kind::TextNote {
+            let target = tag_values(event, "E")
+                .into_iter()
+                .chain(tag_values(event, "e"))
+                .find_map(|id| index.get(id));
+            if let Some(&i) = target {
+                prs[i].comments_count += 1;
+            }
+        }
+    }
+
+    for pr in prs.iter_mut() {
+        pr.reviews = reviews::summarize_reviews(events, &pr.id);
+    }
+}
+
+/// Summarize the CI checks on each PR reported by the maintainers or declared CI keys
+pub fn apply_checks(prs: &mut [PullRequest], events: &[Event], maintainers: &repo::Maintainers) {
+    let reporters = maintainers.check_reporters();
+    for pr in prs.iter_mut() {
+        pr.checks = ci::summarize_checks(events, &pr.id, &reporters);
+    }
+}
+
+/// Map NIP-25 reaction content to a display emoji
+fn normalize_reaction(content: &str) -> String {
+    match content.trim() {
+        "" | "+" => "👍".to_string(),
+        "-" => "👎".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Format compact engagement indicators (e.g. "💬 4 👍 2")
+pub fn format_engagement(pr: &PullRequest) -> String {
+    let mut parts = Vec::new();
+
+    if pr.comments_count > 0 {
+        parts.push(format!(
+            "💬 {comments_count}",
+            comments_count = pr.comments_count
+        ));
+    }
+
+    // Most popular reactions first, ties broken by emoji for stable output
+    let mut reactions: Vec<(&String, &usize)> = pr.reactions.iter().collect();
+    reactions.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    for (emoji, count) in reactions {
+        parts.push(format!("{emoji} {count}"));
+    }
+
+    parts.join(" ")
+}
+
+/// Remove PR, patch and comment events that don't meet the proof-of-work requirement
+///
+/// Status events and reactions are kept since they are either authorized by
+/// author checks or carry no content.
+pub fn filter_low_pow(events: Vec<Event>, min_pow: u8) -> Vec<Event> {
+    if min_pow == 0 {
+        return events;
+    }
+
+    let before = events.len();
+    let kept: Vec<Event> = events
+        .into_iter()
+        .filter(|event| {
+            let requires_pow = event.kind == KIND_PULL_REQUEST
+                || event.kind == KIND_PULL_REQUEST_UPDATE
+                || event.kind == KIND_PATCH
+                || event.kind == Kind::Comment
+                || event.kind == Kind::TextNote;
+            !requires_pow || event.check_pow(min_pow)
+        })
+        .collect();
+
+    if kept.len() < before {
+        info!(
+            "Ignored {count} event(s) below proof-of-work difficulty {min_pow}",
+            count = before - kept.len()
+        );
+    }
+    kept
+}
+
+/// Publish a status event for a pull request
+pub async fn set_pull_request_status(
+    network: &Network,
+    signer: &dyn Signer,
+    pr_event_id: &str,
+    status: PullRequestStatus,
+    repo_coordinate: &str,
+    relays: &[String],
+    message: &str,
+) -> Result<BroadcastResult> {
+    publish_status(
+        network,
+        signer,
+        pr_event_id,
+        status,
+        &AppliedCommits::default(),
+        repo_coordinate,
+        relays,
+        message,
+    )
+    .await
+}
+
+/// Publish a merged status for a pull request, tagged with the commits it landed as
+pub async fn publish_applied_status(
+    network: &Network,
+    signer: &dyn Signer,
+    pr_event_id: &str,
+    commits: &AppliedCommits,
+    repo_coordinate: &str,
+    relays: &[String],
+    message: &str,
+) -> Result<BroadcastResult> {
+    publish_status(
+        network,
+        signer,
+        pr_event_id,
+        PullRequestStatus::Merged,
+        commits,
+        repo_coordinate,
+        relays,
+        message,
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn publish_status(
+    network: &Network,
+    signer: &dyn Signer,
+    pr_event_id: &str,
+    status: PullRequestStatus,
+    commits: &AppliedCommits,
+    repo_coordinate: &str,
+    relays: &[String],
+    message: &str,
+) -> Result<BroadcastResult> {
+    let status_kind = status
+        .status_kind()
+        .with_context(|| format!("Status '{status}' cannot be published"))?;
+    let pr_id = EventId::from_hex(pr_event_id)
+        .with_context(|| format!("Invalid event ID: {pr_event_id}"))?;
+
+    let client = relay::connect(network, relays, Some(signer)).await?;
+
+    // Fetch the PR so the status can notify its author
+    let filter = Filter::new().id(pr_id);
+    let found = relay::fetch_events(network, &client, vec![filter], Duration::from_secs(5)).await?;
+    let Some(pr_event) = found.first() else {
+        return Err(GitsmithError::EventNotFound(format!(
+            "Pull request {pr_event_id}"
+        )));
+    };
+    let author: PublicKey = pr_event.pubkey;
+
+    let builder = events::status_builder(status_kind, &pr_id, &author, repo_coordinate, message)?
+        .tags(commits.tags()?);
+    let event = signer::sign(signer, builder).await?;
+
+    relay::publish_events(&client, &[event]).await
+}
+
+/// Fetch a single pull request with its status, reactions and comments
+pub async fn fetch_pull_request(
+    network: &Network,
+    pr_event_id: &str,
+    repo_coordinate: &str,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<PullRequestDetails> {
+    let pr_id = EventId::from_hex(pr_event_id)
+        .with_context(|| format!("Invalid event ID: {pr_event_id}"))?;
+    let client = relay::connect(network, relays, None).await?;
+
+    let found =
+        relay::fetch_events(network, &client, vec![Filter::new().id(pr_id)], timeout).await?;
+    let event = pull_request_event(found, pr_event_id)?;
+    let mut prs = vec![pull_request_from_event(&event)?];
+    let repo_owner = repo_coordinate.split(':').nth(1);
+    let mut authors = vec![event.pubkey];
+    authors.extend(repo_owner.and_then(|owner| PublicKey::from_hex(owner).ok()));
+    let outbox = relay_lists::connect_outbox(network, &client, authors, relays, timeout).await;
+    // Only the maintainers' and declared CI keys' check reports count
+    let maintainers =
+        match fetch_maintainers(network, Some(&client), repo_coordinate, timeout).await {
+            Ok(maintainers) => maintainers,
+            Err(e) => {
+                warn!("Failed to fetch the maintainers' announcements: {e}");
+                repo::resolve_maintainers(repo_coordinate, &[])?
             }
+        };
+    let related = fetch_related_events(
+        network,
+        &client,
+        outbox.as_ref(),
+        None,
+        related_filters(&prs),
+        timeout,
+    )
+    .await?;
+
+    let (cover_letter, patches, manifest) = if event.kind == KIND_PATCH {
+        let (cover_letter, mut patches) = fetch_thread(network, &client, &event, timeout).await?;
+        if let Err(e) = join_patch_parts(network, &client, &mut patches, timeout).await {
+            warn!("{e}");
+        }
+        (
+            cover_letter.map(|cover_letter| cover_letter.content),
+            patches.iter().map(summarize_patch).collect(),
+            Vec::new(),
+        )
+    } else {
+        fetch_indexed_patches(network, &client, &event, timeout).await?
+    };
+
+    Ok(pull_request_details(
+        prs.remove(0),
+        &related,
+        repo_owner,
+        &maintainers,
+        cover_letter,
+        patches,
+        manifest,
+    ))
+}
+
+/// A single pull request as [`fetch_pull_request`] returns it, built from the
+/// events of a cache only
+pub fn cached_pull_request(
+    store: &dyn cache::CacheStore,
+    pr_event_id: &str,
+    repo_coordinate: &str,
+) -> Result<PullRequestDetails> {
+    let pr_id = EventId::from_hex(pr_event_id)
+        .with_context(|| format!("Invalid event ID: {pr_event_id}"))?;
+    let found = store.query(&[Filter::new().id(pr_id)])?;
+    let event = pull_request_event(found, pr_event_id)?;
+    let pr = pull_request_from_event(&event)?;
+    let repo_owner = repo_coordinate.split(':').nth(1);
+    let related = store.query(&related_filters([&pr]))?;
+    let maintainers = cached_maintainers(store, repo_coordinate)?;
+
+    let mut series = if event.kind == KIND_PATCH {
+        let replies = store.query(&[Filter::new().kind(KIND_PATCH).event(event.id)])?;
+        split_cover_letter(&event, order_thread(&event, replies))
+    } else {
+        let wanted = indexed_event_ids(&event);
+        let mut cached: HashMap<EventId, Event> = if wanted.is_empty() {
+            HashMap::new()
         } else {
-            // New PR
-            prs.insert(event.id, pr);
+            store
+                .query(&[Filter::new()
+                    .kinds([KIND_PATCH, KIND_SERIES_MANIFEST])
+                    .ids(wanted)])?
+                .into_iter()
+                .map(|e| (e.id, e))
+                .collect()
+        };
+        take_indexed_patches(&event, &mut cached)
+    };
+    let wanted: Vec<EventId> = series.patches.iter().flat_map(part_ids).collect();
+    if !wanted.is_empty() {
+        let parts = store
+            .query(&[Filter::new().kind(patches::KIND_PATCH_PART).ids(wanted)])?
+            .into_iter()
+            .map(|part| (part.id, part))
+            .collect();
+        if let Err(e) = join_parts(&mut series.patches, &parts) {
+            warn!("{e}");
         }
     }
 
-    // Convert to vector and sort by creation time
-    let mut result: Vec<PullRequest> = prs.into_values().collect();
-    result.sort_by_key(|pr| std::cmp::Reverse(pr.created_at));
+    Ok(pull_request_details(
+        pr,
+        &related,
+        repo_owner,
+        &maintainers,
+        series.cover_letter.map(|cover_letter| cover_letter.content),
+        series.patches.iter().map(summarize_patch).collect(),
+        series.manifest,
+    ))
+}
 
-    info!(
-        "Returning {} unique pull requests after processing",
-        result.len()
+/// The PR, update or root patch event among `found`
+pub(crate) fn pull_request_event(found: Vec<Event>, pr_event_id: &str) -> Result<Event> {
+    let Some(event) = found.into_iter().next() else {
+        return Err(GitsmithError::EventNotFound(format!(
+            "Pull request {pr_event_id}"
+        )));
+    };
+    if event.kind != KIND_PULL_REQUEST
+        && event.kind != KIND_PULL_REQUEST_UPDATE
+        && !is_root_patch(&event)
+    {
+        return Err(not_a_pull_request(pr_event_id, event.kind));
+    }
+    Ok(event)
+}
+
+fn pull_request_from_event(event: &Event) -> Result<PullRequest> {
+    if event.kind == KIND_PATCH {
+        Ok(root_patch_to_pull_request(event))
+    } else {
+        event_to_pull_request(event)
+    }
+}
+
+/// Apply the related events to the PR and collect its details
+fn pull_request_details(
+    pr: PullRequest,
+    related: &[Event],
+    repo_owner: Option<&str>,
+    maintainers: &repo::Maintainers,
+    cover_letter: Option<String>,
+    patches: Vec<PatchSummary>,
+    manifest: Vec<ManifestEntry>,
+) -> PullRequestDetails {
+    let mut prs = [pr];
+    apply_status_events(&mut prs, related, repo_owner);
+    apply_engagement(&mut prs, related);
+    apply_checks(&mut prs, related, maintainers);
+    let [pr] = prs;
+
+    let comments = related
+        .iter()
+        .filter(|e| e.kind == Kind::Comment || e.kind == Kind::TextNote)
+        .map(comments::parse_comment_event)
+        .collect();
+
+    PullRequestDetails {
+        status_history: status_timeline(&pr, related, repo_owner),
+        pr,
+        patches,
+        cover_letter,
+        manifest,
+        comments,
+    }
+}
+
+/// Patch events of a series, before they are summarized
+struct SeriesEvents {
+    cover_letter: Option<Event>,
+    patches: Vec<Event>,
+    manifest: Vec<ManifestEntry>,
+}
+
+/// IDs of the patches, cover letter and manifest a PR event lists
+fn indexed_event_ids(event: &Event) -> Vec<EventId> {
+    patch_event_ids(event)
+        .into_iter()
+        .chain(cover_letter_id(event))
+        .chain(manifest_id(event))
+        .collect()
+}
+
+/// Take the events a PR event lists out of `fetched`, patches in series order
+fn take_indexed_patches(event: &Event, fetched: &mut HashMap<EventId, Event>) -> SeriesEvents {
+    let cover_letter = cover_letter_id(event).and_then(|id| fetched.remove(&id));
+    let manifest = manifest_id(event)
+        .and_then(|id| fetched.remove(&id))
+        .map(|manifest| manifest_entries(&manifest))
+        .unwrap_or_default();
+    let patches = patch_event_ids(event)
+        .iter()
+        .filter_map(|id| {
+            let patch = fetched.remove(id);
+            if patch.is_none() {
+                warn!("Patch event {id} not found on any relay");
+            }
+            patch
+        })
+        .collect();
+    SeriesEvents {
+        cover_letter,
+        patches,
+        manifest,
+    }
+}
+
+/// Cover letter, patch summaries and manifest of a series listed by a PR event
+async fn fetch_indexed_patches(
+    network: &Network,
+    client: &Client,
+    event: &Event,
+    timeout: Duration,
+) -> Result<(Option<String>, Vec<PatchSummary>, Vec<ManifestEntry>)> {
+    let wanted = indexed_event_ids(event);
+    let mut fetched: HashMap<EventId, Event> = if wanted.is_empty() {
+        HashMap::new()
+    } else {
+        let filter = Filter::new()
+            .kinds([KIND_PATCH, KIND_SERIES_MANIFEST])
+            .ids(wanted);
+        relay::fetch_events(network, client, vec![filter], timeout)
+            .await?
+            .into_iter()
+            .map(|e| (e.id, e))
+            .collect()
+    };
+    let SeriesEvents {
+        cover_letter,
+        mut patches,
+        manifest,
+    } = take_indexed_patches(event, &mut fetched);
+    // A patch missing parts is still summarized from the parts that were found
+    if let Err(e) = join_patch_parts(network, client, &mut patches, timeout).await {
+        warn!("{e}");
+    }
+    let patches = patches.iter().map(summarize_patch).collect();
+
+    Ok((
+        cover_letter.map(|cover_letter| cover_letter.content),
+        patches,
+        manifest,
+    ))
+}
+
+/// Fetch the patches threaded under a root patch, in series order
+///
+/// A cover letter root is returned separately from the patches.
+async fn fetch_thread(
+    network: &Network,
+    client: &Client,
+    root: &Event,
+    timeout: Duration,
+) -> Result<(Option<Event>, Vec<Event>)> {
+    let filter = Filter::new().kind(KIND_PATCH).event(root.id);
+    let replies = relay::fetch_events(network, client, vec![filter], timeout).await?;
+    let series = split_cover_letter(root, order_thread(root, replies));
+    Ok((series.cover_letter, series.patches))
+}
+
+/// Take the cover letter root off a thread ordered by [`order_thread`]
+fn split_cover_letter(root: &Event, mut series: Vec<Event>) -> SeriesEvents {
+    let cover_letter = if tag_values(root, "t").contains(&patches::COVER_LETTER_MARKER) {
+        Some(series.remove(0))
+    } else {
+        None
+    };
+    SeriesEvents {
+        cover_letter,
+        patches: series,
+        manifest: Vec::new(),
+    }
+}
+
+/// Order the events of a series published as a reply thread, root first
+///
+/// Each patch follows the event its `reply` tag points at, or the root when it
+/// only has a `root` tag. Patches that can't be placed go last, oldest first.
+pub fn order_thread(root: &Event, replies: Vec<Event>) -> Vec<Event> {
+    let mut remaining: Vec<Event> = replies
+        .into_iter()
+        .filter(|event| event.kind == KIND_PATCH && event.id != root.id)
+        .collect();
+    remaining.sort_by_key(|event| (event.created_at, event.id));
+    remaining.dedup_by_key(|event| event.id);
+
+    let mut ordered = vec![root.clone()];
+    while let Some(last) = ordered.last().map(|event| event.id) {
+        let parent = |event: &Event| {
+            marked_event(event, "reply")
+                .or_else(|| marked_event(event, "root"))
+                .or_else(|| event.tags.event_ids().next().copied())
+        };
+        let Some(next) = remaining
+            .iter()
+            .position(|event| parent(event) == Some(last))
+        else {
+            break;
+        };
+        ordered.push(remaining.remove(next));
+    }
+    ordered.extend(remaining);
+
+    ordered
+}
+
+/// Whether an event is the first patch (or cover letter) of a series (NIP-34 `t` root tag)
+pub fn is_root_patch(event: &Event) -> bool {
+    event.kind == KIND_PATCH
+        && tag_values(event, "t")
+            .iter()
+            .any(|t| *t == patches::ROOT_HASHTAG || *t == patches::REVISION_HASHTAG)
+}
+
+/// A PR for a series that was published as patches only, without a PR event
+fn root_patch_to_pull_request(root: &Event) -> PullRequest {
+    let parsed = apply::parse_patch(&root.content).ok();
+
+    PullRequest {
+        id: root.id.to_string(),
+        title: parsed
+            .as_ref()
+            .map(|patch| patch.subject.clone())
+            .unwrap_or_else(|| "Untitled PR".to_string()),
+        description: parsed.map(|patch| patch.body).unwrap_or_default(),
+        author: root.pubkey.to_string(),
+        author_name: None,
+        created_at: root.created_at.as_u64(),
+        updated_at: None,
+        patches_count: series_patch_count(root),
+        root_commit: None,
+        status: PullRequestStatus::Open,
+        comments_count: 0,
+        reactions: BTreeMap::new(),
+        revisions: Vec::new(),
+        by_maintainer: false,
+        reviews: ReviewSummary::default(),
+        checks: CheckSummary::default(),
+        labels: patches::labels(root),
+        merged_detected: false,
+    }
+}
+
+/// Number of patches in the series a PR event lists or a root patch starts
+///
+/// Root patches only announce the size in their subject (`[PATCH 1/3]`).
+fn series_patch_count(event: &Event) -> usize {
+    if event.kind != KIND_PATCH {
+        return patch_event_ids(event).len();
+    }
+    event
+        .content
+        .lines()
+        .find_map(|line| line.strip_prefix("Subject: ["))
+        .and_then(|subject| subject.split_once(']'))
+        .and_then(|(marker, _)| marker.rsplit_once('/'))
+        .and_then(|(_, total)| total.trim().parse().ok())
+        .unwrap_or(1)
+}
+
+/// Summarize a patch event; unparseable patches keep their raw content only
+pub fn summarize_patch(event: &Event) -> PatchSummary {
+    let parsed = apply::parse_patch(&event.content).ok();
+    let subject = parsed
+        .as_ref()
+        .map(|patch| patch.subject.clone())
+        .or_else(|| get_tag_value(event, "subject"))
+        .unwrap_or_else(|| "Untitled patch".to_string());
+    let git_author = parsed
+        .as_ref()
+        .map(|patch| {
+            format!(
+                "{name} <{email}>",
+                name = patch.author_name,
+                email = patch.author_email
+            )
+        })
+        .unwrap_or_default();
+    let diffstat = parsed
+        .as_ref()
+        .map(|patch| apply::diffstat(&patch.diff))
+        .unwrap_or_default();
+
+    PatchSummary {
+        id: event.id.to_hex(),
+        subject,
+        git_author,
+        created_at: event.created_at.as_u64(),
+        diffstat,
+        content: event.content.clone(),
+        commit: CommitMetadata::from_event(event),
+    }
+}
+
+/// Fetch a pull request and all of its patch events from relays
+///
+/// A bare patch event ID is accepted as well and yields a single-patch series.
+pub async fn fetch_patch_series(
+    network: &Network,
+    pr_event_id: &str,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<PatchSeries> {
+    let client = relay::connect(network, relays, None).await?;
+    fetch_patch_series_with(network, &client, pr_event_id, timeout).await
+}
+
+/// [`fetch_patch_series`] through an already connected client
+pub(crate) async fn fetch_patch_series_with(
+    network: &Network,
+    client: &Client,
+    pr_event_id: &str,
+    timeout: Duration,
+) -> Result<PatchSeries> {
+    let pr_id = EventId::from_hex(pr_event_id)
+        .with_context(|| format!("Invalid event ID: {pr_event_id}"))?;
+
+    let found =
+        relay::fetch_events(network, client, vec![Filter::new().id(pr_id)], timeout).await?;
+    let Some(pr) = found.into_iter().next() else {
+        return Err(GitsmithError::EventNotFound(format!(
+            "Pull request {pr_event_id}"
+        )));
+    };
+
+    if is_root_patch(&pr) {
+        let (_, mut patches) = fetch_thread(network, client, &pr, timeout).await?;
+        join_patch_parts(network, client, &mut patches, timeout).await?;
+        return Ok(PatchSeries { pr, patches });
+    }
+    if pr.kind == KIND_PATCH {
+        let mut patches = vec![pr.clone()];
+        join_patch_parts(network, client, &mut patches, timeout).await?;
+        return Ok(PatchSeries { patches, pr });
+    }
+    if pr.kind != KIND_PULL_REQUEST && pr.kind != KIND_PULL_REQUEST_UPDATE {
+        return Err(not_a_pull_request(pr_event_id, pr.kind));
+    }
+
+    let patch_ids = patch_event_ids(&pr);
+    ensure!(
+        !patch_ids.is_empty(),
+        "Pull request {pr_event_id} does not reference any patches"
     );
-    Ok(result)
+    debug!("Fetching {count} patch event(s)", count = patch_ids.len());
+
+    let filter = Filter::new()
+        .kinds([KIND_PATCH, KIND_SERIES_MANIFEST])
+        .ids(patch_ids.iter().copied().chain(manifest_id(&pr)));
+    let mut fetched: HashMap<EventId, Event> =
+        relay::fetch_events(network, client, vec![filter], timeout)
+            .await?
+            .into_iter()
+            .map(|e| (e.id, e))
+            .collect();
+
+    // The manifest tells which commits the missing patches were
+    let manifest = manifest_id(&pr)
+        .and_then(|id| fetched.remove(&id))
+        .map(|manifest| manifest_entries(&manifest))
+        .unwrap_or_default();
+    let missing: Vec<String> = patch_ids
+        .iter()
+        .filter(|id| !fetched.contains_key(id))
+        .map(|id| {
+            let entry = manifest.iter().find(|entry| entry.event_id == id.to_hex());
+            match entry.and_then(|entry| entry.commit.as_ref().map(|commit| (commit, entry))) {
+                Some((commit, entry)) => {
+                    format!("{id} ({commit:.7} {subject})", subject = entry.subject)
+                }
+                None => id.to_hex(),
+            }
+        })
+        .collect();
+    ensure!(
+        missing.is_empty(),
+        "Missing patch event(s) on relays: {missing}",
+        missing = missing.join(", ")
+    );
+
+    let mut patches: Vec<Event> = patch_ids
+        .iter()
+        .filter_map(|id| fetched.remove(id))
+        .collect();
+    join_patch_parts(network, client, &mut patches, timeout).await?;
+
+    Ok(PatchSeries { pr, patches })
 }
 
-/// Convert an event to a PullRequest
-fn event_to_pull_request(event: &Event) -> Result<PullRequest> {
-    let title = get_tag_value(event, "subject").unwrap_or_else(|| "Untitled PR".to_string());
+/// IDs of the parts a split patch continues in, in order
+pub fn part_ids(event: &Event) -> Vec<EventId> {
+    event
+        .tags
+        .iter()
+        .map(|tag| tag.as_slice())
+        .filter(|tag| tag.len() > 3 && tag[0] == "e" && tag[3] == patches::PART_MARKER)
+        .filter_map(|tag| EventId::from_hex(&tag[1]).ok())
+        .collect()
+}
 
-    let root_commit = get_tag_value(event, "c");
+/// Put split patches back together from their part events
+///
+/// The content of a split patch event is replaced by the whole patch, so it
+/// no longer matches the event's signature and must not be republished.
+pub async fn join_patch_parts(
+    network: &Network,
+    client: &Client,
+    patches: &mut [Event],
+    timeout: Duration,
+) -> Result<()> {
+    let wanted: Vec<EventId> = patches.iter().flat_map(part_ids).collect();
+    if wanted.is_empty() {
+        return Ok(());
+    }
+    debug!("Fetching {count} patch part(s)", count = wanted.len());
+    let filter = Filter::new().kind(patches::KIND_PATCH_PART).ids(wanted);
+    let parts: HashMap<EventId, Event> =
+        relay::fetch_events(network, client, vec![filter], timeout)
+            .await?
+            .into_iter()
+            .map(|part| (part.id, part))
+            .collect();
+    join_parts(patches, &parts)
+}
+
+/// Append the parts to the patches they continue
+fn join_parts(patches: &mut [Event], parts: &HashMap<EventId, Event>) -> Result<()> {
+    for patch in patches.iter_mut() {
+        let ids = part_ids(patch);
+        let missing = ids.iter().filter(|id| !parts.contains_key(id)).count();
+        ensure!(
+            missing == 0,
+            "{missing} of {total} part(s) of patch {id} not found on relays",
+            total = ids.len() + 1,
+            id = patch.id
+        );
+        for id in &ids {
+            if let Some(part) = parts.get(id) {
+                patch.content.push_str(&part.content);
+            }
+        }
+    }
+    Ok(())
+}
 
-    // Count patch references
-    let patches_count = event
+/// Patch event IDs referenced by a PR event, in series order
+pub fn patch_event_ids(event: &Event) -> Vec<EventId> {
+    event
         .tags
         .iter()
         .filter(|tag| {
-            tag.as_slice().len() > 1
-                && tag.as_slice()[0] == "e"
-                && tag.as_slice().get(2).is_some_and(|s| s == "patch")
+            tag.as_slice().len() > 2 && tag.as_slice()[0] == "e" && tag.as_slice()[2] == "patch"
         })
-        .count();
+        .filter_map(|tag| EventId::from_hex(&tag.as_slice()[1]).ok())
+        .collect()
+}
+
+/// ID of the cover letter referenced by a PR event (`e` tag with the cover-letter marker)
+pub fn cover_letter_id(event: &Event) -> Option<EventId> {
+    event
+        .tags
+        .iter()
+        .map(|tag| tag.as_slice())
+        .find(|tag| tag.len() > 2 && tag[0] == "e" && tag[2] == patches::COVER_LETTER_MARKER)
+        .and_then(|tag| EventId::from_hex(&tag[1]).ok())
+}
+
+/// ID of the series manifest referenced by a PR event (`e` tag with the manifest marker)
+pub fn manifest_id(event: &Event) -> Option<EventId> {
+    event
+        .tags
+        .iter()
+        .map(|tag| tag.as_slice())
+        .find(|tag| tag.len() > 2 && tag[0] == "e" && tag[2] == patches::MANIFEST_MARKER)
+        .and_then(|tag| EventId::from_hex(&tag[1]).ok())
+}
+
+/// Patches listed by a series manifest event, in series order
+pub fn manifest_entries(manifest: &Event) -> Vec<ManifestEntry> {
+    manifest
+        .tags
+        .iter()
+        .filter_map(|tag| ManifestEntry::from_tag(tag.as_slice()))
+        .collect()
+}
+
+/// Summary of every patch of a PR, from its manifest when it has one
+///
+/// Series without a manifest (published by older clients or ngit) are
+/// summarized from their patches.
+pub async fn fetch_series_manifest(
+    network: &Network,
+    pr_event_id: &str,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<Vec<ManifestEntry>> {
+    let pr_id = EventId::from_hex(pr_event_id)
+        .with_context(|| format!("Invalid event ID: {pr_event_id}"))?;
+    let client = relay::connect(network, relays, None).await?;
+
+    let found =
+        relay::fetch_events(network, &client, vec![Filter::new().id(pr_id)], timeout).await?;
+    let Some(pr) = found.first() else {
+        return Err(GitsmithError::EventNotFound(format!(
+            "Pull request {pr_event_id}"
+        )));
+    };
+    if let Some(id) = manifest_id(pr) {
+        let filter = Filter::new().kind(KIND_SERIES_MANIFEST).id(id);
+        let found = relay::fetch_events(network, &client, vec![filter], timeout).await?;
+        if let Some(manifest) = found.first() {
+            return Ok(manifest_entries(manifest));
+        }
+        warn!("Series manifest {id} not found on any relay, summarizing the patches instead");
+    }
+
+    let series = fetch_patch_series(network, pr_event_id, relays, timeout).await?;
+    Ok(series
+        .patches
+        .iter()
+        .map(|patch| ManifestEntry::new(patch.id, &patch.content, None))
+        .collect())
+}
+
+/// Convert an event to a PullRequest
+fn event_to_pull_request(event: &Event) -> Result<PullRequest> {
+    let title = get_tag_value(event, "subject").unwrap_or_else(|| "Untitled PR".to_string());
+
+    let root_commit = get_tag_value(event, "c");
+
+    let patches_count = patch_event_ids(event).len();
 
     let status = if event.kind == KIND_PULL_REQUEST_UPDATE {
         PullRequestStatus::Updated
@@ -184,11 +2260,20 @@ fn event_to_pull_request(event: &Event) -> Result<PullRequest> {
         title,
         description: event.content.clone(),
         author: event.pubkey.to_string(),
+        author_name: None,
         created_at: event.created_at.as_u64(),
         updated_at: None,
         patches_count,
         root_commit,
         status,
+        comments_count: 0,
+        reactions: BTreeMap::new(),
+        revisions: Vec::new(),
+        by_maintainer: false,
+        reviews: ReviewSummary::default(),
+        checks: CheckSummary::default(),
+        labels: patches::labels(event),
+        merged_detected: false,
     })
 }
 
@@ -202,52 +2287,271 @@ fn get_tag_value(event: &Event, tag_name: &str) -> Option<String> {
         .map(|s| s.to_string())
 }
 
+fn not_a_pull_request(event_id: &str, kind: Kind) -> GitsmithError {
+    GitsmithError::protocol(format!(
+        "Event {event_id} is not a pull request (kind {kind})"
+    ))
+}
+
 /// Find the event ID this event is replying to
 fn find_reply_to(event: &Event) -> Option<EventId> {
+    marked_event(event, "reply")
+}
+
+/// Target of the `e` tag with the given NIP-10 marker (`root` or `reply`)
+fn marked_event(event: &Event, marker: &str) -> Option<EventId> {
     event
         .tags
         .iter()
         .find(|tag| {
             tag.as_slice().len() > 1
                 && tag.as_slice()[0] == "e"
-                && tag.as_slice().get(3).is_some_and(|s| s == "reply")
+                && tag.as_slice().get(3).is_some_and(|s| s == marker)
         })
         .and_then(|tag| tag.as_slice().get(1))
         .and_then(|s| s.parse().ok())
 }
 
+/// Author's display name with a short public key, or just the short key
+fn format_author(pr: &PullRequest) -> String {
+    let short = &pr.author[0..16.min(pr.author.len())];
+    let author = match &pr.author_name {
+        Some(name) => format!("{name} ({short}...)"),
+        None => format!("{short}..."),
+    };
+    if pr.by_maintainer {
+        format!("{author} [maintainer]")
+    } else {
+        author
+    }
+}
+
+/// Fill in `author_name` from display names keyed by hex public key
+pub fn set_author_names(prs: &mut [PullRequest], names: &HashMap<String, String>) {
+    for pr in prs {
+        pr.author_name = names.get(&pr.author).cloned();
+    }
+}
+
 /// Format a pull request for display
-pub fn format_pull_request(pr: &PullRequest) -> String {
+pub fn format_pull_request(pr: &PullRequest, time: &TimeFormat) -> String {
     let mut output = String::new();
 
     output.push_str(&format!("Title: {title}\n", title = pr.title));
+    output.push_str(&format!("Author: {author}\n", author = format_author(pr)));
+    if pr.merged_detected {
+        output.push_str(&format!(
+            "Status: {status} (detected)\n",
+            status = pr.status
+        ));
+    } else {
+        output.push_str(&format!("Status: {status}\n", status = pr.status));
+    }
     output.push_str(&format!(
-        "Author: {author}...\n",
-        author = &pr.author[0..16]
+        "Created: {created_at}\n",
+        created_at = format_timestamp(time, pr.created_at)
     ));
-    output.push_str(&format!("Status: {status}\n", status = pr.status));
     output.push_str(&format!(
         "Patches: {patches_count}\n",
         patches_count = pr.patches_count
     ));
+    if let Some(latest) = pr.revisions.last() {
+        output.push_str(&format!(
+            "Version: v{version} ({id}...)\n",
+            version = latest.version,
+            id = &latest.id[0..8.min(latest.id.len())]
+        ));
+    }
+    if !pr.labels.is_empty() {
+        output.push_str(&format!(
+            "Labels: {labels}\n",
+            labels = pr.labels.join(", ")
+        ));
+    }
+
+    if let Some(commit) = &pr.root_commit {
+        output.push_str(&format!(
+            "Root: {commit}...\n",
+            commit = &commit[0..8.min(commit.len())]
+        ));
+    }
 
+    let engagement = format_engagement(pr);
+    if !engagement.is_empty() {
+        output.push_str(&format!("Activity: {engagement}\n"));
+    }
+    if !pr.reviews.is_empty() {
+        output.push_str(&format!(
+            "Reviews: {reviews}\n",
+            reviews = reviews::format_reviews(&pr.reviews)
+        ));
+    }
+    if !pr.checks.is_empty() {
+        output.push_str(&format!(
+            "Checks: {checks}\n",
+            checks = ci::format_checks(&pr.checks)
+        ));
+    }
+
+    if !pr.description.is_empty() {
+        output.push_str(&format!("\n{description}\n", description = pr.description));
+    }
+
+    output
+}
+
+/// Format a pull request with its patches and status history for display
+pub fn format_pull_request_details(details: &PullRequestDetails, time: &TimeFormat) -> String {
+    let pr = &details.pr;
+    let mut output = String::new();
+
+    output.push_str(&format!("Title: {title}\n", title = pr.title));
+    output.push_str(&format!("ID: {id}\n", id = pr.id));
+    let author = npub(&pr.author);
+    match &pr.author_name {
+        Some(name) => output.push_str(&format!("Author: {name} ({author})\n")),
+        None => output.push_str(&format!("Author: {author}\n")),
+    }
+    output.push_str(&format!("Status: {status}\n", status = pr.status));
+    output.push_str(&format!(
+        "Created: {created_at}\n",
+        created_at = format_timestamp(time, pr.created_at)
+    ));
     if let Some(commit) = &pr.root_commit {
         output.push_str(&format!(
             "Root: {commit}...\n",
             commit = &commit[0..8.min(commit.len())]
         ));
     }
+    let engagement = format_engagement(pr);
+    if !engagement.is_empty() {
+        output.push_str(&format!("Activity: {engagement}\n"));
+    }
+    if !pr.reviews.is_empty() {
+        output.push_str(&format!(
+            "Reviews: {reviews}\n",
+            reviews = reviews::format_reviews(&pr.reviews)
+        ));
+    }
+    if !pr.checks.is_empty() {
+        output.push_str(&format!(
+            "Checks: {checks}\n",
+            checks = ci::format_checks(&pr.checks)
+        ));
+    }
 
     if !pr.description.is_empty() {
         output.push_str(&format!("\n{description}\n", description = pr.description));
     }
 
+    if let Some(cover_letter) = &details.cover_letter {
+        output.push_str(&format!(
+            "\nCover letter:\n{body}\n",
+            body = patches::cover_letter_body(cover_letter)
+        ));
+    }
+
+    let total = details.patches.len();
+    output.push_str(&format!("\nPatches ({total}):\n"));
+    for (i, patch) in details.patches.iter().enumerate() {
+        let stat = &patch.diffstat;
+        output.push_str(&format!(
+            "  [{n}/{total}] {subject} ({files} file(s), +{insertions} -{deletions})\n",
+            n = i + 1,
+            subject = patch.subject,
+            files = stat.files.len(),
+            insertions = stat.insertions,
+            deletions = stat.deletions
+        ));
+        if !patch.git_author.is_empty() {
+            output.push_str(&format!(
+                "        by {git_author}\n",
+                git_author = patch.git_author
+            ));
+        }
+        let width = stat.files.iter().map(|f| f.path.len()).max().unwrap_or(0);
+        for file in &stat.files {
+            output.push_str(&format!(
+                "        {path:<width$} | +{insertions} -{deletions}\n",
+                path = file.path,
+                insertions = file.insertions,
+                deletions = file.deletions
+            ));
+        }
+    }
+    let missing: Vec<(usize, &ManifestEntry)> = details
+        .manifest
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| {
+            !details
+                .patches
+                .iter()
+                .any(|patch| patch.id == entry.event_id)
+        })
+        .collect();
+    if !missing.is_empty() {
+        let total = details.manifest.len();
+        for (i, entry) in missing {
+            output.push_str(&format!(
+                "  [{n}/{total}] {subject} ({commit:.7}, not found on relays)\n",
+                n = i + 1,
+                subject = entry.subject,
+                commit = entry.commit.as_deref().unwrap_or("?")
+            ));
+        }
+    } else if details.patches.len() < pr.patches_count {
+        output.push_str(&format!(
+            "  ({missing} patch(es) not found on relays)\n",
+            missing = pr.patches_count - details.patches.len()
+        ));
+    }
+
+    if !details.status_history.is_empty() {
+        output.push_str("\nStatus history:\n");
+        for change in &details.status_history {
+            output.push_str(&format!(
+                "  {created_at}  {status:<7} by {author}",
+                created_at = format_timestamp(time, change.created_at),
+                status = change.status.to_string(),
+                author = npub(&change.author)
+            ));
+            if !change.message.is_empty() {
+                output.push_str(&format!(": {message}", message = change.message));
+            }
+            if let Some(commit) = &change.commits.merge_commit {
+                output.push_str(&format!(" (merge {commit:.8})"));
+            } else if !change.commits.applied_as_commits.is_empty() {
+                let commits: Vec<&str> = change
+                    .commits
+                    .applied_as_commits
+                    .iter()
+                    .map(|commit| &commit[..8.min(commit.len())])
+                    .collect();
+                output.push_str(&format!(
+                    " (applied as {commits})",
+                    commits = commits.join(", ")
+                ));
+            }
+            output.push('\n');
+        }
+    }
+
     output
 }
 
+/// Bech32 form of a hex public key, falling back to the input
+fn npub(public_key: &str) -> String {
+    PublicKey::from_hex(public_key)
+        .ok()
+        .and_then(|key| key.to_bech32().ok())
+        .unwrap_or_else(|| public_key.to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use nostr::{EventBuilder, JsonUtil, Keys, Tag, TagKind};
     use serde_json;
 
     #[test]
@@ -287,11 +2591,20 @@ mod tests {
             title: "Test PR".to_string(),
             description: "This is a test pull request".to_string(),
             author: "npub1234567890abcdef".to_string(),
+            author_name: None,
             created_at: 1234567890,
             updated_at: Some(1234567900),
             patches_count: 3,
             root_commit: Some("abc123def456".to_string()),
             status: PullRequestStatus::Open,
+            comments_count: 0,
+            reactions: BTreeMap::new(),
+            revisions: Vec::new(),
+            by_maintainer: false,
+            reviews: ReviewSummary::default(),
+            checks: CheckSummary::default(),
+            labels: Vec::new(),
+            merged_detected: false,
         };
 
         let json = serde_json::to_value(&pr).unwrap();
@@ -342,20 +2655,1211 @@ mod tests {
             title: "Test Title".to_string(),
             description: "Test description".to_string(),
             author: "npub1234567890123456789".to_string(),
+            author_name: None,
             created_at: 1000000,
             updated_at: None,
             patches_count: 2,
             root_commit: Some("commit12345678".to_string()),
             status: PullRequestStatus::Open,
+            comments_count: 0,
+            reactions: BTreeMap::new(),
+            revisions: Vec::new(),
+            by_maintainer: false,
+            reviews: ReviewSummary::default(),
+            checks: CheckSummary::default(),
+            labels: vec!["bug".to_string(), "backend".to_string()],
+            merged_detected: false,
         };
 
-        let formatted = format_pull_request(&pr);
+        let formatted = format_pull_request(&pr, &TimeFormat::default());
 
         assert!(formatted.contains("Title: Test Title"));
         assert!(formatted.contains("Author: npub123456789012"));
         assert!(formatted.contains("Status: open"));
         assert!(formatted.contains("Patches: 2"));
         assert!(formatted.contains("Root: commit12"));
+        assert!(formatted.contains("Labels: bug, backend"));
         assert!(formatted.contains("Test description"));
+
+        let mut prs = [pr];
+        let names = [("npub1234567890123456789".to_string(), "alice".to_string())].into();
+        set_author_names(&mut prs, &names);
+        assert!(
+            format_pull_request(&prs[0], &TimeFormat::default())
+                .contains("Author: alice (npub123456789012...)")
+        );
+    }
+
+    fn sample_pr(id: &str) -> PullRequest {
+        PullRequest {
+            id: id.to_string(),
+            title: "Sample".to_string(),
+            description: String::new(),
+            author: "a".repeat(64),
+            author_name: None,
+            created_at: 1000,
+            updated_at: None,
+            patches_count: 1,
+            root_commit: None,
+            status: PullRequestStatus::Open,
+            comments_count: 0,
+            reactions: BTreeMap::new(),
+            revisions: Vec::new(),
+            by_maintainer: false,
+            reviews: ReviewSummary::default(),
+            checks: CheckSummary::default(),
+            labels: Vec::new(),
+            merged_detected: false,
+        }
+    }
+
+    #[test]
+    fn test_sort_pull_requests() {
+        let mut prs = vec![sample_pr("a"), sample_pr("b"), sample_pr("c")];
+        prs[0].created_at = 3000;
+        prs[1].created_at = 1000;
+        prs[1].updated_at = Some(5000);
+        prs[2].created_at = 2000;
+        let order = |prs: &[PullRequest]| prs.iter().map(|pr| pr.id.clone()).collect::<Vec<_>>();
+
+        sort_pull_requests(&mut prs, PullRequestSort::Newest);
+        assert_eq!(order(&prs), ["a", "c", "b"]);
+        sort_pull_requests(&mut prs, PullRequestSort::Oldest);
+        assert_eq!(order(&prs), ["b", "c", "a"]);
+        sort_pull_requests(&mut prs, PullRequestSort::Updated);
+        assert_eq!(order(&prs), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn test_take_page() {
+        let ids = ["1", "2", "3", "4", "5"].map(|n| n.repeat(64));
+        let mut prs: Vec<PullRequest> = ids.iter().map(|id| sample_pr(id)).collect();
+        // Two PRs opened in the same second are told apart by ID
+        for (pr, created_at) in prs.iter_mut().zip([5000, 4000, 4000, 3000, 2000]) {
+            pr.created_at = created_at;
+        }
+        let page_ids =
+            |page: &[PullRequest]| page.iter().map(|pr| pr.id.clone()).collect::<Vec<_>>();
+
+        let newest = PullRequestSort::Newest;
+        let (first, cursor) = take_page(prs.clone(), newest, None, 2);
+        assert_eq!(page_ids(&first), [ids[0].clone(), ids[1].clone()]);
+        let cursor = cursor.unwrap();
+        assert_eq!(cursor.to_string(), format!("4000-{id}", id = ids[1]));
+        assert_eq!(cursor.to_string().parse::<Cursor>().unwrap(), cursor);
+
+        let (second, cursor) = take_page(prs.clone(), newest, Some(&cursor), 2);
+        assert_eq!(page_ids(&second), [ids[2].clone(), ids[3].clone()]);
+        let (last, cursor) = take_page(prs.clone(), newest, cursor.as_ref(), 2);
+        assert_eq!(page_ids(&last), [ids[4].clone()]);
+        assert!(cursor.is_none());
+
+        // Other orders continue after the cursor's key in that order
+        let oldest = PullRequestSort::Oldest;
+        let (first, cursor) = take_page(prs.clone(), oldest, None, 2);
+        assert_eq!(page_ids(&first), [ids[4].clone(), ids[3].clone()]);
+        let cursor = cursor.unwrap();
+        assert_eq!(cursor.to_string(), format!("oldest-3000-{id}", id = ids[3]));
+        assert_eq!(cursor.to_string().parse::<Cursor>().unwrap(), cursor);
+        let (second, _) = take_page(prs, oldest, Some(&cursor), 2);
+        assert_eq!(page_ids(&second), [ids[1].clone(), ids[2].clone()]);
+
+        assert!("5000".parse::<Cursor>().is_err());
+        assert!("abc-123".parse::<Cursor>().is_err());
+        assert!(
+            format!("sideways-3000-{id}", id = ids[0])
+                .parse::<Cursor>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_pull_request_filters() {
+        let author = Keys::generate().public_key();
+        let options = ListOptions {
+            author: Some(author),
+            since: Some(Timestamp::from(1_700_000_000)),
+            limit: Some(10),
+            cursor: Some(Cursor {
+                sort: PullRequestSort::Newest,
+                key: 1_800_000_000,
+                id: "a".repeat(64),
+            }),
+            ..ListOptions::default()
+        };
+        let (filter, root_patches) =
+            pull_request_filters(&["30617:abc:repo".to_string()], &options);
+        for filter in [filter, root_patches] {
+            assert!(filter.authors.unwrap().contains(&author));
+            assert_eq!(filter.since, Some(Timestamp::from(1_700_000_000)));
+            // Pages are fetched back from the cursor a batch at a time
+            assert_eq!(filter.until, None);
+            assert_eq!(filter.limit, None);
+        }
+    }
+
+    #[test]
+    fn test_apply_engagement_counts_reactions_and_comments() {
+        let keys = Keys::generate();
+        let pr_event = EventBuilder::new(KIND_PULL_REQUEST, "pr")
+            .sign_with_keys(&keys)
+            .unwrap();
+        let mut prs = vec![sample_pr(&pr_event.id.to_hex())];
+
+        let like = EventBuilder::new(Kind::Reaction, "+")
+            .tags(vec![Tag::event(pr_event.id)])
+            .sign_with_keys(&keys)
+            .unwrap();
+        let rocket = EventBuilder::new(Kind::Reaction, "🚀")
+            .tags(vec![Tag::event(pr_event.id)])
+            .sign_with_keys(&keys)
+            .unwrap();
+        let comment = EventBuilder::new(Kind::Comment, "Looks good")
+            .tags(vec![Tag::custom(
+                TagKind::Custom("E".into()),
+                vec![pr_event.id.to_hex()],
+            )])
+            .sign_with_keys(&keys)
+            .unwrap();
+        let unrelated = EventBuilder::new(Kind::Reaction, "+")
+            .tags(vec![Tag::event(comment.id)])
+            .sign_with_keys(&keys)
+            .unwrap();
+
+        // Duplicates from multiple relays must only be counted once
+        let events = vec![like.clone(), like, rocket, comment, unrelated];
+        apply_engagement(&mut prs, &events);
+
+        assert_eq!(prs[0].comments_count, 1);
+        assert_eq!(prs[0].reactions.get("👍"), Some(&1));
+        assert_eq!(prs[0].reactions.get("🚀"), Some(&1));
+        assert_eq!(prs[0].reactions.len(), 2);
+    }
+
+    #[test]
+    fn test_format_engagement() {
+        let mut pr = sample_pr("id");
+        assert_eq!(format_engagement(&pr), "");
+
+        pr.comments_count = 4;
+        pr.reactions.insert("👍".to_string(), 2);
+        pr.reactions.insert("🚀".to_string(), 3);
+        assert_eq!(format_engagement(&pr), "💬 4 🚀 3 👍 2");
+        assert!(
+            format_pull_request(&pr, &TimeFormat::default()).contains("Activity: 💬 4 🚀 3 👍 2")
+        );
+    }
+
+    #[test]
+    fn test_status_kind_round_trip() {
+        for status in [
+            PullRequestStatus::Open,
+            PullRequestStatus::Merged,
+            PullRequestStatus::Closed,
+            PullRequestStatus::Draft,
+        ] {
+            let kind = status.status_kind().unwrap();
+            assert_eq!(PullRequestStatus::from_status_kind(kind), Some(status));
+        }
+        assert_eq!(PullRequestStatus::Updated.status_kind(), None);
+        assert_eq!(PullRequestStatus::from_status_kind(1617), None);
+        assert_eq!(PullRequestStatus::Merged.to_string(), "merged");
+    }
+
+    #[test]
+    fn test_apply_status_events_uses_latest_authorized_status() {
+        let author = Keys::generate();
+        let stranger = Keys::generate();
+        let pr_event = EventBuilder::new(KIND_PULL_REQUEST, "pr")
+            .sign_with_keys(&author)
+            .unwrap();
+        let mut pr = sample_pr(&pr_event.id.to_hex());
+        pr.author = author.public_key().to_hex();
+        let mut prs = vec![pr];
+        let coordinate = format!("30617:{owner}:repo", owner = "b".repeat(64));
+
+        let status = |kind: u16, keys: &Keys, created_at: u64| {
+            let mut event = events::build_status_event(
+                kind,
+                &pr_event.id,
+                &author.public_key(),
+                &coordinate,
+                "",
+                keys,
+            )
+            .unwrap();
+            event.created_at = nostr::Timestamp::from(created_at);
+            event
+        };
+
+        let events = vec![
+            status(KIND_GIT_STATUS_APPLIED, &author, 200),
+            status(KIND_GIT_STATUS_DRAFT, &author, 100),
+            // Strangers cannot change the status, even with a newer event
+            status(KIND_GIT_STATUS_CLOSED, &stranger, 300),
+        ];
+        apply_status_events(&mut prs, &events, Some(&"b".repeat(64)));
+
+        assert_eq!(prs[0].status, PullRequestStatus::Merged);
+    }
+
+    #[test]
+    fn test_status_timeline_and_details_format() {
+        let author = Keys::generate();
+        let pr_event = EventBuilder::new(KIND_PULL_REQUEST, "pr")
+            .sign_with_keys(&author)
+            .unwrap();
+        let mut pr = sample_pr(&pr_event.id.to_hex());
+        pr.author = author.public_key().to_hex();
+        pr.patches_count = 2;
+        let coordinate = format!("30617:{owner}:repo", owner = "b".repeat(64));
+
+        let status = |kind: u16, message: &str, created_at: u64| {
+            let mut event = events::build_status_event(
+                kind,
+                &pr_event.id,
+                &author.public_key(),
+                &coordinate,
+                message,
+                &author,
+            )
+            .unwrap();
+            event.created_at = nostr::Timestamp::from(created_at);
+            event
+        };
+        let commits = AppliedCommits {
+            merge_commit: Some("c".repeat(40)),
+            applied_as_commits: vec!["d".repeat(40)],
+        };
+        let merged = events::status_builder(
+            KIND_GIT_STATUS_APPLIED,
+            &pr_event.id,
+            &author.public_key(),
+            &coordinate,
+            "Thanks!",
+        )
+        .unwrap()
+        .tags(commits.tags().unwrap())
+        .custom_created_at(nostr::Timestamp::from(200))
+        .sign_with_keys(&author)
+        .unwrap();
+        let draft = status(KIND_GIT_STATUS_DRAFT, "", 100);
+
+        // Duplicates from multiple relays are listed once
+        let timeline = status_timeline(&pr, &[merged.clone(), draft, merged], None);
+        let statuses: Vec<PullRequestStatus> = timeline.iter().map(|c| c.status).collect();
+        assert_eq!(
+            statuses,
+            vec![PullRequestStatus::Draft, PullRequestStatus::Merged]
+        );
+        assert!(timeline[0].commits.is_empty());
+        assert_eq!(timeline[1].commits, commits);
+
+        let patch = EventBuilder::new(
+            KIND_PATCH,
+            "From: Alice <alice@example.com>\nSubject: [PATCH] Fix\n\n---\n\
+             diff --git a/src/lib.rs b/src/lib.rs\n@@ -1 +1,2 @@\n-a\n+b\n+c\n",
+        )
+        .sign_with_keys(&author)
+        .unwrap();
+        let details = PullRequestDetails {
+            pr,
+            patches: vec![summarize_patch(&patch)],
+            cover_letter: None,
+            manifest: vec![],
+            status_history: timeline,
+            comments: vec![],
+        };
+
+        let formatted = format_pull_request_details(&details, &TimeFormat::default());
+        assert!(formatted.contains(&author.public_key().to_bech32().unwrap()));
+        assert!(formatted.contains("[1/1] Fix (1 file(s), +2 -1)"));
+        assert!(formatted.contains("src/lib.rs | +2 -1"));
+        assert!(formatted.contains("(1 patch(es) not found on relays)"));
+        assert!(formatted.contains("merged  by npub"));
+        assert!(formatted.contains(": Thanks! (merge cccccccc)"));
+    }
+
+    #[tokio::test]
+    async fn test_cover_letter() {
+        let keys = Keys::generate();
+        let options = patches::PullRequestOptions {
+            cover_letter: Some("Why this series exists\n".to_string()),
+            ..Default::default()
+        };
+        let events = patches::create_pull_request_event(
+            &keys,
+            "30617:abc:repo",
+            "Refactor",
+            "",
+            vec!["patch 1".to_string(), "patch 2".to_string()],
+            "root",
+            &options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(events.len(), 5);
+
+        let cover_letter = &events[0];
+        assert!(
+            cover_letter
+                .content
+                .contains("Subject: [PATCH 0/2] Refactor\n")
+        );
+        assert_eq!(
+            patches::cover_letter_body(&cover_letter.content),
+            "Why this series exists"
+        );
+        // The first patch replies to the cover letter
+        assert!(events[1].tags.event_ids().any(|id| *id == cover_letter.id));
+
+        let pr = &events[4];
+        assert_eq!(cover_letter_id(pr), Some(cover_letter.id));
+        assert_eq!(manifest_id(pr), Some(events[3].id));
+        assert_eq!(patch_event_ids(pr), vec![events[1].id, events[2].id]);
+    }
+
+    #[tokio::test]
+    async fn test_series_manifest() {
+        let keys = Keys::generate();
+        let patch = |commit: char, subject: &str| {
+            format!(
+                "From {commit} Mon Sep 17 00:00:00 2001\nSubject: [PATCH] {subject}\n\n---\n\
+                 diff --git a/f.txt b/f.txt\n@@ -1 +1,2 @@\n-a\n+b\n+c\n",
+                commit = commit.to_string().repeat(40)
+            )
+        };
+        let events = patches::create_pull_request_event(
+            &keys,
+            "30617:abc:repo",
+            "Series",
+            "",
+            vec![patch('a', "First"), patch('b', "Second")],
+            "root",
+            &patches::PullRequestOptions::default(),
+        )
+        .await
+        .unwrap();
+        let (manifest, pr) = (&events[2], &events[3]);
+        assert_eq!(manifest_id(pr), Some(manifest.id));
+
+        let entries = manifest_entries(manifest);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].event_id, events[1].id.to_hex());
+        assert_eq!(entries[1].commit, Some("b".repeat(40)));
+        assert_eq!(entries[1].subject, "Second");
+        assert_eq!(
+            (
+                entries[1].files,
+                entries[1].insertions,
+                entries[1].deletions
+            ),
+            (1, 2, 1)
+        );
+
+        // A patch the relays dropped is still named in the summary
+        let details = PullRequestDetails {
+            pr: event_to_pull_request(pr).unwrap(),
+            patches: vec![summarize_patch(&events[0])],
+            cover_letter: None,
+            manifest: entries,
+            status_history: vec![],
+            comments: vec![],
+        };
+        let formatted = format_pull_request_details(&details, &TimeFormat::default());
+        assert!(formatted.contains("[2/2] Second (bbbbbbb, not found on relays)"));
+    }
+
+    #[tokio::test]
+    async fn test_build_pull_requests_groups_revisions() {
+        let author = Keys::generate();
+        let stranger = Keys::generate();
+        async fn series(keys: &Keys, patches: usize, revision: Option<patches::Revision>) -> Event {
+            let options = patches::PullRequestOptions {
+                revision,
+                ..Default::default()
+            };
+            let patches = (0..patches).map(|i| format!("patch {i}")).collect();
+            patches::create_pull_request_event(
+                keys,
+                "30617:abc:repo",
+                "Fix",
+                "",
+                patches,
+                "root",
+                &options,
+            )
+            .await
+            .unwrap()
+            .pop()
+            .unwrap()
+        }
+        let original = series(&author, 1, None).await;
+        let revise = |version| {
+            Some(patches::Revision {
+                pr_id: original.id.to_hex(),
+                version,
+            })
+        };
+        let v2 = series(&author, 2, revise(2)).await;
+        let v3 = series(&author, 3, revise(3)).await;
+        let hijack = series(&stranger, 5, revise(4)).await;
+        assert_eq!(revision_of(&original), None);
+        assert_eq!(revision_of(&v3), Some(Some(3)));
+
+        // Relays deliver events in any order, and sometimes twice
+        let events = vec![v3.clone(), hijack, v2.clone(), original.clone(), v3.clone()];
+        let prs = build_pull_requests(events, None).unwrap();
+        assert_eq!(prs.len(), 1);
+
+        let pr = &prs[0];
+        assert_eq!(pr.id, original.id.to_hex());
+        let versions: Vec<u32> = pr.revisions.iter().map(|r| r.version).collect();
+        assert_eq!(versions, vec![2, 3]);
+        assert_eq!(pr.version(), 3);
+        assert_eq!(pr.latest_event_id(), v3.id.to_hex());
+        assert_eq!(pr.patches_count, 3);
+        assert_eq!(pr.status, PullRequestStatus::Updated);
+    }
+
+    #[tokio::test]
+    async fn test_cached_pull_request() {
+        use crate::cache::{CacheStore, MemoryStore};
+
+        let keys = Keys::generate();
+        let coordinate = format!("30617:{owner}:repo", owner = keys.public_key().to_hex());
+        let patches = vec![
+            "From 5f1e2c0b Mon Sep 17 00:00:00 2001\nFrom: Alice <alice@example.com>\nSubject: [PATCH 1/2] First\n\n---\n".to_string(),
+            "From 6a0b3d1e Mon Sep 17 00:00:00 2001\nFrom: Alice <alice@example.com>\nSubject: [PATCH 2/2] Second\n\n---\n".to_string(),
+        ];
+        let mut events = patches::create_pull_request_event(
+            &keys,
+            &coordinate,
+            "Fix",
+            "",
+            patches,
+            "root",
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+        let pr = events.pop().unwrap();
+        let comment = comments::build_comment_event(&keys, &pr, None, "LGTM", Some(&coordinate))
+            .await
+            .unwrap();
+        let merged = events::build_status_event(
+            KIND_GIT_STATUS_APPLIED,
+            &pr.id,
+            &pr.pubkey,
+            &coordinate,
+            "Merged",
+            &keys,
+        )
+        .unwrap();
+
+        let store = MemoryStore::new();
+        let missing = cached_pull_request(&store, &pr.id.to_hex(), &coordinate);
+        assert!(matches!(missing, Err(GitsmithError::EventNotFound(_))));
+
+        events.extend([pr.clone(), comment, merged]);
+        store.save_events(&events).unwrap();
+        let details = cached_pull_request(&store, &pr.id.to_hex(), &coordinate).unwrap();
+        assert_eq!(details.pr.title, "Fix");
+        assert_eq!(details.pr.status, PullRequestStatus::Merged);
+        let subjects: Vec<&str> = details.patches.iter().map(|p| p.subject.as_str()).collect();
+        assert_eq!(subjects, vec!["First", "Second"]);
+        assert_eq!(details.comments.len(), 1);
+        assert_eq!(details.status_history.len(), 1);
+    }
+
+    #[test]
+    fn test_build_pull_requests_breaks_timestamp_ties_by_id() {
+        let keys = Keys::generate();
+        let at = nostr::Timestamp::from(1_700_000_000);
+        let event = |kind: Kind, content: &str, tags: Vec<Tag>| {
+            EventBuilder::new(kind, content)
+                .tags(tags)
+                .custom_created_at(at)
+                .sign_with_keys(&keys)
+                .unwrap()
+        };
+        // Scripted sends publish several PRs and updates within the same second
+        let prs: Vec<Event> = (0..3)
+            .map(|i| event(KIND_PULL_REQUEST, &format!("pr {i}"), vec![]))
+            .collect();
+        let reply = Tag::parse(["e", &prs[0].id.to_hex(), "", "reply"]).unwrap();
+        let updates: Vec<Event> = (0..2)
+            .map(|i| {
+                event(
+                    KIND_PULL_REQUEST_UPDATE,
+                    &format!("update {i}"),
+                    vec![reply.clone()],
+                )
+            })
+            .collect();
+
+        let forward: Vec<Event> = prs.iter().chain(&updates).cloned().collect();
+        let backward: Vec<Event> = forward.iter().rev().cloned().collect();
+        let first = build_pull_requests(forward, None).unwrap();
+        let second = build_pull_requests(backward, None).unwrap();
+
+        let ids = |prs: &[PullRequest]| prs.iter().map(|pr| pr.id.clone()).collect::<Vec<_>>();
+        let mut expected: Vec<String> = prs.iter().map(|event| event.id.to_hex()).collect();
+        expected.sort();
+        assert_eq!(ids(&first), expected);
+        assert_eq!(ids(&second), expected);
+
+        // The update with the highest ID is applied last
+        let latest = updates.iter().max_by_key(|event| event.id).unwrap();
+        for result in [&first, &second] {
+            let updated = result
+                .iter()
+                .find(|pr| pr.id == prs[0].id.to_hex())
+                .unwrap();
+            assert_eq!(updated.description, latest.content);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_finds_comments_on_author_relays() {
+        use crate::cache::MemoryStore;
+        use crate::localnet::{self, LocalRelay};
+        use std::sync::Arc;
+        use tokio::net::TcpListener;
+
+        async fn start_relay() -> String {
+            let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+            let url = localnet::relay_url(listener.local_addr().unwrap().port());
+            tokio::spawn(LocalRelay::new(Arc::new(MemoryStore::new())).serve(listener));
+            url
+        }
+        let repo_relay = start_relay().await;
+        let author_relay = start_relay().await;
+
+        let author = Keys::generate();
+        let coordinate = format!("30617:{owner}:repo", owner = "b".repeat(64));
+        let pr = EventBuilder::new(KIND_PULL_REQUEST, "")
+            .tags([
+                Tag::parse(["a", &coordinate]).unwrap(),
+                Tag::parse(["subject", "Fix"]).unwrap(),
+            ])
+            .sign_with_keys(&author)
+            .unwrap();
+        let relay_list = relay_lists::RelayList::new(std::slice::from_ref(&author_relay), &[], &[])
+            .to_event_builder()
+            .unwrap()
+            .sign_with_keys(&author)
+            .unwrap();
+        // Only the author's own relay has the comment
+        let comment = EventBuilder::new(Kind::Comment, "LGTM")
+            .tags([Tag::parse(["E", &pr.id.to_hex()]).unwrap()])
+            .sign_with_keys(&Keys::generate())
+            .unwrap();
+
+        let publish = |relay: String, events: Vec<Event>| async move {
+            let client = relay::connect(&Network::default(), &[relay], None)
+                .await
+                .unwrap();
+            for event in &events {
+                client.send_event(event).await.unwrap();
+            }
+        };
+        publish(repo_relay.clone(), vec![pr, relay_list]).await;
+        publish(author_relay, vec![comment]).await;
+
+        let options = ListOptions {
+            timeout: Duration::from_millis(500),
+            ..ListOptions::default()
+        };
+        let prs = list_pull_requests_with_options(
+            &Network::default(),
+            &coordinate,
+            vec![repo_relay.clone()],
+            &options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(prs.len(), 1);
+        assert_eq!(prs[0].comments_count, 1);
+
+        let options = ListOptions {
+            outbox: false,
+            ..options
+        };
+        let prs = list_pull_requests_with_options(
+            &Network::default(),
+            &coordinate,
+            vec![repo_relay],
+            &options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(prs[0].comments_count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_list_pull_request_page() {
+        use crate::cache::MemoryStore;
+        use crate::localnet::{self, LocalRelay};
+        use std::sync::Arc;
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let relay_url = localnet::relay_url(listener.local_addr().unwrap().port());
+        tokio::spawn(LocalRelay::new(Arc::new(MemoryStore::new())).serve(listener));
+
+        let author = Keys::generate();
+        let coordinate = format!("30617:{owner}:repo", owner = author.public_key().to_hex());
+        let event = |kind: Kind, content: &str, tags: Vec<Tag>, created_at: u64| {
+            EventBuilder::new(kind, content)
+                .tags(tags)
+                .custom_created_at(Timestamp::from(created_at))
+                .sign_with_keys(&author)
+                .unwrap()
+        };
+        let prs: Vec<Event> = (0..3u64)
+            .map(|i| {
+                let tags = vec![
+                    Tag::parse(["a", &coordinate]).unwrap(),
+                    Tag::parse(["subject", &format!("PR {i}")]).unwrap(),
+                ];
+                event(KIND_PULL_REQUEST, "", tags, 1_000 + i)
+            })
+            .collect();
+        // The oldest PR was updated after the newest was opened
+        let reply = Tag::parse(["e", &prs[0].id.to_hex(), "", "reply"]).unwrap();
+        let update = event(
+            KIND_PULL_REQUEST_UPDATE,
+            "Updated",
+            vec![Tag::parse(["a", &coordinate]).unwrap(), reply],
+            2_000,
+        );
+        let client = relay::connect(&Network::default(), std::slice::from_ref(&relay_url), None)
+            .await
+            .unwrap();
+        for event in prs.iter().chain([&update]) {
+            client.send_event(event).await.unwrap();
+        }
+
+        let mut options = ListOptions {
+            timeout: Duration::from_millis(500),
+            outbox: false,
+            limit: Some(2),
+            ..ListOptions::default()
+        };
+        let first = list_pull_request_page(
+            &Network::default(),
+            &coordinate,
+            vec![relay_url.clone()],
+            &options,
+        )
+        .await
+        .unwrap();
+        let titles = |page: &PullRequestPage| {
+            page.pull_requests
+                .iter()
+                .map(|pr| pr.title.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(titles(&first), ["PR 2", "PR 1"]);
+
+        options.cursor = Some(first.next_cursor.unwrap().parse().unwrap());
+        let second =
+            list_pull_request_page(&Network::default(), &coordinate, vec![relay_url], &options)
+                .await
+                .unwrap();
+        assert_eq!(titles(&second), ["PR 0"]);
+        assert_eq!(second.pull_requests[0].description, "Updated");
+        assert!(second.next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_list_pull_request_page_filtered_and_sorted() {
+        use crate::cache::MemoryStore;
+        use crate::localnet::{self, LocalRelay};
+        use std::sync::Arc;
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let relay_url = localnet::relay_url(listener.local_addr().unwrap().port());
+        tokio::spawn(LocalRelay::new(Arc::new(MemoryStore::new())).serve(listener));
+
+        let author = Keys::generate();
+        let coordinate = format!("30617:{owner}:repo", owner = author.public_key().to_hex());
+        let prs: Vec<Event> = (0..5u64)
+            .map(|i| {
+                let tags = vec![
+                    Tag::parse(["a", &coordinate]).unwrap(),
+                    Tag::parse(["subject", &format!("PR {i}")]).unwrap(),
+                ];
+                EventBuilder::new(KIND_PULL_REQUEST, "")
+                    .tags(tags)
+                    .custom_created_at(Timestamp::from(1_000 + i))
+                    .sign_with_keys(&author)
+                    .unwrap()
+            })
+            .collect();
+        // The oldest PR and one in the middle were closed
+        let closed = [&prs[0], &prs[2]].map(|pr| {
+            events::build_status_event(
+                KIND_GIT_STATUS_CLOSED,
+                &pr.id,
+                &author.public_key(),
+                &coordinate,
+                "",
+                &author,
+            )
+            .unwrap()
+        });
+        let client = relay::connect(&Network::default(), std::slice::from_ref(&relay_url), None)
+            .await
+            .unwrap();
+        for event in prs.iter().chain(&closed) {
+            client.send_event(event).await.unwrap();
+        }
+
+        let mut options = ListOptions {
+            timeout: Duration::from_millis(500),
+            outbox: false,
+            limit: Some(2),
+            sort: PullRequestSort::Oldest,
+            status: Some(PullRequestStatus::Open),
+            ..ListOptions::default()
+        };
+        let titles = |page: &PullRequestPage| {
+            page.pull_requests
+                .iter()
+                .map(|pr| pr.title.clone())
+                .collect::<Vec<_>>()
+        };
+        let first = list_pull_request_page(
+            &Network::default(),
+            &coordinate,
+            vec![relay_url.clone()],
+            &options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(titles(&first), ["PR 1", "PR 3"]);
+
+        options.cursor = Some(first.next_cursor.unwrap().parse().unwrap());
+        let second = list_pull_request_page(
+            &Network::default(),
+            &coordinate,
+            vec![relay_url.clone()],
+            &options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(titles(&second), ["PR 4"]);
+        assert!(second.next_cursor.is_none());
+
+        // A cursor only continues the order it was made for
+        options.sort = PullRequestSort::Newest;
+        assert!(
+            list_pull_request_page(&Network::default(), &coordinate, vec![relay_url], &options)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_pull_request_page_same_second() {
+        use crate::cache::MemoryStore;
+        use crate::localnet::{self, LocalRelay};
+        use std::sync::Arc;
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let relay_url = localnet::relay_url(listener.local_addr().unwrap().port());
+        tokio::spawn(LocalRelay::new(Arc::new(MemoryStore::new())).serve(listener));
+
+        let author = Keys::generate();
+        let coordinate = format!("30617:{owner}:repo", owner = author.public_key().to_hex());
+        // More PRs than fit a page were opened in the same second, listed by ID
+        let created_at = [1_001, 1_000, 1_000, 1_000, 1_000, 1_000, 999];
+        let prs: Vec<Event> = created_at
+            .iter()
+            .enumerate()
+            .map(|(i, created_at)| {
+                let tags = vec![
+                    Tag::parse(["a", &coordinate]).unwrap(),
+                    Tag::parse(["subject", &format!("PR {i}")]).unwrap(),
+                ];
+                EventBuilder::new(KIND_PULL_REQUEST, "")
+                    .tags(tags)
+                    .custom_created_at(Timestamp::from(*created_at))
+                    .sign_with_keys(&author)
+                    .unwrap()
+            })
+            .collect();
+        let client = relay::connect(&Network::default(), std::slice::from_ref(&relay_url), None)
+            .await
+            .unwrap();
+        for event in &prs {
+            client.send_event(event).await.unwrap();
+        }
+
+        let mut options = ListOptions {
+            timeout: Duration::from_millis(500),
+            outbox: false,
+            limit: Some(2),
+            ..ListOptions::default()
+        };
+        let mut listed = Vec::new();
+        loop {
+            let page = list_pull_request_page(
+                &Network::default(),
+                &coordinate,
+                vec![relay_url.clone()],
+                &options,
+            )
+            .await
+            .unwrap();
+            assert!(page.pull_requests.len() <= 2);
+            listed.extend(page.pull_requests.into_iter().map(|pr| pr.id));
+            match page.next_cursor {
+                Some(cursor) => options.cursor = Some(cursor.parse().unwrap()),
+                None => break,
+            }
+        }
+
+        let mut expected: Vec<String> = prs.iter().map(|pr| pr.id.to_hex()).collect();
+        expected[1..6].sort();
+        assert_eq!(listed, expected);
+    }
+
+    #[tokio::test]
+    async fn test_list_pull_request_page_skips_spam() {
+        use crate::cache::MemoryStore;
+        use crate::localnet::{self, LocalRelay};
+        use std::sync::Arc;
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let relay_url = localnet::relay_url(listener.local_addr().unwrap().port());
+        tokio::spawn(LocalRelay::new(Arc::new(MemoryStore::new())).serve(listener));
+
+        let author = Keys::generate();
+        let coordinate = format!("30617:{owner}:repo", owner = author.public_key().to_hex());
+        let event = |title: &str, created_at: u64, difficulty: u8| {
+            let tags = vec![
+                Tag::parse(["a", &coordinate]).unwrap(),
+                Tag::parse(["subject", title]).unwrap(),
+            ];
+            EventBuilder::new(KIND_PULL_REQUEST, "")
+                .tags(tags)
+                .custom_created_at(Timestamp::from(created_at))
+                .pow(difficulty)
+                .sign_with_keys(&author)
+                .unwrap()
+        };
+        // Newer spam below the difficulty fills whole batches of relay results
+        let mut events: Vec<Event> = (0..4u64)
+            .map(|i| {
+                std::iter::repeat_with(|| event("Spam", 1_002 + i, 0))
+                    .find(|spam| !spam.check_pow(8))
+                    .unwrap()
+            })
+            .collect();
+        events.push(event("PR 1", 1_001, 8));
+        events.push(event("PR 0", 1_000, 8));
+        let client = relay::connect(&Network::default(), std::slice::from_ref(&relay_url), None)
+            .await
+            .unwrap();
+        for event in &events {
+            client.send_event(event).await.unwrap();
+        }
+
+        let mut options = ListOptions {
+            timeout: Duration::from_millis(500),
+            outbox: false,
+            limit: Some(2),
+            min_pow: 8,
+            ..ListOptions::default()
+        };
+        let first = list_pull_request_page(
+            &Network::default(),
+            &coordinate,
+            vec![relay_url.clone()],
+            &options,
+        )
+        .await
+        .unwrap();
+        let titles: Vec<&str> = first
+            .pull_requests
+            .iter()
+            .map(|pr| pr.title.as_str())
+            .collect();
+        assert_eq!(titles, ["PR 1", "PR 0"]);
+
+        // Relays returned a full batch, so older PRs may follow
+        options.cursor = Some(first.next_cursor.unwrap().parse().unwrap());
+        let second =
+            list_pull_request_page(&Network::default(), &coordinate, vec![relay_url], &options)
+                .await
+                .unwrap();
+        assert!(second.pull_requests.is_empty());
+        assert!(second.next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_detect_merged() {
+        use crate::cache::MemoryStore;
+        use crate::localnet::{self, LocalRelay};
+        use git2::{Repository, Signature};
+        use std::sync::Arc;
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let relay_url = localnet::relay_url(listener.local_addr().unwrap().port());
+        tokio::spawn(LocalRelay::new(Arc::new(MemoryStore::new())).serve(listener));
+
+        // Three commits, the last of which never landed
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let commit = |file: &str| {
+            std::fs::write(dir.path().join(file), format!("{file}\n")).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new(file)).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let sig = Signature::now("Alice Example", "alice@example.com").unwrap();
+            let parents: Vec<git2::Commit> = repo
+                .head()
+                .ok()
+                .map(|head| head.peel_to_commit().unwrap())
+                .into_iter()
+                .collect();
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            repo.commit(
+                Some("HEAD"),
+                &sig,
+                &sig,
+                &format!("Add {file}"),
+                &tree,
+                &parents,
+            )
+            .unwrap()
+        };
+        commit("base.txt");
+        commit("a.txt");
+        let landed = commit("b.txt");
+        commit("c.txt");
+        let [a, b, c]: [String; 3] = patches::generate_patches(dir.path(), Some("HEAD~3"), None)
+            .unwrap()
+            .try_into()
+            .unwrap();
+        repo.head().unwrap().set_target(landed, "drop c").unwrap();
+
+        let keys = Keys::generate();
+        let coordinate = format!("30617:{owner}:repo", owner = keys.public_key().to_hex());
+        let client = relay::connect(&Network::default(), std::slice::from_ref(&relay_url), None)
+            .await
+            .unwrap();
+        let mut pr_ids = Vec::new();
+        for (title, series) in [("Merged", vec![a, b.clone()]), ("Partial", vec![b, c])] {
+            let events = patches::create_pull_request_event(
+                &keys,
+                &coordinate,
+                title,
+                "",
+                series,
+                "root",
+                &patches::PullRequestOptions::default(),
+            )
+            .await
+            .unwrap();
+            for event in &events {
+                client.send_event(event).await.unwrap();
+            }
+            pr_ids.push(events.last().unwrap().id.to_hex());
+        }
+
+        let options = ListOptions {
+            timeout: Duration::from_millis(500),
+            outbox: false,
+            repo_path: Some(dir.path().to_path_buf()),
+            ..ListOptions::default()
+        };
+        let prs = list_pull_requests_with_options(
+            &Network::default(),
+            &coordinate,
+            vec![relay_url],
+            &options,
+        )
+        .await
+        .unwrap();
+        let pr = |id: &str| prs.iter().find(|pr| pr.id == id).unwrap();
+
+        let merged = pr(&pr_ids[0]);
+        assert_eq!(merged.status, PullRequestStatus::Merged);
+        assert!(merged.merged_detected);
+        assert!(
+            format_pull_request(merged, &TimeFormat::default())
+                .contains("Status: merged (detected)\n")
+        );
+
+        // Only some of the patches are upstream, so the PR stays open
+        let partial = pr(&pr_ids[1]);
+        assert_eq!(partial.status, PullRequestStatus::Open);
+        assert!(!partial.merged_detected);
+        assert!(format_pull_request(partial, &TimeFormat::default()).contains("Status: open\n"));
+    }
+
+    /// A cover letter and two patches in the layout ngit publishes, signed with
+    /// a fixed test key so their IDs and signatures check out
+    const NGIT_SERIES: [&str; 3] = [
+        r#"{"id":"736d8047afce846d72aa711ea1a114fb714843ed849c9f60f169e9989fb1fee7","pubkey":"385c3a6ec0b9d57a4330dbd6284989be5bd00e41c535f9ca39b6ae7c521b81cd","created_at":1700000000,"kind":1617,"tags":[["alt","git patch cover letter: Add frobnicator"],["t","cover-letter"],["t","root"],["a","30617:385c3a6ec0b9d57a4330dbd6284989be5bd00e41c535f9ca39b6ae7c521b81cd:gitsmith"],["r","9d2a7b1c4e5f60718293a4b5c6d7e8f901234567"],["p","385c3a6ec0b9d57a4330dbd6284989be5bd00e41c535f9ca39b6ae7c521b81cd"]],"content":"From 6a0b3d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c Mon Sep 17 00:00:00 2001\nFrom: Alice <alice@example.com>\nDate: Tue, 14 Nov 2023 22:13:20 +0000\nSubject: [PATCH 0/2] Add frobnicator\n\nFrobnicate all the things.\n","sig":"9bddfd0877435974dcc52da3a383b137ccd3ca9ce1fe0d1e99b147ce677eed672f0d81f2dfe7e841ab242d0e3ce4362cffc7b2b8212d3b4cd01d1b67753a13fa"}"#,
+        r#"{"id":"2557347be3d8e531a1f9a5ced5fc488da8acb834454687111412dbfd8a30f17a","pubkey":"385c3a6ec0b9d57a4330dbd6284989be5bd00e41c535f9ca39b6ae7c521b81cd","created_at":1700000001,"kind":1617,"tags":[["alt","git patch: Add frobnicator module"],["a","30617:385c3a6ec0b9d57a4330dbd6284989be5bd00e41c535f9ca39b6ae7c521b81cd:gitsmith"],["r","9d2a7b1c4e5f60718293a4b5c6d7e8f901234567"],["p","385c3a6ec0b9d57a4330dbd6284989be5bd00e41c535f9ca39b6ae7c521b81cd"],["e","736d8047afce846d72aa711ea1a114fb714843ed849c9f60f169e9989fb1fee7","","root"],["commit","5f1e2c0b9a8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b"],["r","5f1e2c0b9a8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b"],["parent-commit","9d2a7b1c4e5f60718293a4b5c6d7e8f901234567"],["commit-pgp-sig",""],["description","Add frobnicator module"],["author","Alice","alice@example.com","1700000000","0"],["committer","Alice","alice@example.com","1700000000","0"]],"content":"From 5f1e2c0b9a8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b Mon Sep 17 00:00:00 2001\nFrom: Alice <alice@example.com>\nDate: Tue, 14 Nov 2023 22:13:20 +0000\nSubject: [PATCH 1/2] Add frobnicator module\n\n---\n","sig":"f3130625ff6b723dd63cf52e159c9fbd4c061df0f3fa3abed57ed37de51af0ed7db06b61b87fb985efe0a8b347e46d28e7b0330ac74b7ce512f977f53d512ee2"}"#,
+        r#"{"id":"784ec6c0bacf8f4fcdafc8f5c08d2bec695554de362923d13b231c02938c1f9e","pubkey":"385c3a6ec0b9d57a4330dbd6284989be5bd00e41c535f9ca39b6ae7c521b81cd","created_at":1700000001,"kind":1617,"tags":[["alt","git patch: Wire up frobnicator"],["a","30617:385c3a6ec0b9d57a4330dbd6284989be5bd00e41c535f9ca39b6ae7c521b81cd:gitsmith"],["r","9d2a7b1c4e5f60718293a4b5c6d7e8f901234567"],["p","385c3a6ec0b9d57a4330dbd6284989be5bd00e41c535f9ca39b6ae7c521b81cd"],["e","736d8047afce846d72aa711ea1a114fb714843ed849c9f60f169e9989fb1fee7","","root"],["e","2557347be3d8e531a1f9a5ced5fc488da8acb834454687111412dbfd8a30f17a","","reply"],["commit","6a0b3d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c"],["r","6a0b3d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c"],["parent-commit","5f1e2c0b9a8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b"],["commit-pgp-sig",""],["description","Wire up frobnicator"],["author","Alice","alice@example.com","1700000000","0"],["committer","Alice","alice@example.com","1700000000","0"]],"content":"From 6a0b3d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c Mon Sep 17 00:00:00 2001\nFrom: Alice <alice@example.com>\nDate: Tue, 14 Nov 2023 22:13:20 +0000\nSubject: [PATCH 2/2] Wire up frobnicator\n\n---\n","sig":"6ce8c4007f1a68d2d84f01be5744a29ade15a3739fc8e25c93d13e8d8776a86f28c3dcd85dc53b85b73b0f14abceae0bd349f706ad4bffe77d4b56557ec660b3"}"#,
+    ];
+
+    #[test]
+    fn test_read_ngit_series() {
+        let events: Vec<Event> = NGIT_SERIES
+            .iter()
+            .map(|json| Event::from_json(json).unwrap())
+            .collect();
+        for event in &events {
+            assert!(event.verify().is_ok());
+        }
+        let (cover_letter, first, second) = (&events[0], &events[1], &events[2]);
+        assert!(is_root_patch(cover_letter));
+        assert!(!is_root_patch(first));
+
+        // Both patches share a timestamp, the reply tags decide the order
+        let ordered = order_thread(cover_letter, vec![second.clone(), first.clone()]);
+        let ids: Vec<EventId> = ordered.iter().map(|event| event.id).collect();
+        assert_eq!(ids, vec![cover_letter.id, first.id, second.id]);
+
+        let prs = build_pull_requests(events.clone(), None).unwrap();
+        assert_eq!(prs.len(), 1);
+        assert_eq!(prs[0].id, cover_letter.id.to_hex());
+        assert_eq!(prs[0].title, "Add frobnicator");
+        assert_eq!(prs[0].patches_count, 2);
+        assert_eq!(prs[0].status, PullRequestStatus::Open);
+    }
+
+    #[tokio::test]
+    async fn test_ngit_layout_round_trip() {
+        let keys = Keys::generate();
+        let patches = (1..=3)
+            .map(|i| format!("From 0000000 Mon Sep 17 00:00:00 2001\nSubject: [PATCH {i}/3] Step {i}\n\n---\n"))
+            .collect();
+        let mut events = patches::create_pull_request_event(
+            &keys,
+            "30617:abc:repo",
+            "Steps",
+            "",
+            patches,
+            "root",
+            &patches::PullRequestOptions::default(),
+        )
+        .await
+        .unwrap();
+        let pr = events.pop().unwrap();
+        let manifest = events.pop().unwrap();
+        assert_eq!(manifest.kind, KIND_SERIES_MANIFEST);
+        assert!(is_root_patch(&events[0]));
+        assert_eq!(series_patch_count(&events[0]), 3);
+
+        // ngit's reader follows the root and reply markers
+        for (previous, patch) in events.iter().zip(&events[1..]) {
+            assert_eq!(marked_event(patch, "root"), Some(events[0].id));
+            let parent = marked_event(patch, "reply").or(marked_event(patch, "root"));
+            assert_eq!(parent, Some(previous.id));
+        }
+        let mut shuffled = events[1..].to_vec();
+        shuffled.reverse();
+        let ordered = order_thread(&events[0], shuffled);
+        assert_eq!(ordered, events);
+
+        // Listed once through the PR event, not again through its root patch
+        let mut all = events.clone();
+        all.extend([manifest, pr.clone()]);
+        let prs = build_pull_requests(all, None).unwrap();
+        assert_eq!(prs.len(), 1);
+        assert_eq!(prs[0].id, pr.id.to_hex());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_patch_series_joins_split_patches() {
+        use crate::cache::MemoryStore;
+        use crate::localnet::{self, LocalRelay};
+        use std::sync::Arc;
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let url = localnet::relay_url(listener.local_addr().unwrap().port());
+        tokio::spawn(LocalRelay::new(Arc::new(MemoryStore::new())).serve(listener));
+
+        let patch = format!(
+            "From {commit} Mon Sep 17 00:00:00 2001\nSubject: [PATCH] Big\n\n---\n{lines}",
+            commit = "a".repeat(40),
+            lines = "+line\n".repeat(3000)
+        );
+        let options = patches::PullRequestOptions {
+            max_event_size: Some(8 * 1024),
+            split_large_patches: true,
+            ..Default::default()
+        };
+        let events = patches::create_pull_request_event(
+            &Keys::generate(),
+            "30617:abc:repo",
+            "Big",
+            "",
+            vec![patch.clone(), "small patch".to_string()],
+            "root",
+            &options,
+        )
+        .await
+        .unwrap();
+        let client = relay::connect(&Network::default(), std::slice::from_ref(&url), None)
+            .await
+            .unwrap();
+        for event in &events {
+            client.send_event(event).await.unwrap();
+        }
+        client.disconnect().await;
+
+        let pr = events.last().unwrap();
+        let series = fetch_patch_series(
+            &Network::default(),
+            &pr.id.to_hex(),
+            &[url],
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            series.patch_contents(),
+            vec![patch, "small patch".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_filter_low_pow() {
+        let keys = Keys::generate();
+        let mined = EventBuilder::new(KIND_PULL_REQUEST, "mined")
+            .pow(8)
+            .sign_with_keys(&keys)
+            .unwrap();
+        let spam = EventBuilder::new(KIND_PULL_REQUEST, "spam")
+            .sign_with_keys(&keys)
+            .unwrap();
+        let reaction = EventBuilder::new(Kind::Reaction, "+")
+            .sign_with_keys(&keys)
+            .unwrap();
+        // Make sure the unmined event really is below the threshold
+        let events: Vec<Event> = vec![mined.clone(), spam.clone(), reaction.clone()];
+        let spam_passes = spam.check_pow(8);
+
+        let kept = filter_low_pow(events.clone(), 8);
+        assert!(kept.iter().any(|e| e.id == mined.id));
+        assert!(kept.iter().any(|e| e.id == reaction.id));
+        assert_eq!(kept.iter().any(|e| e.id == spam.id), spam_passes);
+
+        assert_eq!(filter_low_pow(events, 0).len(), 3);
     }
 }
@@ -0,0 +1,219 @@
+//! README published as a NIP-23 long-form article
+//!
+//! The article is addressable (`d` = `<identifier>-readme`) and references the
+//! repository with an `a` tag; the announcement links back to it with a
+//! `readme` tag, so clients browsing relays can show the project description
+//! without cloning. Re-publishing replaces the previous version.
+
+use nostr::{EventBuilder, Kind, PublicKey, Tag};
+use std::fs;
+use std::path::Path;
+
+use crate::error::{Context, Result, ensure};
+use crate::events::KIND_GIT_REPO_ANNOUNCEMENT;
+use crate::relay::{self, Network};
+use crate::signer::{self, Signer};
+use crate::types::BroadcastResult;
+
+/// Kind of the README article (NIP-23 long-form content)
+pub const KIND_README: Kind = Kind::LongFormTextNote;
+
+/// README looked up in the repository when no path is given
+pub const DEFAULT_README: &str = "README.md";
+
+/// Longest summary [`summarize`] produces, in characters
+const SUMMARY_MAX_CHARS: usize = 280;
+
+/// `d` tag value of the README article of a repository
+pub fn readme_identifier(repo_identifier: &str) -> String {
+    format!("{repo_identifier}-readme")
+}
+
+/// Coordinate of the README article, as linked from the announcement
+pub fn readme_coordinate(owner: &PublicKey, repo_identifier: &str) -> String {
+    format!(
+        "{kind}:{owner}:{identifier}",
+        kind = KIND_README.as_u16(),
+        owner = owner.to_hex(),
+        identifier = readme_identifier(repo_identifier)
+    )
+}
+
+/// First prose paragraph of a markdown document, shortened to a few sentences
+///
+/// Headings, badges, images, HTML and code blocks are skipped.
+pub fn summarize(markdown: &str) -> String {
+    let mut paragraph = Vec::new();
+    let mut in_code = false;
+    for line in markdown.lines() {
+        let line = line.trim();
+        if line.starts_with("```") || line.starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        let skipped = in_code
+            || line.starts_with('#')
+            || line.starts_with("![")
+            || line.starts_with("[![")
+            || line.starts_with('<')
+            || line.starts_with("---")
+            || line.starts_with("===");
+        if line.is_empty() || skipped {
+            if !paragraph.is_empty() {
+                break;
+            }
+            continue;
+        }
+        paragraph.push(line);
+    }
+
+    let summary = paragraph.join(" ");
+    if summary.chars().count() <= SUMMARY_MAX_CHARS {
+        return summary;
+    }
+    let cut: String = summary.chars().take(SUMMARY_MAX_CHARS).collect();
+    let cut = cut.rsplit_once(' ').map_or(cut.as_str(), |(head, _)| head);
+    format!("{cut}…", cut = cut.trim_end_matches([',', ';', ':', '.']))
+}
+
+/// Read the README of a repository; relative paths are resolved against it
+pub fn read_readme(repo_path: &Path, path: &Path) -> Result<String> {
+    let path = repo_path.join(path);
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {path}", path = path.display()))?;
+    ensure!(
+        !content.trim().is_empty(),
+        "{path} is empty",
+        path = path.display()
+    );
+    Ok(content)
+}
+
+/// Start the README article of a repository
+pub fn readme_builder(
+    repo_coordinate: &str,
+    repo_identifier: &str,
+    title: &str,
+    content: &str,
+) -> Result<EventBuilder> {
+    ensure!(!content.trim().is_empty(), "README content cannot be empty");
+
+    let tags = vec![
+        Tag::identifier(readme_identifier(repo_identifier)),
+        Tag::parse(["title", title])?,
+        Tag::parse(["summary", &summarize(content)])?,
+        Tag::parse(["a", repo_coordinate])?,
+    ];
+    Ok(EventBuilder::new(KIND_README, content).tags(tags))
+}
+
+/// Publish the README article of the signer's repository, with only its summary
+/// when `summary_only`
+///
+/// Returns the coordinate to link from the announcement.
+pub async fn publish_readme(
+    network: &Network,
+    signer: &dyn Signer,
+    repo_identifier: &str,
+    title: &str,
+    content: &str,
+    summary_only: bool,
+    relays: &[String],
+) -> Result<(String, BroadcastResult)> {
+    let summary;
+    let content = if summary_only {
+        summary = summarize(content);
+        ensure!(
+            !summary.is_empty(),
+            "The README has no paragraph to summarize"
+        );
+        summary.as_str()
+    } else {
+        content
+    };
+    let repo_coordinate = format!(
+        "{kind}:{owner}:{repo_identifier}",
+        kind = KIND_GIT_REPO_ANNOUNCEMENT,
+        owner = signer.public_key().to_hex()
+    );
+    let builder = readme_builder(&repo_coordinate, repo_identifier, title, content)?;
+    let event = signer::sign(signer, builder).await?;
+
+    let client = relay::connect(network, relays, Some(signer)).await?;
+    let result = relay::publish_events(&client, &[event]).await?;
+    client.disconnect().await;
+    Ok((
+        readme_coordinate(&signer.public_key(), repo_identifier),
+        result,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::Keys;
+
+    const README: &str = "\
+# gitsmith
+
+[![CI](https://example.com/badge.svg)](https://example.com/ci)
+
+Publish git repositories to Nostr,
+without a forge.
+
+## Install
+
+```
+cargo install gitsmith
+```
+";
+
+    #[test]
+    fn test_summarize() {
+        assert_eq!(
+            summarize(README),
+            "Publish git repositories to Nostr, without a forge."
+        );
+        assert_eq!(summarize("# Title only\n"), "");
+
+        let long = "word ".repeat(100);
+        let summary = summarize(&long);
+        assert!(summary.ends_with("word…"));
+        assert!(summary.chars().count() <= SUMMARY_MAX_CHARS + 1);
+    }
+
+    #[test]
+    fn test_readme_builder() {
+        let keys = Keys::generate();
+        let repo = format!("30617:{owner}:gitsmith", owner = keys.public_key().to_hex());
+        let event = readme_builder(&repo, "gitsmith", "gitsmith", README)
+            .unwrap()
+            .sign_with_keys(&keys)
+            .unwrap();
+        assert_eq!(event.kind, KIND_README);
+        assert_eq!(event.content, README);
+        assert_eq!(event.tags.identifier(), Some("gitsmith-readme"));
+        let value = |name: &str| {
+            event
+                .tags
+                .iter()
+                .find(|tag| tag.kind().as_str() == name)
+                .and_then(|tag| tag.content().map(String::from))
+        };
+        assert_eq!(value("a"), Some(repo.clone()));
+        assert_eq!(value("title").as_deref(), Some("gitsmith"));
+        assert_eq!(
+            value("summary").as_deref(),
+            Some("Publish git repositories to Nostr, without a forge.")
+        );
+        assert_eq!(
+            readme_coordinate(&keys.public_key(), "gitsmith"),
+            format!(
+                "30023:{owner}:gitsmith-readme",
+                owner = keys.public_key().to_hex()
+            )
+        );
+
+        assert!(readme_builder(&repo, "gitsmith", "gitsmith", " \n").is_err());
+    }
+}
@@ -0,0 +1,958 @@
+use nostr::{Event, EventId, Filter, RelayMessage, RelayUrl, SubscriptionId, Timestamp};
+use nostr_sdk::client::{Connection, ConnectionTarget};
+use nostr_sdk::{Client, ClientOptions, RelayPoolNotification, RelayStatus};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+
+use crate::cache::CacheStore;
+use crate::config;
+use crate::error::{Context, Result, bail};
+use crate::signer::Signer;
+use crate::types::BroadcastResult;
+
+/// How long `connect` waits for a relay connection
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest wait for relays to deliver stored events, fetches end as soon as
+/// every relay sent EOSE
+pub const DEFAULT_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often relay statuses are checked while waiting for connections
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Not enough relays connected within the timeout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayConnectionError {
+    pub required: usize,
+    pub connected: usize,
+    /// Relays that were not connected, with their last status
+    pub failed: Vec<(String, String)>,
+}
+
+impl fmt::Display for RelayConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Connected to {connected} relay(s), {required} required",
+            connected = self.connected,
+            required = self.required
+        )?;
+        if !self.failed.is_empty() {
+            let failed: Vec<String> = self
+                .failed
+                .iter()
+                .map(|(relay, status)| format!("{relay} ({status})"))
+                .collect();
+            write!(f, "; not connected: {failed}", failed = failed.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RelayConnectionError {}
+
+/// Progress of the relay connections towards the required minimum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConnectionProgress {
+    /// Enough relays are connected
+    Ready(usize),
+    /// Some relays are still connecting
+    Waiting,
+    /// The minimum can no longer be reached
+    Unreachable,
+}
+
+fn connection_progress(statuses: &[RelayStatus], min_relays: usize) -> ConnectionProgress {
+    let connected = statuses
+        .iter()
+        .filter(|status| **status == RelayStatus::Connected)
+        .count();
+    // Terminated and banned relays won't reconnect on their own
+    let possible = statuses
+        .iter()
+        .filter(|status| !matches!(status, RelayStatus::Terminated | RelayStatus::Banned))
+        .count();
+
+    if connected >= min_relays {
+        ConnectionProgress::Ready(connected)
+    } else if possible < min_relays {
+        ConnectionProgress::Unreachable
+    } else {
+        ConnectionProgress::Waiting
+    }
+}
+
+/// Wait until at least `min_relays` relays of the client's pool are connected
+///
+/// Returns the number of connected relays as soon as the minimum is reached.
+pub async fn wait_for_connections(
+    client: &Client,
+    min_relays: usize,
+    timeout: Duration,
+) -> Result<usize, RelayConnectionError> {
+    let deadline = tokio::time::Instant::now() + timeout;
+
+    loop {
+        let relays = client.pool().relays().await;
+        let statuses: Vec<(String, RelayStatus)> = relays
+            .iter()
+            .map(|(url, relay)| (url.to_string(), relay.status()))
+            .collect();
+        let progress = connection_progress(
+            &statuses
+                .iter()
+                .map(|(_, status)| *status)
+                .collect::<Vec<_>>(),
+            min_relays,
+        );
+
+        match progress {
+            ConnectionProgress::Ready(connected) => {
+                debug!("{connected} relay(s) connected");
+                return Ok(connected);
+            }
+            ConnectionProgress::Waiting if tokio::time::Instant::now() < deadline => {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            _ => {
+                let mut failed: Vec<(String, String)> = statuses
+                    .iter()
+                    .filter(|(_, status)| *status != RelayStatus::Connected)
+                    .map(|(url, status)| (url.clone(), status.to_string()))
+                    .collect();
+                failed.sort();
+                return Err(RelayConnectionError {
+                    required: min_relays,
+                    connected: statuses.len() - failed.len(),
+                    failed,
+                });
+            }
+        }
+    }
+}
+
+/// Create a client for the given relays and connect to them
+///
+/// Waits until at least one relay is connected.
+pub async fn connect(
+    network: &Network,
+    relays: &[String],
+    signer: Option<&dyn Signer>,
+) -> Result<Client> {
+    connect_with_minimum(network, relays, signer, 1, DEFAULT_CONNECT_TIMEOUT).await
+}
+
+/// Git config key holding the SOCKS5 proxy relays are reached through
+pub const PROXY_KEY: &str = "nostr.proxy";
+
+/// Environment variable overriding the configured proxy
+pub const PROXY_ENV: &str = "GITSMITH_PROXY";
+
+/// Environment variable overriding the configured default relays
+pub const DEFAULT_RELAYS_ENV: &str = "GITSMITH_RELAYS";
+
+/// How clients reach relays and where fetched events are cached
+///
+/// Built once by the caller from its flags and configuration and passed to
+/// everything that connects to relays.
+#[derive(Clone)]
+pub struct Network {
+    /// SOCKS5 proxy (Tor for example) every relay, `.onion` ones included, is
+    /// reached through
+    pub proxy: Option<SocketAddr>,
+    /// Answer NIP-42 AUTH challenges of relays
+    pub authentication: bool,
+    /// Cache of fetched events, synced fetches only ask relays for newer ones
+    pub cache: Option<Arc<dyn CacheStore>>,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            authentication: true,
+            cache: None,
+        }
+    }
+}
+
+impl fmt::Debug for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Network")
+            .field("proxy", &self.proxy)
+            .field("authentication", &self.authentication)
+            .field("cache", &self.cache.is_some())
+            .finish()
+    }
+}
+
+/// Parse a SOCKS5 proxy given as `socks5://host:port`, `socks5h://host:port` or `host:port`
+pub fn parse_proxy(value: &str) -> Result<SocketAddr> {
+    let value = value.trim();
+    let address = match value.split_once("://") {
+        Some(("socks5" | "socks5h", address)) => address,
+        Some((scheme, _)) => bail!("Unsupported proxy scheme {scheme}, only socks5 is supported"),
+        None => value,
+    };
+    let address = address.trim_end_matches('/');
+    address
+        .to_socket_addrs()
+        .with_context(|| format!("Invalid proxy address {address}, expected host:port"))?
+        .next()
+        .with_context(|| format!("Proxy host {address} did not resolve"))
+}
+
+/// Read the proxy configured for the repository containing `repo_path`
+///
+/// `GITSMITH_PROXY` takes precedence over the git config, outside a
+/// repository the global git config is read. The `proxy` of the gitsmith
+/// configuration file comes last.
+pub fn configured_proxy(repo_path: &Path) -> Result<Option<SocketAddr>> {
+    let value = match std::env::var(PROXY_ENV) {
+        Ok(value) => Some(value),
+        Err(_) => {
+            let config = match git2::Repository::discover(repo_path) {
+                Ok(repo) => repo.config(),
+                Err(_) => git2::Config::open_default(),
+            };
+            match config
+                .ok()
+                .and_then(|config| config.get_string(PROXY_KEY).ok())
+            {
+                Some(value) => Some(value),
+                None => config::load()?.string("proxy").map(String::from),
+            }
+        }
+    };
+    value
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| parse_proxy(&value))
+        .transpose()
+}
+
+/// Client signing with `signer`, which also answers the relays' AUTH challenges
+///
+/// Clients without a signer can't authenticate, relays that demand AUTH
+/// before serving reads close their subscriptions. With a proxy in `network`
+/// every relay, `.onion` ones included, is reached through it.
+pub fn new_client(network: &Network, signer: Option<&dyn Signer>) -> Client {
+    let mut options = ClientOptions::new().automatic_authentication(network.authentication);
+    if let Some(proxy) = network.proxy {
+        options = options.connection(Connection::new().proxy(proxy).target(ConnectionTarget::All));
+    }
+    let builder = Client::builder().opts(options);
+    match signer {
+        Some(signer) => builder.signer(signer.nostr_signer()).build(),
+        None => builder.build(),
+    }
+}
+
+/// Whether the client answers AUTH challenges, see [`Network::authentication`]
+fn authenticates(client: &Client) -> bool {
+    client.pool().state().is_auto_authentication_enabled()
+}
+
+/// Explain a relay's error when it demanded NIP-42 AUTH that didn't happen
+fn describe_auth_failure(message: &str, enabled: bool) -> Option<String> {
+    let refused = message == "authentication failed";
+    if !refused && !message.starts_with("auth-required:") {
+        return None;
+    }
+    let reason = if !enabled {
+        "authentication is turned off with --no-auth"
+    } else if refused {
+        "the relay rejected the account's AUTH or the signer declined to sign it"
+    } else {
+        "the account did not authenticate"
+    };
+    Some(format!(
+        "{message} (the relay requires NIP-42 AUTH, {reason})"
+    ))
+}
+
+/// Create a client for the given relays and wait until `min_relays` are connected
+pub async fn connect_with_minimum(
+    network: &Network,
+    relays: &[String],
+    signer: Option<&dyn Signer>,
+    min_relays: usize,
+    timeout: Duration,
+) -> Result<Client> {
+    let client = new_client(network, signer);
+
+    for relay_url in relays {
+        debug!(%relay_url, "Adding relay");
+        client.add_relay(relay_url).await?;
+    }
+
+    info!("Connecting to {count} relay(s)", count = relays.len());
+    client.connect().await;
+    wait_for_connections(&client, min_relays, timeout).await?;
+
+    Ok(client)
+}
+
+/// Connection status of a single relay
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RelayHealth {
+    pub url: String,
+    pub connected: bool,
+    pub status: String,
+}
+
+/// Try to connect to every relay and report which ones are reachable
+pub async fn check_relays(
+    network: &Network,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<Vec<RelayHealth>> {
+    let client = new_client(network, None);
+    for relay_url in relays {
+        client.add_relay(relay_url).await?;
+    }
+    client.connect().await;
+
+    // Failing relays are reported below, so not reaching all of them is fine
+    let _ = wait_for_connections(&client, relays.len(), timeout).await;
+
+    let pool = client.pool().relays().await;
+    let health = relays
+        .iter()
+        .map(|url| {
+            let status = pool
+                .iter()
+                .find(|(relay_url, _)| same_relay(&relay_url.to_string(), url))
+                .map(|(_, relay)| relay.status());
+            RelayHealth {
+                url: url.clone(),
+                connected: status == Some(RelayStatus::Connected),
+                status: status.map(|s| s.to_string()).unwrap_or_default(),
+            }
+        })
+        .collect();
+    client.disconnect().await;
+
+    Ok(health)
+}
+
+/// Differences between the locally configured relays and the announced ones
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RelayDrift {
+    /// Configured locally but missing from the announcement
+    pub only_local: Vec<String>,
+    /// Announced but not configured locally
+    pub only_remote: Vec<String>,
+}
+
+impl RelayDrift {
+    pub fn is_empty(&self) -> bool {
+        self.only_local.is_empty() && self.only_remote.is_empty()
+    }
+}
+
+/// Compare two relay lists, ignoring order and trailing slashes
+pub fn relay_drift(local: &[String], remote: &[String]) -> RelayDrift {
+    let missing_from = |list: &[String], other: &[String]| -> Vec<String> {
+        list.iter()
+            .filter(|relay| !other.iter().any(|o| same_relay(o, relay)))
+            .cloned()
+            .collect()
+    };
+
+    RelayDrift {
+        only_local: missing_from(local, remote),
+        only_remote: missing_from(remote, local),
+    }
+}
+
+/// Whether two relay URLs name the same relay, ignoring case and trailing slashes
+pub fn same_relay(a: &str, b: &str) -> bool {
+    a.trim_end_matches('/')
+        .eq_ignore_ascii_case(b.trim_end_matches('/'))
+}
+
+/// Fetch events matching any of the filters, de-duplicated by event ID
+///
+/// The filters are fetched concurrently, each returns once every relay sent
+/// EOSE, so `timeout` only bounds the wait for slow relays.
+pub async fn fetch_events(
+    network: &Network,
+    client: &Client,
+    filters: Vec<Filter>,
+    timeout: Duration,
+) -> Result<Vec<Event>> {
+    let fetches = filters
+        .into_iter()
+        .map(|filter| client.fetch_events(filter, timeout));
+    let mut seen = HashSet::new();
+    let mut events = Vec::new();
+    for found in futures::future::join_all(fetches).await {
+        for event in found? {
+            if seen.insert(event.id) {
+                events.push(event);
+            }
+        }
+    }
+
+    debug!("Fetched {count} unique event(s)", count = events.len());
+    if let Some(cache) = &network.cache
+        && let Err(e) = cache.save_events(&events)
+    {
+        warn!("Failed to cache fetched events: {e}");
+    }
+    Ok(events)
+}
+
+/// Relays are asked for events this much older than their last complete sync,
+/// so events published late or by clocks running behind aren't missed
+const SYNC_OVERLAP_SECS: u64 = 10 * 60;
+
+/// Cache key of the sync mark of `scope` on `relay`
+fn sync_key(relay: &RelayUrl, scope: &str) -> String {
+    format!(
+        "{relay}|{scope}",
+        relay = relay.as_str().trim_end_matches('/')
+    )
+}
+
+/// Fetch events matching any of the filters, together with their cached matches
+///
+/// With a cache in `network` every relay is only asked for events since its last
+/// complete sync of `scope`, minus an overlap. A sync is complete once the
+/// relay sent EOSE for every filter, relays that time out or close a
+/// subscription are asked for everything again next time. Without a cache
+/// every relay is asked for all matching events.
+pub async fn fetch_events_synced(
+    network: &Network,
+    client: &Client,
+    scope: &str,
+    filters: Vec<Filter>,
+    timeout: Duration,
+) -> Result<Vec<Event>> {
+    sync_events(client, network.cache.clone(), scope, filters, timeout).await
+}
+
+async fn sync_events(
+    client: &Client,
+    cache: Option<Arc<dyn CacheStore>>,
+    scope: &str,
+    filters: Vec<Filter>,
+    timeout: Duration,
+) -> Result<Vec<Event>> {
+    let started = Timestamp::now();
+    // Subscribe to notifications first so no EOSE is missed
+    let mut notifications = client.notifications();
+
+    let mut pending: HashMap<RelayUrl, HashSet<SubscriptionId>> = HashMap::new();
+    let mut incomplete: HashSet<RelayUrl> = HashSet::new();
+    for url in client.relays().await.into_keys() {
+        let mark = match &cache {
+            Some(cache) => cache.sync_mark(&sync_key(&url, scope)).unwrap_or_else(|e| {
+                warn!("Failed to read the sync mark of {url}: {e}");
+                None
+            }),
+            None => None,
+        };
+        let since =
+            mark.map(|mark| Timestamp::from(mark.as_u64().saturating_sub(SYNC_OVERLAP_SECS)));
+
+        for filter in &filters {
+            let filter = match since {
+                Some(since) if filter.since.is_none_or(|current| current < since) => {
+                    filter.clone().since(since)
+                }
+                _ => filter.clone(),
+            };
+            match client.subscribe_to([url.clone()], filter, None).await {
+                Ok(output) if output.success.contains(&url) => {
+                    pending.entry(url.clone()).or_default().insert(output.val);
+                }
+                Ok(_) => {
+                    incomplete.insert(url.clone());
+                }
+                Err(e) => {
+                    debug!("Failed to subscribe on {url}: {e}");
+                    incomplete.insert(url.clone());
+                }
+            }
+        }
+    }
+    let subscriptions: HashSet<SubscriptionId> = pending.values().flatten().cloned().collect();
+
+    let mut seen = HashSet::new();
+    let mut fetched = Vec::new();
+    let deadline = tokio::time::sleep(timeout);
+    tokio::pin!(deadline);
+    while pending.values().any(|waiting| !waiting.is_empty()) {
+        let notification = tokio::select! {
+            _ = &mut deadline => break,
+            notification = notifications.recv() => notification,
+        };
+        let (relay_url, message) = match notification {
+            Ok(RelayPoolNotification::Message { relay_url, message }) => (relay_url, message),
+            Ok(_) => continue,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                // Dropped notifications may have held events, no relay is complete
+                warn!("Missed {skipped} relay notification(s)");
+                incomplete.extend(pending.keys().cloned());
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        match message {
+            RelayMessage::Event {
+                subscription_id,
+                event,
+            } if subscriptions.contains(subscription_id.as_ref()) && seen.insert(event.id) => {
+                fetched.push(event.into_owned());
+            }
+            RelayMessage::EndOfStoredEvents(subscription_id) => {
+                if let Some(waiting) = pending.get_mut(&relay_url) {
+                    waiting.remove(subscription_id.as_ref());
+                }
+            }
+            RelayMessage::Closed {
+                subscription_id,
+                message,
+            } if subscriptions.contains(subscription_id.as_ref()) => {
+                match describe_auth_failure(&message, authenticates(client)) {
+                    Some(error) => warn!("{relay_url} refused to serve events: {error}"),
+                    None => debug!("{relay_url} closed the subscription: {message}"),
+                }
+                if let Some(waiting) = pending.get_mut(&relay_url) {
+                    waiting.remove(subscription_id.as_ref());
+                }
+                incomplete.insert(relay_url);
+            }
+            _ => {}
+        }
+    }
+    for id in &subscriptions {
+        client.unsubscribe(id).await;
+    }
+    debug!("Fetched {count} unique event(s)", count = fetched.len());
+
+    let Some(cache) = cache else {
+        return Ok(fetched);
+    };
+    if let Err(e) = cache.save_events(&fetched) {
+        warn!("Failed to cache fetched events: {e}");
+        return Ok(fetched);
+    }
+    for (url, waiting) in &pending {
+        if waiting.is_empty()
+            && !incomplete.contains(url)
+            && let Err(e) = cache.set_sync_mark(&sync_key(url, scope), started)
+        {
+            warn!("Failed to save the sync mark of {url}: {e}");
+        }
+    }
+    let cached = cache.query(&filters)?;
+    Ok(merge_events(cached, fetched))
+}
+
+/// Both sets of events, each event once
+pub fn merge_events(mut events: Vec<Event>, more: Vec<Event>) -> Vec<Event> {
+    let mut seen: HashSet<_> = events.iter().map(|event| event.id).collect();
+    events.extend(more.into_iter().filter(|event| seen.insert(event.id)));
+    events
+}
+
+/// How events are sent to each relay
+#[derive(Debug, Clone)]
+pub struct PublishOptions {
+    /// Longest wait for a relay to answer one event
+    pub relay_timeout: Duration,
+    /// Extra attempts after a timeout, connection error or transient rejection
+    pub retries: u32,
+    /// Wait before the first retry, doubled for every further one
+    pub backoff: Duration,
+}
+
+impl Default for PublishOptions {
+    fn default() -> Self {
+        Self {
+            relay_timeout: Duration::from_secs(10),
+            retries: 2,
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Whether a relay's rejection may go away when the event is sent again
+///
+/// NIP-01 prefixes like `blocked:`, `pow:` or `invalid:` are final.
+fn is_transient_rejection(message: &str) -> bool {
+    message.starts_with("rate-limited:") || message.starts_with("error:")
+}
+
+/// Backoff before retry number `attempt` (from 0), randomized by ±50% so
+/// clients don't retry in lockstep
+fn retry_delay(backoff: Duration, attempt: u32) -> Duration {
+    let base = backoff.saturating_mul(2u32.saturating_pow(attempt));
+    base.mul_f64(rand::random_range(0.5..1.5))
+}
+
+/// Send events to all connected relays and aggregate the per-relay results
+///
+/// See [`broadcast_events`].
+pub async fn publish_events(client: &Client, events: &[Event]) -> Result<BroadcastResult> {
+    broadcast_events(client, events, &PublishOptions::default()).await
+}
+
+/// Send signed events to all of the client's relays concurrently
+///
+/// Each relay gets the events in order, so later events can reference earlier
+/// ones. A relay counts as a success once it accepted every event, it is
+/// listed in the failures with its first error otherwise.
+pub async fn broadcast_events(
+    client: &Client,
+    events: &[Event],
+    options: &PublishOptions,
+) -> Result<BroadcastResult> {
+    let relays: Vec<RelayUrl> = client.relays().await.into_keys().collect();
+    let sends = relays
+        .iter()
+        .map(|url| send_to_relay(client, url, events, options));
+    let outcomes = futures::future::join_all(sends).await;
+
+    let mut successes = Vec::new();
+    let mut failures = Vec::new();
+    let mut accepted = BTreeMap::new();
+    for (url, (ids, error)) in relays.iter().zip(outcomes) {
+        let relay = url.to_string();
+        match error {
+            None => successes.push(relay.clone()),
+            Some(error) => failures.push((relay.clone(), error)),
+        }
+        accepted.insert(relay, ids);
+    }
+    successes.sort();
+    failures.sort();
+
+    Ok(BroadcastResult {
+        event_ids: events.iter().map(|e| e.id).collect(),
+        successes,
+        failures,
+        accepted,
+    })
+}
+
+/// Send the events to one relay, returning the accepted IDs and the first error
+async fn send_to_relay(
+    client: &Client,
+    url: &RelayUrl,
+    events: &[Event],
+    options: &PublishOptions,
+) -> (Vec<EventId>, Option<String>) {
+    let mut accepted = Vec::new();
+    let mut first_error = None;
+    for event in events {
+        let mut attempt = 0;
+        let error = loop {
+            debug!(id = %event.id, kind = %event.kind, %url, attempt, "Sending event");
+            let sent = tokio::time::timeout(
+                options.relay_timeout,
+                client.send_event_to([url.clone()], event),
+            )
+            .await;
+            let error = match sent {
+                Ok(Ok(output)) if output.success.contains(url) => break None,
+                Ok(Ok(output)) => match output.failed.get(url) {
+                    // The relay already has it, from an earlier attempt or another client
+                    Some(message) if message.starts_with("duplicate:") => break None,
+                    Some(message)
+                        if let Some(error) =
+                            describe_auth_failure(message, authenticates(client)) =>
+                    {
+                        break Some(error);
+                    }
+                    Some(message) if !is_transient_rejection(message) => {
+                        break Some(message.clone());
+                    }
+                    Some(message) => message.clone(),
+                    None => "no answer".to_string(),
+                },
+                Ok(Err(e)) => {
+                    let error = e.to_string();
+                    if let Some(error) = describe_auth_failure(&error, authenticates(client)) {
+                        break Some(error);
+                    }
+                    error
+                }
+                Err(_) => format!(
+                    "timed out after {secs}s",
+                    secs = options.relay_timeout.as_secs()
+                ),
+            };
+            if attempt >= options.retries {
+                break Some(error);
+            }
+            let delay = retry_delay(options.backoff, attempt);
+            debug!(%url, %error, ?delay, "Retrying event");
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        };
+        match error {
+            None => accepted.push(event.id),
+            Some(error) => {
+                warn!(id = %event.id, %url, %error, "Relay did not accept event");
+                first_error.get_or_insert(error);
+            }
+        }
+    }
+    (accepted, first_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_progress() {
+        use RelayStatus::*;
+
+        assert_eq!(
+            connection_progress(&[Connected, Connecting], 1),
+            ConnectionProgress::Ready(1)
+        );
+        assert_eq!(
+            connection_progress(&[Connected, Connecting], 2),
+            ConnectionProgress::Waiting
+        );
+        assert_eq!(
+            connection_progress(&[Connected, Terminated, Banned], 2),
+            ConnectionProgress::Unreachable
+        );
+        // A threshold above the number of relays can never be met
+        assert_eq!(
+            connection_progress(&[Connecting], 2),
+            ConnectionProgress::Unreachable
+        );
+    }
+
+    #[test]
+    fn test_relay_drift() {
+        let local = vec![
+            "wss://relay.damus.io".to_string(),
+            "wss://nos.lol/".to_string(),
+        ];
+        let remote = vec![
+            "wss://nos.lol".to_string(),
+            "wss://relay.nostr.band".to_string(),
+        ];
+
+        let drift = relay_drift(&local, &remote);
+        assert_eq!(drift.only_local, vec!["wss://relay.damus.io".to_string()]);
+        assert_eq!(
+            drift.only_remote,
+            vec!["wss://relay.nostr.band".to_string()]
+        );
+        assert!(relay_drift(&local, &local).is_empty());
+    }
+
+    #[test]
+    fn test_relay_connection_error_display() {
+        let error = RelayConnectionError {
+            required: 2,
+            connected: 1,
+            failed: vec![(
+                "wss://down.example.com".to_string(),
+                "Terminated".to_string(),
+            )],
+        };
+        assert_eq!(
+            error.to_string(),
+            "Connected to 1 relay(s), 2 required; not connected: wss://down.example.com (Terminated)"
+        );
+    }
+
+    #[test]
+    fn test_merge_events() {
+        use nostr::{EventBuilder, Keys};
+
+        let keys = Keys::generate();
+        let note = |content: &str| {
+            EventBuilder::text_note(content)
+                .sign_with_keys(&keys)
+                .unwrap()
+        };
+        let (cached, fetched) = (note("cached"), note("fetched"));
+
+        let merged = merge_events(vec![cached.clone()], vec![fetched.clone(), cached.clone()]);
+        assert_eq!(merged, vec![cached, fetched]);
+    }
+
+    #[tokio::test]
+    async fn test_sync_events_only_fetches_new_events() {
+        use crate::cache::MemoryStore;
+        use crate::localnet::{self, LocalRelay};
+        use nostr::{EventBuilder, Keys, Kind};
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let url = localnet::relay_url(listener.local_addr().unwrap().port());
+        tokio::spawn(LocalRelay::new(Arc::new(MemoryStore::new())).serve(listener));
+        let client = connect(&Network::default(), std::slice::from_ref(&url), None)
+            .await
+            .unwrap();
+
+        let keys = Keys::generate();
+        let note = |content: &str, created_at: u64| {
+            EventBuilder::text_note(content)
+                .custom_created_at(Timestamp::from(created_at))
+                .sign_with_keys(&keys)
+                .unwrap()
+        };
+        let now = Timestamp::now().as_u64();
+        let filters = vec![Filter::new().author(keys.public_key()).kind(Kind::TextNote)];
+        let cache: Arc<dyn CacheStore> = Arc::new(MemoryStore::new());
+        let sync = || {
+            sync_events(
+                &client,
+                Some(cache.clone()),
+                "notes",
+                filters.clone(),
+                Duration::from_secs(5),
+            )
+        };
+
+        let first = note("first", now - 60);
+        client.send_event(&first).await.unwrap();
+        assert_eq!(sync().await.unwrap(), vec![first.clone()]);
+        assert!(cache.sync_mark(&format!("{url}|notes")).unwrap().is_some());
+
+        // Backdated past the overlap, so the relay isn't asked for it again
+        let backdated = note("backdated", now - 2 * SYNC_OVERLAP_SECS);
+        let second = note("second", now);
+        client.send_event(&backdated).await.unwrap();
+        client.send_event(&second).await.unwrap();
+        assert_eq!(sync().await.unwrap(), vec![second, first]);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_events_ends_on_eose() {
+        use crate::cache::MemoryStore;
+        use crate::localnet::{self, LocalRelay};
+        use nostr::{EventBuilder, Keys, Kind};
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let url = localnet::relay_url(listener.local_addr().unwrap().port());
+        tokio::spawn(LocalRelay::new(Arc::new(MemoryStore::new())).serve(listener));
+        let client = connect(&Network::default(), std::slice::from_ref(&url), None)
+            .await
+            .unwrap();
+
+        let keys = Keys::generate();
+        let note = EventBuilder::text_note("hello")
+            .sign_with_keys(&keys)
+            .unwrap();
+        client.send_event(&note).await.unwrap();
+
+        let filters = vec![
+            Filter::new().author(keys.public_key()),
+            Filter::new().kind(Kind::TextNote).author(keys.public_key()),
+            Filter::new().kind(Kind::Metadata).author(keys.public_key()),
+        ];
+        let started = std::time::Instant::now();
+        let found = fetch_events(
+            &Network::default(),
+            &client,
+            filters,
+            Duration::from_secs(30),
+        )
+        .await
+        .unwrap();
+        assert_eq!(found, vec![note]);
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_parse_proxy() {
+        let tor: SocketAddr = "127.0.0.1:9050".parse().unwrap();
+        assert_eq!(parse_proxy("socks5://127.0.0.1:9050").unwrap(), tor);
+        assert_eq!(parse_proxy("socks5h://127.0.0.1:9050/").unwrap(), tor);
+        assert_eq!(parse_proxy("127.0.0.1:9050").unwrap(), tor);
+        assert!(parse_proxy("http://127.0.0.1:8080").is_err());
+        assert!(parse_proxy("socks5://127.0.0.1").is_err());
+    }
+
+    #[test]
+    fn test_describe_auth_failure() {
+        let demanded = describe_auth_failure("auth-required: members only", true).unwrap();
+        assert!(demanded.contains("did not authenticate"), "{demanded}");
+        let refused = describe_auth_failure("authentication failed", true).unwrap();
+        assert!(refused.contains("declined"), "{refused}");
+        let disabled = describe_auth_failure("auth-required: members only", false).unwrap();
+        assert!(disabled.contains("--no-auth"), "{disabled}");
+        assert_eq!(describe_auth_failure("blocked: spam", true), None);
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let backoff = Duration::from_millis(100);
+        for attempt in 0..3 {
+            let base = 100 * 2u64.pow(attempt);
+            let delay = retry_delay(backoff, attempt).as_millis() as u64;
+            assert!((base / 2..=base * 3 / 2).contains(&delay), "{delay}ms");
+        }
+        assert!(is_transient_rejection("rate-limited: slow down"));
+        assert!(!is_transient_rejection("blocked: not allowed"));
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_events_reports_each_relay() {
+        use crate::cache::MemoryStore;
+        use crate::localnet::{self, LocalRelay};
+        use nostr::{EventBuilder, Keys};
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let live = localnet::relay_url(listener.local_addr().unwrap().port());
+        tokio::spawn(LocalRelay::new(Arc::new(MemoryStore::new())).serve(listener));
+        // Bound and dropped, nothing listens there anymore
+        let closed = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let dead = localnet::relay_url(closed.local_addr().unwrap().port());
+        drop(closed);
+        let client = connect(&Network::default(), &[live.clone(), dead.clone()], None)
+            .await
+            .unwrap();
+
+        let keys = Keys::generate();
+        let events: Vec<Event> = ["first", "second"]
+            .into_iter()
+            .map(|content| {
+                EventBuilder::text_note(content)
+                    .sign_with_keys(&keys)
+                    .unwrap()
+            })
+            .collect();
+        let options = PublishOptions {
+            relay_timeout: Duration::from_secs(2),
+            retries: 1,
+            backoff: Duration::from_millis(10),
+        };
+        let result = broadcast_events(&client, &events, &options).await.unwrap();
+        let ids: Vec<EventId> = events.iter().map(|event| event.id).collect();
+        assert_eq!(result.successes, vec![live.clone()]);
+        assert_eq!(result.failures.len(), 1);
+        assert_eq!(result.failures[0].0, dead);
+        assert_eq!(result.accepted[&live], ids);
+        assert!(result.accepted[&dead].is_empty());
+
+        // Relays answering duplicate already have the event
+        let client = connect(&Network::default(), std::slice::from_ref(&live), None)
+            .await
+            .unwrap();
+        let again = broadcast_events(&client, &events, &options).await.unwrap();
+        assert_eq!(again.successes, vec![live]);
+    }
+}
@@ -0,0 +1,226 @@
+//! NIP-65 relay lists (kind 10002) and outbox-model relay discovery
+
+use nostr::nips::nip65::{self, RelayMetadata};
+use nostr::{Event, EventBuilder, Filter, Kind, PublicKey, RelayUrl};
+use nostr_sdk::Client;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::error::Result;
+use crate::events;
+use crate::relay::{self, Network};
+use crate::signer::{self, Signer};
+use crate::types::BroadcastResult;
+
+/// At most this many relays from relay lists are added to a lookup
+pub const MAX_OUTBOX_RELAYS: usize = 8;
+
+/// Relays a user reads from and writes to
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RelayList {
+    /// Where others should send events for the user (inbox)
+    pub read: Vec<String>,
+    /// Where the user publishes (outbox)
+    pub write: Vec<String>,
+}
+
+impl RelayList {
+    /// Relay list with `both` used for reading and writing
+    pub fn new(both: &[String], read: &[String], write: &[String]) -> Self {
+        let mut list = Self::default();
+        for relay in both.iter().chain(read) {
+            push_unique(&mut list.read, relay);
+        }
+        for relay in both.iter().chain(write) {
+            push_unique(&mut list.write, relay);
+        }
+        list
+    }
+
+    pub fn from_event(event: &Event) -> Self {
+        let mut list = Self::default();
+        for (url, metadata) in nip65::extract_relay_list(event) {
+            let url = url.to_string();
+            if metadata != &Some(RelayMetadata::Write) {
+                push_unique(&mut list.read, &url);
+            }
+            if metadata != &Some(RelayMetadata::Read) {
+                push_unique(&mut list.write, &url);
+            }
+        }
+        list
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read.is_empty() && self.write.is_empty()
+    }
+
+    /// Every relay of the list, write relays first
+    pub fn all(&self) -> Vec<String> {
+        let mut all = self.write.clone();
+        for relay in &self.read {
+            push_unique(&mut all, relay);
+        }
+        all
+    }
+
+    /// Unsigned kind 10002 event, relays in both lists get no marker
+    pub fn to_event_builder(&self) -> Result<EventBuilder> {
+        let mut tags = Vec::new();
+        for relay in self.all() {
+            let metadata = match (self.read.contains(&relay), self.write.contains(&relay)) {
+                (true, false) => Some(RelayMetadata::Read),
+                (false, true) => Some(RelayMetadata::Write),
+                _ => None,
+            };
+            tags.push((RelayUrl::parse(&relay)?, metadata));
+        }
+        Ok(EventBuilder::relay_list(tags))
+    }
+}
+
+fn push_unique(relays: &mut Vec<String>, relay: &str) {
+    if !relays.iter().any(|known| relay::same_relay(known, relay)) {
+        relays.push(relay.trim_end_matches('/').to_string());
+    }
+}
+
+/// Newest relay list of each author
+pub async fn fetch_relay_lists(
+    network: &Network,
+    client: &Client,
+    authors: Vec<PublicKey>,
+    timeout: Duration,
+) -> Result<HashMap<PublicKey, RelayList>> {
+    let filter = Filter::new().kind(Kind::RelayList).authors(authors);
+    let mut found = relay::fetch_events(network, client, vec![filter], timeout).await?;
+    found.sort_by(events::newest_first);
+
+    let mut lists = HashMap::new();
+    for event in found {
+        lists
+            .entry(event.pubkey)
+            .or_insert_with(|| RelayList::from_event(&event));
+    }
+    Ok(lists)
+}
+
+/// Published relay list of a public key
+pub async fn fetch_relay_list(
+    network: &Network,
+    public_key: PublicKey,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<Option<RelayList>> {
+    let client = relay::connect(network, relays, None).await?;
+    let mut lists = fetch_relay_lists(network, &client, vec![public_key], timeout).await?;
+    client.disconnect().await;
+    Ok(lists.remove(&public_key))
+}
+
+/// Publish the signer's relay list to `relays`
+pub async fn publish_relay_list(
+    network: &Network,
+    signer: &dyn Signer,
+    relays: &[String],
+    list: &RelayList,
+) -> Result<BroadcastResult> {
+    let event = signer::sign(signer, list.to_event_builder()?).await?;
+    let client = relay::connect(network, relays, Some(signer)).await?;
+    relay::publish_events(&client, &[event]).await
+}
+
+/// Relays of the lists that aren't in `known`, at most [`MAX_OUTBOX_RELAYS`]
+///
+/// Write relays hold what the authors published, read relays what others
+/// sent them (comments, status changes), so both are included.
+pub fn outbox_relays<'a>(
+    lists: impl IntoIterator<Item = &'a RelayList>,
+    known: &[String],
+) -> Vec<String> {
+    let mut extra: Vec<String> = Vec::new();
+    for relay in lists.into_iter().flat_map(RelayList::all) {
+        if !known.iter().any(|known| relay::same_relay(known, &relay)) {
+            push_unique(&mut extra, &relay);
+        }
+    }
+    extra.truncate(MAX_OUTBOX_RELAYS);
+    extra
+}
+
+/// Connect to the relays the authors listed that aren't in `relays`
+///
+/// Returns None when the authors list no other relays or none of them can be
+/// reached, lookups then just use `relays`.
+pub async fn connect_outbox(
+    network: &Network,
+    client: &Client,
+    authors: Vec<PublicKey>,
+    relays: &[String],
+    timeout: Duration,
+) -> Option<Client> {
+    let lists = match fetch_relay_lists(network, client, authors, timeout).await {
+        Ok(lists) => lists,
+        Err(e) => {
+            warn!("Failed to fetch relay lists: {e}");
+            return None;
+        }
+    };
+    let extra = outbox_relays(lists.values(), relays);
+    if extra.is_empty() {
+        return None;
+    }
+
+    debug!("Also looking up events on the authors' relays: {extra:?}");
+    match relay::connect(network, &extra, None).await {
+        Ok(outbox) => Some(outbox),
+        Err(e) => {
+            warn!("Failed to connect to the authors' relays: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::Keys;
+
+    #[test]
+    fn test_relay_list_round_trip() {
+        let list = RelayList::new(
+            &["wss://both.example".to_string()],
+            &["wss://inbox.example/".to_string()],
+            &["wss://outbox.example".to_string()],
+        );
+        let event = list
+            .to_event_builder()
+            .unwrap()
+            .sign_with_keys(&Keys::generate())
+            .unwrap();
+
+        assert_eq!(event.kind, Kind::RelayList);
+        let parsed = RelayList::from_event(&event);
+        assert_eq!(parsed.read, ["wss://both.example", "wss://inbox.example"]);
+        assert_eq!(parsed.write, ["wss://both.example", "wss://outbox.example"]);
+    }
+
+    #[test]
+    fn test_outbox_relays() {
+        let alice = RelayList::new(
+            &["wss://repo.example".to_string()],
+            &[],
+            &["wss://alice.example".to_string()],
+        );
+        let bob = RelayList::new(&["wss://ALICE.example/".to_string()], &[], &[]);
+        let known = ["wss://repo.example/".to_string()];
+
+        assert_eq!(
+            outbox_relays([&alice, &bob], &known),
+            ["wss://alice.example"]
+        );
+        assert!(outbox_relays([&RelayList::default()], &known).is_empty());
+    }
+}
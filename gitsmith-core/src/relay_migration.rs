@@ -0,0 +1,297 @@
+//! Moving a repository's events from one relay to another
+//!
+//! The history of a repository is its announcement and state events plus
+//! everything that references them: pull requests, patches, issues, status
+//! events and comments, followed through `e`/`E` tags until no new events
+//! turn up. [`migrate_history`] copies it to the new relay as signed and
+//! checks the new relay returns every event before the caller re-announces.
+
+use nostr::{Alphabet, Event, EventId, Filter, Kind, PublicKey, SingleLetterTag};
+use nostr_sdk::Client;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
+
+use crate::error::{Context, Result, ensure};
+use crate::events;
+use crate::relay::{self, Network};
+
+/// Event IDs asked for per filter, relays reject overly large filters
+const IDS_PER_FILTER: usize = 256;
+
+/// Outcome of copying a repository's history to another relay
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationReport {
+    /// Events found on the old relay
+    pub found: usize,
+    /// Events the new relay accepted
+    pub copied: usize,
+    /// Events the new relay doesn't return after copying
+    pub missing: Vec<EventId>,
+    /// Rejection messages of the new relay, by event
+    pub rejected: BTreeMap<EventId, String>,
+}
+
+impl MigrationReport {
+    /// Whether the new relay holds the whole history
+    pub fn is_verified(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+/// Every event of a repository the client's relays have
+///
+/// `authors` are the owner and maintainers whose announcement and state
+/// events belong to the repository.
+pub async fn fetch_repository_history(
+    network: &Network,
+    client: &Client,
+    repo_coordinate: &str,
+    authors: &[PublicKey],
+    timeout: Duration,
+) -> Result<Vec<Event>> {
+    let (_kind, _pubkey, identifier) = crate::patches::parse_repo_coordinate(repo_coordinate)?;
+    let filters = vec![
+        Filter::new()
+            .kinds([
+                Kind::from(events::KIND_GIT_REPO_ANNOUNCEMENT),
+                Kind::from(events::KIND_GIT_STATE),
+            ])
+            .authors(authors.to_vec())
+            .identifier(identifier),
+        Filter::new().custom_tag(SingleLetterTag::lowercase(Alphabet::A), repo_coordinate),
+        Filter::new().custom_tag(SingleLetterTag::uppercase(Alphabet::A), repo_coordinate),
+    ];
+    let mut history = relay::fetch_events(network, client, filters, timeout).await?;
+
+    // Follow replies, statuses and comments until nothing new turns up
+    let mut seen: HashSet<EventId> = history.iter().map(|event| event.id).collect();
+    let mut frontier: Vec<EventId> = seen.iter().copied().collect();
+    while !frontier.is_empty() {
+        let filters = frontier
+            .chunks(IDS_PER_FILTER)
+            .flat_map(|ids| {
+                let ids: Vec<String> = ids.iter().map(EventId::to_hex).collect();
+                [
+                    Filter::new().custom_tags(SingleLetterTag::lowercase(Alphabet::E), ids.clone()),
+                    Filter::new().custom_tags(SingleLetterTag::uppercase(Alphabet::E), ids),
+                ]
+            })
+            .collect();
+        let found: Vec<Event> = relay::fetch_events(network, client, filters, timeout)
+            .await?
+            .into_iter()
+            .filter(|event| seen.insert(event.id))
+            .collect();
+        frontier = found.iter().map(|event| event.id).collect();
+        history.extend(found);
+    }
+
+    history.sort_by_key(|event| event.created_at);
+    Ok(history)
+}
+
+/// IDs of the events the client's relays don't return
+pub async fn missing_events(
+    network: &Network,
+    client: &Client,
+    ids: &[EventId],
+    timeout: Duration,
+) -> Result<Vec<EventId>> {
+    let filters = ids
+        .chunks(IDS_PER_FILTER)
+        .map(|chunk| Filter::new().ids(chunk.iter().copied()))
+        .collect();
+    let found: HashSet<EventId> = relay::fetch_events(network, client, filters, timeout)
+        .await?
+        .into_iter()
+        .map(|event| event.id)
+        .collect();
+    Ok(ids
+        .iter()
+        .filter(|id| !found.contains(id))
+        .copied()
+        .collect())
+}
+
+/// Copy a repository's history from one relay to another and verify it
+///
+/// Events are sent oldest first and as they are, the pre-sign hook is not
+/// applied since they were signed when first published.
+pub async fn migrate_history(
+    network: &Network,
+    repo_coordinate: &str,
+    authors: &[PublicKey],
+    from: &str,
+    to: &str,
+    timeout: Duration,
+) -> Result<MigrationReport> {
+    ensure!(
+        !relay::same_relay(from, to),
+        "The old and new relay are the same: {to}"
+    );
+    let source = relay::connect_with_minimum(network, &[from.to_string()], None, 1, timeout)
+        .await
+        .with_context(|| format!("Failed to connect to {from}"))?;
+    let history =
+        fetch_repository_history(network, &source, repo_coordinate, authors, timeout).await;
+    source.disconnect().await;
+    let history = history?;
+
+    let target = relay::connect_with_minimum(network, &[to.to_string()], None, 1, timeout)
+        .await
+        .with_context(|| format!("Failed to connect to {to}"))?;
+    let mut copied = 0;
+    let mut rejected = BTreeMap::new();
+    for event in &history {
+        let output = target.send_event(event).await?;
+        if output.success.is_empty() {
+            let message = output.failed.into_values().next().unwrap_or_default();
+            rejected.insert(event.id, message);
+        } else {
+            copied += 1;
+        }
+    }
+    let ids: Vec<EventId> = history.iter().map(|event| event.id).collect();
+    let missing = missing_events(network, &target, &ids, timeout).await;
+    target.disconnect().await;
+
+    Ok(MigrationReport {
+        found: history.len(),
+        copied,
+        missing: missing?,
+        rejected,
+    })
+}
+
+/// Relay list with `from` replaced by `to` in place, `to` listed once
+pub fn replace_relay(relays: &[String], from: &str, to: &str) -> Vec<String> {
+    let mut replaced: Vec<String> = Vec::with_capacity(relays.len() + 1);
+    for relay in relays {
+        let relay = if relay::same_relay(relay, from) {
+            to
+        } else {
+            relay.as_str()
+        };
+        if !replaced.iter().any(|kept| relay::same_relay(kept, relay)) {
+            replaced.push(relay.to_string());
+        }
+    }
+    if !replaced.iter().any(|kept| relay::same_relay(kept, to)) {
+        replaced.push(to.to_string());
+    }
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::MemoryStore;
+    use crate::localnet::{self, LocalRelay};
+    use nostr::{EventBuilder, Keys, Tag};
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    async fn spawn_relay() -> String {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let url = localnet::relay_url(listener.local_addr().unwrap().port());
+        tokio::spawn(LocalRelay::new(Arc::new(MemoryStore::new())).serve(listener));
+        url
+    }
+
+    #[test]
+    fn test_replace_relay() {
+        let relays = vec![
+            "wss://old.example/".to_string(),
+            "wss://other.example".to_string(),
+        ];
+        assert_eq!(
+            replace_relay(&relays, "wss://old.example", "wss://new.example"),
+            vec!["wss://new.example", "wss://other.example"]
+        );
+        // The new relay may already be listed
+        assert_eq!(
+            replace_relay(&relays, "wss://old.example", "wss://other.example"),
+            vec!["wss://other.example"]
+        );
+        // An old relay that isn't listed only adds the new one
+        assert_eq!(
+            replace_relay(&relays[1..], "wss://old.example", "wss://new.example"),
+            vec!["wss://other.example", "wss://new.example"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migrate_history() {
+        let from = spawn_relay().await;
+        let to = spawn_relay().await;
+        let owner = Keys::generate();
+        let contributor = Keys::generate();
+        let coordinate = format!("30617:{owner}:repo", owner = owner.public_key().to_hex());
+
+        let announcement = EventBuilder::new(Kind::from(events::KIND_GIT_REPO_ANNOUNCEMENT), "")
+            .tag(Tag::identifier("repo"))
+            .sign_with_keys(&owner)
+            .unwrap();
+        let pr = EventBuilder::new(Kind::from(1618), "PR")
+            .tag(Tag::parse(["a", &coordinate]).unwrap())
+            .sign_with_keys(&contributor)
+            .unwrap();
+        let comment = EventBuilder::new(Kind::from(1111), "LGTM")
+            .tag(Tag::parse(["E", &pr.id.to_hex()]).unwrap())
+            .sign_with_keys(&owner)
+            .unwrap();
+        let reply = EventBuilder::new(Kind::TextNote, "thanks")
+            .tag(Tag::event(comment.id))
+            .sign_with_keys(&contributor)
+            .unwrap();
+        let unrelated = EventBuilder::text_note("unrelated")
+            .sign_with_keys(&owner)
+            .unwrap();
+
+        let client = relay::connect(&Network::default(), std::slice::from_ref(&from), None)
+            .await
+            .unwrap();
+        for event in [&announcement, &pr, &comment, &reply, &unrelated] {
+            client.send_event(event).await.unwrap();
+        }
+        client.disconnect().await;
+
+        let timeout = Duration::from_secs(5);
+        let report = migrate_history(
+            &Network::default(),
+            &coordinate,
+            &[owner.public_key()],
+            &from,
+            &to,
+            timeout,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.found, 4);
+        assert_eq!(report.copied, 4);
+        assert!(report.is_verified());
+
+        let target = relay::connect(&Network::default(), std::slice::from_ref(&to), None)
+            .await
+            .unwrap();
+        let ids = [announcement.id, pr.id, comment.id, reply.id, unrelated.id];
+        let missing = missing_events(&Network::default(), &target, &ids, timeout)
+            .await
+            .unwrap();
+        assert_eq!(missing, vec![unrelated.id]);
+
+        assert!(
+            migrate_history(
+                &Network::default(),
+                &coordinate,
+                &[owner.public_key()],
+                &from,
+                &from,
+                timeout
+            )
+            .await
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,464 @@
+//! Testing what a relay offers a repository
+//!
+//! [`probe_relay`] connects, publishes a throwaway event signed by a fresh key
+//! and asks for its deletion right away, then reads the relay's NIP-11
+//! information document. Relays that only take events from known keys reject
+//! the throwaway key even when they would take the account's.
+//!
+//! [`check_payload`] compares events against the limits relays advertise in
+//! that document, so oversized patches skip a relay up front instead of
+//! failing halfway through a series.
+
+use nostr::nips::nip09::EventDeletionRequest;
+use nostr::nips::nip11::RelayInformationDocument;
+use nostr::{Event, EventBuilder, JsonUtil, Keys, Kind, Tag, Timestamp};
+use serde::Serialize;
+use std::time::Duration;
+use tracing::debug;
+
+use crate::error::{Context, GitsmithError, Result, bail, ensure};
+use crate::relay::{self, Network};
+
+/// Kind of the test event, application-specific data nobody else reads
+const TEST_EVENT_KIND: u16 = 30078;
+
+/// Identifier of the test event
+const TEST_EVENT_IDENTIFIER: &str = "gitsmith-relay-test";
+
+/// Seconds until the test event expires (NIP-40) should the deletion be ignored
+const TEST_EVENT_EXPIRATION_SECS: u64 = 60;
+
+/// NIPs gitsmith relies on: deletions, git collaboration and authentication
+const WANTED_NIPS: [u16; 3] = [9, 34, 42];
+
+/// NIP of git collaboration events
+const GIT_NIP: u16 = 34;
+
+/// Bytes the `["EVENT", ...]` envelope adds to an event's JSON
+const EVENT_ENVELOPE_BYTES: usize = r#"["EVENT",]"#.len();
+
+/// Whether a relay took the test event and its deletion
+#[derive(Debug, Clone, Serialize)]
+pub struct WriteCheck {
+    pub accepted: bool,
+    pub deleted: bool,
+    /// Rejection message of the relay
+    pub message: Option<String>,
+}
+
+/// What a relay offers: connectivity, writes and its NIP-11 document
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayProbe {
+    pub url: String,
+    pub connected: bool,
+    /// Connection error when the relay was unreachable
+    pub error: Option<String>,
+    pub write: Option<WriteCheck>,
+    pub info: Option<RelayInformationDocument>,
+    /// Why the information document couldn't be read
+    pub info_error: Option<String>,
+}
+
+impl RelayProbe {
+    /// Limitations of the relay worth knowing before publishing to it
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let Some(info) = &self.info else {
+            return warnings;
+        };
+        if let Some(nips) = &info.supported_nips {
+            let missing: Vec<String> = WANTED_NIPS
+                .iter()
+                .filter(|nip| !nips.contains(nip))
+                .map(|nip| format!("NIP-{nip:02}"))
+                .collect();
+            if !missing.is_empty() {
+                warnings.push(format!(
+                    "Doesn't list {missing} as supported",
+                    missing = missing.join(", ")
+                ));
+            }
+        }
+        if let Some(limitation) = &info.limitation {
+            if limitation.auth_required == Some(true) {
+                warnings.push("Requires NIP-42 authentication".to_string());
+            }
+            if limitation.payment_required == Some(true) {
+                warnings.push("Requires payment".to_string());
+            }
+            if let Some(difficulty) = limitation.min_pow_difficulty
+                && difficulty > 0
+            {
+                warnings.push(format!("Requires proof of work of difficulty {difficulty}"));
+            }
+        }
+        warnings
+    }
+}
+
+/// Whether the events fit the limits a relay advertises
+#[derive(Debug, Clone, Serialize)]
+pub struct PayloadCheck {
+    pub url: String,
+    /// Limits the events exceed, the relay would reject them
+    pub problems: Vec<String>,
+    /// Doubts that don't keep the events from being sent
+    pub warnings: Vec<String>,
+}
+
+impl PayloadCheck {
+    /// Whether the relay should get the events
+    pub fn accepts(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Limits of the information document that the events exceed
+///
+/// Only limits the relay advertises are checked, an empty list doesn't
+/// promise the relay takes the events.
+pub fn payload_problems(info: &RelayInformationDocument, events: &[Event]) -> Vec<String> {
+    let mut problems = Vec::new();
+    let Some(limitation) = &info.limitation else {
+        return problems;
+    };
+    if limitation.payment_required == Some(true) {
+        problems.push("Requires payment".to_string());
+    }
+
+    let largest = |measure: &dyn Fn(&Event) -> usize| events.iter().map(measure).max();
+    if let Some(max) = limitation.max_message_length
+        && let Some(size) = largest(&|event| event.as_json().len() + EVENT_ENVELOPE_BYTES)
+        && size > max as usize
+    {
+        problems.push(format!(
+            "Events of up to {max} bytes are accepted, the largest is {size} bytes"
+        ));
+    }
+    if let Some(max) = limitation.max_content_length
+        && let Some(length) = largest(&|event| event.content.chars().count())
+        && length > max as usize
+    {
+        problems.push(format!(
+            "Content of up to {max} characters is accepted, the largest is {length}"
+        ));
+    }
+    if let Some(max) = limitation.max_event_tags
+        && let Some(count) = largest(&|event| event.tags.len())
+        && count > max as usize
+    {
+        problems.push(format!(
+            "Up to {max} tags per event are accepted, an event has {count}"
+        ));
+    }
+    if let Some(difficulty) = limitation.min_pow_difficulty
+        && difficulty > 0
+        && !events
+            .iter()
+            .all(|event| event.check_pow(u8::try_from(difficulty).unwrap_or(u8::MAX)))
+    {
+        problems.push(format!("Requires proof of work of difficulty {difficulty}"));
+    }
+    problems
+}
+
+/// Check events against the NIP-11 limits of every relay, concurrently
+///
+/// Relays without a readable information document are assumed to take the
+/// events, publishing reports their rejections as usual.
+pub async fn check_payload(
+    network: &Network,
+    relays: &[String],
+    events: &[Event],
+    timeout: Duration,
+) -> Vec<PayloadCheck> {
+    let checks = relays.iter().map(|url| async move {
+        let mut check = PayloadCheck {
+            url: url.clone(),
+            problems: Vec::new(),
+            warnings: Vec::new(),
+        };
+        match fetch_relay_info(network, url, timeout).await {
+            Ok(info) => {
+                check.problems = payload_problems(&info, events);
+                if let Some(nips) = &info.supported_nips
+                    && !nips.contains(&GIT_NIP)
+                {
+                    check
+                        .warnings
+                        .push(format!("Doesn't list NIP-{GIT_NIP} as supported"));
+                }
+            }
+            Err(e) => debug!("No information document for {url}: {e}"),
+        }
+        check
+    });
+    futures::future::join_all(checks).await
+}
+
+/// HTTP(S) URL serving the NIP-11 document of a relay
+pub fn info_url(relay_url: &str) -> Result<String> {
+    let (scheme, rest) = relay_url
+        .split_once("://")
+        .with_context(|| format!("Invalid relay URL {relay_url}"))?;
+    let scheme = match scheme {
+        "wss" => "https",
+        "ws" => "http",
+        _ => bail!("Invalid relay URL {relay_url}, expected ws:// or wss://"),
+    };
+    Ok(format!("{scheme}://{rest}"))
+}
+
+/// Fetch the NIP-11 information document of a relay
+///
+/// Goes through the proxy of `network` like relay connections.
+pub async fn fetch_relay_info(
+    network: &Network,
+    relay_url: &str,
+    timeout: Duration,
+) -> Result<RelayInformationDocument> {
+    let url = info_url(relay_url)?;
+    let mut builder = reqwest::Client::builder()
+        .user_agent(concat!("gitsmith/", env!("CARGO_PKG_VERSION")))
+        .timeout(timeout);
+    if let Some(proxy) = network.proxy {
+        builder = builder.proxy(reqwest::Proxy::all(format!("socks5h://{proxy}"))?);
+    }
+    let response = builder
+        .build()?
+        .get(&url)
+        .header("Accept", "application/nostr+json")
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach {url}"))?;
+    let status = response.status();
+    ensure!(status.is_success(), "{url} answered {status}");
+    response
+        .json()
+        .await
+        .with_context(|| format!("{url} didn't return a NIP-11 document"))
+}
+
+/// Publish a throwaway event and its deletion with a fresh key
+async fn check_write(network: &Network, relay_url: &str, timeout: Duration) -> Result<WriteCheck> {
+    let keys = Keys::generate();
+    // The key also answers AUTH challenges, so relays demanding AUTH can be tested
+    let client =
+        relay::connect_with_minimum(network, &[relay_url.to_string()], Some(&keys), 1, timeout)
+            .await?;
+    let expiration = Timestamp::now() + TEST_EVENT_EXPIRATION_SECS;
+    let event = EventBuilder::new(Kind::from(TEST_EVENT_KIND), "gitsmith relay test")
+        .tags([
+            Tag::identifier(TEST_EVENT_IDENTIFIER),
+            Tag::expiration(expiration),
+        ])
+        .sign_with_keys(&keys)
+        .map_err(GitsmithError::msg)?;
+
+    let output = client.send_event(&event).await;
+    let mut check = WriteCheck {
+        accepted: false,
+        deleted: false,
+        message: None,
+    };
+    match output {
+        Ok(output) if !output.success.is_empty() => check.accepted = true,
+        Ok(output) => check.message = output.failed.into_values().next(),
+        Err(e) => check.message = Some(e.to_string()),
+    }
+    if check.accepted {
+        let deletion = EventBuilder::delete(
+            EventDeletionRequest::new()
+                .id(event.id)
+                .reason("gitsmith relay test"),
+        )
+        .sign_with_keys(&keys)
+        .map_err(GitsmithError::msg)?;
+        match client.send_event(&deletion).await {
+            Ok(output) if !output.success.is_empty() => check.deleted = true,
+            Ok(output) => check.message = output.failed.into_values().next(),
+            Err(e) => check.message = Some(e.to_string()),
+        }
+    }
+    client.disconnect().await;
+    Ok(check)
+}
+
+/// Test a relay's connectivity, write acceptance and NIP-11 capabilities
+pub async fn probe_relay(network: &Network, relay_url: &str, timeout: Duration) -> RelayProbe {
+    let (write, info) = futures::future::join(
+        check_write(network, relay_url, timeout),
+        fetch_relay_info(network, relay_url, timeout),
+    )
+    .await;
+    let (connected, write, error) = match write {
+        Ok(write) => (true, Some(write), None),
+        Err(e) => (false, None, Some(e.to_string())),
+    };
+    let (info, info_error) = match info {
+        Ok(info) => (Some(info), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    RelayProbe {
+        url: relay_url.to_string(),
+        connected,
+        error,
+        write,
+        info,
+        info_error,
+    }
+}
+
+/// Test every relay concurrently, in the given order
+pub async fn probe_relays(
+    network: &Network,
+    relays: &[String],
+    timeout: Duration,
+) -> Vec<RelayProbe> {
+    futures::future::join_all(
+        relays
+            .iter()
+            .map(|relay| probe_relay(network, relay, timeout)),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::MemoryStore;
+    use crate::localnet::{self, LocalRelay};
+    use nostr::nips::nip11::Limitation;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_info_url() {
+        assert_eq!(
+            info_url("wss://relay.example/nostr").unwrap(),
+            "https://relay.example/nostr"
+        );
+        assert_eq!(
+            info_url("ws://127.0.0.1:7777").unwrap(),
+            "http://127.0.0.1:7777"
+        );
+        assert!(info_url("https://relay.example").is_err());
+        assert!(info_url("relay.example").is_err());
+    }
+
+    #[test]
+    fn test_probe_warnings() {
+        let mut probe = RelayProbe {
+            url: "wss://relay.example".to_string(),
+            connected: true,
+            error: None,
+            write: None,
+            info: None,
+            info_error: None,
+        };
+        assert!(probe.warnings().is_empty());
+
+        probe.info = Some(RelayInformationDocument {
+            supported_nips: Some(vec![1, 9, 11, 42]),
+            limitation: Some(Limitation {
+                payment_required: Some(true),
+                min_pow_difficulty: Some(0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        assert_eq!(
+            probe.warnings(),
+            vec!["Doesn't list NIP-34 as supported", "Requires payment"]
+        );
+    }
+
+    #[test]
+    fn test_payload_problems() {
+        let keys = Keys::generate();
+        let patch = EventBuilder::new(Kind::from(1617), "x".repeat(1000))
+            .tags((0..5).map(|i| Tag::hashtag(i.to_string())))
+            .sign_with_keys(&keys)
+            .unwrap();
+        let small = EventBuilder::text_note("hi").sign_with_keys(&keys).unwrap();
+        let events = [small, patch];
+
+        let mut info = RelayInformationDocument::default();
+        assert!(payload_problems(&info, &events).is_empty());
+
+        info.limitation = Some(Limitation {
+            max_message_length: Some(2000),
+            max_content_length: Some(1000),
+            max_event_tags: Some(5),
+            ..Default::default()
+        });
+        assert!(payload_problems(&info, &events).is_empty());
+
+        info.limitation = Some(Limitation {
+            max_message_length: Some(500),
+            max_content_length: Some(999),
+            max_event_tags: Some(4),
+            ..Default::default()
+        });
+        let problems = payload_problems(&info, &events);
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].starts_with("Events of up to 500 bytes are accepted"));
+
+        // Mining for a relay's difficulty is left to the repository's own requirement
+        info.limitation = Some(Limitation {
+            min_pow_difficulty: Some(40),
+            ..Default::default()
+        });
+        assert_eq!(
+            payload_problems(&info, &events),
+            vec!["Requires proof of work of difficulty 40"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_payload_without_information_document() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let url = localnet::relay_url(listener.local_addr().unwrap().port());
+        tokio::spawn(LocalRelay::new(Arc::new(MemoryStore::new())).serve(listener));
+
+        let event = EventBuilder::text_note("hi")
+            .sign_with_keys(&Keys::generate())
+            .unwrap();
+        let checks = check_payload(
+            &Network::default(),
+            &[url],
+            &[event],
+            Duration::from_secs(5),
+        )
+        .await;
+        assert_eq!(checks.len(), 1);
+        assert!(checks[0].accepts());
+        assert!(checks[0].warnings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_probe_relay() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let url = localnet::relay_url(listener.local_addr().unwrap().port());
+        tokio::spawn(LocalRelay::new(Arc::new(MemoryStore::new())).serve(listener));
+
+        let probe = probe_relay(&Network::default(), &url, Duration::from_secs(5)).await;
+        assert!(probe.connected);
+        let write = probe.write.unwrap();
+        assert!(write.accepted);
+        assert!(write.deleted);
+        // The local relay doesn't serve an information document
+        assert!(probe.info.is_none());
+        assert!(probe.info_error.is_some());
+
+        let unreachable = localnet::relay_url(1);
+        let probe = probe_relay(
+            &Network::default(),
+            &unreachable,
+            Duration::from_millis(500),
+        )
+        .await;
+        assert!(!probe.connected);
+        assert!(probe.error.is_some());
+    }
+}
@@ -0,0 +1,420 @@
+//! Releases with their artifacts stored on Blossom servers
+//!
+//! A release is an addressable event (`d` = `<identifier>@<tag>`) referencing
+//! the repository, the git tag and the commit it points to. Every artifact is
+//! uploaded to the repository's Blossom servers and listed in an `artifact`
+//! tag (name, sha256, size, media type and the URLs holding it), so it can be
+//! downloaded from any of them and checked against its hash.
+
+use nostr::{Alphabet, Event, EventBuilder, Filter, Kind, PublicKey, SingleLetterTag, Tag};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::blossom;
+use crate::error::{Context, Result, bail, ensure};
+use crate::relay::{self, Network};
+use crate::repo::open_repository;
+use crate::signer::{self, Signer};
+use crate::types::BroadcastResult;
+
+/// Kind for release events (addressable, one per repository and tag)
+pub const KIND_RELEASE: Kind = Kind::Custom(30063);
+
+/// A file published with a release
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReleaseArtifact {
+    pub name: String,
+    pub sha256: String,
+    pub size: u64,
+    pub mime_type: String,
+    /// Blossom URLs holding the blob
+    pub urls: Vec<String>,
+}
+
+impl ReleaseArtifact {
+    /// `artifact` tag listing the artifact
+    pub fn tag(&self) -> Result<Tag> {
+        let size = self.size.to_string();
+        let mut values = vec!["artifact", &self.name, &self.sha256, &size, &self.mime_type];
+        values.extend(self.urls.iter().map(String::as_str));
+        Ok(Tag::parse(values)?)
+    }
+
+    /// Artifact from an `artifact` tag, None for other tags
+    pub fn from_tag(tag: &[String]) -> Option<Self> {
+        match tag {
+            [kind, name, sha256, size, mime_type, urls @ ..] if kind == "artifact" => Some(Self {
+                name: name.clone(),
+                sha256: sha256.clone(),
+                size: size.parse().ok()?,
+                mime_type: mime_type.clone(),
+                urls: urls.to_vec(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// A release published for a repository
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Release {
+    pub id: String,
+    /// Git tag released
+    pub tag: String,
+    /// Commit the tag points to
+    pub commit: Option<String>,
+    pub notes: String,
+    pub author: String,
+    pub created_at: u64,
+    pub artifacts: Vec<ReleaseArtifact>,
+}
+
+/// What [`create_release`] publishes
+#[derive(Debug, Clone)]
+pub struct ReleaseOptions {
+    /// Git tag to release, it must exist in the local repository
+    pub tag: String,
+    pub notes: String,
+    /// Files to upload
+    pub artifacts: Vec<PathBuf>,
+    /// Blossom servers every artifact is uploaded to
+    pub servers: Vec<String>,
+}
+
+/// `d` tag value of the release of `tag`
+pub fn release_identifier(repo_identifier: &str, tag: &str) -> String {
+    format!("{repo_identifier}@{tag}")
+}
+
+/// Start a release event; the builder is returned so callers can attach extra tags
+pub fn release_builder(
+    repo_coordinate: &str,
+    repo_identifier: &str,
+    tag: &str,
+    commit: &str,
+    notes: &str,
+    artifacts: &[ReleaseArtifact],
+) -> Result<EventBuilder> {
+    ensure!(!tag.trim().is_empty(), "Release tag cannot be empty");
+
+    let mut tags = vec![
+        Tag::identifier(release_identifier(repo_identifier, tag)),
+        Tag::parse(["a", repo_coordinate])?,
+        Tag::parse(["tag", tag])?,
+        Tag::parse(["commit", commit])?,
+    ];
+    for artifact in artifacts {
+        tags.push(artifact.tag()?);
+        tags.push(Tag::parse(["x", &artifact.sha256])?);
+    }
+
+    Ok(EventBuilder::new(KIND_RELEASE, notes).tags(tags))
+}
+
+/// Convert a release event to a Release, None for events without a tag
+pub fn parse_release_event(event: &Event) -> Option<Release> {
+    let value = |name: &str| {
+        event.tags.iter().find_map(|tag| {
+            let tag = tag.as_slice();
+            (tag.len() > 1 && tag[0] == name).then(|| tag[1].clone())
+        })
+    };
+
+    Some(Release {
+        id: event.id.to_hex(),
+        tag: value("tag")?,
+        commit: value("commit"),
+        notes: event.content.clone(),
+        author: event.pubkey.to_hex(),
+        created_at: event.created_at.as_u64(),
+        artifacts: event
+            .tags
+            .iter()
+            .filter_map(|tag| ReleaseArtifact::from_tag(tag.as_slice()))
+            .collect(),
+    })
+}
+
+/// Media type of an artifact, by its file name
+pub fn artifact_mime_type(name: &str) -> &'static str {
+    let name = name.to_lowercase();
+    [
+        (".tar.gz", "application/gzip"),
+        (".tgz", "application/gzip"),
+        (".gz", "application/gzip"),
+        (".tar.xz", "application/x-xz"),
+        (".xz", "application/x-xz"),
+        (".tar.zst", "application/zstd"),
+        (".zip", "application/zip"),
+        (".tar", "application/x-tar"),
+        (".deb", "application/vnd.debian.binary-package"),
+        (".rpm", "application/x-rpm"),
+        (".txt", "text/plain"),
+        (".asc", "application/pgp-signature"),
+        (".sig", "application/pgp-signature"),
+    ]
+    .into_iter()
+    .find(|(extension, _)| name.ends_with(extension))
+    .map_or("application/octet-stream", |(_, mime_type)| mime_type)
+}
+
+/// Commit a local tag points to
+pub fn tag_commit(repo_path: &Path, tag: &str) -> Result<String> {
+    let repo = open_repository(repo_path)?;
+    let reference = repo
+        .find_reference(&format!("refs/tags/{tag}"))
+        .with_context(|| format!("Tag {tag} not found. Create it with 'git tag {tag}' first"))?;
+    Ok(reference.peel_to_commit()?.id().to_string())
+}
+
+/// Upload a file to every server, failing only if none of them accepted it
+pub async fn upload_artifact(
+    servers: &[String],
+    signer: &dyn Signer,
+    path: &Path,
+) -> Result<ReleaseArtifact> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("Invalid artifact path: {path}", path = path.display()))?
+        .to_string();
+    let data =
+        fs::read(path).with_context(|| format!("Failed to read {path}", path = path.display()))?;
+    let mime_type = artifact_mime_type(&name);
+    let blobs = blossom::upload_to_all(servers, signer, data, mime_type).await?;
+    info!(%name, servers = blobs.len(), "Uploaded release artifact");
+
+    Ok(ReleaseArtifact {
+        name,
+        sha256: blobs[0].sha256.clone(),
+        size: blobs[0].size,
+        mime_type: mime_type.to_string(),
+        urls: blobs.into_iter().map(|blob| blob.url).collect(),
+    })
+}
+
+/// Upload the artifacts and publish the release of a local tag
+pub async fn create_release(
+    network: &Network,
+    signer: &dyn Signer,
+    repo_path: &Path,
+    repo_coordinate: &str,
+    repo_identifier: &str,
+    relays: &[String],
+    options: &ReleaseOptions,
+) -> Result<(Release, BroadcastResult)> {
+    let commit = tag_commit(repo_path, &options.tag)?;
+    ensure!(
+        options.artifacts.is_empty() || !options.servers.is_empty(),
+        "No Blossom servers configured for the artifacts"
+    );
+    let mut names = HashMap::new();
+    for path in &options.artifacts {
+        if let Some(previous) = names.insert(path.file_name(), path) {
+            bail!(
+                "Artifacts {previous} and {path} have the same file name",
+                previous = previous.display(),
+                path = path.display()
+            );
+        }
+    }
+
+    let mut artifacts = Vec::new();
+    for path in &options.artifacts {
+        artifacts.push(upload_artifact(&options.servers, signer, path).await?);
+    }
+
+    let builder = release_builder(
+        repo_coordinate,
+        repo_identifier,
+        &options.tag,
+        &commit,
+        &options.notes,
+        &artifacts,
+    )?;
+    let event = signer::sign(signer, builder).await?;
+    let release = parse_release_event(&event).context("Built an invalid release event")?;
+
+    let client = relay::connect(network, relays, Some(signer)).await?;
+    let result = relay::publish_events(&client, &[event]).await?;
+    client.disconnect().await;
+    Ok((release, result))
+}
+
+/// Fetch the releases of a repository published by its owner or maintainers, newest first
+///
+/// `maintainers` are public keys in hex or npub form. Only the latest event of
+/// each release counts, a re-published release replaces the earlier one.
+pub async fn list_releases(
+    network: &Network,
+    repo_coordinate: &str,
+    maintainers: &[String],
+    relays: &[String],
+    timeout: Duration,
+) -> Result<Vec<Release>> {
+    let owner = repo_coordinate
+        .split(':')
+        .nth(1)
+        .with_context(|| format!("Invalid repository coordinate: {repo_coordinate}"))?;
+    let authors = std::iter::once(owner)
+        .chain(maintainers.iter().map(String::as_str))
+        .map(PublicKey::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let client = relay::connect(network, relays, None).await?;
+    let filter = Filter::new()
+        .kind(KIND_RELEASE)
+        .authors(authors)
+        .custom_tag(SingleLetterTag::lowercase(Alphabet::A), repo_coordinate);
+    let events = relay::fetch_events(network, &client, vec![filter], timeout).await?;
+    client.disconnect().await;
+    Ok(latest_releases(&events))
+}
+
+/// Releases from events, the newest event of each author and tag, newest first
+pub fn latest_releases(events: &[Event]) -> Vec<Release> {
+    let mut latest: HashMap<(String, String), Release> = HashMap::new();
+    for release in events.iter().filter_map(parse_release_event) {
+        let key = (release.author.clone(), release.tag.clone());
+        match latest.get(&key) {
+            Some(known) if known.created_at >= release.created_at => {}
+            _ => {
+                latest.insert(key, release);
+            }
+        }
+    }
+    let mut releases: Vec<Release> = latest.into_values().collect();
+    releases.sort_by_key(|release| std::cmp::Reverse(release.created_at));
+    releases
+}
+
+/// Download an artifact into `directory`, trying its URLs in order
+pub async fn download_artifact(artifact: &ReleaseArtifact, directory: &Path) -> Result<PathBuf> {
+    // Artifact names come from the event, never let them leave the directory
+    let name = Path::new(&artifact.name)
+        .file_name()
+        .with_context(|| format!("Invalid artifact name: {name}", name = artifact.name))?;
+    let path = directory.join(name);
+
+    for url in &artifact.urls {
+        match blossom::download_blob(url, &artifact.sha256).await {
+            Ok(data) => {
+                fs::create_dir_all(directory).with_context(|| {
+                    format!(
+                        "Failed to create directory {directory}",
+                        directory = directory.display()
+                    )
+                })?;
+                fs::write(&path, data)
+                    .with_context(|| format!("Failed to write {path}", path = path.display()))?;
+                return Ok(path);
+            }
+            Err(e) => warn!("Download from {url} failed: {e}"),
+        }
+    }
+
+    bail!(
+        "Failed to download {name} from any of its {count} URL(s)",
+        name = artifact.name,
+        count = artifact.urls.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::Keys;
+
+    fn artifact() -> ReleaseArtifact {
+        ReleaseArtifact {
+            name: "tool.tar.gz".to_string(),
+            sha256: blossom::sha256_hex(b"tool"),
+            size: 4,
+            mime_type: "application/gzip".to_string(),
+            urls: vec![
+                "https://blossom.one/abc".to_string(),
+                "https://blossom.two/abc".to_string(),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_release_event_roundtrip() {
+        let keys = Keys::generate();
+        let coordinate = format!("30617:{owner}:repo", owner = keys.public_key().to_hex());
+        let event = release_builder(
+            &coordinate,
+            "repo",
+            "v1.2.0",
+            "abc123",
+            "Faster",
+            &[artifact()],
+        )
+        .unwrap()
+        .sign_with_keys(&keys)
+        .unwrap();
+        assert_eq!(event.kind, KIND_RELEASE);
+        assert_eq!(event.tags.identifier(), Some("repo@v1.2.0"));
+
+        let release = parse_release_event(&event).unwrap();
+        assert_eq!(release.tag, "v1.2.0");
+        assert_eq!(release.commit.as_deref(), Some("abc123"));
+        assert_eq!(release.notes, "Faster");
+        assert_eq!(release.artifacts, [artifact()]);
+
+        assert!(release_builder(&coordinate, "repo", " ", "abc123", "", &[]).is_err());
+    }
+
+    #[test]
+    fn test_latest_releases() {
+        let keys = Keys::generate();
+        let event = |tag: &str, notes: &str, created_at: u64| {
+            release_builder("30617:x:repo", "repo", tag, "abc", notes, &[])
+                .unwrap()
+                .custom_created_at(nostr::Timestamp::from(created_at))
+                .sign_with_keys(&keys)
+                .unwrap()
+        };
+        let releases = latest_releases(&[
+            event("v1.0.0", "first", 100),
+            event("v1.1.0", "second", 200),
+            event("v1.0.0", "first, fixed notes", 300),
+        ]);
+        assert_eq!(releases.len(), 2);
+        assert_eq!(releases[0].tag, "v1.0.0");
+        assert_eq!(releases[0].notes, "first, fixed notes");
+        assert_eq!(releases[1].tag, "v1.1.0");
+    }
+
+    #[test]
+    fn test_artifact_mime_type() {
+        assert_eq!(artifact_mime_type("tool-x86_64.tar.gz"), "application/gzip");
+        assert_eq!(artifact_mime_type("TOOL.ZIP"), "application/zip");
+        assert_eq!(artifact_mime_type("tool"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_tag_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        let sig = git2::Signature::now("Alice", "alice@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let commit = repo
+            .commit(Some("HEAD"), &sig, &sig, "Initial", &tree, &[])
+            .unwrap();
+        let object = repo.find_object(commit, None).unwrap();
+        repo.tag("v1.0.0", &object, &sig, "Release", false).unwrap();
+
+        assert_eq!(
+            tag_commit(dir.path(), "v1.0.0").unwrap(),
+            commit.to_string()
+        );
+        assert!(tag_commit(dir.path(), "v2.0.0").is_err());
+    }
+}
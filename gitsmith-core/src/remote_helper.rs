@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use std::process::{Command, Stdio};
+use tracing::debug;
+
+use nostr::PublicKey;
+
+use crate::error::{Context, Result, bail, ensure};
+use crate::grasp;
+use crate::types::{GitState, RepoAnnouncement};
+
+/// A single `push <src>:<dst>` request from git
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PushSpec {
+    /// Local ref or object to push (empty when deleting)
+    pub src: String,
+    /// Remote ref to update
+    pub dst: String,
+    pub force: bool,
+}
+
+impl PushSpec {
+    /// Refspec to hand to `git push`
+    pub fn refspec(&self) -> String {
+        let force = if self.force { "+" } else { "" };
+        format!("{force}{src}:{dst}", src = self.src, dst = self.dst)
+    }
+}
+
+/// Outcome of pushing one ref
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PushOutcome {
+    pub dst: String,
+    /// Rejection reason, None when the ref was updated (or already up to date)
+    pub error: Option<String>,
+}
+
+impl PushOutcome {
+    /// Status line reported back to git
+    pub fn status_line(&self) -> String {
+        match &self.error {
+            None => format!("ok {dst}", dst = self.dst),
+            Some(error) => format!("error {dst} {error}", dst = self.dst),
+        }
+    }
+}
+
+/// Parse the argument of a `push` command (`[+]<src>:<dst>`)
+pub fn parse_push_spec(spec: &str) -> Result<PushSpec> {
+    let (force, spec) = match spec.strip_prefix('+') {
+        Some(rest) => (true, rest),
+        None => (false, spec),
+    };
+    let (src, dst) = spec
+        .split_once(':')
+        .with_context(|| format!("Invalid push refspec: {spec}"))?;
+    ensure!(!dst.is_empty(), "Push refspec has no destination: {spec}");
+
+    Ok(PushSpec {
+        src: src.to_string(),
+        dst: dst.to_string(),
+        force,
+    })
+}
+
+/// Parse the output of `git push --porcelain` into per-ref outcomes
+pub fn parse_porcelain_push(output: &str) -> Vec<PushOutcome> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let flag = fields.next()?;
+            let refs = fields.next()?;
+            let summary = fields.next().unwrap_or_default();
+            let dst = refs.split_once(':').map(|(_, dst)| dst)?;
+
+            let error = (flag.trim() == "!").then(|| {
+                // "[rejected] (non-fast-forward)" -> "non-fast-forward"
+                summary
+                    .split_once('(')
+                    .map(|(_, reason)| reason.trim_end_matches(')').to_string())
+                    .unwrap_or_else(|| summary.trim().to_string())
+            });
+
+            Some(PushOutcome {
+                dst: dst.to_string(),
+                error,
+            })
+        })
+        .collect()
+}
+
+/// Refs of a remote repository as reported by `git ls-remote`
+pub fn ls_remote(url: &str) -> Result<Vec<(String, String)>> {
+    let output = Command::new("git")
+        .args(["ls-remote", url])
+        .output()
+        .context("Failed to run git ls-remote")?;
+    if !output.status.success() {
+        bail!(
+            "git ls-remote {url} failed: {stderr}",
+            stderr = String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(parse_ls_remote(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse `git ls-remote` output into (object id, ref name) pairs
+pub fn parse_ls_remote(output: &str) -> Vec<(String, String)> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(oid, name)| (oid.to_string(), name.to_string()))
+        .collect()
+}
+
+/// Push the given refs to `url` and report the outcome for each of them
+pub fn push_to(url: &str, specs: &[PushSpec]) -> Result<Vec<PushOutcome>> {
+    let mut command = Command::new("git");
+    command.args(["push", "--porcelain", url]);
+    command.args(specs.iter().map(PushSpec::refspec));
+    debug!(?command, "Forwarding push");
+
+    // Stdout is read back, git's progress output on stderr goes straight to the user
+    let output = command.output().context("Failed to run git push")?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let outcomes = parse_porcelain_push(&stdout);
+
+    // A failing push without per-ref output (e.g. authentication) fails every ref
+    if outcomes.is_empty() && !output.status.success() {
+        return Ok(specs
+            .iter()
+            .map(|spec| PushOutcome {
+                dst: spec.dst.clone(),
+                error: Some("push failed".to_string()),
+            })
+            .collect());
+    }
+
+    Ok(outcomes)
+}
+
+/// Parse the argument of a `fetch` command (`<sha> <name>`)
+pub fn parse_fetch_command(args: &str) -> Result<(String, String)> {
+    let (oid, name) = args
+        .split_once(' ')
+        .with_context(|| format!("Invalid fetch command: {args}"))?;
+    Ok((oid.to_string(), name.to_string()))
+}
+
+/// Fetch the objects of the given refs from `url` into the current repository
+pub fn fetch_from(url: &str, names: &[String]) -> Result<()> {
+    let mut command = Command::new("git");
+    command.args([
+        "fetch",
+        "--quiet",
+        "--no-tags",
+        "--no-write-fetch-head",
+        url,
+    ]);
+    command.args(names);
+    debug!(?command, "Forwarding fetch");
+
+    // Stdout carries the remote helper protocol, keep git's output off it
+    let status = command
+        .stdout(Stdio::null())
+        .status()
+        .context("Failed to run git fetch")?;
+    if !status.success() {
+        bail!("git fetch {url} failed");
+    }
+    Ok(())
+}
+
+/// Clone URLs worth trying, in order of preference
+///
+/// HTTPS first as it works without credentials, then any other non-nostr URL.
+pub fn clone_url_candidates(clone_urls: &[String]) -> Vec<&str> {
+    let (https, other): (Vec<&String>, Vec<&String>) = clone_urls
+        .iter()
+        .filter(|url| !url.starts_with("nostr://"))
+        .partition(|url| url.starts_with("https://"));
+    https.into_iter().chain(other).map(String::as_str).collect()
+}
+
+/// URLs the git data of `owner`'s repository can be fetched from
+///
+/// The announcement's clone URLs, or its repositories on GRASP servers when it
+/// lists no clone URL.
+pub fn fetch_urls(announcement: &RepoAnnouncement, owner: &PublicKey) -> Result<Vec<String>> {
+    if !clone_url_candidates(&announcement.clone_urls).is_empty() {
+        return Ok(announcement.clone_urls.clone());
+    }
+    announcement
+        .grasp_servers
+        .iter()
+        .map(|server| grasp::clone_url(server, owner, &announcement.identifier))
+        .collect()
+}
+
+/// First clone URL that answers `git ls-remote`, along with its refs
+pub fn select_clone_url(clone_urls: &[String]) -> Result<(String, Vec<(String, String)>)> {
+    let candidates = clone_url_candidates(clone_urls);
+    if candidates.is_empty() {
+        bail!("Repository announcement has no clone URL");
+    }
+
+    let mut errors = Vec::new();
+    for url in candidates {
+        match ls_remote(url) {
+            Ok(refs) => return Ok((url.to_string(), refs)),
+            Err(e) => {
+                debug!("Skipping clone URL {url}: {e}");
+                errors.push(format!("{e}"));
+            }
+        }
+    }
+    bail!(
+        "None of the clone URLs is reachable:\n  {errors}",
+        errors = errors.join("\n  ")
+    )
+}
+
+/// Repository state built from the refs of the remote after a push
+///
+/// Only branches, tags and HEAD are part of the state; peeled tag entries and
+/// other namespaces (e.g. `refs/pull/*`) are ignored.
+pub fn state_from_remote_refs(identifier: &str, refs: &[(String, String)]) -> GitState {
+    let refs: HashMap<String, String> = refs
+        .iter()
+        .filter(|(_, name)| {
+            name == "HEAD"
+                || ((name.starts_with("refs/heads/") || name.starts_with("refs/tags/"))
+                    && !name.ends_with("^{}"))
+        })
+        .map(|(oid, name)| (name.clone(), oid.clone()))
+        .collect();
+
+    GitState {
+        identifier: identifier.to_string(),
+        refs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_push_spec() {
+        assert_eq!(
+            parse_push_spec("+refs/heads/main:refs/heads/main").unwrap(),
+            PushSpec {
+                src: "refs/heads/main".to_string(),
+                dst: "refs/heads/main".to_string(),
+                force: true,
+            }
+        );
+
+        let delete = parse_push_spec(":refs/heads/old").unwrap();
+        assert_eq!(delete.src, "");
+        assert_eq!(delete.refspec(), ":refs/heads/old");
+
+        assert!(parse_push_spec("refs/heads/main").is_err());
+    }
+
+    #[test]
+    fn test_fetch_urls_fall_back_to_grasp_servers() {
+        let owner = nostr::Keys::generate().public_key();
+        let mut announcement = RepoAnnouncement {
+            identifier: "repo".to_string(),
+            name: "repo".to_string(),
+            description: String::new(),
+            clone_urls: vec!["nostr://npub1.../repo".to_string()],
+            relays: vec![],
+            web: vec![],
+            root_commit: String::new(),
+            maintainers: vec![],
+            ci_keys: vec![],
+            grasp_servers: vec!["https://grasp.example.com".to_string()],
+            blossom_servers: vec![],
+            min_pow: 0,
+            license: None,
+            topics: vec![],
+            readme: None,
+        };
+        assert_eq!(
+            fetch_urls(&announcement, &owner).unwrap(),
+            [grasp::clone_url("https://grasp.example.com", &owner, "repo").unwrap()]
+        );
+
+        announcement
+            .clone_urls
+            .push("https://example.com/repo.git".to_string());
+        assert_eq!(
+            fetch_urls(&announcement, &owner).unwrap(),
+            announcement.clone_urls
+        );
+    }
+
+    #[test]
+    fn test_parse_porcelain_push() {
+        let output = "To https://example.com/repo.git\n\
+                      *\trefs/heads/feature:refs/heads/feature\t[new branch]\n\
+                      !\trefs/heads/main:refs/heads/main\t[rejected] (non-fast-forward)\n\
+                      =\trefs/tags/v1:refs/tags/v1\t[up to date]\n\
+                      Done\n";
+
+        let outcomes = parse_porcelain_push(output);
+        assert_eq!(outcomes.len(), 3);
+        assert_eq!(outcomes[0].status_line(), "ok refs/heads/feature");
+        assert_eq!(
+            outcomes[1].status_line(),
+            "error refs/heads/main non-fast-forward"
+        );
+        assert_eq!(outcomes[2].error, None);
+    }
+
+    #[test]
+    fn test_clone_url_candidates() {
+        let urls = vec![
+            "git@example.com:repo.git".to_string(),
+            "nostr://npub1abc/relay.example.com/repo".to_string(),
+            "https://example.com/repo.git".to_string(),
+        ];
+        assert_eq!(
+            clone_url_candidates(&urls),
+            vec!["https://example.com/repo.git", "git@example.com:repo.git"]
+        );
+
+        assert_eq!(
+            parse_fetch_command("abc123 refs/heads/main").unwrap(),
+            ("abc123".to_string(), "refs/heads/main".to_string())
+        );
+        assert!(parse_fetch_command("abc123").is_err());
+    }
+
+    #[test]
+    fn test_state_from_remote_refs() {
+        let refs = parse_ls_remote(
+            "aaa\tHEAD\naaa\trefs/heads/main\nbbb\trefs/tags/v1\nccc\trefs/tags/v1^{}\nddd\trefs/pull/1/head\n",
+        );
+
+        let state = state_from_remote_refs("repo", &refs);
+        assert_eq!(state.identifier, "repo");
+        assert_eq!(state.refs.len(), 3);
+        assert_eq!(state.refs["refs/heads/main"], "aaa");
+        assert_eq!(state.refs["refs/tags/v1"], "bbb");
+        assert!(state.refs.contains_key("HEAD"));
+    }
+}
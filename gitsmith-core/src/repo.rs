@@ -1,28 +1,34 @@
-use anyhow::{Context, Result, bail};
 use git2::Repository;
-use nostr::{FromBech32, Keys, RelayUrl, ToBech32};
-use nostr_sdk::Client;
+use nostr::nips::nip01::Coordinate;
+use nostr::nips::nip19::Nip19Coordinate;
+use nostr::{
+    Event, EventBuilder, Filter, FromBech32, Kind, PublicKey, RelayUrl, TagKind, Timestamp,
+    ToBech32,
+};
 use std::path::Path;
 use std::time::Duration;
+use tracing::warn;
 
+use crate::cache::CacheStore;
+use crate::error::{Context, GitsmithError, Result, bail, ensure};
 use crate::events;
+use crate::relay::{self, Network};
+use crate::remote_helper;
+use crate::signer::{self, Signer};
 use crate::types::*;
 
 /// Publish repository announcement to Nostr (Kind 30617)
 pub async fn announce_repository(
+    network: &Network,
     announcement: RepoAnnouncement,
-    private_key_hex: &str,
+    signer: &dyn Signer,
     config: PublishConfig,
 ) -> Result<PublishResult> {
-    // Parse keys
-    let keys = Keys::parse(private_key_hex)?;
-
     // Build announcement event
-    let event = events::build_announcement_event(&announcement, &keys)?;
-    let event_id = event.id;
+    let event = events::build_announcement_event(&announcement, signer).await?;
 
     // Create client
-    let client = Client::new(keys.clone());
+    let client = relay::new_client(network, Some(signer));
 
     // Add relays
     for relay_url in &announcement.relays {
@@ -33,16 +39,17 @@ pub async fn announce_repository(
     // Connect to relays
     client.connect().await;
 
-    // Send event and track relay responses
-    let output = client.send_event(&event).await?;
-
-    // Wait a bit for propagation if requested
+    // Make sure a relay is actually connected before sending if requested
     if config.wait_for_send {
-        tokio::time::sleep(Duration::from_secs(1)).await;
+        relay::wait_for_connections(&client, 1, Duration::from_secs(config.timeout_secs)).await?;
     }
 
+    // Send event and track relay responses
+    let event_id = event.id;
+    let output = client.send_event(&event).await?;
+
     // Build nostr URL (using naddr format)
-    let npub = keys.public_key().to_bech32()?;
+    let npub = signer.public_key().to_bech32()?;
     let first_relay = announcement
         .relays
         .first()
@@ -75,6 +82,313 @@ pub async fn announce_repository(
     })
 }
 
+/// Fetch the latest announcement for a repository coordinate (30617:pubkey:identifier)
+pub async fn fetch_announcement(
+    network: &Network,
+    repo_coordinate: &str,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<Option<RepoAnnouncement>> {
+    let (_kind, pubkey, identifier) = crate::patches::parse_repo_coordinate(repo_coordinate)?;
+    let author = PublicKey::from_hex(&pubkey)
+        .with_context(|| format!("Invalid public key in coordinate {repo_coordinate}"))?;
+
+    let client = relay::connect(network, relays, None).await?;
+    let filter = Filter::new()
+        .kind(Kind::from(events::KIND_GIT_REPO_ANNOUNCEMENT))
+        .author(author)
+        .identifier(identifier);
+    let mut found = relay::fetch_events(network, &client, vec![filter], timeout).await?;
+
+    // Replaceable event: the newest one wins
+    found.sort_by(events::newest_first);
+    found
+        .first()
+        .map(events::parse_announcement_event)
+        .transpose()
+}
+
+/// Announcements of a repository by its owner and the maintainers it trusts
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Maintainers {
+    /// Coordinates (30617:pubkey:identifier) of the announcements, the owner's first
+    pub coordinates: Vec<String>,
+    /// The owner and every trusted maintainer, whether or not they announced
+    pub keys: Vec<PublicKey>,
+    /// CI systems the owner's or a maintainer's announcement declares
+    pub ci_keys: Vec<PublicKey>,
+}
+
+impl Maintainers {
+    /// Whether `pubkey` (hex) is the owner or a trusted maintainer
+    pub fn is_maintainer(&self, pubkey: &str) -> bool {
+        self.keys.iter().any(|key| key.to_hex() == pubkey)
+    }
+
+    /// Keys whose CI check reports are trusted: maintainers and declared CI systems
+    pub fn check_reporters(&self) -> Vec<PublicKey> {
+        self.keys.iter().chain(&self.ci_keys).copied().collect()
+    }
+}
+
+/// Resolve the maintainers of a repository from announcement events
+///
+/// The owner trusts the maintainers its newest announcement lists, and each of
+/// them the maintainers their own announcement lists. A maintainer's
+/// announcement belongs to the repository when it uses the same identifier or
+/// names the same earliest unique commit (`r` tag). The CI keys of every
+/// trusted announcement are collected too.
+pub fn resolve_maintainers(repo_coordinate: &str, announcements: &[Event]) -> Result<Maintainers> {
+    let (_kind, owner, identifier) = crate::patches::parse_repo_coordinate(repo_coordinate)?;
+    let owner = PublicKey::from_hex(&owner)
+        .with_context(|| format!("Invalid public key in coordinate {repo_coordinate}"))?;
+
+    let mut newest: Vec<&Event> = announcements
+        .iter()
+        .filter(|event| event.kind == Kind::from(events::KIND_GIT_REPO_ANNOUNCEMENT))
+        .collect();
+    newest.sort_by(|a, b| events::newest_first(a, b));
+    let owned = newest.iter().find(|event| {
+        event.pubkey == owner && event.tags.identifier() == Some(identifier.as_str())
+    });
+    let euc = owned.and_then(|event| events::earliest_unique_commit(event));
+
+    let mut maintainers = Maintainers {
+        coordinates: vec![repo_coordinate.to_string()],
+        keys: vec![owner],
+        ci_keys: vec![],
+    };
+    let mut pending: Vec<&Event> = owned.into_iter().copied().collect();
+    while let Some(announcement) = pending.pop() {
+        let Ok(parsed) = events::parse_announcement_event(announcement) else {
+            continue;
+        };
+        for npub in &parsed.ci_keys {
+            if let Ok(key) = PublicKey::parse(npub)
+                && !maintainers.ci_keys.contains(&key)
+            {
+                maintainers.ci_keys.push(key);
+            }
+        }
+        for npub in parsed.maintainers {
+            let Ok(key) = PublicKey::parse(&npub) else {
+                continue;
+            };
+            if maintainers.keys.contains(&key) {
+                continue;
+            }
+            maintainers.keys.push(key);
+
+            // Prefer the same identifier over other announcements of the project, like forks
+            let theirs = newest
+                .iter()
+                .find(|event| {
+                    event.pubkey == key && event.tags.identifier() == Some(identifier.as_str())
+                })
+                .or_else(|| {
+                    newest.iter().find(|event| {
+                        event.pubkey == key
+                            && euc.is_some()
+                            && events::earliest_unique_commit(event) == euc
+                    })
+                });
+            if let Some(event) = theirs
+                && let Some(their_identifier) = event.tags.identifier()
+            {
+                maintainers.coordinates.push(format!(
+                    "{kind}:{key}:{their_identifier}",
+                    kind = events::KIND_GIT_REPO_ANNOUNCEMENT,
+                    key = key.to_hex()
+                ));
+                pending.push(event);
+            }
+        }
+    }
+
+    Ok(maintainers)
+}
+
+/// The newest announcement for a repository coordinate held by an event cache
+pub fn cached_announcement(
+    store: &dyn CacheStore,
+    repo_coordinate: &str,
+) -> Result<Option<RepoAnnouncement>> {
+    let (_kind, pubkey, identifier) = crate::patches::parse_repo_coordinate(repo_coordinate)?;
+    let author = PublicKey::from_hex(&pubkey)
+        .with_context(|| format!("Invalid public key in coordinate {repo_coordinate}"))?;
+    let filter = Filter::new()
+        .kind(Kind::from(events::KIND_GIT_REPO_ANNOUNCEMENT))
+        .author(author)
+        .identifier(identifier);
+    let mut found = store.query(&[filter])?;
+    found.sort_by(events::newest_first);
+    found
+        .first()
+        .map(events::parse_announcement_event)
+        .transpose()
+}
+
+/// Latest announcement and state events of a repository, either may be missing
+pub async fn fetch_replaceable_events(
+    network: &Network,
+    repo_coordinate: &str,
+    relays: &[String],
+    timeout: Duration,
+) -> Result<(Option<Event>, Option<Event>)> {
+    let (_kind, pubkey, identifier) = crate::patches::parse_repo_coordinate(repo_coordinate)?;
+    let author = PublicKey::from_hex(&pubkey)
+        .with_context(|| format!("Invalid public key in coordinate {repo_coordinate}"))?;
+
+    let client = relay::connect(network, relays, None).await?;
+    let filter = Filter::new()
+        .kinds([
+            Kind::from(events::KIND_GIT_REPO_ANNOUNCEMENT),
+            Kind::from(events::KIND_GIT_STATE),
+        ])
+        .author(author)
+        .identifier(identifier);
+    let found = relay::fetch_events(network, &client, vec![filter], timeout).await?;
+    client.disconnect().await;
+
+    // Replaceable events: the newest one of each kind wins
+    let latest = |kind: u16| {
+        found
+            .iter()
+            .filter(|e| e.kind == Kind::from(kind))
+            .min_by(|a, b| events::newest_first(a, b))
+            .cloned()
+    };
+    Ok((
+        latest(events::KIND_GIT_REPO_ANNOUNCEMENT),
+        latest(events::KIND_GIT_STATE),
+    ))
+}
+
+/// Newest state event of a repository published at or before `at`
+///
+/// Only relays that keep replaced events can return old states, the others
+/// just have the latest one.
+pub async fn fetch_state_at(
+    network: &Network,
+    repo_coordinate: &str,
+    relays: &[String],
+    at: Timestamp,
+    timeout: Duration,
+) -> Result<Option<Event>> {
+    let (_kind, pubkey, identifier) = crate::patches::parse_repo_coordinate(repo_coordinate)?;
+    let author = PublicKey::from_hex(&pubkey)
+        .with_context(|| format!("Invalid public key in coordinate {repo_coordinate}"))?;
+
+    let client = relay::connect(network, relays, None).await?;
+    let filter = Filter::new()
+        .kind(Kind::from(events::KIND_GIT_STATE))
+        .author(author)
+        .identifier(identifier)
+        .until(at);
+    let found = relay::fetch_events(network, &client, vec![filter], timeout).await?;
+    client.disconnect().await;
+
+    Ok(state_at(found, at))
+}
+
+/// Pick the newest event created at or before `at`, relays may ignore `until`
+fn state_at(events: Vec<Event>, at: Timestamp) -> Option<Event> {
+    events
+        .into_iter()
+        .filter(|e| e.created_at <= at)
+        .min_by(events::newest_first)
+}
+
+/// Check out a commit with a detached HEAD, refusing to drop local changes
+pub fn checkout_commit(repo_path: &Path, commit: &str) -> Result<()> {
+    let repo = Repository::open(repo_path)
+        .with_context(|| format!("Failed to open repository at {repo_path:?}"))?;
+    let oid = git2::Oid::from_str(commit).with_context(|| format!("Invalid commit {commit}"))?;
+    let target = repo.find_commit(oid).with_context(|| {
+        format!("Commit {commit} is not in the local repository, fetch it first")
+    })?;
+
+    crate::apply::ensure_clean_worktree(&repo)?;
+    repo.checkout_tree(
+        target.as_object(),
+        Some(git2::build::CheckoutBuilder::new().safe()),
+    )?;
+    repo.set_head_detached(oid)?;
+    Ok(())
+}
+
+/// Re-sign a replaceable event with the current time, keeping its content and tags
+///
+/// Relays with retention policies drop old events, a fresh timestamp keeps them.
+pub async fn refresh_event(event: &Event, signer: &dyn Signer) -> Result<Event> {
+    ensure!(
+        event.pubkey == signer.public_key(),
+        "Event {id} was published by another key",
+        id = event.id
+    );
+    // The old proof-of-work nonce doesn't apply to the new event
+    let tags = event
+        .tags
+        .iter()
+        .filter(|tag| tag.kind() != TagKind::Nonce)
+        .cloned();
+
+    signer::sign(
+        signer,
+        EventBuilder::new(event.kind, &event.content).tags(tags),
+    )
+    .await
+}
+
+/// Requirements and recipients of new PRs for a repository
+#[derive(Debug, Clone, Default)]
+pub struct PullRequestTarget {
+    /// Proof-of-work difficulty PR events must meet
+    pub pow_difficulty: u8,
+    /// Owner and maintainers to notify with `p` tags
+    pub maintainers: Vec<PublicKey>,
+}
+
+/// Work out what new PRs for a repository must look like
+///
+/// Takes the larger of the locally configured proof-of-work requirement and the
+/// one advertised in the announcement on relays. Maintainers come from the
+/// announcement, the owner in the coordinate is always included.
+pub async fn pull_request_target(
+    network: &Network,
+    local: &RepoAnnouncement,
+    repo_coordinate: &str,
+    relays: &[String],
+) -> PullRequestTarget {
+    let remote =
+        match fetch_announcement(network, repo_coordinate, relays, Duration::from_secs(3)).await {
+            Ok(remote) => remote,
+            Err(e) => {
+                warn!("Failed to fetch repository announcement: {e}");
+                None
+            }
+        };
+
+    let owner = crate::patches::parse_repo_coordinate(repo_coordinate)
+        .ok()
+        .and_then(|(_, owner, _)| PublicKey::from_hex(&owner).ok());
+    let announced = remote.as_ref().unwrap_or(local).maintainers.iter();
+    let mut maintainers = Vec::new();
+    for key in owner
+        .into_iter()
+        .chain(announced.filter_map(|m| PublicKey::parse(m).ok()))
+    {
+        if !maintainers.contains(&key) {
+            maintainers.push(key);
+        }
+    }
+
+    PullRequestTarget {
+        pow_difficulty: local.min_pow.max(remote.map_or(0, |remote| remote.min_pow)),
+        maintainers,
+    }
+}
+
 /// Load nostr configuration from git config
 fn load_nostr_config(repo: &Repository) -> Option<(String, Vec<String>, Option<String>)> {
     let config = repo.config().ok()?;
@@ -86,16 +400,7 @@ fn load_nostr_config(repo: &Repository) -> Option<(String, Vec<String>, Option<S
     let owner = config.get_string("nostr.owner").ok();
 
     // Load all relay entries
-    let mut relays = Vec::new();
-    if let Ok(mut entries) = config.entries(Some("nostr.relay")) {
-        while let Some(entry) = entries.next() {
-            if let Ok(entry) = entry
-                && let Some(value) = entry.value()
-            {
-                relays.push(value.to_string());
-            }
-        }
-    }
+    let relays = config_values(&config, "nostr.relay");
 
     if relays.is_empty() {
         return None;
@@ -106,8 +411,7 @@ fn load_nostr_config(repo: &Repository) -> Option<(String, Vec<String>, Option<S
 
 /// Detect repository information from git
 pub fn detect_from_git(repo_path: &Path) -> Result<RepoAnnouncement> {
-    let repo = Repository::open(repo_path)
-        .with_context(|| format!("Failed to open git repository at {repo_path:?}"))?;
+    let repo = open_repository(repo_path)?;
 
     // Get repo name from directory
     let name = repo_path
@@ -148,6 +452,19 @@ pub fn detect_from_git(repo_path: &Path) -> Result<RepoAnnouncement> {
         .as_ref()
         .and_then(|c| c.get_string("nostr.description").ok())
         .unwrap_or_default();
+    let saved_blossom_servers = config
+        .as_ref()
+        .map(|c| config_values(c, "nostr.blossom"))
+        .unwrap_or_default();
+    let saved_grasp_servers = config
+        .as_ref()
+        .map(|c| config_values(c, "nostr.grasp-server"))
+        .unwrap_or_default();
+    let saved_min_pow = config
+        .as_ref()
+        .and_then(|c| c.get_i32("nostr.min-pow").ok())
+        .and_then(|v| u8::try_from(v).ok())
+        .unwrap_or(0);
 
     Ok(RepoAnnouncement {
         identifier,
@@ -158,14 +475,122 @@ pub fn detect_from_git(repo_path: &Path) -> Result<RepoAnnouncement> {
         web: vec![],
         root_commit,
         maintainers: vec![],
-        grasp_servers: vec![],
+        ci_keys: vec![],
+        grasp_servers: saved_grasp_servers,
+        blossom_servers: saved_blossom_servers,
+        min_pow: saved_min_pow,
+        license: config
+            .as_ref()
+            .and_then(|c| c.get_string("nostr.license").ok()),
+        topics: vec![],
+        readme: config
+            .as_ref()
+            .and_then(|c| c.get_string("nostr.readme").ok()),
+    })
+}
+
+/// Replace the relays saved in the repository's git config
+pub fn save_relays(repo_path: &Path, relays: &[String]) -> Result<()> {
+    let repo = open_repository(repo_path)?;
+    let mut config = repo.config()?;
+
+    // Removing fails when no relay was configured yet
+    let _ = config.remove_multivar("nostr.relay", ".*");
+    for relay in relays {
+        config.set_multivar("nostr.relay", "^$", relay)?;
+    }
+    Ok(())
+}
+
+/// All values of a multi-valued git config key
+pub(crate) fn config_values(config: &git2::Config, key: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    if let Ok(mut entries) = config.entries(Some(key)) {
+        while let Some(entry) = entries.next() {
+            if let Ok(entry) = entry
+                && let Some(value) = entry.value()
+            {
+                values.push(value.to_string());
+            }
+        }
+    }
+    values
+}
+
+/// Git state as published in state events: HEAD, branches and tags only
+///
+/// Remote-tracking refs, stashes and other local namespaces are left out.
+pub fn get_publishable_state(repo_path: &Path, identifier: &str) -> Result<GitState> {
+    let mut state = get_git_state(repo_path, identifier)?;
+    state.refs.retain(|name, _| {
+        name == "HEAD" || name.starts_with("refs/heads/") || name.starts_with("refs/tags/")
+    });
+    Ok(state)
+}
+
+/// Open the git repository at `repo_path`
+pub fn open_repository(repo_path: &Path) -> Result<Repository> {
+    Repository::open(repo_path).map_err(|e| match e.code() {
+        git2::ErrorCode::NotFound => GitsmithError::NotARepository(repo_path.to_path_buf()),
+        _ => GitsmithError::Context {
+            message: format!("Failed to open git repository at {repo_path:?}"),
+            source: Box::new(e.into()),
+        },
+    })
+}
+
+/// Whether a repository is the project an announcement describes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Identity {
+    /// The repository contains the announced earliest unique commit
+    Verified,
+    /// The announcement names no earliest unique commit
+    Unverified,
+    /// The repository lacks the announced commit, so it's a different project
+    Mismatch {
+        announced: String,
+        /// Root commit of the local repository
+        local: String,
+    },
+}
+
+/// Check the announced earliest unique commit against a local repository
+pub fn verify_identity(repo_path: &Path, announcement: &RepoAnnouncement) -> Result<Identity> {
+    if announcement.root_commit.is_empty() {
+        return Ok(Identity::Unverified);
+    }
+    let repo = open_repository(repo_path)?;
+    let contains = git2::Oid::from_str(&announcement.root_commit)
+        .is_ok_and(|oid| repo.find_commit(oid).is_ok());
+    if contains {
+        return Ok(Identity::Verified);
+    }
+    Ok(Identity::Mismatch {
+        announced: announcement.root_commit.clone(),
+        local: get_root_commit(&repo)?,
     })
 }
 
 /// Get the root commit of a repository
 fn get_root_commit(repo: &Repository) -> Result<String> {
+    let head = repo.head()?.peel_to_commit()?.id();
+    root_commit_from(repo, head)
+}
+
+/// Root commit of `branch`, local or as fetched from origin
+pub fn get_branch_root_commit(repo_path: &Path, branch: &str) -> Result<String> {
+    let repo = open_repository(repo_path)?;
+    let reference = repo
+        .find_reference(&format!("refs/heads/{branch}"))
+        .or_else(|_| repo.find_reference(&format!("refs/remotes/origin/{branch}")))
+        .with_context(|| format!("Branch {branch} not found"))?;
+    let tip = reference.peel_to_commit()?.id();
+    root_commit_from(&repo, tip)
+}
+
+fn root_commit_from(repo: &Repository, tip: git2::Oid) -> Result<String> {
     let mut revwalk = repo.revwalk()?;
-    revwalk.push_head()?;
+    revwalk.push(tip)?;
     revwalk.set_sorting(git2::Sort::TIME | git2::Sort::REVERSE)?;
 
     let mut root = None;
@@ -182,7 +607,7 @@ fn get_root_commit(repo: &Repository) -> Result<String> {
 
 /// Get current git state (refs and HEAD)
 pub fn get_git_state(repo_path: &Path, identifier: &str) -> Result<GitState> {
-    let repo = Repository::open(repo_path)?;
+    let repo = open_repository(repo_path)?;
     let mut refs = std::collections::HashMap::new();
 
     // Get all references
@@ -209,7 +634,7 @@ pub fn get_git_state(repo_path: &Path, identifier: &str) -> Result<GitState> {
 }
 
 /// Sanitize identifier to be valid for Nostr
-fn sanitize_identifier(s: &str) -> String {
+pub(crate) fn sanitize_identifier(s: &str) -> String {
     s.chars()
         .map(|c| {
             if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
@@ -221,9 +646,158 @@ fn sanitize_identifier(s: &str) -> String {
         .collect()
 }
 
+/// Name of the git remote gitsmith configures for Nostr repositories
+pub const NOSTR_REMOTE: &str = "nostr";
+
+/// Parts of a `nostr://<npub>/<relay>/<identifier>` repository URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NostrUrl {
+    /// Owner public key in hex
+    pub owner: String,
+    pub relays: Vec<String>,
+    pub identifier: String,
+}
+
+impl NostrUrl {
+    /// Repository coordinate (30617:pubkey:identifier)
+    pub fn coordinate(&self) -> String {
+        format!(
+            "{kind}:{owner}:{identifier}",
+            kind = events::KIND_GIT_REPO_ANNOUNCEMENT,
+            owner = self.owner,
+            identifier = self.identifier
+        )
+    }
+
+    /// `nostr://<npub>/<relay>/<identifier>` URL, using the first relay only
+    pub fn to_url(&self) -> Result<String> {
+        let npub = PublicKey::from_hex(&self.owner)?.to_bech32()?;
+        let relay = self
+            .relays
+            .first()
+            .map(|relay| {
+                format!(
+                    "/{host}",
+                    host = relay
+                        .trim_start_matches("wss://")
+                        .trim_start_matches("ws://")
+                        .trim_end_matches('/')
+                )
+            })
+            .unwrap_or_default();
+
+        Ok(format!(
+            "nostr://{npub}{relay}/{identifier}",
+            identifier = self.identifier
+        ))
+    }
+}
+
+/// Parse a repository URL in the format produced by `announce_repository`
+pub fn parse_nostr_url(url: &str) -> Result<NostrUrl> {
+    let rest = url
+        .strip_prefix("nostr://")
+        .with_context(|| format!("Not a nostr:// URL: {url}"))?;
+    let parts: Vec<&str> = rest.trim_end_matches('/').split('/').collect();
+    if parts.len() < 2 {
+        return Err(GitsmithError::protocol(format!(
+            "Expected nostr://<npub>/<relay>/<identifier>, got: {url}"
+        )));
+    }
+
+    let owner = PublicKey::parse(parts[0])
+        .with_context(|| format!("Invalid public key in {url}"))?
+        .to_hex();
+    let identifier = parts[parts.len() - 1].to_string();
+    // Everything between the key and the identifier is the relay (which may have a path)
+    let relay = parts[1..parts.len() - 1].join("/");
+    let relays = if relay.is_empty() {
+        vec![]
+    } else {
+        vec![format!("wss://{relay}")]
+    };
+
+    Ok(NostrUrl {
+        owner,
+        relays,
+        identifier,
+    })
+}
+
+/// Parse a repository address: a `nostr://` URL, an `naddr` or a `30617:<pubkey>:<identifier>` coordinate
+pub fn parse_repository_address(address: &str) -> Result<NostrUrl> {
+    let address = address.trim();
+    if address.starts_with("nostr://") {
+        return parse_nostr_url(address);
+    }
+
+    let address = address.trim_start_matches("nostr:");
+    let (coordinate, relays) = match Nip19Coordinate::from_bech32(address) {
+        Ok(naddr) => (
+            naddr.coordinate,
+            naddr.relays.iter().map(|r| r.to_string()).collect(),
+        ),
+        Err(_) => (
+            Coordinate::from_kpi_format(address)
+                .with_context(|| format!("Invalid repository address: {address}"))?,
+            vec![],
+        ),
+    };
+    if coordinate.kind != Kind::from(events::KIND_GIT_REPO_ANNOUNCEMENT) {
+        return Err(GitsmithError::protocol(format!(
+            "{address} is not a repository announcement (kind {kind})",
+            kind = coordinate.kind
+        )));
+    }
+
+    Ok(NostrUrl {
+        owner: coordinate.public_key.to_hex(),
+        relays,
+        identifier: coordinate.identifier,
+    })
+}
+
+/// Pick the clone URL the `nostr` remote fetches from, preferring https
+pub fn preferred_clone_url(clone_urls: &[String]) -> Option<&str> {
+    clone_urls
+        .iter()
+        .find(|url| url.starts_with("https://"))
+        .or_else(|| clone_urls.iter().find(|url| !url.starts_with("nostr://")))
+        .map(String::as_str)
+}
+
+/// Create or update the `nostr` remote
+///
+/// Fetches go straight to `clone_url`, while pushes go through `nostr_url` so the
+/// `git-remote-nostr` helper can publish the new repository state after pushing.
+pub fn configure_nostr_remote(repo_path: &Path, clone_url: &str, nostr_url: &str) -> Result<()> {
+    let repo = open_repository(repo_path)?;
+
+    if repo.find_remote(NOSTR_REMOTE).is_ok() {
+        repo.remote_set_url(NOSTR_REMOTE, clone_url)?;
+    } else {
+        repo.remote(NOSTR_REMOTE, clone_url)?;
+    }
+    repo.remote_set_pushurl(NOSTR_REMOTE, Some(nostr_url))?;
+
+    Ok(())
+}
+
+/// Sign and broadcast a repository state event (Kind 30618)
+pub async fn publish_git_state(
+    network: &Network,
+    signer: &dyn Signer,
+    state: &GitState,
+    relays: &[String],
+) -> Result<BroadcastResult> {
+    let event = events::build_state_event(state, signer).await?;
+    let client = relay::connect(network, relays, Some(signer)).await?;
+    relay::publish_events(&client, &[event]).await
+}
+
 /// Update git config with nostr remote
 pub fn update_git_config(repo_path: &Path, nostr_url: &str) -> Result<()> {
-    let repo = Repository::open(repo_path)?;
+    let repo = open_repository(repo_path)?;
     let mut config = repo.config()?;
 
     // Save the nostr URL in git config
@@ -234,8 +808,7 @@ pub fn update_git_config(repo_path: &Path, nostr_url: &str) -> Result<()> {
 
 /// Get repository owner from git config
 pub fn get_repo_owner(repo_path: &Path) -> Result<Option<String>> {
-    let repo = Repository::open(repo_path)
-        .with_context(|| format!("Failed to open git repository at {repo_path:?}"))?;
+    let repo = open_repository(repo_path)?;
 
     let config = repo.config()?;
 
@@ -249,6 +822,100 @@ pub fn get_repo_owner(repo_path: &Path) -> Result<Option<String>> {
     Ok(None)
 }
 
+/// Account bound to the repository with `init --account`, as an npub
+pub fn get_repo_account(repo_path: &Path) -> Result<Option<String>> {
+    let repo = open_repository(repo_path)?;
+    Ok(repo.config()?.get_string("nostr.account").ok())
+}
+
+/// Bind an account (npub) to the repository, so commands sign with it instead
+/// of the globally active account
+pub fn set_repo_account(repo_path: &Path, npub: &str) -> Result<()> {
+    let repo = open_repository(repo_path)?;
+    repo.config()?.set_str("nostr.account", npub)?;
+    Ok(())
+}
+
+/// Repository this one is a fork of, recorded by `gitsmith fork`
+pub fn get_upstream(repo_path: &Path) -> Result<Option<NostrUrl>> {
+    let repo = open_repository(repo_path)?;
+    let config = repo.config()?;
+    let Ok(coordinate) = config.get_string("nostr.upstream") else {
+        return Ok(None);
+    };
+
+    let mut upstream = parse_repository_address(&coordinate)
+        .with_context(|| format!("Invalid git config nostr.upstream {coordinate}"))?;
+    let mut entries = config.multivar("nostr.upstream-relay", None)?;
+    while let Some(entry) = entries.next() {
+        if let Some(relay) = entry?.value() {
+            upstream.relays.push(relay.to_string());
+        }
+    }
+    Ok(Some(upstream))
+}
+
+/// Record the repository this one is a fork of, along with the relays it's announced on
+pub fn set_upstream(repo_path: &Path, upstream: &NostrUrl) -> Result<()> {
+    let repo = open_repository(repo_path)?;
+    let mut config = repo.config()?;
+    config.set_str("nostr.upstream", &upstream.coordinate())?;
+    let _ = config.remove_multivar("nostr.upstream-relay", ".*");
+    for relay in &upstream.relays {
+        config.set_multivar("nostr.upstream-relay", "^$", relay)?;
+    }
+    Ok(())
+}
+
+/// Git config key of the tip commit of the last series sent from a branch
+fn last_send_key(branch: &str) -> String {
+    format!("branch.{branch}.nostr-last-send")
+}
+
+fn current_branch(repo: &Repository) -> Result<String> {
+    let head = repo.head()?;
+    ensure!(
+        head.is_branch(),
+        "HEAD is detached, check out a branch first"
+    );
+    head.shorthand()
+        .map(str::to_string)
+        .context("Branch name is not valid UTF-8")
+}
+
+/// Tip commit of the last series sent from the current branch, if any
+///
+/// Fails when the branch no longer contains that commit (e.g. after a rebase),
+/// since every commit of the branch would then count as new.
+pub fn last_send(repo_path: &Path) -> Result<Option<String>> {
+    let repo = open_repository(repo_path)?;
+    let branch = current_branch(&repo)?;
+    let Ok(last) = repo.config()?.get_string(&last_send_key(&branch)) else {
+        return Ok(None);
+    };
+
+    let head = repo.head()?.peel_to_commit()?.id();
+    let last_oid = git2::Oid::from_str(&last)?;
+    let descends = head == last_oid
+        || repo
+            .graph_descendant_of(head, last_oid)
+            .with_context(|| format!("Failed to check whether {branch} contains {last}"))?;
+    ensure!(
+        descends,
+        "{branch} was rewritten since the last send ({last}), pass the commit range explicitly"
+    );
+
+    Ok(Some(last))
+}
+
+/// Record the tip commit of a series sent from the current branch
+pub fn record_last_send(repo_path: &Path, commit: &str) -> Result<()> {
+    let repo = open_repository(repo_path)?;
+    let branch = current_branch(&repo)?;
+    repo.config()?.set_str(&last_send_key(&branch), commit)?;
+    Ok(())
+}
+
 /// Update git config with full repository announcement
 pub fn update_git_config_full(
     repo_path: &Path,
@@ -256,7 +923,7 @@ pub fn update_git_config_full(
     nostr_url: &str,
     owner_npub: &str,
 ) -> Result<()> {
-    let repo = Repository::open(repo_path)?;
+    let repo = open_repository(repo_path)?;
     let mut config = repo.config()?;
 
     // Save all nostr configuration
@@ -269,13 +936,385 @@ pub fn update_git_config_full(
         config.set_str("nostr.description", &announcement.description)?;
     }
 
-    // Clear any existing relays first
-    let _ = config.remove_multivar("nostr.relay", ".*");
+    if announcement.min_pow > 0 {
+        config.set_i32("nostr.min-pow", i32::from(announcement.min_pow))?;
+    } else {
+        let _ = config.remove("nostr.min-pow");
+    }
 
-    // Save each relay
-    for relay in &announcement.relays {
-        config.set_multivar("nostr.relay", "^$", relay)?;
+    match &announcement.license {
+        Some(license) => config.set_str("nostr.license", license)?,
+        None => {
+            let _ = config.remove("nostr.license");
+        }
+    }
+
+    match &announcement.readme {
+        Some(readme) => config.set_str("nostr.readme", readme)?,
+        None => {
+            let _ = config.remove("nostr.readme");
+        }
+    }
+
+    save_relays(repo_path, &announcement.relays)?;
+
+    let _ = config.remove_multivar("nostr.blossom", ".*");
+    for server in &announcement.blossom_servers {
+        config.set_multivar("nostr.blossom", "^$", server)?;
+    }
+
+    let _ = config.remove_multivar("nostr.grasp-server", ".*");
+    for server in &announcement.grasp_servers {
+        config.set_multivar("nostr.grasp-server", "^$", server)?;
+    }
+
+    let owner = PublicKey::parse(owner_npub)?;
+    let fetch_urls = remote_helper::fetch_urls(announcement, &owner)?;
+    if let Some(clone_url) = preferred_clone_url(&fetch_urls) {
+        configure_nostr_remote(repo_path, clone_url, nostr_url)?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::Keys;
+
+    fn announce(
+        keys: &Keys,
+        identifier: &str,
+        root_commit: &str,
+        maintainers: &[&Keys],
+        created_at: u64,
+    ) -> Event {
+        let announcement = RepoAnnouncement {
+            identifier: identifier.to_string(),
+            name: identifier.to_string(),
+            description: String::new(),
+            clone_urls: vec![],
+            relays: vec![],
+            web: vec![],
+            root_commit: root_commit.to_string(),
+            maintainers: maintainers
+                .iter()
+                .map(|keys| keys.public_key().to_bech32().unwrap())
+                .collect(),
+            ci_keys: vec![],
+            grasp_servers: vec![],
+            blossom_servers: vec![],
+            min_pow: 0,
+            license: None,
+            topics: vec![],
+            readme: None,
+        };
+        events::announcement_builder(&announcement, &keys.public_key())
+            .unwrap()
+            .custom_created_at(Timestamp::from(created_at))
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_maintainers() {
+        let [owner, alice, bob, mallory, carol, ci] = std::array::from_fn(|_| Keys::generate());
+        let root = "a".repeat(40);
+        // Alice declares a CI key, mallory's declaration isn't trusted
+        let with_ci = |keys: &Keys, maintainers: &[&Keys]| {
+            let announced = announce(keys, "repo", &root, maintainers, 2_500);
+            EventBuilder::new(announced.kind, "")
+                .tags(announced.tags.clone())
+                .tag(nostr::Tag::parse(["ci", &ci.public_key().to_hex()]).unwrap())
+                .custom_created_at(announced.created_at)
+                .sign_with_keys(keys)
+                .unwrap()
+        };
+        let announcements = vec![
+            // Superseded, the owner no longer trusts mallory
+            announce(&owner, "repo", &root, &[&mallory], 1_000),
+            announce(&owner, "repo", &root, &[&alice, &carol], 2_000),
+            // Alice trusts bob, who announced the repository under another identifier
+            announce(&alice, "repo", &root, &[&bob], 2_000),
+            announce(&alice, "alice-fork", &root, &[], 3_000),
+            announce(&bob, "fork-of-repo", &root, &[], 2_000),
+            announce(&mallory, "repo", &root, &[], 2_000),
+            with_ci(&alice, &[&bob]),
+        ];
+        let coordinate = format!("30617:{owner}:repo", owner = owner.public_key());
+
+        let maintainers = resolve_maintainers(&coordinate, &announcements).unwrap();
+        assert_eq!(
+            maintainers.coordinates,
+            [
+                coordinate.clone(),
+                format!("30617:{alice}:repo", alice = alice.public_key()),
+                format!("30617:{bob}:fork-of-repo", bob = bob.public_key()),
+            ]
+        );
+        // Carol is trusted without an announcement of her own
+        assert!(maintainers.is_maintainer(&carol.public_key().to_hex()));
+        assert!(!maintainers.is_maintainer(&mallory.public_key().to_hex()));
+        assert_eq!(maintainers.ci_keys, [ci.public_key()]);
+        assert!(maintainers.check_reporters().contains(&ci.public_key()));
+
+        let with_mallory = [announcements[1].clone(), with_ci(&mallory, &[])];
+        let maintainers = resolve_maintainers(&coordinate, &with_mallory).unwrap();
+        assert!(maintainers.ci_keys.is_empty());
+
+        let alone = resolve_maintainers(&coordinate, &[]).unwrap();
+        assert_eq!(alone.coordinates, [coordinate]);
+        assert_eq!(alone.keys, [owner.public_key()]);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_event() {
+        let keys = Keys::generate();
+        let state = GitState {
+            identifier: "repo".to_string(),
+            refs: [("refs/heads/main".to_string(), "abc123".to_string())].into(),
+        };
+        let event = events::build_state_event(&state, &keys).await.unwrap();
+        let old = EventBuilder::new(event.kind, "")
+            .tags(event.tags.clone())
+            .custom_created_at(nostr::Timestamp::from(1_000))
+            .pow(1)
+            .sign_with_keys(&keys)
+            .unwrap();
+
+        let refreshed = refresh_event(&old, &keys).await.unwrap();
+        assert!(refreshed.created_at > old.created_at);
+        assert_eq!(refreshed.tags.identifier(), Some("repo"));
+        assert!(
+            refreshed
+                .tags
+                .iter()
+                .any(|tag| tag.as_slice()[0] == "refs/heads/main")
+        );
+        assert!(
+            refreshed
+                .tags
+                .iter()
+                .all(|tag| tag.kind() != TagKind::Nonce)
+        );
+
+        assert!(refresh_event(&old, &Keys::generate()).await.is_err());
+    }
+
+    #[test]
+    fn test_state_at() {
+        let keys = Keys::generate();
+        let state = |secs: u64| {
+            EventBuilder::new(Kind::from(events::KIND_GIT_STATE), "")
+                .custom_created_at(Timestamp::from(secs))
+                .sign_with_keys(&keys)
+                .unwrap()
+        };
+        let history = vec![state(100), state(300), state(200)];
+
+        let found = state_at(history.clone(), Timestamp::from(250)).unwrap();
+        assert_eq!(found.created_at, Timestamp::from(200));
+        let found = state_at(history.clone(), Timestamp::from(300)).unwrap();
+        assert_eq!(found.created_at, Timestamp::from(300));
+        assert!(state_at(history, Timestamp::from(50)).is_none());
+    }
+
+    #[test]
+    fn test_parse_nostr_url() {
+        let keys = Keys::generate();
+        let npub = keys.public_key().to_bech32().unwrap();
+
+        let url = parse_nostr_url(&format!("nostr://{npub}/relay.damus.io/my-repo")).unwrap();
+        assert_eq!(url.owner, keys.public_key().to_hex());
+        assert_eq!(url.relays, vec!["wss://relay.damus.io"]);
+        assert_eq!(url.identifier, "my-repo");
+        assert_eq!(
+            url.coordinate(),
+            format!("30617:{owner}:my-repo", owner = url.owner)
+        );
+
+        let url = parse_nostr_url(&format!("nostr://{npub}/my-repo")).unwrap();
+        assert!(url.relays.is_empty());
+
+        assert!(parse_nostr_url("https://github.com/owner/repo").is_err());
+        assert!(parse_nostr_url("nostr://not-a-key/relay/repo").is_err());
+
+        let coordinate = Coordinate::new(
+            Kind::from(events::KIND_GIT_REPO_ANNOUNCEMENT),
+            keys.public_key(),
+        )
+        .identifier("my-repo");
+        let relay = RelayUrl::parse("wss://relay.damus.io").unwrap();
+        let naddr = Nip19Coordinate::new(coordinate.clone(), [relay])
+            .to_bech32()
+            .unwrap();
+        let address = parse_repository_address(&naddr).unwrap();
+        assert_eq!(address.identifier, "my-repo");
+        assert_eq!(address.relays.len(), 1);
+        assert_eq!(
+            address.to_url().unwrap(),
+            format!("nostr://{npub}/relay.damus.io/my-repo")
+        );
+        assert_eq!(
+            parse_repository_address(&coordinate.to_string())
+                .unwrap()
+                .coordinate(),
+            address.coordinate()
+        );
+        // Other addressable kinds are rejected
+        assert!(
+            parse_repository_address(&format!("30620:{pk}:org", pk = keys.public_key().to_hex()))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_get_publishable_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let sig = git2::Signature::now("Alice", "alice@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let commit = repo
+            .commit(Some("HEAD"), &sig, &sig, "Initial", &tree, &[])
+            .unwrap();
+        repo.reference("refs/tags/v1", commit, false, "").unwrap();
+        repo.reference("refs/remotes/origin/main", commit, false, "")
+            .unwrap();
+
+        let state = get_publishable_state(dir.path(), "repo").unwrap();
+        let mut names: Vec<&str> = state.refs.keys().map(String::as_str).collect();
+        names.sort();
+        let branch = repo.head().unwrap().name().unwrap().to_string();
+        assert_eq!(names, vec!["HEAD", branch.as_str(), "refs/tags/v1"]);
+    }
+
+    #[test]
+    fn test_verify_identity() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let sig = git2::Signature::now("Alice", "alice@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let root = repo
+            .commit(Some("HEAD"), &sig, &sig, "Initial", &tree, &[])
+            .unwrap()
+            .to_string();
+
+        let keys = Keys::generate();
+        let parse = |root_commit: &str| {
+            events::parse_announcement_event(&announce(&keys, "repo", root_commit, &[], 1)).unwrap()
+        };
+        assert_eq!(
+            verify_identity(dir.path(), &parse(&root)).unwrap(),
+            Identity::Verified
+        );
+        assert_eq!(
+            verify_identity(dir.path(), &parse("")).unwrap(),
+            Identity::Unverified
+        );
+        let other = "0123456789abcdef0123456789abcdef01234567";
+        assert_eq!(
+            verify_identity(dir.path(), &parse(other)).unwrap(),
+            Identity::Mismatch {
+                announced: other.to_string(),
+                local: root,
+            }
+        );
+    }
+
+    #[test]
+    fn test_cached_announcement() {
+        let keys = Keys::generate();
+        let store = crate::cache::MemoryStore::new();
+        let coordinate = format!("30617:{pubkey}:repo", pubkey = keys.public_key().to_hex());
+        assert!(cached_announcement(&store, &coordinate).unwrap().is_none());
+
+        store
+            .save_events(&[
+                announce(&keys, "repo", "old", &[], 1),
+                announce(&keys, "repo", "new", &[], 2),
+                announce(&keys, "other", "other", &[], 3),
+            ])
+            .unwrap();
+        let announcement = cached_announcement(&store, &coordinate).unwrap().unwrap();
+        assert_eq!(announcement.root_commit, "new");
+    }
+
+    #[test]
+    fn test_configure_nostr_remote() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let clone_urls = vec![
+            "git@github.com:owner/repo.git".to_string(),
+            "https://github.com/owner/repo.git".to_string(),
+        ];
+        let clone_url = preferred_clone_url(&clone_urls).unwrap();
+        assert_eq!(clone_url, "https://github.com/owner/repo.git");
+
+        configure_nostr_remote(dir.path(), clone_url, "nostr://npub1x/relay/repo").unwrap();
+        // Reconfiguring updates the existing remote instead of failing
+        configure_nostr_remote(dir.path(), clone_url, "nostr://npub1y/relay/repo").unwrap();
+
+        let remote = repo.find_remote(NOSTR_REMOTE).unwrap();
+        assert_eq!(remote.url(), Some(clone_url));
+        assert_eq!(remote.pushurl(), Some("nostr://npub1y/relay/repo"));
+    }
+
+    #[test]
+    fn test_repo_account() {
+        let dir = tempfile::tempdir().unwrap();
+        Repository::init(dir.path()).unwrap();
+        assert_eq!(get_repo_account(dir.path()).unwrap(), None);
+
+        let npub = Keys::generate().public_key().to_bech32().unwrap();
+        set_repo_account(dir.path(), &npub).unwrap();
+        assert_eq!(get_repo_account(dir.path()).unwrap(), Some(npub));
+    }
+
+    #[test]
+    fn test_upstream() {
+        let dir = tempfile::tempdir().unwrap();
+        Repository::init(dir.path()).unwrap();
+        assert!(get_upstream(dir.path()).unwrap().is_none());
+
+        let upstream = NostrUrl {
+            owner: Keys::generate().public_key().to_hex(),
+            relays: vec![
+                "wss://relay.example.com".to_string(),
+                "wss://other.example.com".to_string(),
+            ],
+            identifier: "repo".to_string(),
+        };
+        set_upstream(dir.path(), &upstream).unwrap();
+        set_upstream(dir.path(), &upstream).unwrap();
+        assert_eq!(get_upstream(dir.path()).unwrap(), Some(upstream));
+    }
+
+    #[test]
+    fn test_last_send() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let sig = git2::Signature::now("Alice", "alice@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let first = repo
+            .commit(Some("HEAD"), &sig, &sig, "First", &tree, &[])
+            .unwrap();
+        assert_eq!(last_send(dir.path()).unwrap(), None);
+
+        record_last_send(dir.path(), &first.to_string()).unwrap();
+        let parent = repo.find_commit(first).unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, "Second", &tree, &[&parent])
+            .unwrap();
+        assert_eq!(last_send(dir.path()).unwrap(), Some(first.to_string()));
+
+        // A commit the branch doesn't contain means history was rewritten
+        let orphan = repo.commit(None, &sig, &sig, "Orphan", &tree, &[]).unwrap();
+        record_last_send(dir.path(), &orphan.to_string()).unwrap();
+        assert!(last_send(dir.path()).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+use nostr::{Event, EventId, Filter, Tag};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use strum::{Display, EnumString};
+
+use crate::comments;
+use crate::error::{Context, Result, ensure};
+use crate::pull_request;
+use crate::relay::{self, Network};
+use crate::signer::{self, Signer};
+use crate::types::BroadcastResult;
+
+/// NIP-32 label namespace of review verdicts
+pub const REVIEW_NAMESPACE: &str = "review";
+
+/// Verdict of a review, published as a NIP-32 label on a NIP-22 comment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display, EnumString)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum ReviewVerdict {
+    Approved,
+    ChangesRequested,
+}
+
+/// Latest verdict of each reviewer of a PR
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReviewSummary {
+    /// Public keys (hex) of the reviewers who approved
+    pub approved_by: Vec<String>,
+    /// Public keys (hex) of the reviewers who requested changes
+    pub changes_requested_by: Vec<String>,
+}
+
+impl ReviewSummary {
+    pub fn is_empty(&self) -> bool {
+        self.approved_by.is_empty() && self.changes_requested_by.is_empty()
+    }
+
+    /// Overall state: changes requested by anyone outweigh approvals
+    pub fn state(&self) -> Option<ReviewVerdict> {
+        if !self.changes_requested_by.is_empty() {
+            Some(ReviewVerdict::ChangesRequested)
+        } else if !self.approved_by.is_empty() {
+            Some(ReviewVerdict::Approved)
+        } else {
+            None
+        }
+    }
+}
+
+/// Build and sign a review of `pr`, a comment on it labelled with the verdict
+///
+/// Approvals may leave the message empty, change requests must say what to change.
+pub async fn build_review_event(
+    signer: &dyn Signer,
+    pr: &Event,
+    verdict: ReviewVerdict,
+    message: &str,
+    repo_coordinate: Option<&str>,
+) -> Result<Event> {
+    let content = match verdict {
+        ReviewVerdict::Approved if message.trim().is_empty() => "Approved",
+        ReviewVerdict::Approved => message,
+        ReviewVerdict::ChangesRequested => {
+            ensure!(
+                !message.trim().is_empty(),
+                "Requesting changes needs a message saying what to change"
+            );
+            message
+        }
+    };
+    let verdict = verdict.to_string();
+    let builder = comments::comment_builder(pr, None, content, repo_coordinate)?
+        .tag(Tag::parse(["L", REVIEW_NAMESPACE])?)
+        .tag(Tag::parse(["l", &verdict, REVIEW_NAMESPACE])?);
+    signer::sign(signer, builder).await
+}
+
+/// Verdict of a review event, None for plain comments
+pub fn review_verdict(event: &Event) -> Option<ReviewVerdict> {
+    event.tags.iter().find_map(|tag| {
+        let tag = tag.as_slice();
+        (tag.len() > 2 && tag[0] == "l" && tag[2] == REVIEW_NAMESPACE)
+            .then(|| tag[1].parse().ok())
+            .flatten()
+    })
+}
+
+/// Summarize the reviews of the PR `pr_id` among `events`, keeping the latest
+/// review of each reviewer
+pub fn summarize_reviews(events: &[Event], pr_id: &str) -> ReviewSummary {
+    let mut latest: HashMap<String, (u64, EventId, ReviewVerdict)> = HashMap::new();
+    for event in events {
+        let Some(verdict) = review_verdict(event) else {
+            continue;
+        };
+        let on_pr = event.tags.iter().any(|tag| {
+            let tag = tag.as_slice();
+            tag.len() > 1 && tag[0] == "E" && tag[1] == pr_id
+        });
+        if !on_pr {
+            continue;
+        }
+        let review = (event.created_at.as_u64(), event.id, verdict);
+        let entry = latest.entry(event.pubkey.to_hex()).or_insert(review);
+        // Ties are broken by event ID for deterministic results
+        if (review.0, review.1) > (entry.0, entry.1) {
+            *entry = review;
+        }
+    }
+
+    let mut summary = ReviewSummary::default();
+    for (reviewer, (_, _, verdict)) in latest {
+        match verdict {
+            ReviewVerdict::Approved => summary.approved_by.push(reviewer),
+            ReviewVerdict::ChangesRequested => summary.changes_requested_by.push(reviewer),
+        }
+    }
+    summary.approved_by.sort();
+    summary.changes_requested_by.sort();
+    summary
+}
+
+/// Format a review summary for display (e.g. "✅ 2 approved, 🔁 1 changes requested")
+pub fn format_reviews(summary: &ReviewSummary) -> String {
+    let mut parts = Vec::new();
+    if !summary.approved_by.is_empty() {
+        parts.push(format!(
+            "✅ {count} approved",
+            count = summary.approved_by.len()
+        ));
+    }
+    if !summary.changes_requested_by.is_empty() {
+        parts.push(format!(
+            "🔁 {count} changes requested",
+            count = summary.changes_requested_by.len()
+        ));
+    }
+    parts.join(", ")
+}
+
+/// Publish a review of a pull request
+pub async fn post_review(
+    network: &Network,
+    signer: &dyn Signer,
+    pr_event_id: &str,
+    verdict: ReviewVerdict,
+    message: &str,
+    repo_coordinate: Option<&str>,
+    relays: &[String],
+) -> Result<BroadcastResult> {
+    let pr_id = EventId::from_hex(pr_event_id)
+        .with_context(|| format!("Invalid event ID: {pr_event_id}"))?;
+    let client = relay::connect(network, relays, Some(signer)).await?;
+
+    let found = relay::fetch_events(
+        network,
+        &client,
+        vec![Filter::new().id(pr_id)],
+        Duration::from_secs(5),
+    )
+    .await?;
+    let pr = pull_request::pull_request_event(found, pr_event_id)?;
+    let event = build_review_event(signer, &pr, verdict, message, repo_coordinate).await?;
+
+    relay::publish_events(&client, &[event]).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patches::KIND_PULL_REQUEST;
+    use nostr::{EventBuilder, Keys, Timestamp};
+
+    #[tokio::test]
+    async fn test_review_event() {
+        let keys = Keys::generate();
+        let pr = EventBuilder::new(KIND_PULL_REQUEST, "pr")
+            .sign_with_keys(&keys)
+            .unwrap();
+
+        let approval = build_review_event(
+            &keys,
+            &pr,
+            ReviewVerdict::Approved,
+            "",
+            Some("30617:abc:repo"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(approval.content, "Approved");
+        assert_eq!(review_verdict(&approval), Some(ReviewVerdict::Approved));
+        assert_eq!(
+            comments::parse_comment_event(&approval).parent_id,
+            Some(pr.id.to_hex())
+        );
+
+        let changes = build_review_event(
+            &keys,
+            &pr,
+            ReviewVerdict::ChangesRequested,
+            "Add tests",
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            review_verdict(&changes),
+            Some(ReviewVerdict::ChangesRequested)
+        );
+        assert!(
+            build_review_event(&keys, &pr, ReviewVerdict::ChangesRequested, " ", None)
+                .await
+                .is_err()
+        );
+
+        let comment = comments::build_comment_event(&keys, &pr, None, "Nice", None)
+            .await
+            .unwrap();
+        assert_eq!(review_verdict(&comment), None);
+    }
+
+    #[test]
+    fn test_summarize_reviews() {
+        let author = Keys::generate();
+        let alice = Keys::generate();
+        let bob = Keys::generate();
+        let pr = EventBuilder::new(KIND_PULL_REQUEST, "pr")
+            .sign_with_keys(&author)
+            .unwrap();
+        let other = EventBuilder::new(KIND_PULL_REQUEST, "other")
+            .sign_with_keys(&author)
+            .unwrap();
+        let review = |keys: &Keys, pr: &Event, verdict: ReviewVerdict, created_at: u64| {
+            comments::comment_builder(pr, None, "review", None)
+                .unwrap()
+                .tag(Tag::parse(["L", REVIEW_NAMESPACE]).unwrap())
+                .tag(Tag::parse(["l", &verdict.to_string(), REVIEW_NAMESPACE]).unwrap())
+                .custom_created_at(Timestamp::from(created_at))
+                .sign_with_keys(keys)
+                .unwrap()
+        };
+
+        let events = vec![
+            // Alice asked for changes, then approved
+            review(&alice, &pr, ReviewVerdict::ChangesRequested, 100),
+            review(&alice, &pr, ReviewVerdict::Approved, 200),
+            review(&bob, &pr, ReviewVerdict::ChangesRequested, 150),
+            // Reviews of other PRs don't count
+            review(&bob, &other, ReviewVerdict::Approved, 300),
+        ];
+        let summary = summarize_reviews(&events, &pr.id.to_hex());
+        assert_eq!(summary.approved_by, [alice.public_key().to_hex()]);
+        assert_eq!(summary.changes_requested_by, [bob.public_key().to_hex()]);
+        assert_eq!(summary.state(), Some(ReviewVerdict::ChangesRequested));
+        assert_eq!(
+            format_reviews(&summary),
+            "✅ 1 approved, 🔁 1 changes requested"
+        );
+
+        let summary = summarize_reviews(&events, &other.id.to_hex());
+        assert_eq!(summary.state(), Some(ReviewVerdict::Approved));
+        assert!(summarize_reviews(&[], &pr.id.to_hex()).is_empty());
+    }
+}
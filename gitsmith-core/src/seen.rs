@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Context, Result};
+
+/// When an event was last viewed and how much activity it had at that time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeenEntry {
+    pub seen_at: u64,
+    /// Number of comments on the PR or issue when it was viewed
+    #[serde(default)]
+    pub comments: usize,
+}
+
+/// Read state of an item compared to the last time it was viewed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "state", content = "count")]
+pub enum ReadState {
+    /// Never viewed
+    Unread,
+    /// Viewed before, but comments were added since
+    NewComments(usize),
+    Read,
+}
+
+impl ReadState {
+    /// Short marker shown next to unread items, empty for read ones
+    pub fn badge(self) -> String {
+        match self {
+            ReadState::Unread => "● new".to_string(),
+            ReadState::NewComments(count) => format!("● +{count} comment(s)"),
+            ReadState::Read => String::new(),
+        }
+    }
+
+    pub fn is_unread(self) -> bool {
+        self != ReadState::Read
+    }
+}
+
+/// Locally persisted record of viewed PR, issue and comment events
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SeenStore {
+    /// Seen entries keyed by event ID (hex)
+    pub events: BTreeMap<String, SeenEntry>,
+}
+
+impl SeenStore {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read seen events from {path:?}"))?;
+
+        serde_json::from_str(&data).context("Failed to parse seen events")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {parent:?}"))?;
+        }
+
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(path, data).with_context(|| format!("Failed to write seen events to {path:?}"))
+    }
+
+    /// Record an event as viewed with `comments` comments
+    pub fn mark_read(&mut self, event_id: &str, comments: usize) {
+        let entry = SeenEntry {
+            seen_at: nostr::Timestamp::now().as_u64(),
+            comments,
+        };
+        self.events.insert(event_id.to_string(), entry);
+    }
+
+    /// Forget that an event was viewed. Returns false if it never was.
+    pub fn mark_unread(&mut self, event_id: &str) -> bool {
+        self.events.remove(event_id).is_some()
+    }
+
+    pub fn read_state(&self, event_id: &str, comments: usize) -> ReadState {
+        match self.events.get(event_id) {
+            None => ReadState::Unread,
+            Some(entry) if comments > entry.comments => {
+                ReadState::NewComments(comments - entry.comments)
+            }
+            Some(_) => ReadState::Read,
+        }
+    }
+}
+
+/// Get the default path of the seen events store
+pub fn get_seen_storage_path() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("Failed to get home directory")?;
+    Ok(home.join(".config").join("gitsmith").join("seen.json"))
+}
+
+/// Load the seen events store from its default location
+pub fn load() -> Result<SeenStore> {
+    SeenStore::load(&get_seen_storage_path()?)
+}
+
+/// Mark events as read in the default store
+pub fn mark_read(events: &[(String, usize)]) -> Result<()> {
+    let path = get_seen_storage_path()?;
+    let mut store = SeenStore::load(&path)?;
+    for (event_id, comments) in events {
+        store.mark_read(event_id, *comments);
+    }
+    store.save(&path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("seen.json");
+
+        let mut store = SeenStore::load(&path).unwrap();
+        assert_eq!(store.read_state("pr", 2), ReadState::Unread);
+
+        store.mark_read("pr", 2);
+        store.save(&path).unwrap();
+
+        let mut store = SeenStore::load(&path).unwrap();
+        assert_eq!(store.read_state("pr", 2), ReadState::Read);
+        assert_eq!(store.read_state("pr", 5), ReadState::NewComments(3));
+        assert_eq!(ReadState::NewComments(3).badge(), "● +3 comment(s)");
+        assert!(ReadState::Read.badge().is_empty());
+
+        assert!(store.mark_unread("pr"));
+        assert!(!store.mark_unread("pr"));
+        assert_eq!(store.read_state("pr", 2), ReadState::Unread);
+    }
+}
@@ -0,0 +1,187 @@
+use futures::future::BoxFuture;
+use nostr::nips::nip46::NostrConnectURI;
+use nostr::{Event, EventBuilder, Keys, NostrSigner, PublicKey, UnsignedEvent};
+use nostr_connect::client::NostrConnect;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{Context, Result, ensure};
+use crate::hooks;
+
+/// How long to wait for the remote signer to answer a request
+///
+/// Generous, since the signer may ask its user to approve each request.
+pub const BUNKER_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Signs events on behalf of an account
+///
+/// Implemented for in-memory [`Keys`]. Remote (NIP-46) and hardware signers
+/// implement it so events can be published without gitsmith holding the
+/// private key.
+pub trait Signer: fmt::Debug + Send + Sync {
+    /// Public key the events are signed for
+    fn public_key(&self) -> PublicKey;
+
+    /// Sign an event built for [`Signer::public_key`]
+    fn sign_event(&self, unsigned: UnsignedEvent) -> BoxFuture<'_, Result<Event>>;
+
+    /// The signer as used by relay clients, for NIP-42 authentication and to
+    /// sign again events changed by the pre-sign hook
+    fn nostr_signer(&self) -> Arc<dyn NostrSigner>;
+
+    /// Command run on every event before it's signed, see
+    /// [`hooks::run_pre_sign_hook`]
+    fn pre_sign_hook(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl Signer for Keys {
+    fn public_key(&self) -> PublicKey {
+        Keys::public_key(self)
+    }
+
+    fn sign_event(&self, unsigned: UnsignedEvent) -> BoxFuture<'_, Result<Event>> {
+        Box::pin(async move { Ok(unsigned.sign_with_keys(self)?) })
+    }
+
+    fn nostr_signer(&self) -> Arc<dyn NostrSigner> {
+        Arc::new(self.clone())
+    }
+}
+
+/// Signs events with a NIP-46 remote signer (bunker)
+///
+/// Requests are encrypted with local app keys, which the remote signer
+/// authorizes when the connection is first made.
+#[derive(Debug, Clone)]
+pub struct BunkerSigner {
+    connect: NostrConnect,
+    public_key: PublicKey,
+}
+
+impl BunkerSigner {
+    /// Connect to the remote signer of a `bunker://` URI and ask it for the user's public key
+    pub async fn connect(uri: &str, app_keys: Keys) -> Result<Self> {
+        let uri = parse_bunker_uri(uri)?;
+        let connect = NostrConnect::new(uri, app_keys, BUNKER_TIMEOUT, None)?;
+        let public_key = connect
+            .get_public_key()
+            .await
+            .context("Remote signer did not return a public key")?;
+        Ok(Self {
+            connect,
+            public_key,
+        })
+    }
+
+    /// Reuse a connection made earlier with [`BunkerSigner::connect`]
+    ///
+    /// Nothing is sent to the remote signer until the first event is signed.
+    pub fn reconnect(uri: &str, app_keys: Keys, public_key: PublicKey) -> Result<Self> {
+        let connect = NostrConnect::new(parse_bunker_uri(uri)?, app_keys, BUNKER_TIMEOUT, None)?;
+        connect.non_secure_set_user_public_key(public_key)?;
+        Ok(Self {
+            connect,
+            public_key,
+        })
+    }
+
+    /// `bunker://` URI of the remote signer, to reconnect later
+    pub async fn bunker_uri(&self) -> Result<String> {
+        Ok(self.connect.bunker_uri().await?.to_string())
+    }
+}
+
+impl Signer for BunkerSigner {
+    fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+
+    fn sign_event(&self, unsigned: UnsignedEvent) -> BoxFuture<'_, Result<Event>> {
+        Box::pin(async move {
+            NostrSigner::sign_event(&self.connect, unsigned)
+                .await
+                .context("Remote signer failed to sign the event")
+        })
+    }
+
+    fn nostr_signer(&self) -> Arc<dyn NostrSigner> {
+        Arc::new(self.connect.clone())
+    }
+}
+
+fn parse_bunker_uri(uri: &str) -> Result<NostrConnectURI> {
+    let uri = NostrConnectURI::parse(uri)?;
+    ensure!(
+        matches!(uri, NostrConnectURI::Bunker { .. }),
+        "Expected a bunker:// URI from the remote signer"
+    );
+    Ok(uri)
+}
+
+/// Build an event, run the pre-sign hook on it and sign it
+pub async fn sign(signer: &dyn Signer, builder: EventBuilder) -> Result<Event> {
+    sign_with_pow(signer, builder, 0).await
+}
+
+/// Like [`sign`], mining proof of work of `difficulty` once the hook is done
+/// with the event
+pub async fn sign_with_pow(
+    signer: &dyn Signer,
+    builder: EventBuilder,
+    difficulty: u8,
+) -> Result<Event> {
+    let mut unsigned = builder.build(signer.public_key());
+    if let Some(command) = signer.pre_sign_hook() {
+        unsigned = hooks::run_pre_sign_hook(command, &unsigned)?;
+    }
+    let unsigned = match difficulty {
+        0 => unsigned,
+        difficulty => hooks::mine(unsigned, difficulty),
+    };
+    signer.sign_event(unsigned).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::Kind;
+
+    #[tokio::test]
+    async fn test_sign_with_keys() {
+        let keys = Keys::generate();
+        let event = sign(&keys, EventBuilder::new(Kind::TextNote, "hello"))
+            .await
+            .unwrap();
+        assert_eq!(event.pubkey, keys.public_key());
+        assert!(event.verify().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_sign_runs_pre_sign_hook() {
+        let keys = Keys::generate();
+        let signer = hooks::HookedSigner::new(
+            Box::new(keys.clone()),
+            "sed 's/hello/hello world/'".to_string(),
+        );
+        let event = sign(&signer, EventBuilder::new(Kind::TextNote, "hello"))
+            .await
+            .unwrap();
+        assert_eq!(event.content, "hello world");
+        assert_eq!(event.pubkey, keys.public_key());
+        assert!(event.verify().is_ok());
+    }
+
+    #[test]
+    fn test_parse_bunker_uri() {
+        let remote = Keys::generate().public_key();
+        let uri = format!("bunker://{remote}?relay=wss://relay.nsec.app&secret=abc");
+        assert!(parse_bunker_uri(&uri).is_ok());
+
+        let client = format!("nostrconnect://{remote}?relay=wss://relay.nsec.app&metadata={{}}");
+        assert!(parse_bunker_uri(&client).is_err());
+        assert!(parse_bunker_uri("https://example.com").is_err());
+    }
+}
@@ -0,0 +1,369 @@
+//! Reconciling the state events of several maintainers
+//!
+//! State events (Kind 30618) are replaceable per author, so every maintainer
+//! keeps their own, while clients read the newest one as the repository
+//! state. When two maintainers publish around the same time, or one publishes
+//! from a stale clone, the newest state silently drops the other's updates.
+//! [`reconcile`] compares the latest state of each maintainer and builds a
+//! snapshot that keeps every update it can order by commit ancestry.
+
+use git2::{Oid, Repository};
+use nostr::{Event, Filter, Kind, PublicKey, ToBech32};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::Duration;
+
+use crate::error::{Context, Result};
+use crate::events;
+use crate::relay::{self, Network};
+use crate::types::GitState;
+
+/// States published this close to the newest one are treated as concurrent,
+/// their publishers could not have seen each other's refs
+pub const CONCURRENT_STATE_SECS: u64 = 10 * 60;
+
+/// Latest state event of one maintainer
+#[derive(Debug, Clone)]
+pub struct MaintainerState {
+    pub author: PublicKey,
+    pub event: Event,
+    pub state: GitState,
+}
+
+/// A ref the maintainers' states disagree on and ancestry can't settle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RefConflict {
+    pub name: String,
+    /// Value published by each maintainer (npub), maintainers without the ref are left out
+    pub values: BTreeMap<String, String>,
+}
+
+/// Snapshot combining the latest states of all maintainers
+#[derive(Debug, Clone)]
+pub struct Reconciliation {
+    /// Reconciled refs, conflicting refs keep the value of the newest state
+    pub state: GitState,
+    /// Refs whose reconciled value differs from the newest state
+    pub updated: Vec<String>,
+    pub conflicts: Vec<RefConflict>,
+}
+
+impl Reconciliation {
+    /// Whether the newest state already reflects every maintainer's updates
+    pub fn is_consistent(&self) -> bool {
+        self.updated.is_empty() && self.conflicts.is_empty()
+    }
+}
+
+/// Latest state event of every maintainer, newest first
+///
+/// Events of authors that aren't maintainers are ignored, anyone can publish
+/// a state event with the repository's identifier.
+pub fn latest_states(found: &[Event], maintainers: &[PublicKey]) -> Vec<MaintainerState> {
+    let mut latest: HashMap<PublicKey, &Event> = HashMap::new();
+    for event in found {
+        if event.kind != Kind::from(events::KIND_GIT_STATE) || !maintainers.contains(&event.pubkey)
+        {
+            continue;
+        }
+        let newer = latest
+            .get(&event.pubkey)
+            .is_none_or(|current| events::newest_first(event, current).is_lt());
+        if newer {
+            latest.insert(event.pubkey, event);
+        }
+    }
+
+    let mut states: Vec<&Event> = latest.into_values().collect();
+    states.sort_by(|a, b| events::newest_first(a, b));
+    states
+        .into_iter()
+        .filter_map(|event| match events::parse_state_event(event) {
+            Ok(state) => Some(MaintainerState {
+                author: event.pubkey,
+                event: event.clone(),
+                state,
+            }),
+            Err(e) => {
+                tracing::debug!("Skipping state event {id}: {e}", id = event.id);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Combine the latest states of the maintainers (newest first)
+///
+/// For every ref the value that descends from all the others wins, so updates
+/// of different maintainers fast-forward each other. `descends(commit,
+/// ancestor)` tells whether `commit` has `ancestor` in its history. A ref
+/// missing from the newest state is kept only when a concurrent state has it,
+/// older states may predate its deletion.
+pub fn reconcile(
+    states: &[MaintainerState],
+    descends: impl Fn(&str, &str) -> bool,
+) -> Option<Reconciliation> {
+    let newest = states.first()?;
+    let concurrent = |other: &MaintainerState| {
+        newest
+            .event
+            .created_at
+            .as_u64()
+            .saturating_sub(other.event.created_at.as_u64())
+            <= CONCURRENT_STATE_SECS
+    };
+
+    let names: BTreeSet<&String> = states
+        .iter()
+        .flat_map(|maintainer| maintainer.state.refs.keys())
+        .collect();
+    let mut refs = HashMap::new();
+    let mut updated = Vec::new();
+    let mut conflicts = Vec::new();
+    for name in names {
+        let current = newest.state.refs.get(name);
+        let published: Vec<(&MaintainerState, &String)> = states
+            .iter()
+            .filter(|maintainer| current.is_some() || concurrent(maintainer))
+            .filter_map(|maintainer| Some((maintainer, maintainer.state.refs.get(name)?)))
+            .collect();
+        let Some(&(_, tentative)) = published.first() else {
+            continue;
+        };
+
+        let values: BTreeSet<&String> = published.iter().map(|(_, value)| *value).collect();
+        let winner = values.iter().find(|value| {
+            values
+                .iter()
+                .all(|other| value == &other || descends(value, other))
+        });
+        let value = match winner {
+            Some(value) => (*value).clone(),
+            None => {
+                conflicts.push(RefConflict {
+                    name: name.clone(),
+                    values: published
+                        .iter()
+                        .map(|(maintainer, value)| (npub(&maintainer.author), (*value).clone()))
+                        .collect(),
+                });
+                tentative.clone()
+            }
+        };
+        if current != Some(&value) {
+            updated.push(name.clone());
+        }
+        refs.insert(name.clone(), value);
+    }
+
+    Some(Reconciliation {
+        state: GitState {
+            identifier: newest.state.identifier.clone(),
+            refs,
+        },
+        updated,
+        conflicts,
+    })
+}
+
+/// Whether `commit` is `ancestor` or has it in its history in `repo`
+///
+/// Values that aren't commit IDs, like a symbolic `ref: refs/heads/main`, and
+/// commits missing from the repository only match themselves.
+pub fn commit_descends(repo: &Repository, commit: &str, ancestor: &str) -> bool {
+    if commit == ancestor {
+        return true;
+    }
+    let (Ok(commit), Ok(ancestor)) = (Oid::from_str(commit), Oid::from_str(ancestor)) else {
+        return false;
+    };
+    repo.graph_descendant_of(commit, ancestor).unwrap_or(false)
+}
+
+/// Latest state of the repository owner and of each maintainer
+pub async fn fetch_maintainer_states(
+    network: &Network,
+    repo_coordinate: &str,
+    maintainers: &[PublicKey],
+    relays: &[String],
+    timeout: Duration,
+) -> Result<Vec<MaintainerState>> {
+    let (_kind, pubkey, identifier) = crate::patches::parse_repo_coordinate(repo_coordinate)?;
+    let owner = PublicKey::from_hex(&pubkey)
+        .with_context(|| format!("Invalid public key in coordinate {repo_coordinate}"))?;
+    let mut authors = vec![owner];
+    authors.extend(maintainers.iter().filter(|key| **key != owner));
+
+    let client = relay::connect(network, relays, None).await?;
+    let filter = Filter::new()
+        .kind(Kind::from(events::KIND_GIT_STATE))
+        .authors(authors.clone())
+        .identifier(identifier);
+    let found = relay::fetch_events(network, &client, vec![filter], timeout).await?;
+    client.disconnect().await;
+
+    Ok(latest_states(&found, &authors))
+}
+
+fn npub(public_key: &PublicKey) -> String {
+    public_key
+        .to_bech32()
+        .unwrap_or_else(|_| public_key.to_hex())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::{Keys, Timestamp};
+
+    fn commit(n: u8) -> String {
+        n.to_string().repeat(40)
+    }
+
+    /// History 1 <- 2 <- 3 and 1 <- 4, a higher commit descends from a lower one on its line
+    fn descends(commit: &str, ancestor: &str) -> bool {
+        let line = |value: &str| -> Option<(u8, u8)> {
+            let n: u8 = value[..1].parse().ok()?;
+            Some((if n == 4 { 1 } else { 0 }, n))
+        };
+        match (line(commit), line(ancestor)) {
+            (Some((_, c)), Some((_, 1))) => c >= 1,
+            (Some((lc, c)), Some((la, a))) => lc == la && c >= a,
+            _ => false,
+        }
+    }
+
+    async fn state_event(keys: &Keys, secs: u64, refs: &[(&str, String)]) -> Event {
+        let state = GitState {
+            identifier: "repo".to_string(),
+            refs: refs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+        };
+        let event = events::build_state_event(&state, keys).await.unwrap();
+        let builder = nostr::EventBuilder::new(event.kind, "")
+            .tags(event.tags.to_vec())
+            .custom_created_at(Timestamp::from(secs));
+        builder.sign_with_keys(keys).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_latest_states() {
+        let alice = Keys::generate();
+        let bob = Keys::generate();
+        let mallory = Keys::generate();
+        let found = vec![
+            state_event(&alice, 100, &[("refs/heads/main", commit(1))]).await,
+            state_event(&alice, 300, &[("refs/heads/main", commit(3))]).await,
+            state_event(&bob, 200, &[("refs/heads/main", commit(2))]).await,
+            state_event(&mallory, 400, &[("refs/heads/main", commit(4))]).await,
+        ];
+
+        let states = latest_states(&found, &[alice.public_key(), bob.public_key()]);
+        let authors: Vec<PublicKey> = states.iter().map(|s| s.author).collect();
+        assert_eq!(authors, vec![alice.public_key(), bob.public_key()]);
+        assert_eq!(states[0].state.refs["refs/heads/main"], commit(3));
+    }
+
+    #[tokio::test]
+    async fn test_reconcile() {
+        let alice = Keys::generate();
+        let bob = Keys::generate();
+        let maintainers = [alice.public_key(), bob.public_key()];
+
+        // Bob published last from a clone that missed Alice's commit on main
+        // and her new branch
+        let found = vec![
+            state_event(
+                &alice,
+                1_000,
+                &[
+                    ("refs/heads/main", commit(3)),
+                    ("refs/heads/feature", commit(2)),
+                ],
+            )
+            .await,
+            state_event(
+                &bob,
+                1_060,
+                &[("refs/heads/main", commit(2)), ("refs/tags/v1", commit(1))],
+            )
+            .await,
+        ];
+        let merged = reconcile(&latest_states(&found, &maintainers), descends).unwrap();
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.state.refs["refs/heads/main"], commit(3));
+        assert_eq!(merged.state.refs["refs/heads/feature"], commit(2));
+        assert_eq!(merged.state.refs["refs/tags/v1"], commit(1));
+        assert_eq!(
+            merged.updated,
+            vec!["refs/heads/feature", "refs/heads/main"]
+        );
+        assert!(!merged.is_consistent());
+
+        // Diverged histories can't be ordered, the newest value is kept
+        let found = vec![
+            state_event(&alice, 1_000, &[("refs/heads/main", commit(3))]).await,
+            state_event(&bob, 1_060, &[("refs/heads/main", commit(4))]).await,
+        ];
+        let merged = reconcile(&latest_states(&found, &maintainers), descends).unwrap();
+        assert_eq!(merged.state.refs["refs/heads/main"], commit(4));
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(
+            merged.conflicts[0].values,
+            BTreeMap::from([
+                (alice.public_key().to_bech32().unwrap(), commit(3)),
+                (bob.public_key().to_bech32().unwrap(), commit(4)),
+            ])
+        );
+
+        // A branch only an old state has was deleted since
+        let found = vec![
+            state_event(
+                &alice,
+                1_000,
+                &[
+                    ("refs/heads/main", commit(1)),
+                    ("refs/heads/old", commit(1)),
+                ],
+            )
+            .await,
+            state_event(
+                &bob,
+                1_000 + CONCURRENT_STATE_SECS + 1,
+                &[("refs/heads/main", commit(2))],
+            )
+            .await,
+        ];
+        let merged = reconcile(&latest_states(&found, &maintainers), descends).unwrap();
+        assert!(merged.is_consistent());
+        assert!(!merged.state.refs.contains_key("refs/heads/old"));
+
+        assert!(reconcile(&[], descends).is_none());
+    }
+
+    #[test]
+    fn test_commit_descends() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let sig = git2::Signature::now("Alice", "alice@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let first = repo
+            .commit(Some("HEAD"), &sig, &sig, "First", &tree, &[])
+            .unwrap();
+        let parent = repo.find_commit(first).unwrap();
+        let second = repo
+            .commit(Some("HEAD"), &sig, &sig, "Second", &tree, &[&parent])
+            .unwrap();
+
+        let (first, second) = (first.to_string(), second.to_string());
+        assert!(commit_descends(&repo, &second, &first));
+        assert!(commit_descends(&repo, &first, &first));
+        assert!(!commit_descends(&repo, &first, &second));
+        assert!(!commit_descends(&repo, &second, &"f".repeat(40)));
+        assert!(!commit_descends(&repo, "ref: refs/heads/main", &first));
+    }
+}
@@ -0,0 +1,282 @@
+//! Live subscriptions to many repositories over few REQs
+//!
+//! Relays cap the subscriptions open on a connection and answer extra REQs
+//! with CLOSED. [`RepoSubscriptions`] puts many repository coordinates into
+//! the `#a` tag of each filter and re-balances the groups as repositories are
+//! added and removed, re-using subscription IDs so a changed group replaces
+//! its REQ in place.
+
+use nostr::{Alphabet, Filter, Kind, SingleLetterTag, SubscriptionId, Timestamp};
+use nostr_sdk::Client;
+use std::collections::BTreeSet;
+use tracing::debug;
+
+use crate::error::{Result, ensure};
+
+/// Subscriptions used at most by default, well below common relay limits
+pub const DEFAULT_MAX_SUBSCRIPTIONS: usize = 8;
+
+/// Coordinates per filter, relays also reject overly large filters
+pub const MAX_COORDINATES_PER_FILTER: usize = 256;
+
+/// Re-subscribed groups ask for events this far back, the pool drops the
+/// events it already delivered
+const RESUBSCRIBE_OVERLAP_SECS: u64 = 60;
+
+/// Live subscription to events referencing any of a set of repositories
+pub struct RepoSubscriptions {
+    client: Client,
+    kinds: Vec<Kind>,
+    max_subscriptions: usize,
+    groups: Vec<(SubscriptionId, BTreeSet<String>)>,
+}
+
+impl RepoSubscriptions {
+    /// Subscribe to events of `kinds` on the client's relays
+    pub fn new(client: Client, kinds: impl IntoIterator<Item = Kind>) -> Self {
+        Self {
+            client,
+            kinds: kinds.into_iter().collect(),
+            max_subscriptions: DEFAULT_MAX_SUBSCRIPTIONS,
+            groups: Vec::new(),
+        }
+    }
+
+    /// Use at most `max` subscriptions
+    pub fn max_subscriptions(mut self, max: usize) -> Self {
+        self.max_subscriptions = max.max(1);
+        self
+    }
+
+    /// Watched repository coordinates
+    pub fn repos(&self) -> impl Iterator<Item = &str> {
+        self.groups
+            .iter()
+            .flat_map(|(_, repos)| repos.iter().map(String::as_str))
+    }
+
+    /// Whether events of the subscription belong to these repositories
+    pub fn contains(&self, id: &SubscriptionId) -> bool {
+        self.groups.iter().any(|(group_id, _)| group_id == id)
+    }
+
+    /// Number of open subscriptions
+    pub fn subscription_count(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Start watching a repository
+    pub async fn add_repo(&mut self, coordinate: &str) -> Result<()> {
+        let mut repos: BTreeSet<String> = self.repos().map(str::to_string).collect();
+        if repos.insert(coordinate.to_string()) {
+            self.rebalance(repos).await?;
+        }
+        Ok(())
+    }
+
+    /// Stop watching a repository
+    pub async fn remove_repo(&mut self, coordinate: &str) -> Result<()> {
+        let mut repos: BTreeSet<String> = self.repos().map(str::to_string).collect();
+        if repos.remove(coordinate) {
+            self.rebalance(repos).await?;
+        }
+        Ok(())
+    }
+
+    /// Close every subscription
+    pub async fn clear(&mut self) {
+        for (id, _) in self.groups.drain(..) {
+            self.client.unsubscribe(&id).await;
+        }
+    }
+
+    async fn rebalance(&mut self, repos: BTreeSet<String>) -> Result<()> {
+        let current: Vec<BTreeSet<String>> =
+            self.groups.iter().map(|(_, repos)| repos.clone()).collect();
+        let planned = plan_groups(&current, &repos, self.max_subscriptions)?;
+        let since = Timestamp::from(
+            Timestamp::now()
+                .as_u64()
+                .saturating_sub(RESUBSCRIBE_OVERLAP_SECS),
+        );
+
+        let mut groups = Vec::with_capacity(planned.len());
+        for (index, repos) in planned.into_iter().enumerate() {
+            let existing = self.groups.get(index);
+            let id = match existing {
+                Some((id, current)) if *current == repos => {
+                    groups.push((id.clone(), repos));
+                    continue;
+                }
+                Some((id, _)) => id.clone(),
+                None => SubscriptionId::generate(),
+            };
+            // A REQ with an open subscription's ID replaces its filter
+            let filter = Filter::new()
+                .kinds(self.kinds.clone())
+                .custom_tags(SingleLetterTag::lowercase(Alphabet::A), repos.iter())
+                .since(since);
+            self.client
+                .subscribe_with_id(id.clone(), filter, None)
+                .await?;
+            debug!(
+                "Subscription {id} watches {count} repositories",
+                count = repos.len()
+            );
+            groups.push((id, repos));
+        }
+        for (id, _) in self.groups.iter().skip(groups.len()) {
+            self.client.unsubscribe(id).await;
+        }
+        self.groups = groups;
+        Ok(())
+    }
+}
+
+/// Groups of at most [`MAX_COORDINATES_PER_FILTER`] repositories covering `repos`
+///
+/// Repositories stay in their current group where possible so few REQs
+/// change. Groups are repacked when they fit in fewer subscriptions.
+pub fn plan_groups(
+    current: &[BTreeSet<String>],
+    repos: &BTreeSet<String>,
+    max_groups: usize,
+) -> Result<Vec<BTreeSet<String>>> {
+    let needed = repos.len().div_ceil(MAX_COORDINATES_PER_FILTER);
+    ensure!(
+        needed <= max_groups,
+        "Watching {count} repositories needs {needed} subscriptions, at most {max_groups} allowed",
+        count = repos.len()
+    );
+
+    let mut groups: Vec<BTreeSet<String>> = current
+        .iter()
+        .map(|group| group.intersection(repos).cloned().collect())
+        .filter(|group: &BTreeSet<String>| !group.is_empty())
+        .collect();
+    if groups.len() > needed.max(1) {
+        // Removals left groups that fit in fewer subscriptions
+        groups = repos
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>()
+            .chunks(MAX_COORDINATES_PER_FILTER)
+            .map(|chunk| chunk.iter().cloned().collect())
+            .collect();
+        return Ok(groups);
+    }
+
+    let grouped: BTreeSet<&String> = groups.iter().flatten().collect();
+    let added: Vec<String> = repos
+        .iter()
+        .filter(|repo| !grouped.contains(repo))
+        .cloned()
+        .collect();
+    for repo in added {
+        match groups
+            .iter_mut()
+            .filter(|group| group.len() < MAX_COORDINATES_PER_FILTER)
+            .min_by_key(|group| group.len())
+        {
+            Some(group) => {
+                group.insert(repo);
+            }
+            None => groups.push(BTreeSet::from([repo])),
+        }
+    }
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coordinates(range: std::ops::Range<usize>) -> BTreeSet<String> {
+        range
+            .map(|i| format!("30617:{owner}:repo-{i}", owner = "a".repeat(64)))
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_groups() {
+        // Many repositories share one filter
+        let first = plan_groups(&[], &coordinates(0..3), 2).unwrap();
+        assert_eq!(first, vec![coordinates(0..3)]);
+
+        // Beyond the filter size a second group opens, the first is kept as is
+        let full = coordinates(0..MAX_COORDINATES_PER_FILTER);
+        let grown = plan_groups(
+            std::slice::from_ref(&full),
+            &coordinates(0..MAX_COORDINATES_PER_FILTER + 1),
+            2,
+        )
+        .unwrap();
+        assert_eq!(grown.len(), 2);
+        assert_eq!(grown[0], full);
+
+        // Shrinking below one filter merges the groups again
+        let shrunk = plan_groups(&grown, &coordinates(0..10), 2).unwrap();
+        assert_eq!(shrunk, vec![coordinates(0..10)]);
+
+        assert!(plan_groups(&[], &coordinates(0..MAX_COORDINATES_PER_FILTER + 1), 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_repo_subscriptions_follow_added_and_removed_repos() {
+        use crate::cache::MemoryStore;
+        use crate::localnet::{self, LocalRelay};
+        use crate::relay::{self, Network};
+        use nostr::{EventBuilder, Keys, Tag};
+        use nostr_sdk::RelayPoolNotification;
+        use std::sync::Arc;
+        use std::time::Duration;
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let url = localnet::relay_url(listener.local_addr().unwrap().port());
+        tokio::spawn(LocalRelay::new(Arc::new(MemoryStore::new())).serve(listener));
+        let client = relay::connect(&Network::default(), std::slice::from_ref(&url), None)
+            .await
+            .unwrap();
+
+        let repos: Vec<String> = coordinates(0..2).into_iter().collect();
+        let mut subscriptions = RepoSubscriptions::new(client.clone(), [Kind::TextNote]);
+        subscriptions.add_repo(&repos[0]).await.unwrap();
+        subscriptions.add_repo(&repos[1]).await.unwrap();
+        assert_eq!(subscriptions.subscription_count(), 1);
+        subscriptions.remove_repo(&repos[0]).await.unwrap();
+
+        let keys = Keys::generate();
+        let publisher = relay::connect(&Network::default(), std::slice::from_ref(&url), None)
+            .await
+            .unwrap();
+        let mut notifications = client.notifications();
+        for repo in &repos {
+            let note = EventBuilder::text_note(repo)
+                .tag(Tag::parse(["a", repo]).unwrap())
+                .sign_with_keys(&keys)
+                .unwrap();
+            publisher.send_event(&note).await.unwrap();
+        }
+
+        let received = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Ok(RelayPoolNotification::Event {
+                    subscription_id,
+                    event,
+                    ..
+                }) = notifications.recv().await
+                    && subscriptions.contains(&subscription_id)
+                {
+                    return event.content;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(received, repos[1]);
+
+        subscriptions.clear().await;
+        assert_eq!(subscriptions.subscription_count(), 0);
+    }
+}
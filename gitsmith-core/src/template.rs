@@ -0,0 +1,301 @@
+use git2::{IndexAddOption, Repository, Signature};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::debug;
+
+use crate::error::{Context, Result, bail, ensure};
+
+/// Files larger than this are copied verbatim instead of having placeholders replaced
+const MAX_REWRITE_SIZE: u64 = 1024 * 1024;
+
+/// A text replacement applied to a template
+#[derive(Debug, Clone)]
+pub struct Replacement {
+    pub from: String,
+    pub to: String,
+    /// Only replace occurrences not surrounded by letters or digits
+    pub whole_word: bool,
+}
+
+/// Text replacements applied to a template's file contents and paths
+#[derive(Debug, Clone, Default)]
+pub struct TemplateReplacements {
+    /// Applied in a single pass, so replaced text is never replaced again
+    pub replacements: Vec<Replacement>,
+}
+
+impl TemplateReplacements {
+    /// Replacements turning a template into a new project
+    ///
+    /// Covers the `{{identifier}}`, `{{name}}` and `{{description}}` placeholders
+    /// as well as whole-word occurrences of the template's own identifier and name.
+    pub fn for_project(
+        template_identifier: &str,
+        template_name: &str,
+        identifier: &str,
+        name: &str,
+        description: &str,
+    ) -> Self {
+        let placeholder = |from: &str, to: &str| Replacement {
+            from: from.to_string(),
+            to: to.to_string(),
+            whole_word: false,
+        };
+        let mut replacements = vec![
+            placeholder("{{identifier}}", identifier),
+            placeholder("{{name}}", name),
+            placeholder("{{description}}", description),
+        ];
+        replacements.extend(
+            [(template_name, name), (template_identifier, identifier)]
+                .into_iter()
+                .filter(|(from, to)| !from.is_empty() && from != to)
+                .map(|(from, to)| Replacement {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    whole_word: true,
+                }),
+        );
+
+        Self { replacements }
+    }
+
+    /// Replace matches left to right, preferring the longest match at each position
+    fn apply(&self, text: &str) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            let offset = text.len() - rest.len();
+            let matched = self
+                .replacements
+                .iter()
+                .filter(|r| !r.from.is_empty() && rest.starts_with(r.from.as_str()))
+                .filter(|r| !r.whole_word || on_word_boundaries(text, offset, r.from.len()))
+                .max_by_key(|r| r.from.len());
+            match matched {
+                Some(replacement) => {
+                    result.push_str(&replacement.to);
+                    rest = &rest[replacement.from.len()..];
+                }
+                None => {
+                    result.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Whether `text[start..start + len]` isn't part of a longer word
+///
+/// Only edges that are letters or digits themselves need a boundary.
+fn on_word_boundaries(text: &str, start: usize, len: usize) -> bool {
+    let (before, matched, after) = (
+        &text[..start],
+        &text[start..start + len],
+        &text[start + len..],
+    );
+    let joins = |a: Option<char>, b: Option<char>| matches!((a, b), (Some(a), Some(b)) if a.is_alphanumeric() && b.is_alphanumeric());
+    !joins(before.chars().next_back(), matched.chars().next())
+        && !joins(matched.chars().next_back(), after.chars().next())
+}
+
+/// Shallow clone a template repository into `directory`, without its history
+pub fn clone_template(clone_url: &str, directory: &Path) -> Result<()> {
+    ensure!(
+        !directory.exists(),
+        "{path} already exists",
+        path = directory.display()
+    );
+
+    let status = Command::new("git")
+        .arg("clone")
+        .args(["--depth", "1", "--quiet"])
+        .arg(clone_url)
+        .arg(directory)
+        .status()
+        .context("Failed to run git clone")?;
+    if !status.success() {
+        bail!("git clone {clone_url} failed");
+    }
+
+    fs::remove_dir_all(directory.join(".git")).context("Failed to remove template history")?;
+    Ok(())
+}
+
+/// Replace placeholders in file contents and names below `root`
+///
+/// Binary and large files are left untouched. Returns the number of files
+/// rewritten plus files and directories renamed.
+pub fn apply_template(root: &Path, replacements: &TemplateReplacements) -> Result<usize> {
+    let mut changed = 0;
+
+    for path in files_below(root)? {
+        let metadata = fs::metadata(&path)?;
+        if metadata.len() <= MAX_REWRITE_SIZE
+            && let Ok(contents) = fs::read_to_string(&path)
+        {
+            let rewritten = replacements.apply(&contents);
+            if rewritten != contents {
+                fs::write(&path, rewritten)
+                    .with_context(|| format!("Failed to write {path}", path = path.display()))?;
+                changed += 1;
+                debug!(path = %path.display(), "Rewrote placeholders");
+            }
+        }
+    }
+
+    // Rename deepest paths first so parent renames don't invalidate child paths
+    let mut paths = files_below(root)?;
+    paths.extend(dirs_below(root)?);
+    paths.sort_by_key(|path| std::cmp::Reverse(path.components().count()));
+    for path in paths {
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let renamed = replacements.apply(file_name);
+        if renamed != file_name {
+            let target = path.with_file_name(&renamed);
+            fs::rename(&path, &target).with_context(|| {
+                format!(
+                    "Failed to rename {path} to {renamed}",
+                    path = path.display()
+                )
+            })?;
+            changed += 1;
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Initialize a repository in `root` with all files in a single root commit
+///
+/// Returns the ID of the new commit.
+pub fn init_fresh_history(root: &Path, message: &str) -> Result<String> {
+    let repo = Repository::init(root)
+        .with_context(|| format!("Failed to initialize repository at {root:?}"))?;
+
+    let mut index = repo.index()?;
+    index.add_all(["*"].iter(), IndexAddOption::DEFAULT, None)?;
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+
+    let signature = repo
+        .signature()
+        .or_else(|_| Signature::now("gitsmith", "gitsmith@localhost"))?;
+    let commit = repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &[])?;
+
+    Ok(commit.to_string())
+}
+
+fn files_below(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    walk(root, &mut |path, is_dir| {
+        if !is_dir {
+            files.push(path.to_path_buf());
+        }
+    })?;
+    Ok(files)
+}
+
+fn dirs_below(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    walk(root, &mut |path, is_dir| {
+        if is_dir {
+            dirs.push(path.to_path_buf());
+        }
+    })?;
+    Ok(dirs)
+}
+
+fn walk(dir: &Path, visit: &mut dyn FnMut(&Path, bool)) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {dir:?}"))? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if path.file_name().is_some_and(|name| name == ".git") {
+            continue;
+        }
+
+        if file_type.is_dir() {
+            walk(&path, visit)?;
+            visit(&path, true);
+        } else if file_type.is_file() {
+            visit(&path, false);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("crates/starter-core")).unwrap();
+        fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"starter\"\ndescription = \"{{description}}\"\n",
+        )
+        .unwrap();
+        fs::write(root.join("crates/starter-core/lib.rs"), "// Starter Kit\n").unwrap();
+        fs::write(root.join("logo.bin"), [0xff, 0xfe, 0x00]).unwrap();
+
+        let replacements = TemplateReplacements::for_project(
+            "starter",
+            "Starter Kit",
+            "weather",
+            "Weather App",
+            "Forecasts",
+        );
+        let changed = apply_template(root, &replacements).unwrap();
+        assert_eq!(changed, 3);
+
+        assert_eq!(
+            fs::read_to_string(root.join("Cargo.toml")).unwrap(),
+            "[package]\nname = \"weather\"\ndescription = \"Forecasts\"\n"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("crates/weather-core/lib.rs")).unwrap(),
+            "// Weather App\n"
+        );
+        assert_eq!(fs::read(root.join("logo.bin")).unwrap(), [0xff, 0xfe, 0x00]);
+
+        let commit = init_fresh_history(root, "Initial commit").unwrap();
+        let repo = Repository::open(root).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.id().to_string(), commit);
+        assert_eq!(head.parent_count(), 0);
+    }
+
+    #[test]
+    fn test_replacements_are_not_replaced_again() {
+        let replacements = TemplateReplacements::for_project(
+            "starter",
+            "Starter Kit",
+            "weather",
+            "Weather App",
+            "A {{name}} fork of starter",
+        );
+        assert_eq!(
+            replacements.apply("{{description}} ({{identifier}})"),
+            "A {{name}} fork of starter (weather)"
+        );
+    }
+
+    #[test]
+    fn test_literals_match_whole_words() {
+        let replacements =
+            TemplateReplacements::for_project("app", "App", "tool", "Tool", "A tool");
+        assert_eq!(
+            replacements.apply("app-core/application.rs: use app::mapper; // App, Apps"),
+            "tool-core/application.rs: use tool::mapper; // Tool, Apps"
+        );
+    }
+}
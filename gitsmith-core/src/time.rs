@@ -0,0 +1,288 @@
+//! Human readable timestamps: relative ("3 days ago") or absolute in a chosen timezone
+
+use chrono::{DateTime, FixedOffset, Local, TimeZone, Utc};
+use std::str::FromStr;
+use strum::{Display, EnumString};
+
+use crate::error::{GitsmithError, Result};
+
+/// Whether timestamps print relative to now or as dates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum TimeStyle {
+    /// "3 days ago"
+    Relative,
+    /// "2024-06-01 12:00 UTC"
+    #[default]
+    Absolute,
+}
+
+/// Timezone of absolute timestamps
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Timezone {
+    #[default]
+    Utc,
+    /// The system timezone
+    Local,
+    /// An IANA name like `Europe/Berlin`
+    Named(chrono_tz::Tz),
+    /// A fixed offset like `+02:00`
+    Fixed(FixedOffset),
+}
+
+impl FromStr for Timezone {
+    type Err = GitsmithError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim() {
+            "" | "utc" | "UTC" | "Z" => Ok(Self::Utc),
+            "local" => Ok(Self::Local),
+            value if value.starts_with(['+', '-']) => FixedOffset::from_str(value)
+                .map(Self::Fixed)
+                .map_err(|_| GitsmithError::msg(format!("Invalid UTC offset '{value}'"))),
+            value => value.parse().map(Self::Named).map_err(|_| {
+                GitsmithError::msg(format!(
+                    "Unknown timezone '{value}', use utc, local, an offset like +02:00 or a name like Europe/Berlin"
+                ))
+            }),
+        }
+    }
+}
+
+/// How timestamps are printed for humans, JSON output always carries ISO 8601
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeFormat {
+    pub style: TimeStyle,
+    pub timezone: Timezone,
+    /// strftime pattern of absolute timestamps, `%Z` prints the timezone
+    pub date_format: String,
+}
+
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d %H:%M %Z";
+
+impl Default for TimeFormat {
+    fn default() -> Self {
+        Self {
+            style: TimeStyle::default(),
+            timezone: Timezone::default(),
+            date_format: DEFAULT_DATE_FORMAT.to_string(),
+        }
+    }
+}
+
+/// Check a strftime pattern, chrono panics when printing an invalid one
+pub fn parse_date_format(pattern: &str) -> Result<String> {
+    let invalid = chrono::format::StrftimeItems::new(pattern)
+        .any(|item| matches!(item, chrono::format::Item::Error));
+    if invalid {
+        return Err(GitsmithError::msg(format!(
+            "Invalid date format '{pattern}'"
+        )));
+    }
+    Ok(pattern.to_string())
+}
+
+/// Format a unix timestamp, relative times are measured from the current time
+pub fn format_timestamp(format: &TimeFormat, timestamp: u64) -> String {
+    format_timestamp_with(format, timestamp, Utc::now().timestamp().max(0) as u64)
+}
+
+/// Format a unix timestamp, relative times are measured from `now`
+pub fn format_timestamp_with(format: &TimeFormat, timestamp: u64, now: u64) -> String {
+    match format.style {
+        TimeStyle::Relative => relative(timestamp, now),
+        TimeStyle::Absolute => absolute(format, timestamp),
+    }
+}
+
+fn absolute(format: &TimeFormat, timestamp: u64) -> String {
+    let Some(time) = DateTime::from_timestamp(timestamp as i64, 0) else {
+        return String::new();
+    };
+    let pattern = match parse_date_format(&format.date_format) {
+        Ok(_) => format.date_format.as_str(),
+        Err(_) => DEFAULT_DATE_FORMAT,
+    };
+    match format.timezone {
+        Timezone::Utc => time.format(pattern).to_string(),
+        Timezone::Local => time.with_timezone(&Local).format(pattern).to_string(),
+        Timezone::Named(tz) => time.with_timezone(&tz).format(pattern).to_string(),
+        Timezone::Fixed(offset) => time.with_timezone(&offset).format(pattern).to_string(),
+    }
+}
+
+/// "just now", "5 minutes ago", "in 2 hours", ...
+fn relative(timestamp: u64, now: u64) -> String {
+    const UNITS: [(u64, &str); 6] = [
+        (365 * 24 * 60 * 60, "year"),
+        (30 * 24 * 60 * 60, "month"),
+        (7 * 24 * 60 * 60, "week"),
+        (24 * 60 * 60, "day"),
+        (60 * 60, "hour"),
+        (60, "minute"),
+    ];
+
+    let seconds = now.abs_diff(timestamp);
+    let Some((count, unit)) = UNITS
+        .iter()
+        .find(|(size, _)| seconds >= *size)
+        .map(|(size, unit)| (seconds / size, unit))
+    else {
+        return "just now".to_string();
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    if timestamp > now {
+        format!("in {count} {unit}{plural}")
+    } else {
+        format!("{count} {unit}{plural} ago")
+    }
+}
+
+/// Start of a time range: a date (2024-06-01, start of that day in UTC), an
+/// RFC 3339 time, a unix timestamp, or an age like `3d` (s, m, h, d or w) before `now`
+pub fn parse_since(value: &str, now: u64) -> Result<u64> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Ok(seconds);
+    }
+    if let Some(unit) = value.chars().last().filter(char::is_ascii_alphabetic)
+        && let Ok(count) = value[..value.len() - 1].parse::<u64>()
+    {
+        let seconds = match unit {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => {
+                return Err(GitsmithError::msg(format!(
+                    "Unknown unit '{unit}' in '{value}', use s, m, h, d or w"
+                )));
+            }
+        };
+        return Ok(now.saturating_sub(count.saturating_mul(seconds)));
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.timestamp().max(0) as u64);
+    }
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|start| start.and_utc().timestamp().max(0) as u64)
+        .ok_or_else(|| {
+            GitsmithError::msg(format!(
+                "Invalid date '{value}', use YYYY-MM-DD, RFC 3339 or an age like 2w"
+            ))
+        })
+}
+
+/// ISO 8601 form of a unix timestamp, in UTC
+pub fn iso8601(timestamp: u64) -> String {
+    Utc.timestamp_opt(timestamp as i64, 0)
+        .single()
+        .map(|time| time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+        .unwrap_or_default()
+}
+
+/// Add an ISO 8601 `<key>_iso` next to every unix `created_at`, `updated_at`
+/// and `last_used` in JSON output
+pub fn add_iso_timestamps(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let iso: Vec<(String, String)> = map
+                .iter()
+                .filter(|(key, _)| {
+                    matches!(key.as_str(), "created_at" | "updated_at" | "last_used")
+                })
+                .filter_map(|(key, value)| Some((format!("{key}_iso"), iso8601(value.as_u64()?))))
+                .collect();
+            for value in map.values_mut() {
+                add_iso_timestamps(value);
+            }
+            for (key, value) in iso {
+                map.insert(key, serde_json::Value::String(value));
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(add_iso_timestamps),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp_with() {
+        let now = 1_717_243_200; // 2024-06-01 12:00 UTC
+        let relative = TimeFormat {
+            style: TimeStyle::Relative,
+            ..TimeFormat::default()
+        };
+        assert_eq!(format_timestamp_with(&relative, now - 30, now), "just now");
+        assert_eq!(
+            format_timestamp_with(&relative, now - 60, now),
+            "1 minute ago"
+        );
+        assert_eq!(
+            format_timestamp_with(&relative, now - 3 * 24 * 60 * 60, now),
+            "3 days ago"
+        );
+        assert_eq!(
+            format_timestamp_with(&relative, now + 2 * 60 * 60, now),
+            "in 2 hours"
+        );
+
+        let absolute = TimeFormat::default();
+        assert_eq!(
+            format_timestamp_with(&absolute, now, now),
+            "2024-06-01 12:00 UTC"
+        );
+        let berlin = TimeFormat {
+            timezone: "Europe/Berlin".parse().unwrap(),
+            ..TimeFormat::default()
+        };
+        assert_eq!(
+            format_timestamp_with(&berlin, now, now),
+            "2024-06-01 14:00 CEST"
+        );
+        let offset = TimeFormat {
+            timezone: "-03:00".parse().unwrap(),
+            date_format: "%d/%m/%Y %H:%M".to_string(),
+            ..TimeFormat::default()
+        };
+        assert_eq!(format_timestamp_with(&offset, now, now), "01/06/2024 09:00");
+        assert!("Mars/Olympus".parse::<Timezone>().is_err());
+        assert!(parse_date_format("%Y-%m-%d").is_ok());
+        assert!(parse_date_format("%Q").is_err());
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let now = 1_717_243_200; // 2024-06-01 12:00 UTC
+        assert_eq!(parse_since("2024-06-01", now).unwrap(), now - 12 * 60 * 60);
+        assert_eq!(
+            parse_since("2024-06-01T12:00:00+02:00", now).unwrap(),
+            now - 2 * 60 * 60
+        );
+        assert_eq!(parse_since("1700000000", now).unwrap(), 1_700_000_000);
+        assert_eq!(parse_since("3d", now).unwrap(), now - 3 * 24 * 60 * 60);
+        assert_eq!(parse_since("2w", now).unwrap(), now - 14 * 24 * 60 * 60);
+        assert!(parse_since("3y", now).is_err());
+        assert!(parse_since("June 1st", now).is_err());
+    }
+
+    #[test]
+    fn test_add_iso_timestamps() {
+        let mut json = serde_json::json!([
+            {"id": "a", "created_at": 1_717_243_200u64, "updated_at": null},
+            {"nested": {"created_at": 0}},
+        ]);
+        add_iso_timestamps(&mut json);
+
+        assert_eq!(json[0]["created_at"], 1_717_243_200u64);
+        assert_eq!(json[0]["created_at_iso"], "2024-06-01T12:00:00Z");
+        assert!(json[0].get("updated_at_iso").is_none());
+        assert_eq!(json[1]["nested"]["created_at_iso"], "1970-01-01T00:00:00Z");
+    }
+}
@@ -1,6 +1,6 @@
 use nostr::EventId;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// Repository configuration for Nostr announcement
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,7 +13,26 @@ pub struct RepoAnnouncement {
     pub web: Vec<String>,
     pub root_commit: String,
     pub maintainers: Vec<String>, // npubs
+    /// Keys (npubs) of CI systems whose check reports on PRs are trusted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ci_keys: Vec<String>,
+    /// GRASP servers relaying the repository's events and hosting its git data
     pub grasp_servers: Vec<String>,
+    /// Blossom servers for build logs and other large files
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blossom_servers: Vec<String>,
+    /// Minimum NIP-13 proof-of-work difficulty required for PRs and comments (0 = none)
+    #[serde(default)]
+    pub min_pow: u8,
+    /// SPDX license expression
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    /// Topics, published as `t` tags
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub topics: Vec<String>,
+    /// Coordinate (`30023:<owner>:<d>`) of the long-form event holding the README
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub readme: Option<String>,
 }
 
 /// Result of publishing to Nostr
@@ -25,6 +44,16 @@ pub struct PublishResult {
     pub failures: Vec<(String, String)>, // (relay, error)
 }
 
+/// Result of broadcasting one or more events to relays
+#[derive(Debug, Serialize)]
+pub struct BroadcastResult {
+    pub event_ids: Vec<EventId>,
+    pub successes: Vec<String>,
+    pub failures: Vec<(String, String)>, // (relay, error)
+    /// IDs of the events each relay accepted
+    pub accepted: BTreeMap<String, Vec<EventId>>,
+}
+
 /// Git state for Kind 30618 events
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GitState {
@@ -36,5 +65,6 @@ pub struct GitState {
 #[derive(Debug, Clone)]
 pub struct PublishConfig {
     pub timeout_secs: u64,
+    /// Wait (up to `timeout_secs`) for a relay connection before sending
     pub wait_for_send: bool,
 }
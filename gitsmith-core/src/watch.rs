@@ -0,0 +1,418 @@
+//! Notifications about repository activity as it reaches the relays
+//!
+//! [`Notifier`]s pass each notification on: to a webhook, a shell command or
+//! a named pipe. Repositories configure theirs in git config
+//! (`nostr.notify.webhook`, `nostr.notify.exec`, `nostr.notify.pipe`).
+
+use nostr::nips::nip19::ToBech32;
+use nostr::{Alphabet, Event, Kind, TagKind};
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+use crate::apply;
+use crate::error::{Context, GitsmithError, Result, ensure};
+use crate::events::{
+    KIND_GIT_STATUS_APPLIED, KIND_GIT_STATUS_CLOSED, KIND_GIT_STATUS_DRAFT, KIND_GIT_STATUS_OPEN,
+};
+use crate::inspect;
+use crate::issues::KIND_ISSUE;
+use crate::patches::{KIND_PATCH, KIND_PULL_REQUEST, KIND_PULL_REQUEST_UPDATE};
+
+/// Kinds a maintainer is notified about
+pub const WATCHED_KINDS: [Kind; 9] = [
+    KIND_PULL_REQUEST,
+    KIND_PULL_REQUEST_UPDATE,
+    KIND_PATCH,
+    KIND_ISSUE,
+    Kind::Comment,
+    Kind::Custom(KIND_GIT_STATUS_OPEN),
+    Kind::Custom(KIND_GIT_STATUS_APPLIED),
+    Kind::Custom(KIND_GIT_STATUS_CLOSED),
+    Kind::Custom(KIND_GIT_STATUS_DRAFT),
+];
+
+/// Longest summary kept, longer ones are cut at a character boundary
+const MAX_SUMMARY_CHARS: usize = 100;
+
+/// Webhooks that take longer are given up on, so one slow endpoint doesn't
+/// hold back the others
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// One piece of repository activity
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub id: String,
+    pub kind: u16,
+    pub kind_name: String,
+    pub author: String,
+    pub created_at: u64,
+    /// Title of a pull request, patch or issue, first line of a comment or status
+    pub summary: String,
+    /// Event a comment, status or PR update is about
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// Coordinate of the repository the event belongs to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repo: Option<String>,
+}
+
+impl Notification {
+    pub fn from_event(event: &Event) -> Self {
+        let kind_name = inspect::kind_name(event.kind)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("kind {kind}", kind = event.kind.as_u16()));
+        Self {
+            id: event.id.to_hex(),
+            kind: event.kind.as_u16(),
+            kind_name,
+            author: event
+                .pubkey
+                .to_bech32()
+                .unwrap_or_else(|_| event.pubkey.to_hex()),
+            created_at: event.created_at.as_u64(),
+            summary: summary(event),
+            target: target(event),
+            repo: tag_value(event, TagKind::single_letter(Alphabet::A, false)),
+        }
+    }
+
+    /// One line for chat rooms, like `[gitsmith] issue by npub1abcd…wxyz: Crash on start`
+    pub fn text(&self) -> String {
+        let identifier = self
+            .repo
+            .as_deref()
+            .and_then(|coordinate| coordinate.splitn(3, ':').nth(2));
+        let mut text = match identifier {
+            Some(identifier) => format!("[{identifier}] "),
+            None => String::new(),
+        };
+        text.push_str(&format!(
+            "{kind_name} by {author}",
+            kind_name = self.kind_name,
+            author = short_npub(&self.author)
+        ));
+        if !self.summary.is_empty() {
+            text.push_str(&format!(": {summary}", summary = self.summary));
+        }
+        text
+    }
+}
+
+/// `npub1abcd…wxyz`, enough to tell authors apart
+pub fn short_npub(npub: &str) -> String {
+    match (npub.get(..10), npub.get(npub.len().saturating_sub(4)..)) {
+        (Some(head), Some(tail)) if npub.len() > 16 => format!("{head}…{tail}"),
+        _ => npub.to_string(),
+    }
+}
+
+/// What notifiers receive: the notification, a ready-made `text` line that
+/// Slack and Matrix hooks display as is, and the signed event
+#[derive(Serialize)]
+pub struct NotifierPayload<'a> {
+    pub text: String,
+    #[serde(flatten)]
+    pub notification: &'a Notification,
+    pub event: &'a Event,
+}
+
+impl<'a> NotifierPayload<'a> {
+    pub fn new(notification: &'a Notification, event: &'a Event) -> Self {
+        Self {
+            text: notification.text(),
+            notification,
+            event,
+        }
+    }
+}
+
+/// Where notifications are passed on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notifier {
+    /// POST the payload as JSON
+    Webhook(String),
+    /// Run with `sh -c`, the payload on stdin and the main fields in
+    /// `GITSMITH_EVENT_*` variables
+    Command(String),
+    /// Write the payload as one JSON line to a named pipe (FIFO)
+    Pipe(PathBuf),
+}
+
+impl fmt::Display for Notifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Webhook(url) => write!(f, "webhook {url}"),
+            Self::Command(command) => write!(f, "command {command}"),
+            Self::Pipe(path) => write!(f, "pipe {path}", path = path.display()),
+        }
+    }
+}
+
+impl Notifier {
+    /// Notifiers configured for the repository with `nostr.notify.*`
+    pub fn from_git_config(repo_path: &Path) -> Result<Vec<Self>> {
+        let config = crate::repo::open_repository(repo_path)?.config()?;
+        let webhooks = crate::repo::config_values(&config, "nostr.notify.webhook");
+        let commands = crate::repo::config_values(&config, "nostr.notify.exec");
+        let pipes = crate::repo::config_values(&config, "nostr.notify.pipe");
+        webhooks
+            .into_iter()
+            .map(|url| {
+                reqwest::Url::parse(&url).map_err(|e| {
+                    GitsmithError::msg(format!(
+                        "Invalid webhook URL {url} in git config nostr.notify.webhook: {e}"
+                    ))
+                })?;
+                Ok(Self::Webhook(url))
+            })
+            .chain(
+                commands
+                    .into_iter()
+                    .map(|command| Ok(Self::Command(command))),
+            )
+            .chain(pipes.into_iter().map(|path| Ok(Self::Pipe(path.into()))))
+            .collect()
+    }
+
+    /// Pass a notification on
+    pub async fn notify(&self, payload: &NotifierPayload<'_>) -> Result<()> {
+        match self {
+            Self::Webhook(url) => {
+                let client = reqwest::Client::builder()
+                    .user_agent(concat!("gitsmith/", env!("CARGO_PKG_VERSION")))
+                    .timeout(WEBHOOK_TIMEOUT)
+                    .build()?;
+                let response = client
+                    .post(url)
+                    .json(payload)
+                    .send()
+                    .await
+                    .with_context(|| format!("Failed to reach {url}"))?;
+                let status = response.status();
+                ensure!(status.is_success(), "Webhook {url} returned {status}");
+            }
+            Self::Command(command) => {
+                let notification = payload.notification;
+                let mut child = tokio::process::Command::new("sh")
+                    .args(["-c", command])
+                    .env("GITSMITH_EVENT_ID", &notification.id)
+                    .env("GITSMITH_EVENT_KIND", notification.kind.to_string())
+                    .env("GITSMITH_EVENT_AUTHOR", &notification.author)
+                    .env("GITSMITH_EVENT_SUMMARY", &notification.summary)
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .with_context(|| format!("Failed to run {command}"))?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    // The command may not read its input
+                    let _ = stdin.write_all(&json_line(payload)?).await;
+                }
+                // Waiting keeps commands from piling up on bursts of events
+                let status = child.wait().await?;
+                ensure!(status.success(), "{command} exited with {status}");
+            }
+            Self::Pipe(path) => write_pipe(path, &json_line(payload)?).await?,
+        }
+        Ok(())
+    }
+}
+
+fn json_line(payload: &NotifierPayload<'_>) -> Result<Vec<u8>> {
+    let mut line = serde_json::to_vec(payload)?;
+    line.push(b'\n');
+    Ok(line)
+}
+
+/// Write to a FIFO without blocking when nobody reads it
+#[cfg(unix)]
+async fn write_pipe(path: &Path, line: &[u8]) -> Result<()> {
+    let mut sender = tokio::net::unix::pipe::OpenOptions::new()
+        .open_sender(path)
+        .with_context(|| {
+            format!(
+                "Failed to open the named pipe {path}, is something reading it?",
+                path = path.display()
+            )
+        })?;
+    sender.write_all(line).await?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn write_pipe(path: &Path, _line: &[u8]) -> Result<()> {
+    Err(GitsmithError::msg(format!(
+        "Named pipes are only supported on Unix, can't write to {path}",
+        path = path.display()
+    )))
+}
+
+fn summary(event: &Event) -> String {
+    let subject = if event.kind == KIND_PATCH {
+        apply::parse_patch(&event.content)
+            .ok()
+            .map(|patch| patch.subject)
+    } else if [KIND_PULL_REQUEST, KIND_PULL_REQUEST_UPDATE, KIND_ISSUE].contains(&event.kind) {
+        tag_value(event, TagKind::Subject)
+    } else {
+        None
+    };
+    let text = subject.unwrap_or_else(|| {
+        event
+            .content
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .unwrap_or_default()
+            .to_string()
+    });
+    match text.char_indices().nth(MAX_SUMMARY_CHARS) {
+        Some((end, _)) => format!("{text}…", text = &text[..end]),
+        None => text,
+    }
+}
+
+/// The event a reply, status or update refers to: the NIP-22 root of a
+/// comment, the first `e` tag otherwise
+fn target(event: &Event) -> Option<String> {
+    if event.kind == Kind::Comment {
+        return tag_value(event, TagKind::single_letter(Alphabet::E, true));
+    }
+    if [KIND_PATCH, KIND_PULL_REQUEST, KIND_ISSUE].contains(&event.kind) {
+        return None;
+    }
+    tag_value(event, TagKind::single_letter(Alphabet::E, false))
+}
+
+fn tag_value(event: &Event, kind: TagKind) -> Option<String> {
+    event
+        .tags
+        .find(kind)
+        .and_then(|tag| tag.content())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr::{EventBuilder, Keys, Tag};
+
+    const REPO: &str = "30617:abc:repo";
+
+    fn event(kind: Kind, content: &str, tags: Vec<Tag>) -> Event {
+        EventBuilder::new(kind, content)
+            .tags(tags)
+            .sign_with_keys(&Keys::generate())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_notification_from_event() {
+        let pr = event(
+            KIND_PULL_REQUEST,
+            "Longer description",
+            vec![
+                Tag::parse(["a", REPO]).unwrap(),
+                Tag::parse(["subject", "Add frobnicator"]).unwrap(),
+            ],
+        );
+        let notification = Notification::from_event(&pr);
+        assert_eq!(notification.kind_name, "pull request");
+        assert_eq!(notification.summary, "Add frobnicator");
+        assert_eq!(notification.repo.as_deref(), Some(REPO));
+        assert_eq!(notification.target, None);
+        assert!(notification.author.starts_with("npub1"));
+        assert!(
+            notification
+                .text()
+                .starts_with("[repo] pull request by npub1")
+        );
+        assert!(notification.text().ends_with(": Add frobnicator"));
+
+        let status = event(
+            Kind::Custom(KIND_GIT_STATUS_APPLIED),
+            "\nMerged, thanks!\nMore",
+            vec![
+                Tag::parse(["e", &pr.id.to_hex(), "", "root"]).unwrap(),
+                Tag::parse(["a", REPO]).unwrap(),
+            ],
+        );
+        let notification = Notification::from_event(&status);
+        assert_eq!(notification.summary, "Merged, thanks!");
+        assert_eq!(notification.target, Some(pr.id.to_hex()));
+
+        let comment = event(
+            Kind::Comment,
+            &"x".repeat(150),
+            vec![Tag::parse(["E", &pr.id.to_hex()]).unwrap()],
+        );
+        let notification = Notification::from_event(&comment);
+        assert_eq!(notification.summary.chars().count(), MAX_SUMMARY_CHARS + 1);
+        assert_eq!(notification.target, Some(pr.id.to_hex()));
+    }
+
+    #[test]
+    fn test_notifiers_from_git_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        assert!(Notifier::from_git_config(dir.path()).unwrap().is_empty());
+
+        let mut config = repo.config().unwrap();
+        config
+            .set_multivar("nostr.notify.exec", "^$", "notify-send gitsmith")
+            .unwrap();
+        config
+            .set_multivar("nostr.notify.webhook", "^$", "https://hooks.example.com/a")
+            .unwrap();
+        config
+            .set_multivar("nostr.notify.pipe", "^$", "/tmp/gitsmith.fifo")
+            .unwrap();
+        assert_eq!(
+            Notifier::from_git_config(dir.path()).unwrap(),
+            vec![
+                Notifier::Webhook("https://hooks.example.com/a".to_string()),
+                Notifier::Command("notify-send gitsmith".to_string()),
+                Notifier::Pipe("/tmp/gitsmith.fifo".into()),
+            ]
+        );
+
+        config
+            .set_multivar("nostr.notify.webhook", "^$", "not a url")
+            .unwrap();
+        assert!(Notifier::from_git_config(dir.path()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_command_notifier() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let pr = event(
+            KIND_PULL_REQUEST,
+            "",
+            vec![Tag::parse(["subject", "Fix"]).unwrap()],
+        );
+        let notification = Notification::from_event(&pr);
+        let payload = NotifierPayload::new(&notification, &pr);
+
+        let command = format!(
+            "echo \"$GITSMITH_EVENT_KIND\" > {out} && cat >> {out}",
+            out = out.display()
+        );
+        Notifier::Command(command).notify(&payload).await.unwrap();
+        let written = std::fs::read_to_string(&out).unwrap();
+        let (kind, json) = written.split_once('\n').unwrap();
+        assert_eq!(kind, "1618");
+        let json: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(json["summary"], "Fix");
+        assert_eq!(json["event"]["id"], pr.id.to_hex());
+
+        assert!(
+            Notifier::Command("exit 3".into())
+                .notify(&payload)
+                .await
+                .is_err()
+        );
+    }
+}
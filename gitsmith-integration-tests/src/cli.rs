@@ -1,4 +1,5 @@
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(name = "gitsmith-integration-tests")]
@@ -84,4 +85,35 @@ pub enum Commands {
         #[arg(long, default_value = "5")]
         max_wait_minutes: u64,
     },
+
+    /// Publish PRs and comments to public relays over hours and report per-relay latencies
+    Soak {
+        /// Keep temporary directories after the run
+        #[arg(long)]
+        keep_temp: bool,
+
+        /// Relay URLs to test (can be specified multiple times)
+        #[arg(long = "relay", required = true)]
+        relays: Vec<String>,
+
+        /// Number of PRs to send, spread evenly over the run
+        #[arg(long, default_value = "12")]
+        prs: usize,
+
+        /// Comments to post on each PR
+        #[arg(long, default_value = "2")]
+        comments_per_pr: usize,
+
+        /// Length of the run (in minutes)
+        #[arg(long, default_value = "120")]
+        duration_minutes: u64,
+
+        /// Time to wait for each event on each relay before counting it as lost (in seconds)
+        #[arg(long, default_value = "300")]
+        max_wait_secs: u64,
+
+        /// Report file, CSV if it ends in .csv, JSON otherwise
+        #[arg(long, default_value = "soak-report.json")]
+        report: PathBuf,
+    },
 }
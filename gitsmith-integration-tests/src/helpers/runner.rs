@@ -304,6 +304,7 @@ impl CommandOutput {
 
     /// Parse stdout as a list of pull requests
     pub fn parse_pr_list(&self) -> Result<Vec<crate::helpers::PullRequest>> {
-        self.stdout_json()
+        let page: crate::helpers::PullRequestPage = self.stdout_json()?;
+        Ok(page.pull_requests)
     }
 }
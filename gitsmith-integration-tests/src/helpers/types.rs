@@ -14,6 +14,13 @@ pub struct PullRequest {
     pub status: String, // Using String for simplicity in tests
 }
 
+/// Page of pull requests printed by gitsmith list --json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequestPage {
+    pub pull_requests: Vec<PullRequest>,
+    pub next_cursor: Option<String>,
+}
+
 impl PullRequest {
     /// Find a PR by title in a list
     #[allow(dead_code)]
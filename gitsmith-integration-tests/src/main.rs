@@ -121,6 +121,27 @@ async fn main() -> Result<()> {
             }
             run_public_relay_tests(keep_temp, &relays, max_wait_minutes).await
         }
+        cli::Commands::Soak {
+            keep_temp,
+            relays,
+            prs,
+            comments_per_pr,
+            duration_minutes,
+            max_wait_secs,
+            report,
+        } => {
+            // Soak runs only make sense against public relays
+            let config = public_relay::SoakConfig {
+                prs,
+                comments_per_pr,
+                duration: std::time::Duration::from_secs(duration_minutes * 60),
+                max_wait: std::time::Duration::from_secs(max_wait_secs),
+                report_path: report,
+            };
+            println!("{}", "🌐 Running Public Relay Soak Test".blue().bold());
+            println!("  Testing with relay(s): {}", relays.join(", "));
+            public_relay::run_soak(keep_temp, &relays, &config).await
+        }
     }
 }
 
@@ -1,9 +1,14 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use colored::*;
+use nostr_sdk::{Client, EventId, Filter, RelayUrl};
+use serde::Serialize;
 use serde_json::Value;
-use std::time::Duration;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinSet;
 use tokio::time::sleep;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::helpers::{GitsmithRunner, TestContext};
 
@@ -27,10 +32,16 @@ async fn wait_for_pr_with_backoff(
 
         // Try to list PRs
         let output = runner
-            .run_success(&["list", "--repo-path", repo_path, "--json"])
+            .run_success(&["list", "--repo-path", repo_path, "--json", "--all"])
             .await?;
 
-        if let Ok(prs) = serde_json::from_str::<Vec<Value>>(&output.stdout) {
+        if let Some(prs) =
+            serde_json::from_str::<Value>(&output.stdout)
+                .ok()
+                .and_then(|mut page: Value| {
+                    serde_json::from_value::<Vec<Value>>(page["pull_requests"].take()).ok()
+                })
+        {
             if prs.len() >= expected_count {
                 let elapsed = start.elapsed();
                 info!(
@@ -260,3 +271,409 @@ pub async fn run_tests(
 
     Ok((passed, failed))
 }
+
+/// Settings of a soak run against public relays
+pub struct SoakConfig {
+    /// Number of PRs to send over the run
+    pub prs: usize,
+    /// Comments posted on each PR
+    pub comments_per_pr: usize,
+    /// Total length of the run, PRs are spread evenly over it
+    pub duration: Duration,
+    /// How long to wait for an event to show up on a relay before counting it as lost
+    pub max_wait: Duration,
+    /// Where to write the report, CSV if the extension is `.csv`, JSON otherwise
+    pub report_path: PathBuf,
+}
+
+/// How long one event took to become visible on one relay
+#[derive(Debug, Clone, Serialize)]
+pub struct SoakSample {
+    pub relay: String,
+    pub kind: &'static str,
+    pub event_id: String,
+    /// Unix time the event was published
+    pub published_at: u64,
+    /// Time until the relay returned the event, `None` if it never did
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Aggregated results of a relay over the whole run
+#[derive(Debug, Clone, Serialize)]
+pub struct RelaySummary {
+    pub relay: String,
+    pub events: usize,
+    pub found: usize,
+    pub failure_rate: f64,
+    pub p50_ms: Option<u64>,
+    pub p90_ms: Option<u64>,
+    pub p99_ms: Option<u64>,
+    pub max_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct SoakReport<'a> {
+    started_at: u64,
+    finished_at: u64,
+    prs: usize,
+    comments_per_pr: usize,
+    max_wait_secs: u64,
+    summary: &'a [RelaySummary],
+    samples: &'a [SoakSample],
+}
+
+/// Poll a single relay until it returns the event, measuring how long it took
+async fn measure_visibility(
+    event_id: EventId,
+    relay_url: &str,
+    max_wait: Duration,
+) -> Result<Duration> {
+    let start = Instant::now();
+    let url = RelayUrl::parse(relay_url)?;
+    let client = Client::default();
+    client.add_relay(url.clone()).await?;
+    client.connect().await;
+
+    let filter = Filter::new().id(event_id);
+    let mut delay = Duration::from_millis(250);
+    let result = loop {
+        let remaining = max_wait.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            break Err(anyhow::anyhow!(
+                "Not visible after {secs}s",
+                secs = max_wait.as_secs()
+            ));
+        }
+
+        match client
+            .fetch_events_from(
+                vec![url.clone()],
+                filter.clone(),
+                remaining.min(Duration::from_secs(10)),
+            )
+            .await
+        {
+            Ok(events) if !events.is_empty() => break Ok(start.elapsed()),
+            Ok(_) => debug!("{event_id} not on {relay_url} yet"),
+            Err(e) => debug!("Query to {relay_url} failed: {e}"),
+        }
+
+        sleep(delay.min(max_wait.saturating_sub(start.elapsed()))).await;
+        delay = (delay * 2).min(Duration::from_secs(15));
+    };
+
+    client.disconnect().await;
+    result
+}
+
+/// Measure an event on every relay concurrently
+async fn sample_event(
+    kind: &'static str,
+    event_id: &str,
+    relays: &[String],
+    max_wait: Duration,
+) -> Result<Vec<SoakSample>> {
+    let id = EventId::from_hex(event_id.trim())
+        .with_context(|| format!("Invalid event ID printed by gitsmith: {event_id}"))?;
+    let published_at = unix_now();
+
+    let mut tasks = JoinSet::new();
+    for relay in relays {
+        let relay = relay.clone();
+        tasks.spawn(async move {
+            let result = measure_visibility(id, &relay, max_wait).await;
+            (relay, result)
+        });
+    }
+
+    let mut samples = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (relay, result) = joined?;
+        let (latency_ms, error) = match result {
+            Ok(latency) => (Some(latency.as_millis() as u64), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        samples.push(SoakSample {
+            relay,
+            kind,
+            event_id: id.to_hex(),
+            published_at,
+            latency_ms,
+            error,
+        });
+    }
+    Ok(samples)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Latency at the given percentile (0-100) of sorted values
+fn percentile(sorted: &[u64], percentile: usize) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (percentile * sorted.len()).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied()
+}
+
+/// Aggregate samples per relay
+pub fn summarize(samples: &[SoakSample]) -> Vec<RelaySummary> {
+    let mut by_relay: BTreeMap<&str, Vec<&SoakSample>> = BTreeMap::new();
+    for sample in samples {
+        by_relay.entry(&sample.relay).or_default().push(sample);
+    }
+
+    by_relay
+        .into_iter()
+        .map(|(relay, samples)| {
+            let mut latencies: Vec<u64> = samples.iter().filter_map(|s| s.latency_ms).collect();
+            latencies.sort_unstable();
+            let lost = samples.len() - latencies.len();
+            RelaySummary {
+                relay: relay.to_string(),
+                events: samples.len(),
+                found: latencies.len(),
+                failure_rate: lost as f64 / samples.len() as f64,
+                p50_ms: percentile(&latencies, 50),
+                p90_ms: percentile(&latencies, 90),
+                p99_ms: percentile(&latencies, 99),
+                max_ms: latencies.last().copied(),
+            }
+        })
+        .collect()
+}
+
+fn samples_csv(samples: &[SoakSample]) -> String {
+    let mut csv = String::from("relay,kind,event_id,published_at,latency_ms,error\n");
+    for sample in samples {
+        let latency = sample
+            .latency_ms
+            .map(|ms| ms.to_string())
+            .unwrap_or_default();
+        let error = sample
+            .error
+            .as_deref()
+            .unwrap_or_default()
+            .replace('"', "\"\"");
+        csv.push_str(&format!(
+            "{relay},{kind},{event_id},{published_at},{latency},\"{error}\"\n",
+            relay = sample.relay,
+            kind = sample.kind,
+            event_id = sample.event_id,
+            published_at = sample.published_at,
+        ));
+    }
+    csv
+}
+
+fn write_report(
+    path: &Path,
+    config: &SoakConfig,
+    started_at: u64,
+    summary: &[RelaySummary],
+    samples: &[SoakSample],
+) -> Result<()> {
+    let data = if path.extension().is_some_and(|ext| ext == "csv") {
+        samples_csv(samples)
+    } else {
+        serde_json::to_string_pretty(&SoakReport {
+            started_at,
+            finished_at: unix_now(),
+            prs: config.prs,
+            comments_per_pr: config.comments_per_pr,
+            max_wait_secs: config.max_wait.as_secs(),
+            summary,
+            samples,
+        })?
+    };
+    std::fs::write(path, data).with_context(|| format!("Failed to write report to {path:?}"))
+}
+
+fn print_summary(summary: &[RelaySummary]) {
+    let ms = |value: Option<u64>| value.map_or("-".to_string(), |ms| ms.to_string());
+    println!(
+        "  {relay:<40} {events:>6} {lost:>7} {p50:>8} {p90:>8} {p99:>8} {max:>8}",
+        relay = "relay",
+        events = "events",
+        lost = "lost %",
+        p50 = "p50 ms",
+        p90 = "p90 ms",
+        p99 = "p99 ms",
+        max = "max ms"
+    );
+    for relay in summary {
+        println!(
+            "  {url:<40} {events:>6} {lost:>7.1} {p50:>8} {p90:>8} {p99:>8} {max:>8}",
+            url = relay.relay,
+            events = relay.events,
+            lost = relay.failure_rate * 100.0,
+            p50 = ms(relay.p50_ms),
+            p90 = ms(relay.p90_ms),
+            p99 = ms(relay.p99_ms),
+            max = ms(relay.max_ms)
+        );
+    }
+}
+
+/// Publish PRs and comments over a long period and record how each relay serves them
+///
+/// The report is rewritten after every PR, so an interrupted run still leaves data.
+pub async fn run_soak(keep_temp: bool, relays: &[String], config: &SoakConfig) -> Result<()> {
+    anyhow::ensure!(config.prs > 0, "A soak run needs at least one PR");
+
+    let ctx = TestContext::new("public-relay-soak", keep_temp)?;
+    let runner = GitsmithRunner::new(&ctx.home_dir);
+    ctx.setup_git_repo(3)?;
+    let repo_path = ctx.repo_path.to_str().context("Non UTF-8 test path")?;
+
+    let started_at = unix_now();
+    let nsec = TestContext::generate_test_key();
+    let identifier = format!("soak-test-{started_at}");
+    runner
+        .run_success(&["account", "login", "--nsec", &nsec, "--password", "test"])
+        .await?;
+
+    let mut init_args = vec![
+        "init",
+        "--identifier",
+        &identifier,
+        "--name",
+        "Soak Test",
+        "--description",
+        "Public relay soak test",
+        "--nsec",
+        &nsec,
+        "--repo-path",
+        repo_path,
+    ];
+    for relay in relays {
+        init_args.extend(["--relay", relay.as_str()]);
+    }
+    runner.run_success(&init_args).await?;
+
+    let interval = config.duration / config.prs as u32;
+    println!(
+        "  🕒 Sending {prs} PR(s) with {comments} comment(s) each, one every {interval:?}",
+        prs = config.prs,
+        comments = config.comments_per_pr
+    );
+
+    let mut samples = Vec::new();
+    for n in 1..=config.prs {
+        let round_start = Instant::now();
+        let title = format!("Soak PR {n}");
+
+        match runner
+            .run(&[
+                "send",
+                "--title",
+                &title,
+                "--description",
+                "Soak test",
+                "--repo-path",
+                repo_path,
+                "--password",
+                "test",
+                "HEAD~1",
+            ])
+            .await
+        {
+            Ok(output) if output.success => {
+                let pr_id = output.stdout.trim().to_string();
+                samples.extend(sample_event("pr", &pr_id, relays, config.max_wait).await?);
+
+                for c in 1..=config.comments_per_pr {
+                    let message = format!("Soak comment {c} on PR {n}");
+                    let output = runner
+                        .run(&[
+                            "comment",
+                            &pr_id,
+                            "--message",
+                            &message,
+                            "--repo-path",
+                            repo_path,
+                            "--password",
+                            "test",
+                        ])
+                        .await?;
+                    match output.stdout.lines().next() {
+                        Some(comment_id) if output.success => samples.extend(
+                            sample_event("comment", comment_id, relays, config.max_wait).await?,
+                        ),
+                        _ => warn!(
+                            "Failed to post comment {c} on PR {n}: {err}",
+                            err = output.stderr
+                        ),
+                    }
+                }
+            }
+            Ok(output) => warn!("Failed to send PR {n}: {err}", err = output.stderr),
+            Err(e) => warn!("Failed to send PR {n}: {e}"),
+        }
+
+        let summary = summarize(&samples);
+        write_report(&config.report_path, config, started_at, &summary, &samples)?;
+        println!("  📊 After PR {n}/{prs}:", prs = config.prs);
+        print_summary(&summary);
+
+        if n < config.prs {
+            sleep(interval.saturating_sub(round_start.elapsed())).await;
+        }
+    }
+
+    println!(
+        "  📝 Report written to {path}",
+        path = config.report_path.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(relay: &str, latency_ms: Option<u64>) -> SoakSample {
+        SoakSample {
+            relay: relay.to_string(),
+            kind: "pr",
+            event_id: "00".repeat(32),
+            published_at: 0,
+            latency_ms,
+            error: latency_ms.is_none().then(|| "Not visible".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        let mut samples: Vec<SoakSample> =
+            (1..=9).map(|i| sample("wss://a", Some(i * 100))).collect();
+        samples.push(sample("wss://a", None));
+        samples.push(sample("wss://b", None));
+
+        let summary = summarize(&samples);
+        assert_eq!(summary.len(), 2);
+        let a = &summary[0];
+        assert_eq!((a.events, a.found), (10, 9));
+        assert!((a.failure_rate - 0.1).abs() < f64::EPSILON);
+        assert_eq!(a.p50_ms, Some(500));
+        assert_eq!(a.p90_ms, Some(900));
+        assert_eq!(a.max_ms, Some(900));
+
+        let b = &summary[1];
+        assert_eq!(b.found, 0);
+        assert_eq!(b.p50_ms, None);
+        assert!(
+            samples_csv(&samples)
+                .lines()
+                .nth(10)
+                .unwrap()
+                .ends_with(",\"Not visible\"")
+        );
+    }
+}
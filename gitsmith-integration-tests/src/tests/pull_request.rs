@@ -119,6 +119,22 @@ pub async fn run_tests(keep_temp: bool, relays: &[String]) -> Result<(usize, usi
         }
     }
 
+    // Test checking a PR out for CI twice in the same workspace
+    match test_ci_checkout_twice(keep_temp, relays).await {
+        Ok(_) => {
+            println!("  {check} test_ci_checkout_twice", check = "✓".green());
+            passed += 1;
+        }
+        Err(e) => {
+            println!(
+                "  {cross} test_ci_checkout_twice: {error}",
+                cross = "✗".red(),
+                error = e
+            );
+            failed += 1;
+        }
+    }
+
     Ok((passed, failed))
 }
 
@@ -707,3 +723,105 @@ async fn test_multiple_prs(keep_temp: bool, relays: &[String]) -> Result<()> {
 
     Ok(())
 }
+
+async fn test_ci_checkout_twice(keep_temp: bool, relays: &[String]) -> Result<()> {
+    let ctx = TestContext::new("test_ci_checkout_twice", keep_temp)?;
+    let runner = GitsmithRunner::new(&ctx.home_dir);
+
+    ctx.setup_git_repo(4)?;
+
+    let nsec = TestContext::generate_test_key();
+    runner
+        .run_success(&["account", "login", "--nsec", &nsec, "--password", "test"])
+        .await?;
+
+    let identifier = generate_unique_identifier("ci-checkout-test");
+    let repo_path = ctx.repo_path.to_string_lossy();
+    let mut init_args = vec![
+        "init",
+        "--identifier",
+        &identifier,
+        "--name",
+        "CI Checkout Test",
+        "--description",
+        "Testing ci checkout reruns",
+        "--nsec",
+        &nsec,
+    ];
+    for relay in relays {
+        init_args.push("--relay");
+        init_args.push(relay);
+    }
+    init_args.push("--repo-path");
+    init_args.push(&repo_path);
+    runner.run_success(&init_args).await?;
+
+    runner
+        .run_success(&[
+            "send",
+            "--title",
+            "CI PR",
+            "--description",
+            "Checked out by CI",
+            "--repo-path",
+            &repo_path,
+            "--password",
+            "test",
+            "HEAD~2",
+        ])
+        .await?;
+    let prs = crate::helpers::list_prs_with_retry(&runner, &repo_path, 10).await?;
+    let pr = assert_pr_exists(&prs, "CI PR")?;
+
+    // The second run replaces the branch the first one left checked out
+    let mut outputs = Vec::new();
+    for _ in 0..2 {
+        let output = runner
+            .run_success(&["ci", "checkout", &pr.id, "--workspace", &repo_path])
+            .await?;
+        outputs.push(output.stdout);
+    }
+    let value = |stdout: &str, key: &str| -> Result<String> {
+        stdout
+            .lines()
+            .find_map(|line| line.strip_prefix(&format!("{key}=")))
+            .map(str::to_string)
+            .with_context(|| format!("No {key}= line in the output: {stdout}"))
+    };
+    // The patches are committed again, so only the head changes
+    for key in ["pr_id", "branch", "base"] {
+        let (first, second) = (value(&outputs[0], key)?, value(&outputs[1], key)?);
+        if first != second {
+            anyhow::bail!("{key} changed between runs: {first} then {second}");
+        }
+    }
+    if value(&outputs[0], "pr_id")? != pr.id {
+        anyhow::bail!("Checked out the wrong PR");
+    }
+
+    let branch = value(&outputs[1], "branch")?;
+    let head = value(&outputs[1], "head")?;
+    let git = |args: &[&str]| -> Result<String> {
+        let output = std::process::Command::new("git")
+            .args(args)
+            .current_dir(&ctx.repo_path)
+            .output()?;
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    let current = git(&["rev-parse", "--abbrev-ref", "HEAD"])?;
+    if current != branch {
+        anyhow::bail!("Expected {branch} to be checked out, HEAD is {current}");
+    }
+    if git(&["rev-parse", "HEAD"])? != head {
+        anyhow::bail!("Expected HEAD at {head}");
+    }
+
+    // Cloning needs a workspace to clone into
+    runner
+        .run_failure(&["ci", "checkout", &pr.id, "--repo", &identifier])
+        .await?;
+
+    println!("    ✓ Checked out {branch} twice");
+
+    Ok(())
+}
@@ -242,7 +242,11 @@ async fn test_list_empty_repo(keep_temp: bool, relays: &[String]) -> Result<()>
         ])
         .await?;
 
-    assert_contains(&output.stdout, "[]", "Should output empty JSON array")?;
+    assert_contains(
+        &output.stdout,
+        "\"pull_requests\": []",
+        "Should output an empty page",
+    )?;
 
     // Also parse to verify it's truly empty
     let prs = output.parse_pr_list()?;
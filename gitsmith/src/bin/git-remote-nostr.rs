@@ -0,0 +1,206 @@
+//! Git remote helper for `nostr://` URLs
+//!
+//! Fetches and pushes are forwarded to the repository's clone URL. That is the
+//! remote's own URL when it is not a `nostr://` URL (see the `nostr` remote
+//! configured by `gitsmith init`), otherwise the first reachable clone URL of the
+//! announcement found on relays, or of its GRASP servers when it lists none, so
+//! `git clone nostr://...` works too. After a
+//! push, the refs of the clone URL are published as a Kind 30618 state event.
+
+use anyhow::{Context, Result, bail};
+use gitsmith_core::{
+    account::{self, AccountOptions},
+    detect_from_git, hooks,
+    relay::{self, Network},
+    remote_helper::{self, PushSpec},
+    repo::{self, NostrUrl},
+};
+use nostr_sdk::PublicKey;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
+
+    let args: Vec<String> = std::env::args().collect();
+    let (Some(remote), Some(url)) = (args.get(1), args.get(2)) else {
+        bail!("Usage: git-remote-nostr <remote> <url>");
+    };
+
+    // Git gives no way to pass flags to the helper, only the environment
+    let keyring = std::env::var("GITSMITH_KEYRING")
+        .is_ok_and(|value| !matches!(value.as_str(), "" | "0" | "false"));
+    let accounts = AccountOptions {
+        keyring,
+        account: None,
+    };
+
+    let nostr_url = repo::parse_nostr_url(url)?;
+    let git_dir = std::env::var_os("GIT_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let network = Network {
+        proxy: relay::configured_proxy(&git_dir)?,
+        ..Network::default()
+    };
+    let clone_url = clone_url(&network, &git_dir, remote, &nostr_url).await?;
+
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut pushes = Vec::new();
+    let mut fetches = Vec::new();
+
+    for line in stdin.lock().lines() {
+        let line = line?;
+
+        if line == "capabilities" {
+            writeln!(stdout, "fetch\npush\n")?;
+        } else if line == "list" || line == "list for-push" {
+            for (oid, name) in remote_helper::ls_remote(&clone_url)? {
+                if !name.ends_with("^{}") {
+                    writeln!(stdout, "{oid} {name}")?;
+                }
+            }
+            writeln!(stdout)?;
+        } else if let Some(args) = line.strip_prefix("fetch ") {
+            let (_oid, name) = remote_helper::parse_fetch_command(args)?;
+            fetches.push(name);
+        } else if line.is_empty() && !fetches.is_empty() {
+            // A blank line ends a batch of fetch commands
+            remote_helper::fetch_from(&clone_url, &std::mem::take(&mut fetches))?;
+            writeln!(stdout)?;
+        } else if let Some(spec) = line.strip_prefix("push ") {
+            pushes.push(remote_helper::parse_push_spec(spec)?);
+        } else if line.is_empty() && !pushes.is_empty() {
+            // A blank line ends a batch of push commands
+            let specs: Vec<PushSpec> = std::mem::take(&mut pushes);
+            let outcomes = remote_helper::push_to(&clone_url, &specs)?;
+            for outcome in &outcomes {
+                writeln!(stdout, "{status}", status = outcome.status_line())?;
+            }
+            writeln!(stdout)?;
+            stdout.flush()?;
+
+            if outcomes.iter().any(|outcome| outcome.error.is_none())
+                && let Err(e) =
+                    publish_state(&network, &accounts, &git_dir, &nostr_url, &clone_url).await
+            {
+                eprintln!("warning: pushed, but failed to publish repository state: {e:#}");
+            }
+        } else if line.is_empty() {
+            break;
+        } else {
+            bail!("Unsupported remote helper command: {line}");
+        }
+
+        stdout.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Clone URL fetches and pushes are forwarded to
+///
+/// The remote's fetch URL when it is a regular git URL, otherwise resolved from
+/// the repository announcement.
+async fn clone_url(
+    network: &Network,
+    git_dir: &Path,
+    remote: &str,
+    nostr_url: &NostrUrl,
+) -> Result<String> {
+    let repo = git2::Repository::open(git_dir)
+        .with_context(|| format!("Failed to open git repository at {git_dir:?}"))?;
+    let config = repo.config()?;
+
+    if let Ok(url) = config.get_string(&format!("remote.{remote}.url"))
+        && !url.starts_with("nostr://")
+    {
+        return Ok(url);
+    }
+
+    let relays = relays(git_dir, nostr_url);
+    if relays.is_empty() {
+        bail!("No relay to look up the repository announcement on. Add one to the nostr:// URL");
+    }
+    let Some(announcement) = repo::fetch_announcement(
+        network,
+        &nostr_url.coordinate(),
+        &relays,
+        Duration::from_secs(10),
+    )
+    .await?
+    else {
+        bail!(
+            "Repository {identifier} not found on any relay",
+            identifier = nostr_url.identifier
+        );
+    };
+
+    let owner = PublicKey::from_hex(&nostr_url.owner)?;
+    let fetch_urls = remote_helper::fetch_urls(&announcement, &owner)?;
+    let (url, _refs) = remote_helper::select_clone_url(&fetch_urls)?;
+    Ok(url)
+}
+
+/// Relays from the URL plus the ones configured for the repository, if any
+fn relays(git_dir: &Path, nostr_url: &NostrUrl) -> Vec<String> {
+    let mut relays = detect_from_git(git_dir)
+        .map(|announcement| announcement.relays)
+        .unwrap_or_default();
+    for relay in &nostr_url.relays {
+        if !relays.contains(relay) {
+            relays.push(relay.clone());
+        }
+    }
+    relays
+}
+
+/// Publish the remote's refs as the repository state
+async fn publish_state(
+    network: &Network,
+    accounts: &AccountOptions,
+    git_dir: &Path,
+    nostr_url: &NostrUrl,
+    clone_url: &str,
+) -> Result<()> {
+    let relays = relays(git_dir, nostr_url);
+    if relays.is_empty() {
+        bail!("No relays configured for repository");
+    }
+
+    // Sign with the account bound to the repository, if any
+    let bound = repo::get_repo_account(git_dir).ok().flatten();
+    let password = match std::env::var("GITSMITH_PASSWORD") {
+        Ok(password) => password,
+        // Stdin and stdout belong to git, so the prompt goes through the terminal
+        Err(_) => match &bound {
+            Some(npub) => rpassword::prompt_password(format!("gitsmith password for {npub}: "))?,
+            None => rpassword::prompt_password("gitsmith password: ")?,
+        },
+    };
+    let signer = account::get_signer(accounts, bound.as_deref(), &password)?;
+    let signer = hooks::with_configured_hook(signer, git_dir);
+
+    let refs = remote_helper::ls_remote(clone_url)?;
+    let state = remote_helper::state_from_remote_refs(&nostr_url.identifier, &refs);
+    let result = repo::publish_git_state(network, signer.as_ref(), &state, &relays).await?;
+
+    if result.successes.is_empty() {
+        bail!("no relay accepted the state event");
+    }
+    eprintln!(
+        "Published repository state to {count} relay(s)",
+        count = result.successes.len()
+    );
+
+    Ok(())
+}
@@ -1,20 +1,42 @@
-use anyhow::Result;
-use clap::Subcommand;
-use gitsmith_core::account;
+use anyhow::{Context, Result, bail};
+use clap::{Args, Subcommand, ValueEnum};
+use clap_complete::ArgValueCandidates;
+use gitsmith_core::{BroadcastResult, account, detect_from_git, profiles, relay_lists};
+use nostr_sdk::{Metadata, PublicKey, Url};
 use rpassword::read_password;
-use std::io::{self, Write};
+use serde::Serialize;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use super::{accounts, active_signer, format_timestamp, network};
 
 #[derive(Subcommand)]
 pub enum AccountCommands {
     /// Login with a private key
     Login {
         /// nsec or hex private key
+        #[arg(
+            long,
+            required_unless_present = "ncryptsec",
+            conflicts_with = "ncryptsec"
+        )]
+        nsec: Option<String>,
+        /// NIP-49 encrypted private key, decrypted with the password
         #[arg(long)]
-        nsec: String,
+        ncryptsec: Option<String>,
         /// Password to encrypt the key (will prompt if not provided)
         #[arg(long, env = "GITSMITH_PASSWORD")]
         password: Option<String>,
     },
+    /// Login through a NIP-46 remote signer, without sharing the private key
+    ConnectBunker {
+        /// bunker:// URI given by the remote signer
+        uri: String,
+        /// Password to encrypt the connection key (will prompt if not provided)
+        #[arg(long, env = "GITSMITH_PASSWORD")]
+        password: Option<String>,
+    },
     /// Logout from active account
     Logout,
     /// Export active account private key
@@ -22,30 +44,202 @@ pub enum AccountCommands {
         /// Password to decrypt the key (will prompt if not provided)
         #[arg(long, env = "GITSMITH_PASSWORD")]
         password: Option<String>,
+        /// Key format, ncryptsec is encrypted with the same password
+        #[arg(long, value_enum, default_value_t = ExportFormat::Nsec)]
+        format: ExportFormat,
     },
     /// List all accounts
     List,
+    /// Make another stored account the active one
+    Switch {
+        /// npub or hex public key of the account
+        #[arg(add = ArgValueCandidates::new(super::completions::account_candidates))]
+        npub: String,
+    },
+    /// Delete a stored account and its encrypted key
+    Remove {
+        /// npub or hex public key of the account
+        #[arg(add = ArgValueCandidates::new(super::completions::account_candidates))]
+        npub: String,
+        /// Don't ask for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Show the active account
+    Show {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Manage the active account's public profile (kind 0 metadata)
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommands,
+    },
+    /// Manage the active account's relay list (NIP-65)
+    Relays {
+        #[command(subcommand)]
+        command: RelaysCommands,
+    },
+    /// Report key usage and hygiene problems of all accounts
+    Audit {
+        /// Relays to look up what each key published
+        #[arg(long = "relay", default_values_t = [
+            "wss://relay.damus.io".to_string(),
+            "wss://nos.lol".to_string(),
+        ])]
+        relays: Vec<String>,
+        /// Only use local account data
+        #[arg(long)]
+        offline: bool,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ProfileCommands {
+    /// Publish profile fields, the ones not given keep their published value
+    Set(ProfileSetArgs),
+}
+
+#[derive(Args)]
+pub struct ProfileSetArgs {
+    /// Name (an empty value removes a field)
+    #[arg(long)]
+    pub name: Option<String>,
+    /// Short bio
+    #[arg(long)]
+    pub about: Option<String>,
+    /// NIP-05 identifier (name@domain)
+    #[arg(long)]
+    pub nip05: Option<String>,
+    /// Avatar URL
+    #[arg(long)]
+    pub picture: Option<String>,
+    /// Relays to publish to (defaults to the repository's relays)
+    #[arg(long = "relay")]
+    pub relays: Vec<String>,
+    /// Password to decrypt the key (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum RelaysCommands {
+    /// Show the published relay list of the active account or another key
+    Show {
+        /// npub or hex public key (defaults to the active account)
+        npub: Option<String>,
+        /// Relays to look the list up on (defaults to the repository's relays)
+        #[arg(long = "lookup-relay")]
+        lookup_relays: Vec<String>,
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Publish the relays others should use to find and reach you
+    Publish(RelaysPublishArgs),
+}
+
+#[derive(Args)]
+pub struct RelaysPublishArgs {
+    /// Relays you read from and write to
+    #[arg(long = "relay", value_delimiter = ',')]
+    pub relays: Vec<String>,
+    /// Relays you only read from (where others send you events)
+    #[arg(long, value_delimiter = ',')]
+    pub read: Vec<String>,
+    /// Relays you only write to (where others find your events)
+    #[arg(long, value_delimiter = ',')]
+    pub write: Vec<String>,
+    /// Password to decrypt the key (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+/// `account export` result with `--format json`
+#[derive(Serialize)]
+struct ExportedKey {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nsec: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ncryptsec: Option<String>,
+}
+
+/// `account profile set` result with `--format json`
+#[derive(Serialize)]
+struct ProfileOutput {
+    profile: Metadata,
+    publish: BroadcastResult,
+}
+
+/// `account relays publish` result with `--format json`
+#[derive(Serialize)]
+struct RelayListOutput {
+    relay_list: relay_lists::RelayList,
+    publish: BroadcastResult,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    /// Plain bech32 private key
+    Nsec,
+    /// NIP-49 password encrypted private key
+    Ncryptsec,
 }
 
 pub async fn handle_account_command(command: AccountCommands) -> Result<()> {
     match command {
-        AccountCommands::Login { nsec, password } => {
+        AccountCommands::Login {
+            nsec,
+            ncryptsec,
+            password,
+        } => {
             let password = if let Some(pwd) = password {
                 pwd
+            } else if ncryptsec.is_some() {
+                eprint!("Enter ncryptsec password: ");
+                io::stderr().flush()?;
+                read_password()?
             } else {
                 eprint!("Enter password to encrypt key: ");
                 io::stderr().flush()?;
                 read_password()?
             };
 
-            account::login(&nsec, &password)?;
-            Ok(())
+            match (nsec, ncryptsec) {
+                (_, Some(ncryptsec)) => {
+                    account::login_ncryptsec(accounts(), &ncryptsec, &password)?
+                }
+                (Some(nsec), None) => account::login(accounts(), &nsec, &password)?,
+                (None, None) => anyhow::bail!("Either --nsec or --ncryptsec is required"),
+            }
+            print_active_account()
+        }
+        AccountCommands::ConnectBunker { uri, password } => {
+            let password = if let Some(pwd) = password {
+                pwd
+            } else {
+                eprint!("Enter password to encrypt connection key: ");
+                io::stderr().flush()?;
+                read_password()?
+            };
+
+            eprintln!("Waiting for the remote signer to approve the connection...");
+            account::connect_bunker(accounts(), &uri, &password).await?;
+            print_active_account()
         }
         AccountCommands::Logout => {
-            account::logout()?;
+            let active = account::get_active_account(accounts()).ok();
+            account::logout(accounts())?;
+            if super::json_output() {
+                return super::print_json(&active.map(|a| a.summary(false)).transpose()?);
+            }
             Ok(())
         }
-        AccountCommands::Export { password } => {
+        AccountCommands::Export { password, format } => {
             let password = if let Some(pwd) = password {
                 pwd
             } else {
@@ -54,12 +248,111 @@ pub async fn handle_account_command(command: AccountCommands) -> Result<()> {
                 read_password()?
             };
 
-            let nsec = account::export_keys(&password)?;
-            println!("Private key: {nsec}");
+            match format {
+                ExportFormat::Nsec => {
+                    let nsec = account::export_keys(accounts(), &password)?;
+                    if super::json_output() {
+                        return super::print_json(&ExportedKey {
+                            nsec: Some(nsec),
+                            ncryptsec: None,
+                        });
+                    }
+                    println!("Private key: {nsec}");
+                }
+                ExportFormat::Ncryptsec => {
+                    eprintln!("Encrypting key, this takes a moment...");
+                    let ncryptsec = account::export_ncryptsec(accounts(), &password)?;
+                    if super::json_output() {
+                        return super::print_json(&ExportedKey {
+                            nsec: None,
+                            ncryptsec: Some(ncryptsec),
+                        });
+                    }
+                    println!("Encrypted key: {ncryptsec}");
+                }
+            }
+            Ok(())
+        }
+        AccountCommands::Profile {
+            command: ProfileCommands::Set(args),
+        } => set_profile(args).await,
+        AccountCommands::Relays {
+            command:
+                RelaysCommands::Show {
+                    npub,
+                    lookup_relays,
+                    json,
+                },
+        } => show_relay_list(npub, lookup_relays, json || super::json_output()).await,
+        AccountCommands::Relays {
+            command: RelaysCommands::Publish(args),
+        } => publish_relay_list(args).await,
+        AccountCommands::Audit {
+            relays,
+            offline,
+            json,
+        } => {
+            let relays = if offline { Vec::new() } else { relays };
+            let audits =
+                account::audit_accounts(accounts(), network(), &relays, Duration::from_secs(5))
+                    .await?;
+            if json || super::json_output() {
+                return super::print_json(&audits);
+            }
+            if audits.is_empty() {
+                eprintln!("No accounts found");
+            }
+            for audit in &audits {
+                print_audit(audit, !relays.is_empty());
+            }
+            Ok(())
+        }
+        AccountCommands::Switch { npub } => {
+            account::switch_account(accounts(), &npub)?;
+            print_active_account()
+        }
+        AccountCommands::Remove { npub, yes } => {
+            let stored = account::get_account(accounts(), &npub)?;
+            if !yes {
+                confirm_remove(&stored)?;
+            }
+            let removed = account::remove_account(accounts(), &npub)?;
+            eprintln!("Removed {npub}", npub = removed.npub);
+            if super::json_output() {
+                return super::print_json(&removed.summary(false)?);
+            }
+            Ok(())
+        }
+        AccountCommands::Show { json } => {
+            let active = account::get_active_account(accounts())?;
+            if json || super::json_output() {
+                return super::print_json(&active.summary(true)?);
+            }
+            println!("{npub}", npub = active.npub);
+            println!(
+                "  Public key: {hex}",
+                hex = nostr_sdk::PublicKey::parse(&active.npub)?.to_hex()
+            );
+            match &active.bunker_uri {
+                // The query may carry the connection secret
+                Some(uri) => println!(
+                    "  Signer:     remote ({signer})",
+                    signer = uri.split('?').next().unwrap_or_default()
+                ),
+                None => println!("  Signer:     local key"),
+            }
+            let last_used = active
+                .last_used
+                .map(format_timestamp)
+                .unwrap_or_else(|| "unknown".to_string());
+            println!("  Last used:  {last_used}");
             Ok(())
         }
         AccountCommands::List => {
-            let accounts = account::list_accounts()?;
+            if super::json_output() {
+                return super::print_json(&account::account_summaries(accounts())?);
+            }
+            let accounts = account::list_accounts(accounts())?;
             if accounts.is_empty() {
                 eprintln!("No accounts found");
             } else {
@@ -72,3 +365,240 @@ pub async fn handle_account_command(command: AccountCommands) -> Result<()> {
         }
     }
 }
+
+/// The account that is active now, as JSON with `--format json`
+fn print_active_account() -> Result<()> {
+    if super::json_output() {
+        return super::print_json(&account::get_active_account(accounts())?.summary(true)?);
+    }
+    Ok(())
+}
+
+/// Ask before deleting an account, insisting when its key exists nowhere else
+fn confirm_remove(stored: &account::StoredAccount) -> Result<()> {
+    anyhow::ensure!(
+        io::stdin().is_terminal(),
+        "Refusing to remove {npub} without a terminal to confirm, pass --yes",
+        npub = stored.npub
+    );
+
+    if stored.bunker_uri.is_some() {
+        eprint!(
+            "Remove {npub}? The key stays in the remote signer [y/N]: ",
+            npub = stored.npub
+        );
+        io::stderr().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        anyhow::ensure!(
+            matches!(answer.trim(), "y" | "Y" | "yes"),
+            "Aborted, the account was kept"
+        );
+        return Ok(());
+    }
+
+    eprintln!(
+        "This deletes the private key of {npub}. Unless you exported it \
+         (gitsmith account export), it is lost for good.",
+        npub = stored.npub
+    );
+    eprint!("Type 'remove' to confirm: ");
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    anyhow::ensure!(answer.trim() == "remove", "Aborted, the account was kept");
+    Ok(())
+}
+
+fn print_audit(audit: &account::AccountAudit, looked_up: bool) {
+    let mut flags = Vec::new();
+    if audit.active {
+        flags.push("active");
+    }
+    if audit.remote_signer {
+        flags.push("remote signer");
+    }
+    if flags.is_empty() {
+        println!("{npub}", npub = audit.npub);
+    } else {
+        println!(
+            "{npub} ({flags})",
+            npub = audit.npub,
+            flags = flags.join(", ")
+        );
+    }
+
+    let last_used = audit
+        .last_used
+        .map(format_timestamp)
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("  Last used:  {last_used}");
+    let encryption = if audit.remote_signer {
+        "held by the remote signer"
+    } else if audit.legacy_encryption {
+        "legacy SHA-256"
+    } else {
+        "Argon2id"
+    };
+    println!("  Encryption: {encryption}");
+    if looked_up {
+        let repos = if audit.announced_repos.is_empty() {
+            "none".to_string()
+        } else {
+            audit.announced_repos.join(", ")
+        };
+        println!("  Repos:      {repos}");
+        let identity = if audit.personal_identity { "yes" } else { "no" };
+        println!("  Personal:   {identity}");
+    }
+    for warning in &audit.warnings {
+        println!("  ⚠ {warning}");
+    }
+    println!();
+}
+
+/// Relays used when neither --relay nor the repository configures any
+const DEFAULT_PROFILE_RELAYS: [&str; 2] = ["wss://relay.damus.io", "wss://nos.lol"];
+
+/// The current repository's relays, or the configured or well-known ones
+/// outside a repository
+fn default_relays() -> Vec<String> {
+    detect_from_git(Path::new("."))
+        .map(|announcement| announcement.relays)
+        .ok()
+        .filter(|relays| !relays.is_empty())
+        .or_else(|| Some(super::default_relays()).filter(|relays| !relays.is_empty()))
+        .unwrap_or_else(|| DEFAULT_PROFILE_RELAYS.map(String::from).to_vec())
+}
+
+async fn set_profile(args: ProfileSetArgs) -> Result<()> {
+    let update = profiles::ProfileUpdate {
+        name: args.name,
+        about: args.about,
+        nip05: args.nip05,
+        picture: args.picture,
+    };
+    if update.is_empty() {
+        bail!("Nothing to update, pass --name, --about, --nip05 or --picture");
+    }
+    if let Some(picture) = update.picture.as_deref().filter(|url| !url.is_empty()) {
+        Url::parse(picture).with_context(|| format!("Invalid picture URL '{picture}'"))?;
+    }
+
+    let relays = if !args.relays.is_empty() {
+        args.relays
+    } else {
+        default_relays()
+    };
+
+    let password = super::password_or_prompt(args.password, "Enter password: ")?;
+    let signer = active_signer(&password)?;
+    let (metadata, result) = profiles::set_profile(
+        network(),
+        signer.as_ref(),
+        &relays,
+        &update,
+        Duration::from_secs(5),
+    )
+    .await?;
+    if result.successes.is_empty() {
+        bail!("Failed to publish profile to any relay");
+    }
+
+    eprintln!(
+        "✅ Profile published to {count} relay(s)",
+        count = result.successes.len()
+    );
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+    if let Some(name) = profiles::display_name(&metadata) {
+        eprintln!("Name: {name}");
+    }
+    if super::json_output() {
+        return super::print_json(&ProfileOutput {
+            profile: metadata,
+            publish: result,
+        });
+    }
+    Ok(())
+}
+
+async fn show_relay_list(
+    npub: Option<String>,
+    lookup_relays: Vec<String>,
+    json: bool,
+) -> Result<()> {
+    let public_key = match npub {
+        Some(npub) => {
+            PublicKey::parse(&npub).with_context(|| format!("Invalid public key '{npub}'"))?
+        }
+        None => PublicKey::parse(&account::get_active_public_key(accounts())?)?,
+    };
+    let lookup_relays = if lookup_relays.is_empty() {
+        default_relays()
+    } else {
+        lookup_relays
+    };
+
+    let list = relay_lists::fetch_relay_list(
+        network(),
+        public_key,
+        &lookup_relays,
+        Duration::from_secs(5),
+    )
+    .await?;
+    if json {
+        return super::print_json(&list);
+    }
+    let Some(list) = list else {
+        bail!("No relay list found, publish one with 'gitsmith account relays publish'");
+    };
+    for relay in list.all() {
+        let marker = match (list.read.contains(&relay), list.write.contains(&relay)) {
+            (true, false) => " (read)",
+            (false, true) => " (write)",
+            _ => "",
+        };
+        println!("{relay}{marker}");
+    }
+    Ok(())
+}
+
+async fn publish_relay_list(args: RelaysPublishArgs) -> Result<()> {
+    let list = relay_lists::RelayList::new(&args.relays, &args.read, &args.write);
+    if list.is_empty() {
+        bail!("No relays given, pass --relay, --read or --write");
+    }
+
+    // The list itself goes to its relays and to the usual lookup relays
+    let mut targets = list.all();
+    for relay in default_relays() {
+        if !targets.contains(&relay) {
+            targets.push(relay);
+        }
+    }
+
+    let password = super::password_or_prompt(args.password, "Enter password: ")?;
+    let signer = active_signer(&password)?;
+    let result =
+        relay_lists::publish_relay_list(network(), signer.as_ref(), &targets, &list).await?;
+    if result.successes.is_empty() {
+        bail!("Failed to publish relay list to any relay");
+    }
+
+    eprintln!(
+        "✅ Relay list published to {count} relay(s)",
+        count = result.successes.len()
+    );
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+    if super::json_output() {
+        return super::print_json(&RelayListOutput {
+            relay_list: list,
+            publish: result,
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,454 @@
+use anyhow::{Context, Result, bail};
+use clap::{Args, Subcommand};
+use gitsmith_core::{
+    detect_from_git,
+    email::{ImapProfile, SmtpProfile},
+    email_bridge::{self, EmailBridgeOptions, EmailBridgeReport},
+    github,
+    github_bridge::{self, ExportOptions, ExportReport, ImportOptions},
+    relay,
+    subscriptions::RepoSubscriptions,
+    watch::WATCHED_KINDS,
+};
+use nostr_sdk::{PublicKey, RelayPoolNotification};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::broadcast::{Receiver, error::RecvError};
+
+use super::{network, print_json, repo_coordinate, repo_signer};
+
+#[derive(Subcommand)]
+pub enum BridgeCommands {
+    /// Bridge a GitHub repository
+    Github {
+        #[command(subcommand)]
+        command: GithubBridgeCommands,
+    },
+    /// Email new patch series to a mailing list and publish the replies as comments
+    Email(EmailBridgeArgs),
+}
+
+#[derive(Subcommand)]
+pub enum GithubBridgeCommands {
+    /// Republish the open pull requests and issues of a GitHub repository
+    Import(ImportArgs),
+    /// Mirror open PRs to GitHub pull requests and their comments both ways
+    Export(ExportArgs),
+}
+
+#[derive(Args)]
+pub struct ImportArgs {
+    /// GitHub repository (owner/name or URL)
+    #[arg(long)]
+    pub repo: String,
+
+    /// Only import pull requests
+    #[arg(long, conflicts_with = "no_pull_requests")]
+    pub no_issues: bool,
+
+    /// Only import issues
+    #[arg(long)]
+    pub no_pull_requests: bool,
+
+    /// GitHub token, only needed for private repositories or to avoid rate limits
+    #[arg(long, env = "GITHUB_TOKEN")]
+    pub github_token: Option<String>,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// GitHub repository (owner/name or URL)
+    #[arg(long)]
+    pub repo: String,
+
+    /// Git remote of your GitHub fork of the repository to push PR branches to
+    #[arg(long)]
+    pub fork_remote: String,
+
+    /// Author whose PRs are mirrored without a maintainer's approval (npub, can be repeated)
+    #[arg(long = "allow", value_name = "NPUB")]
+    pub allowed_authors: Vec<String>,
+
+    /// Branch to open pull requests against (default: the GitHub default branch)
+    #[arg(long)]
+    pub base: Option<String>,
+
+    /// Seconds between checks for new GitHub comments
+    #[arg(long, default_value_t = 60)]
+    pub interval: u64,
+
+    /// Mirror once and exit instead of watching
+    #[arg(long)]
+    pub once: bool,
+
+    /// GitHub token allowed to push, open pull requests and comment
+    #[arg(long, env = "GITHUB_TOKEN")]
+    pub github_token: String,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+#[derive(Args)]
+pub struct EmailBridgeArgs {
+    /// Mailing list address to send patch series to (can be repeated)
+    #[arg(long, required = true, value_name = "ADDRESS")]
+    pub to: Vec<String>,
+
+    /// git send-email identity to read SMTP settings from (sendemail.<profile>.*)
+    #[arg(long)]
+    pub smtp_profile: Option<String>,
+
+    /// SMTP password (overrides sendemail.smtpPass)
+    #[arg(long, env = "GITSMITH_SMTP_PASSWORD")]
+    pub smtp_password: Option<String>,
+
+    /// IMAP password (overrides imap.pass)
+    #[arg(long, env = "GITSMITH_IMAP_PASSWORD")]
+    pub imap_password: Option<String>,
+
+    /// IMAP folder replies arrive in, every unseen message in it is read
+    #[arg(long, default_value = "INBOX")]
+    pub folder: String,
+
+    /// Seconds between checks of the IMAP folder
+    #[arg(long, default_value_t = 60)]
+    pub interval: u64,
+
+    /// Bridge once and exit instead of watching
+    #[arg(long)]
+    pub once: bool,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+pub async fn handle_bridge_command(command: BridgeCommands) -> Result<()> {
+    match command {
+        BridgeCommands::Github { command } => match command {
+            GithubBridgeCommands::Import(args) => handle_import(args).await,
+            GithubBridgeCommands::Export(args) => handle_export(args).await,
+        },
+        BridgeCommands::Email(args) => handle_email(args).await,
+    }
+}
+
+async fn handle_import(args: ImportArgs) -> Result<()> {
+    let (owner, name) = github::parse_github_repository(&args.repo)?;
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let signer = repo_signer(&args.repo_path, args.password)?;
+    let coordinate = repo_coordinate(&args.repo_path, &repo_announcement.identifier)?;
+    let options = ImportOptions {
+        token: args.github_token,
+        pull_requests: !args.no_pull_requests,
+        issues: !args.no_issues,
+        network: network().clone(),
+    };
+
+    eprintln!("Importing open items of {owner}/{name} from GitHub...");
+    let report = github_bridge::import_from_github(
+        signer.as_ref(),
+        &owner,
+        &name,
+        &coordinate,
+        &repo_announcement.root_commit,
+        &repo_announcement.relays,
+        &options,
+    )
+    .await?;
+
+    for item in &report.imported {
+        eprintln!(
+            "✅ {kind} #{number}: {title}",
+            kind = item.kind,
+            number = item.number,
+            title = item.title
+        );
+    }
+    for item in &report.failed {
+        eprintln!(
+            "❌ {kind} #{number}: {error}",
+            kind = item.kind,
+            number = item.number,
+            error = item.error.as_deref().unwrap_or_default()
+        );
+    }
+    eprintln!(
+        "Imported {imported}, skipped {skipped} imported before, {failed} failed",
+        imported = report.imported.len(),
+        skipped = report.skipped.len(),
+        failed = report.failed.len()
+    );
+    if super::json_output() {
+        return print_json(&report);
+    }
+    if report.imported.is_empty() && !report.failed.is_empty() {
+        bail!("Failed to import any item");
+    }
+
+    Ok(())
+}
+
+async fn handle_export(args: ExportArgs) -> Result<()> {
+    let (owner, name) = github::parse_github_repository(&args.repo)?;
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let signer = repo_signer(&args.repo_path, args.password)?;
+    let coordinate = repo_coordinate(&args.repo_path, &repo_announcement.identifier)?;
+    let base = match args.base {
+        Some(base) => base,
+        None => {
+            github::fetch_github_repository(&owner, &name, Some(&args.github_token))
+                .await?
+                .default_branch
+        }
+    };
+    let allowed_authors = args
+        .allowed_authors
+        .iter()
+        .map(|npub| PublicKey::parse(npub).with_context(|| format!("Invalid npub: {npub}")))
+        .collect::<Result<Vec<_>>>()?;
+    let options = ExportOptions {
+        token: args.github_token,
+        repo_path: args.repo_path.clone(),
+        fork_owner: github_bridge::fork_owner(&args.repo_path, &args.fork_remote)?,
+        fork_remote: args.fork_remote,
+        allowed_authors,
+        base,
+        network: network().clone(),
+    };
+    let state_path = github_bridge::export_state_path(&args.repo_path)?;
+    let relays = &repo_announcement.relays;
+
+    // New events on the relays trigger a run right away, GitHub is polled
+    let client = relay::connect(network(), relays, None).await?;
+    let mut subscriptions = RepoSubscriptions::new(client.clone(), WATCHED_KINDS);
+    if !args.once {
+        subscriptions.add_repo(&coordinate).await?;
+        eprintln!(
+            "Mirroring {identifier} to {owner}/{name}, press Ctrl-C to stop",
+            identifier = repo_announcement.identifier
+        );
+    }
+    let mut notifications = client.notifications();
+
+    loop {
+        match github_bridge::export_to_github(
+            signer.as_ref(),
+            &owner,
+            &name,
+            &coordinate,
+            relays,
+            &options,
+            &state_path,
+        )
+        .await
+        {
+            Ok(report) => print_export_report(&report)?,
+            Err(e) if !args.once => eprintln!("Warning: Failed to mirror to GitHub: {e}"),
+            Err(e) => return Err(e.into()),
+        }
+        if args.once {
+            break;
+        }
+
+        let interval = Duration::from_secs(args.interval);
+        if !wait_for_activity(&mut notifications, &subscriptions, interval).await {
+            break;
+        }
+    }
+
+    subscriptions.clear().await;
+    client.disconnect().await;
+    Ok(())
+}
+
+/// Wait until new events reach the relays or `interval` passes, false once
+/// the bridge should stop
+async fn wait_for_activity(
+    notifications: &mut Receiver<RelayPoolNotification>,
+    subscriptions: &RepoSubscriptions,
+    interval: Duration,
+) -> bool {
+    let poll = tokio::time::sleep(interval);
+    tokio::pin!(poll);
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => return false,
+            _ = &mut poll => return true,
+            notification = notifications.recv() => match notification {
+                Ok(RelayPoolNotification::Event { subscription_id, .. })
+                    if subscriptions.contains(&subscription_id) => return true,
+                Ok(_) => continue,
+                Err(RecvError::Lagged(_)) => return true,
+                Err(RecvError::Closed) => return false,
+            },
+        }
+    }
+}
+
+fn print_export_report(report: &ExportReport) -> Result<()> {
+    if super::json_output() {
+        println!("{report}", report = serde_json::to_string(report)?);
+        return Ok(());
+    }
+    for pull in &report.opened {
+        eprintln!(
+            "✅ Opened {url}: {title}",
+            url = pull.url.as_deref().unwrap_or_default(),
+            title = pull.title
+        );
+    }
+    for pull in &report.updated {
+        eprintln!(
+            "✅ Pushed new revision to {url}: {title}",
+            url = pull.url.as_deref().unwrap_or_default(),
+            title = pull.title
+        );
+    }
+    for pull in &report.skipped {
+        eprintln!(
+            "⏸️  Not pushed, waiting for a maintainer's approval: {title} ({id})",
+            title = pull.title,
+            id = &pull.event_id[..8]
+        );
+    }
+    for pull in &report.failed {
+        eprintln!(
+            "❌ {id}: {error}",
+            id = &pull.event_id[..8],
+            error = pull.error.as_deref().unwrap_or_default()
+        );
+    }
+    if report.comments_to_github > 0 || report.comments_to_nostr > 0 {
+        eprintln!(
+            "✅ Mirrored {to_github} comment(s) to GitHub, {to_nostr} to Nostr",
+            to_github = report.comments_to_github,
+            to_nostr = report.comments_to_nostr
+        );
+    }
+    Ok(())
+}
+
+async fn handle_email(args: EmailBridgeArgs) -> Result<()> {
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let mut smtp = SmtpProfile::from_git_config(&args.repo_path, args.smtp_profile.as_deref())?;
+    if args.smtp_password.is_some() {
+        smtp.password = args.smtp_password;
+    }
+    let mut imap = ImapProfile::from_git_config(&args.repo_path)?;
+    if args.imap_password.is_some() {
+        imap.password = args.imap_password;
+    }
+    let signer = repo_signer(&args.repo_path, args.password)?;
+    let coordinate = repo_coordinate(&args.repo_path, &repo_announcement.identifier)?;
+    let options = EmailBridgeOptions {
+        to: args.to,
+        smtp,
+        imap,
+        folder: args.folder,
+    };
+    let state_path = email_bridge::email_bridge_state_path(&args.repo_path)?;
+    let relays = &repo_announcement.relays;
+
+    // New events on the relays trigger a run right away, the inbox is polled
+    let client = relay::connect(network(), relays, None).await?;
+    let mut subscriptions = RepoSubscriptions::new(client.clone(), WATCHED_KINDS);
+    if !args.once {
+        subscriptions.add_repo(&coordinate).await?;
+        eprintln!(
+            "Bridging {identifier} with {to}, press Ctrl-C to stop",
+            identifier = repo_announcement.identifier,
+            to = options.to.join(", ")
+        );
+    }
+    let mut notifications = client.notifications();
+
+    loop {
+        match email_bridge::run_email_bridge(
+            network(),
+            signer.as_ref(),
+            &coordinate,
+            relays,
+            &options,
+            &state_path,
+        )
+        .await
+        {
+            Ok(report) => print_email_report(&report)?,
+            Err(e) if !args.once => eprintln!("Warning: Failed to bridge email: {e}"),
+            Err(e) => return Err(e.into()),
+        }
+        if args.once {
+            break;
+        }
+
+        let interval = Duration::from_secs(args.interval);
+        if !wait_for_activity(&mut notifications, &subscriptions, interval).await {
+            break;
+        }
+    }
+
+    subscriptions.clear().await;
+    client.disconnect().await;
+    Ok(())
+}
+
+fn print_email_report(report: &EmailBridgeReport) -> Result<()> {
+    if super::json_output() {
+        println!("{report}", report = serde_json::to_string(report)?);
+        return Ok(());
+    }
+    for series in &report.emailed {
+        eprintln!(
+            "✅ Emailed {title} ({count} message(s))",
+            title = series.title,
+            count = series.messages
+        );
+    }
+    if report.comments > 0 {
+        eprintln!(
+            "✅ Published {count} emailed reply(ies) as comments",
+            count = report.comments
+        );
+    }
+    for failure in &report.failed {
+        eprintln!("❌ {failure}");
+    }
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use gitsmith_core::cache::{self, CacheBackend};
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(Subcommand)]
+pub enum CacheCommands {
+    /// Show where the cache is and how many events it holds
+    Stats,
+    /// Remove all cached events
+    Clear,
+}
+
+/// `cache stats` result with `--format json`
+#[derive(Serialize)]
+struct CacheStats {
+    backend: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
+    events: usize,
+}
+
+/// `cache clear` result with `--format json`
+#[derive(Serialize)]
+struct CacheCleared {
+    removed: usize,
+}
+
+pub fn handle_cache_command(command: CacheCommands, backend: CacheBackend) -> Result<()> {
+    let store = cache::open_cache(backend).context("Failed to open the event cache")?;
+    match command {
+        CacheCommands::Stats => {
+            let path = cache::get_cache_path(backend)?;
+            if super::json_output() {
+                return super::print_json(&CacheStats {
+                    backend: backend.to_string(),
+                    path: (backend != CacheBackend::Memory).then_some(path),
+                    events: store.count()?,
+                });
+            }
+            println!("Backend: {backend}");
+            if backend != CacheBackend::Memory {
+                println!("Path: {path}", path = path.display());
+            }
+            println!("Events: {count}", count = store.count()?);
+        }
+        CacheCommands::Clear => {
+            let count = store.count()?;
+            store.clear()?;
+            if super::json_output() {
+                return super::print_json(&CacheCleared { removed: count });
+            }
+            println!("✅ Removed {count} cached event(s)");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,482 @@
+use anyhow::{Context, Result, bail};
+use clap::{Args, Subcommand};
+use gitsmith_core::{
+    apply,
+    ci::{self, AttachLogOptions, CheckResult, CheckState, ReportOptions},
+    detect_from_git,
+};
+use nostr_sdk::ToBech32;
+use serde::Serialize;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use super::{network, repo_coordinate, repo_signer};
+
+#[derive(Subcommand)]
+pub enum CiCommands {
+    /// Upload a build log and report it as a check on a PR
+    AttachLog(AttachLogArgs),
+
+    /// Report a check on a PR, linking to the run on the CI system
+    Report(ReportArgs),
+
+    /// Download and page the build log attached to a PR
+    Logs(LogsArgs),
+
+    /// Check out a PR's patches on their parent commit for a CI build
+    Checkout(CheckoutArgs),
+}
+
+#[derive(Args)]
+pub struct AttachLogArgs {
+    /// PR event ID
+    pub pr_id: String,
+
+    /// Build log file to upload
+    #[arg(long)]
+    pub log: PathBuf,
+
+    /// Check name
+    #[arg(long, default_value = "build")]
+    pub name: String,
+
+    /// Check outcome (pending, success, failure, error)
+    #[arg(long)]
+    pub state: CheckState,
+
+    /// Short summary shown with the check
+    #[arg(long, short = 'm', default_value = "")]
+    pub message: String,
+
+    /// Blossom server to upload to (defaults to the repository's blossom servers)
+    #[arg(long = "server")]
+    pub servers: Vec<String>,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ReportArgs {
+    /// PR event ID
+    pub pr_id: String,
+
+    /// Check outcome (pending, success, failure, error)
+    #[arg(long)]
+    pub state: CheckState,
+
+    /// Page with the details of the run, e.g. its log
+    #[arg(long)]
+    pub url: Option<String>,
+
+    /// Check name
+    #[arg(long, default_value = "build")]
+    pub name: String,
+
+    /// Short summary shown with the check
+    #[arg(long, short = 'm', default_value = "")]
+    pub message: String,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+#[derive(Args)]
+pub struct LogsArgs {
+    /// PR event ID
+    pub pr_id: String,
+
+    /// Only consider checks with this name
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Print the log instead of opening it in a pager
+    #[arg(long)]
+    pub no_pager: bool,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+}
+
+#[derive(Args)]
+pub struct CheckoutArgs {
+    /// PR event ID
+    pub pr_id: String,
+
+    /// Directory to check the PR out in, cloned into unless it holds the
+    /// repository (defaults to the current directory)
+    #[arg(long)]
+    pub workspace: Option<PathBuf>,
+
+    /// Repository address (naddr or nostr://<npub>/<relay>/<identifier>) to
+    /// clone, into the --workspace it requires
+    #[arg(long, requires = "workspace")]
+    pub repo: Option<String>,
+
+    /// Branch to check the patches out on (defaults to pr/<short id>)
+    #[arg(long)]
+    pub branch: Option<String>,
+
+    /// Check out the PR even if it is below the repository's proof-of-work requirement
+    #[arg(long)]
+    pub include_low_pow: bool,
+}
+
+/// What `ci checkout` checked out, printed as `key=value` lines or JSON
+#[derive(Serialize)]
+struct CheckoutOutput {
+    pr_id: String,
+    branch: String,
+    base: String,
+    head: String,
+    author: String,
+    workspace: PathBuf,
+}
+
+impl CheckoutOutput {
+    /// `key=value` lines, ready to append to e.g. $GITHUB_OUTPUT
+    fn key_values(&self) -> String {
+        format!(
+            "pr_id={pr_id}\nbranch={branch}\nbase={base}\nhead={head}\nauthor={author}\nworkspace={workspace}\n",
+            pr_id = self.pr_id,
+            branch = self.branch,
+            base = self.base,
+            head = self.head,
+            author = self.author,
+            workspace = self.workspace.display()
+        )
+    }
+}
+
+/// `ci logs` result with `--format json`
+#[derive(Serialize)]
+struct LogOutput<'a> {
+    check: &'a CheckResult,
+    log: String,
+}
+
+pub async fn handle_ci_command(command: CiCommands) -> Result<()> {
+    match command {
+        CiCommands::AttachLog(args) => handle_attach_log(args).await,
+        CiCommands::Report(args) => handle_report(args).await,
+        CiCommands::Logs(args) => handle_logs(args).await,
+        CiCommands::Checkout(args) => handle_checkout(args).await,
+    }
+}
+
+async fn handle_attach_log(args: AttachLogArgs) -> Result<()> {
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let servers = if args.servers.is_empty() {
+        repo_announcement.blossom_servers.clone()
+    } else {
+        args.servers
+    };
+    if servers.is_empty() {
+        bail!("No Blossom servers configured. Pass --server or run 'gitsmith init --blossoms'");
+    }
+
+    let log = std::fs::read(&args.log)
+        .with_context(|| format!("Failed to read {path}", path = args.log.display()))?;
+
+    let signer = repo_signer(&args.repo_path, args.password)?;
+    let coordinate = repo_coordinate(&args.repo_path, &repo_announcement.identifier)?;
+
+    let options = AttachLogOptions {
+        name: args.name,
+        state: args.state,
+        log,
+        servers,
+        description: args.message,
+    };
+    let result = ci::attach_log(
+        network(),
+        signer.as_ref(),
+        &args.pr_id,
+        &coordinate,
+        &repo_announcement.relays,
+        &options,
+    )
+    .await?;
+
+    if result.successes.is_empty() {
+        bail!("Failed to publish check to any relay");
+    }
+
+    eprintln!(
+        "✅ Check '{name}' ({state}) published to {count} relay(s)",
+        name = options.name,
+        state = options.state,
+        count = result.successes.len()
+    );
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+    if super::json_output() {
+        return super::print_json(&result);
+    }
+    for event_id in &result.event_ids {
+        println!("{event_id}");
+    }
+
+    Ok(())
+}
+
+async fn handle_report(args: ReportArgs) -> Result<()> {
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let signer = repo_signer(&args.repo_path, args.password)?;
+    let coordinate = repo_coordinate(&args.repo_path, &repo_announcement.identifier)?;
+
+    let options = ReportOptions {
+        name: args.name,
+        state: args.state,
+        url: args.url,
+        description: args.message,
+    };
+    let result = ci::report_check(
+        network(),
+        signer.as_ref(),
+        &args.pr_id,
+        &coordinate,
+        &repo_announcement.relays,
+        &options,
+    )
+    .await?;
+
+    if result.successes.is_empty() {
+        bail!("Failed to publish check to any relay");
+    }
+
+    eprintln!(
+        "✅ Check '{name}' ({state}) published to {count} relay(s)",
+        name = options.name,
+        state = options.state,
+        count = result.successes.len()
+    );
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+    if super::json_output() {
+        return super::print_json(&result);
+    }
+    for event_id in &result.event_ids {
+        println!("{event_id}");
+    }
+
+    Ok(())
+}
+
+async fn handle_logs(args: LogsArgs) -> Result<()> {
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let checks = ci::fetch_checks(
+        network(),
+        &args.pr_id,
+        &repo_announcement.relays,
+        Duration::from_secs(10),
+    )
+    .await?;
+
+    // Checks are newest first, so this picks the latest log
+    let Some((check, log)) = checks
+        .iter()
+        .filter(|check| args.name.as_ref().is_none_or(|name| &check.name == name))
+        .find_map(|check| check.log.as_ref().map(|log| (check, log)))
+    else {
+        bail!(
+            "No build log attached to PR {pr_id}",
+            pr_id = &args.pr_id[..8.min(args.pr_id.len())]
+        );
+    };
+
+    eprintln!(
+        "📄 {name} ({state}) by {author}...",
+        name = check.name,
+        state = check.state,
+        author = &check.author[..16.min(check.author.len())]
+    );
+    let contents = ci::download_log(log).await?;
+    if super::json_output() {
+        return super::print_json(&LogOutput {
+            check,
+            log: contents,
+        });
+    }
+
+    if args.no_pager || !std::io::stdout().is_terminal() {
+        print!("{contents}");
+        return Ok(());
+    }
+
+    page(&contents)
+}
+
+async fn handle_checkout(args: CheckoutArgs) -> Result<()> {
+    let workspace = args.workspace.unwrap_or_else(|| PathBuf::from("."));
+    let workspace = if git2::Repository::open(&workspace).is_ok() {
+        eprintln!(
+            "Fetching into {workspace}...",
+            workspace = workspace.display()
+        );
+        run_git(&workspace, &["fetch", "--all", "--quiet"])?;
+        workspace
+    } else {
+        let Some(url) = &args.repo else {
+            bail!(
+                "{workspace} is not a git repository. Pass --repo to clone it",
+                workspace = workspace.display()
+            );
+        };
+        super::clone::clone_repository(url, Some(workspace), vec![])
+            .await?
+            .directory
+    };
+
+    let repo_announcement =
+        detect_from_git(&workspace).context("Failed to detect repository in the workspace")?;
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let series = super::pr::fetch_series(&args.pr_id, &repo_announcement.relays).await?;
+    if !args.include_low_pow {
+        super::pr::ensure_min_pow(&series, repo_announcement.min_pow)?;
+    }
+    let branch = args
+        .branch
+        .unwrap_or_else(|| format!("pr/{short_id}", short_id = series.short_id()));
+
+    // Reruns of a job replace the branch of the previous run, which may be
+    // checked out. A fresh clone of an empty repository has nothing to detach
+    if git2::Repository::open(&workspace)?.head().is_ok() {
+        run_git(&workspace, &["checkout", "--quiet", "--detach"])?;
+    }
+    let result = apply::create_branch_from_patches(
+        &workspace,
+        &branch,
+        &series.patch_contents(),
+        series.parent_commit().as_deref(),
+        true,
+    )?;
+    run_git(&workspace, &["checkout", "--quiet", &branch])?;
+    eprintln!(
+        "✅ Checked out {count} patch(es) on {branch} at {head:.8}",
+        count = result.patches.len(),
+        head = result.head
+    );
+
+    let output = CheckoutOutput {
+        pr_id: series.pr.id.to_hex(),
+        branch: result.branch,
+        base: result.base,
+        head: result.head,
+        author: series.pr.pubkey.to_bech32()?,
+        workspace,
+    };
+    if super::json_output() {
+        return super::print_json(&output);
+    }
+    print!("{lines}", lines = output.key_values());
+
+    Ok(())
+}
+
+/// Run git in `repo_path`, its output going to stderr
+fn run_git(repo_path: &Path, args: &[&str]) -> Result<()> {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(args)
+        .stdout(Stdio::from(io::stderr()))
+        .status()
+        .context("Failed to run git")?;
+    if !status.success() {
+        bail!("git {command} failed", command = args.join(" "));
+    }
+    Ok(())
+}
+
+/// Show text through $PAGER (or `less -R`)
+fn page(contents: &str) -> Result<()> {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less -R".to_string());
+    let mut parts = pager.split_whitespace();
+    let Some(program) = parts.next() else {
+        print!("{contents}");
+        return Ok(());
+    };
+
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start pager '{pager}'"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager may exit before reading everything (e.g. the user quits early)
+        let _ = stdin.write_all(contents.as_bytes());
+    }
+    child.wait()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkout_output() {
+        let output = CheckoutOutput {
+            pr_id: "a".repeat(64),
+            branch: "pr/aaaaaaaa".to_string(),
+            base: "b".repeat(40),
+            head: "c".repeat(40),
+            author: "npub1author".to_string(),
+            workspace: PathBuf::from("/tmp/workspace"),
+        };
+        assert_eq!(
+            output.key_values(),
+            format!(
+                "pr_id={pr_id}\nbranch=pr/aaaaaaaa\nbase={base}\nhead={head}\n\
+                 author=npub1author\nworkspace=/tmp/workspace\n",
+                pr_id = "a".repeat(64),
+                base = "b".repeat(40),
+                head = "c".repeat(40)
+            )
+        );
+
+        let json = serde_json::to_value(&output).unwrap();
+        assert_eq!(json["branch"], "pr/aaaaaaaa");
+        assert_eq!(json["head"], "c".repeat(40));
+        assert_eq!(json["workspace"], "/tmp/workspace");
+        assert_eq!(json.as_object().unwrap().len(), 6);
+    }
+}
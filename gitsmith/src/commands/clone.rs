@@ -0,0 +1,132 @@
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use gitsmith_core::{remote_helper, repo, update_git_config_full};
+use nostr_sdk::{PublicKey, ToBech32};
+use serde::Serialize;
+use std::io;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use super::{default_relays, network};
+
+#[derive(Args)]
+pub struct CloneArgs {
+    /// Repository address (naddr or nostr://<npub>/<relay>/<identifier>)
+    pub url: String,
+
+    /// Directory to clone into (defaults to the repository identifier)
+    pub directory: Option<PathBuf>,
+
+    /// Additional relay to look up the repository announcement on (can be repeated)
+    #[arg(long = "relay", short = 'r')]
+    pub relays: Vec<String>,
+}
+
+/// `clone` result with `--format json`
+#[derive(Serialize)]
+pub(super) struct CloneOutput {
+    pub identifier: String,
+    pub name: String,
+    pub directory: PathBuf,
+    pub clone_url: String,
+    pub nostr_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+}
+
+pub async fn handle_clone_command(args: CloneArgs) -> Result<()> {
+    let cloned = clone_repository(&args.url, args.directory, args.relays).await?;
+    if super::json_output() {
+        return super::print_json(&cloned);
+    }
+
+    Ok(())
+}
+
+/// Clone the repository announced at `url` into `directory` and configure its
+/// nostr remote
+pub(super) async fn clone_repository(
+    url: &str,
+    directory: Option<PathBuf>,
+    extra_relays: Vec<String>,
+) -> Result<CloneOutput> {
+    let nostr_url = repo::parse_repository_address(url)?;
+
+    let mut relays = nostr_url.relays.clone();
+    relays.extend(extra_relays);
+    if relays.is_empty() {
+        relays = default_relays();
+    }
+    if relays.is_empty() {
+        bail!("No relay in {url}. Pass one with --relay");
+    }
+
+    eprintln!(
+        "Fetching announcement for {identifier} from {count} relay(s)...",
+        identifier = nostr_url.identifier,
+        count = relays.len()
+    );
+    let Some(mut announcement) = repo::fetch_announcement(
+        network(),
+        &nostr_url.coordinate(),
+        &relays,
+        Duration::from_secs(10),
+    )
+    .await?
+    else {
+        bail!(
+            "Repository {identifier} not found on any relay",
+            identifier = nostr_url.identifier
+        );
+    };
+    if announcement.relays.is_empty() {
+        announcement.relays = relays;
+    }
+
+    // Skip clone URLs that are down instead of failing on the first one
+    let owner = PublicKey::from_hex(&nostr_url.owner)?;
+    let fetch_urls = remote_helper::fetch_urls(&announcement, &owner)?;
+    let (clone_url, _refs) = remote_helper::select_clone_url(&fetch_urls)?;
+    let directory = directory.unwrap_or_else(|| PathBuf::from(&announcement.identifier));
+
+    eprintln!("Cloning from {clone_url}...");
+    let mut git = Command::new("git");
+    git.arg("clone").arg(&clone_url).arg(&directory);
+    // Keep stdout for the result of the command cloning
+    git.stdout(Stdio::from(io::stderr()));
+    let status = git.status().context("Failed to run git clone")?;
+    if !status.success() {
+        bail!("git clone failed");
+    }
+
+    // Addresses without a relay hint point at the relays the announcement lists
+    let mut nostr_url = nostr_url;
+    if nostr_url.relays.is_empty() {
+        nostr_url.relays = announcement.relays.clone();
+    }
+    let owner_npub = owner.to_bech32()?;
+    let url = nostr_url.to_url()?;
+    update_git_config_full(&directory, &announcement, &url, &owner_npub)
+        .context("Failed to save nostr configuration")?;
+    super::warn_identity_mismatch(&directory, &announcement);
+
+    eprintln!(
+        "✅ Cloned {name} into {directory}",
+        name = announcement.name,
+        directory = directory.display()
+    );
+    if let Some(license) = &announcement.license {
+        eprintln!("   License: {license}");
+    }
+    eprintln!("   Push with 'git push nostr <branch>' to also publish the repository state");
+
+    Ok(CloneOutput {
+        identifier: announcement.identifier,
+        name: announcement.name,
+        directory,
+        clone_url,
+        nostr_url: url,
+        license: announcement.license,
+    })
+}
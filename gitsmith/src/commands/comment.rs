@@ -0,0 +1,136 @@
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use gitsmith_core::{
+    comments::{self, CodeLocation},
+    detect_from_git, pull_request,
+};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::{network, repo_coordinate, repo_signer};
+
+#[derive(Args)]
+pub struct CommentArgs {
+    /// PR, patch or issue event ID (or a comment ID to reply to it)
+    pub event_id: String,
+
+    /// Comment text (markdown)
+    #[arg(long, short = 'm')]
+    pub message: String,
+
+    /// Anchor the comment to a file of the PR's diff
+    #[arg(long)]
+    pub file: Option<String>,
+
+    /// Line of the file, as numbered after the change
+    #[arg(long, requires = "file")]
+    pub line: Option<u32>,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+pub async fn handle_comment_command(args: CommentArgs) -> Result<()> {
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let signer = repo_signer(&args.repo_path, args.password)?;
+    let coordinate = repo_coordinate(&args.repo_path, &repo_announcement.identifier)?;
+
+    let location = match &args.file {
+        Some(path) => Some(
+            code_location(
+                &args.event_id,
+                path,
+                args.line,
+                &coordinate,
+                &repo_announcement.relays,
+            )
+            .await?,
+        ),
+        None => None,
+    };
+
+    let result = comments::post_comment(
+        network(),
+        signer.as_ref(),
+        &args.event_id,
+        &args.message,
+        location.as_ref(),
+        Some(&coordinate),
+        &repo_announcement.relays,
+    )
+    .await?;
+
+    if result.successes.is_empty() {
+        bail!("Failed to publish comment to any relay");
+    }
+
+    eprintln!(
+        "✅ Comment published to {count} relay(s)",
+        count = result.successes.len()
+    );
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+    if super::json_output() {
+        return super::print_json(&result);
+    }
+    for event_id in &result.event_ids {
+        println!("{event_id}");
+    }
+
+    Ok(())
+}
+
+/// Where in the PR's diff an inline comment goes, with the hunk holding the line
+async fn code_location(
+    event_id: &str,
+    path: &str,
+    line: Option<u32>,
+    coordinate: &str,
+    relays: &[String],
+) -> Result<CodeLocation> {
+    let details = pull_request::fetch_pull_request(
+        network(),
+        event_id,
+        coordinate,
+        relays,
+        Duration::from_secs(5),
+    )
+    .await
+    .context("Inline comments go on a pull request")?;
+    let touched = details
+        .patches
+        .iter()
+        .any(|patch| patch.diffstat.files.iter().any(|file| file.path == path));
+    if !touched {
+        eprintln!("Warning: The pull request doesn't change {path}");
+    }
+    let hunk = line.and_then(|line| {
+        details
+            .patches
+            .iter()
+            .rev()
+            .find_map(|patch| comments::find_hunk(&patch.content, path, line))
+    });
+    if let (Some(line), None, true) = (line, &hunk, touched) {
+        eprintln!(
+            "Warning: Line {line} of {path} is outside the diff, the comment only shows in the thread"
+        );
+    }
+    Ok(CodeLocation {
+        path: path.to_string(),
+        line,
+        hunk,
+    })
+}
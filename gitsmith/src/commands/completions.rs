@@ -0,0 +1,124 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::env::{Bash, EnvCompleter, Fish, Powershell, Zsh};
+use gitsmith_core::{account, cache, detect_from_git, events};
+use nostr_sdk::{Filter, Kind};
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::accounts;
+
+/// Environment variable that makes gitsmith answer completion requests
+pub const COMPLETE_ENV: &str = "COMPLETE";
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+impl Shell {
+    fn completer(self) -> &'static dyn EnvCompleter {
+        match self {
+            Shell::Bash => &Bash,
+            Shell::Zsh => &Zsh,
+            Shell::Fish => &Fish,
+            Shell::Powershell => &Powershell,
+        }
+    }
+}
+
+/// Print the script registering gitsmith's completions in `shell`
+///
+/// The script calls back into gitsmith on every completion, so stored
+/// accounts and repositories are offered as they are at that moment.
+pub fn handle_completions_command(shell: Shell) -> Result<()> {
+    let mut script = Vec::new();
+    shell.completer().write_registration(
+        COMPLETE_ENV,
+        "gitsmith",
+        "gitsmith",
+        "gitsmith",
+        &mut script,
+    )?;
+    io::stdout().write_all(&script)?;
+    Ok(())
+}
+
+/// Stored accounts, the active one first
+pub fn account_candidates() -> Vec<CompletionCandidate> {
+    let mut summaries = account::account_summaries(accounts()).unwrap_or_default();
+    summaries.sort_by_key(|summary| !summary.active);
+    summaries
+        .into_iter()
+        .map(|summary| {
+            let help = match (summary.active, summary.remote_signer) {
+                (true, true) => Some("active, remote signer"),
+                (true, false) => Some("active"),
+                (false, true) => Some("remote signer"),
+                (false, false) => None,
+            };
+            CompletionCandidate::new(summary.npub).help(help.map(Into::into))
+        })
+        .collect()
+}
+
+/// The current repository's identifier and those of announcements in the event cache
+pub fn identifier_candidates() -> Vec<CompletionCandidate> {
+    let mut identifiers: Vec<(String, Option<String>)> = Vec::new();
+    if let Ok(announcement) = detect_from_git(Path::new(".")) {
+        identifiers.push((announcement.identifier, Some("this repository".into())));
+    }
+
+    let filter = Filter::new().kind(Kind::from(events::KIND_GIT_REPO_ANNOUNCEMENT));
+    let cached = cache::open_cache(cache::CacheBackend::default())
+        .and_then(|store| store.query(&[filter]))
+        .unwrap_or_default();
+    for event in cached {
+        let Some(identifier) = event.tags.identifier() else {
+            continue;
+        };
+        if !identifiers.iter().any(|(known, _)| known == identifier) {
+            let name = event
+                .tags
+                .iter()
+                .find(|tag| tag.kind().as_str() == "name")
+                .and_then(|tag| tag.content())
+                .map(String::from);
+            identifiers.push((identifier.to_string(), name));
+        }
+    }
+
+    identifiers
+        .into_iter()
+        .map(|(identifier, help)| CompletionCandidate::new(identifier).help(help.map(Into::into)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registration_scripts() {
+        for shell in Shell::value_variants() {
+            let mut script = Vec::new();
+            shell
+                .completer()
+                .write_registration(
+                    COMPLETE_ENV,
+                    "gitsmith",
+                    "gitsmith",
+                    "gitsmith",
+                    &mut script,
+                )
+                .unwrap();
+            let script = String::from_utf8(script).unwrap();
+            assert!(script.contains("COMPLETE"), "{shell:?}");
+            assert!(script.contains("gitsmith"), "{shell:?}");
+        }
+    }
+}
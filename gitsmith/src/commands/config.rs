@@ -0,0 +1,210 @@
+use anyhow::{Context, Result, bail};
+use clap::{Command, Subcommand, ValueEnum};
+use gitsmith_core::cache::CacheBackend;
+use gitsmith_core::config::{self, Config, Value};
+use gitsmith_core::relay;
+use gitsmith_core::time::{self, TimeStyle, Timezone};
+use nostr_sdk::{PublicKey, RelayUrl};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use super::print_json;
+
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    /// Print the value of a key
+    Get {
+        /// Configuration key, see 'gitsmith config list --all'
+        key: String,
+    },
+    /// Set a key, lists like relays are comma separated
+    Set {
+        /// Configuration key, see 'gitsmith config list --all'
+        key: String,
+        value: String,
+    },
+    /// Remove a key, going back to the built-in default
+    Unset {
+        /// Configuration key
+        key: String,
+    },
+    /// Show the keys set in the configuration file
+    List {
+        /// Also list the keys that are not set, with their meaning
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+/// Keys that set the default of the command-line flag of the same name
+const FLAG_KEYS: [&str; 6] = [
+    "timeout",
+    "format",
+    "cache",
+    "time",
+    "timezone",
+    "date-format",
+];
+
+pub fn handle_config_command(command: ConfigCommands) -> Result<()> {
+    let path = config::get_config_path()?;
+    let mut config = Config::load(&path)?;
+    match command {
+        ConfigCommands::Get { key } => {
+            config::key(&key)?;
+            let value = config.get(&key);
+            if super::json_output() {
+                return print_json(&value);
+            }
+            let Some(value) = value else {
+                bail!("{key} is not set");
+            };
+            match value {
+                Value::List(items) => items.iter().for_each(|item| println!("{item}")),
+                value => println!("{value}"),
+            }
+        }
+        ConfigCommands::Set { key, value } => {
+            let value = Value::parse(config::key(&key)?.kind, &value)?;
+            validate(&key, &value)?;
+            config.set(&key, value)?;
+            config.save(&path)?;
+            eprintln!("✅ Set {key} in {path}", path = path.display());
+        }
+        ConfigCommands::Unset { key } => {
+            config::key(&key)?;
+            if !config.unset(&key) {
+                bail!("{key} is not set");
+            }
+            config.save(&path)?;
+            eprintln!("✅ Removed {key} from {path}", path = path.display());
+        }
+        ConfigCommands::List { all } => {
+            if super::json_output() {
+                let values: BTreeMap<&str, &Value> = config.entries().collect();
+                return print_json(&values);
+            }
+            eprintln!("# {path}", path = path.display());
+            for (key, value) in config.entries() {
+                println!("{key} = {value}");
+            }
+            if all {
+                for key in config::KEYS {
+                    if config.get(key.name).is_none() {
+                        println!(
+                            "# {name} ({kind}): {description}",
+                            name = key.name,
+                            kind = key.kind,
+                            description = key.description
+                        );
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Refuse values the command would fail on, so a bad value can't break every command
+fn validate(key: &str, value: &Value) -> Result<()> {
+    match (key, value) {
+        ("relays", Value::List(relays)) => {
+            for url in relays {
+                RelayUrl::parse(url).with_context(|| format!("Invalid relay URL {url}"))?;
+            }
+        }
+        ("account", Value::String(account)) => {
+            PublicKey::parse(account).with_context(|| format!("Invalid public key {account}"))?;
+        }
+        ("proxy", Value::String(proxy)) => {
+            relay::parse_proxy(proxy)?;
+        }
+        ("format", Value::String(format)) => {
+            crate::OutputFormat::from_str(format, true)
+                .map_err(|e| anyhow::anyhow!("Invalid output format {format}: {e}"))?;
+        }
+        ("cache", Value::String(backend)) => {
+            CacheBackend::from_str(backend)
+                .with_context(|| format!("Invalid cache backend {backend}"))?;
+        }
+        ("time", Value::String(style)) => {
+            TimeStyle::from_str(style).with_context(|| format!("Invalid time style {style}"))?;
+        }
+        ("timezone", Value::String(timezone)) => {
+            Timezone::from_str(timezone)?;
+        }
+        ("date-format", Value::String(pattern)) => {
+            time::parse_date_format(pattern)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Use the values of the configuration file as defaults of the matching flags
+///
+/// Defaults apply only when neither the flag nor its environment variable is
+/// set, so the configuration file has the lowest precedence.
+pub fn with_defaults(command: Command, config: &Config) -> Command {
+    let defaults: Vec<(&str, String)> = FLAG_KEYS
+        .into_iter()
+        .filter_map(|key| config.get(key).map(|value| (key, value.to_string())))
+        .collect();
+    apply_defaults(command, &defaults)
+}
+
+fn apply_defaults(command: Command, defaults: &[(&str, String)]) -> Command {
+    command
+        .mut_args(|arg| {
+            match defaults
+                .iter()
+                .find(|(flag, _)| arg.get_long() == Some(flag))
+            {
+                Some((_, value)) => arg.default_value(value.clone()),
+                None => arg,
+            }
+        })
+        .mut_subcommands(|subcommand| apply_defaults(subcommand, defaults))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+
+    #[test]
+    fn test_with_defaults() {
+        let mut config = Config::default();
+        config.set("timeout", Value::Integer(42)).unwrap();
+        config
+            .set("time", Value::String("absolute".into()))
+            .unwrap();
+        let command = with_defaults(crate::Cli::command(), &config);
+
+        let matches = command
+            .clone()
+            .try_get_matches_from(["gitsmith", "list"])
+            .unwrap();
+        let cli = crate::Cli::from_arg_matches(&matches).unwrap();
+        assert_eq!(cli.time, TimeStyle::Absolute);
+        let (_, list) = matches.subcommand().unwrap();
+        assert_eq!(list.get_one::<u64>("timeout"), Some(&42));
+
+        // Flags still take precedence
+        let matches = command
+            .try_get_matches_from(["gitsmith", "list", "--timeout", "5"])
+            .unwrap();
+        let (_, list) = matches.subcommand().unwrap();
+        assert_eq!(list.get_one::<u64>("timeout"), Some(&5));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate("format", &Value::String("json".into())).is_ok());
+        assert!(validate("format", &Value::String("yaml".into())).is_err());
+        assert!(validate("timezone", &Value::String("Europe/Berlin".into())).is_ok());
+        assert!(validate("timeout", &Value::Integer(30)).is_ok());
+        assert!(validate("relays", &Value::List(vec!["not a url".into()])).is_err());
+        assert!(validate("account", &Value::String("npub1nope".into())).is_err());
+    }
+}
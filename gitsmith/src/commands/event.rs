@@ -0,0 +1,149 @@
+use anyhow::{Result, ensure};
+use clap::{Args, Subcommand};
+use gitsmith_core::{
+    detect_from_git,
+    inspect::{self, Holding},
+    relay,
+};
+use nostr_sdk::nips::nip19::ToBech32;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::{default_relays, format_timestamp, network, print_json};
+
+#[derive(Subcommand)]
+pub enum EventCommands {
+    /// Fetch an event from relays and show its tags, content, signature and which relays hold it
+    Inspect(InspectArgs),
+}
+
+#[derive(Args)]
+pub struct InspectArgs {
+    /// Event ID (hex, note or nevent) or address (naddr)
+    pub event: String,
+
+    /// Additional relays to ask (the repository's relays and the reference's hints are asked too)
+    #[arg(long = "relay", value_delimiter = ',')]
+    pub relays: Vec<String>,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Timeout in seconds
+    #[arg(long, default_value = "10")]
+    pub timeout: u64,
+
+    /// Output as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+pub async fn handle_event_command(command: EventCommands) -> Result<()> {
+    match command {
+        EventCommands::Inspect(args) => handle_inspect_command(args).await,
+    }
+}
+
+async fn handle_inspect_command(args: InspectArgs) -> Result<()> {
+    let (reference, hints) = inspect::parse_event_reference(&args.event)?;
+
+    // Outside a repository the configured relays stand in for the repository's
+    let repo_relays = detect_from_git(&args.repo_path)
+        .map(|announcement| announcement.relays)
+        .ok()
+        .filter(|relays| !relays.is_empty())
+        .unwrap_or_else(default_relays);
+    let mut relays: Vec<String> = Vec::new();
+    for url in repo_relays.into_iter().chain(hints).chain(args.relays) {
+        if !relays.iter().any(|known| relay::same_relay(known, &url)) {
+            relays.push(url);
+        }
+    }
+    ensure!(
+        !relays.is_empty(),
+        "No relays to ask. Run inside a repository or pass --relay"
+    );
+
+    eprintln!("Asking {count} relay(s)...", count = relays.len());
+    let inspection = inspect::inspect_event(
+        network(),
+        &reference,
+        &relays,
+        Duration::from_secs(args.timeout),
+    )
+    .await?;
+
+    if args.json || super::json_output() {
+        return print_json(&inspection);
+    }
+
+    match &inspection.event {
+        Some(event) => {
+            let kind = event.kind.as_u16();
+            match inspect::kind_name(event.kind) {
+                Some(name) => println!("Kind:      {kind} ({name})"),
+                None => println!("Kind:      {kind}"),
+            }
+            println!("ID:        {id}", id = event.id);
+            println!(
+                "Author:    {author}",
+                author = event
+                    .pubkey
+                    .to_bech32()
+                    .unwrap_or_else(|_| event.pubkey.to_hex())
+            );
+            println!(
+                "Created:   {created}",
+                created = format_timestamp(event.created_at.as_u64())
+            );
+            let check = |valid: bool| if valid { "✅ valid" } else { "❌ INVALID" };
+            println!("ID hash:   {id}", id = check(inspection.id_valid));
+            println!(
+                "Signature: {signature}",
+                signature = check(inspection.signature_valid)
+            );
+
+            println!();
+            println!("Tags ({count}):", count = event.tags.len());
+            for tag in event.tags.iter() {
+                println!("  {tag:?}", tag = tag.as_slice());
+            }
+
+            println!();
+            if event.content.is_empty() {
+                println!("Content: (empty)");
+            } else {
+                println!("Content:");
+                for line in event.content.lines() {
+                    println!("  {line}");
+                }
+            }
+        }
+        None => println!("❌ No relay holds the event"),
+    }
+
+    println!();
+    println!("Relays:");
+    let width = inspection
+        .relays
+        .iter()
+        .map(|r| r.url.len())
+        .max()
+        .unwrap_or(0);
+    for holding in &inspection.relays {
+        let status = match holding.holding {
+            Holding::Holds => "✅ holds it",
+            Holding::Outdated => "⚠️  older version",
+            Holding::Missing => "❌ missing",
+            Holding::Unreachable => "❌ unreachable",
+        };
+        print!("  {url:<width$}  {status}", url = holding.url);
+        if let Some(error) = &holding.error {
+            print!(" ({error})");
+        }
+        println!();
+    }
+
+    Ok(())
+}
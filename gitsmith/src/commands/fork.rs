@@ -0,0 +1,306 @@
+use anyhow::{Context, Result, bail, ensure};
+use clap::Args;
+use gitsmith_core::{
+    PublishConfig, RepoAnnouncement, announce_repository, grasp, license, relay, remote_helper,
+    repo, update_git_config_full,
+};
+use nostr_sdk::{PublicKey, ToBech32};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::{default_relays, network, print_json, repo_signer};
+
+/// Git remote fetching from the upstream repository
+const UPSTREAM_REMOTE: &str = "upstream";
+
+#[derive(Args, Default)]
+pub struct ForkArgs {
+    /// Upstream repository address (naddr or nostr://<npub>/<relay>/<identifier>)
+    pub url: String,
+
+    /// Identifier of the fork (defaults to the upstream identifier)
+    #[arg(long)]
+    pub identifier: Option<String>,
+
+    /// Git URL the fork is cloned from (can be repeated)
+    #[arg(long = "clone-url")]
+    pub clone_urls: Vec<String>,
+
+    /// GRASP server hosting the fork's git data (can be repeated)
+    #[arg(long = "grasp-server")]
+    pub grasp_servers: Vec<String>,
+
+    /// License of the fork (SPDX expression, defaults to the upstream's)
+    #[arg(long)]
+    pub license: Option<String>,
+
+    /// Relay to announce the fork on (can be repeated, defaults to the upstream relays)
+    #[arg(long = "relay", short = 'r')]
+    pub relays: Vec<String>,
+
+    /// Show the fork announcement without publishing it
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+/// `fork` result with `--format json`
+#[derive(Serialize)]
+struct ForkOutput {
+    identifier: String,
+    nostr_url: String,
+    upstream: String,
+}
+
+pub async fn handle_fork_command(args: ForkArgs) -> Result<()> {
+    let mut upstream = repo::parse_repository_address(&args.url)?;
+    if upstream.relays.is_empty() {
+        upstream.relays = default_relays();
+    }
+    ensure!(
+        !upstream.relays.is_empty(),
+        "No relay in {url}. Pass one in the address",
+        url = args.url
+    );
+
+    eprintln!(
+        "Fetching announcement for {identifier} from {count} relay(s)...",
+        identifier = upstream.identifier,
+        count = upstream.relays.len()
+    );
+    let Some(announced) = repo::fetch_announcement(
+        network(),
+        &upstream.coordinate(),
+        &upstream.relays,
+        Duration::from_secs(10),
+    )
+    .await?
+    else {
+        bail!(
+            "Repository {identifier} not found on any relay",
+            identifier = upstream.identifier
+        );
+    };
+    super::warn_identity_mismatch(&args.repo_path, &announced);
+    // PRs later go to the relays the upstream announcement lists
+    for relay_url in &announced.relays {
+        if !upstream
+            .relays
+            .iter()
+            .any(|known| relay::same_relay(known, relay_url))
+        {
+            upstream.relays.push(relay_url.clone());
+        }
+    }
+
+    let signer = repo_signer(&args.repo_path, args.password.clone())?;
+    let owner = signer.public_key();
+    let fork = fork_announcement(&announced, &args)?;
+    if let Some(conflict) =
+        license::license_conflict(announced.license.as_deref(), fork.license.as_deref())
+    {
+        eprintln!("⚠️  License conflict: {conflict}");
+    }
+    if owner.to_hex() == upstream.owner && fork.identifier == announced.identifier {
+        bail!(
+            "This would replace your own announcement, pass --identifier to fork it under another name"
+        );
+    }
+    if fork.clone_urls.is_empty() && fork.grasp_servers.is_empty() {
+        eprintln!(
+            "Warning: The fork has no --clone-url or --grasp-server, others can only read the PRs you send"
+        );
+    }
+    if args.dry_run {
+        if super::json_output() {
+            return print_json(&fork);
+        }
+        eprintln!(
+            "Would announce {identifier} on {relays}",
+            identifier = fork.identifier,
+            relays = fork.relays.join(", ")
+        );
+        return Ok(());
+    }
+
+    let config = PublishConfig {
+        timeout_secs: 30,
+        wait_for_send: true,
+    };
+    let result = announce_repository(network(), fork.clone(), signer.as_ref(), config)
+        .await
+        .context("Failed to announce fork")?;
+    if result.successes.is_empty() {
+        bail!("Failed to announce fork to any relay");
+    }
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+
+    update_git_config_full(
+        &args.repo_path,
+        &fork,
+        &result.nostr_url,
+        &owner.to_bech32()?,
+    )
+    .context("Failed to save nostr configuration")?;
+    repo::set_upstream(&args.repo_path, &upstream).context("Failed to record the upstream")?;
+    add_upstream_remote(&args.repo_path, &announced, &upstream.owner)?;
+
+    eprintln!(
+        "✅ Forked {name} as {identifier}",
+        name = announced.name,
+        identifier = fork.identifier
+    );
+    eprintln!(
+        "   'gitsmith send' now opens PRs on the upstream repository, pass --to-fork for your fork"
+    );
+    if super::json_output() {
+        return print_json(&ForkOutput {
+            identifier: fork.identifier,
+            nostr_url: result.nostr_url,
+            upstream: upstream.to_url()?,
+        });
+    }
+
+    Ok(())
+}
+
+/// The user's announcement of a fork of `upstream`
+fn fork_announcement(upstream: &RepoAnnouncement, args: &ForkArgs) -> Result<RepoAnnouncement> {
+    let grasp_servers = args
+        .grasp_servers
+        .iter()
+        .map(|server| grasp::normalize_server(server))
+        .collect::<gitsmith_core::Result<Vec<_>>>()?;
+    let mut relays = if args.relays.is_empty() {
+        upstream.relays.clone()
+    } else {
+        args.relays.clone()
+    };
+    // The announcement, and later events, go to the GRASP servers too
+    for server in &grasp_servers {
+        let relay_url = grasp::relay_url(server);
+        if !relays
+            .iter()
+            .any(|known| relay::same_relay(known, &relay_url))
+        {
+            relays.push(relay_url);
+        }
+    }
+
+    let identifier = args
+        .identifier
+        .clone()
+        .unwrap_or_else(|| upstream.identifier.clone());
+    ensure!(
+        !identifier.contains(' ') && !identifier.contains('/'),
+        "Identifier must not contain spaces or slashes"
+    );
+    if let Some(license) = &args.license {
+        license::validate_license(license)?;
+    }
+
+    Ok(RepoAnnouncement {
+        identifier,
+        name: upstream.name.clone(),
+        description: upstream.description.clone(),
+        clone_urls: args.clone_urls.clone(),
+        relays,
+        web: vec![],
+        root_commit: upstream.root_commit.clone(),
+        maintainers: vec![],
+        ci_keys: vec![],
+        grasp_servers,
+        blossom_servers: vec![],
+        min_pow: 0,
+        license: args.license.clone().or_else(|| upstream.license.clone()),
+        topics: upstream.topics.clone(),
+        readme: upstream.readme.clone(),
+    })
+}
+
+/// Add an `upstream` remote fetching from the upstream repository, unless one exists
+fn add_upstream_remote(
+    repo_path: &Path,
+    announced: &RepoAnnouncement,
+    upstream_owner: &str,
+) -> Result<()> {
+    let git = git2::Repository::open(repo_path)
+        .with_context(|| format!("Failed to open {path}", path = repo_path.display()))?;
+    if git.find_remote(UPSTREAM_REMOTE).is_ok() {
+        return Ok(());
+    }
+    let owner = PublicKey::from_hex(upstream_owner)?;
+    let fetch_urls = remote_helper::fetch_urls(announced, &owner)?;
+    if let Some(url) = repo::preferred_clone_url(&fetch_urls) {
+        git.remote(UPSTREAM_REMOTE, url)
+            .context("Failed to add the upstream remote")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fork_announcement() {
+        let upstream = RepoAnnouncement {
+            identifier: "repo".to_string(),
+            name: "Repo".to_string(),
+            description: "A repository".to_string(),
+            clone_urls: vec!["https://example.com/repo.git".to_string()],
+            relays: vec!["wss://relay.example.com".to_string()],
+            web: vec!["https://example.com/repo".to_string()],
+            root_commit: "abc123".to_string(),
+            maintainers: vec![],
+            ci_keys: vec![],
+            grasp_servers: vec![],
+            blossom_servers: vec![],
+            min_pow: 8,
+            license: Some("MIT".to_string()),
+            topics: vec!["rust".to_string()],
+            readme: None,
+        };
+
+        let args = ForkArgs {
+            clone_urls: vec!["https://example.org/fork.git".to_string()],
+            grasp_servers: vec!["grasp.example.org".to_string()],
+            ..Default::default()
+        };
+        let fork = fork_announcement(&upstream, &args).unwrap();
+        assert_eq!(fork.identifier, "repo");
+        assert_eq!(fork.root_commit, "abc123");
+        assert_eq!(fork.clone_urls, ["https://example.org/fork.git"]);
+        assert_eq!(fork.grasp_servers, ["https://grasp.example.org"]);
+        assert_eq!(
+            fork.relays,
+            ["wss://relay.example.com", "wss://grasp.example.org"]
+        );
+        assert!(fork.web.is_empty());
+        assert_eq!(fork.min_pow, 0);
+        assert_eq!(fork.license.as_deref(), Some("MIT"));
+
+        let args = ForkArgs {
+            license: Some("Apache-2.0".to_string()),
+            ..Default::default()
+        };
+        let fork = fork_announcement(&upstream, &args).unwrap();
+        assert_eq!(fork.license.as_deref(), Some("Apache-2.0"));
+
+        let args = ForkArgs {
+            identifier: Some("my repo".to_string()),
+            ..Default::default()
+        };
+        assert!(fork_announcement(&upstream, &args).is_err());
+    }
+}
@@ -0,0 +1,255 @@
+use anyhow::{Context, Result, bail};
+use clap::{Args, Subcommand};
+use gitsmith_core::{
+    detect_from_git, issues,
+    pull_request::{self, ListOptions},
+    seen::{self, ReadState},
+};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::{network, repo_coordinate};
+
+#[derive(Args)]
+pub struct InboxArgs {
+    #[command(subcommand)]
+    pub command: Option<InboxCommands>,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Only show unread items
+    #[arg(long)]
+    pub unread: bool,
+
+    /// Output as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Subcommand)]
+pub enum InboxCommands {
+    /// Mark pull requests, issues or comments as read
+    MarkRead(MarkReadArgs),
+    /// Mark pull requests, issues or comments as unread again
+    MarkUnread(MarkUnreadArgs),
+}
+
+#[derive(Args)]
+pub struct MarkReadArgs {
+    /// Event IDs (or the short IDs shown by the inbox) to mark as read
+    #[arg(required_unless_present = "all")]
+    pub event_ids: Vec<String>,
+
+    /// Mark every pull request and issue of the repository as read
+    #[arg(long, conflicts_with = "event_ids")]
+    pub all: bool,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+}
+
+#[derive(Args)]
+pub struct MarkUnreadArgs {
+    /// Event IDs (or the short IDs shown by the inbox) to mark as unread
+    #[arg(required = true)]
+    pub event_ids: Vec<String>,
+}
+
+/// A pull request or issue in the inbox
+#[derive(Debug, Serialize)]
+struct InboxItem {
+    kind: &'static str,
+    id: String,
+    title: String,
+    author: String,
+    status: String,
+    created_at: u64,
+    comments: usize,
+    read_state: ReadState,
+}
+
+/// `inbox mark-read` and `mark-unread` result with `--format json`
+#[derive(Serialize)]
+struct MarkedOutput {
+    marked: Vec<String>,
+}
+
+pub async fn handle_inbox_command(args: InboxArgs) -> Result<()> {
+    match args.command {
+        Some(InboxCommands::MarkRead(mark_args)) => handle_mark_read_command(mark_args).await,
+        Some(InboxCommands::MarkUnread(mark_args)) => handle_mark_unread_command(mark_args),
+        None => {
+            handle_show_inbox(
+                &args.repo_path,
+                args.unread,
+                args.json || super::json_output(),
+            )
+            .await
+        }
+    }
+}
+
+/// Fetch the pull requests and issues of a repository, newest first
+async fn fetch_inbox(repo_path: &Path) -> Result<Vec<InboxItem>> {
+    let repo_announcement = detect_from_git(repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let coordinate = repo_coordinate(repo_path, &repo_announcement.identifier)?;
+    let relays = repo_announcement.relays;
+    eprintln!(
+        "Fetching pull requests and issues from {count} relay(s)...",
+        count = relays.len()
+    );
+
+    let options = ListOptions {
+        min_pow: repo_announcement.min_pow,
+        ..ListOptions::default()
+    };
+    let prs = pull_request::list_pull_requests_with_options(
+        network(),
+        &coordinate,
+        relays.clone(),
+        &options,
+    )
+    .await?;
+    let issues =
+        issues::list_issues(network(), &coordinate, &relays, Duration::from_secs(5)).await?;
+
+    let store = seen::load()?;
+    let mut items: Vec<InboxItem> = prs
+        .into_iter()
+        .map(|pr| InboxItem {
+            kind: "pr",
+            read_state: store.read_state(&pr.id, pr.comments_count),
+            id: pr.id,
+            title: pr.title,
+            author: pr.author,
+            status: pr.status.to_string(),
+            created_at: pr.created_at,
+            comments: pr.comments_count,
+        })
+        .chain(issues.into_iter().map(|issue| InboxItem {
+            kind: "issue",
+            read_state: store.read_state(&issue.id, issue.comments_count),
+            id: issue.id,
+            title: issue.subject,
+            author: issue.author,
+            status: issue.status.to_string(),
+            created_at: issue.created_at,
+            comments: issue.comments_count,
+        }))
+        .collect();
+    items.sort_by_key(|item| std::cmp::Reverse(item.created_at));
+
+    Ok(items)
+}
+
+async fn handle_show_inbox(repo_path: &Path, unread_only: bool, json: bool) -> Result<()> {
+    let mut items = fetch_inbox(repo_path).await?;
+    let unread = items
+        .iter()
+        .filter(|item| item.read_state.is_unread())
+        .count();
+    if unread_only {
+        items.retain(|item| item.read_state.is_unread());
+    }
+
+    if json {
+        super::print_json(&items)?;
+        return Ok(());
+    }
+
+    if items.is_empty() {
+        eprintln!("Inbox is empty");
+        return Ok(());
+    }
+
+    eprintln!("\n{count} item(s), {unread} unread:\n", count = items.len());
+    for item in &items {
+        println!(
+            "{badge:<20} {kind:<5} {id}  {title} [{status}]",
+            badge = item.read_state.badge(),
+            kind = item.kind,
+            id = &item.id[..8.min(item.id.len())],
+            title = item.title,
+            status = item.status
+        );
+    }
+    eprintln!("\nMark items as read with 'gitsmith inbox mark-read <id>...' or '--all'");
+
+    Ok(())
+}
+
+/// Resolve a full or short event ID (as printed by the inbox) among `ids`
+fn resolve_id<'a>(ids: impl Iterator<Item = &'a str>, event_id: &str) -> Result<&'a str> {
+    let matches: Vec<&str> = ids.filter(|id| id.starts_with(event_id)).collect();
+    match matches.as_slice() {
+        [id] => Ok(id),
+        [] => bail!("No item matches {event_id}"),
+        _ => bail!(
+            "{event_id} is ambiguous, it matches {count} items",
+            count = matches.len()
+        ),
+    }
+}
+
+async fn handle_mark_read_command(args: MarkReadArgs) -> Result<()> {
+    // Remember the current comment counts so only later comments show up as new
+    let items = fetch_inbox(&args.repo_path).await?;
+
+    let events: Vec<(String, usize)> = if args.all {
+        items
+            .iter()
+            .map(|item| (item.id.clone(), item.comments))
+            .collect()
+    } else {
+        args.event_ids
+            .iter()
+            .map(|event_id| {
+                let id = resolve_id(items.iter().map(|item| item.id.as_str()), event_id)?;
+                let item = items.iter().find(|item| item.id == id);
+                Ok((id.to_string(), item.map_or(0, |item| item.comments)))
+            })
+            .collect::<Result<_>>()?
+    };
+
+    seen::mark_read(&events)?;
+    eprintln!("✅ Marked {count} item(s) as read", count = events.len());
+    if super::json_output() {
+        return super::print_json(&MarkedOutput {
+            marked: events.into_iter().map(|(event_id, _)| event_id).collect(),
+        });
+    }
+
+    Ok(())
+}
+
+fn handle_mark_unread_command(args: MarkUnreadArgs) -> Result<()> {
+    let path = seen::get_seen_storage_path()?;
+    let mut store = seen::SeenStore::load(&path)?;
+
+    let marked = args
+        .event_ids
+        .iter()
+        .map(|event_id| resolve_id(store.events.keys().map(String::as_str), event_id))
+        .map(|id| id.map(str::to_string))
+        .collect::<Result<Vec<_>>>()?;
+    for event_id in &marked {
+        store.mark_unread(event_id);
+    }
+    store.save(&path)?;
+    eprintln!("✅ Marked {count} item(s) as unread", count = marked.len());
+    if super::json_output() {
+        return super::print_json(&MarkedOutput { marked });
+    }
+
+    Ok(())
+}
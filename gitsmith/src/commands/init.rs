@@ -0,0 +1,286 @@
+use anyhow::{Context, Result, bail, ensure};
+use gitsmith_core::{RepoAnnouncement, account, detect_from_git, events, relay};
+use nostr_sdk::{Keys, PublicKey, RelayUrl};
+use rpassword::read_password;
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::{accounts, default_relays};
+
+/// Well-known relays the wizard offers next to the configured ones
+const SUGGESTED_RELAYS: [&str; 5] = [
+    "wss://relay.damus.io",
+    "wss://nos.lol",
+    "wss://relay.nostr.band",
+    "wss://relay.primal.net",
+    "wss://nostr.mom",
+];
+
+/// Values `gitsmith init` needs, from flags or asked on the terminal
+pub struct InitAnswers {
+    pub identifier: Option<String>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub relays: Vec<String>,
+    pub private_key: Option<String>,
+    pub account: Option<String>,
+    /// Sign with the active account, chosen in the wizard
+    pub active_account: bool,
+}
+
+impl InitAnswers {
+    /// Flags that are still missing
+    pub fn missing(&self) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        if self.identifier.is_none() {
+            missing.push("--identifier");
+        }
+        if self.name.is_none() {
+            missing.push("--name");
+        }
+        if self.description.is_none() {
+            missing.push("--description");
+        }
+        if self.relays.is_empty() {
+            missing.push("--relay");
+        }
+        if self.private_key.is_none() && self.account.is_none() && !self.active_account {
+            missing.push("--nsec or --account");
+        }
+        missing
+    }
+
+    /// Ask for every missing value, checking each answer before moving on
+    pub fn prompt(&mut self, repo_path: &Path) -> Result<()> {
+        eprintln!("Announcing a repository on Nostr, press Enter to take the [default]");
+        eprintln!();
+
+        if self.identifier.is_none() {
+            let suggestion = suggested_identifier(repo_path);
+            self.identifier = Some(ask_until(
+                "Identifier (unique, no spaces)",
+                suggestion.as_deref(),
+                |answer| {
+                    ensure!(!answer.is_empty(), "The identifier can't be empty");
+                    ensure!(
+                        !answer.contains(' ') && !answer.contains('/'),
+                        "Identifier must not contain spaces or slashes"
+                    );
+                    Ok(())
+                },
+            )?);
+        }
+        if self.name.is_none() {
+            let identifier = self.identifier.clone();
+            self.name = Some(ask_until("Name", identifier.as_deref(), |answer| {
+                ensure!(!answer.is_empty(), "The name can't be empty");
+                Ok(())
+            })?);
+        }
+        if self.description.is_none() {
+            self.description = Some(ask("Description (optional)", None)?);
+        }
+        if self.relays.is_empty() {
+            self.relays = prompt_relays()?;
+        }
+        if self.private_key.is_none() && self.account.is_none() {
+            self.prompt_signer()?;
+        }
+        Ok(())
+    }
+
+    /// Sign with the active account if the user agrees, otherwise with a pasted key
+    fn prompt_signer(&mut self) -> Result<()> {
+        if let Ok(active) = account::get_active_account(accounts())
+            && confirm(&format!(
+                "Sign with the active account {npub}?",
+                npub = active.npub
+            ))?
+        {
+            self.active_account = true;
+            return Ok(());
+        }
+
+        loop {
+            eprint!("Private key (nsec or hex, hidden): ");
+            io::stderr().flush()?;
+            let key = read_password()?;
+            match Keys::parse(key.trim()) {
+                Ok(_) => {
+                    self.private_key = Some(key.trim().to_string());
+                    return Ok(());
+                }
+                Err(e) => eprintln!("❌ Invalid private key: {e}"),
+            }
+        }
+    }
+}
+
+/// The identifier the repository already has, or one made from its directory's name
+fn suggested_identifier(repo_path: &Path) -> Option<String> {
+    if let Ok(announcement) = detect_from_git(repo_path) {
+        return Some(announcement.identifier);
+    }
+    let path = repo_path.canonicalize().ok()?;
+    let name = path.file_name()?.to_str()?;
+    Some(name.replace(' ', "-"))
+}
+
+/// Show the configured and well-known relays and let the user pick some or enter others
+fn prompt_relays() -> Result<Vec<String>> {
+    let configured = default_relays();
+    let mut candidates = configured.clone();
+    for url in SUGGESTED_RELAYS {
+        if !candidates.iter().any(|known| relay::same_relay(known, url)) {
+            candidates.push(url.to_string());
+        }
+    }
+
+    eprintln!("Relays to announce on:");
+    for (index, url) in candidates.iter().enumerate() {
+        let note = if configured.contains(url) {
+            "  (configured)"
+        } else {
+            ""
+        };
+        eprintln!("  {number}) {url}{note}", number = index + 1);
+    }
+    let selected = if configured.is_empty() {
+        2
+    } else {
+        configured.len()
+    };
+    let default: Vec<String> = (1..=selected).map(|n| n.to_string()).collect();
+    let answer = ask_until(
+        "Numbers and/or relay URLs, comma separated",
+        Some(&default.join(",")),
+        |answer| parse_relay_selection(answer, &candidates).map(|_| ()),
+    )?;
+    parse_relay_selection(&answer, &candidates)
+}
+
+/// Relays picked by number from the candidates or entered as URLs
+fn parse_relay_selection(answer: &str, candidates: &[String]) -> Result<Vec<String>> {
+    let mut relays: Vec<String> = Vec::new();
+    for item in answer
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+    {
+        let url = match item.parse::<usize>() {
+            Ok(number) => candidates
+                .get(number.wrapping_sub(1))
+                .cloned()
+                .with_context(|| format!("No relay number {number}"))?,
+            Err(_) => {
+                RelayUrl::parse(item).with_context(|| format!("Invalid relay URL {item}"))?;
+                item.to_string()
+            }
+        };
+        if !relays.iter().any(|known| relay::same_relay(known, &url)) {
+            relays.push(url);
+        }
+    }
+    ensure!(!relays.is_empty(), "Pick at least one relay");
+    Ok(relays)
+}
+
+/// Print the announcement as it will be signed
+pub fn preview(announcement: &RepoAnnouncement, public_key: PublicKey) -> Result<()> {
+    let event = events::announcement_builder(announcement, &public_key)?.build(public_key);
+    eprintln!();
+    eprintln!("Announcement to publish (kind {kind}):", kind = event.kind);
+    for tag in event.tags.iter() {
+        eprintln!("  {tag:?}", tag = tag.as_slice());
+    }
+    eprintln!();
+    Ok(())
+}
+
+/// Ask a yes/no question, yes by default
+pub fn confirm(question: &str) -> Result<bool> {
+    let answer = ask(&format!("{question} [Y/n]"), None)?;
+    Ok(matches!(answer.to_lowercase().as_str(), "" | "y" | "yes"))
+}
+
+fn ask(prompt: &str, default: Option<&str>) -> Result<String> {
+    match default {
+        Some(default) => eprint!("{prompt} [{default}]: "),
+        None => eprint!("{prompt}: "),
+    }
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer)? == 0 {
+        bail!("Aborted, nothing was published");
+    }
+    let answer = answer.trim();
+    Ok(match default {
+        Some(default) if answer.is_empty() => default.to_string(),
+        _ => answer.to_string(),
+    })
+}
+
+/// Ask again until the answer passes `validate`
+fn ask_until(
+    prompt: &str,
+    default: Option<&str>,
+    validate: impl Fn(&str) -> Result<()>,
+) -> Result<String> {
+    loop {
+        let answer = ask(prompt, default)?;
+        match validate(&answer) {
+            Ok(()) => return Ok(answer),
+            Err(e) => eprintln!("❌ {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_relay_selection() {
+        let candidates = vec![
+            "wss://relay.damus.io".to_string(),
+            "wss://nos.lol".to_string(),
+        ];
+
+        assert_eq!(
+            parse_relay_selection("2, wss://relay.example.com", &candidates).unwrap(),
+            vec!["wss://nos.lol", "wss://relay.example.com"]
+        );
+        // Duplicates are dropped
+        assert_eq!(
+            parse_relay_selection("1,wss://relay.damus.io/", &candidates).unwrap(),
+            vec!["wss://relay.damus.io"]
+        );
+        assert!(parse_relay_selection("3", &candidates).is_err());
+        assert!(parse_relay_selection("0", &candidates).is_err());
+        assert!(parse_relay_selection("not a relay", &candidates).is_err());
+        assert!(parse_relay_selection(" , ", &candidates).is_err());
+    }
+
+    #[test]
+    fn test_missing() {
+        let mut answers = InitAnswers {
+            identifier: Some("repo".to_string()),
+            name: None,
+            description: None,
+            relays: vec![],
+            private_key: None,
+            account: None,
+            active_account: false,
+        };
+        assert_eq!(
+            answers.missing(),
+            vec!["--name", "--description", "--relay", "--nsec or --account"]
+        );
+
+        answers.name = Some("Repo".to_string());
+        answers.description = Some(String::new());
+        answers.relays = vec!["wss://nos.lol".to_string()];
+        answers.active_account = true;
+        assert!(answers.missing().is_empty());
+    }
+}
@@ -0,0 +1,244 @@
+use anyhow::{Context, Result, bail};
+use clap::{Args, Subcommand};
+use gitsmith_core::{
+    comments, detect_from_git,
+    issues::{self, IssueStatus},
+    seen,
+    types::BroadcastResult,
+};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::{network, repo_coordinate, repo_signer, time_format};
+
+#[derive(Subcommand)]
+pub enum IssueCommands {
+    /// Open a new issue for the repository
+    Create(CreateArgs),
+
+    /// List issues for the repository
+    List(ListArgs),
+
+    /// Show an issue with its comments
+    Show(ShowArgs),
+
+    /// Close an issue
+    Close(CloseArgs),
+}
+
+#[derive(Args)]
+pub struct CreateArgs {
+    /// Issue subject
+    #[arg(long, short = 't')]
+    pub title: String,
+
+    /// Issue description (markdown)
+    #[arg(long, short = 'b', default_value = "")]
+    pub body: String,
+
+    /// Label to attach (can be repeated)
+    #[arg(long = "label", short = 'l')]
+    pub labels: Vec<String>,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ListArgs {
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Output as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Only list issues with this label (can be repeated, issues must have them all)
+    #[arg(long = "label", short = 'l')]
+    pub labels: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct ShowArgs {
+    /// Issue event ID
+    pub event_id: String,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Output as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct CloseArgs {
+    /// Issue event ID
+    pub event_id: String,
+
+    /// Optional message explaining why the issue is closed
+    #[arg(long, short = 'm', default_value = "")]
+    pub message: String,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+pub async fn handle_issue_command(command: IssueCommands) -> Result<()> {
+    match command {
+        IssueCommands::Create(args) => handle_create_command(args).await,
+        IssueCommands::List(args) => handle_list_command(args).await,
+        IssueCommands::Show(args) => handle_show_command(args).await,
+        IssueCommands::Close(args) => handle_close_command(args).await,
+    }
+}
+
+/// Repository coordinate and relays for the repository at `repo_path`
+fn repository_context(repo_path: &Path) -> Result<(String, Vec<String>)> {
+    let repo_announcement = detect_from_git(repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let coordinate = repo_coordinate(repo_path, &repo_announcement.identifier)?;
+    Ok((coordinate, repo_announcement.relays))
+}
+
+fn report_broadcast(result: &BroadcastResult, what: &str) -> Result<()> {
+    if result.successes.is_empty() {
+        bail!("Failed to publish {what} to any relay");
+    }
+
+    eprintln!(
+        "✅ {what} published to {count} relay(s)",
+        count = result.successes.len()
+    );
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+
+    Ok(())
+}
+
+async fn handle_create_command(args: CreateArgs) -> Result<()> {
+    let (coordinate, relays) = repository_context(&args.repo_path)?;
+    let signer = repo_signer(&args.repo_path, args.password)?;
+
+    let result = issues::create_issue(
+        network(),
+        signer.as_ref(),
+        &coordinate,
+        &relays,
+        &args.title,
+        &args.body,
+        &args.labels,
+    )
+    .await?;
+
+    report_broadcast(&result, "Issue")?;
+    if super::json_output() {
+        return super::print_json(&result);
+    }
+    for event_id in &result.event_ids {
+        println!("{event_id}");
+    }
+
+    Ok(())
+}
+
+async fn handle_list_command(args: ListArgs) -> Result<()> {
+    let (coordinate, relays) = repository_context(&args.repo_path)?;
+
+    eprintln!(
+        "Fetching issues from {count} relay(s)...",
+        count = relays.len()
+    );
+    let mut issues =
+        issues::list_issues(network(), &coordinate, &relays, Duration::from_secs(5)).await?;
+    issues.retain(|issue| super::has_labels(&issue.labels, &args.labels));
+
+    if args.json || super::json_output() {
+        super::print_json(&issues)?;
+    } else if issues.is_empty() {
+        eprintln!("No issues found");
+    } else {
+        let store = seen::load()?;
+        eprintln!("\nFound {count} issue(s):\n", count = issues.len());
+        eprintln!("{:-<80}", "");
+
+        for (i, issue) in issues.iter().enumerate() {
+            let badge = store.read_state(&issue.id, issue.comments_count).badge();
+            eprintln!("Issue #{num} {badge}", num = i + 1);
+            eprintln!("{issue_output}", issue_output = issues::format_issue(issue));
+            eprintln!("{:-<80}", "");
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_show_command(args: ShowArgs) -> Result<()> {
+    let (coordinate, relays) = repository_context(&args.repo_path)?;
+
+    let thread = issues::fetch_issue(
+        network(),
+        &args.event_id,
+        &coordinate,
+        &relays,
+        Duration::from_secs(5),
+    )
+    .await?;
+    super::mark_viewed(&thread.issue.id, thread.comments.len());
+
+    if args.json || super::json_output() {
+        super::print_json(&thread)?;
+        return Ok(());
+    }
+
+    println!("{issue}", issue = issues::format_issue(&thread.issue));
+    if !thread.comments.is_empty() {
+        println!("{:-<80}", "");
+        print!(
+            "{comments}",
+            comments = comments::format_thread(&thread.comments, time_format())
+        );
+    }
+
+    Ok(())
+}
+
+async fn handle_close_command(args: CloseArgs) -> Result<()> {
+    let (coordinate, relays) = repository_context(&args.repo_path)?;
+    let signer = repo_signer(&args.repo_path, args.password)?;
+
+    let result = issues::set_issue_status(
+        network(),
+        signer.as_ref(),
+        &args.event_id,
+        IssueStatus::Closed,
+        &coordinate,
+        &relays,
+        &args.message,
+    )
+    .await?;
+
+    report_broadcast(&result, "Status")?;
+    if super::json_output() {
+        return super::print_json(&result);
+    }
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use anyhow::{Context, Result, bail};
+use clap::{Args, Subcommand};
+use gitsmith_core::{detect_from_git, github};
+use std::path::PathBuf;
+
+use super::{network, repo_coordinate, repo_signer};
+
+#[derive(Subcommand)]
+pub enum LinkCommands {
+    /// Attach a snapshot of a GitHub issue or pull request to a PR or issue
+    Github(GithubArgs),
+}
+
+#[derive(Args)]
+pub struct GithubArgs {
+    /// GitHub issue or pull request URL
+    pub url: String,
+
+    /// PR or issue event ID to attach the reference to
+    #[arg(long)]
+    pub to: String,
+
+    /// GitHub token, only needed for private repositories or to avoid rate limits
+    #[arg(long, env = "GITHUB_TOKEN")]
+    pub github_token: Option<String>,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+pub async fn handle_link_command(command: LinkCommands) -> Result<()> {
+    match command {
+        LinkCommands::Github(args) => handle_github_command(args).await,
+    }
+}
+
+async fn handle_github_command(args: GithubArgs) -> Result<()> {
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let reference = github::parse_github_url(&args.url)?;
+    eprintln!(
+        "Fetching {owner}/{repo}#{number} from GitHub...",
+        owner = reference.owner,
+        repo = reference.repo,
+        number = reference.number
+    );
+    let item = github::fetch_github_item(&reference, args.github_token.as_deref()).await?;
+    eprintln!("{title} ({state})", title = item.title, state = item.state);
+
+    let signer = repo_signer(&args.repo_path, args.password)?;
+    let coordinate = repo_coordinate(&args.repo_path, &repo_announcement.identifier)?;
+
+    let result = github::link_github_item(
+        network(),
+        signer.as_ref(),
+        &item,
+        &args.to,
+        &coordinate,
+        &repo_announcement.relays,
+    )
+    .await?;
+
+    if result.successes.is_empty() {
+        bail!("Failed to publish reference to any relay");
+    }
+
+    eprintln!(
+        "✅ Reference published to {count} relay(s)",
+        count = result.successes.len()
+    );
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+    if super::json_output() {
+        return super::print_json(&result);
+    }
+
+    Ok(())
+}
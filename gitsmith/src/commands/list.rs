@@ -1,7 +1,39 @@
 use anyhow::{Context, Result};
-use clap::Args;
-use gitsmith_core::{account, detect_from_git, get_repo_owner, pull_request};
+use clap::{Args, ValueEnum};
+use gitsmith_core::{
+    detect_from_git, profiles,
+    pull_request::{self, Cursor, ListOptions, PullRequestSort},
+    repo, seen, time,
+};
+use nostr_sdk::{PublicKey, Timestamp};
 use std::path::PathBuf;
+use std::time::Duration;
+use tracing::warn;
+
+use super::pr::StatusValue;
+use super::{network, repo_coordinate, time_format};
+
+/// Order of the listed PRs
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum SortValue {
+    /// Most recently opened first
+    #[default]
+    Newest,
+    /// Least recently opened first
+    Oldest,
+    /// Most recently revised first
+    Updated,
+}
+
+impl From<SortValue> for PullRequestSort {
+    fn from(value: SortValue) -> Self {
+        match value {
+            SortValue::Newest => PullRequestSort::Newest,
+            SortValue::Oldest => PullRequestSort::Oldest,
+            SortValue::Updated => PullRequestSort::Updated,
+        }
+    }
+}
 
 #[derive(Args)]
 pub struct ListArgs {
@@ -12,6 +44,58 @@ pub struct ListArgs {
     /// Output as JSON
     #[arg(long)]
     pub json: bool,
+
+    /// Include PRs and comments below the repository's proof-of-work requirement
+    #[arg(long)]
+    pub include_low_pow: bool,
+
+    /// Only look on the repository's relays, not on the authors' NIP-65 relays
+    #[arg(long)]
+    pub no_outbox: bool,
+
+    /// Only list PRs with this status, open includes updated PRs
+    #[arg(long, value_enum)]
+    pub status: Option<StatusValue>,
+
+    /// Only list PRs opened by this author (npub or hex)
+    #[arg(long, value_parser = |value: &str| PublicKey::parse(value))]
+    pub author: Option<PublicKey>,
+
+    /// Only list PRs opened since a date (2024-06-01), RFC 3339 time, unix timestamp or age (2w)
+    #[arg(long)]
+    pub since: Option<String>,
+
+    /// PRs per page
+    #[arg(long, value_name = "N", default_value_t = pull_request::DEFAULT_PAGE_SIZE)]
+    pub limit: usize,
+
+    /// Continue after a previous page, with the token it printed
+    #[arg(long, value_name = "TOKEN", conflicts_with = "all")]
+    pub cursor: Option<Cursor>,
+
+    /// List every PR instead of a page, e.g. for a full export
+    #[arg(long, conflicts_with = "limit")]
+    pub all: bool,
+
+    /// Order of the listed PRs
+    #[arg(long, value_enum, default_value_t)]
+    pub sort: SortValue,
+
+    /// Only list PRs with this label (can be repeated, PRs must have them all)
+    #[arg(long = "label", short = 'l')]
+    pub labels: Vec<String>,
+
+    /// Only list what the local event cache holds, without contacting relays
+    #[arg(long, conflicts_with = "no_outbox")]
+    pub offline: bool,
+
+    /// Show open PRs whose patches are already in the local history as merged
+    #[arg(long, conflicts_with = "offline")]
+    pub detect_merged: bool,
+
+    /// Seconds to wait for slow relays, listing ends early once every relay sent EOSE
+    #[arg(long, default_value = "10", conflicts_with = "offline")]
+    pub timeout: u64,
 }
 
 pub async fn handle_list_command(args: ListArgs) -> Result<()> {
@@ -25,54 +109,116 @@ pub async fn handle_list_command(args: ListArgs) -> Result<()> {
         return Ok(());
     }
 
-    // Get the repository owner's public key
-    // First try to get it from the repo config (set during init)
-    // If not found, fall back to active account
-    let public_key = if let Some(owner) = get_repo_owner(&args.repo_path)? {
-        owner
-    } else {
-        // Fall back to active account if repo doesn't have owner saved
-        account::get_active_public_key().context(
-            "Repository owner not found in config and no active account. Please login first with 'gitsmith account login'",
-        )?
-    };
+    let repo_coordinate = repo_coordinate(&args.repo_path, &repo_announcement.identifier)?;
 
-    let repo_coordinate = format!(
-        "30617:{pubkey}:{identifier}",
-        pubkey = public_key,
-        identifier = repo_announcement.identifier
-    );
+    if args.offline {
+        eprintln!("Listing cached pull requests...");
+    } else {
+        eprintln!(
+            "Fetching pull requests from {count} relay(s)...",
+            count = repo_announcement.relays.len()
+        );
+    }
 
-    eprintln!(
-        "Fetching pull requests from {count} relay(s)...",
-        count = repo_announcement.relays.len()
-    );
+    // The coordinate may have been announced by an unrelated project
+    let announced = if args.offline {
+        network()
+            .cache
+            .clone()
+            .map(|store| repo::cached_announcement(store.as_ref(), &repo_coordinate))
+            .transpose()
+            .map(Option::flatten)
+    } else {
+        repo::fetch_announcement(
+            network(),
+            &repo_coordinate,
+            &repo_announcement.relays,
+            Duration::from_secs(args.timeout),
+        )
+        .await
+    };
+    match announced {
+        Ok(Some(announced)) => super::warn_identity_mismatch(&args.repo_path, &announced),
+        Ok(None) => {}
+        Err(e) => warn!("Failed to fetch the repository announcement: {e}"),
+    }
 
     // List pull requests
-    let prs = pull_request::list_pull_requests(&repo_coordinate, repo_announcement.relays.clone())
-        .await?;
+    let options = ListOptions {
+        min_pow: if args.include_low_pow {
+            0
+        } else {
+            repo_announcement.min_pow
+        },
+        outbox: !args.no_outbox,
+        offline: args.offline,
+        timeout: Duration::from_secs(args.timeout),
+        author: args.author,
+        since: args
+            .since
+            .as_deref()
+            .map(|since| time::parse_since(since, Timestamp::now().as_u64()))
+            .transpose()?
+            .map(Timestamp::from),
+        limit: (!args.all).then_some(args.limit),
+        sort: args.sort.into(),
+        status: args.status.map(Into::into),
+        labels: args.labels,
+        cursor: args.cursor.clone(),
+        repo_path: args.detect_merged.then(|| args.repo_path.clone()),
+    };
+    let mut page = pull_request::list_pull_request_page(
+        network(),
+        &repo_coordinate,
+        repo_announcement.relays.clone(),
+        &options,
+    )
+    .await?;
+    let prs = &mut page.pull_requests;
 
-    if args.json {
+    // Show display names from the authors' profiles instead of raw keys
+    let authors: Vec<String> = prs.iter().map(|pr| pr.author.clone()).collect();
+    if !args.offline {
+        match profiles::fetch_display_names(
+            network(),
+            &authors,
+            &repo_announcement.relays,
+            Duration::from_secs(5),
+        )
+        .await
+        {
+            Ok(names) => pull_request::set_author_names(prs, &names),
+            Err(e) => warn!("Failed to fetch author profiles: {e}"),
+        }
+    }
+
+    if args.json || super::json_output() {
         // Output as JSON
-        let json = serde_json::to_string_pretty(&prs)?;
-        println!("{json}");
+        super::print_json(&page)?;
     } else {
         // Human-readable output
         if prs.is_empty() {
             eprintln!("No pull requests found");
         } else {
+            let store = seen::load()?;
             eprintln!("\nFound {count} pull request(s):\n", count = prs.len());
             eprintln!("{:-<80}", "");
 
             for (i, pr) in prs.iter().enumerate() {
-                eprintln!("PR #{num}", num = i + 1);
+                let badge = store.read_state(&pr.id, pr.comments_count).badge();
+                eprintln!("PR #{num} {badge}", num = i + 1);
                 eprintln!(
                     "{pr_output}",
-                    pr_output = pull_request::format_pull_request(pr)
+                    pr_output = pull_request::format_pull_request(pr, time_format())
                 );
                 eprintln!("{:-<80}", "");
             }
         }
+        if let Some(next) = &page.next_cursor {
+            eprintln!(
+                "More pull requests: repeat the command with --cursor {next} (or --all for every PR)"
+            );
+        }
     }
 
     Ok(())
@@ -0,0 +1,232 @@
+use super::network;
+use anyhow::{Context, Result, bail, ensure};
+use clap::{Args, Subcommand};
+use gitsmith_core::{detect_from_git, localnet, repo};
+use serde::Serialize;
+use std::fs;
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+#[derive(Subcommand)]
+pub enum LocalnetCommands {
+    /// Start the local relay in the background and use it in the repository
+    Up(UpArgs),
+    /// Stop the local relay started with `up`
+    Down,
+    /// Copy everything published on the local relay to other relays
+    Replicate(ReplicateArgs),
+}
+
+#[derive(Args)]
+pub struct UpArgs {
+    /// Port to listen on (localhost only)
+    #[arg(long, default_value_t = localnet::DEFAULT_PORT)]
+    pub port: u16,
+
+    /// Repository to configure with the local relay
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Use only the local relay in the repository instead of adding it
+    #[arg(long, conflicts_with = "no_configure")]
+    pub exclusive: bool,
+
+    /// Leave the repository's relays unchanged
+    #[arg(long)]
+    pub no_configure: bool,
+
+    /// Run in the foreground until interrupted
+    #[arg(long)]
+    pub foreground: bool,
+}
+
+#[derive(Args)]
+pub struct ReplicateArgs {
+    /// Relays to copy to (defaults to the repository's non-local relays)
+    #[arg(long = "relay", value_delimiter = ',')]
+    pub relays: Vec<String>,
+
+    /// Repository whose relays are used when --relay is not given
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Timeout in seconds to connect to the relays
+    #[arg(long, default_value = "10")]
+    pub timeout: u64,
+}
+
+/// `localnet up` result with `--format json`
+#[derive(Serialize)]
+struct UpOutput {
+    url: String,
+    /// Whether this call started the relay, false if it was already running
+    started: bool,
+}
+
+/// `localnet down` result with `--format json`
+#[derive(Serialize)]
+struct DownOutput {
+    pid: String,
+}
+
+pub async fn handle_localnet_command(command: LocalnetCommands) -> Result<()> {
+    match command {
+        LocalnetCommands::Up(args) => handle_up_command(args).await,
+        LocalnetCommands::Down => handle_down_command(),
+        LocalnetCommands::Replicate(args) => handle_replicate_command(args).await,
+    }
+}
+
+fn pid_path() -> Result<PathBuf> {
+    Ok(localnet::get_localnet_dir()?.join("relay.pid"))
+}
+
+fn is_listening(port: u16) -> bool {
+    TcpStream::connect(("127.0.0.1", port)).is_ok()
+}
+
+async fn handle_up_command(args: UpArgs) -> Result<()> {
+    let url = localnet::relay_url(args.port);
+
+    if args.foreground {
+        if !args.no_configure {
+            configure_repository(&args.repo_path, &url, args.exclusive);
+        }
+        eprintln!("Local relay running at {url}, press Ctrl-C to stop");
+        let store = localnet::open_store()?;
+        tokio::select! {
+            result = localnet::run(args.port, store) => result?,
+            _ = tokio::signal::ctrl_c() => eprintln!("Stopping local relay"),
+        }
+        return Ok(());
+    }
+
+    let started = !is_listening(args.port);
+    if !started {
+        eprintln!("Local relay already running at {url}");
+    } else {
+        let child = Command::new(std::env::current_exe()?)
+            .args(["localnet", "up", "--foreground", "--port"])
+            .arg(args.port.to_string())
+            .arg("--no-configure")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("Failed to start the local relay")?;
+
+        let pid_path = pid_path()?;
+        if let Some(parent) = pid_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&pid_path, child.id().to_string())
+            .with_context(|| format!("Failed to write {path}", path = pid_path.display()))?;
+
+        let started = Instant::now();
+        while !is_listening(args.port) {
+            ensure!(
+                started.elapsed() < Duration::from_secs(5),
+                "Local relay didn't start listening on port {port}",
+                port = args.port
+            );
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        eprintln!("✅ Local relay running at {url}");
+    }
+
+    if !args.no_configure {
+        configure_repository(&args.repo_path, &url, args.exclusive);
+    }
+    eprintln!("Stop it with 'gitsmith localnet down'");
+    if super::json_output() {
+        return super::print_json(&UpOutput { url, started });
+    }
+    Ok(())
+}
+
+/// Add the local relay to the repository's relays, if it is a gitsmith repository
+fn configure_repository(repo_path: &Path, url: &str, exclusive: bool) {
+    let Ok(announcement) = detect_from_git(repo_path) else {
+        return;
+    };
+
+    let mut relays = vec![url.to_string()];
+    if !exclusive {
+        relays.extend(announcement.relays.into_iter().filter(|relay| relay != url));
+    }
+    match repo::save_relays(repo_path, &relays) {
+        Ok(()) if exclusive => eprintln!("Repository now uses only the local relay"),
+        Ok(()) => eprintln!("Added the local relay to the repository's relays"),
+        Err(e) => eprintln!("Warning: Failed to configure the repository's relays: {e}"),
+    }
+}
+
+fn handle_down_command() -> Result<()> {
+    let pid_path = pid_path()?;
+    let Ok(pid) = fs::read_to_string(&pid_path) else {
+        bail!("No local relay was started with 'gitsmith localnet up'");
+    };
+    let pid = pid.trim();
+
+    let status = Command::new("kill")
+        .arg(pid)
+        .status()
+        .context("Failed to run kill")?;
+    fs::remove_file(&pid_path)
+        .with_context(|| format!("Failed to remove {path}", path = pid_path.display()))?;
+    if !status.success() {
+        bail!("Local relay (pid {pid}) was not running");
+    }
+    eprintln!("✅ Local relay stopped");
+    if super::json_output() {
+        return super::print_json(&DownOutput {
+            pid: pid.to_string(),
+        });
+    }
+    Ok(())
+}
+
+async fn handle_replicate_command(args: ReplicateArgs) -> Result<()> {
+    let relays = if !args.relays.is_empty() {
+        args.relays
+    } else {
+        detect_from_git(&args.repo_path)
+            .map(|announcement| announcement.relays)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|relay| !localnet::is_local_relay(relay))
+            .collect()
+    };
+    ensure!(
+        !relays.is_empty(),
+        "No relays to replicate to, pass --relay or configure public relays in the repository"
+    );
+
+    let store = localnet::open_store()?;
+    let result = localnet::replicate(
+        network(),
+        store.as_ref(),
+        &relays,
+        Duration::from_secs(args.timeout),
+    )
+    .await
+    .context("Failed to replicate the local relay")?;
+
+    eprintln!(
+        "✅ Replicated {count} event(s) to {relays} relay(s)",
+        count = result.event_ids.len(),
+        relays = result.successes.len()
+    );
+    for relay in &result.successes {
+        eprintln!("  ✓ {relay}");
+    }
+    for (relay, error) in &result.failures {
+        eprintln!("  ✗ {relay}: {error}");
+    }
+    if super::json_output() {
+        return super::print_json(&result);
+    }
+    Ok(())
+}
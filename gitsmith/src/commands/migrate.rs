@@ -0,0 +1,244 @@
+use anyhow::{Context, Result, bail, ensure};
+use clap::{Args, Subcommand};
+use clap_complete::ArgValueCandidates;
+use gitsmith_core::{
+    PublishConfig, account, announce_repository, detect_from_git, github, hooks, repo,
+    update_git_config_full,
+};
+use nostr_sdk::{PublicKey, ToBech32};
+use std::collections::HashMap;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+
+use super::{accounts, default_relays, network, password_or_prompt, print_json};
+
+#[derive(Subcommand)]
+pub enum MigrateCommands {
+    /// Announce a GitHub repository with its description, topics and contributors
+    Github(GithubArgs),
+}
+
+#[derive(Args)]
+pub struct GithubArgs {
+    /// GitHub repository (owner/name or URL)
+    pub repository: String,
+
+    /// Relays to announce on (can be specified multiple times, defaults to the configured relays)
+    #[arg(long = "relay", value_delimiter = ',')]
+    pub relays: Vec<String>,
+
+    /// Npub of a contributor as LOGIN=NPUB, skips the prompt for that contributor
+    #[arg(long = "maintainer", value_parser = parse_maintainer)]
+    pub maintainers: Vec<(String, String)>,
+
+    /// How many of the most active contributors to ask npubs for
+    #[arg(long, default_value = "10")]
+    pub contributors: usize,
+
+    /// Don't ask for contributor npubs, only use --maintainer
+    #[arg(long)]
+    pub no_prompt: bool,
+
+    /// Repository identifier (defaults to the GitHub name)
+    #[arg(long)]
+    pub identifier: Option<String>,
+
+    /// GitHub token, only needed for private repositories or to avoid rate limits
+    #[arg(long, env = "GITHUB_TOKEN")]
+    pub github_token: Option<String>,
+
+    /// Local clone of the repository
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Stored account (npub) that owns the repository (defaults to the active one)
+    #[arg(long, add = ArgValueCandidates::new(super::completions::account_candidates))]
+    pub account: Option<String>,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+
+    /// Print the announcement as JSON instead of publishing it
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+fn parse_maintainer(value: &str) -> Result<(String, String)> {
+    let Some((login, npub)) = value.split_once('=') else {
+        bail!("Expected LOGIN=NPUB, got {value}");
+    };
+    let npub = PublicKey::parse(npub)
+        .with_context(|| format!("Invalid npub for {login}: {npub}"))?
+        .to_bech32()?;
+    Ok((login.trim_start_matches('@').to_string(), npub))
+}
+
+pub async fn handle_migrate_command(command: MigrateCommands) -> Result<()> {
+    match command {
+        MigrateCommands::Github(args) => handle_github_command(args).await,
+    }
+}
+
+async fn handle_github_command(args: GithubArgs) -> Result<()> {
+    let (owner, name) = github::parse_github_repository(&args.repository)?;
+    let token = args.github_token.as_deref();
+
+    eprintln!("Fetching {owner}/{name} from GitHub...");
+    let github = github::fetch_github_repository(&owner, &name, token)
+        .await
+        .context("Failed to fetch the GitHub repository")?;
+    let contributors = if args.contributors > 0 {
+        github::fetch_github_contributors(&owner, &name, args.contributors, token)
+            .await
+            .unwrap_or_else(|e| {
+                eprintln!("Warning: Failed to fetch contributors: {e}");
+                Vec::new()
+            })
+    } else {
+        Vec::new()
+    };
+
+    // The announcement commits to the root of GitHub's default branch
+    let root_commit = match repo::get_branch_root_commit(&args.repo_path, &github.default_branch) {
+        Ok(root_commit) => root_commit,
+        Err(e) => {
+            let detected = detect_from_git(&args.repo_path).with_context(|| {
+                format!(
+                    "Run this in a clone of {full_name} or pass --repo-path",
+                    full_name = github.full_name
+                )
+            })?;
+            eprintln!("Warning: {e}, using the root commit of the current branch");
+            detected.root_commit
+        }
+    };
+
+    let relays = if args.relays.is_empty() {
+        default_relays()
+    } else {
+        args.relays
+    };
+    ensure!(
+        !relays.is_empty(),
+        "No relays to announce on. Pass them with --relay or set them with 'gitsmith config set relays'"
+    );
+
+    let maintainers = contributor_npubs(&contributors, &args.maintainers, !args.no_prompt)?;
+    let mut announcement =
+        github::announcement_from_github(&github, root_commit, relays, maintainers);
+    if let Some(identifier) = args.identifier {
+        ensure!(
+            !identifier.contains(' ') && !identifier.contains('/'),
+            "Identifier must not contain spaces or slashes"
+        );
+        announcement.identifier = identifier;
+    }
+
+    if args.dry_run {
+        return print_json(&announcement);
+    }
+
+    let prompt = match &args.account {
+        Some(account) => format!("Enter password for {account}: "),
+        None => "Enter password: ".to_string(),
+    };
+    let password = password_or_prompt(args.password, &prompt)?;
+    let signer = account::get_signer(accounts(), args.account.as_deref(), &password)?;
+    let signer = hooks::with_configured_hook(signer, &args.repo_path);
+    let owner_npub = signer.public_key().to_bech32()?;
+    // The owner signs the announcement, listing them as maintainer too is redundant
+    announcement.maintainers.retain(|npub| npub != &owner_npub);
+
+    let config = PublishConfig {
+        timeout_secs: 30,
+        wait_for_send: true,
+    };
+    let result = announce_repository(network(), announcement.clone(), signer.as_ref(), config)
+        .await
+        .context("Failed to announce repository")?;
+
+    if let Err(e) = update_git_config_full(
+        &args.repo_path,
+        &announcement,
+        &result.nostr_url,
+        &owner_npub,
+    ) {
+        eprintln!("Warning: Failed to update git config: {e}");
+    }
+    if args.account.is_some()
+        && let Err(e) = repo::set_repo_account(&args.repo_path, &owner_npub)
+    {
+        eprintln!("Warning: Failed to bind the account to the repository: {e}");
+    }
+
+    if result.successes.is_empty() {
+        bail!("Failed to announce repository to any relay");
+    }
+    eprintln!(
+        "✅ Migrated {full_name} to {count} relay(s)",
+        full_name = github.full_name,
+        count = result.successes.len()
+    );
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+    if super::json_output() {
+        return print_json(&result);
+    }
+    println!("{nostr_url}", nostr_url = result.nostr_url);
+    Ok(())
+}
+
+/// Npubs of the contributors, from --maintainer or asked on the terminal
+fn contributor_npubs(
+    contributors: &[github::GithubContributor],
+    mapped: &[(String, String)],
+    prompt: bool,
+) -> Result<Vec<String>> {
+    let known: HashMap<&str, &str> = mapped
+        .iter()
+        .map(|(login, npub)| (login.as_str(), npub.as_str()))
+        .collect();
+    let mut npubs: Vec<String> = Vec::new();
+    let mut push = |npub: String| {
+        if !npubs.contains(&npub) {
+            npubs.push(npub);
+        }
+    };
+
+    let prompt = prompt && io::stdin().is_terminal();
+    for contributor in contributors {
+        if let Some(npub) = known.get(contributor.login.as_str()) {
+            push(npub.to_string());
+            continue;
+        }
+        if !prompt {
+            continue;
+        }
+        eprint!(
+            "npub of @{login} ({count} contributions, Enter to skip): ",
+            login = contributor.login,
+            count = contributor.contributions
+        );
+        io::stderr().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        let answer = answer.trim();
+        if answer.is_empty() {
+            continue;
+        }
+        match PublicKey::parse(answer) {
+            Ok(public_key) => push(public_key.to_bech32()?),
+            Err(e) => eprintln!("Warning: Skipping @{login}: {e}", login = contributor.login),
+        }
+    }
+
+    // Maintainers that aren't among the fetched contributors are kept as well
+    for (login, npub) in mapped {
+        if !contributors.iter().any(|c| &c.login == login) {
+            push(npub.to_string());
+        }
+    }
+    Ok(npubs)
+}
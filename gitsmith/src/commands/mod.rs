@@ -1,4 +1,265 @@
 pub mod account;
+pub mod bridge;
+pub mod cache;
+pub mod ci;
+pub mod clone;
+pub mod comment;
+pub mod completions;
+pub mod config;
+pub mod event;
+pub mod fork;
+pub mod inbox;
+pub mod init;
+pub mod issue;
+pub mod link;
 pub mod list;
+pub mod localnet;
+pub mod migrate;
+pub mod new;
+pub mod org;
+pub mod patch;
+pub mod pr;
+pub mod push;
+pub mod refresh;
+pub mod relays;
+pub mod release;
+pub mod repos;
+pub mod review;
 pub mod send;
+#[cfg(feature = "serve")]
+pub mod serve;
+pub mod state;
 pub mod sync;
+pub mod watch;
+
+use anyhow::{Context, Result, ensure};
+use gitsmith_core::{
+    RepoAnnouncement, Signer,
+    account::{self as core_account, AccountOptions},
+    get_repo_account, get_repo_owner, hooks, readme,
+    relay::Network,
+    repo::{self, Identity},
+    seen,
+    time::{self, TimeFormat},
+};
+use nostr_sdk::PublicKey;
+use rpassword::read_password;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::Command;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether commands print their results as JSON, see [`use_json_output`]
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Print command results as JSON for the rest of the process (`--format json`)
+///
+/// Stdout then carries a single JSON document, progress and log messages go
+/// to stderr.
+pub fn use_json_output(enabled: bool) {
+    JSON_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether the global output mode is JSON
+pub fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Settings of the process chosen by the global flags and the configuration
+#[derive(Debug, Default)]
+pub struct Settings {
+    pub network: Network,
+    pub accounts: AccountOptions,
+    pub time_format: TimeFormat,
+    /// Relays used when neither `--relay` nor the repository configures any
+    pub default_relays: Vec<String>,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// Use these settings for the rest of the process, only the first call counts
+pub fn use_settings(settings: Settings) {
+    let _ = SETTINGS.set(settings);
+}
+
+fn settings() -> &'static Settings {
+    SETTINGS.get_or_init(Settings::default)
+}
+
+/// How relays are reached and where fetched events are cached
+pub fn network() -> &'static Network {
+    &settings().network
+}
+
+/// Where accounts are stored and which one signs
+pub fn accounts() -> &'static AccountOptions {
+    &settings().accounts
+}
+
+/// How timestamps are printed (`--time`, `--timezone`, `--date-format`)
+pub fn time_format() -> &'static TimeFormat {
+    &settings().time_format
+}
+
+/// Format a unix timestamp with [`time_format`]
+pub fn format_timestamp(timestamp: u64) -> String {
+    time::format_timestamp(time_format(), timestamp)
+}
+
+/// Relays used when neither `--relay` nor the repository configures any
+pub fn default_relays() -> Vec<String> {
+    settings().default_relays.clone()
+}
+
+/// Print JSON output, with ISO 8601 timestamps next to the unix ones
+pub fn print_json<T: serde::Serialize>(value: &T) -> Result<()> {
+    let mut value = serde_json::to_value(value)?;
+    time::add_iso_timestamps(&mut value);
+    println!("{json}", json = serde_json::to_string_pretty(&value)?);
+    Ok(())
+}
+
+/// Use the provided password or prompt for one on the terminal
+pub fn password_or_prompt(password: Option<String>, prompt: &str) -> Result<String> {
+    if let Some(pwd) = password {
+        return Ok(pwd);
+    }
+
+    eprint!("{prompt}");
+    io::stderr().flush()?;
+    Ok(read_password()?)
+}
+
+/// Account bound to the repository and the password prompt for it
+fn repo_account(repo_path: &Path) -> (Option<String>, String) {
+    // Outside a repository there is nothing bound
+    match get_repo_account(repo_path).ok().flatten() {
+        Some(npub) => {
+            let prompt = format!("Enter password for {npub}: ");
+            (Some(npub), prompt)
+        }
+        None => (None, "Enter password: ".to_string()),
+    }
+}
+
+/// Signer of the account bound to the repository (`init --account`), falling
+/// back to the active account, running the repository's pre-sign hook
+pub fn repo_signer(repo_path: &Path, password: Option<String>) -> Result<Box<dyn Signer>> {
+    let (account, prompt) = repo_account(repo_path);
+    let password = password_or_prompt(password, &prompt)?;
+    let signer = core_account::get_signer(accounts(), account.as_deref(), &password)?;
+    Ok(hooks::with_configured_hook(signer, repo_path))
+}
+
+/// Signer of the active account (or the one selected with `GITSMITH_ACCOUNT`),
+/// running the pre-sign hook of the current repository
+pub fn active_signer(password: &str) -> Result<Box<dyn Signer>> {
+    let signer = core_account::get_active_signer(accounts(), password)?;
+    Ok(hooks::with_configured_hook(signer, Path::new(".")))
+}
+
+/// Build the repository coordinate (30617:pubkey:identifier) for a local repository
+///
+/// The owner saved in the repo config (set during init) takes precedence over the
+/// account bound to the repository, then the active account.
+pub fn repo_coordinate(repo_path: &Path, identifier: &str) -> Result<String> {
+    let public_key = if let Some(owner) = get_repo_owner(repo_path)? {
+        owner
+    } else if let Some(npub) = get_repo_account(repo_path)? {
+        PublicKey::parse(&npub)
+            .with_context(|| format!("Invalid account {npub} in git config nostr.account"))?
+            .to_hex()
+    } else {
+        core_account::get_active_public_key(accounts()).context(
+            "Repository owner not found in config and no active account. Please login first with 'gitsmith account login'",
+        )?
+    };
+
+    Ok(format!("30617:{public_key}:{identifier}"))
+}
+
+/// Publish the README (or its summary) as a long-form article and link it
+/// from the announcement, which still has to be published
+pub async fn publish_readme(
+    signer: &dyn Signer,
+    announcement: &mut RepoAnnouncement,
+    repo_path: &Path,
+    path: &Path,
+    summary_only: bool,
+) -> Result<()> {
+    let content = readme::read_readme(repo_path, path)?;
+    let (readme, result) = readme::publish_readme(
+        network(),
+        signer,
+        &announcement.identifier,
+        &announcement.name,
+        &content,
+        summary_only,
+        &announcement.relays,
+    )
+    .await?;
+    ensure!(
+        !result.successes.is_empty(),
+        "Failed to publish the README to any relay"
+    );
+    eprintln!(
+        "✅ README published to {count} relay(s)",
+        count = result.successes.len()
+    );
+    announcement.readme = Some(readme);
+    Ok(())
+}
+
+/// Open a file in the user's editor, picked like git does (GIT_EDITOR, VISUAL, EDITOR, vi)
+pub fn run_editor(path: &Path) -> Result<()> {
+    let editor = ["GIT_EDITOR", "VISUAL", "EDITOR"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|value| !value.is_empty()))
+        .unwrap_or_else(|| "vi".to_string());
+    let status = Command::new("sh")
+        .args(["-c", &format!("{editor} \"$1\""), "editor"])
+        .arg(path)
+        .status()
+        .with_context(|| format!("Failed to run editor '{editor}'"))?;
+    ensure!(status.success(), "Editor '{editor}' exited with {status}");
+    Ok(())
+}
+
+/// Warn when a local repository isn't the project announced at a coordinate
+///
+/// Anyone can announce an identifier, so a coordinate may name an unrelated
+/// project. Failing to check only logs a warning.
+pub fn warn_identity_mismatch(repo_path: &Path, announcement: &RepoAnnouncement) {
+    match repo::verify_identity(repo_path, announcement) {
+        Ok(Identity::Mismatch { announced, local }) => {
+            eprintln!();
+            eprintln!(
+                "⚠️  WARNING: the announcement of '{identifier}' describes a different project!",
+                identifier = announcement.identifier
+            );
+            eprintln!("   Announced earliest unique commit: {announced}");
+            eprintln!("   Root commit of this repository:   {local}");
+            eprintln!(
+                "   Pull requests and state from this coordinate may not belong to this code."
+            );
+            eprintln!();
+        }
+        Ok(Identity::Verified | Identity::Unverified) => {}
+        Err(e) => tracing::warn!("Failed to verify repository identity: {e}"),
+    }
+}
+
+/// Whether an item carries every label asked for with `--label`
+pub fn has_labels(labels: &[String], wanted: &[String]) -> bool {
+    wanted.iter().all(|label| labels.contains(label))
+}
+
+/// Record a pull request or issue as read, along with its comments
+///
+/// Read tracking is a convenience, so failing to save it only logs a warning.
+pub fn mark_viewed(event_id: &str, comments: usize) {
+    if let Err(e) = seen::mark_read(&[(event_id.to_string(), comments)]) {
+        tracing::warn!("Failed to record {event_id} as read: {e}");
+    }
+}
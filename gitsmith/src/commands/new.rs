@@ -0,0 +1,218 @@
+use anyhow::{Context, Result, bail, ensure};
+use clap::Args;
+use gitsmith_core::{
+    PublishConfig, PublishResult, RepoAnnouncement, announce_repository, license, repo,
+    template::{self, TemplateReplacements},
+    update_git_config_full,
+};
+use nostr_sdk::ToBech32;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::{active_signer, default_relays, network, password_or_prompt};
+
+#[derive(Args)]
+pub struct NewArgs {
+    /// Template repository (naddr or nostr:// URL)
+    #[arg(long)]
+    pub template: String,
+
+    /// Repository identifier for the new project (unique, no spaces)
+    #[arg(long)]
+    pub identifier: String,
+
+    /// Project name (defaults to the identifier)
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Project description
+    #[arg(long, default_value = "")]
+    pub description: String,
+
+    /// Directory to create (defaults to the identifier)
+    pub directory: Option<PathBuf>,
+
+    /// License of the new project (SPDX expression, defaults to the template's)
+    #[arg(long)]
+    pub license: Option<String>,
+
+    /// Clone URLs of the new project (can be specified multiple times)
+    #[arg(long = "clone-url", value_delimiter = ',')]
+    pub clone_urls: Vec<String>,
+
+    /// Relays to announce on (defaults to the template's relays)
+    #[arg(long = "relay", value_delimiter = ',')]
+    pub relays: Vec<String>,
+
+    /// Only create the project locally, without announcing it
+    #[arg(long)]
+    pub no_announce: bool,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+/// `new` result with `--format json`
+#[derive(Serialize)]
+struct NewOutput {
+    directory: PathBuf,
+    root_commit: String,
+    /// Number of files the template replacements changed
+    customized_files: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    announcement: Option<PublishResult>,
+}
+
+pub async fn handle_new_command(args: NewArgs) -> Result<()> {
+    ensure!(
+        !args.identifier.contains(' ') && !args.identifier.contains('/'),
+        "Identifier must not contain spaces or slashes"
+    );
+
+    let address = repo::parse_repository_address(&args.template)?;
+    let mut lookup_relays = address.relays.clone();
+    lookup_relays.extend(args.relays.iter().cloned());
+    if lookup_relays.is_empty() {
+        lookup_relays = default_relays();
+    }
+    if lookup_relays.is_empty() {
+        bail!(
+            "No relay in {template}. Pass one with --relay",
+            template = args.template
+        );
+    }
+
+    eprintln!(
+        "Fetching template {identifier}...",
+        identifier = address.identifier
+    );
+    let Some(template) = repo::fetch_announcement(
+        network(),
+        &address.coordinate(),
+        &lookup_relays,
+        Duration::from_secs(10),
+    )
+    .await?
+    else {
+        bail!(
+            "Template {identifier} not found on any relay",
+            identifier = address.identifier
+        );
+    };
+    let Some(clone_url) = repo::preferred_clone_url(&template.clone_urls) else {
+        bail!("Template announcement has no clone URL");
+    };
+
+    if let Some(license) = &args.license {
+        license::validate_license(license)?;
+    }
+    let license = args.license.or_else(|| template.license.clone());
+    if let Some(conflict) =
+        license::license_conflict(template.license.as_deref(), license.as_deref())
+    {
+        eprintln!("⚠️  License conflict: {conflict}");
+    }
+
+    let name = args.name.unwrap_or_else(|| args.identifier.clone());
+    let directory = args
+        .directory
+        .unwrap_or_else(|| PathBuf::from(&args.identifier));
+
+    eprintln!("Cloning {clone_url}...");
+    template::clone_template(clone_url, &directory)?;
+
+    let replacements = TemplateReplacements::for_project(
+        &template.identifier,
+        &template.name,
+        &args.identifier,
+        &name,
+        &args.description,
+    );
+    let changed = template::apply_template(&directory, &replacements)?;
+    let root_commit = template::init_fresh_history(
+        &directory,
+        &format!("Initial commit from template {name}", name = template.name),
+    )?;
+    eprintln!(
+        "✅ Created {directory} from {template} ({changed} file(s) customized)",
+        directory = directory.display(),
+        template = template.name
+    );
+
+    if args.no_announce {
+        if super::json_output() {
+            return super::print_json(&NewOutput {
+                directory,
+                root_commit,
+                customized_files: changed,
+                announcement: None,
+            });
+        }
+        return Ok(());
+    }
+
+    let relays = if args.relays.is_empty() {
+        if template.relays.is_empty() {
+            lookup_relays
+        } else {
+            template.relays.clone()
+        }
+    } else {
+        args.relays
+    };
+    let announcement = RepoAnnouncement {
+        identifier: args.identifier,
+        name,
+        description: args.description,
+        clone_urls: args.clone_urls,
+        relays,
+        web: vec![],
+        root_commit: root_commit.clone(),
+        maintainers: vec![],
+        ci_keys: vec![],
+        grasp_servers: vec![],
+        blossom_servers: vec![],
+        min_pow: 0,
+        license,
+        topics: vec![],
+        readme: None,
+    };
+
+    let password = password_or_prompt(args.password, "Enter password: ")?;
+    let signer = active_signer(&password)?;
+    let config = PublishConfig {
+        timeout_secs: 30,
+        wait_for_send: true,
+    };
+    let result = announce_repository(network(), announcement.clone(), signer.as_ref(), config)
+        .await
+        .context("Failed to announce repository")?;
+
+    let owner_npub = signer.public_key().to_bech32()?;
+    update_git_config_full(&directory, &announcement, &result.nostr_url, &owner_npub)
+        .context("Failed to save nostr configuration")?;
+
+    if result.successes.is_empty() {
+        bail!("Failed to announce repository to any relay");
+    }
+    eprintln!(
+        "✅ Announced on {count} relay(s)",
+        count = result.successes.len()
+    );
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+    if super::json_output() {
+        return super::print_json(&NewOutput {
+            directory,
+            root_commit,
+            customized_files: changed,
+            announcement: Some(result),
+        });
+    }
+    println!("{nostr_url}", nostr_url = result.nostr_url);
+
+    Ok(())
+}
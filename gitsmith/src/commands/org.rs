@@ -0,0 +1,302 @@
+use anyhow::{Context, Result, bail};
+use clap::{Args, Subcommand};
+use gitsmith_core::{
+    detect_from_git,
+    orgs::{self, Organization},
+    types::BroadcastResult,
+};
+use nostr_sdk::{PublicKey, ToBech32};
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+
+use super::{active_signer, default_relays, network, password_or_prompt};
+
+#[derive(Subcommand)]
+pub enum OrgCommands {
+    /// Announce a new organization grouping related repositories
+    Create(CreateArgs),
+
+    /// Update an organization's details, repositories or maintainers
+    Update(UpdateArgs),
+
+    /// Show an organization
+    Show(ShowArgs),
+}
+
+#[derive(Args)]
+pub struct CreateArgs {
+    /// Organization identifier (unique, no spaces)
+    #[arg(long)]
+    pub identifier: String,
+
+    /// Organization name
+    #[arg(long)]
+    pub name: String,
+
+    /// Organization description
+    #[arg(long, default_value = "")]
+    pub description: String,
+
+    /// Member repository (nostr:// URL, naddr or coordinate; can be repeated)
+    #[arg(long = "repo")]
+    pub repos: Vec<String>,
+
+    /// Maintainer npub (can be repeated)
+    #[arg(long = "maintainer")]
+    pub maintainers: Vec<String>,
+
+    /// Web URL (can be repeated)
+    #[arg(long)]
+    pub web: Vec<String>,
+
+    /// Relays to publish to (defaults to the current repository's relays)
+    #[arg(long = "relay", value_delimiter = ',')]
+    pub relays: Vec<String>,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+#[derive(Args)]
+pub struct UpdateArgs {
+    /// Organization address (naddr)
+    pub org: String,
+
+    /// New name
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// New description
+    #[arg(long)]
+    pub description: Option<String>,
+
+    /// Repository to add (can be repeated)
+    #[arg(long)]
+    pub add_repo: Vec<String>,
+
+    /// Repository to remove (can be repeated)
+    #[arg(long)]
+    pub remove_repo: Vec<String>,
+
+    /// Maintainer npub to add (can be repeated)
+    #[arg(long)]
+    pub add_maintainer: Vec<String>,
+
+    /// Maintainer npub to remove (can be repeated)
+    #[arg(long)]
+    pub remove_maintainer: Vec<String>,
+
+    /// Additional relays (the naddr relay hints and current repository's relays are used too)
+    #[arg(long = "relay", value_delimiter = ',')]
+    pub relays: Vec<String>,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ShowArgs {
+    /// Organization address (naddr)
+    pub org: String,
+
+    /// Additional relays to look the organization up on
+    #[arg(long = "relay", value_delimiter = ',')]
+    pub relays: Vec<String>,
+
+    /// Output as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+pub async fn handle_org_command(command: OrgCommands) -> Result<()> {
+    match command {
+        OrgCommands::Create(args) => handle_create_command(args).await,
+        OrgCommands::Update(args) => handle_update_command(args).await,
+        OrgCommands::Show(args) => handle_show_command(args).await,
+    }
+}
+
+/// Explicit relays, then relay hints, falling back to the current repository's
+/// relays and then the configured ones
+pub fn org_relays(explicit: Vec<String>, hints: Vec<String>) -> Result<Vec<String>> {
+    let mut relays = explicit;
+    for relay in hints {
+        if !relays.contains(&relay) {
+            relays.push(relay);
+        }
+    }
+    if relays.is_empty()
+        && let Ok(announcement) = detect_from_git(Path::new("."))
+    {
+        relays = announcement.relays;
+    }
+    if relays.is_empty() {
+        relays = default_relays();
+    }
+
+    if relays.is_empty() {
+        bail!("No relays available. Pass them with --relay");
+    }
+    Ok(relays)
+}
+
+/// Fetch an organization by address along with the relays used to find it
+pub async fn resolve_org(
+    address: &str,
+    relays: Vec<String>,
+) -> Result<(Organization, Vec<String>)> {
+    let (coordinate, hints) = orgs::parse_organization_address(address)?;
+    let relays = org_relays(relays, hints)?;
+
+    let Some(org) =
+        orgs::fetch_organization(network(), &coordinate, &relays, Duration::from_secs(10)).await?
+    else {
+        bail!(
+            "Organization {identifier} not found on any relay",
+            identifier = coordinate.identifier
+        );
+    };
+    Ok((org, relays))
+}
+
+fn parse_maintainers(npubs: &[String]) -> Result<Vec<String>> {
+    npubs
+        .iter()
+        .map(|npub| {
+            PublicKey::parse(npub)
+                .map(|key| key.to_hex())
+                .with_context(|| format!("Invalid maintainer public key: {npub}"))
+        })
+        .collect()
+}
+
+fn parse_repositories(references: &[String]) -> Result<Vec<String>> {
+    references
+        .iter()
+        .map(|reference| Ok(orgs::parse_repository_reference(reference)?))
+        .collect()
+}
+
+/// `org create` and `org update` result with `--format json`
+#[derive(Serialize)]
+struct PublishedOrganization<'a> {
+    naddr: String,
+    organization: &'a Organization,
+    publish: &'a BroadcastResult,
+}
+
+fn report_published(result: &BroadcastResult, org: &Organization, relays: &[String]) -> Result<()> {
+    if result.successes.is_empty() {
+        bail!("Failed to publish organization to any relay");
+    }
+
+    eprintln!(
+        "✅ Organization {name} published to {count} relay(s)",
+        name = org.name,
+        count = result.successes.len()
+    );
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+    let naddr = orgs::organization_naddr(org, relays)?;
+    if super::json_output() {
+        return super::print_json(&PublishedOrganization {
+            naddr,
+            organization: org,
+            publish: result,
+        });
+    }
+    println!("{naddr}");
+
+    Ok(())
+}
+
+async fn handle_create_command(args: CreateArgs) -> Result<()> {
+    let relays = org_relays(args.relays, vec![])?;
+    let password = password_or_prompt(args.password, "Enter password: ")?;
+    let signer = active_signer(&password)?;
+
+    let org = Organization {
+        identifier: args.identifier,
+        name: args.name,
+        description: args.description,
+        owner: signer.public_key().to_hex(),
+        repositories: parse_repositories(&args.repos)?,
+        maintainers: parse_maintainers(&args.maintainers)?,
+        web: args.web,
+    };
+
+    let result = orgs::publish_organization(network(), signer.as_ref(), &org, &relays).await?;
+    report_published(&result, &org, &relays)
+}
+
+async fn handle_update_command(args: UpdateArgs) -> Result<()> {
+    let (mut org, relays) = resolve_org(&args.org, args.relays).await?;
+
+    let password = password_or_prompt(args.password, "Enter password: ")?;
+    let signer = active_signer(&password)?;
+    if signer.public_key().to_hex() != org.owner {
+        bail!("Only the organization owner can update it");
+    }
+
+    if let Some(name) = args.name {
+        org.name = name;
+    }
+    if let Some(description) = args.description {
+        org.description = description;
+    }
+    orgs::update_members(
+        &mut org.repositories,
+        &parse_repositories(&args.add_repo)?,
+        &parse_repositories(&args.remove_repo)?,
+    )?;
+    orgs::update_members(
+        &mut org.maintainers,
+        &parse_maintainers(&args.add_maintainer)?,
+        &parse_maintainers(&args.remove_maintainer)?,
+    )?;
+
+    let result = orgs::publish_organization(network(), signer.as_ref(), &org, &relays).await?;
+    report_published(&result, &org, &relays)
+}
+
+async fn handle_show_command(args: ShowArgs) -> Result<()> {
+    let (org, _relays) = resolve_org(&args.org, args.relays).await?;
+
+    if args.json || super::json_output() {
+        let json = serde_json::to_string_pretty(&org)?;
+        println!("{json}");
+        return Ok(());
+    }
+
+    println!("Name: {name}", name = org.name);
+    println!("Identifier: {identifier}", identifier = org.identifier);
+    println!(
+        "Owner: {owner}",
+        owner = PublicKey::from_hex(&org.owner)?.to_bech32()?
+    );
+    if !org.description.is_empty() {
+        println!("\n{description}\n", description = org.description);
+    }
+    if !org.web.is_empty() {
+        println!("Web: {web}", web = org.web.join(", "));
+    }
+    println!("Repositories: {count}", count = org.repositories.len());
+    for coordinate in &org.repositories {
+        println!("  {coordinate}");
+    }
+    if !org.maintainers.is_empty() {
+        println!("Maintainers:");
+        for maintainer in &org.maintainers {
+            println!(
+                "  {npub}",
+                npub = PublicKey::from_hex(maintainer)?.to_bech32()?
+            );
+        }
+    }
+
+    Ok(())
+}
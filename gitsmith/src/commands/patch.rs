@@ -0,0 +1,127 @@
+use anyhow::{Context, Result, bail, ensure};
+use clap::{Args, Subcommand};
+use gitsmith_core::{detect_from_git, format_patch, patches, pull_request};
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::network;
+
+#[derive(Subcommand)]
+pub enum PatchCommands {
+    /// Write patches to numbered .patch files or an mbox, like git format-patch
+    Export(ExportArgs),
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Commits to export: a revision to export the commits after (HEAD~2, main), A..B or A...B
+    #[arg(default_value = "HEAD~1")]
+    pub since: String,
+
+    /// Export the newest N commits of HEAD's history
+    #[arg(long, short = 'n', value_name = "N", conflicts_with = "since")]
+    pub count: Option<usize>,
+
+    /// Export the patches of a pull request from relays instead of local commits
+    #[arg(long, value_name = "EVENT_ID", conflicts_with_all = ["since", "count"])]
+    pub pr: Option<String>,
+
+    /// Directory for the numbered .patch files
+    #[arg(long, short = 'o', default_value = ".")]
+    pub output_directory: PathBuf,
+
+    /// Write a single mbox file instead of one file per patch
+    #[arg(long, value_name = "FILE", conflicts_with = "stdout")]
+    pub mbox: Option<PathBuf>,
+
+    /// Print the patches as an mbox to standard output
+    #[arg(long)]
+    pub stdout: bool,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+}
+
+/// `patch export` result with `--format json`
+#[derive(Serialize)]
+struct ExportOutput {
+    patches: usize,
+    /// Files written: the numbered patches or the mbox
+    files: Vec<PathBuf>,
+    /// The mbox itself with --stdout
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mbox: Option<String>,
+}
+
+pub async fn handle_patch_command(command: PatchCommands) -> Result<()> {
+    match command {
+        PatchCommands::Export(args) => handle_export_command(args).await,
+    }
+}
+
+async fn handle_export_command(args: ExportArgs) -> Result<()> {
+    let patches = match &args.pr {
+        Some(event_id) => {
+            let repo_announcement = detect_from_git(&args.repo_path)
+                .context("Failed to detect repository. Make sure you're in a git repository")?;
+            if repo_announcement.relays.is_empty() {
+                bail!("No relays configured for repository. Please run 'gitsmith init' first");
+            }
+            eprintln!(
+                "Fetching pull request {event_id} from {count} relay(s)...",
+                count = repo_announcement.relays.len()
+            );
+            pull_request::fetch_patch_series(
+                network(),
+                event_id,
+                &repo_announcement.relays,
+                Duration::from_secs(5),
+            )
+            .await?
+            .patch_contents()
+        }
+        None => patches::generate_patches(
+            &args.repo_path,
+            args.count.is_none().then_some(args.since.as_str()),
+            args.count,
+        )?,
+    };
+    ensure!(!patches.is_empty(), "No patches to export");
+
+    let mut output = ExportOutput {
+        patches: patches.len(),
+        files: Vec::new(),
+        mbox: None,
+    };
+    if args.stdout {
+        let mbox = format_patch::mbox(&patches);
+        if !super::json_output() {
+            print!("{mbox}");
+        }
+        output.mbox = Some(mbox);
+    } else if let Some(path) = args.mbox {
+        fs::write(&path, format_patch::mbox(&patches))
+            .with_context(|| format!("Failed to write {path}", path = path.display()))?;
+        eprintln!(
+            "Wrote {count} patch(es) to {path}",
+            count = patches.len(),
+            path = path.display()
+        );
+        output.files.push(path);
+    } else {
+        output.files = format_patch::write_patch_files(&patches, &args.output_directory)?;
+        if !super::json_output() {
+            for path in &output.files {
+                println!("{path}", path = path.display());
+            }
+        }
+    }
+
+    if super::json_output() {
+        return super::print_json(&output);
+    }
+    Ok(())
+}
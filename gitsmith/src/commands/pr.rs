@@ -0,0 +1,904 @@
+use anyhow::{Context, Result, bail};
+use clap::{Args, Subcommand, ValueEnum};
+use gitsmith_core::{
+    GitsmithError,
+    apply::{self, AppliedPatch, ApplyOptions, LandedPatches, MergeStrategy, MergedPatches},
+    comments,
+    commit_signatures::{self, CommitSignature, SignatureStatus},
+    detect_from_git,
+    pull_request::{self, AppliedCommits, PatchSeries, PullRequestDetails, PullRequestStatus},
+    repo,
+    types::BroadcastResult,
+};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use super::{network, repo_coordinate, repo_signer, run_editor, time_format};
+
+#[derive(Subcommand)]
+pub enum PrCommands {
+    /// Publish a status (open, merged, closed, draft) for a pull request
+    Status(StatusArgs),
+
+    /// Apply the patches of a pull request to the current branch
+    Apply(ApplyArgs),
+
+    /// Fetch the patches of a pull request into a local `pr/<id>` branch
+    Fetch(FetchArgs),
+
+    /// Show a pull request with its patches, status history and comments
+    Show(ShowArgs),
+
+    /// Merge a pull request into a branch and publish its merged status
+    Merge(MergeArgs),
+
+    /// Publish the merged status of a pull request applied outside gitsmith
+    MarkApplied(MarkAppliedArgs),
+}
+
+/// Statuses that can be published for a pull request
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum StatusValue {
+    Open,
+    Merged,
+    Closed,
+    Draft,
+}
+
+/// How `pr merge` brings the patches into the branch
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum MergeStrategyValue {
+    /// Commit the patches on top of the branch
+    #[default]
+    Apply,
+    /// Commit the patches onto their parent commit and create a merge commit
+    Merge,
+}
+
+impl From<MergeStrategyValue> for MergeStrategy {
+    fn from(value: MergeStrategyValue) -> Self {
+        match value {
+            MergeStrategyValue::Apply => MergeStrategy::Apply,
+            MergeStrategyValue::Merge => MergeStrategy::Merge,
+        }
+    }
+}
+
+impl From<StatusValue> for PullRequestStatus {
+    fn from(value: StatusValue) -> Self {
+        match value {
+            StatusValue::Open => PullRequestStatus::Open,
+            StatusValue::Merged => PullRequestStatus::Merged,
+            StatusValue::Closed => PullRequestStatus::Closed,
+            StatusValue::Draft => PullRequestStatus::Draft,
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct StatusArgs {
+    /// Pull request event ID
+    pub event_id: String,
+
+    /// New status for the pull request
+    #[arg(long = "set", value_enum)]
+    pub status: StatusValue,
+
+    /// Optional message explaining the status change
+    #[arg(long, short = 'm', default_value = "")]
+    pub message: String,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ApplyArgs {
+    /// Pull request event ID
+    #[arg(required_unless_present_any = ["continue_apply", "abort"])]
+    pub event_id: Option<String>,
+
+    /// Only check whether the patches apply cleanly
+    #[arg(long, conflicts_with_all = ["three_way", "interactive"])]
+    pub check: bool,
+
+    /// Fall back to a 3-way merge when patches don't apply cleanly
+    #[arg(long = "3way")]
+    pub three_way: bool,
+
+    /// Resolve conflicts file by file in an interactive prompt (implies --3way)
+    #[arg(long, short = 'i')]
+    pub interactive: bool,
+
+    /// Resume after resolving conflicts of a stopped apply
+    #[arg(long = "continue", conflicts_with_all = ["event_id", "abort", "check"])]
+    pub continue_apply: bool,
+
+    /// Give up a stopped apply and restore the branch to its state before applying
+    #[arg(long, conflicts_with_all = ["event_id", "check", "interactive"])]
+    pub abort: bool,
+
+    /// Apply even if the PR is below the repository's proof-of-work requirement
+    #[arg(long)]
+    pub include_low_pow: bool,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+}
+
+#[derive(Args)]
+pub struct FetchArgs {
+    /// Pull request event ID
+    pub event_id: String,
+
+    /// Branch name to create (defaults to pr/<short-event-id>)
+    #[arg(long)]
+    pub branch: Option<String>,
+
+    /// Commit to apply the patches onto (defaults to the PR's parent commit, then HEAD)
+    #[arg(long)]
+    pub base: Option<String>,
+
+    /// Overwrite the branch if it already exists
+    #[arg(long, short = 'f')]
+    pub force: bool,
+
+    /// Fetch even if the PR is below the repository's proof-of-work requirement
+    #[arg(long)]
+    pub include_low_pow: bool,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+}
+
+#[derive(Args)]
+pub struct ShowArgs {
+    /// Pull request event ID
+    pub event_id: String,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Output as JSON
+    #[arg(long, conflicts_with = "patch")]
+    pub json: bool,
+
+    /// Print the raw patches instead of the summary, with inline comments
+    /// under their hunks when printing to a terminal
+    #[arg(long)]
+    pub patch: bool,
+
+    /// Only print the diffstat of each patch, from the series manifest when available
+    #[arg(long, conflicts_with = "patch")]
+    pub stat: bool,
+}
+
+#[derive(Args)]
+pub struct MergeArgs {
+    /// Pull request event ID
+    pub event_id: String,
+
+    /// Branch to merge into (defaults to the current branch)
+    #[arg(long)]
+    pub into: Option<String>,
+
+    /// How to bring the patches into the branch
+    #[arg(long, value_enum, default_value_t)]
+    pub strategy: MergeStrategyValue,
+
+    /// Commit to apply the patches onto before merging (defaults to the PR's parent commit)
+    #[arg(long)]
+    pub base: Option<String>,
+
+    /// Optional message for the merged status
+    #[arg(long, short = 'm', default_value = "")]
+    pub message: String,
+
+    /// Also publish the repository state with the merged branch
+    #[arg(long)]
+    pub publish_state: bool,
+
+    /// Merge even if the PR is below the repository's proof-of-work requirement
+    #[arg(long)]
+    pub include_low_pow: bool,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+#[derive(Args)]
+pub struct MarkAppliedArgs {
+    /// Pull request event ID
+    pub event_id: String,
+
+    /// Commits the patches were applied as, or the merge commit that brought them in
+    #[arg(long = "commit", required = true, num_args = 1..)]
+    pub commits: Vec<String>,
+
+    /// Optional message for the merged status
+    #[arg(long, short = 'm', default_value = "")]
+    pub message: String,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+/// `pr mark-applied` result with `--format json`
+#[derive(Serialize)]
+struct MarkAppliedOutput {
+    #[serde(flatten)]
+    landed: LandedPatches,
+    status: BroadcastResult,
+}
+
+/// `pr merge` result with `--format json`
+#[derive(Serialize)]
+struct MergeOutput {
+    #[serde(flatten)]
+    merged: MergedPatches,
+    status: BroadcastResult,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<BroadcastResult>,
+}
+
+/// How `pr apply` ended
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum ApplyOutcome {
+    Applied,
+    /// Only checked with --check, nothing was committed
+    Checked,
+    Aborted,
+    /// Left stopped with conflicts to resolve later
+    Stopped,
+}
+
+/// `pr apply` result with `--format json`
+#[derive(Serialize)]
+struct ApplyOutput {
+    outcome: ApplyOutcome,
+    patches: Vec<AppliedPatch>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    signatures: Vec<CommitSignature>,
+}
+
+fn report_apply(
+    outcome: ApplyOutcome,
+    patches: Vec<AppliedPatch>,
+    signatures: Vec<CommitSignature>,
+) -> Result<()> {
+    if super::json_output() {
+        return super::print_json(&ApplyOutput {
+            outcome,
+            patches,
+            signatures,
+        });
+    }
+    Ok(())
+}
+
+/// `pr show` result with `--format json`
+#[derive(Serialize)]
+struct ShowOutput {
+    #[serde(flatten)]
+    details: PullRequestDetails,
+    signatures: Vec<CommitSignature>,
+}
+
+pub async fn handle_pr_command(command: PrCommands) -> Result<()> {
+    match command {
+        PrCommands::Status(args) => handle_status_command(args).await,
+        PrCommands::Apply(args) => handle_apply_command(args).await,
+        PrCommands::Fetch(args) => handle_fetch_command(args).await,
+        PrCommands::Show(args) => handle_show_command(args).await,
+        PrCommands::Merge(args) => handle_merge_command(args).await,
+        PrCommands::MarkApplied(args) => handle_mark_applied_command(args).await,
+    }
+}
+
+async fn handle_status_command(args: StatusArgs) -> Result<()> {
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let signer = repo_signer(&args.repo_path, args.password)?;
+    let coordinate = repo_coordinate(&args.repo_path, &repo_announcement.identifier)?;
+    let status = PullRequestStatus::from(args.status);
+
+    eprintln!(
+        "Setting status of {event_id} to {status}...",
+        event_id = args.event_id
+    );
+
+    let result = pull_request::set_pull_request_status(
+        network(),
+        signer.as_ref(),
+        &args.event_id,
+        status,
+        &coordinate,
+        &repo_announcement.relays,
+        &args.message,
+    )
+    .await?;
+
+    if result.successes.is_empty() {
+        bail!("Failed to publish status event to any relay");
+    }
+
+    eprintln!(
+        "✅ Status published to {count} relay(s)",
+        count = result.successes.len()
+    );
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+    if super::json_output() {
+        return super::print_json(&result);
+    }
+
+    Ok(())
+}
+
+async fn handle_apply_command(args: ApplyArgs) -> Result<()> {
+    if args.abort {
+        apply::abort_apply(&args.repo_path)?;
+        eprintln!("✅ Apply aborted, branch restored to its state before applying");
+        return report_apply(ApplyOutcome::Aborted, vec![], vec![]);
+    }
+    if args.continue_apply {
+        let outcome = match apply::continue_apply(&args.repo_path) {
+            Ok(()) => {
+                eprintln!("✅ Patch series applied");
+                ApplyOutcome::Applied
+            }
+            Err(e) => handle_conflict(e, &args.repo_path, args.interactive)?,
+        };
+        return report_apply(outcome, vec![], vec![]);
+    }
+    let event_id = args
+        .event_id
+        .as_deref()
+        .context("Pull request event ID is required")?;
+
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let series = fetch_series(event_id, &repo_announcement.relays).await?;
+    if !args.include_low_pow {
+        ensure_min_pow(&series, repo_announcement.min_pow)?;
+    }
+    // Checked against the keys declared before the patches land
+    let patches: Vec<_> = series
+        .patches
+        .iter()
+        .map(pull_request::summarize_patch)
+        .collect();
+    let signatures = commit_signatures::verify_patch_signatures(&args.repo_path, &patches)?;
+
+    let options = ApplyOptions {
+        check: args.check,
+        three_way: args.three_way || args.interactive,
+    };
+    let applied = match apply::apply_patches(&args.repo_path, &series.patch_contents(), options) {
+        Ok(applied) => applied,
+        Err(e) => {
+            eprint!("{signatures}", signatures = format_signatures(&signatures));
+            let outcome = handle_conflict(e, &args.repo_path, args.interactive)?;
+            return report_apply(outcome, vec![], signatures);
+        }
+    };
+
+    if args.check {
+        eprintln!(
+            "✅ All {count} patch(es) apply cleanly",
+            count = applied.len()
+        );
+    } else {
+        eprintln!("✅ Applied {count} patch(es):", count = applied.len());
+    }
+    for patch in &applied {
+        match &patch.commit {
+            Some(commit) => eprintln!(
+                "  {short} {subject}",
+                short = &commit[..8.min(commit.len())],
+                subject = patch.subject
+            ),
+            None => eprintln!("  {subject}", subject = patch.subject),
+        }
+    }
+    eprint!("{signatures}", signatures = format_signatures(&signatures));
+
+    let outcome = if args.check {
+        ApplyOutcome::Checked
+    } else {
+        ApplyOutcome::Applied
+    };
+    report_apply(outcome, applied, signatures)
+}
+
+/// Signature status of each commit, marked by how far it can be trusted
+fn format_signatures(signatures: &[CommitSignature]) -> String {
+    let mut output = String::from("Commit signatures:\n");
+    for signature in signatures {
+        let marker = match signature.status {
+            SignatureStatus::Verified { .. } => "✅",
+            SignatureStatus::Bad { .. } => "❌",
+            SignatureStatus::Unsigned
+            | SignatureStatus::UnknownKey { .. }
+            | SignatureStatus::Unverified { .. } => "⚠️ ",
+        };
+        output.push_str(&format!(
+            "  {marker} {line}\n",
+            line = commit_signatures::format_signature(signature)
+        ));
+    }
+    output
+}
+
+/// Report a stopped apply, and walk the user through resolving it when interactive
+fn handle_conflict(
+    error: GitsmithError,
+    repo_path: &Path,
+    interactive: bool,
+) -> Result<ApplyOutcome> {
+    let GitsmithError::Conflict(conflict) = error.root() else {
+        return Err(error.into());
+    };
+    eprintln!("⚠️  {conflict}");
+
+    if !interactive {
+        eprintln!(
+            "Resolve the conflicts, then run 'gitsmith pr apply --continue' or 'gitsmith pr apply --abort'"
+        );
+        return Err(error.into());
+    }
+    resolve_conflicts(repo_path)
+}
+
+/// Prompt loop to edit conflicted files, then continue or abort the apply
+fn resolve_conflicts(repo_path: &Path) -> Result<ApplyOutcome> {
+    loop {
+        let files = apply::conflicted_files(repo_path)?;
+        eprintln!("\nConflicted files:");
+        for (i, file) in files.iter().enumerate() {
+            let state = if apply::has_conflict_markers(repo_path, file).unwrap_or(true) {
+                "unresolved"
+            } else {
+                "resolved"
+            };
+            eprintln!("  {n}) {file} ({state})", n = i + 1);
+        }
+        eprint!("[e N] edit, [m N] merge tool, [c]ontinue, [a]bort, [q]uit: ");
+        io::stderr().flush()?;
+
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line)? == 0 {
+            // No more input, leave the apply stopped
+            return quit_resolving();
+        }
+        let mut words = line.split_whitespace();
+        let action = words.next().unwrap_or_default();
+        let file = words
+            .next()
+            .and_then(|n| n.parse::<usize>().ok())
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|i| files.get(i));
+
+        match (action, file) {
+            ("e", Some(file)) => {
+                if let Err(e) = run_editor(&repo_path.join(file)) {
+                    eprintln!("⚠️  {e}");
+                }
+            }
+            ("m", Some(file)) => {
+                let status = Command::new("git")
+                    .args(["mergetool", "--no-prompt", "--", file])
+                    .current_dir(repo_path)
+                    .status()
+                    .context("Failed to run git mergetool")?;
+                if !status.success() {
+                    eprintln!("⚠️  git mergetool exited with {status}");
+                }
+            }
+            ("c", _) => match apply::continue_apply(repo_path) {
+                Ok(()) => {
+                    eprintln!("✅ Patch series applied");
+                    return Ok(ApplyOutcome::Applied);
+                }
+                // The next patch conflicts too, keep resolving
+                Err(e) => eprintln!("⚠️  {e}"),
+            },
+            ("a", _) => {
+                apply::abort_apply(repo_path)?;
+                eprintln!("✅ Apply aborted, branch restored to its state before applying");
+                return Ok(ApplyOutcome::Aborted);
+            }
+            ("q", _) => return quit_resolving(),
+            ("e" | "m", None) => eprintln!("Pick a file by number, e.g. 'e 1'"),
+            _ => eprintln!("Unknown action '{action}'"),
+        }
+    }
+}
+
+fn quit_resolving() -> Result<ApplyOutcome> {
+    eprintln!(
+        "Apply stopped. Run 'gitsmith pr apply --continue' or 'gitsmith pr apply --abort' when ready"
+    );
+    Ok(ApplyOutcome::Stopped)
+}
+
+async fn handle_fetch_command(args: FetchArgs) -> Result<()> {
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let series = fetch_series(&args.event_id, &repo_announcement.relays).await?;
+    if !args.include_low_pow {
+        ensure_min_pow(&series, repo_announcement.min_pow)?;
+    }
+
+    let branch = args
+        .branch
+        .unwrap_or_else(|| format!("pr/{short_id}", short_id = series.short_id()));
+    let base = args.base.or_else(|| series.parent_commit());
+
+    let result = apply::create_branch_from_patches(
+        &args.repo_path,
+        &branch,
+        &series.patch_contents(),
+        base.as_deref(),
+        args.force,
+    )?;
+
+    eprintln!(
+        "✅ Created branch {branch} with {count} commit(s) on top of {base}",
+        branch = result.branch,
+        count = result.patches.len(),
+        base = &result.base[..8.min(result.base.len())]
+    );
+    eprintln!(
+        "   Check it out with: git checkout {branch}",
+        branch = result.branch
+    );
+    if super::json_output() {
+        return super::print_json(&result);
+    }
+
+    Ok(())
+}
+
+async fn handle_show_command(args: ShowArgs) -> Result<()> {
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    if args.stat {
+        return show_stat(&args, &repo_announcement.relays).await;
+    }
+
+    let coordinate = repo_coordinate(&args.repo_path, &repo_announcement.identifier)?;
+    let details = pull_request::fetch_pull_request(
+        network(),
+        &args.event_id,
+        &coordinate,
+        &repo_announcement.relays,
+        Duration::from_secs(5),
+    )
+    .await?;
+    super::mark_viewed(&details.pr.id, details.comments.len());
+
+    if args.json || super::json_output() {
+        let signatures =
+            commit_signatures::verify_patch_signatures(&args.repo_path, &details.patches)?;
+        return super::print_json(&ShowOutput {
+            details,
+            signatures,
+        });
+    }
+
+    if args.patch {
+        // Piped patches stay plain so `git am` can apply them
+        let annotate = io::stdout().is_terminal();
+        let mut shown = HashSet::new();
+        for patch in &details.patches {
+            if annotate {
+                print!(
+                    "{content}",
+                    content = comments::annotate_patch(
+                        &patch.content,
+                        &details.comments,
+                        &mut shown,
+                        time_format()
+                    )
+                );
+                continue;
+            }
+            print!("{content}", content = patch.content);
+            if !patch.content.ends_with('\n') {
+                println!();
+            }
+        }
+        return Ok(());
+    }
+
+    println!(
+        "{pr}",
+        pr = pull_request::format_pull_request_details(&details, time_format())
+    );
+    let signatures = commit_signatures::verify_patch_signatures(&args.repo_path, &details.patches)?;
+    if !signatures.is_empty() {
+        print!("{signatures}", signatures = format_signatures(&signatures));
+    }
+    if !details.comments.is_empty() {
+        println!("{:-<80}", "");
+        print!(
+            "{comments}",
+            comments = comments::format_thread(&details.comments, time_format())
+        );
+    }
+
+    Ok(())
+}
+
+/// Print the size of each patch without downloading the patches when the series has a manifest
+async fn show_stat(args: &ShowArgs, relays: &[String]) -> Result<()> {
+    let entries = pull_request::fetch_series_manifest(
+        network(),
+        &args.event_id,
+        relays,
+        Duration::from_secs(5),
+    )
+    .await?;
+
+    if args.json || super::json_output() {
+        let json = serde_json::to_string_pretty(&entries)?;
+        println!("{json}");
+        return Ok(());
+    }
+
+    let total = entries.len();
+    for (i, entry) in entries.iter().enumerate() {
+        println!(
+            "[{n}/{total}] {commit:.7} {subject} ({files} file(s), +{insertions} -{deletions})",
+            n = i + 1,
+            commit = entry.commit.as_deref().unwrap_or("-------"),
+            subject = entry.subject,
+            files = entry.files,
+            insertions = entry.insertions,
+            deletions = entry.deletions
+        );
+    }
+    println!(
+        "{total} patch(es), +{insertions} -{deletions}",
+        insertions = entries.iter().map(|e| e.insertions).sum::<usize>(),
+        deletions = entries.iter().map(|e| e.deletions).sum::<usize>()
+    );
+
+    Ok(())
+}
+
+async fn handle_merge_command(args: MergeArgs) -> Result<()> {
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let branch = match args.into {
+        Some(branch) => branch,
+        None => current_branch(&args.repo_path)?,
+    };
+    let series = fetch_series(&args.event_id, &repo_announcement.relays).await?;
+    if !args.include_low_pow {
+        ensure_min_pow(&series, repo_announcement.min_pow)?;
+    }
+    let signer = repo_signer(&args.repo_path, args.password)?;
+    let coordinate = repo_coordinate(&args.repo_path, &repo_announcement.identifier)?;
+
+    let merged = apply::merge_patches(
+        &args.repo_path,
+        &branch,
+        &series.patch_contents(),
+        args.strategy.into(),
+        args.base.as_deref(),
+        &format!(
+            "Merge pull request {short_id}: {title}\n",
+            short_id = series.short_id(),
+            title = series.title()
+        ),
+    )?;
+    eprintln!(
+        "✅ Merged {count} patch(es) into {branch}, now at {head:.8}",
+        count = merged.patches.len(),
+        head = merged.head
+    );
+
+    let commits = AppliedCommits {
+        merge_commit: merged.merge_commit.clone(),
+        applied_as_commits: merged
+            .patches
+            .iter()
+            .filter_map(|patch| patch.commit.clone())
+            .collect(),
+    };
+    let status = pull_request::publish_applied_status(
+        network(),
+        signer.as_ref(),
+        &series.pr.id.to_hex(),
+        &commits,
+        &coordinate,
+        &repo_announcement.relays,
+        &args.message,
+    )
+    .await?;
+    if status.successes.is_empty() {
+        bail!(
+            "Merged locally, but failed to publish the merged status to any relay. Retry with 'gitsmith pr status {event_id} --set merged'",
+            event_id = args.event_id
+        );
+    }
+    eprintln!(
+        "✅ Merged status published to {count} relay(s)",
+        count = status.successes.len()
+    );
+    for (relay, error) in &status.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+
+    let state = if args.publish_state {
+        let git_state = repo::get_publishable_state(&args.repo_path, &repo_announcement.identifier)
+            .context("Failed to get git state")?;
+        let result = repo::publish_git_state(
+            network(),
+            signer.as_ref(),
+            &git_state,
+            &repo_announcement.relays,
+        )
+        .await?;
+        if result.successes.is_empty() {
+            bail!("Failed to publish state event to any relay. Retry with 'gitsmith push'");
+        }
+        eprintln!(
+            "✅ State published to {count} relay(s)",
+            count = result.successes.len()
+        );
+        Some(result)
+    } else {
+        None
+    };
+
+    if super::json_output() {
+        return super::print_json(&MergeOutput {
+            merged,
+            status,
+            state,
+        });
+    }
+    Ok(())
+}
+
+async fn handle_mark_applied_command(args: MarkAppliedArgs) -> Result<()> {
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let series = fetch_series(&args.event_id, &repo_announcement.relays).await?;
+    let landed =
+        apply::find_applied_commits(&args.repo_path, &series.patch_contents(), &args.commits)?;
+    for patch in &landed.patches {
+        eprintln!(
+            "   {commit:.8} {subject}",
+            commit = patch.commit.as_deref().unwrap_or_default(),
+            subject = patch.subject
+        );
+    }
+    let signer = repo_signer(&args.repo_path, args.password)?;
+    let coordinate = repo_coordinate(&args.repo_path, &repo_announcement.identifier)?;
+
+    let commits = AppliedCommits {
+        merge_commit: landed.merge_commit.clone(),
+        applied_as_commits: landed
+            .patches
+            .iter()
+            .filter_map(|patch| patch.commit.clone())
+            .collect(),
+    };
+    let status = pull_request::publish_applied_status(
+        network(),
+        signer.as_ref(),
+        &series.pr.id.to_hex(),
+        &commits,
+        &coordinate,
+        &repo_announcement.relays,
+        &args.message,
+    )
+    .await?;
+    if status.successes.is_empty() {
+        bail!("Failed to publish the merged status to any relay");
+    }
+    eprintln!(
+        "✅ Merged status published to {count} relay(s)",
+        count = status.successes.len()
+    );
+    for (relay, error) in &status.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+
+    if super::json_output() {
+        return super::print_json(&MarkAppliedOutput { landed, status });
+    }
+    Ok(())
+}
+
+/// Name of the checked out branch
+fn current_branch(repo_path: &Path) -> Result<String> {
+    let git = git2::Repository::open(repo_path)
+        .with_context(|| format!("Failed to open {path}", path = repo_path.display()))?;
+    let head = git.head().context("Failed to read HEAD")?;
+    match head.shorthand() {
+        Some(branch) if head.is_branch() => Ok(branch.to_string()),
+        _ => bail!("HEAD is detached, pass the branch to merge into with --into"),
+    }
+}
+
+pub(super) async fn fetch_series(event_id: &str, relays: &[String]) -> Result<PatchSeries> {
+    eprintln!(
+        "Fetching pull request {event_id} from {count} relay(s)...",
+        count = relays.len()
+    );
+    Ok(
+        pull_request::fetch_patch_series(network(), event_id, relays, Duration::from_secs(5))
+            .await?,
+    )
+}
+
+pub(super) fn ensure_min_pow(series: &PatchSeries, min_pow: u8) -> Result<()> {
+    let meets_pow = std::iter::once(&series.pr)
+        .chain(&series.patches)
+        .all(|event| event.check_pow(min_pow));
+    if min_pow > 0 && !meets_pow {
+        bail!(
+            "Pull request is below the required proof-of-work difficulty {min_pow}. Use --include-low-pow to use it anyway"
+        );
+    }
+    Ok(())
+}
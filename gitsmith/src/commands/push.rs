@@ -0,0 +1,75 @@
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use gitsmith_core::{detect_from_git, repo};
+use std::path::PathBuf;
+
+use super::{network, repo_signer};
+
+#[derive(Args)]
+pub struct PushArgs {
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+
+    /// Output as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+pub async fn handle_push_command(args: PushArgs) -> Result<()> {
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let state = repo::get_publishable_state(&args.repo_path, &repo_announcement.identifier)
+        .context("Failed to get git state")?;
+
+    let signer = repo_signer(&args.repo_path, args.password)?;
+
+    eprintln!(
+        "Publishing state of {count} ref(s) to {relay_count} relay(s)...",
+        count = state.refs.len(),
+        relay_count = repo_announcement.relays.len()
+    );
+    let result = repo::publish_git_state(
+        network(),
+        signer.as_ref(),
+        &state,
+        &repo_announcement.relays,
+    )
+    .await?;
+
+    let json = args.json || super::json_output();
+    if json {
+        let json = serde_json::to_string_pretty(&result)?;
+        println!("{json}");
+    } else {
+        for relay in &result.successes {
+            eprintln!("  ✓ {relay}");
+        }
+        for (relay, error) in &result.failures {
+            eprintln!("  ✗ {relay}: {error}");
+        }
+    }
+
+    if result.successes.is_empty() {
+        bail!("Failed to publish state event to any relay");
+    }
+
+    if !json {
+        eprintln!(
+            "✅ State published to {count}/{total} relay(s)",
+            count = result.successes.len(),
+            total = result.successes.len() + result.failures.len()
+        );
+    }
+
+    Ok(())
+}
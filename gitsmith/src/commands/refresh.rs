@@ -0,0 +1,199 @@
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use gitsmith_core::{
+    BroadcastResult, RepoAnnouncement, Signer, detect_from_git, events, relay, repo,
+};
+use nostr_sdk::{Event, Timestamp};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::info;
+
+use super::{network, repo_coordinate, repo_signer};
+
+#[derive(Args)]
+pub struct RefreshArgs {
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Republish the announcement and state when older than this (e.g. 30m, 12h, 7d)
+    #[arg(long, default_value = "7d", env = "GITSMITH_REFRESH_MAX_AGE", value_parser = parse_age)]
+    pub max_age: Duration,
+
+    /// Keep running and check again every --interval
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// How often the daemon checks the events' age
+    #[arg(long, default_value = "1h", value_parser = parse_age, requires = "daemon")]
+    pub interval: Duration,
+
+    /// Only report which events would be republished
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+/// Parse an age like "90s", "30m", "12h" or "7d"
+fn parse_age(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .with_context(|| format!("Invalid duration: {value}"))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" | "" => 24 * 60 * 60,
+        _ => bail!("Invalid duration unit '{unit}', use s, m, h or d"),
+    };
+    let seconds = number
+        .checked_mul(seconds)
+        .with_context(|| format!("Duration is too long: {value}"))?;
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Result of one refresh with `--format json`, the daemon prints one per check
+#[derive(Serialize)]
+struct RefreshOutput {
+    announcement_stale: bool,
+    state_stale: bool,
+    /// How the relays took the republished events, absent when nothing was republished
+    #[serde(skip_serializing_if = "Option::is_none")]
+    republished: Option<BroadcastResult>,
+}
+
+pub async fn handle_refresh_command(args: RefreshArgs) -> Result<()> {
+    let local = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if local.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    if !args.daemon {
+        // Cron runs with nothing to refresh don't need the password
+        let mut signer = None;
+        let output = refresh(&args, &local, &mut signer).await?;
+        if super::json_output() {
+            return super::print_json(&output);
+        }
+        return Ok(());
+    }
+
+    // Decrypt once up front, the daemon can't prompt later
+    let mut signer = if args.dry_run {
+        None
+    } else {
+        Some(repo_signer(&args.repo_path, args.password.clone())?)
+    };
+    eprintln!(
+        "Refreshing every {interval}s, republishing events older than {max_age}s",
+        interval = args.interval.as_secs(),
+        max_age = args.max_age.as_secs()
+    );
+    loop {
+        match refresh(&args, &local, &mut signer).await {
+            Ok(output) if super::json_output() => super::print_json(&output)?,
+            Ok(_) => {}
+            Err(e) => eprintln!("⚠️  Refresh failed: {e:#}"),
+        }
+        tokio::time::sleep(args.interval).await;
+    }
+}
+
+/// Republish the announcement and state if they are missing or older than the maximum age
+async fn refresh(
+    args: &RefreshArgs,
+    local: &RepoAnnouncement,
+    signer: &mut Option<Box<dyn Signer>>,
+) -> Result<RefreshOutput> {
+    let coordinate = repo_coordinate(&args.repo_path, &local.identifier)?;
+    let (announcement, state) = repo::fetch_replaceable_events(
+        network(),
+        &coordinate,
+        &local.relays,
+        Duration::from_secs(10),
+    )
+    .await?;
+
+    let stale = |event: &Option<Event>, name: &str| -> bool {
+        let Some(event) = event else {
+            eprintln!("{name}: not found on relays");
+            return true;
+        };
+        let age = Timestamp::now()
+            .as_u64()
+            .saturating_sub(event.created_at.as_u64());
+        eprintln!("{name}: published {hours}h ago", hours = age / 3600);
+        age > args.max_age.as_secs()
+    };
+    let refresh_announcement = stale(&announcement, "Announcement");
+    let refresh_state = stale(&state, "State");
+    let mut output = RefreshOutput {
+        announcement_stale: refresh_announcement,
+        state_stale: refresh_state,
+        republished: None,
+    };
+
+    if !refresh_announcement && !refresh_state {
+        eprintln!("✅ Announcement and state are fresh");
+        return Ok(output);
+    }
+    if args.dry_run {
+        eprintln!("Would republish stale events (dry run)");
+        return Ok(output);
+    }
+
+    let signer: &dyn Signer = match signer {
+        Some(signer) => &**signer,
+        None => &**signer.insert(repo_signer(&args.repo_path, args.password.clone())?),
+    };
+
+    // Events that vanished from every relay are rebuilt from the local repository
+    let mut republish = Vec::new();
+    if refresh_announcement {
+        republish.push(match &announcement {
+            Some(event) => repo::refresh_event(event, signer).await?,
+            None => events::build_announcement_event(local, signer).await?,
+        });
+    }
+    if refresh_state {
+        republish.push(match &state {
+            Some(event) => repo::refresh_event(event, signer).await?,
+            None => {
+                let state = repo::get_publishable_state(&args.repo_path, &local.identifier)
+                    .context("Failed to get git state")?;
+                events::build_state_event(&state, signer).await?
+            }
+        });
+    }
+
+    let client = relay::connect(network(), &local.relays, Some(signer)).await?;
+    let result = relay::publish_events(&client, &republish).await?;
+    client.disconnect().await;
+    info!(count = republish.len(), "Republished replaceable events");
+
+    if result.successes.is_empty() {
+        bail!("Failed to republish to any relay");
+    }
+    eprintln!(
+        "✅ Republished {count} event(s) to {relays} relay(s)",
+        count = republish.len(),
+        relays = result.successes.len()
+    );
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+
+    output.republished = Some(result);
+    Ok(output)
+}
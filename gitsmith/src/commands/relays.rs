@@ -0,0 +1,756 @@
+use anyhow::{Context, Result, bail};
+use clap::{Args, Subcommand};
+use gitsmith_core::{
+    BroadcastResult, PublishConfig, PublishResult, RepoAnnouncement, announce_repository,
+    detect_from_git, events, patches,
+    relay::{self, RelayDrift, RelayHealth},
+    relay_migration::{self, MigrationReport},
+    relay_probe, repo,
+};
+use nostr_sdk::RelayUrl;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::{network, print_json, repo_coordinate, repo_signer};
+
+#[derive(Subcommand)]
+pub enum RelaysCommands {
+    /// List the relays configured for the repository
+    List(ListArgs),
+    /// Add relays to the repository and re-announce it
+    Add(EditArgs),
+    /// Remove relays from the repository and re-announce it
+    Remove(EditArgs),
+    /// Test relays for connectivity, write acceptance and NIP-11 capabilities
+    Test(TestArgs),
+    /// Check configured relays and compare them with the published announcement
+    Check(CheckArgs),
+    /// Replace the local relay configuration with the announced relays
+    AdoptRemote(AdoptRemoteArgs),
+    /// Re-announce the repository with the locally configured relays
+    PublishLocal(PublishLocalArgs),
+    /// Move the repository to another relay: copy and verify its events, then re-announce
+    Migrate(MigrateArgs),
+}
+
+#[derive(Args)]
+pub struct ListArgs {
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Output as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct EditArgs {
+    /// Relay URLs
+    #[arg(required = true)]
+    pub urls: Vec<String>,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Only change the git config, without re-announcing the repository
+    #[arg(long)]
+    pub no_announce: bool,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+#[derive(Args)]
+pub struct TestArgs {
+    /// Relay URLs to test (defaults to the configured relays)
+    pub urls: Vec<String>,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Timeout in seconds for each relay
+    #[arg(long, default_value = "10")]
+    pub timeout: u64,
+
+    /// Output as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct CheckArgs {
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Additional relays to look the announcement up on
+    #[arg(long = "relay", value_delimiter = ',')]
+    pub relays: Vec<String>,
+
+    /// Output as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct AdoptRemoteArgs {
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Additional relays to look the announcement up on
+    #[arg(long = "relay", value_delimiter = ',')]
+    pub relays: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct PublishLocalArgs {
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Additional relays to look the announcement up on
+    #[arg(long = "relay", value_delimiter = ',')]
+    pub relays: Vec<String>,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+#[derive(Args)]
+pub struct MigrateArgs {
+    /// Relay the repository moves away from
+    #[arg(long)]
+    pub from: String,
+
+    /// Relay the repository moves to
+    #[arg(long)]
+    pub to: String,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Timeout in seconds to connect to and fetch from each relay
+    #[arg(long, default_value = "10")]
+    pub timeout: u64,
+
+    /// Only count the events to copy, without changing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RelayReport {
+    health: Vec<RelayHealth>,
+    announced_relays: Option<Vec<String>>,
+    drift: Option<RelayDrift>,
+}
+
+/// Configured relays after `add`, `remove`, `adopt-remote` and `publish-local`, with `--format json`
+#[derive(Serialize)]
+struct RelaysOutput {
+    relays: Vec<String>,
+    /// Re-announcement, absent when the announcement was left as it is
+    #[serde(skip_serializing_if = "Option::is_none")]
+    announcement: Option<PublishResult>,
+}
+
+/// `relays migrate` result with `--format json`
+#[derive(Serialize)]
+struct MigrateOutput {
+    /// Relays announced, or that would be with --dry-run
+    relays: Vec<String>,
+    /// Events of the repository found on the old relay
+    events: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    copy: Option<MigrationReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    announcement: Option<BroadcastResult>,
+}
+
+fn report_relays(relays: Vec<String>, announcement: Option<PublishResult>) -> Result<()> {
+    if super::json_output() {
+        return print_json(&RelaysOutput {
+            relays,
+            announcement,
+        });
+    }
+    Ok(())
+}
+
+pub async fn handle_relays_command(command: RelaysCommands) -> Result<()> {
+    match command {
+        RelaysCommands::List(args) => handle_list_command(args),
+        RelaysCommands::Add(args) => handle_add_command(args).await,
+        RelaysCommands::Remove(args) => handle_remove_command(args).await,
+        RelaysCommands::Test(args) => handle_test_command(args).await,
+        RelaysCommands::Check(args) => handle_check_command(args).await,
+        RelaysCommands::AdoptRemote(args) => handle_adopt_remote_command(args).await,
+        RelaysCommands::PublishLocal(args) => handle_publish_local_command(args).await,
+        RelaysCommands::Migrate(args) => handle_migrate_command(args).await,
+    }
+}
+
+/// Fetch the published announcement of a local repository
+///
+/// Looked up on the configured relays plus `extra_relays`, so the announcement
+/// can still be found when every configured relay is gone.
+pub async fn fetch_remote_announcement(
+    repo_path: &Path,
+    local: &RepoAnnouncement,
+    extra_relays: &[String],
+) -> Result<Option<RepoAnnouncement>> {
+    let coordinate = repo_coordinate(repo_path, &local.identifier)?;
+    let mut lookup_relays = local.relays.clone();
+    lookup_relays.extend(extra_relays.iter().cloned());
+    if lookup_relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    Ok(repo::fetch_announcement(
+        network(),
+        &coordinate,
+        &lookup_relays,
+        Duration::from_secs(10),
+    )
+    .await?)
+}
+
+/// Print the relays that differ between local config and the announcement
+pub fn print_drift(drift: &RelayDrift) {
+    for relay in &drift.only_remote {
+        eprintln!("  + {relay} (announced, not configured locally)");
+    }
+    for relay in &drift.only_local {
+        eprintln!("  - {relay} (configured locally, not announced)");
+    }
+}
+
+/// Print how to reconcile the local relays with the announcement
+pub fn print_reconcile_hint() {
+    eprintln!("Run 'gitsmith relays adopt-remote' to use the announced relays locally,");
+    eprintln!("or 'gitsmith relays publish-local' to announce the local relays.");
+}
+
+async fn handle_check_command(args: CheckArgs) -> Result<()> {
+    let local = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if local.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    eprintln!("Checking {count} relay(s)...", count = local.relays.len());
+    let health =
+        relay::check_relays(network(), &local.relays, relay::DEFAULT_CONNECT_TIMEOUT).await?;
+    let remote = fetch_remote_announcement(&args.repo_path, &local, &args.relays).await?;
+    let drift = remote
+        .as_ref()
+        .map(|remote| relay::relay_drift(&local.relays, &remote.relays));
+
+    if args.json || super::json_output() {
+        let report = RelayReport {
+            health,
+            announced_relays: remote.map(|remote| remote.relays),
+            drift,
+        };
+        let json = serde_json::to_string_pretty(&report)?;
+        println!("{json}");
+        return Ok(());
+    }
+
+    for relay in &health {
+        if relay.connected {
+            eprintln!("  ✓ {url}", url = relay.url);
+        } else {
+            eprintln!(
+                "  ✗ {url} ({status})",
+                url = relay.url,
+                status = relay.status
+            );
+        }
+    }
+    let failing = health.iter().filter(|relay| !relay.connected).count();
+
+    let Some(drift) = drift else {
+        eprintln!("⚠️  Announcement not found on any relay");
+        eprintln!("Run 'gitsmith relays publish-local' to announce the local relays.");
+        return Ok(());
+    };
+
+    if drift.is_empty() {
+        if failing == 0 {
+            eprintln!("✅ Local relays match the announcement");
+        } else {
+            eprintln!(
+                "⚠️  {failing} of {total} relay(s) unreachable",
+                total = health.len()
+            );
+        }
+        return Ok(());
+    }
+
+    eprintln!("⚠️  Local relays differ from the announcement:");
+    print_drift(&drift);
+    print_reconcile_hint();
+
+    Ok(())
+}
+
+async fn handle_adopt_remote_command(args: AdoptRemoteArgs) -> Result<()> {
+    let local = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    let Some(remote) = fetch_remote_announcement(&args.repo_path, &local, &args.relays).await?
+    else {
+        bail!("Announcement not found on any relay. Pass a relay carrying it with --relay");
+    };
+    if remote.relays.is_empty() {
+        bail!("Announcement lists no relays");
+    }
+
+    let drift = relay::relay_drift(&local.relays, &remote.relays);
+    if drift.is_empty() {
+        eprintln!("✅ Local relays already match the announcement");
+        return report_relays(local.relays, None);
+    }
+
+    print_drift(&drift);
+    repo::save_relays(&args.repo_path, &remote.relays)
+        .context("Failed to save relay configuration")?;
+    eprintln!(
+        "✅ Saved {count} announced relay(s) to git config",
+        count = remote.relays.len()
+    );
+
+    report_relays(remote.relays, None)
+}
+
+async fn handle_publish_local_command(args: PublishLocalArgs) -> Result<()> {
+    let local = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if local.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+    let announcement =
+        announce_local_relays(&args.repo_path, &local, &args.relays, args.password).await?;
+    report_relays(local.relays, announcement)
+}
+
+/// Re-announce the repository with its locally configured relays
+///
+/// Everything else is kept from the published announcement, which is also
+/// looked up on `extra_relays`. Returns `None` when the announcement already
+/// lists the local relays.
+async fn announce_local_relays(
+    repo_path: &Path,
+    local: &RepoAnnouncement,
+    extra_relays: &[String],
+    password: Option<String>,
+) -> Result<Option<PublishResult>> {
+    let remote = fetch_remote_announcement(repo_path, local, extra_relays).await?;
+    let mut announcement = match remote {
+        Some(remote) => {
+            let drift = relay::relay_drift(&local.relays, &remote.relays);
+            if drift.is_empty() {
+                eprintln!("✅ Announcement already lists the local relays");
+                return Ok(None);
+            }
+            print_drift(&drift);
+            remote
+        }
+        None => local.clone(),
+    };
+    announcement.relays = local.relays.clone();
+
+    let signer = repo_signer(repo_path, password)?;
+    let config = PublishConfig {
+        timeout_secs: 30,
+        wait_for_send: true,
+    };
+    let result = announce_repository(network(), announcement, signer.as_ref(), config)
+        .await
+        .context("Failed to announce repository")?;
+
+    if result.successes.is_empty() {
+        bail!("Failed to announce repository to any relay");
+    }
+    eprintln!(
+        "✅ Announced local relays on {count} relay(s)",
+        count = result.successes.len()
+    );
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+
+    Ok(Some(result))
+}
+
+fn handle_list_command(args: ListArgs) -> Result<()> {
+    let local = detect_announced(&args.repo_path)?;
+
+    if args.json || super::json_output() {
+        return print_json(&local.relays);
+    }
+    if local.relays.is_empty() {
+        eprintln!("No relays configured. Add one with 'gitsmith relay add <URL>'");
+    }
+    for relay in &local.relays {
+        println!("{relay}");
+    }
+    Ok(())
+}
+
+/// Detect a repository that was announced with 'gitsmith init'
+///
+/// The relays in git config belong to the announcement, they are only read
+/// once the repository has an identifier and owner.
+pub fn detect_announced(repo_path: &Path) -> Result<RepoAnnouncement> {
+    let local = detect_from_git(repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+    if repo::get_repo_owner(repo_path)?.is_none() {
+        bail!("Repository not announced yet. Please run 'gitsmith init' first");
+    }
+    Ok(local)
+}
+
+/// Check that every URL is a valid relay URL
+pub fn validate_relay_urls(urls: &[String]) -> Result<()> {
+    for url in urls {
+        RelayUrl::parse(url).with_context(|| format!("Invalid relay URL: {url}"))?;
+    }
+    Ok(())
+}
+
+async fn handle_add_command(args: EditArgs) -> Result<()> {
+    validate_relay_urls(&args.urls)?;
+    let mut local = detect_announced(&args.repo_path)?;
+    // Previously configured relays still carry the old announcement
+    let previous = local.relays.clone();
+
+    let mut added = 0;
+    for url in &args.urls {
+        if local
+            .relays
+            .iter()
+            .any(|relay| relay::same_relay(relay, url))
+        {
+            eprintln!("  {url} is already configured");
+        } else {
+            local.relays.push(url.clone());
+            added += 1;
+        }
+    }
+    if added == 0 {
+        return report_relays(local.relays, None);
+    }
+    repo::save_relays(&args.repo_path, &local.relays)
+        .context("Failed to save relay configuration")?;
+    eprintln!("✅ Added {added} relay(s) to git config");
+
+    if args.no_announce {
+        return report_relays(local.relays, None);
+    }
+    let announcement =
+        announce_local_relays(&args.repo_path, &local, &previous, args.password).await?;
+    report_relays(local.relays, announcement)
+}
+
+async fn handle_remove_command(args: EditArgs) -> Result<()> {
+    let mut local = detect_announced(&args.repo_path)?;
+    for url in &args.urls {
+        if !local
+            .relays
+            .iter()
+            .any(|relay| relay::same_relay(relay, url))
+        {
+            bail!("{url} is not configured for the repository");
+        }
+    }
+    let previous = local.relays.clone();
+    local
+        .relays
+        .retain(|relay| !args.urls.iter().any(|url| relay::same_relay(relay, url)));
+    if local.relays.is_empty() {
+        bail!("Removing every relay would leave the repository unreachable, add another first");
+    }
+
+    repo::save_relays(&args.repo_path, &local.relays)
+        .context("Failed to save relay configuration")?;
+    eprintln!(
+        "✅ Removed {count} relay(s) from git config",
+        count = previous.len() - local.relays.len()
+    );
+
+    if args.no_announce {
+        return report_relays(local.relays, None);
+    }
+    // The removed relays are still asked for the published announcement
+    let announcement =
+        announce_local_relays(&args.repo_path, &local, &previous, args.password).await?;
+    report_relays(local.relays, announcement)
+}
+
+async fn handle_test_command(args: TestArgs) -> Result<()> {
+    let relays = if args.urls.is_empty() {
+        let local = detect_from_git(&args.repo_path)
+            .context("Failed to detect repository. Pass relay URLs to test")?;
+        if local.relays.is_empty() {
+            bail!("No relays configured for repository. Pass relay URLs to test");
+        }
+        local.relays
+    } else {
+        validate_relay_urls(&args.urls)?;
+        args.urls
+    };
+
+    let json = args.json || super::json_output();
+    if !json {
+        eprintln!("Testing {count} relay(s)...", count = relays.len());
+    }
+    let probes =
+        relay_probe::probe_relays(network(), &relays, Duration::from_secs(args.timeout)).await;
+    let failing = probes
+        .iter()
+        .filter(|probe| !probe.write.as_ref().is_some_and(|write| write.accepted))
+        .count();
+
+    if json {
+        print_json(&probes)?;
+    } else {
+        for probe in &probes {
+            print_probe(probe);
+        }
+    }
+
+    if failing > 0 {
+        bail!(
+            "{failing} of {total} relay(s) can't be published to",
+            total = probes.len()
+        );
+    }
+    if !json {
+        eprintln!("✅ All relays accept events");
+    }
+    Ok(())
+}
+
+fn print_probe(probe: &relay_probe::RelayProbe) {
+    let Some(write) = &probe.write else {
+        eprintln!(
+            "  ✗ {url}: unreachable ({error})",
+            url = probe.url,
+            error = probe.error.as_deref().unwrap_or("unknown error")
+        );
+        return;
+    };
+    let reason = write.message.as_deref().unwrap_or("no reason given");
+    match (write.accepted, write.deleted) {
+        (true, true) => eprintln!("  ✓ {url}: accepts events and deletions", url = probe.url),
+        (true, false) => eprintln!(
+            "  ✓ {url}: accepts events, rejected the deletion ({reason})",
+            url = probe.url
+        ),
+        (false, _) => eprintln!(
+            "  ✗ {url}: rejected the test event ({reason})",
+            url = probe.url
+        ),
+    }
+
+    match &probe.info {
+        Some(info) => {
+            let mut software = [
+                info.name.as_deref(),
+                info.software.as_deref(),
+                info.version.as_deref(),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+            if software.is_empty() {
+                software = "unnamed relay".to_string();
+            }
+            eprintln!("      {software}");
+            if let Some(nips) = &info.supported_nips {
+                let nips: Vec<String> = nips.iter().map(u16::to_string).collect();
+                eprintln!("      NIPs: {nips}", nips = nips.join(", "));
+            }
+        }
+        None => eprintln!(
+            "      No NIP-11 document ({error})",
+            error = probe.info_error.as_deref().unwrap_or("unknown error")
+        ),
+    }
+    for warning in probe.warnings() {
+        eprintln!("      ⚠️  {warning}");
+    }
+}
+
+async fn handle_migrate_command(args: MigrateArgs) -> Result<()> {
+    let local = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+    let coordinate = repo_coordinate(&args.repo_path, &local.identifier)?;
+    let (_kind, owner, _identifier) = patches::parse_repo_coordinate(&coordinate)?;
+    let timeout = Duration::from_secs(args.timeout);
+
+    let remote = fetch_remote_announcement(
+        &args.repo_path,
+        &local,
+        &[args.from.clone(), args.to.clone()],
+    )
+    .await?;
+    let maintainers = super::state::maintainer_keys(&coordinate, remote.as_ref())?;
+    let mut announcement = remote.unwrap_or_else(|| local.clone());
+    let announced = if announcement.relays.is_empty() {
+        &local.relays
+    } else {
+        &announcement.relays
+    };
+    let relays = relay_migration::replace_relay(announced, &args.from, &args.to);
+
+    if args.dry_run {
+        let client = relay::connect_with_minimum(
+            network(),
+            std::slice::from_ref(&args.from),
+            None,
+            1,
+            timeout,
+        )
+        .await?;
+        let history = relay_migration::fetch_repository_history(
+            network(),
+            &client,
+            &coordinate,
+            &maintainers,
+            timeout,
+        )
+        .await?;
+        client.disconnect().await;
+        eprintln!(
+            "Would copy {count} event(s) from {from} to {to} and announce:",
+            count = history.len(),
+            from = args.from,
+            to = args.to
+        );
+        for relay in &relays {
+            eprintln!("  {relay}");
+        }
+        if super::json_output() {
+            return print_json(&MigrateOutput {
+                relays,
+                events: history.len(),
+                copy: None,
+                announcement: None,
+            });
+        }
+        return Ok(());
+    }
+
+    // Ask for the password before the long copy, only the owner can re-announce
+    let signer = repo_signer(&args.repo_path, args.password)?;
+    if signer.public_key().to_hex() != owner {
+        bail!("Only the repository owner can move it to another relay");
+    }
+
+    eprintln!(
+        "Copying the events of '{identifier}' from {from} to {to}...",
+        identifier = local.identifier,
+        from = args.from,
+        to = args.to
+    );
+    let report = relay_migration::migrate_history(
+        network(),
+        &coordinate,
+        &maintainers,
+        &args.from,
+        &args.to,
+        timeout,
+    )
+    .await?;
+    if report.found == 0 {
+        bail!(
+            "No events of the repository found on {from}",
+            from = args.from
+        );
+    }
+    eprintln!(
+        "  {copied}/{found} event(s) accepted by {to}",
+        copied = report.copied,
+        found = report.found,
+        to = args.to
+    );
+    for (id, message) in &report.rejected {
+        eprintln!("  ✗ {id}: {message}");
+    }
+    if !report.is_verified() {
+        bail!(
+            "{count} event(s) are missing from {to}, the announcement is left unchanged. \
+             Run the migration again to retry",
+            count = report.missing.len(),
+            to = args.to
+        );
+    }
+    eprintln!(
+        "  ✓ {to} returns all {found} event(s)",
+        to = args.to,
+        found = report.found
+    );
+
+    // The old relay gets the new announcement too, pointing visitors to the new relays
+    announcement.relays = relays;
+    let event = events::build_announcement_event(&announcement, signer.as_ref()).await?;
+    let mut targets = announcement.relays.clone();
+    targets.push(args.from.clone());
+    let client = relay::connect(network(), &targets, Some(signer.as_ref())).await?;
+    let result = relay::publish_events(&client, &[event]).await;
+    client.disconnect().await;
+    let result = result?;
+    if result.successes.is_empty() {
+        bail!("Failed to announce repository to any relay");
+    }
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+    if !result
+        .successes
+        .iter()
+        .any(|relay| relay::same_relay(relay, &args.from))
+    {
+        eprintln!(
+            "Warning: {from} didn't take the new announcement, it still lists the old relays",
+            from = args.from
+        );
+    }
+
+    let local_relays = relay_migration::replace_relay(&local.relays, &args.from, &args.to);
+    repo::save_relays(&args.repo_path, &local_relays)
+        .context("Failed to save relay configuration")?;
+    eprintln!(
+        "✅ Moved to {to}, announced on {count} relay(s)",
+        to = args.to,
+        count = result.successes.len()
+    );
+    if super::json_output() {
+        return print_json(&MigrateOutput {
+            relays: announcement.relays,
+            events: report.found,
+            copy: Some(report),
+            announcement: Some(result),
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,236 @@
+use anyhow::{Context, Result, bail};
+use clap::{Args, Subcommand};
+use gitsmith_core::{
+    detect_from_git,
+    release::{self, Release, ReleaseOptions},
+};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::{format_timestamp, network, print_json, repo_coordinate, repo_signer};
+
+#[derive(Subcommand)]
+pub enum ReleaseCommands {
+    /// Upload artifacts to Blossom and publish a release of a git tag
+    Create(CreateArgs),
+
+    /// List the releases of the repository
+    List(ListArgs),
+
+    /// Download the artifacts of a release
+    Download(DownloadArgs),
+}
+
+#[derive(Args)]
+pub struct CreateArgs {
+    /// Git tag to release (must exist locally)
+    pub tag: String,
+
+    /// Release notes
+    #[arg(long, default_value = "")]
+    pub notes: String,
+
+    /// Read the release notes from a file
+    #[arg(long, conflicts_with = "notes")]
+    pub notes_file: Option<PathBuf>,
+
+    /// File to publish with the release (can be repeated)
+    #[arg(long = "artifact")]
+    pub artifacts: Vec<PathBuf>,
+
+    /// Blossom server to upload to (defaults to the repository's blossom servers)
+    #[arg(long = "server")]
+    pub servers: Vec<String>,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ListArgs {
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+}
+
+#[derive(Args)]
+pub struct DownloadArgs {
+    /// Git tag of the release
+    pub tag: String,
+
+    /// Only download the artifact with this name (can be repeated)
+    #[arg(long = "artifact")]
+    pub artifacts: Vec<String>,
+
+    /// Directory to save the artifacts in
+    #[arg(long, short = 'o', default_value = ".")]
+    pub output: PathBuf,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+}
+
+pub async fn handle_release_command(command: ReleaseCommands) -> Result<()> {
+    match command {
+        ReleaseCommands::Create(args) => handle_create(args).await,
+        ReleaseCommands::List(args) => handle_list(args).await,
+        ReleaseCommands::Download(args) => handle_download(args).await,
+    }
+}
+
+async fn handle_create(args: CreateArgs) -> Result<()> {
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let servers = if args.servers.is_empty() {
+        repo_announcement.blossom_servers.clone()
+    } else {
+        args.servers
+    };
+    if !args.artifacts.is_empty() && servers.is_empty() {
+        bail!("No Blossom servers configured. Pass --server or run 'gitsmith init --blossoms'");
+    }
+    let notes = match &args.notes_file {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {path}", path = path.display()))?,
+        None => args.notes,
+    };
+
+    let signer = repo_signer(&args.repo_path, args.password)?;
+    let coordinate = repo_coordinate(&args.repo_path, &repo_announcement.identifier)?;
+    let options = ReleaseOptions {
+        tag: args.tag,
+        notes,
+        artifacts: args.artifacts,
+        servers,
+    };
+    if !options.artifacts.is_empty() {
+        eprintln!(
+            "Uploading {count} artifact(s) to {servers} Blossom server(s)...",
+            count = options.artifacts.len(),
+            servers = options.servers.len()
+        );
+    }
+    let (release, result) = release::create_release(
+        network(),
+        signer.as_ref(),
+        &args.repo_path,
+        &coordinate,
+        &repo_announcement.identifier,
+        &repo_announcement.relays,
+        &options,
+    )
+    .await?;
+
+    if result.successes.is_empty() {
+        bail!("Failed to publish release to any relay");
+    }
+
+    eprintln!(
+        "✅ Release {tag} published to {count} relay(s)",
+        tag = release.tag,
+        count = result.successes.len()
+    );
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+    if super::json_output() {
+        return print_json(&release);
+    }
+    println!("{id}", id = release.id);
+
+    Ok(())
+}
+
+async fn fetch_releases(repo_path: &Path) -> Result<Vec<Release>> {
+    let repo_announcement = detect_from_git(repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let coordinate = repo_coordinate(repo_path, &repo_announcement.identifier)?;
+    Ok(release::list_releases(
+        network(),
+        &coordinate,
+        &repo_announcement.maintainers,
+        &repo_announcement.relays,
+        Duration::from_secs(10),
+    )
+    .await?)
+}
+
+async fn handle_list(args: ListArgs) -> Result<()> {
+    let releases = fetch_releases(&args.repo_path).await?;
+    if super::json_output() {
+        return print_json(&releases);
+    }
+    if releases.is_empty() {
+        eprintln!("No releases found");
+        return Ok(());
+    }
+
+    for release in &releases {
+        println!(
+            "{tag}  {date}  {count} artifact(s)",
+            tag = release.tag,
+            date = format_timestamp(release.created_at),
+            count = release.artifacts.len()
+        );
+        for artifact in &release.artifacts {
+            println!(
+                "    {name} ({size} bytes, sha256 {sha256})",
+                name = artifact.name,
+                size = artifact.size,
+                sha256 = &artifact.sha256[..16.min(artifact.sha256.len())]
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_download(args: DownloadArgs) -> Result<()> {
+    let releases = fetch_releases(&args.repo_path).await?;
+    // Releases are newest first, so this picks the latest one published for the tag
+    let Some(release) = releases.iter().find(|release| release.tag == args.tag) else {
+        bail!("No release found for tag {tag}", tag = args.tag);
+    };
+
+    let artifacts: Vec<_> = release
+        .artifacts
+        .iter()
+        .filter(|artifact| args.artifacts.is_empty() || args.artifacts.contains(&artifact.name))
+        .collect();
+    if artifacts.is_empty() {
+        bail!("Release {tag} has no matching artifacts", tag = release.tag);
+    }
+
+    let mut paths = Vec::new();
+    for artifact in artifacts {
+        eprintln!(
+            "Downloading {name} ({size} bytes)...",
+            name = artifact.name,
+            size = artifact.size
+        );
+        let path = release::download_artifact(artifact, &args.output).await?;
+        eprintln!("✅ {path} (sha256 verified)", path = path.display());
+        paths.push(path);
+    }
+    if super::json_output() {
+        return print_json(&paths);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,756 @@
+use anyhow::{Context, Result, bail, ensure};
+use clap::{Args, Subcommand};
+use gitsmith_core::{
+    PublishConfig, RepoAnnouncement, announce_repository,
+    coverage::{self, EventCounts},
+    detect_from_git, events, grasp, license, orgs, relay, repo, update_git_config_full,
+};
+use nostr_sdk::{PublicKey, ToBech32};
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::init;
+use super::org::resolve_org;
+use super::relays::{detect_announced, fetch_remote_announcement, validate_relay_urls};
+use super::{network, print_json, repo_coordinate, repo_signer};
+
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
+pub enum ReposCommands {
+    /// List the repositories of an organization
+    List(ListArgs),
+    /// Show how many events the repository has on its relays
+    Stats(StatsArgs),
+    /// Change the repository announcement and publish it again
+    Edit(EditArgs),
+    /// Ask relays to delete the repository announcement (NIP-09)
+    Retract(RetractArgs),
+}
+
+#[derive(Args)]
+pub struct ListArgs {
+    /// Organization address (naddr)
+    #[arg(long)]
+    pub org: String,
+
+    /// Additional relays to look the organization up on
+    #[arg(long = "relay", value_delimiter = ',')]
+    pub relays: Vec<String>,
+
+    /// Output as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct StatsArgs {
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Report which events each relay holds and highlight relays with gaps
+    #[arg(long)]
+    pub network: bool,
+
+    /// Output as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Default)]
+pub struct EditArgs {
+    /// New repository name
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// New repository description
+    #[arg(long)]
+    pub description: Option<String>,
+
+    /// New license (SPDX expression), an empty one removes it
+    #[arg(long)]
+    pub license: Option<String>,
+
+    /// Clone URL to add
+    #[arg(long = "add-clone-url", value_delimiter = ',')]
+    pub add_clone_urls: Vec<String>,
+
+    /// Clone URL to remove
+    #[arg(long = "remove-clone-url", value_delimiter = ',')]
+    pub remove_clone_urls: Vec<String>,
+
+    /// Web URL to add
+    #[arg(long = "add-web", value_delimiter = ',')]
+    pub add_web: Vec<String>,
+
+    /// Web URL to remove
+    #[arg(long = "remove-web", value_delimiter = ',')]
+    pub remove_web: Vec<String>,
+
+    /// Maintainer (npub or hex public key) to add
+    #[arg(long = "add-maintainer", value_delimiter = ',')]
+    pub add_maintainers: Vec<String>,
+
+    /// Maintainer to remove
+    #[arg(long = "remove-maintainer", value_delimiter = ',')]
+    pub remove_maintainers: Vec<String>,
+
+    /// CI key (npub or hex public key) whose check reports are trusted, to add
+    #[arg(long = "add-ci-key", value_delimiter = ',')]
+    pub add_ci_keys: Vec<String>,
+
+    /// CI key to remove
+    #[arg(long = "remove-ci-key", value_delimiter = ',')]
+    pub remove_ci_keys: Vec<String>,
+
+    /// Relay to add
+    #[arg(long = "add-relay", value_delimiter = ',')]
+    pub add_relays: Vec<String>,
+
+    /// Relay to remove
+    #[arg(long = "remove-relay", value_delimiter = ',')]
+    pub remove_relays: Vec<String>,
+
+    /// GRASP server to add
+    #[arg(long = "add-grasp-server", value_delimiter = ',')]
+    pub add_grasp_servers: Vec<String>,
+
+    /// GRASP server to remove
+    #[arg(long = "remove-grasp-server", value_delimiter = ',')]
+    pub remove_grasp_servers: Vec<String>,
+
+    /// Topic to add
+    #[arg(long = "add-topic", visible_alias = "topic", value_delimiter = ',')]
+    pub add_topics: Vec<String>,
+
+    /// Topic to remove
+    #[arg(long = "remove-topic", value_delimiter = ',')]
+    pub remove_topics: Vec<String>,
+
+    /// Publish the README as a long-form article linked from the announcement
+    /// (default: README.md)
+    #[arg(long, num_args = 0..=1, default_missing_value = "README.md")]
+    pub readme: Option<PathBuf>,
+
+    /// Publish only the first paragraph of the README
+    #[arg(long, requires = "readme")]
+    pub readme_summary: bool,
+
+    /// Show the changes without publishing them
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+#[derive(Args)]
+pub struct RetractArgs {
+    /// Why the repository is retracted, shown by clients
+    #[arg(long)]
+    pub reason: Option<String>,
+
+    /// Retract the repository state events too
+    #[arg(long)]
+    pub state: bool,
+
+    /// Don't ask for confirmation
+    #[arg(long, short)]
+    pub yes: bool,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+pub async fn handle_repos_command(command: ReposCommands) -> Result<()> {
+    match command {
+        ReposCommands::List(args) => handle_list_command(args).await,
+        ReposCommands::Stats(args) => handle_stats_command(args).await,
+        ReposCommands::Edit(args) => handle_edit_command(args).await,
+        ReposCommands::Retract(args) => handle_retract_command(args).await,
+    }
+}
+
+async fn handle_list_command(args: ListArgs) -> Result<()> {
+    let (org, relays) = resolve_org(&args.org, args.relays).await?;
+
+    eprintln!(
+        "Fetching {count} repositories of {name} from {relay_count} relay(s)...",
+        count = org.repositories.len(),
+        name = org.name,
+        relay_count = relays.len()
+    );
+    let members =
+        orgs::fetch_member_repositories(network(), &org, &relays, Duration::from_secs(10)).await?;
+
+    if args.json || super::json_output() {
+        let json = serde_json::to_string_pretty(&members)?;
+        println!("{json}");
+        return Ok(());
+    }
+
+    if members.is_empty() {
+        eprintln!("{name} has no repositories", name = org.name);
+        return Ok(());
+    }
+
+    for member in &members {
+        match &member.announcement {
+            Some(repo) => {
+                println!(
+                    "{name} ({identifier})",
+                    name = repo.name,
+                    identifier = repo.identifier
+                );
+                if !repo.description.is_empty() {
+                    println!("    {description}", description = repo.description);
+                }
+                if let Some(license) = &repo.license {
+                    println!("    License: {license}");
+                }
+                if let Some(clone_url) = repo.clone_urls.first() {
+                    println!("    {clone_url}");
+                }
+            }
+            None => println!(
+                "{coordinate} (announcement not found)",
+                coordinate = member.coordinate
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_stats_command(args: StatsArgs) -> Result<()> {
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+    let repo_coordinate = repo_coordinate(&args.repo_path, &repo_announcement.identifier)?;
+
+    eprintln!(
+        "Probing {count} relay(s)...",
+        count = repo_announcement.relays.len()
+    );
+    let report = coverage::repository_coverage(
+        network(),
+        &repo_coordinate,
+        &repo_announcement.relays,
+        Duration::from_secs(10),
+    )
+    .await?;
+
+    if args.json || super::json_output() {
+        let json = if args.network {
+            serde_json::to_string_pretty(&report)?
+        } else {
+            serde_json::to_string_pretty(&report.known)?
+        };
+        println!("{json}");
+        return Ok(());
+    }
+
+    println!("{name}", name = repo_announcement.name);
+    print_counts("Known events", &report.known);
+    if !args.network {
+        return Ok(());
+    }
+
+    println!();
+    let width = report.relays.iter().map(|r| r.url.len()).max().unwrap_or(0);
+    for relay in &report.relays {
+        let marker = if relay.has_gaps() { "⚠️ " } else { "✅" };
+        print!(
+            "{marker} {url:<width$}  {held}/{known}",
+            url = relay.url,
+            held = relay.held.total(),
+            known = report.known.total()
+        );
+        if let Some(error) = &relay.error {
+            print!("  ({error})");
+        } else if relay.has_gaps() {
+            print!("  missing {missing}", missing = describe(&relay.missing));
+        }
+        println!();
+    }
+
+    let gaps = report.relays_with_gaps().count();
+    let complete = report.relays.len() - gaps;
+    println!();
+    if gaps == 0 {
+        println!(
+            "✅ Every relay holds all {known} event(s)",
+            known = report.known.total()
+        );
+    } else {
+        println!("⚠️  {gaps} relay(s) with gaps; {complete} relay(s) hold a full copy");
+        println!(
+            "   'gitsmith relays publish-local' and 'gitsmith push' re-publish the announcement and state"
+        );
+    }
+
+    Ok(())
+}
+
+async fn handle_edit_command(args: EditArgs) -> Result<()> {
+    let local = detect_announced(&args.repo_path)?;
+    let owner = repo::get_repo_owner(&args.repo_path)?
+        .context("Repository owner not found in git config")?;
+    let owner = PublicKey::from_hex(&owner)?;
+
+    // Git config doesn't keep these, the published announcement does
+    let mut announcement = local.clone();
+    match fetch_remote_announcement(&args.repo_path, &local, &[]).await? {
+        Some(remote) => {
+            announcement.clone_urls = remote.clone_urls;
+            announcement.web = remote.web;
+            announcement.maintainers = remote.maintainers;
+            announcement.ci_keys = remote.ci_keys;
+            announcement.topics = remote.topics;
+            announcement.readme = remote.readme.or(announcement.readme);
+            if !remote.root_commit.is_empty() {
+                announcement.root_commit = remote.root_commit;
+            }
+        }
+        None => eprintln!(
+            "Warning: Published announcement not found, its clone URLs, web URLs, \
+             maintainers, CI keys and topics are taken from the local repository"
+        ),
+    }
+
+    let changes = apply_edits(&mut announcement, &owner, &args)?;
+    ensure!(
+        !changes.is_empty(),
+        "Nothing to change, pass e.g. --description or --add-maintainer"
+    );
+    ensure!(
+        !announcement.relays.is_empty(),
+        "Removing every relay would leave the repository unreachable, add another first"
+    );
+    for change in &changes {
+        eprintln!("  {change}");
+    }
+    if args.dry_run {
+        if super::json_output() {
+            return print_json(&announcement);
+        }
+        return Ok(());
+    }
+
+    let signer = repo_signer(&args.repo_path, args.password)?;
+    if signer.public_key() != owner {
+        bail!("Only the repository owner can edit its announcement");
+    }
+    if let Some(path) = &args.readme {
+        super::publish_readme(
+            signer.as_ref(),
+            &mut announcement,
+            &args.repo_path,
+            path,
+            args.readme_summary,
+        )
+        .await?;
+    }
+    let config = PublishConfig {
+        timeout_secs: 30,
+        wait_for_send: true,
+    };
+    let result = announce_repository(network(), announcement.clone(), signer.as_ref(), config)
+        .await
+        .context("Failed to announce repository")?;
+    if result.successes.is_empty() {
+        bail!("Failed to announce repository to any relay");
+    }
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+
+    update_git_config_full(
+        &args.repo_path,
+        &announcement,
+        &result.nostr_url,
+        &owner.to_bech32()?,
+    )
+    .context("Failed to save nostr configuration")?;
+    eprintln!(
+        "✅ Announced {count} change(s) on {relays} relay(s)",
+        count = changes.len(),
+        relays = result.successes.len()
+    );
+    if super::json_output() {
+        return print_json(&result);
+    }
+
+    Ok(())
+}
+
+async fn handle_retract_command(args: RetractArgs) -> Result<()> {
+    let local = detect_announced(&args.repo_path)?;
+    ensure!(
+        !local.relays.is_empty(),
+        "No relays configured for repository. Please run 'gitsmith init' first"
+    );
+    let owner = repo::get_repo_owner(&args.repo_path)?
+        .context("Repository owner not found in git config")?;
+    let owner = PublicKey::from_hex(&owner)?;
+    let coordinate = repo_coordinate(&args.repo_path, &local.identifier)?;
+
+    eprintln!(
+        "Looking up {identifier} on {count} relay(s)...",
+        identifier = local.identifier,
+        count = local.relays.len()
+    );
+    let (announcement, state) = repo::fetch_replaceable_events(
+        network(),
+        &coordinate,
+        &local.relays,
+        Duration::from_secs(10),
+    )
+    .await?;
+    // Relays that took the announcement from elsewhere should drop it too
+    let mut relays = local.relays.clone();
+    if let Some(published) = announcement
+        .as_ref()
+        .and_then(|event| events::parse_announcement_event(event).ok())
+    {
+        for url in published.relays {
+            if !relays.iter().any(|known| relay::same_relay(known, &url)) {
+                relays.push(url);
+            }
+        }
+    }
+    let mut event_ids: Vec<_> = announcement.iter().map(|event| event.id).collect();
+    if args.state {
+        event_ids.extend(state.iter().map(|event| event.id));
+    }
+    if announcement.is_none() {
+        eprintln!("Warning: The announcement wasn't found, relays are asked to delete it anyway");
+    }
+
+    if !args.yes {
+        ensure!(
+            io::stdin().is_terminal(),
+            "Refusing to retract {identifier} without a terminal to confirm, pass --yes",
+            identifier = local.identifier
+        );
+        let what = if args.state {
+            "announcement and state"
+        } else {
+            "announcement"
+        };
+        ensure!(
+            init::confirm(&format!(
+                "Ask {count} relay(s) to delete the {what} of {identifier}? Relays may ignore it",
+                count = relays.len(),
+                identifier = local.identifier
+            ))?,
+            "Aborted, nothing was published"
+        );
+    }
+
+    let signer = repo_signer(&args.repo_path, args.password)?;
+    if signer.public_key() != owner {
+        bail!("Only the repository owner can retract it");
+    }
+    let event = events::build_retraction_event(
+        &local.identifier,
+        &event_ids,
+        args.state,
+        args.reason.as_deref(),
+        signer.as_ref(),
+    )
+    .await?;
+    let client = relay::connect(network(), &relays, Some(signer.as_ref())).await?;
+    let result = relay::publish_events(&client, &[event]).await;
+    client.disconnect().await;
+    let result = result?;
+    if result.successes.is_empty() {
+        bail!("Failed to publish the deletion request to any relay");
+    }
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+    eprintln!(
+        "✅ Asked {count} relay(s) to delete {identifier}",
+        count = result.successes.len(),
+        identifier = local.identifier
+    );
+    eprintln!(
+        "   The git config still holds the nostr settings, 'gitsmith init' announces it again"
+    );
+    if super::json_output() {
+        return print_json(&result);
+    }
+
+    Ok(())
+}
+
+/// Apply the requested edits, returning a line per change
+fn apply_edits(
+    announcement: &mut RepoAnnouncement,
+    owner: &PublicKey,
+    args: &EditArgs,
+) -> Result<Vec<String>> {
+    let mut changes = Vec::new();
+
+    if let Some(name) = &args.name
+        && *name != announcement.name
+    {
+        ensure!(!name.is_empty(), "The name must not be empty");
+        changes.push(format!("name: {name}"));
+        announcement.name = name.clone();
+    }
+    if let Some(description) = &args.description
+        && *description != announcement.description
+    {
+        changes.push(format!("description: {description}"));
+        announcement.description = description.clone();
+    }
+    if let Some(license) = &args.license {
+        let license = Some(license.clone()).filter(|license| !license.is_empty());
+        if let Some(license) = &license {
+            license::validate_license(license)?;
+        }
+        if license != announcement.license {
+            changes.push(format!(
+                "license: {license}",
+                license = license.as_deref().unwrap_or("(none)")
+            ));
+            announcement.license = license;
+        }
+    }
+
+    validate_relay_urls(&args.add_relays)?;
+    let npubs = |values: &[String], what: &str| -> Result<Vec<String>> {
+        values
+            .iter()
+            .map(|value| {
+                PublicKey::parse(value)
+                    .with_context(|| format!("Invalid {what} {value}"))?
+                    .to_bech32()
+                    .context("Failed to convert public key to npub")
+            })
+            .collect()
+    };
+    let grasp_servers = |values: &[String]| -> Result<Vec<String>> {
+        values
+            .iter()
+            .map(|value| Ok(grasp::normalize_server(value)?))
+            .collect()
+    };
+
+    edit_list(
+        &mut announcement.grasp_servers,
+        "GRASP server",
+        &grasp_servers(&args.add_grasp_servers)?,
+        &grasp_servers(&args.remove_grasp_servers)?,
+        &mut changes,
+    )?;
+    // A GRASP server is listed as a clone URL and a relay of its own too,
+    // dropped before the other edits so an explicitly added relay stays
+    for server in grasp_servers(&args.remove_grasp_servers)? {
+        let relay_url = grasp::relay_url(&server);
+        announcement
+            .relays
+            .retain(|known| !relay::same_relay(known, &relay_url));
+        let clone_url = grasp::clone_url(&server, owner, &announcement.identifier)?;
+        announcement.clone_urls.retain(|url| *url != clone_url);
+    }
+    edit_list(
+        &mut announcement.clone_urls,
+        "clone URL",
+        &args.add_clone_urls,
+        &args.remove_clone_urls,
+        &mut changes,
+    )?;
+    edit_list(
+        &mut announcement.web,
+        "web URL",
+        &args.add_web,
+        &args.remove_web,
+        &mut changes,
+    )?;
+    edit_list(
+        &mut announcement.maintainers,
+        "maintainer",
+        &npubs(&args.add_maintainers, "maintainer")?,
+        &npubs(&args.remove_maintainers, "maintainer")?,
+        &mut changes,
+    )?;
+    edit_list(
+        &mut announcement.ci_keys,
+        "CI key",
+        &npubs(&args.add_ci_keys, "CI key")?,
+        &npubs(&args.remove_ci_keys, "CI key")?,
+        &mut changes,
+    )?;
+    edit_list(
+        &mut announcement.relays,
+        "relay",
+        &args.add_relays,
+        &args.remove_relays,
+        &mut changes,
+    )?;
+    edit_list(
+        &mut announcement.topics,
+        "topic",
+        &args.add_topics,
+        &args.remove_topics,
+        &mut changes,
+    )?;
+
+    // Published along with the announcement
+    if let Some(path) = &args.readme {
+        changes.push(format!("readme: {path}", path = path.display()));
+    }
+
+    // Every GRASP server that remains needs its relay
+    for server in &announcement.grasp_servers {
+        let relay_url = grasp::relay_url(server);
+        if !announcement
+            .relays
+            .iter()
+            .any(|known| relay::same_relay(known, &relay_url))
+        {
+            announcement.relays.push(relay_url);
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Add and remove values of one announcement list
+///
+/// Removing a value that isn't listed is an error, adding one that is isn't.
+/// URLs compare like relays do, ignoring case and a trailing slash.
+fn edit_list(
+    list: &mut Vec<String>,
+    label: &str,
+    add: &[String],
+    remove: &[String],
+    changes: &mut Vec<String>,
+) -> Result<()> {
+    for value in remove {
+        ensure!(
+            list.iter().any(|known| relay::same_relay(known, value)),
+            "{value} is not a {label} of the repository"
+        );
+        list.retain(|known| !relay::same_relay(known, value));
+        changes.push(format!("- {label} {value}"));
+    }
+    for value in add {
+        if !list.iter().any(|known| relay::same_relay(known, value)) {
+            list.push(value.clone());
+            changes.push(format!("+ {label} {value}"));
+        }
+    }
+    Ok(())
+}
+
+fn print_counts(label: &str, counts: &EventCounts) {
+    println!("{label}: {total}", total = counts.total());
+    println!("  Announcement:  {count}", count = counts.announcement);
+    println!("  State:         {count}", count = counts.state);
+    println!("  Pull requests: {count}", count = counts.pull_requests);
+    println!("  Patches:       {count}", count = counts.patches);
+}
+
+/// Non-zero counts as "1 announcement, 3 patches"
+fn describe(counts: &EventCounts) -> String {
+    [
+        (counts.announcement, "announcement"),
+        (counts.state, "state"),
+        (counts.pull_requests, "pull request(s)"),
+        (counts.patches, "patch(es)"),
+    ]
+    .iter()
+    .filter(|(count, _)| *count > 0)
+    .map(|(count, label)| format!("{count} {label}"))
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr_sdk::Keys;
+
+    fn announcement() -> RepoAnnouncement {
+        RepoAnnouncement {
+            identifier: "repo".to_string(),
+            name: "repo".to_string(),
+            description: "Old".to_string(),
+            clone_urls: vec!["https://example.com/repo.git".to_string()],
+            relays: vec!["wss://relay.one".to_string(), "wss://relay.two".to_string()],
+            web: vec![],
+            root_commit: "a".repeat(40),
+            maintainers: vec![],
+            ci_keys: vec![],
+            grasp_servers: vec![],
+            blossom_servers: vec![],
+            min_pow: 0,
+            license: None,
+            topics: vec![],
+            readme: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let owner = Keys::generate().public_key();
+        let maintainer = Keys::generate().public_key();
+        let ci = Keys::generate().public_key();
+        let mut announcement = announcement();
+        let args = EditArgs {
+            description: Some("New".to_string()),
+            add_maintainers: vec![maintainer.to_hex()],
+            add_ci_keys: vec![ci.to_bech32().unwrap()],
+            remove_relays: vec!["wss://relay.one/".to_string()],
+            add_relays: vec!["wss://relay.two".to_string()],
+            add_grasp_servers: vec!["grasp.example.com".to_string()],
+            ..Default::default()
+        };
+
+        let changes = apply_edits(&mut announcement, &owner, &args).unwrap();
+        assert_eq!(changes.len(), 5, "{changes:?}");
+        assert_eq!(announcement.description, "New");
+        assert_eq!(announcement.maintainers, [maintainer.to_bech32().unwrap()]);
+        assert_eq!(announcement.ci_keys, [ci.to_bech32().unwrap()]);
+        assert_eq!(
+            announcement.relays,
+            ["wss://relay.two", "wss://grasp.example.com"]
+        );
+        assert_eq!(announcement.grasp_servers, ["https://grasp.example.com"]);
+
+        // Editing again with the same values changes nothing
+        let args = EditArgs {
+            description: Some("New".to_string()),
+            add_maintainers: vec![maintainer.to_hex()],
+            ..Default::default()
+        };
+        assert!(
+            apply_edits(&mut announcement, &owner, &args)
+                .unwrap()
+                .is_empty()
+        );
+
+        let args = EditArgs {
+            remove_clone_urls: vec!["https://example.com/other.git".to_string()],
+            ..Default::default()
+        };
+        assert!(apply_edits(&mut announcement, &owner, &args).is_err());
+    }
+}
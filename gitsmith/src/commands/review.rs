@@ -0,0 +1,87 @@
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use gitsmith_core::{
+    detect_from_git,
+    reviews::{self, ReviewVerdict},
+};
+use std::path::PathBuf;
+
+use super::{network, repo_coordinate, repo_signer};
+
+#[derive(Args)]
+pub struct ReviewArgs {
+    /// Pull request event ID
+    pub event_id: String,
+
+    /// Approve the pull request
+    #[arg(
+        long,
+        conflicts_with = "request_changes",
+        required_unless_present = "request_changes"
+    )]
+    pub approve: bool,
+
+    /// Ask the author for changes before the pull request can be merged
+    #[arg(long)]
+    pub request_changes: bool,
+
+    /// Review text (markdown), required when requesting changes
+    #[arg(long, short = 'm', default_value = "")]
+    pub message: String,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+}
+
+pub async fn handle_review_command(args: ReviewArgs) -> Result<()> {
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+
+    if repo_announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+
+    let verdict = if args.approve {
+        ReviewVerdict::Approved
+    } else {
+        ReviewVerdict::ChangesRequested
+    };
+    let signer = repo_signer(&args.repo_path, args.password)?;
+    let coordinate = repo_coordinate(&args.repo_path, &repo_announcement.identifier)?;
+
+    let result = reviews::post_review(
+        network(),
+        signer.as_ref(),
+        &args.event_id,
+        verdict,
+        &args.message,
+        Some(&coordinate),
+        &repo_announcement.relays,
+    )
+    .await?;
+
+    if result.successes.is_empty() {
+        bail!("Failed to publish review to any relay");
+    }
+
+    eprintln!(
+        "✅ Review ({verdict}) published to {count} relay(s)",
+        count = result.successes.len()
+    );
+    for (relay, error) in &result.failures {
+        eprintln!("   - {relay}: {error}");
+    }
+    if super::json_output() {
+        return super::print_json(&result);
+    }
+    for event_id in &result.event_ids {
+        println!("{event_id}");
+    }
+
+    Ok(())
+}
@@ -1,18 +1,58 @@
-use anyhow::Result;
+use anyhow::{Context, Result, bail, ensure};
 use clap::Args;
-use gitsmith_core::{account, patches};
-use nostr_sdk::Client;
-use rpassword::read_password;
+use gitsmith_core::{
+    RepoAnnouncement, apply,
+    email::{self, PatchEmailOptions, SmtpProfile},
+    format_patch, patches, pull_request, relay, relay_probe, repo, secrets,
+    types::BroadcastResult,
+};
+use nostr_sdk::Event;
+use serde::Serialize;
+use std::fs;
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tracing::{debug, info, warn};
 
+use super::{network, print_json, repo_signer, run_editor};
+
+/// Pull request sent, as printed with `--format json`
+#[derive(Serialize)]
+struct SendOutput<'a> {
+    pr_id: Option<String>,
+    publish: &'a BroadcastResult,
+}
+
 #[derive(Args)]
 pub struct SendArgs {
-    /// Commits to send (e.g., HEAD~2)
+    /// Commits to send: a revision to send the commits after (HEAD~2, main, v1.0), A..B or A...B
     #[arg(default_value = "HEAD~1")]
     pub since: String,
 
+    /// Send the newest N commits of HEAD's history
+    #[arg(long, short = 'n', value_name = "N", conflicts_with_all = ["since", "since_last_send"])]
+    pub count: Option<usize>,
+
+    /// Send only the commits made since the last series sent from this branch
+    #[arg(long, conflicts_with = "since")]
+    pub since_last_send: bool,
+
+    /// Publish the patches of an mbox, like a saved git send-email thread, instead of commits
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["since", "since_last_send", "count"]
+    )]
+    pub from_mbox: Option<PathBuf>,
+
+    /// Publish the .patch files of a directory instead of commits
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["since", "since_last_send", "count", "from_mbox"]
+    )]
+    pub from_patch_dir: Option<PathBuf>,
+
     /// Title for the pull request
     #[arg(long, short = 't')]
     pub title: Option<String>,
@@ -21,10 +61,58 @@ pub struct SendArgs {
     #[arg(long, short = 'd')]
     pub description: Option<String>,
 
+    /// Label to attach (can be repeated)
+    #[arg(long = "label", short = 'l')]
+    pub labels: Vec<String>,
+
     /// Reply to an existing PR (event ID)
     #[arg(long)]
     pub in_reply_to: Option<String>,
 
+    /// Publish a new version of an existing PR (ID of the PR or any of its revisions)
+    #[arg(long, value_name = "PR_ID", conflicts_with = "in_reply_to")]
+    pub revise: Option<String>,
+
+    /// Add a cover letter (patch 0/N); opens an editor unless given as --cover-letter=TEXT
+    #[arg(long, value_name = "TEXT", num_args = 0..=1, require_equals = true)]
+    pub cover_letter: Option<Option<String>>,
+
+    /// Thread patches with a single unmarked reference, as gitsmith did before ngit interop
+    #[arg(long)]
+    pub legacy_threading: bool,
+
+    /// Publish even if the patches look like they contain secrets
+    #[arg(long)]
+    pub allow_secrets: bool,
+
+    /// Largest event in bytes, larger patches are refused unless split
+    #[arg(long, default_value_t = patches::DEFAULT_MAX_EVENT_SIZE)]
+    pub max_event_size: usize,
+
+    /// Send patches over --max-event-size in parts that fetching puts back together
+    #[arg(long)]
+    pub split_large_patches: bool,
+
+    /// Send to every relay even when its NIP-11 limits would reject the events
+    #[arg(long)]
+    pub ignore_relay_limits: bool,
+
+    /// Also email the patch series to this address (can be repeated)
+    #[arg(long = "also-email", value_name = "ADDRESS")]
+    pub also_email: Vec<String>,
+
+    /// git send-email identity to read SMTP settings from (sendemail.<profile>.*)
+    #[arg(long, requires = "also_email")]
+    pub smtp_profile: Option<String>,
+
+    /// SMTP password (overrides sendemail.smtpPass)
+    #[arg(long, env = "GITSMITH_SMTP_PASSWORD")]
+    pub smtp_password: Option<String>,
+
+    /// Send to your fork instead of the upstream repository it was forked from
+    #[arg(long)]
+    pub to_fork: bool,
+
     /// Repository path
     #[arg(long, default_value = ".")]
     pub repo_path: PathBuf,
@@ -37,40 +125,83 @@ pub struct SendArgs {
 pub async fn handle_send_command(args: SendArgs) -> Result<()> {
     info!(repository = %args.repo_path.display(), "Starting send command for repository");
 
-    // Get account keys
-    debug!("Getting account keys");
-    let password = if let Some(pwd) = args.password {
-        pwd
-    } else {
-        eprint!("Enter password: ");
-        io::stderr().flush()?;
-        read_password()?
-    };
-    let keys = account::get_active_keys(&password)?;
-    info!("Account keys loaded successfully");
+    // Get account signer
+    debug!("Getting account signer");
+    let signer = repo_signer(&args.repo_path, args.password.clone())?;
+    info!("Account signer loaded successfully");
 
     // Get repository info
     debug!(path = %args.repo_path.display(), "Detecting repository info");
-    let repo_announcement = gitsmith_core::detect_from_git(&args.repo_path)?;
-    info!(name = %repo_announcement.name, identifier = %repo_announcement.identifier, "Repository detected");
-
-    // Generate patches
-    eprintln!("Generating patches from {since}...", since = args.since);
-    debug!(since = %args.since, "Generating patches from commit range");
-    let patches = patches::generate_patches(&args.repo_path, Some(&args.since), None)?;
-    info!(count = patches.len(), "Generated patches from commits");
-
-    if patches.is_empty() {
-        warn!(since = %args.since, "No patches to send - no commits in range");
-        eprintln!("No patches to send");
-        return Ok(());
+    let mut repo_announcement = gitsmith_core::detect_from_git(&args.repo_path)?;
+    // Forks send to the repository they were forked from
+    let upstream = if args.to_fork {
+        None
+    } else {
+        repo::get_upstream(&args.repo_path)?
+    };
+    if let Some(upstream) = &upstream {
+        repo_announcement = upstream_announcement(upstream, repo_announcement).await;
+        eprintln!(
+            "Sending to upstream {identifier}, pass --to-fork to send to your fork",
+            identifier = upstream.identifier
+        );
     }
+    info!(name = %repo_announcement.name, identifier = %repo_announcement.identifier, "Repository detected");
 
-    eprintln!("Generated {count} patch(es)", count = patches.len());
+    let imported = import_patches(&args)?;
+    // Only a series of local commits moves the --since-last-send marker
+    let (patches, commits, imported_cover_letter, tip) = match imported {
+        Some(series) => {
+            eprintln!("Read {count} patch(es)", count = series.patches.len());
+            // Commits are tagged when the series was made from this repository
+            let commits = patches::commit_metadata(&args.repo_path, &series.patches)
+                .inspect_err(|e| debug!("Imported patches have no local commits: {e}"))
+                .unwrap_or_default();
+            (series.patches, commits, series.cover_letter, None)
+        }
+        None => {
+            let since = if args.since_last_send {
+                repo::last_send(&args.repo_path)?.context(
+                    "No series was sent from this branch yet, pass a commit range (e.g. HEAD~2) instead",
+                )?
+            } else {
+                args.since.clone()
+            };
+            let range = args.count.is_none().then_some(since.as_str());
+
+            // Generate patches
+            match args.count {
+                Some(count) => eprintln!("Generating patches from the last {count} commit(s)..."),
+                None => eprintln!("Generating patches from {since}..."),
+            }
+            debug!(?range, count = ?args.count, "Generating patches from commit range");
+            let patches = patches::generate_patches(&args.repo_path, range, args.count)?;
+            info!(count = patches.len(), "Generated patches from commits");
+
+            if patches.is_empty() {
+                warn!(since = %since, "No patches to send - no commits in range");
+                eprintln!("No patches to send");
+                return Ok(());
+            }
+
+            eprintln!("Generated {count} patch(es)", count = patches.len());
+            let commits = patches::commit_metadata(&args.repo_path, &patches)?;
+            let tip = commits.last().map(|commit| commit.commit.clone());
+            (patches, commits, None, tip)
+        }
+    };
+    check_secrets(&patches, args.allow_secrets)?;
+    let imported_cover_letter = imported_cover_letter
+        .as_deref()
+        .map(apply::parse_patch)
+        .transpose()
+        .context("Failed to read the cover letter")?;
 
     // Get title and description
     let title = if let Some(t) = args.title {
         t
+    } else if let Some(cover_letter) = &imported_cover_letter {
+        cover_letter.subject.clone()
     } else {
         eprint!("Enter PR title: ");
         io::stderr().flush()?;
@@ -81,6 +212,8 @@ pub async fn handle_send_command(args: SendArgs) -> Result<()> {
 
     let description = if let Some(d) = args.description {
         d
+    } else if let Some(cover_letter) = &imported_cover_letter {
+        cover_letter.body.clone()
     } else {
         eprint!("Enter PR description (optional): ");
         io::stderr().flush()?;
@@ -89,25 +222,95 @@ pub async fn handle_send_command(args: SendArgs) -> Result<()> {
         desc.trim().to_string()
     };
 
+    let cover_letter = match args.cover_letter {
+        None => imported_cover_letter.map(|cover_letter| cover_letter.body),
+        Some(Some(text)) => Some(text),
+        Some(None) => Some(edit_cover_letter(&args.repo_path, &title, &description)?),
+    };
+
+    // Prepare the email copy up front so configuration errors surface before publishing
+    let emails = if args.also_email.is_empty() {
+        None
+    } else {
+        let mut profile =
+            SmtpProfile::from_git_config(&args.repo_path, args.smtp_profile.as_deref())?;
+        if args.smtp_password.is_some() {
+            profile.password = args.smtp_password.clone();
+        }
+        let messages = email::build_patch_emails(
+            &patches,
+            &PatchEmailOptions {
+                from: profile.from.clone(),
+                to: args.also_email.clone(),
+                title: title.clone(),
+                description: cover_letter.clone().unwrap_or_else(|| description.clone()),
+            },
+        )?;
+        Some((profile, messages))
+    };
+
     // Create repository coordinate
-    let repo_coordinate = format!(
-        "30617:{pubkey}:{identifier}",
-        pubkey = keys.public_key(),
-        identifier = repo_announcement.identifier
-    );
+    let repo_coordinate = match &upstream {
+        Some(upstream) => upstream.coordinate(),
+        None => format!(
+            "30617:{pubkey}:{identifier}",
+            pubkey = signer.public_key(),
+            identifier = repo_announcement.identifier
+        ),
+    };
     debug!(coordinate = %repo_coordinate, "Repository coordinate created");
 
+    // Mine to the repository's advertised proof-of-work requirement
+    let target = gitsmith_core::repo::pull_request_target(
+        network(),
+        &repo_announcement,
+        &repo_coordinate,
+        &repo_announcement.relays,
+    )
+    .await;
+    if target.pow_difficulty > 0 {
+        eprintln!(
+            "Mining proof of work (difficulty {pow_difficulty})...",
+            pow_difficulty = target.pow_difficulty
+        );
+    }
+
+    let revision = match &args.revise {
+        Some(pr_id) => {
+            Some(resolve_revision(pr_id, &repo_coordinate, &repo_announcement.relays).await?)
+        }
+        None => None,
+    };
+
     // Create PR events
     debug!(title = %title, "Creating PR events");
+    let options = patches::PullRequestOptions {
+        reply_to: args.in_reply_to,
+        revision,
+        pow_difficulty: target.pow_difficulty,
+        cover_letter,
+        commits,
+        maintainers: target.maintainers,
+        layout: if args.legacy_threading {
+            patches::PatchLayout::Legacy
+        } else {
+            patches::PatchLayout::Ngit
+        },
+        max_event_size: Some(args.max_event_size),
+        split_large_patches: args.split_large_patches,
+        labels: args.labels,
+        external_url: None,
+    };
     let events = patches::create_pull_request_event(
-        &keys,
+        signer.as_ref(),
         &repo_coordinate,
         &title,
         &description,
         patches,
         &repo_announcement.root_commit,
-        args.in_reply_to,
-    )?;
+        &options,
+    )
+    .await?;
 
     info!(
         count = events.len(),
@@ -125,82 +328,255 @@ pub async fn handle_send_command(args: SendArgs) -> Result<()> {
 
     debug!(relays = ?repo_announcement.relays, "Configured relays");
 
-    let client = Client::new(keys.clone());
+    // Relays whose limits the events exceed would reject them halfway through the series
+    let relays = if args.ignore_relay_limits {
+        repo_announcement.relays.clone()
+    } else {
+        relays_within_limits(&repo_announcement.relays, &events).await?
+    };
 
-    for relay_url in &repo_announcement.relays {
-        debug!(%relay_url, "Adding relay");
-        client.add_relay(relay_url).await?;
-    }
+    let client = relay::connect(network(), &relays, Some(signer.as_ref())).await?;
 
-    info!(
-        count = repo_announcement.relays.len(),
-        "Connecting to relays"
-    );
-    client.connect().await;
+    eprintln!("Sending PR to {count} relay(s)...", count = relays.len());
 
-    eprintln!(
-        "Sending PR to {count} relay(s)...",
-        count = repo_announcement.relays.len()
-    );
+    // Relays are sent to concurrently, each one gets the patches before the PR
+    let result = relay::publish_events(&client, &events).await;
+    client.disconnect().await;
+    let result = result?;
 
-    // Send events with a small delay between them to avoid overwhelming public relays
-    // This is especially important for multi-patch PRs which create multiple events
-    let mut total_successes = std::collections::HashSet::new();
-    let mut total_failures = std::collections::HashMap::new();
+    if !result.successes.is_empty() {
+        info!(
+            relay_count = result.successes.len(),
+            "All events sent successfully to relays"
+        );
+        eprintln!(
+            "✅ Pull request sent to {count} relay(s)!",
+            count = result.successes.len()
+        );
+    }
 
-    for (i, event) in events.iter().enumerate() {
-        if i > 0 {
-            debug!(
-                delay_ms = 500,
-                "Waiting before sending next event to avoid overwhelming relays"
+    if !result.failures.is_empty() {
+        warn!(failures = ?result.failures, "Failed to send to some relays");
+        eprintln!(
+            "⚠️  Failed to send to {count} relay(s)",
+            count = result.failures.len()
+        );
+        for (relay, msg) in &result.failures {
+            let accepted = result.accepted.get(relay).map_or(0, Vec::len);
+            eprintln!(
+                "   - {relay}: {msg} ({accepted}/{total} event(s) accepted)",
+                total = result.event_ids.len()
             );
-            // Add a 500ms delay between events to give relays time to process
-            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
         }
-        debug!(event_num = i + 1, total = events.len(), kind = %event.kind, id = %event.id, "Sending event");
+    }
 
-        let output = client.send_event(event).await?;
+    if result.successes.is_empty() {
+        anyhow::bail!("Failed to send events to any relay");
+    }
 
-        // Track successes
-        for relay in output.success {
-            total_successes.insert(relay.to_string());
+    // Mailed only once the series is on Nostr, the PR is out even if SMTP fails
+    if let Some((profile, messages)) = emails {
+        eprintln!(
+            "Emailing {count} message(s) to {recipients} via {server}...",
+            count = messages.len(),
+            recipients = args.also_email.join(", "),
+            server = profile.server
+        );
+        match email::send_emails(&profile, messages).await {
+            Ok(()) => eprintln!("✅ Patch series emailed"),
+            Err(e) => {
+                warn!("Failed to email the patch series: {e}");
+                eprintln!("⚠️  Failed to email the patch series: {e}");
+            }
         }
+    }
 
-        // Track failures
-        for (relay, msg) in output.failed {
-            total_failures.insert(relay.to_string(), msg);
-        }
+    // The PR event is published last, its ID identifies the PR in other commands
+    let pr_id = result.event_ids.last().map(|id| id.to_hex());
+    if super::json_output() {
+        print_json(&SendOutput {
+            pr_id,
+            publish: &result,
+        })?;
+    } else if let Some(pr_id) = pr_id {
+        println!("{pr_id}");
+    }
 
-        info!(
-            event_num = i + 1,
-            total = events.len(),
-            "Event sent successfully"
-        );
+    // Remembered for --since-last-send
+    if let Some(tip) = tip
+        && let Err(e) = repo::record_last_send(&args.repo_path, &tip)
+    {
+        warn!("Failed to record the last sent commit: {e}");
     }
 
-    // Report results
-    let success_count = total_successes.len();
-    let failure_count = total_failures.len();
+    Ok(())
+}
 
-    if success_count > 0 {
-        info!(
-            relay_count = success_count,
-            "All events sent successfully to relays"
+/// Patches read from --from-mbox or --from-patch-dir
+fn import_patches(args: &SendArgs) -> Result<Option<format_patch::ImportedSeries>> {
+    let messages = if let Some(path) = &args.from_mbox {
+        eprintln!("Reading patches from {path}...", path = path.display());
+        let mbox = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {path}", path = path.display()))?;
+        format_patch::split_mbox(&mbox)
+    } else if let Some(dir) = &args.from_patch_dir {
+        eprintln!("Reading patches from {dir}...", dir = dir.display());
+        format_patch::read_patch_dir(dir)?
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(format_patch::import_series(&messages)?))
+}
+
+/// Relays whose advertised NIP-11 limits the events fit, reporting the others
+/// Announcement of the upstream repository, or the fork's own one pointed at
+/// the upstream relays when it can't be fetched
+async fn upstream_announcement(
+    upstream: &repo::NostrUrl,
+    mut fork: RepoAnnouncement,
+) -> RepoAnnouncement {
+    let coordinate = upstream.coordinate();
+    match repo::fetch_announcement(
+        network(),
+        &coordinate,
+        &upstream.relays,
+        Duration::from_secs(5),
+    )
+    .await
+    {
+        Ok(Some(mut announcement)) => {
+            if announcement.relays.is_empty() {
+                announcement.relays = upstream.relays.clone();
+            }
+            return announcement;
+        }
+        Ok(None) => warn!("Upstream announcement {coordinate} not found"),
+        Err(e) => warn!("Failed to fetch upstream announcement {coordinate}: {e}"),
+    }
+    fork.identifier = upstream.identifier.clone();
+    fork.relays = upstream.relays.clone();
+    fork
+}
+
+async fn relays_within_limits(relays: &[String], events: &[Event]) -> Result<Vec<String>> {
+    let checks =
+        relay_probe::check_payload(network(), relays, events, relay::DEFAULT_CONNECT_TIMEOUT).await;
+    let mut accepting = Vec::with_capacity(checks.len());
+    for check in checks {
+        for warning in &check.warnings {
+            eprintln!("Warning: {url}: {warning}", url = check.url);
+        }
+        if check.accepts() {
+            accepting.push(check.url);
+            continue;
+        }
+        warn!(relay = %check.url, problems = ?check.problems, "Skipping relay");
+        eprintln!(
+            "⚠️  Skipping {url}, its limits would reject the pull request:",
+            url = check.url
         );
-        eprintln!("✅ Pull request sent to {} relay(s)!", success_count);
+        for problem in &check.problems {
+            eprintln!("   - {problem}");
+        }
     }
+    ensure!(
+        !accepting.is_empty(),
+        "Every relay's limits would reject the pull request, pass --ignore-relay-limits to send anyway"
+    );
+    Ok(accepting)
+}
+
+/// Find the PR being revised and the version number of the new series
+///
+/// Revising a revision is allowed; the new series is still grouped under the original PR.
+async fn resolve_revision(
+    pr_id: &str,
+    repo_coordinate: &str,
+    relays: &[String],
+) -> Result<patches::Revision> {
+    ensure!(
+        !relays.is_empty(),
+        "No relays configured for repository. Please run 'gitsmith init' first"
+    );
+    let prs = pull_request::list_pull_requests(network(), repo_coordinate, relays.to_vec()).await?;
+    let Some(pr) = prs
+        .iter()
+        .find(|pr| pr.id == pr_id || pr.revisions.iter().any(|r| r.id == pr_id))
+    else {
+        bail!("Pull request {pr_id} not found on any relay");
+    };
+
+    let version = pr.version() + 1;
+    eprintln!("Publishing v{version} of \"{title}\"", title = pr.title);
+    Ok(patches::Revision {
+        pr_id: pr.id.clone(),
+        version,
+    })
+}
 
-    if failure_count > 0 {
-        warn!(failure_count, failures = ?total_failures, "Failed to send to some relays");
-        eprintln!("⚠️  Failed to send to {} relay(s)", failure_count);
-        for (relay, msg) in &total_failures {
-            eprintln!("   - {}: {}", relay, msg);
+/// Report possible secrets in the patches and refuse to go on unless allowed
+///
+/// Events can't be deleted from every relay, so this runs before anything is
+/// published or emailed.
+fn check_secrets(patches: &[String], allow_secrets: bool) -> Result<()> {
+    let total = patches.len();
+    let mut count = 0;
+    for (i, patch) in patches.iter().enumerate() {
+        for finding in secrets::scan_patch(patch) {
+            eprintln!(
+                "  [{n}/{total}] {file}:{line}: {rule} ({excerpt})",
+                n = i + 1,
+                file = finding.file,
+                line = finding.line,
+                rule = finding.rule,
+                excerpt = finding.excerpt
+            );
+            count += 1;
         }
     }
 
-    if success_count == 0 {
-        anyhow::bail!("Failed to send events to any relay");
+    if count == 0 {
+        return Ok(());
     }
+    if allow_secrets {
+        warn!(count, "Publishing patches with possible secrets");
+        eprintln!("⚠️  {count} possible secret(s) found, publishing anyway (--allow-secrets)");
+        return Ok(());
+    }
+    bail!(
+        "{count} possible secret(s) found in the patches. Remove them, or pass --allow-secrets if they are false positives"
+    );
+}
 
-    Ok(())
+/// Let the user write the cover letter in their editor, like `git commit` does
+fn edit_cover_letter(repo_path: &Path, title: &str, description: &str) -> Result<String> {
+    let repo = git2::Repository::open(repo_path)
+        .with_context(|| format!("Failed to open git repository at {repo_path:?}"))?;
+    let path = repo.path().join("GITSMITH_COVER_LETTER");
+    fs::write(
+        &path,
+        format!(
+            "{description}\n\n\
+             # Cover letter for \"{title}\".\n\
+             # Lines starting with '#' are ignored, an empty cover letter aborts.\n"
+        ),
+    )
+    .with_context(|| format!("Failed to write {path:?}"))?;
+
+    run_editor(&path)?;
+
+    let text = fs::read_to_string(&path).with_context(|| format!("Failed to read {path:?}"))?;
+    let cover_letter = text
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string();
+    ensure!(
+        !cover_letter.is_empty(),
+        "Aborting due to empty cover letter"
+    );
+
+    Ok(cover_letter)
 }
@@ -0,0 +1,400 @@
+use anyhow::{Context, Result};
+use axum::Router;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use clap::Args;
+use gitsmith_core::cache::{self, CacheStore};
+use gitsmith_core::pull_request::{self, ListOptions, PullRequest, PullRequestDetails};
+use gitsmith_core::{
+    GitsmithError, RepoAnnouncement, comments, detect_from_git, repo, time, watch,
+};
+use nostr_sdk::{EventId, PublicKey, ToBech32};
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+use super::{format_timestamp, network, repo_coordinate};
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Address to listen on, use 0.0.0.0:8080 to serve other machines
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    pub bind: SocketAddr,
+}
+
+/// What every page needs, shared by the request handlers
+struct Site {
+    store: Arc<dyn CacheStore>,
+    coordinate: String,
+    /// Announcement from the git config, until one is cached
+    local: RepoAnnouncement,
+}
+
+impl Site {
+    /// The newest cached announcement of the repository
+    fn announcement(&self) -> RepoAnnouncement {
+        repo::cached_announcement(self.store.as_ref(), &self.coordinate)
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| self.local.clone())
+    }
+}
+
+pub async fn handle_serve_command(args: ServeArgs) -> Result<()> {
+    let store = network()
+        .cache
+        .clone()
+        .context("gitsmith serve shows the event cache, drop --no-cache")?;
+    let local = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+    let coordinate = repo_coordinate(&args.repo_path, &local.identifier)?;
+    let identifier = local.identifier.clone();
+    let site = Arc::new(Site {
+        store,
+        coordinate,
+        local,
+    });
+
+    let app = Router::new()
+        .route("/", get(index))
+        .route("/pr/{id}", get(pull_request_page))
+        .with_state(site);
+    let listener = TcpListener::bind(args.bind)
+        .await
+        .with_context(|| format!("Failed to listen on {bind}", bind = args.bind))?;
+    eprintln!(
+        "Serving {identifier} on http://{address}, press Ctrl-C to stop",
+        address = listener.local_addr()?
+    );
+    eprintln!("Pages show the event cache, 'gitsmith list' or 'gitsmith watch' keep it current");
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+/// A failed page: 404 for events that aren't cached, 500 otherwise
+struct PageError {
+    status: StatusCode,
+    error: anyhow::Error,
+}
+
+impl<E: Into<anyhow::Error>> From<E> for PageError {
+    fn from(error: E) -> Self {
+        let error = error.into();
+        let status = match error.downcast_ref::<GitsmithError>() {
+            Some(GitsmithError::EventNotFound(_)) => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self { status, error }
+    }
+}
+
+impl IntoResponse for PageError {
+    fn into_response(self) -> Response {
+        let body = format!(
+            "<p class=\"error\">{error}</p><p><a href=\"/\">Back</a></p>",
+            error = escape(&format!("{error:#}", error = self.error))
+        );
+        (self.status, Html(page("Error", &body))).into_response()
+    }
+}
+
+async fn index(State(site): State<Arc<Site>>) -> Result<Html<String>, PageError> {
+    let options = ListOptions {
+        offline: true,
+        ..Default::default()
+    };
+    let prs = pull_request::list_pull_requests_with_options(&site.coordinate, vec![], &options)
+        .await
+        .context("Failed to read pull requests from the cache")?;
+    let announcement = site.announcement();
+    Ok(Html(page(
+        &announcement.name,
+        &render_index(&announcement, &prs),
+    )))
+}
+
+async fn pull_request_page(
+    State(site): State<Arc<Site>>,
+    Path(id): Path<String>,
+) -> Result<Html<String>, PageError> {
+    if EventId::from_hex(&id).is_err() {
+        return Err(PageError {
+            status: StatusCode::NOT_FOUND,
+            error: anyhow::anyhow!("{id} is not an event ID"),
+        });
+    }
+    let details = pull_request::cached_pull_request(site.store.as_ref(), &id, &site.coordinate)?;
+    let announcement = site.announcement();
+    Ok(Html(page(
+        &format!(
+            "{title} · {name}",
+            title = details.pr.title,
+            name = announcement.name
+        ),
+        &render_pull_request(&announcement, &details),
+    )))
+}
+
+fn render_index(announcement: &RepoAnnouncement, prs: &[PullRequest]) -> String {
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<h1>{name}</h1><p>{description}</p>",
+        name = escape(&announcement.name),
+        description = escape(&announcement.description)
+    );
+
+    html.push_str("<dl>");
+    for (label, values) in [
+        ("Clone", &announcement.clone_urls),
+        ("Web", &announcement.web),
+        ("Relays", &announcement.relays),
+        ("Maintainers", &announcement.maintainers),
+        ("Topics", &announcement.topics),
+    ] {
+        if values.is_empty() {
+            continue;
+        }
+        let _ = write!(html, "<dt>{label}</dt>");
+        for value in values {
+            let _ = write!(html, "<dd><code>{value}</code></dd>", value = escape(value));
+        }
+    }
+    if let Some(license) = &announcement.license {
+        let _ = write!(
+            html,
+            "<dt>License</dt><dd>{license}</dd>",
+            license = escape(license)
+        );
+    }
+    html.push_str("</dl>");
+
+    let _ = write!(html, "<h2>Pull requests ({count})</h2>", count = prs.len());
+    if prs.is_empty() {
+        html.push_str("<p>No pull requests in the cache yet.</p>");
+        return html;
+    }
+    html.push_str(
+        "<table><tr><th>Status</th><th>Title</th><th>Author</th><th>Updated</th><th>Comments</th></tr>",
+    );
+    for pr in prs {
+        let _ = write!(
+            html,
+            "<tr><td class=\"status {status}\">{status}</td><td><a href=\"/pr/{id}\">{title}</a></td>\
+             <td>{author}</td><td>{updated}</td><td>{comments}</td></tr>",
+            status = pr.status,
+            id = pr.id,
+            title = escape(&pr.title),
+            author = escape(&author(&pr.author, pr.author_name.as_deref())),
+            updated = format_timestamp(pr.updated_at.unwrap_or(pr.created_at)),
+            comments = pr.comments_count
+        );
+    }
+    html.push_str("</table>");
+    html
+}
+
+fn render_pull_request(announcement: &RepoAnnouncement, details: &PullRequestDetails) -> String {
+    let pr = &details.pr;
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<p><a href=\"/\">{name}</a></p><h1>{title}</h1>\
+         <p><span class=\"status {status}\">{status}</span> {author} opened {created}, version {version}</p>",
+        name = escape(&announcement.name),
+        title = escape(&pr.title),
+        status = pr.status,
+        author = escape(&author(&pr.author, pr.author_name.as_deref())),
+        created = format_timestamp(pr.created_at),
+        version = pr.version()
+    );
+    if !pr.description.is_empty() {
+        let _ = write!(
+            html,
+            "<div class=\"text\">{description}</div>",
+            description = escape(&pr.description)
+        );
+    }
+
+    if !details.status_history.is_empty() {
+        html.push_str("<h2>Status</h2><ul>");
+        for change in &details.status_history {
+            let _ = write!(
+                html,
+                "<li><span class=\"status {status}\">{status}</span> by {author}, {when}",
+                status = change.status,
+                author = escape(&author(&change.author, None)),
+                when = format_timestamp(change.created_at)
+            );
+            if !change.message.is_empty() {
+                let _ = write!(html, ": {message}", message = escape(&change.message));
+            }
+            html.push_str("</li>");
+        }
+        html.push_str("</ul>");
+    }
+
+    let _ = write!(
+        html,
+        "<h2>Patches ({count})</h2>",
+        count = details.patches.len()
+    );
+    if let Some(cover_letter) = &details.cover_letter {
+        let _ = write!(
+            html,
+            "<details><summary>Cover letter</summary><pre>{cover_letter}</pre></details>",
+            cover_letter = escape(cover_letter)
+        );
+    }
+    for (index, patch) in details.patches.iter().enumerate() {
+        let _ = write!(
+            html,
+            "<h3 id=\"patch-{number}\">[{number}/{total}] {subject}</h3>\
+             <p>{git_author}, {files} file(s), <span class=\"add\">+{insertions}</span> \
+             <span class=\"del\">-{deletions}</span></p>",
+            number = index + 1,
+            total = details.patches.len(),
+            subject = escape(&patch.subject),
+            git_author = escape(&patch.git_author),
+            files = patch.diffstat.files.len(),
+            insertions = patch.diffstat.insertions,
+            deletions = patch.diffstat.deletions
+        );
+        html.push_str(&render_diff(&patch.content));
+    }
+
+    let thread = comments::thread(&details.comments);
+    let _ = write!(html, "<h2>Comments ({count})</h2>", count = thread.len());
+    for (depth, comment) in thread {
+        let _ = write!(
+            html,
+            "<div class=\"comment\" style=\"margin-left: {indent}em\">\
+             <p class=\"meta\">{author}, {when}</p><div class=\"text\">{content}</div></div>",
+            indent = depth * 2,
+            author = escape(&author(&comment.author, None)),
+            when = format_timestamp(comment.created_at),
+            content = escape(&comment.content)
+        );
+    }
+    html
+}
+
+/// A patch with added, removed and hunk header lines highlighted
+fn render_diff(patch: &str) -> String {
+    let mut html = String::from("<pre class=\"diff\">");
+    let mut in_diff = false;
+    for line in patch.lines() {
+        if line.starts_with("diff --git ") {
+            in_diff = true;
+        } else if line == "-- " {
+            // The signature format-patch appends after the diff
+            in_diff = false;
+        }
+        let class = match line.as_bytes().first() {
+            _ if !in_diff => None,
+            Some(b'+') if !line.starts_with("+++") => Some("add"),
+            Some(b'-') if !line.starts_with("---") => Some("del"),
+            Some(b'@') => Some("hunk"),
+            _ if line.starts_with("diff --git ") => Some("file"),
+            _ => None,
+        };
+        match class {
+            Some(class) => {
+                let _ = writeln!(
+                    html,
+                    "<span class=\"{class}\">{line}</span>",
+                    line = escape(line)
+                );
+            }
+            None => {
+                let _ = writeln!(html, "{line}", line = escape(line));
+            }
+        }
+    }
+    html.push_str("</pre>");
+    html
+}
+
+/// Display name, or the shortened npub of a hex public key
+fn author(public_key: &str, name: Option<&str>) -> String {
+    if let Some(name) = name {
+        return name.to_string();
+    }
+    match PublicKey::from_hex(public_key).map(|key| key.to_bech32()) {
+        Ok(Ok(npub)) => watch::short_npub(&npub),
+        _ => public_key.to_string(),
+    }
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\
+         <title>{title}</title><style>{STYLE}</style></head><body>{body}\
+         <footer>Served by gitsmith from the local event cache</footer></body></html>\n",
+        title = escape(title)
+    )
+}
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:60em;margin:2em auto;padding:0 1em;color:#222}\
+a{color:#0550ae}table{border-collapse:collapse;width:100%}th,td{text-align:left;padding:.3em .6em;border-bottom:1px solid #ddd}\
+dt{font-weight:bold;margin-top:.5em}pre{background:#f6f8fa;padding:.8em;overflow-x:auto}\
+.text{white-space:pre-wrap}.status{font-weight:bold}.open{color:#1a7f37}.merged{color:#8250df}\
+.closed{color:#cf222e}.draft,.updated{color:#9a6700}.add{color:#1a7f37}.del{color:#cf222e}\
+.hunk{color:#8250df}.file{font-weight:bold}.comment{border-left:3px solid #ddd;padding-left:.8em;margin-top:1em}\
+.meta{color:#666;margin:0}.error{color:#cf222e}footer{margin-top:3em;color:#666;font-size:small}";
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("<script>alert('x') & \"y\"</script>"),
+            "&lt;script&gt;alert(&#39;x&#39;) &amp; &quot;y&quot;&lt;/script&gt;"
+        );
+    }
+
+    #[test]
+    fn test_render_diff() {
+        let patch = "Subject: [PATCH] Fix\n\n---\n a.rs | 2 +-\n\
+                     diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -1 +1 @@\n-old\n+new <T>\n-- \n2.39.5\n";
+        let html = render_diff(patch);
+        // The commit message's `---` separator isn't part of the diff
+        assert!(html.contains("\n---\n"));
+        assert!(html.contains("<span class=\"file\">diff --git a/a.rs b/a.rs</span>"));
+        assert!(html.contains("\n--- a/a.rs\n+++ b/a.rs\n"));
+        assert!(html.contains("<span class=\"hunk\">@@ -1 +1 @@</span>"));
+        assert!(html.contains("<span class=\"del\">-old</span>"));
+        assert!(html.contains("<span class=\"add\">+new &lt;T&gt;</span>"));
+        assert!(html.ends_with("\n-- \n2.39.5\n</pre>"));
+    }
+}
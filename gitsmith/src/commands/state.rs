@@ -0,0 +1,365 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, NaiveDate};
+use clap::{Args, Subcommand};
+use gitsmith_core::{GitState, RepoAnnouncement, detect_from_git, events, patches, repo, state};
+use nostr_sdk::{PublicKey, Timestamp, ToBech32};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::relays::fetch_remote_announcement;
+use super::{format_timestamp, network, repo_coordinate, repo_signer};
+
+#[derive(Subcommand)]
+pub enum StateCommands {
+    /// Show the refs published by the newest state event before a date
+    At(StateAtArgs),
+    /// Publish a state combining the latest states of all maintainers
+    Merge(StateMergeArgs),
+}
+
+#[derive(Args)]
+pub struct StateAtArgs {
+    /// Date (2024-06-01, end of that day in UTC), RFC 3339 time or unix timestamp
+    #[arg(long, value_parser = parse_date)]
+    pub date: Timestamp,
+
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Check out the commit of --ref (detached HEAD)
+    #[arg(long)]
+    pub checkout: bool,
+
+    /// Ref to check out with --checkout
+    #[arg(long = "ref", default_value = "HEAD", requires = "checkout")]
+    pub reference: String,
+
+    /// Output as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args)]
+pub struct StateMergeArgs {
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Value for a conflicting ref as NAME=COMMIT (can be specified multiple times)
+    #[arg(long = "ref", value_parser = parse_ref_choice)]
+    pub refs: Vec<(String, String)>,
+
+    /// Password to decrypt account keys (will prompt if not provided)
+    #[arg(long, env = "GITSMITH_PASSWORD")]
+    pub password: Option<String>,
+
+    /// Print the reconciled state instead of publishing it
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Output as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+fn parse_ref_choice(value: &str) -> Result<(String, String)> {
+    let Some((name, commit)) = value.split_once('=') else {
+        bail!("Expected NAME=COMMIT, got {value}");
+    };
+    if name != "HEAD" && !name.starts_with("refs/") {
+        bail!("Ref name must be HEAD or start with refs/, got {name}");
+    }
+    Ok((name.to_string(), commit.to_string()))
+}
+
+/// Public keys of the repository owner and the maintainers it announced
+pub fn maintainer_keys(
+    repo_coordinate: &str,
+    announcement: Option<&RepoAnnouncement>,
+) -> Result<Vec<PublicKey>> {
+    let (_kind, owner, _identifier) = patches::parse_repo_coordinate(repo_coordinate)?;
+    let mut keys = vec![PublicKey::from_hex(&owner).context("Invalid repository owner")?];
+    for npub in announcement.iter().flat_map(|a| &a.maintainers) {
+        match PublicKey::parse(npub) {
+            Ok(key) if !keys.contains(&key) => keys.push(key),
+            Ok(_) => {}
+            Err(e) => tracing::debug!("Skipping invalid maintainer {npub}: {e}"),
+        }
+    }
+    Ok(keys)
+}
+
+/// Print the refs of the newest state that drop other maintainers' updates
+pub fn print_reconciliation(newest: &state::MaintainerState, merged: &state::Reconciliation) {
+    let short = |value: Option<&String>| match value {
+        Some(value) if !value.starts_with("ref: ") => value[..8.min(value.len())].to_string(),
+        Some(value) => value.clone(),
+        None => "(missing)".to_string(),
+    };
+    for name in &merged.updated {
+        if merged
+            .conflicts
+            .iter()
+            .any(|conflict| &conflict.name == name)
+        {
+            continue;
+        }
+        eprintln!(
+            "   {name:<20} {current} -> {merged}",
+            current = short(newest.state.refs.get(name)),
+            merged = short(merged.state.refs.get(name))
+        );
+    }
+    for conflict in &merged.conflicts {
+        eprintln!("   {name:<20} diverged:", name = conflict.name);
+        for (author, value) in &conflict.values {
+            eprintln!("     {author} {value}", value = short(Some(value)));
+        }
+    }
+}
+
+/// Parse a date, a full RFC 3339 time or a unix timestamp
+fn parse_date(value: &str) -> Result<Timestamp> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Ok(Timestamp::from(seconds));
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(Timestamp::from(time.timestamp().max(0) as u64));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .with_context(|| format!("Invalid date '{value}', use YYYY-MM-DD or RFC 3339"))?;
+    // A bare date covers the whole day
+    let end_of_day = date
+        .and_hms_opt(23, 59, 59)
+        .context("Invalid time of day")?
+        .and_utc();
+    Ok(Timestamp::from(end_of_day.timestamp().max(0) as u64))
+}
+
+/// Commit a ref points to, following a symbolic HEAD (`ref: refs/heads/main`)
+fn resolve_ref<'a>(state: &'a GitState, reference: &str) -> Option<&'a str> {
+    let value = state.refs.get(reference)?;
+    match value.strip_prefix("ref: ") {
+        Some(target) => state.refs.get(target).map(String::as_str),
+        None => Some(value),
+    }
+}
+
+pub async fn handle_state_command(command: StateCommands) -> Result<()> {
+    match command {
+        StateCommands::At(args) => state_at(args).await,
+        StateCommands::Merge(args) => state_merge(args).await,
+    }
+}
+
+async fn state_at(args: StateAtArgs) -> Result<()> {
+    let announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+    if announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+    let repo_coordinate = repo_coordinate(&args.repo_path, &announcement.identifier)?;
+
+    let Some(event) = repo::fetch_state_at(
+        network(),
+        &repo_coordinate,
+        &announcement.relays,
+        args.date,
+        Duration::from_secs(10),
+    )
+    .await?
+    else {
+        bail!(
+            "No state event found before {date}, the relays may only keep the latest one",
+            date = format_timestamp(args.date.as_u64())
+        );
+    };
+    let state = events::parse_state_event(&event)?;
+
+    let mut refs: Vec<_> = state.refs.iter().collect();
+    refs.sort();
+    if args.json || super::json_output() {
+        let json = serde_json::json!({
+            "event_id": event.id.to_hex(),
+            "created_at": event.created_at.as_u64(),
+            "identifier": state.identifier,
+            "refs": state.refs,
+        });
+        super::print_json(&json)?;
+    } else {
+        eprintln!(
+            "State of '{identifier}' published {date} (event {id}):",
+            identifier = state.identifier,
+            date = format_timestamp(event.created_at.as_u64()),
+            id = event.id
+        );
+        for (name, value) in refs {
+            println!("{name} {value}");
+        }
+    }
+
+    if args.checkout {
+        let Some(commit) = resolve_ref(&state, &args.reference) else {
+            bail!(
+                "Ref {reference} is not in the state published at that time",
+                reference = args.reference
+            );
+        };
+        repo::checkout_commit(&args.repo_path, commit)?;
+        eprintln!(
+            "Checked out {reference} at {commit} (detached HEAD)",
+            reference = args.reference
+        );
+    }
+
+    Ok(())
+}
+
+async fn state_merge(args: StateMergeArgs) -> Result<()> {
+    let announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+    if announcement.relays.is_empty() {
+        bail!("No relays configured for repository. Please run 'gitsmith init' first");
+    }
+    let repo_coordinate = repo_coordinate(&args.repo_path, &announcement.identifier)?;
+    let remote = fetch_remote_announcement(&args.repo_path, &announcement, &[])
+        .await
+        .context("Failed to fetch the announcement listing the maintainers")?;
+    let maintainers = maintainer_keys(&repo_coordinate, remote.as_ref())?;
+
+    let states = state::fetch_maintainer_states(
+        network(),
+        &repo_coordinate,
+        &maintainers,
+        &announcement.relays,
+        Duration::from_secs(10),
+    )
+    .await?;
+    let repository = repo::open_repository(&args.repo_path)?;
+    let Some(mut merged) = state::reconcile(&states, |commit, ancestor| {
+        state::commit_descends(&repository, commit, ancestor)
+    }) else {
+        bail!("No state published by the maintainers, run 'gitsmith push' first");
+    };
+    let newest = &states[0];
+    let json = args.json || super::json_output();
+
+    for (name, commit) in &args.refs {
+        merged.state.refs.insert(name.clone(), commit.clone());
+        merged.conflicts.retain(|conflict| &conflict.name != name);
+        if newest.state.refs.get(name) != Some(commit) && !merged.updated.contains(name) {
+            merged.updated.push(name.clone());
+        }
+    }
+    if !merged.conflicts.is_empty() {
+        eprintln!("Maintainers published diverged values:");
+        print_reconciliation(newest, &merged);
+        bail!(
+            "Pick a value for each diverged ref with --ref NAME=COMMIT, fetching the \
+             maintainers' commits may also let them fast-forward"
+        );
+    }
+    if merged.updated.is_empty() {
+        eprintln!(
+            "The newest state ({npub}) already has every maintainer's updates",
+            npub = newest.author.to_bech32()?
+        );
+        return Ok(());
+    }
+
+    if args.dry_run {
+        if json {
+            let json = serde_json::json!({
+                "identifier": merged.state.identifier,
+                "refs": merged.state.refs,
+                "updated": merged.updated,
+            });
+            return super::print_json(&json);
+        }
+        eprintln!("Reconciled state changes:");
+        print_reconciliation(newest, &merged);
+        return Ok(());
+    }
+
+    let signer = repo_signer(&args.repo_path, args.password)?;
+    if !maintainers.contains(&signer.public_key()) {
+        bail!("Only states of the owner and maintainers are read, sign with one of their accounts");
+    }
+    eprintln!(
+        "Publishing reconciled state of {count} ref(s), {updated} updated...",
+        count = merged.state.refs.len(),
+        updated = merged.updated.len()
+    );
+    let result = repo::publish_git_state(
+        network(),
+        signer.as_ref(),
+        &merged.state,
+        &announcement.relays,
+    )
+    .await?;
+
+    if json {
+        super::print_json(&result)?;
+    }
+    if result.successes.is_empty() {
+        bail!("Failed to publish state event to any relay");
+    }
+    if !json {
+        eprintln!(
+            "✅ Reconciled state published to {count}/{total} relay(s)",
+            count = result.successes.len(),
+            total = result.successes.len() + result.failures.len()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(
+            parse_date("2024-06-01").unwrap(),
+            Timestamp::from(1_717_286_399)
+        );
+        assert_eq!(
+            parse_date("2024-06-01T12:00:00+02:00").unwrap(),
+            Timestamp::from(1_717_236_000)
+        );
+        assert_eq!(
+            parse_date("1717236000").unwrap(),
+            Timestamp::from(1_717_236_000)
+        );
+        assert!(parse_date("June 1st").is_err());
+    }
+
+    #[test]
+    fn test_resolve_ref() {
+        let state = GitState {
+            identifier: "gitsmith".to_string(),
+            refs: [
+                ("refs/heads/main".to_string(), "a".repeat(40)),
+                ("HEAD".to_string(), "ref: refs/heads/main".to_string()),
+            ]
+            .into(),
+        };
+        let main = "a".repeat(40);
+        assert_eq!(resolve_ref(&state, "HEAD"), Some(main.as_str()));
+        assert_eq!(resolve_ref(&state, "refs/heads/main"), Some(main.as_str()));
+        assert_eq!(resolve_ref(&state, "refs/heads/missing"), None);
+    }
+
+    #[test]
+    fn test_parse_ref_choice() {
+        assert_eq!(
+            parse_ref_choice("refs/heads/main=abc").unwrap(),
+            ("refs/heads/main".to_string(), "abc".to_string())
+        );
+        assert!(parse_ref_choice("main=abc").is_err());
+        assert!(parse_ref_choice("refs/heads/main").is_err());
+    }
+}
@@ -1,9 +1,14 @@
 use anyhow::{Context, Result};
 use clap::Args;
-use gitsmith_core::{detect_from_git, get_git_state};
-use nostr_sdk::{Alphabet, Client, Filter, Kind, RelayPoolNotification, SingleLetterTag};
-use std::path::PathBuf;
-use std::time::Duration;
+use gitsmith_core::{RepoAnnouncement, detect_from_git, get_git_state, relay, repo, state};
+use nostr_sdk::{Alphabet, Filter, Kind, SingleLetterTag};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::relays::{fetch_remote_announcement, print_drift, print_reconcile_hint};
+use super::state::{maintainer_keys, print_reconciliation};
+use super::{format_timestamp, network, repo_coordinate};
 
 #[derive(Args)]
 pub struct SyncArgs {
@@ -16,6 +21,21 @@ pub struct SyncArgs {
     pub json: bool,
 }
 
+#[derive(Serialize)]
+struct SyncOutput {
+    identifier: String,
+    local: HashMap<String, String>,
+    /// Newest state published by a maintainer, `None` when there is none
+    remote: Option<RemoteState>,
+}
+
+#[derive(Serialize)]
+struct RemoteState {
+    event_id: String,
+    created_at: u64,
+    refs: HashMap<String, String>,
+}
+
 pub async fn handle_sync_command(args: SyncArgs) -> Result<()> {
     // Get repository info
     let repo_announcement = detect_from_git(&args.repo_path)
@@ -42,6 +62,7 @@ pub async fn handle_sync_command(args: SyncArgs) -> Result<()> {
     }
     eprintln!();
 
+    let mut remote_state = None;
     // If relays are configured, fetch remote state
     if !repo_announcement.relays.is_empty() {
         eprintln!(
@@ -49,7 +70,7 @@ pub async fn handle_sync_command(args: SyncArgs) -> Result<()> {
             count = repo_announcement.relays.len()
         );
 
-        let client = Client::default();
+        let client = relay::new_client(network(), None);
 
         for relay_url in &repo_announcement.relays {
             client.add_relay(relay_url).await?;
@@ -63,67 +84,145 @@ pub async fn handle_sync_command(args: SyncArgs) -> Result<()> {
             &repo_announcement.identifier,
         );
 
-        // Subscribe to events
-        client.subscribe(filter, None).await?;
-
-        // Collect events for a few seconds
-        let mut state_events = Vec::new();
-        let timeout = tokio::time::sleep(Duration::from_secs(3));
-        tokio::pin!(timeout);
-
-        let mut notifications = client.notifications();
-
-        loop {
-            tokio::select! {
-                _ = &mut timeout => break,
-                notification = notifications.recv() => {
-                    if let Ok(notification) = notification
-                        && let RelayPoolNotification::Event { event, .. } = notification
-                            && event.kind == Kind::Custom(30618) {
-                                state_events.push(*event);
-                            }
-                }
+        // Only state published since the last complete sync is downloaded again
+        let scope = format!(
+            "state:{identifier}",
+            identifier = repo_announcement.identifier
+        );
+        let state_events = relay::fetch_events_synced(
+            network(),
+            &client,
+            &scope,
+            vec![filter],
+            relay::DEFAULT_FETCH_TIMEOUT,
+        )
+        .await?;
+        client.disconnect().await;
+
+        let remote = match fetch_remote_announcement(&args.repo_path, &repo_announcement, &[]).await
+        {
+            Ok(remote) => remote,
+            Err(e) => {
+                tracing::debug!("Failed to fetch announcement: {e}");
+                None
             }
-        }
+        };
+        // Anyone can publish a state with this identifier, only maintainers count
+        let states = match repo_coordinate(&args.repo_path, &repo_announcement.identifier)
+            .and_then(|coordinate| maintainer_keys(&coordinate, remote.as_ref()))
+        {
+            Ok(maintainers) => state::latest_states(&state_events, &maintainers),
+            Err(e) => {
+                tracing::debug!("Failed to resolve the maintainers: {e}");
+                vec![]
+            }
+        };
 
-        if !state_events.is_empty() {
+        if let Some(latest) = states.first() {
             eprintln!("\nRemote Nostr State:");
             eprintln!("{:-<40}", "");
-
-            // Get the most recent state event
-            state_events.sort_by_key(|e| std::cmp::Reverse(e.created_at));
-
-            if let Some(latest_state) = state_events.first() {
-                // Parse refs from content (JSON)
-                if let Ok(refs) = serde_json::from_str::<serde_json::Value>(&latest_state.content)
-                    && let Some(refs_obj) = refs.as_object()
-                {
-                    for (ref_name, commit) in refs_obj {
-                        if let Some(commit_str) = commit.as_str() {
-                            eprintln!(
-                                "{ref_name:<20} {commit}",
-                                commit = &commit_str[..8.min(commit_str.len())]
-                            );
-                        }
-                    }
-                }
-
+            let mut refs: Vec<_> = latest.state.refs.iter().collect();
+            refs.sort();
+            for (ref_name, commit) in refs {
                 eprintln!(
-                    "\nLast updated: {timestamp}",
-                    timestamp = chrono::DateTime::from_timestamp(
-                        latest_state.created_at.as_u64() as i64,
-                        0
-                    )
-                    .map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string())
-                    .unwrap_or_else(|| "Unknown".to_string())
+                    "{ref_name:<20} {commit}",
+                    commit = &commit[..8.min(commit.len())]
                 );
             }
+            eprintln!(
+                "\nLast updated: {timestamp}",
+                timestamp = format_timestamp(latest.event.created_at.as_u64())
+            );
+            warn_about_diverged_states(&args.repo_path, &states);
+            remote_state = Some(RemoteState {
+                event_id: latest.event.id.to_hex(),
+                created_at: latest.event.created_at.as_u64(),
+                refs: latest.state.refs.clone(),
+            });
         } else {
             eprintln!("\nNo remote state found on Nostr relays");
         }
+
+        warn_about_relay_drift(&repo_announcement, remote.as_ref()).await;
     } else {
         eprintln!("No relays configured. Run 'gitsmith init' to configure relays.");
     }
 
+    if args.json || super::json_output() {
+        super::print_json(&SyncOutput {
+            identifier: repo_announcement.identifier,
+            local: local_state.refs,
+            remote: remote_state,
+        })?;
+    }
     Ok(())
 }
+
+/// Warn when the newest state drops updates published by other maintainers
+///
+/// Commits are ordered with the local repository, so fetching first lets
+/// more updates fast-forward instead of showing up as diverged.
+fn warn_about_diverged_states(repo_path: &Path, states: &[state::MaintainerState]) {
+    if states.len() < 2 {
+        return;
+    }
+    let repository = match repo::open_repository(repo_path) {
+        Ok(repository) => repository,
+        Err(e) => {
+            tracing::debug!("Failed to open repository: {e}");
+            return;
+        }
+    };
+    let Some(merged) = state::reconcile(states, |commit, ancestor| {
+        state::commit_descends(&repository, commit, ancestor)
+    }) else {
+        return;
+    };
+    if merged.is_consistent() {
+        return;
+    }
+
+    eprintln!();
+    eprintln!(
+        "⚠️  The newest state drops updates published by other maintainers ({count} state(s) compared):",
+        count = states.len()
+    );
+    print_reconciliation(&states[0], &merged);
+    eprintln!("   Run 'gitsmith state merge' to publish a reconciled state");
+}
+
+/// Warn when configured relays are unreachable or differ from the announcement
+///
+/// Best effort: lookup errors are only logged, they never fail the sync.
+async fn warn_about_relay_drift(local: &RepoAnnouncement, remote: Option<&RepoAnnouncement>) {
+    let unreachable: Vec<String> =
+        match relay::check_relays(network(), &local.relays, relay::DEFAULT_CONNECT_TIMEOUT).await {
+            Ok(health) => health
+                .into_iter()
+                .filter(|relay| !relay.connected)
+                .map(|relay| relay.url)
+                .collect(),
+            Err(e) => {
+                tracing::debug!("Failed to check relays: {e}");
+                vec![]
+            }
+        };
+    let drift = match remote {
+        Some(remote) => relay::relay_drift(&local.relays, &remote.relays),
+        None => Default::default(),
+    };
+
+    if unreachable.is_empty() && drift.is_empty() {
+        return;
+    }
+
+    eprintln!();
+    for relay in &unreachable {
+        eprintln!("⚠️  Relay unreachable: {relay}");
+    }
+    if !drift.is_empty() {
+        eprintln!("⚠️  Local relays differ from the announcement:");
+        print_drift(&drift);
+    }
+    print_reconcile_hint();
+}
@@ -0,0 +1,192 @@
+use anyhow::{Context, Result, ensure};
+use clap::Args;
+use futures::future::join_all;
+use gitsmith_core::{
+    detect_from_git, relay,
+    subscriptions::RepoSubscriptions,
+    time,
+    watch::{self, Notification, Notifier, NotifierPayload, WATCHED_KINDS},
+};
+use nostr_sdk::RelayPoolNotification;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::PathBuf;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+use super::{format_timestamp, network, repo_coordinate};
+
+#[derive(Args)]
+pub struct WatchArgs {
+    /// Repository path
+    #[arg(long, default_value = ".")]
+    pub repo_path: PathBuf,
+
+    /// Additional relays to watch besides the repository's
+    #[arg(long = "relay", value_delimiter = ',')]
+    pub relays: Vec<String>,
+
+    /// Shell command run for every event, with the notification as JSON on
+    /// stdin and GITSMITH_EVENT_ID, GITSMITH_EVENT_KIND, GITSMITH_EVENT_AUTHOR
+    /// and GITSMITH_EVENT_SUMMARY set (adds to git config nostr.notify.exec)
+    #[arg(long)]
+    pub exec: Vec<String>,
+
+    /// URL to POST every notification to as JSON, its `text` field suits
+    /// Slack and Matrix hooks (adds to git config nostr.notify.webhook)
+    #[arg(long)]
+    pub webhook: Vec<String>,
+
+    /// Named pipe to write every notification to as a JSON line (adds to git
+    /// config nostr.notify.pipe)
+    #[arg(long)]
+    pub pipe: Vec<PathBuf>,
+
+    /// Ignore the notifiers configured in git config
+    #[arg(long)]
+    pub no_configured_notifiers: bool,
+
+    /// Print one JSON object per event
+    #[arg(long)]
+    pub json: bool,
+}
+
+pub async fn handle_watch_command(args: WatchArgs) -> Result<()> {
+    let repo_announcement = detect_from_git(&args.repo_path)
+        .context("Failed to detect repository. Make sure you're in a git repository")?;
+    let coordinate = repo_coordinate(&args.repo_path, &repo_announcement.identifier)?;
+
+    let mut relays: Vec<String> = Vec::new();
+    for url in repo_announcement.relays.into_iter().chain(args.relays) {
+        if !relays.iter().any(|known| relay::same_relay(known, &url)) {
+            relays.push(url);
+        }
+    }
+    ensure!(
+        !relays.is_empty(),
+        "No relays configured for repository. Run 'gitsmith init' or pass --relay"
+    );
+
+    let mut notifiers = if args.no_configured_notifiers {
+        Vec::new()
+    } else {
+        Notifier::from_git_config(&args.repo_path)?
+    };
+    notifiers.extend(args.webhook.into_iter().map(Notifier::Webhook));
+    notifiers.extend(args.exec.into_iter().map(Notifier::Command));
+    notifiers.extend(args.pipe.into_iter().map(Notifier::Pipe));
+
+    let client = relay::connect(network(), &relays, None).await?;
+    let mut subscriptions = RepoSubscriptions::new(client.clone(), WATCHED_KINDS);
+    subscriptions.add_repo(&coordinate).await?;
+    eprintln!(
+        "Watching {identifier} on {count} relay(s), press Ctrl-C to stop",
+        identifier = repo_announcement.identifier,
+        count = relays.len()
+    );
+    for notifier in &notifiers {
+        eprintln!("Notifying {notifier}");
+    }
+
+    let json = args.json || super::json_output();
+    // Relays holding the same event each deliver it
+    let mut seen = HashSet::new();
+    let mut notifications = client.notifications();
+    loop {
+        let notification = tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            notification = notifications.recv() => notification,
+        };
+        let event = match notification {
+            Ok(RelayPoolNotification::Event {
+                subscription_id,
+                event,
+                ..
+            }) if subscriptions.contains(&subscription_id) => event,
+            Ok(_) => continue,
+            Err(RecvError::Lagged(missed)) => {
+                warn!("Missed {missed} relay notifications");
+                continue;
+            }
+            Err(RecvError::Closed) => break,
+        };
+        if !seen.insert(event.id) {
+            continue;
+        }
+
+        if let Some(store) = network().cache.clone()
+            && let Err(e) = store.save_events(std::slice::from_ref(&*event))
+        {
+            warn!("Failed to cache event {id}: {e}", id = event.id);
+        }
+
+        let notification = Notification::from_event(&event);
+        if json {
+            let mut value = serde_json::to_value(&notification)?;
+            time::add_iso_timestamps(&mut value);
+            println!("{value}");
+        } else {
+            println!("{line}", line = format_notification(&notification));
+        }
+        std::io::stdout().flush()?;
+
+        let payload = NotifierPayload::new(&notification, &event);
+        let results = join_all(notifiers.iter().map(|notifier| notifier.notify(&payload))).await;
+        for (notifier, result) in notifiers.iter().zip(results) {
+            if let Err(e) = result {
+                eprintln!(
+                    "Warning: Failed to notify {notifier} of {id}: {e}",
+                    id = notification.id
+                );
+            }
+        }
+    }
+
+    subscriptions.clear().await;
+    client.disconnect().await;
+    Ok(())
+}
+
+fn format_notification(notification: &Notification) -> String {
+    let mut line = format!(
+        "{created}  {kind_name}  {id}  {author}",
+        created = format_timestamp(notification.created_at),
+        kind_name = notification.kind_name,
+        id = &notification.id[..8],
+        author = watch::short_npub(&notification.author),
+    );
+    if let Some(target) = &notification.target {
+        line.push_str(&format!(
+            " on {target}",
+            target = &target[..target.len().min(8)]
+        ));
+    }
+    if !notification.summary.is_empty() {
+        line.push_str(&format!(": {summary}", summary = notification.summary));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_notification() {
+        let notification = Notification {
+            id: "a".repeat(64),
+            kind: 1632,
+            kind_name: "status: closed".to_string(),
+            author: "npub10elfcs4fr0l0r8af98jlmgdh9c8tcxjvz9qkw038js35mp4dma8qzvjptg".to_string(),
+            created_at: 1_700_000_000,
+            summary: "Superseded".to_string(),
+            target: Some("b".repeat(64)),
+            repo: None,
+        };
+        let line = format_notification(&notification);
+        assert!(
+            line.ends_with("status: closed  aaaaaaaa  npub10elfc…jptg on bbbbbbbb: Superseded"),
+            "{line}"
+        );
+    }
+}
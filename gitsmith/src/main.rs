@@ -1,11 +1,17 @@
 use anyhow::{Context, Result, ensure};
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use clap_complete::{ArgValueCandidates, CompleteEnv};
+use gitsmith_core::account::{self, AccountOptions};
+use gitsmith_core::cache::{self, CacheBackend};
+use gitsmith_core::config;
+use gitsmith_core::time::{TimeFormat, TimeStyle, Timezone};
 use gitsmith_core::{
-    PublishConfig, RepoAnnouncement, announce_repository, detect_from_git, get_git_state,
-    update_git_config_full,
+    PublishConfig, RepoAnnouncement, Signer, announce_repository, detect_from_git, get_git_state,
+    grasp, hooks, relay, update_git_config_full,
 };
 use nostr_sdk::nostr::{Keys, ToBech32};
-use std::path::PathBuf;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
 use strum::{Display, EnumString};
 use tracing_subscriber::EnvFilter;
 
@@ -29,6 +35,55 @@ enum TransportType {
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Store accounts in the OS keychain instead of accounts.json
+    #[arg(long, global = true, env = "GITSMITH_KEYRING")]
+    keyring: bool,
+
+    /// Event cache backend: sqlite (default), lmdb or memory
+    #[arg(
+        long,
+        global = true,
+        env = "GITSMITH_CACHE",
+        num_args = 0..=1,
+        default_missing_value = "sqlite"
+    )]
+    cache: Option<CacheBackend>,
+
+    /// Don't cache events fetched from relays
+    #[arg(long, global = true, conflicts_with = "cache")]
+    no_cache: bool,
+
+    /// Don't answer relays' NIP-42 AUTH challenges
+    #[arg(long, global = true)]
+    no_auth: bool,
+
+    /// SOCKS5 proxy for every relay connection, like socks5://127.0.0.1:9050 for Tor
+    /// (defaults to git config nostr.proxy)
+    #[arg(long, global = true, env = relay::PROXY_ENV)]
+    proxy: Option<String>,
+
+    /// Print times relative to now (3 days ago) or absolute
+    #[arg(long, global = true, env = "GITSMITH_TIME", default_value = "relative")]
+    time: TimeStyle,
+
+    /// Timezone of absolute times: utc, local, an offset like +02:00 or a name like Europe/Berlin
+    #[arg(
+        long,
+        global = true,
+        env = "GITSMITH_TIMEZONE",
+        default_value = "local"
+    )]
+    timezone: Timezone,
+
+    /// strftime pattern of absolute times
+    #[arg(long, global = true, env = "GITSMITH_DATE_FORMAT", default_value = gitsmith_core::time::DEFAULT_DATE_FORMAT, value_parser = gitsmith_core::time::parse_date_format)]
+    date_format: String,
+
+    /// Output format: human, json (only the result on stdout, messages on stderr) or
+    /// minimal (init and state; state defaults to json)
+    #[arg(long, global = true, value_enum, env = "GITSMITH_FORMAT")]
+    format: Option<OutputFormat>,
 }
 
 #[derive(Subcommand)]
@@ -46,9 +101,118 @@ enum Commands {
     /// List pull requests
     List(commands::list::ListArgs),
 
+    /// Manage pull requests
+    Pr {
+        #[command(subcommand)]
+        command: commands::pr::PrCommands,
+    },
+
+    /// Manage issues
+    Issue {
+        #[command(subcommand)]
+        command: commands::issue::IssueCommands,
+    },
+
+    /// Export patches for email-based review or archiving
+    Patch {
+        #[command(subcommand)]
+        command: commands::patch::PatchCommands,
+    },
+
+    /// Comment on a pull request, patch or issue
+    Comment(commands::comment::CommentArgs),
+
+    /// Approve a pull request or request changes
+    Review(commands::review::ReviewArgs),
+
+    /// Link external tracker items to PRs and issues
+    Link {
+        #[command(subcommand)]
+        command: commands::link::LinkCommands,
+    },
+
+    /// CI integration
+    Ci {
+        #[command(subcommand)]
+        command: commands::ci::CiCommands,
+    },
+
+    /// Manage organizations grouping related repositories
+    Org {
+        #[command(subcommand)]
+        command: commands::org::OrgCommands,
+    },
+
+    /// Browse repositories and check their relay coverage
+    #[command(alias = "repo")]
+    Repos {
+        #[command(subcommand)]
+        command: commands::repos::ReposCommands,
+    },
+
+    /// Publish the current repository state (branches and tags) to relays
+    Push(commands::push::PushArgs),
+
+    /// Publish releases of git tags with artifacts stored on Blossom servers
+    Release {
+        #[command(subcommand)]
+        command: commands::release::ReleaseCommands,
+    },
+
+    /// Show pull requests and issues with unread markers
+    Inbox(commands::inbox::InboxArgs),
+
+    /// Print new pull requests, patches, issues, comments and status changes as they arrive
+    Watch(commands::watch::WatchArgs),
+
+    /// Serve a read-only web view of the repository and its pull requests from the event cache
+    #[cfg(feature = "serve")]
+    Serve(commands::serve::ServeArgs),
+
+    /// Manage, test and reconcile the repository's relays
+    #[command(alias = "relay")]
+    Relays {
+        #[command(subcommand)]
+        command: commands::relays::RelaysCommands,
+    },
+
     /// Sync repository state
     Sync(commands::sync::SyncArgs),
 
+    /// Republish the announcement and state before relays expire them (cron or --daemon)
+    Refresh(commands::refresh::RefreshArgs),
+
+    /// Run a relay on localhost for offline and air-gapped teams
+    Localnet {
+        #[command(subcommand)]
+        command: commands::localnet::LocalnetCommands,
+    },
+
+    /// Inspect or clear the local event cache (selected with --cache)
+    Cache {
+        #[command(subcommand)]
+        command: commands::cache::CacheCommands,
+    },
+
+    /// Debug events: fetch one from relays and show where it is
+    Event {
+        #[command(subcommand)]
+        command: commands::event::EventCommands,
+    },
+
+    /// Show and change the defaults of ~/.config/gitsmith/config.toml
+    Config {
+        #[command(subcommand)]
+        command: commands::config::ConfigCommands,
+    },
+
+    /// Print the script enabling tab completion in a shell, e.g.
+    /// `source <(gitsmith completions bash)` in ~/.bashrc
+    Completions {
+        #[arg(value_enum)]
+        shell: commands::completions::Shell,
+    },
+
     /// Run MCP server for AI assistant integration
     #[command(name = "mcp-server")]
     McpServer {
@@ -57,19 +221,40 @@ enum Commands {
         transport: TransportType,
     },
 
-    /// Initialize and announce a repository on Nostr
+    /// Clone a repository announced on Nostr
+    Clone(commands::clone::CloneArgs),
+
+    /// Announce your fork of a repository, PRs sent from it go to the upstream repository
+    Fork(commands::fork::ForkArgs),
+
+    /// Create a new project from a template repository announced on Nostr
+    New(commands::new::NewArgs),
+
+    /// Mirror PRs and issues between Nostr and other forges
+    Bridge {
+        #[command(subcommand)]
+        command: commands::bridge::BridgeCommands,
+    },
+
+    /// Announce a repository hosted elsewhere with its existing metadata
+    Migrate {
+        #[command(subcommand)]
+        command: commands::migrate::MigrateCommands,
+    },
+
+    /// Initialize and announce a repository on Nostr (asks for missing values in a terminal)
     Init {
         /// Repository identifier (unique, no spaces)
-        #[arg(long, env = "NOSTR_GIT_IDENTIFIER")]
-        identifier: String,
+        #[arg(long, env = "NOSTR_GIT_IDENTIFIER", add = ArgValueCandidates::new(commands::completions::identifier_candidates))]
+        identifier: Option<String>,
 
         /// Repository name
         #[arg(long, env = "NOSTR_GIT_NAME")]
-        name: String,
+        name: Option<String>,
 
         /// Repository description
         #[arg(long, env = "NOSTR_GIT_DESCRIPTION")]
-        description: String,
+        description: Option<String>,
 
         /// Clone URLs (can be specified multiple times)
         #[arg(
@@ -89,7 +274,17 @@ enum Commands {
 
         /// Private key in hex format or nsec bech32
         #[arg(long = "nsec", env = "NOSTR_PRIVATE_KEY")]
-        private_key: String,
+        private_key: Option<String>,
+
+        /// Stored account (npub) that owns the repository, later commands in the
+        /// repository sign with it instead of the active account (takes
+        /// precedence over --nsec)
+        #[arg(long, add = ArgValueCandidates::new(commands::completions::account_candidates))]
+        account: Option<String>,
+
+        /// Password of --account (will prompt if not provided)
+        #[arg(long, env = "GITSMITH_PASSWORD")]
+        password: Option<String>,
 
         /// Root commit (auto-detected if not provided)
         #[arg(long, alias = "earliest-unique-commit")]
@@ -103,6 +298,28 @@ enum Commands {
         #[arg(long = "blossoms", value_delimiter = ',')]
         blossom_servers: Vec<String>,
 
+        /// GRASP servers to host the git data and relay the events (can be
+        /// specified multiple times)
+        #[arg(long = "grasp-server", value_delimiter = ',')]
+        grasp_servers: Vec<String>,
+
+        /// Minimum NIP-13 proof-of-work difficulty required for inbound PRs and comments
+        #[arg(long, default_value = "0")]
+        min_pow: u8,
+
+        /// License of the repository (SPDX expression, e.g. MIT OR Apache-2.0)
+        #[arg(long)]
+        license: Option<String>,
+
+        /// Publish the README as a long-form article linked from the announcement
+        /// (default: README.md)
+        #[arg(long, num_args = 0..=1, default_missing_value = "README.md")]
+        readme: Option<PathBuf>,
+
+        /// Publish only the first paragraph of the README
+        #[arg(long, requires = "readme")]
+        readme_summary: bool,
+
         /// Repository path (default: current directory)
         #[arg(long, default_value = ".")]
         repo_path: PathBuf,
@@ -111,9 +328,9 @@ enum Commands {
         #[arg(long, default_value = "30")]
         timeout: u64,
 
-        /// Output format
-        #[arg(long, value_enum, default_value = "human")]
-        output: OutputFormat,
+        /// Output format (overrides --format)
+        #[arg(long, value_enum)]
+        output: Option<OutputFormat>,
 
         /// Update git config with nostr URL
         #[arg(long, default_value = "true")]
@@ -135,23 +352,28 @@ enum Commands {
         include_sample_relays: bool,
     },
 
-    /// Get current git state
+    /// Get current git state, a published one with `state at`, or reconcile
+    /// the maintainers' states with `state merge`
+    #[command(args_conflicts_with_subcommands = true)]
     State {
+        #[command(subcommand)]
+        command: Option<commands::state::StateCommands>,
+
         /// Repository identifier
-        #[arg(long)]
-        identifier: String,
+        #[arg(long, required = true, add = ArgValueCandidates::new(commands::completions::identifier_candidates))]
+        identifier: Option<String>,
 
         /// Repository path
         #[arg(long, default_value = ".")]
         repo_path: PathBuf,
 
-        /// Output format
-        #[arg(long, value_enum, default_value = "json")]
-        output: OutputFormat,
+        /// Output format (overrides --format)
+        #[arg(long, value_enum)]
+        output: Option<OutputFormat>,
     },
 }
 
-#[derive(ValueEnum, Clone, Debug)]
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
 enum OutputFormat {
     Human,
     Json,
@@ -160,6 +382,11 @@ enum OutputFormat {
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Answer tab completion requests of the script printed by `gitsmith completions`
+    CompleteEnv::with_factory(Cli::command)
+        .var(commands::completions::COMPLETE_ENV)
+        .complete();
+
     // Initialize tracing with RUST_LOG environment variable support
     // Output to stderr to keep stdout clean for JSON output
     tracing_subscriber::fmt()
@@ -169,7 +396,54 @@ async fn main() -> Result<()> {
         .with_writer(std::io::stderr)
         .init();
 
-    let cli = Cli::parse();
+    // Layered settings: configuration file < environment < command line
+    let config = config::load()?;
+    let matches = commands::config::with_defaults(Cli::command(), &config).get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let default_relays = match std::env::var(relay::DEFAULT_RELAYS_ENV) {
+        Ok(relays) => relays
+            .split(',')
+            .map(str::trim)
+            .filter(|relay| !relay.is_empty())
+            .map(String::from)
+            .collect(),
+        Err(_) => config.list("relays"),
+    };
+    let proxy = match &cli.proxy {
+        Some(proxy) => Some(relay::parse_proxy(proxy)?),
+        None => relay::configured_proxy(Path::new("."))?,
+    };
+    let mut cache = None;
+    if !cli.no_cache && !matches!(cli.command, Commands::Cache { .. }) {
+        match cache::open_cache(cli.cache.unwrap_or_default()) {
+            Ok(store) => cache = Some(store),
+            Err(e) if cli.cache.is_some() => {
+                return Err(e).context("Failed to open the event cache");
+            }
+            // The default cache only speeds things up, commands work without it
+            Err(e) => eprintln!("Warning: Failed to open the event cache: {e}"),
+        }
+    }
+    commands::use_settings(commands::Settings {
+        network: relay::Network {
+            proxy,
+            authentication: !cli.no_auth,
+            cache,
+        },
+        accounts: AccountOptions {
+            keyring: cli.keyring,
+            account: std::env::var(account::ACCOUNT_ENV)
+                .ok()
+                .or_else(|| config.string("account").map(String::from)),
+        },
+        time_format: TimeFormat {
+            style: cli.time,
+            timezone: cli.timezone,
+            date_format: cli.date_format.clone(),
+        },
+        default_relays,
+    });
+    commands::use_json_output(cli.format == Some(OutputFormat::Json));
 
     match cli.command {
         Commands::Account { command } => commands::account::handle_account_command(command).await,
@@ -178,8 +452,63 @@ async fn main() -> Result<()> {
 
         Commands::List(args) => commands::list::handle_list_command(args).await,
 
+        Commands::Pr { command } => commands::pr::handle_pr_command(command).await,
+
+        Commands::Issue { command } => commands::issue::handle_issue_command(command).await,
+
+        Commands::Patch { command } => commands::patch::handle_patch_command(command).await,
+
+        Commands::Comment(args) => commands::comment::handle_comment_command(args).await,
+
+        Commands::Review(args) => commands::review::handle_review_command(args).await,
+
+        Commands::Link { command } => commands::link::handle_link_command(command).await,
+
+        Commands::Ci { command } => commands::ci::handle_ci_command(command).await,
+
+        Commands::Org { command } => commands::org::handle_org_command(command).await,
+
+        Commands::Repos { command } => commands::repos::handle_repos_command(command).await,
+
+        Commands::Push(args) => commands::push::handle_push_command(args).await,
+
+        Commands::Release { command } => commands::release::handle_release_command(command).await,
+
+        Commands::Inbox(args) => commands::inbox::handle_inbox_command(args).await,
+
+        Commands::Watch(args) => commands::watch::handle_watch_command(args).await,
+
+        #[cfg(feature = "serve")]
+        Commands::Serve(args) => commands::serve::handle_serve_command(args).await,
+        Commands::Relays { command } => commands::relays::handle_relays_command(command).await,
         Commands::Sync(args) => commands::sync::handle_sync_command(args).await,
 
+        Commands::Refresh(args) => commands::refresh::handle_refresh_command(args).await,
+
+        Commands::Localnet { command } => {
+            commands::localnet::handle_localnet_command(command).await
+        }
+
+        Commands::Cache { command } => {
+            commands::cache::handle_cache_command(command, cli.cache.unwrap_or_default())
+        }
+
+        Commands::Event { command } => commands::event::handle_event_command(command).await,
+
+        Commands::Config { command } => commands::config::handle_config_command(command),
+
+        Commands::Completions { shell } => commands::completions::handle_completions_command(shell),
+
+        Commands::Clone(args) => commands::clone::handle_clone_command(args).await,
+
+        Commands::Fork(args) => commands::fork::handle_fork_command(args).await,
+
+        Commands::New(args) => commands::new::handle_new_command(args).await,
+
+        Commands::Migrate { command } => commands::migrate::handle_migrate_command(command).await,
+
+        Commands::Bridge { command } => commands::bridge::handle_bridge_command(command).await,
+
         Commands::Init {
             identifier,
             name,
@@ -188,19 +517,56 @@ async fn main() -> Result<()> {
             relays,
             web,
             private_key,
+            account,
+            password,
             root_commit,
             maintainers,
             blossom_servers,
+            grasp_servers,
+            min_pow,
+            license,
+            readme,
+            readme_summary,
             repo_path,
             timeout,
             output,
             update_git_config: update_config,
         } => {
-            // Validate inputs
+            let mut answers = commands::init::InitAnswers {
+                identifier,
+                name,
+                description,
+                relays,
+                private_key,
+                account,
+                active_account: false,
+            };
+            // Missing values are asked in a terminal, scripts get all of them listed at once
+            let interactive = !answers.missing().is_empty() && io::stdin().is_terminal();
+            if interactive {
+                answers.prompt(&repo_path)?;
+            } else if answers.relays.is_empty() {
+                answers.relays = commands::default_relays();
+            }
+            let missing = answers.missing();
             ensure!(
-                !relays.is_empty(),
-                "At least one relay is required (--relay)"
+                missing.is_empty(),
+                "Missing {missing}, pass them or run 'gitsmith init' in a terminal \
+                 (relays can also be set with 'gitsmith config set relays')",
+                missing = missing.join(", ")
             );
+            let commands::init::InitAnswers {
+                identifier: Some(identifier),
+                name: Some(name),
+                description: Some(description),
+                relays,
+                private_key,
+                account,
+                active_account,
+            } = answers
+            else {
+                anyhow::bail!("Missing repository details");
+            };
 
             ensure!(
                 !identifier.contains(' ') && !identifier.contains('/'),
@@ -218,7 +584,13 @@ async fn main() -> Result<()> {
                     web: vec![],
                     root_commit: String::new(),
                     maintainers: vec![],
-                    grasp_servers: blossom_servers.clone(),
+                    ci_keys: vec![],
+                    grasp_servers: vec![],
+                    blossom_servers: vec![],
+                    min_pow,
+                    license: None,
+                    topics: vec![],
+                    readme: None,
                 })
             } else {
                 RepoAnnouncement {
@@ -230,7 +602,13 @@ async fn main() -> Result<()> {
                     web: vec![],
                     root_commit: String::new(),
                     maintainers: vec![],
-                    grasp_servers: blossom_servers.clone(),
+                    ci_keys: vec![],
+                    grasp_servers: vec![],
+                    blossom_servers: vec![],
+                    min_pow,
+                    license: None,
+                    topics: vec![],
+                    readme: None,
                 }
             };
 
@@ -244,7 +622,27 @@ async fn main() -> Result<()> {
             announcement.relays = relays;
             announcement.web = web;
             announcement.maintainers = maintainers;
-            announcement.grasp_servers = blossom_servers;
+            announcement.blossom_servers = blossom_servers;
+            announcement.grasp_servers = grasp_servers
+                .iter()
+                .map(|server| grasp::normalize_server(server))
+                .collect::<gitsmith_core::Result<_>>()?;
+            // The announcement, and later events, go to the GRASP servers too
+            for server in &announcement.grasp_servers {
+                let relay_url = grasp::relay_url(server);
+                if !announcement
+                    .relays
+                    .iter()
+                    .any(|known| relay::same_relay(known, &relay_url))
+                {
+                    announcement.relays.push(relay_url);
+                }
+            }
+            announcement.min_pow = min_pow;
+            if let Some(license) = license {
+                gitsmith_core::license::validate_license(&license)?;
+                announcement.license = Some(license);
+            }
 
             if let Some(commit) = root_commit {
                 announcement.root_commit = commit;
@@ -256,22 +654,68 @@ async fn main() -> Result<()> {
                 "Root commit could not be detected. Please specify --root-commit"
             );
 
-            // Parse the private key (supports both nsec bech32 and hex format)
-            let keys = Keys::parse(&private_key).context("Failed to parse private key")?;
-            let owner_npub = keys
+            let signer: Box<dyn Signer> = match (&account, private_key) {
+                (Some(account), _) => {
+                    let password = commands::password_or_prompt(
+                        password,
+                        &format!("Enter password for {account}: "),
+                    )?;
+                    gitsmith_core::account::get_signer(
+                        commands::accounts(),
+                        Some(account),
+                        &password,
+                    )?
+                }
+                // Parse the private key (supports both nsec bech32 and hex format)
+                (None, Some(private_key)) => {
+                    Box::new(Keys::parse(&private_key).context("Failed to parse private key")?)
+                }
+                (None, None) if active_account => {
+                    let password = commands::password_or_prompt(password, "Enter password: ")?;
+                    commands::active_signer(&password)?
+                }
+                (None, None) => anyhow::bail!("Either --nsec or --account is required"),
+            };
+            let signer = hooks::with_configured_hook(signer, Path::new("."));
+            let owner_npub = signer
                 .public_key()
                 .to_bech32()
                 .context("Failed to convert public key to npub")?;
 
+            if interactive {
+                commands::init::preview(&announcement, signer.public_key())?;
+                ensure!(
+                    commands::init::confirm("Publish this announcement?")?,
+                    "Aborted, nothing was published"
+                );
+            }
+
+            // The article goes first so the link in the announcement resolves
+            if let Some(path) = &readme {
+                commands::publish_readme(
+                    signer.as_ref(),
+                    &mut announcement,
+                    &repo_path,
+                    path,
+                    readme_summary,
+                )
+                .await?;
+            }
+
             // Publish
             let config = PublishConfig {
                 timeout_secs: timeout,
                 wait_for_send: true,
             };
 
-            let result = announce_repository(announcement.clone(), &private_key, config)
-                .await
-                .context("Failed to announce repository")?;
+            let result = announce_repository(
+                commands::network(),
+                announcement.clone(),
+                signer.as_ref(),
+                config,
+            )
+            .await
+            .context("Failed to announce repository")?;
 
             // Update git config if requested
             if update_config
@@ -285,9 +729,15 @@ async fn main() -> Result<()> {
             {
                 eprintln!("Warning: Failed to update git config: {}", e);
             }
+            if account.is_some()
+                && repo_path.exists()
+                && let Err(e) = gitsmith_core::repo::set_repo_account(&repo_path, &owner_npub)
+            {
+                eprintln!("Warning: Failed to bind the account to the repository: {e}");
+            }
 
             // Output result
-            match output {
+            match output.or(cli.format).unwrap_or(OutputFormat::Human) {
                 OutputFormat::Human => {
                     eprintln!("✅ Repository announced successfully!");
                     eprintln!();
@@ -307,7 +757,16 @@ async fn main() -> Result<()> {
                     }
                     eprintln!();
                     eprintln!("To clone this repository:");
-                    eprintln!("  git clone {}", result.nostr_url);
+                    eprintln!("  gitsmith clone {nostr_url}", nostr_url = result.nostr_url);
+                    if update_config
+                        && (!announcement.clone_urls.is_empty()
+                            || !announcement.grasp_servers.is_empty())
+                    {
+                        eprintln!();
+                        eprintln!(
+                            "Push with 'git push nostr <branch>' to also publish the repository state"
+                        );
+                    }
                 }
                 OutputFormat::Json => {
                     println!("{}", serde_json::to_string_pretty(&result)?);
@@ -349,14 +808,21 @@ async fn main() -> Result<()> {
         }
 
         Commands::State {
+            command: Some(command),
+            ..
+        } => commands::state::handle_state_command(command).await,
+
+        Commands::State {
+            command: None,
             identifier,
             repo_path,
             output,
         } => {
+            let identifier = identifier.context("--identifier is required")?;
             let state =
                 get_git_state(&repo_path, &identifier).context("Failed to get git state")?;
 
-            match output {
+            match output.or(cli.format).unwrap_or(OutputFormat::Json) {
                 OutputFormat::Json => {
                     let json = serde_json::json!({
                         "identifier": state.identifier,
@@ -396,3 +862,30 @@ async fn main() -> Result<()> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        // Global flags like --format must not clash with any subcommand's arguments
+        Cli::command().debug_assert();
+
+        // Subcommands keep their own --output
+        let cli = Cli::try_parse_from([
+            "gitsmith",
+            "--format",
+            "json",
+            "generate",
+            "--output",
+            "repo.json",
+        ])
+        .unwrap();
+        assert_eq!(cli.format, Some(OutputFormat::Json));
+        assert!(
+            matches!(cli.command, Commands::Generate { output: Some(path), .. } if path == Path::new("repo.json"))
+        );
+    }
+}
@@ -1,6 +1,7 @@
+use crate::commands;
 use anyhow::Result;
 use gitsmith_core::{
-    account, announce_repository, detect_from_git, patches, pull_request, repo, types,
+    account, announce_repository, detect_from_git, patches, pull_request, repo, signer, types,
 };
 use nostr_sdk::prelude::*;
 use rmcp::{
@@ -123,7 +124,9 @@ pub struct PrSendRequest {
     pub title: String,
     #[schemars(description = "PR description")]
     pub description: String,
-    #[schemars(description = "Commits to include (e.g., HEAD~1)")]
+    #[schemars(
+        description = "Commits to include: a revision to include the commits after (e.g., HEAD~1), A..B or A...B"
+    )]
     pub since: Option<String>,
     #[schemars(description = "Repository path")]
     pub repo_path: Option<String>,
@@ -175,7 +178,9 @@ pub struct RepoGenerateRequest {
 pub struct PatchSendRequest {
     #[schemars(description = "Patch title")]
     pub title: Option<String>,
-    #[schemars(description = "Commits to include (e.g., HEAD~1)")]
+    #[schemars(
+        description = "Commits to include: a revision to include the commits after (e.g., HEAD~1), A..B or A...B"
+    )]
     pub since: Option<String>,
     #[schemars(description = "Repository path")]
     pub repo_path: Option<String>,
@@ -236,7 +241,7 @@ impl GitSmithMcpServer {
     #[tool(description = "Import an existing Nostr account")]
     async fn account_import(&self, req: AccountImportRequest) -> CallToolResult {
         // The actual login function takes nsec/hex and password
-        match account::login(&req.private_key, &req.name) {
+        match account::login(commands::accounts(), &req.private_key, &req.name) {
             Ok(_) => CallToolResult::success(vec![Content::text(
                 serde_json::json!({
                     "success": true,
@@ -250,7 +255,7 @@ impl GitSmithMcpServer {
 
     #[tool(description = "Logout from active account")]
     async fn account_logout(&self) -> CallToolResult {
-        match account::logout() {
+        match account::logout(commands::accounts()) {
             Ok(_) => CallToolResult::success(vec![Content::text(
                 serde_json::json!({
                     "success": true,
@@ -264,7 +269,7 @@ impl GitSmithMcpServer {
 
     #[tool(description = "List all gitsmith accounts")]
     async fn account_list(&self) -> CallToolResult {
-        match account::list_accounts() {
+        match account::list_accounts(commands::accounts()) {
             Ok(accounts) => CallToolResult::success(vec![Content::text(
                 serde_json::to_string_pretty(&accounts).unwrap_or_else(|e| e.to_string()),
             )]),
@@ -274,7 +279,7 @@ impl GitSmithMcpServer {
 
     #[tool(description = "Export active account private key")]
     async fn account_export(&self, req: AccountExportRequest) -> CallToolResult {
-        match account::export_keys(&req.password) {
+        match account::export_keys(commands::accounts(), &req.password) {
             Ok(nsec) => CallToolResult::success(vec![Content::text(
                 serde_json::json!({
                     "success": true,
@@ -328,7 +333,13 @@ impl GitSmithMcpServer {
             web: vec![],
             root_commit,
             maintainers: req.maintainers.unwrap_or_default(),
+            ci_keys: vec![],
             grasp_servers: vec![],
+            blossom_servers: vec![],
+            min_pow: 0,
+            license: None,
+            topics: vec![],
+            readme: None,
         };
 
         let config = types::PublishConfig {
@@ -336,7 +347,7 @@ impl GitSmithMcpServer {
             wait_for_send: true,
         };
 
-        match announce_repository(announcement, &keys.secret_key().to_secret_hex(), config).await {
+        match announce_repository(commands::network(), announcement, &keys, config).await {
             Ok(result) => CallToolResult::success(vec![Content::text(
                 serde_json::to_string_pretty(&result).unwrap_or_else(|e| e.to_string()),
             )]),
@@ -442,7 +453,7 @@ impl GitSmithMcpServer {
         }
 
         // Fetch remote state from relays
-        let client = Client::new(Keys::generate());
+        let client = gitsmith_core::relay::new_client(commands::network(), None);
         for relay_url in &repo_announcement.relays {
             if let Err(e) = client.add_relay(relay_url).await {
                 warn!(%relay_url, error = %e, "Failed to add relay");
@@ -489,12 +500,12 @@ impl GitSmithMcpServer {
     // Pull request tools
     #[tool(description = "Send a pull request to Nostr")]
     async fn pr_send(&self, req: PrSendRequest) -> CallToolResult {
-        // Get account keys
-        let keys = match account::get_active_keys(&req.password) {
-            Ok(k) => k,
+        // Get account signer
+        let signer = match commands::active_signer(&req.password) {
+            Ok(s) => s,
             Err(e) => {
                 return CallToolResult::error(vec![Content::text(format!(
-                    "Failed to get account keys: {e}"
+                    "Failed to get account signer: {e}"
                 ))]);
             }
         };
@@ -529,23 +540,50 @@ impl GitSmithMcpServer {
             return CallToolResult::error(vec![Content::text("No patches to send".to_string())]);
         }
 
+        let commits = match patches::commit_metadata(&repo_path, &patches_list) {
+            Ok(commits) => commits,
+            Err(e) => {
+                return CallToolResult::error(vec![Content::text(format!(
+                    "Failed to read commits: {e}"
+                ))]);
+            }
+        };
+
         // Create repository coordinate
         let repo_coordinate = format!(
             "30617:{pubkey}:{identifier}",
-            pubkey = keys.public_key(),
+            pubkey = signer.public_key(),
             identifier = repo_announcement.identifier
         );
 
+        // Mine to the repository's advertised proof-of-work requirement
+        let target = repo::pull_request_target(
+            commands::network(),
+            &repo_announcement,
+            &repo_coordinate,
+            &repo_announcement.relays,
+        )
+        .await;
+        let options = patches::PullRequestOptions {
+            pow_difficulty: target.pow_difficulty,
+            commits,
+            maintainers: target.maintainers,
+            max_event_size: Some(patches::DEFAULT_MAX_EVENT_SIZE),
+            ..Default::default()
+        };
+
         // Create PR events
         let events = match patches::create_pull_request_event(
-            &keys,
+            signer.as_ref(),
             &repo_coordinate,
             &req.title,
             &req.description,
             patches_list,
             &repo_announcement.root_commit,
-            None,
-        ) {
+            &options,
+        )
+        .await
+        {
             Ok(e) => e,
             Err(e) => {
                 return CallToolResult::error(vec![Content::text(format!(
@@ -561,7 +599,7 @@ impl GitSmithMcpServer {
             )]);
         }
 
-        let client = Client::new(keys.clone());
+        let client = gitsmith_core::relay::new_client(commands::network(), Some(signer.as_ref()));
         for relay_url in &repo_announcement.relays {
             if let Err(e) = client.add_relay(relay_url).await {
                 warn!(%relay_url, error = %e, "Failed to add relay");
@@ -600,12 +638,12 @@ impl GitSmithMcpServer {
 
     #[tool(description = "List pull requests")]
     async fn pr_list(&self, req: PrListRequest) -> CallToolResult {
-        // Get account keys
-        let keys = match account::get_active_keys(&req.password) {
-            Ok(k) => k,
+        // Get account signer
+        let signer = match commands::active_signer(&req.password) {
+            Ok(s) => s,
             Err(e) => {
                 return CallToolResult::error(vec![Content::text(format!(
-                    "Failed to get account keys: {e}"
+                    "Failed to get account signer: {e}"
                 ))]);
             }
         };
@@ -631,12 +669,18 @@ impl GitSmithMcpServer {
         // Create repository coordinate
         let repo_coordinate = format!(
             "30617:{pubkey}:{identifier}",
-            pubkey = keys.public_key(),
+            pubkey = signer.public_key(),
             identifier = identifier
         );
 
         // List PRs
-        match pull_request::list_pull_requests(&repo_coordinate, repo_announcement.relays).await {
+        match pull_request::list_pull_requests(
+            commands::network(),
+            &repo_coordinate,
+            repo_announcement.relays,
+        )
+        .await
+        {
             Ok(prs) => CallToolResult::success(vec![Content::text(
                 serde_json::to_string_pretty(&prs).unwrap_or_else(|e| e.to_string()),
             )]),
@@ -646,12 +690,12 @@ impl GitSmithMcpServer {
 
     #[tool(description = "Sync a specific pull request from Nostr")]
     async fn pr_sync(&self, req: PrSyncRequest) -> CallToolResult {
-        // Get account keys
-        let keys = match account::get_active_keys(&req.password) {
-            Ok(k) => k,
+        // Get account signer
+        let signer = match commands::active_signer(&req.password) {
+            Ok(s) => s,
             Err(e) => {
                 return CallToolResult::error(vec![Content::text(format!(
-                    "Failed to get account keys: {e}"
+                    "Failed to get account signer: {e}"
                 ))]);
             }
         };
@@ -688,7 +732,7 @@ impl GitSmithMcpServer {
         };
 
         // Connect to relays
-        let client = Client::new(keys);
+        let client = gitsmith_core::relay::new_client(commands::network(), Some(signer.as_ref()));
         for relay_url in &repo_announcement.relays {
             if let Err(e) = client.add_relay(relay_url).await {
                 warn!(%relay_url, error = %e, "Failed to add relay");
@@ -735,12 +779,12 @@ impl GitSmithMcpServer {
     // Patch tools
     #[tool(description = "Send patches to Nostr")]
     async fn patch_send(&self, req: PatchSendRequest) -> CallToolResult {
-        // Get account keys
-        let keys = match account::get_active_keys(&req.password) {
-            Ok(k) => k,
+        // Get account signer
+        let signer = match commands::active_signer(&req.password) {
+            Ok(s) => s,
             Err(e) => {
                 return CallToolResult::error(vec![Content::text(format!(
-                    "Failed to get account keys: {e}"
+                    "Failed to get account signer: {e}"
                 ))]);
             }
         };
@@ -776,7 +820,7 @@ impl GitSmithMcpServer {
         }
 
         // Send patches as individual events
-        let client = Client::new(keys.clone());
+        let client = gitsmith_core::relay::new_client(commands::network(), Some(signer.as_ref()));
         for relay_url in &repo_announcement.relays {
             if let Err(e) = client.add_relay(relay_url).await {
                 warn!(%relay_url, error = %e, "Failed to add relay");
@@ -797,10 +841,10 @@ impl GitSmithMcpServer {
             .to_string();
 
             let event_builder = EventBuilder::text_note(content);
-            let event = match event_builder.sign_with_keys(&keys) {
+            let event = match signer::sign(signer.as_ref(), event_builder).await {
                 Ok(e) => e,
                 Err(e) => {
-                    failures.push(format!("Failed to sign event: {e}"));
+                    failures.push(format!("Failed to sign event: {e:#}"));
                     continue;
                 }
             };
@@ -1054,7 +1098,7 @@ impl ServerHandler for GitSmithMcpServer {
                         },
                         "since": {
                             "type": "string",
-                            "description": "Commits to include (e.g., HEAD~1)"
+                            "description": "Commits to include: a revision to include the commits after (e.g., HEAD~1), A..B or A...B"
                         },
                         "repo_path": {
                             "type": "string",
@@ -1129,7 +1173,7 @@ impl ServerHandler for GitSmithMcpServer {
                         },
                         "since": {
                             "type": "string",
-                            "description": "Commits to include (e.g., HEAD~1)"
+                            "description": "Commits to include: a revision to include the commits after (e.g., HEAD~1), A..B or A...B"
                         },
                         "repo_path": {
                             "type": "string",